@@ -26,6 +26,7 @@ use inferno::interfaces::desktop::{
     ActivityLogger, ActivityLog, ActivityStats, ActivityStatus, ActivityType,
     SecurityManager, ApiKey, SecurityEvent, SecurityMetrics, CreateApiKeyRequest, CreateApiKeyResponse,
     ModelRepositoryService, ModelDownloadManager, ExternalModelInfo, ModelSearchQuery, ModelSearchResponse, DownloadProgress,
+    StreamEvent, with_heartbeat, STREAM_HEARTBEAT_INTERVAL,
 };
 
 // Keep dashboard-specific modules
@@ -399,6 +400,9 @@ fn handle_menu_event(app: &tauri::AppHandle, menu_id: &str) {
             show_main_window(app);
         }
         MENU_ID_STOP_INFERENCE => {
+            if let Some(state) = app.try_state::<AppState>() {
+                state.backend_manager.stop_all_inference();
+            }
             let _ = app.emit("menu://stop-inference", ());
         }
         MENU_ID_VIEW_DASHBOARD => {
@@ -1032,6 +1036,24 @@ async fn unload_model(
     }
 }
 
+#[tauri::command]
+async fn force_unload_model(
+    backend_id: String,
+    state: State<'_, AppState>
+) -> Result<(), String> {
+    match state.backend_manager.force_unload(backend_id.clone()).await {
+        Ok(()) => {
+            if let Ok(event_mgr) = state.event_manager.lock() {
+                if let Some(ref manager) = *event_mgr {
+                    let _ = manager.emit_model_unloaded("unknown".to_string(), backend_id);
+                }
+            }
+            Ok(())
+        }
+        Err(e) => Err(e.to_string())
+    }
+}
+
 #[tauri::command]
 async fn infer(
     backend_id: String,
@@ -1072,6 +1094,12 @@ async fn infer(
     }
 }
 
+#[tauri::command]
+async fn stop_inference(state: State<'_, AppState>) -> Result<(), String> {
+    state.backend_manager.stop_all_inference();
+    Ok(())
+}
+
 #[tauri::command]
 async fn infer_stream(
     app: tauri::AppHandle,
@@ -1144,15 +1172,25 @@ async fn infer_stream(
     let streaming_counter_clone = streaming_counter.clone();
 
     tokio::spawn(async move {
-        let mut stream = stream;
         let _guard = guard;
         let _stream_guard = StreamingGuard::new(streaming_counter_clone);
         let start_time = Instant::now();
         let mut response = String::new();
 
-        while let Some(chunk) = stream.next().await {
-            match chunk {
-                Ok(token) => {
+        let mut events = std::pin::pin!(with_heartbeat(stream, STREAM_HEARTBEAT_INTERVAL));
+        while let Some(event) = events.next().await {
+            match event {
+                StreamEvent::Heartbeat(heartbeat) => {
+                    let _ = app_clone.emit(
+                        "inference_heartbeat",
+                        serde_json::json!({
+                            "inference_id": inference_id_clone.clone(),
+                            "elapsed_ms": heartbeat.elapsed_ms,
+                            "tokens": heartbeat.tokens
+                        }),
+                    );
+                }
+                StreamEvent::Token(Ok(token)) => {
                     let token: String = token;
                     response.push_str(&token);
                     let _ = app_clone.emit(
@@ -1188,7 +1226,7 @@ async fn infer_stream(
                         }
                     }
                 }
-                Err(err) => {
+                StreamEvent::Token(Err(err)) => {
                     let error_message = err.to_string();
                     backend_manager_clone.record_inference_result(
                         &backend_id_clone,
@@ -2015,9 +2053,10 @@ async fn search_external_models(
 #[tauri::command]
 async fn get_external_model_details(
     model_id: String,
+    revision: Option<String>,
     state: State<'_, AppState>
 ) -> Result<ExternalModelInfo, String> {
-    state.model_repository.get_model_details(&model_id).await
+    state.model_repository.get_model_details(&model_id, revision.as_deref()).await
         .map_err(|e| e.to_string())
 }
 
@@ -2060,7 +2099,7 @@ async fn start_model_download(
 
     state
         .download_manager
-        .start_download(&model, &target_dir_string)
+        .start_download(&state.model_repository, &model, &target_dir_string)
         .await
         .map_err(|e| e.to_string())
 }
@@ -2303,8 +2342,10 @@ async fn main() {
             get_loaded_models,
             load_model,
             unload_model,
+            force_unload_model,
             infer,
             infer_stream,
+            stop_inference,
             validate_model,
             open_file_dialog,
             upload_model,
@@ -32,6 +32,8 @@ interface ExternalModelInfo {
   created_at: string;
   updated_at: string;
   file_info: ModelFileInfo[];
+  revision?: string;
+  sha?: string;
 }
 
 interface ModelSearchResponse {
@@ -53,6 +55,7 @@ interface DownloadProgress {
   eta_seconds?: number;
   started_at: string;
   completed_at?: string;
+  revision?: string;
 }
 
 export function ModelMarketplace() {
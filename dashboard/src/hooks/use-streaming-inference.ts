@@ -12,6 +12,9 @@ interface StreamingInferenceState {
   error: string | null;
   inferenceId: string | null;
   progress: number;
+  // From heartbeats, which keep arriving while tokens are slow
+  elapsedMs: number;
+  tokenCount: number;
 }
 
 interface StreamingTokenEvent {
@@ -29,6 +32,12 @@ interface StreamingErrorEvent {
   error: string;
 }
 
+interface StreamingHeartbeatEvent {
+  inference_id: string;
+  elapsed_ms: number;
+  tokens: number;
+}
+
 interface StreamingProgressEvent {
   inference_id: string;
   progress: number;
@@ -43,6 +52,8 @@ export function useStreamingInference() {
     error: null,
     inferenceId: null,
     progress: 0,
+    elapsedMs: 0,
+    tokenCount: 0,
   });
 
   const notifyNative = useCallback((title: string, body: string) => {
@@ -65,6 +76,8 @@ export function useStreamingInference() {
         error: null,
         inferenceId: null,
         progress: 0,
+        elapsedMs: 0,
+        tokenCount: 0,
       });
 
       const inferenceId = await tauriApi.inferStream(backendId, prompt, params);
@@ -95,6 +108,8 @@ export function useStreamingInference() {
       error: null,
       inferenceId: null,
       progress: 0,
+      elapsedMs: 0,
+      tokenCount: 0,
     });
   }, []);
 
@@ -105,6 +120,7 @@ export function useStreamingInference() {
     let unlistenError: (() => void) | null = null;
     let unlistenStart: (() => void) | null = null;
     let unlistenProgress: (() => void) | null = null;
+    let unlistenHeartbeat: (() => void) | null = null;
 
     const setupListeners = async () => {
       // Listen for streaming start
@@ -187,6 +203,21 @@ export function useStreamingInference() {
           return prev;
         });
       });
+
+      // Listen for heartbeats, sent on an interval while the stream runs
+      unlistenHeartbeat = await listen('inference_heartbeat', (event) => {
+        const heartbeatEvent = event.payload as StreamingHeartbeatEvent;
+        setState(prev => {
+          if (prev.inferenceId === heartbeatEvent.inference_id) {
+            return {
+              ...prev,
+              elapsedMs: heartbeatEvent.elapsed_ms,
+              tokenCount: heartbeatEvent.tokens,
+            };
+          }
+          return prev;
+        });
+      });
     };
 
     setupListeners();
@@ -198,6 +229,7 @@ export function useStreamingInference() {
       if (unlistenComplete) unlistenComplete();
       if (unlistenError) unlistenError();
       if (unlistenProgress) unlistenProgress();
+      if (unlistenHeartbeat) unlistenHeartbeat();
     };
   }, [notifyNative]);
 
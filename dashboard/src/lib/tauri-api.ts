@@ -141,6 +141,19 @@ export class TauriApiService {
     }
   }
 
+  async forceUnloadModel(backendId: string): Promise<void> {
+    if (!isTauri) {
+      console.log(`[Browser Mode] Would force unload model: ${backendId}`);
+      return Promise.resolve();
+    }
+    try {
+      await safeInvoke('force_unload_model', { backend_id: backendId });
+    } catch (error) {
+      console.error('Failed to force unload model:', error);
+      throw new Error(`Failed to force unload model: ${error}`);
+    }
+  }
+
   async getLoadedModels(): Promise<string[]> {
     try {
       if (isTauri && invoke) {
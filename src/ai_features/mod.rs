@@ -20,3 +20,6 @@ pub mod sampling;
 
 // Real-time token streaming with channels
 pub mod streaming;
+
+// Speech-to-text over audio files
+pub mod transcription;
@@ -38,6 +38,14 @@ pub struct SamplingConfig {
     /// Penalty for repeating tokens (1.0 = no penalty, > 1.0 = discourage repetition)
     pub repeat_penalty: f32,
 
+    /// How many recent tokens the repeat penalty looks back over (0 = disabled)
+    #[serde(default = "default_repeat_last_n")]
+    pub repeat_last_n: usize,
+
+    /// Min-P: drop tokens less likely than this fraction of the top token (0.0 = disabled)
+    #[serde(default)]
+    pub min_p: f32,
+
     /// Optional seed for reproducibility
     pub seed: Option<u64>,
 }
@@ -50,11 +58,17 @@ impl Default for SamplingConfig {
             top_k: 40,
             top_p: 0.9,
             repeat_penalty: 1.1,
+            repeat_last_n: default_repeat_last_n(),
+            min_p: 0.0,
             seed: None,
         }
     }
 }
 
+fn default_repeat_last_n() -> usize {
+    64
+}
+
 /// Simple token data structure for sampling
 #[derive(Clone, Debug)]
 pub struct TokenCandidate {
@@ -114,6 +128,14 @@ impl Sampler {
             return None;
         }
 
+        // Penalize recently generated tokens before anything looks at probabilities
+        if self.config.repeat_penalty != 1.0
+            && self.config.repeat_last_n > 0
+            && !self.recent_tokens.is_empty()
+        {
+            Self::apply_repeat_penalty(candidates, &self.recent_tokens, self.config.repeat_penalty);
+        }
+
         // Apply temperature scaling if not greedy
         if matches!(
             self.config.strategy,
@@ -142,6 +164,11 @@ impl Sampler {
             Self::apply_top_p(&mut adjusted, self.config.top_p);
         }
 
+        // Apply min-p filtering
+        if self.config.min_p > 0.0 && self.config.min_p <= 1.0 {
+            Self::apply_min_p(&mut adjusted, self.config.min_p);
+        }
+
         // Sample based on strategy
         let token = match self.config.strategy {
             SamplingStrategy::Greedy => Self::greedy_sample(&adjusted),
@@ -151,9 +178,10 @@ impl Sampler {
         // Track for repeat penalty
         if let Some(t) = token {
             self.recent_tokens.push(t);
-            // Keep only recent history (last 50 tokens)
-            if self.recent_tokens.len() > 50 {
-                self.recent_tokens.remove(0);
+            // Keep only the window the repeat penalty looks at
+            if self.recent_tokens.len() > self.config.repeat_last_n {
+                let excess = self.recent_tokens.len() - self.config.repeat_last_n;
+                self.recent_tokens.drain(..excess);
             }
         }
 
@@ -183,6 +211,47 @@ impl Sampler {
         );
     }
 
+    /// Apply the repeat penalty to tokens in `recent`, as llama.cpp does: positive
+    /// logits are divided by the penalty and negative ones multiplied, then
+    /// probabilities are recomputed from the penalized logits
+    fn apply_repeat_penalty(candidates: &mut [TokenCandidate], recent: &[i32], penalty: f32) {
+        if penalty <= 0.0 {
+            return;
+        }
+
+        let mut penalized = 0;
+        for token in candidates.iter_mut() {
+            if recent.contains(&token.id) {
+                if token.logit > 0.0 {
+                    token.logit /= penalty;
+                } else {
+                    token.logit *= penalty;
+                }
+                penalized += 1;
+            }
+        }
+        if penalized == 0 {
+            return;
+        }
+
+        let max_logit = candidates
+            .iter()
+            .map(|c| c.logit)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let scores: Vec<f32> = candidates
+            .iter()
+            .map(|c| (c.logit - max_logit).exp())
+            .collect();
+        let sum: f32 = scores.iter().sum();
+        if sum > 0.0 {
+            for (token, score) in candidates.iter_mut().zip(scores) {
+                token.p = score / sum;
+            }
+        }
+
+        debug!("Applied repeat penalty {} to {} tokens", penalty, penalized);
+    }
+
     /// Apply top-k filtering (keep only top k tokens)
     fn apply_top_k(candidates: &mut Vec<TokenCandidate>, k: usize) {
         if candidates.len() <= k {
@@ -227,6 +296,23 @@ impl Sampler {
         );
     }
 
+    /// Apply min-p filtering (keep tokens with prob >= min_p * top prob)
+    fn apply_min_p(candidates: &mut Vec<TokenCandidate>, min_p: f32) {
+        let max_p = candidates.iter().map(|c| c.p).fold(0.0, f32::max);
+        if max_p <= 0.0 {
+            return;
+        }
+
+        let threshold = min_p * max_p;
+        candidates.retain(|c| c.p >= threshold);
+
+        debug!(
+            "Applied min-p filtering: kept {} tokens for min_p={}",
+            candidates.len(),
+            min_p
+        );
+    }
+
     /// Greedy sampling: pick token with highest probability
     fn greedy_sample(candidates: &[TokenCandidate]) -> Option<i32> {
         candidates
@@ -382,6 +468,8 @@ mod tests {
             top_k: 40,
             top_p: 0.9,
             repeat_penalty: 1.1,
+            repeat_last_n: 64,
+            min_p: 0.0,
             seed: None,
         };
 
@@ -398,6 +486,95 @@ mod tests {
         assert_eq!(sampler.get_recent_tokens(), &[5]);
     }
 
+    fn spread_candidates() -> Vec<(i32, f32, f32)> {
+        vec![
+            (1, 1.0, 0.15),
+            (2, 2.5, 0.4),
+            (3, 2.0, 0.3),
+            (4, 0.5, 0.1),
+            (5, 0.0, 0.05),
+        ]
+    }
+
+    #[test]
+    fn test_seeded_top_k_one_is_deterministic_greedy() {
+        let config = SamplingConfig {
+            strategy: SamplingStrategy::TopKP,
+            temperature: 1.5,
+            top_k: 1,
+            top_p: 1.0,
+            repeat_penalty: 1.0,
+            seed: Some(7),
+            ..Default::default()
+        };
+
+        let run = || {
+            let mut sampler = Sampler::new(config.clone());
+            (0..20)
+                .map(|_| sampler.sample_from_candidates(spread_candidates()))
+                .collect::<Vec<_>>()
+        };
+
+        let first = run();
+        assert_eq!(first, run());
+        // With one candidate left after top-k the pick always matches greedy
+        assert!(first.iter().all(|&token| token == Some(2)));
+    }
+
+    #[test]
+    fn test_same_seed_reproduces_stochastic_sampling() {
+        let run = |seed| {
+            let mut sampler = Sampler::new(SamplingConfig {
+                strategy: SamplingStrategy::TopKP,
+                temperature: 0.9,
+                top_k: 4,
+                top_p: 0.95,
+                repeat_penalty: 1.1,
+                seed: Some(seed),
+                ..Default::default()
+            });
+            (0..64)
+                .map(|_| sampler.sample_from_candidates(spread_candidates()))
+                .map(|token| token.unwrap().to_string())
+                .collect::<String>()
+        };
+
+        let output = run(42);
+        assert_eq!(output.as_bytes(), run(42).as_bytes());
+        assert_ne!(output, run(43));
+    }
+
+    #[test]
+    fn test_repeat_penalty_discourages_recent_tokens() {
+        let config = SamplingConfig {
+            strategy: SamplingStrategy::Greedy,
+            repeat_penalty: 2.0,
+            repeat_last_n: 1,
+            ..Default::default()
+        };
+        let mut sampler = Sampler::new(config);
+
+        assert_eq!(sampler.sample_from_candidates(spread_candidates()), Some(2));
+        // Token 2's logit halves to 1.25, so token 3 now leads
+        assert_eq!(sampler.sample_from_candidates(spread_candidates()), Some(3));
+        // Only the last token is remembered, so token 2 is allowed back
+        assert_eq!(sampler.sample_from_candidates(spread_candidates()), Some(2));
+        assert_eq!(sampler.get_recent_tokens(), &[2]);
+    }
+
+    #[test]
+    fn test_min_p_filtering() {
+        let mut candidates: Vec<TokenCandidate> = spread_candidates()
+            .into_iter()
+            .map(|(id, logit, p)| TokenCandidate { id, logit, p })
+            .collect();
+
+        // Keeps tokens with p >= 0.5 * 0.4
+        Sampler::apply_min_p(&mut candidates, 0.5);
+        let ids: Vec<i32> = candidates.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![2, 3]);
+    }
+
     #[test]
     fn test_temperature_scaling() {
         let mut candidates = vec![
@@ -0,0 +1,325 @@
+//! Speech-to-text over audio files
+//!
+//! Audio is loaded from a WAV file, mixed down to mono and resampled to the
+//! rate the speech model was trained on. Long recordings are cut into windows
+//! that are transcribed one after another, so partial transcripts can be shown
+//! before the whole file is done and the model never sees more audio at once
+//! than a window holds.
+//!
+//! FLAC is not decoded: FLAC files are refused, whatever their extension, with
+//! a hint to convert them to WAV.
+//!
+//! Speech models are CTC acoustic models such as wav2vec2 exported to ONNX:
+//! they take raw samples and return a distribution over characters for each
+//! frame, decoded here with the `vocab.json` shipped next to the model.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+use crate::io::audio::{load_wav_file, resample_audio};
+
+/// Seconds of audio transcribed at a time when no window is given
+pub const DEFAULT_WINDOW_SECS: f32 = 30.0;
+
+/// A model turning mono audio into text
+pub trait SpeechModel: Send {
+    /// Sample rate the model expects its input at
+    fn sample_rate(&self) -> u32;
+
+    /// Text spoken in `samples`, mono at [`sample_rate`](Self::sample_rate)
+    fn transcribe(&mut self, samples: &[f32]) -> Result<String>;
+}
+
+/// Mono audio samples in -1.0..=1.0
+#[derive(Debug, Clone)]
+pub struct Audio {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+impl Audio {
+    pub fn duration_secs(&self) -> f32 {
+        self.samples.len() as f32 / self.sample_rate as f32
+    }
+
+    /// The same audio at `sample_rate`
+    pub fn resampled(&self, sample_rate: u32) -> Audio {
+        Audio {
+            samples: resample_audio(&self.samples, self.sample_rate, sample_rate),
+            sample_rate,
+        }
+    }
+}
+
+/// Load the audio file at `path` as mono
+pub async fn load_audio(path: &Path) -> Result<Audio> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("wav") | Some("wave") => {}
+        Some("flac") => return Err(flac_unsupported(path)),
+        _ => anyhow::bail!(
+            "Unsupported audio file {}: expected a .wav file",
+            path.display()
+        ),
+    }
+    if is_flac(path).await {
+        return Err(flac_unsupported(path));
+    }
+
+    let (samples, spec) = load_wav_file(path).await?;
+    if spec.channels == 0 || spec.sample_rate == 0 {
+        anyhow::bail!("WAV file {} has no audio channels", path.display());
+    }
+    let channels = usize::from(spec.channels);
+    let samples = if channels == 1 {
+        samples
+    } else {
+        samples
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+            .collect()
+    };
+    Ok(Audio {
+        samples,
+        sample_rate: spec.sample_rate,
+    })
+}
+
+/// Whether the file at `path` starts with the FLAC stream marker
+async fn is_flac(path: &Path) -> bool {
+    let mut marker = [0u8; 4];
+    match tokio::fs::File::open(path).await {
+        Ok(mut file) => file.read_exact(&mut marker).await.is_ok() && marker == *b"fLaC",
+        Err(_) => false,
+    }
+}
+
+fn flac_unsupported(path: &Path) -> anyhow::Error {
+    anyhow::anyhow!(
+        "FLAC input is not supported; convert {} to WAV first, e.g. with `ffmpeg -i <file>.flac <file>.wav`",
+        path.display()
+    )
+}
+
+/// Transcript of one window of the audio
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptSegment {
+    pub start_secs: f32,
+    pub end_secs: f32,
+    pub text: String,
+}
+
+/// Transcribe `audio` with `model`, `window_secs` at a time, passing each
+/// window's transcript to `on_segment` as soon as it is ready
+///
+/// Returns the whole transcript, the windows' texts joined by spaces.
+pub fn transcribe(
+    model: &mut dyn SpeechModel,
+    audio: &Audio,
+    window_secs: f32,
+    mut on_segment: impl FnMut(&TranscriptSegment),
+) -> Result<String> {
+    if window_secs.is_nan() || window_secs <= 0.0 {
+        anyhow::bail!("Transcription window must be longer than 0 seconds");
+    }
+    let sample_rate = model.sample_rate();
+    let audio = audio.resampled(sample_rate);
+    let window = ((window_secs * sample_rate as f32) as usize).max(1);
+
+    let mut transcript = Vec::new();
+    for (index, samples) in audio.samples.chunks(window).enumerate() {
+        let start = index * window;
+        let text = model.transcribe(samples).with_context(|| {
+            format!(
+                "Transcribing audio from {:.1}s failed",
+                start as f32 / sample_rate as f32
+            )
+        })?;
+        let segment = TranscriptSegment {
+            start_secs: start as f32 / sample_rate as f32,
+            end_secs: (start + samples.len()) as f32 / sample_rate as f32,
+            text: text.trim().to_string(),
+        };
+        on_segment(&segment);
+        if !segment.text.is_empty() {
+            transcript.push(segment.text);
+        }
+    }
+    Ok(transcript.join(" "))
+}
+
+/// Output alphabet of a CTC speech model
+#[derive(Debug, Clone)]
+pub struct CtcVocabulary {
+    tokens: Vec<String>,
+    blank: usize,
+}
+
+impl CtcVocabulary {
+    /// Read a `vocab.json` mapping each token to its id, as shipped with
+    /// wav2vec2 models; `<pad>` is the CTC blank and `|` separates words
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read vocabulary {}", path.display()))?;
+        let ids: HashMap<String, usize> = serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid vocabulary {}", path.display()))?;
+        Ok(Self::from_ids(ids))
+    }
+
+    pub fn from_ids(ids: HashMap<String, usize>) -> Self {
+        let size = ids.values().max().map_or(0, |&max| max + 1);
+        let mut tokens = vec![String::new(); size];
+        for (token, id) in ids {
+            tokens[id] = token;
+        }
+        let blank = tokens
+            .iter()
+            .position(|token| token == "<pad>" || token == "<blank>")
+            .unwrap_or(0);
+        Self { tokens, blank }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Greedy CTC decoding of `logits`, one row of `len()` scores per frame:
+    /// the best token of each frame, with repeats collapsed and blanks dropped
+    pub fn decode(&self, logits: &[f32]) -> String {
+        let mut text = String::new();
+        let mut previous = None;
+        for frame in logits.chunks_exact(self.tokens.len().max(1)) {
+            let best = frame
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(id, _)| id);
+            if best != previous
+                && let Some(id) = best
+                && id != self.blank
+            {
+                match self.tokens[id].as_str() {
+                    "|" => text.push(' '),
+                    token if token.starts_with('<') && token.ends_with('>') => {}
+                    token => text.push_str(token),
+                }
+            }
+            previous = best;
+        }
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Says the next word for each window with sound in it
+    struct MockSpeechModel {
+        words: Vec<&'static str>,
+        window_lengths: Vec<usize>,
+    }
+
+    impl SpeechModel for MockSpeechModel {
+        fn sample_rate(&self) -> u32 {
+            16_000
+        }
+
+        fn transcribe(&mut self, samples: &[f32]) -> Result<String> {
+            self.window_lengths.push(samples.len());
+            if samples.iter().all(|&s| s == 0.0) {
+                return Ok(String::new());
+            }
+            Ok(self.words.remove(0).to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transcribes_synthetic_wav_in_windows() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        // Two seconds of a stereo tone at 8 kHz, then a second of silence
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 8_000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..24_000 {
+            let sample = if i < 16_000 {
+                ((i as f32 * 0.05).sin() * 16_000.0) as i16
+            } else {
+                0
+            };
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let audio = load_audio(&path).await.unwrap();
+        assert_eq!(audio.sample_rate, 8_000);
+        assert_eq!(audio.duration_secs(), 3.0);
+        assert!(audio.samples.iter().all(|s| (-1.0..=1.0).contains(s)));
+
+        let mut model = MockSpeechModel {
+            words: vec!["hello", "world"],
+            window_lengths: Vec::new(),
+        };
+        let mut segments = Vec::new();
+        let transcript = transcribe(&mut model, &audio, 1.0, |segment| {
+            segments.push(segment.clone())
+        })
+        .unwrap();
+
+        assert_eq!(transcript, "hello world");
+        // Resampled to the model's rate before windowing
+        assert_eq!(model.window_lengths, vec![16_000; 3]);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[1].text, "world");
+        assert_eq!((segments[2].start_secs, segments[2].end_secs), (2.0, 3.0));
+    }
+
+    #[tokio::test]
+    async fn test_flac_input_is_refused_with_a_conversion_hint() {
+        let dir = tempfile::tempdir().unwrap();
+        // By extension, and by content when a FLAC file is named .wav
+        for name in ["speech.flac", "speech.FLAC", "speech.wav"] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, b"fLaC\0\0\0\x22").unwrap();
+            let err = load_audio(&path).await.unwrap_err().to_string();
+            assert!(err.contains("FLAC input is not supported"), "{}", err);
+            assert!(err.contains("to WAV"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_ctc_decoding_collapses_repeats_and_blanks() {
+        let vocab = CtcVocabulary::from_ids(HashMap::from([
+            ("<pad>".to_string(), 0),
+            ("|".to_string(), 1),
+            ("H".to_string(), 2),
+            ("I".to_string(), 3),
+        ]));
+        let frame = |id: usize| {
+            let mut scores = vec![0.0; 4];
+            scores[id] = 1.0;
+            scores
+        };
+        // H H <pad> I | I <pad> I -> "HI II"
+        let logits: Vec<f32> = [2, 2, 0, 3, 1, 3, 0, 3]
+            .into_iter()
+            .flat_map(frame)
+            .collect();
+        assert_eq!(vocab.decode(&logits), "HI II");
+    }
+}
@@ -0,0 +1,380 @@
+//! API key authentication for the HTTP server
+//!
+//! With `server.api_key_file` set (or `inferno serve --api-key-file`), every
+//! request must carry one of the file's keys as `Authorization: Bearer <key>`.
+//! Requests without a key, or with an unknown one, get `401 Unauthorized`. The
+//! file stores only the SHA-256 digest of each key, so it can be checked in or
+//! shared without handing out the keys themselves.
+//!
+//! Each key has a scope. `read_only` keys may only read: model listings, status
+//! and metrics. `inference` keys may also run completions, embeddings and the
+//! WebSocket stream and follow their requests in the queue, and `admin` keys may
+//! use every endpoint, such as worker registration and upgrades. A key used beyond its scope gets `403 Forbidden`.
+//! Paths in `public_paths`, by default the health checks, need no key.
+//!
+//! ```toml
+//! public_paths = ["/health", "/healthz", "/readyz"]
+//!
+//! [[keys]]
+//! name = "dashboard"
+//! sha256 = "<hex SHA-256 of the key>"
+//! scope = "read_only"
+//! ```
+
+use crate::api::openai_compliance::ErrorResponse;
+use anyhow::{Context, Result, bail};
+use axum::{
+    Json,
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+/// What a key may do, each scope allowing everything the ones before it do
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    /// Read-only endpoints: model listings, status and metrics
+    ReadOnly,
+    /// Completions, embeddings, WebSocket streaming and the queue of waiting
+    /// requests as well
+    #[default]
+    Inference,
+    /// Every endpoint, including worker registration and upgrades
+    Admin,
+}
+
+impl fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReadOnly => write!(f, "read_only"),
+            Self::Inference => write!(f, "inference"),
+            Self::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl ApiKeyScope {
+    /// Scope a request needs: `/v1/admin/` endpoints need `admin`, inference
+    /// endpoints `inference`, other reads `read_only`, and any other write `admin`
+    pub fn required_for(method: &Method, path: &str) -> Self {
+        let inference = matches!(
+            path,
+            "/v1/chat/completions" | "/v1/completions" | "/v1/embeddings"
+        );
+        if path.starts_with("/v1/admin/") {
+            Self::Admin
+        } else if path == "/ws/stream"
+            || path.starts_with("/v1/queue/")
+            || (inference && method == Method::POST)
+        {
+            Self::Inference
+        } else if method == Method::GET || method == Method::HEAD || method == Method::OPTIONS {
+            Self::ReadOnly
+        } else {
+            Self::Admin
+        }
+    }
+}
+
+/// One key of the key file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiKeyEntry {
+    pub name: String,
+    /// Hex SHA-256 digest of the key
+    pub sha256: String,
+    #[serde(default)]
+    pub scope: ApiKeyScope,
+}
+
+/// Layout of the API key file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeysFile {
+    #[serde(default = "default_public_paths")]
+    pub public_paths: Vec<String>,
+    #[serde(default)]
+    pub keys: Vec<ApiKeyEntry>,
+}
+
+fn default_public_paths() -> Vec<String> {
+    ["/health", "/healthz", "/readyz"]
+        .map(String::from)
+        .to_vec()
+}
+
+/// The keys requests are checked against, by digest
+#[derive(Debug)]
+pub struct ApiKeys {
+    by_digest: HashMap<String, ApiKeyEntry>,
+    public_paths: Vec<String>,
+}
+
+impl ApiKeys {
+    /// Load the key file at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read API key file {}", path.display()))?;
+        let file: ApiKeysFile = toml::from_str(&contents)
+            .with_context(|| format!("Invalid API key file {}", path.display()))?;
+        Self::new(file).with_context(|| format!("Invalid API key file {}", path.display()))
+    }
+
+    pub fn new(file: ApiKeysFile) -> Result<Self> {
+        let mut by_digest = HashMap::new();
+        for mut entry in file.keys {
+            entry.sha256 = entry.sha256.trim().to_ascii_lowercase();
+            if entry.sha256.len() != 64 || !entry.sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+                bail!(
+                    "Key '{}' has no valid sha256; it must be the 64 hex digit SHA-256 digest of the key",
+                    entry.name
+                );
+            }
+            if let Some(other) = by_digest.insert(entry.sha256.clone(), entry.clone()) {
+                bail!(
+                    "Keys '{}' and '{}' are the same key",
+                    other.name,
+                    entry.name
+                );
+            }
+        }
+        Ok(Self {
+            by_digest,
+            public_paths: file.public_paths,
+        })
+    }
+
+    /// Digest `api_key` is listed under in the key file
+    pub fn digest(api_key: &str) -> String {
+        hex::encode(Sha256::digest(api_key.trim().as_bytes()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_digest.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_digest.is_empty()
+    }
+
+    pub fn is_public(&self, path: &str) -> bool {
+        self.public_paths.iter().any(|public| public == path)
+    }
+
+    /// The entry for `api_key`, if it is one of the keys
+    pub fn authenticate(&self, api_key: &str) -> Option<&ApiKeyEntry> {
+        self.by_digest.get(&Self::digest(api_key))
+    }
+}
+
+/// Middleware refusing requests outside the public paths that lack a known
+/// key or whose key's scope does not cover them
+pub async fn require_api_key(
+    State(keys): State<Arc<ApiKeys>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if keys.is_public(path) {
+        return next.run(request).await;
+    }
+    let api_key = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let Some(api_key) = api_key else {
+        return unauthorized("Missing API key; send it as 'Authorization: Bearer <key>'");
+    };
+    let Some(entry) = keys.authenticate(&api_key) else {
+        return unauthorized("Invalid API key");
+    };
+
+    let required = ApiKeyScope::required_for(request.method(), path);
+    if entry.scope < required {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::new(
+                format!(
+                    "API key '{}' has scope {}, but {} {} needs {}",
+                    entry.name,
+                    entry.scope,
+                    request.method(),
+                    path,
+                    required
+                ),
+                "invalid_request_error",
+                None,
+                Some("insufficient_scope"),
+            )),
+        )
+            .into_response();
+    }
+    request.extensions_mut().insert(AuthenticatedKey(api_key));
+    next.run(request).await
+}
+
+/// API key a request was authenticated with, added to the request's
+/// extensions by [`require_api_key`]
+#[derive(Debug, Clone)]
+pub struct AuthenticatedKey(pub String);
+
+/// Refuse requests from other hosts; guards admin endpoints when the server
+/// runs without an API key file
+pub async fn require_loopback(
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(ConnectInfo(peer)) = connect_info
+        && peer.ip().is_loopback()
+    {
+        return next.run(request).await;
+    }
+    (
+        StatusCode::FORBIDDEN,
+        Json(ErrorResponse::new(
+            format!(
+                "{} is only served to this host unless the server is started with an API key file",
+                request.uri().path()
+            ),
+            "invalid_request_error",
+            None,
+            Some("insufficient_scope"),
+        )),
+    )
+        .into_response()
+}
+
+fn unauthorized(message: &str) -> Response {
+    let mut response = (
+        StatusCode::UNAUTHORIZED,
+        Json(ErrorResponse::new(
+            message,
+            "invalid_request_error",
+            None,
+            Some("invalid_api_key"),
+        )),
+    )
+        .into_response();
+    response
+        .headers_mut()
+        .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Extension, Router,
+        body::Body,
+        middleware,
+        routing::{get, post},
+    };
+    use tower::ServiceExt;
+
+    async fn send(app: &Router, method: Method, uri: &str, key: Option<&str>) -> StatusCode {
+        let mut request = axum::http::Request::builder().method(method).uri(uri);
+        if let Some(key) = key {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", key));
+        }
+        let request = request.body(Body::empty()).unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_requests_need_a_key_with_enough_scope() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("api_keys.toml");
+        std::fs::write(
+            &path,
+            format!(
+                "[[keys]]\nname = \"app\"\nsha256 = \"{}\"\n\n\
+                 [[keys]]\nname = \"dashboard\"\nsha256 = \"{}\"\nscope = \"read_only\"\n",
+                ApiKeys::digest("sk-app"),
+                ApiKeys::digest("sk-dashboard").to_uppercase()
+            ),
+        )
+        .unwrap();
+        let keys = ApiKeys::load(&path).unwrap();
+        assert_eq!(keys.len(), 2);
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("sk-app"));
+
+        // Fails with a 500 unless the middleware passes on the key it checked
+        async fn completion(
+            Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+        ) -> String {
+            key
+        }
+        let app = Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .route("/v1/models", get(|| async { "models" }))
+            .route("/v1/chat/completions", post(completion))
+            .layer(middleware::from_fn_with_state(
+                Arc::new(keys),
+                require_api_key,
+            ));
+
+        // No key
+        assert_eq!(
+            send(&app, Method::POST, "/v1/chat/completions", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            send(&app, Method::GET, "/v1/models", Some("sk-wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            send(&app, Method::GET, "/health", None).await,
+            StatusCode::OK
+        );
+
+        // Valid inference key
+        assert_eq!(
+            send(&app, Method::POST, "/v1/chat/completions", Some("sk-app")).await,
+            StatusCode::OK
+        );
+
+        // Read-only key can read but not run inference
+        assert_eq!(
+            send(&app, Method::GET, "/v1/models", Some("sk-dashboard")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(
+                &app,
+                Method::POST,
+                "/v1/chat/completions",
+                Some("sk-dashboard")
+            )
+            .await,
+            StatusCode::FORBIDDEN
+        );
+
+        assert_eq!(
+            ApiKeyScope::required_for(&Method::DELETE, "/v1/admin/workers/w1"),
+            ApiKeyScope::Admin
+        );
+        // Queued requests belong to inference callers, whatever the method
+        for method in [Method::GET, Method::DELETE] {
+            assert_eq!(
+                ApiKeyScope::required_for(&method, "/v1/queue/req-1"),
+                ApiKeyScope::Inference
+            );
+        }
+        // Listing workers exposes the cluster, so reads need admin too
+        assert_eq!(
+            ApiKeyScope::required_for(&Method::GET, "/v1/admin/workers"),
+            ApiKeyScope::Admin
+        );
+    }
+}
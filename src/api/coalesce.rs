@@ -0,0 +1,188 @@
+//! Coalescing of identical in-flight requests
+//!
+//! When several requests from the same tenant and API key for the same backend,
+//! prompt and parameters are being served at once, only the first runs inference;
+//! the rest wait for it and receive the same output. A request arriving after that computation has finished starts a new one,
+//! so this only removes duplicate concurrent work and never serves stale results.
+
+use crate::backends::InferenceParams;
+use crate::response_cache::{CacheKey, HashAlgorithm};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Outcome shared with every waiter
+type Slot<T> = Arc<OnceCell<Result<T, SharedError>>>;
+
+/// Error of a computation, handed to every request that waited on it
+///
+/// The original error is its source, so waiters can still tell what kind of
+/// failure it was by walking the error chain.
+#[derive(Debug, Clone)]
+pub struct SharedError(Arc<anyhow::Error>);
+
+impl std::fmt::Display for SharedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+impl std::error::Error for SharedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&**self.0)
+    }
+}
+
+pub struct RequestCoalescer<T> {
+    in_flight: Mutex<HashMap<String, Slot<T>>>,
+    coalesced: AtomicU64,
+}
+
+impl<T: Clone> RequestCoalescer<T> {
+    pub fn new() -> Self {
+        Self {
+            in_flight: Mutex::new(HashMap::new()),
+            coalesced: AtomicU64::new(0),
+        }
+    }
+
+    /// Key identifying requests whose results are interchangeable: those served
+    /// by the backend with id `backend` for the same `requester`
+    ///
+    /// Model names are not enough, as tenants may resolve the same name to
+    /// different models.
+    pub fn key(backend: usize, requester: &str, prompt: &str, params: &InferenceParams) -> String {
+        let parameters = serde_json::to_string(params).unwrap_or_default();
+        let served_by = format!("{}:{}", backend, requester);
+        CacheKey::new(prompt, &served_by, &parameters, &HashAlgorithm::Blake3).to_string()
+    }
+
+    /// Run `compute` unless an identical request is already running it, in which
+    /// case wait for that result instead
+    ///
+    /// If the request doing the work is cancelled, one of the waiters takes over.
+    pub async fn run<F, Fut>(&self, key: String, compute: F) -> Result<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let slot = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        let mut computed = false;
+        let outcome = slot
+            .get_or_init(|| async {
+                computed = true;
+                compute().await.map_err(|e| SharedError(Arc::new(e)))
+            })
+            .await
+            .clone();
+
+        if computed {
+            // Later requests start over rather than reuse this result
+            let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+            if in_flight
+                .get(&key)
+                .is_some_and(|current| Arc::ptr_eq(current, &slot))
+            {
+                in_flight.remove(&key);
+            }
+        } else {
+            self.coalesced.fetch_add(1, Ordering::Relaxed);
+        }
+        outcome.map_err(anyhow::Error::new)
+    }
+
+    /// Requests served with another request's result so far
+    pub fn coalesced_count(&self) -> u64 {
+        self.coalesced.load(Ordering::Relaxed)
+    }
+
+    /// Distinct computations currently running
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+}
+
+impl<T: Clone> Default for RequestCoalescer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_different_keys_and_later_requests_compute_again() {
+        let coalescer = RequestCoalescer::<u32>::new();
+        let calls = AtomicUsize::new(0);
+        let compute = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok(7)
+        };
+
+        let (a, b) = tokio::join!(
+            coalescer.run("a".to_string(), compute),
+            coalescer.run("b".to_string(), compute)
+        );
+        assert_eq!((a.unwrap(), b.unwrap()), (7, 7));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        coalescer.run("a".to_string(), compute).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(coalescer.coalesced_count(), 0);
+        assert_eq!(coalescer.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_errors_reach_every_waiter() {
+        let coalescer = RequestCoalescer::<u32>::new();
+        let compute = || async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err(anyhow::anyhow!("model crashed"))
+        };
+        let (a, b) = tokio::join!(
+            coalescer.run("k".to_string(), compute),
+            coalescer.run("k".to_string(), compute)
+        );
+        assert_eq!(a.unwrap_err().to_string(), "model crashed");
+        assert_eq!(b.unwrap_err().to_string(), "model crashed");
+        assert_eq!(coalescer.coalesced_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_shared_errors_keep_their_source() {
+        let coalescer = RequestCoalescer::<u32>::new();
+        let compute = || async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err(crate::InfernoError::Timeout("model stalled".to_string()).into())
+        };
+        let (a, b) = tokio::join!(
+            coalescer.run("k".to_string(), compute),
+            coalescer.run("k".to_string(), compute)
+        );
+        for error in [a.unwrap_err(), b.unwrap_err()] {
+            assert!(error.chain().any(|cause| matches!(
+                cause.downcast_ref::<crate::InfernoError>(),
+                Some(crate::InfernoError::Timeout(_))
+            )));
+        }
+        assert_eq!(coalescer.coalesced_count(), 1);
+    }
+}
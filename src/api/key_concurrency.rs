@@ -0,0 +1,357 @@
+//! Concurrent request limits per API key
+//!
+//! Each API key may have a limited number of inference requests in flight, set
+//! under `[server.key_concurrency]` with a default and overrides for particular
+//! keys, listed by the SHA-256 digest the API key file stores for them. A request
+//! over its key's limit is refused at once with `429 Too Many Requests` rather
+//! than queued, so one busy client cannot take every worker slot. A streamed
+//! response holds its slot until the stream ends, and a WebSocket connection
+//! until it closes.
+//!
+//! Only keys [`require_api_key`](crate::api::auth::require_api_key) has
+//! validated count as keys. Every other request, including one carrying a key
+//! no key file vouched for, is counted against the `default` limit of the
+//! address it comes from, so clients cannot escape their limit by inventing keys.
+//!
+//! ```toml
+//! [server.key_concurrency]
+//! default = 4
+//!
+//! [server.key_concurrency.keys]
+//! # SHA-256 of the batch job key, as in the API key file
+//! "3b7e...c2f1" = 16
+//! ```
+
+use crate::api::{
+    auth::{ApiKeys, AuthenticatedKey},
+    openai_compliance::ErrorResponse,
+};
+use axum::{
+    Json,
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Requests each API key may have in flight at once; 0 is no limit
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyConcurrencyConfig {
+    /// Limit for keys without an override
+    pub default: u32,
+    /// Limits for particular keys by their SHA-256 digest, in place of `default`
+    pub keys: HashMap<String, u32>,
+}
+
+impl KeyConcurrencyConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.default > 0 || self.keys.values().any(|&limit| limit > 0)
+    }
+
+    pub fn limit(&self, client: &Client) -> u32 {
+        match client {
+            Client::Key(digest) => self.keys.get(digest).copied().unwrap_or(self.default),
+            Client::Address(_) => self.default,
+        }
+    }
+}
+
+/// Who a request's slot is counted against
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Client {
+    /// Requests authenticated with the API key with this digest
+    Key(String),
+    /// Requests without a validated key from this address
+    Address(IpAddr),
+}
+
+/// A semaphore for each client with requests in flight
+///
+/// Semaphores are dropped once their client has nothing in flight, so clients
+/// that are seen once do not accumulate.
+#[derive(Debug, Default)]
+pub struct KeyConcurrency {
+    config: KeyConcurrencyConfig,
+    semaphores: Mutex<HashMap<Client, Arc<Semaphore>>>,
+}
+
+impl KeyConcurrency {
+    pub fn new(config: KeyConcurrencyConfig) -> Self {
+        Self {
+            config,
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take one of `client`'s slots, or fail with its limit if all are in use
+    pub fn try_acquire(self: &Arc<Self>, client: &Client) -> Result<KeySlot, u32> {
+        let limit = self.config.limit(client);
+        if limit == 0 {
+            return Ok(KeySlot {
+                permit: None,
+                limits: Arc::clone(self),
+                client: client.clone(),
+            });
+        }
+        let semaphore = {
+            let mut semaphores = self.semaphores.lock().unwrap();
+            let semaphore = semaphores
+                .entry(client.clone())
+                .or_insert_with(|| Arc::new(Semaphore::new(limit as usize)));
+            Arc::clone(semaphore)
+        };
+        let permit = semaphore.try_acquire_owned().map_err(|_| limit)?;
+        Ok(KeySlot {
+            permit: Some(permit),
+            limits: Arc::clone(self),
+            client: client.clone(),
+        })
+    }
+
+    /// Requests `client` has in flight
+    pub fn in_flight(&self, client: &Client) -> usize {
+        let semaphores = self.semaphores.lock().unwrap();
+        semaphores.get(client).map_or(0, |semaphore| {
+            self.config.limit(client) as usize - semaphore.available_permits()
+        })
+    }
+}
+
+/// One request's slot under its client's limit, given back when dropped
+#[derive(Debug)]
+pub struct KeySlot {
+    permit: Option<OwnedSemaphorePermit>,
+    limits: Arc<KeyConcurrency>,
+    client: Client,
+}
+
+/// The request's slot, added to its extensions by [`limit_key_concurrency`] so
+/// handlers whose work outlives the response, such as WebSocket upgrades, can
+/// keep it taken
+#[derive(Debug, Clone)]
+pub struct HeldSlot(pub Arc<KeySlot>);
+
+impl Drop for KeySlot {
+    fn drop(&mut self) {
+        let Some(permit) = self.permit.take() else {
+            return;
+        };
+        drop(permit);
+        let mut semaphores = self.limits.semaphores.lock().unwrap();
+        // Only the map holds the semaphore once no permits are out
+        if semaphores
+            .get(&self.client)
+            .is_some_and(|semaphore| Arc::strong_count(semaphore) == 1)
+        {
+            semaphores.remove(&self.client);
+        }
+    }
+}
+
+/// Middleware holding a slot of the request's authenticated API key, or without
+/// one its client address, for as long as its response runs
+///
+/// Must run inside [`require_api_key`](crate::api::auth::require_api_key),
+/// which marks the requests whose key it validated.
+pub async fn limit_key_concurrency(
+    State(limits): State<Arc<KeyConcurrency>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let authenticated = request
+        .extensions()
+        .get::<AuthenticatedKey>()
+        .map(|AuthenticatedKey(api_key)| ApiKeys::digest(api_key));
+    let client = match (authenticated, connect_info) {
+        (Some(digest), _) => Client::Key(digest),
+        (None, Some(ConnectInfo(peer))) => Client::Address(peer.ip()),
+        // Only in-process requests, such as tests, come without an address
+        (None, None) => return next.run(request).await,
+    };
+
+    let slot = match limits.try_acquire(&client) {
+        Ok(slot) => Arc::new(slot),
+        Err(limit) => return too_many_concurrent(limit, &client),
+    };
+    request.extensions_mut().insert(HeldSlot(Arc::clone(&slot)));
+    let (parts, body) = next.run(request).await.into_parts();
+    // Keep the slot taken until the body, which may be a stream, is sent
+    let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+        let _ = &slot;
+        chunk
+    }));
+    Response::from_parts(parts, body)
+}
+
+fn too_many_concurrent(limit: u32, client: &Client) -> Response {
+    let who = match client {
+        Client::Key(_) => "This API key",
+        Client::Address(_) => "This client",
+    };
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(ErrorResponse::new(
+            format!(
+                "{} has reached its limit of {} concurrent requests",
+                who, limit
+            ),
+            "requests",
+            None,
+            Some("rate_limit_exceeded"),
+        )),
+    )
+        .into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(1));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, middleware, routing::post};
+    use tokio::sync::watch;
+    use tower::ServiceExt;
+
+    /// Send a request `require_api_key` authenticated with `key`
+    async fn send(app: &Router, key: &str) -> Response {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions")
+            .header(header::AUTHORIZATION, format!("Bearer {}", key))
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(AuthenticatedKey(key.to_string()));
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    /// Send a request from `peer` carrying `key` if given, but not authenticated
+    async fn send_anonymous(app: &Router, peer: &str, key: Option<&str>) -> Response {
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions");
+        if let Some(key) = key {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", key));
+        }
+        let mut request = request.body(Body::empty()).unwrap();
+        let peer: SocketAddr = peer.parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(peer));
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    fn key(api_key: &str) -> Client {
+        Client::Key(ApiKeys::digest(api_key))
+    }
+
+    /// Requests to `app` stay in flight until `true` is sent on the returned channel
+    fn held_app(limits: &Arc<KeyConcurrency>) -> (Router, watch::Sender<bool>) {
+        let (release, released) = watch::channel(false);
+        let app = Router::new()
+            .route(
+                "/v1/chat/completions",
+                post(move || {
+                    let mut released = released.clone();
+                    async move {
+                        let _ = released.wait_for(|&released| released).await;
+                        "done"
+                    }
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(
+                Arc::clone(limits),
+                limit_key_concurrency,
+            ));
+        (app, release)
+    }
+
+    #[tokio::test]
+    async fn test_key_over_its_limit_is_refused_others_are_not() {
+        let limits = Arc::new(KeyConcurrency::new(KeyConcurrencyConfig {
+            default: 1,
+            keys: HashMap::from([(ApiKeys::digest("sk-batch"), 2)]),
+        }));
+        let (app, release) = held_app(&limits);
+
+        let busy = tokio::spawn({
+            let app = app.clone();
+            async move { send(&app, "sk-a").await }
+        });
+        while limits.in_flight(&key("sk-a")) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let refused = send(&app, "sk-a").await;
+        assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(refused.headers().contains_key(header::RETRY_AFTER));
+
+        // Another key has its own slots
+        let other = tokio::spawn({
+            let app = app.clone();
+            async move { send(&app, "sk-b").await }
+        });
+        while limits.in_flight(&key("sk-b")) == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(limits.in_flight(&key("sk-a")), 1);
+
+        release.send(true).unwrap();
+        assert_eq!(busy.await.unwrap().status(), StatusCode::OK);
+        assert_eq!(other.await.unwrap().status(), StatusCode::OK);
+
+        // Finished requests give their slot back
+        assert_eq!(limits.in_flight(&key("sk-a")), 0);
+        assert_eq!(limits.config.limit(&key("sk-batch")), 2);
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_a_key_are_limited_by_address() {
+        let limits = Arc::new(KeyConcurrency::new(KeyConcurrencyConfig {
+            default: 1,
+            keys: HashMap::new(),
+        }));
+        let (app, release) = held_app(&limits);
+        let first = Client::Address("203.0.113.7".parse().unwrap());
+
+        let busy = tokio::spawn({
+            let app = app.clone();
+            async move { send_anonymous(&app, "203.0.113.7:4000", None).await }
+        });
+        while limits.in_flight(&first) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        // Another connection from the same host shares its slots, even with a
+        // made-up key
+        let refused = send_anonymous(&app, "203.0.113.7:4001", None).await;
+        assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+        let refused = send_anonymous(&app, "203.0.113.7:4002", Some("sk-invented")).await;
+        assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(limits.in_flight(&key("sk-invented")), 0);
+
+        let other = tokio::spawn({
+            let app = app.clone();
+            async move { send_anonymous(&app, "203.0.113.8:4000", None).await }
+        });
+        let second = Client::Address("203.0.113.8".parse().unwrap());
+        while limits.in_flight(&second) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        release.send(true).unwrap();
+        assert_eq!(busy.await.unwrap().status(), StatusCode::OK);
+        assert_eq!(other.await.unwrap().status(), StatusCode::OK);
+        assert_eq!(limits.in_flight(&first), 0);
+    }
+}
@@ -0,0 +1,240 @@
+//! Server-side request size limits
+//!
+//! Limits are configured per endpoint under `[server.request_limits]` and checked for
+//! every request body before it reaches a handler, whether or not strict OpenAI
+//! compatibility is enabled. Token counts are estimated with [`estimate_tokens`],
+//! since the model may not be loaded when the request arrives.
+
+use crate::api::openai_compliance::OpenAIEndpoint;
+use crate::backends::tokenizer::estimate_tokens;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Limits for one endpoint; `None` leaves a dimension unlimited
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EndpointLimits {
+    /// Characters in one prompt, or across all messages of a chat request
+    pub max_prompt_chars: Option<usize>,
+    /// Estimated tokens in one prompt, or across all messages of a chat request
+    pub max_prompt_tokens: Option<usize>,
+    pub max_messages: Option<usize>,
+    /// Prompts or inputs sent together in one request
+    pub max_batch_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestLimits {
+    pub chat_completions: EndpointLimits,
+    pub completions: EndpointLimits,
+    pub embeddings: EndpointLimits,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            chat_completions: EndpointLimits {
+                max_prompt_chars: Some(400_000),
+                max_prompt_tokens: Some(131_072),
+                max_messages: Some(1_024),
+                max_batch_size: None,
+            },
+            completions: EndpointLimits {
+                max_prompt_chars: Some(400_000),
+                max_prompt_tokens: Some(131_072),
+                max_messages: None,
+                max_batch_size: Some(64),
+            },
+            embeddings: EndpointLimits {
+                max_prompt_chars: Some(32_000),
+                max_prompt_tokens: Some(8_192),
+                max_messages: None,
+                max_batch_size: Some(2_048),
+            },
+        }
+    }
+}
+
+impl RequestLimits {
+    pub fn for_endpoint(&self, endpoint: OpenAIEndpoint) -> &EndpointLimits {
+        match endpoint {
+            OpenAIEndpoint::ChatCompletions => &self.chat_completions,
+            OpenAIEndpoint::Completions => &self.completions,
+            OpenAIEndpoint::Embeddings => &self.embeddings,
+        }
+    }
+}
+
+/// The parts of a request body that limits apply to
+#[derive(Debug, Clone)]
+pub enum RequestInputs<'a> {
+    /// Chat message contents, limited together as one prompt
+    Messages(Vec<&'a str>),
+    /// Independent prompts sent as a batch in the `param` field
+    Batch {
+        param: &'static str,
+        items: Vec<&'a str>,
+    },
+}
+
+/// A request over one of its endpoint's limits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitExceeded {
+    /// Name of the exceeded limit, as configured
+    pub limit: &'static str,
+    pub max: usize,
+    pub actual: usize,
+    /// Request field the limit applies to, such as `messages` or `input[3]`
+    pub param: String,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.limit {
+            "max_prompt_chars" => "characters",
+            "max_prompt_tokens" => "tokens (estimated)",
+            "max_messages" => "messages",
+            _ => "items",
+        };
+        write!(
+            f,
+            "'{}' has {} {}, exceeding the server limit {} = {}",
+            self.param, self.actual, unit, self.limit, self.max
+        )
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl EndpointLimits {
+    /// Check `inputs` against every configured limit, reporting the first exceeded
+    pub fn check(&self, inputs: &RequestInputs<'_>) -> Result<(), LimitExceeded> {
+        match inputs {
+            RequestInputs::Messages(contents) => {
+                exceeds(
+                    self.max_messages,
+                    contents.len(),
+                    "max_messages",
+                    "messages",
+                )?;
+                self.check_prompt(contents, "messages")
+            }
+            RequestInputs::Batch { param, items } => {
+                exceeds(self.max_batch_size, items.len(), "max_batch_size", param)?;
+                for (i, &item) in items.iter().enumerate() {
+                    let param = if items.len() == 1 {
+                        param.to_string()
+                    } else {
+                        format!("{}[{}]", param, i)
+                    };
+                    self.check_prompt(&[item], &param)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn check_prompt(&self, parts: &[&str], param: &str) -> Result<(), LimitExceeded> {
+        if self.max_prompt_chars.is_some() {
+            let chars = parts.iter().map(|part| part.chars().count()).sum();
+            exceeds(self.max_prompt_chars, chars, "max_prompt_chars", param)?;
+        }
+        if self.max_prompt_tokens.is_some() {
+            let tokens = parts
+                .iter()
+                .map(|part| estimate_tokens(part) as usize)
+                .sum();
+            exceeds(self.max_prompt_tokens, tokens, "max_prompt_tokens", param)?;
+        }
+        Ok(())
+    }
+}
+
+fn exceeds(
+    max: Option<usize>,
+    actual: usize,
+    limit: &'static str,
+    param: &str,
+) -> Result<(), LimitExceeded> {
+    match max {
+        Some(max) if actual > max => Err(LimitExceeded {
+            limit,
+            max,
+            actual,
+            param: param.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> EndpointLimits {
+        EndpointLimits {
+            max_prompt_chars: Some(20),
+            max_prompt_tokens: Some(4),
+            max_messages: Some(2),
+            max_batch_size: Some(2),
+        }
+    }
+
+    fn batch<'a>(items: &[&'a str]) -> RequestInputs<'a> {
+        RequestInputs::Batch {
+            param: "input",
+            items: items.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_requests_under_limits_pass() {
+        let limits = limits();
+        assert!(
+            limits
+                .check(&RequestInputs::Messages(vec!["hi", "hello"]))
+                .is_ok()
+        );
+        assert!(limits.check(&batch(&["one", "two"])).is_ok());
+        assert!(
+            EndpointLimits::default()
+                .check(&batch(&["x"; 10_000]))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_each_limit_enforced() {
+        let limits = limits();
+
+        let err = limits
+            .check(&RequestInputs::Messages(vec!["a", "b", "c"]))
+            .unwrap_err();
+        assert_eq!((err.limit, err.max, err.actual), ("max_messages", 2, 3));
+        assert_eq!(err.param, "messages");
+
+        let err = limits.check(&batch(&["a", "b", "c"])).unwrap_err();
+        assert_eq!((err.limit, err.actual), ("max_batch_size", 3));
+
+        // Characters are summed across chat messages
+        let err = limits
+            .check(&RequestInputs::Messages(vec!["0123456789", "0123456789a"]))
+            .unwrap_err();
+        assert_eq!((err.limit, err.actual), ("max_prompt_chars", 21));
+
+        let err = limits
+            .check(&batch(&["ok", "one two three four five"]))
+            .unwrap_err();
+        assert_eq!(err.param, "input[1]");
+        assert_eq!(err.limit, "max_prompt_chars");
+
+        let err = limits.check(&batch(&["a b c d e f"])).unwrap_err();
+        assert_eq!(err.limit, "max_prompt_tokens");
+        assert_eq!(err.param, "input");
+        assert_eq!(
+            err.to_string(),
+            "'input' has 8 tokens (estimated), exceeding the server limit max_prompt_tokens = 4"
+        );
+    }
+}
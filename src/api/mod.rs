@@ -1,13 +1,25 @@
+pub mod auth;
+pub mod coalesce;
 pub mod flow_control;
+pub mod key_concurrency;
+pub mod limits;
 pub mod openai;
 pub mod openai_compliance;
+pub mod resume;
 pub mod streaming_enhancements;
+pub mod timeouts;
+pub mod tools;
 pub mod websocket;
 
 pub use flow_control::{BackpressureLevel, ConnectionPool, FlowControlConfig, StreamFlowControl};
+pub use limits::{EndpointLimits, LimitExceeded, RequestLimits};
 pub use openai::*;
-pub use openai_compliance::{ComplianceValidator, ErrorResponse, ModelInfo, OPENAI_API_VERSION};
+pub use openai_compliance::{
+    ComplianceValidator, ErrorResponse, ModelInfo, OPENAI_API_VERSION, OpenAIEndpoint,
+};
+pub use resume::StreamSessions;
 pub use streaming_enhancements::{
     CompressionFormat, KeepAlive, SSEConfig, SSEMessage, StreamingOptimizationConfig,
     TimeoutManager, TokenBatcher,
 };
+pub use timeouts::RouteTimeouts;
@@ -1,13 +1,42 @@
 use crate::{
-    backends::{BackendHandle, BackendType, InferenceParams},
+    InfernoError,
+    api::{
+        auth::{ApiKeys, AuthenticatedKey},
+        coalesce::RequestCoalescer,
+        limits::RequestInputs,
+        openai_compliance::{
+            ComplianceValidator, ErrorResponse, OpenAIEndpoint, OpenAIError, ValidationResult,
+        },
+        resume::{ResumeError, StreamSession, StreamSessions},
+        streaming_enhancements::{
+            KeepAlive, StreamingOptimizationConfig, TimeoutManager, TokenBatcher, WatchedToken,
+            batch_token_stream, watch_token_stream,
+        },
+        tools::{self, Tool, ToolCall, ToolChoice, ToolMode},
+    },
+    backends::{
+        BackendHandle, BackendType, CancellationToken, Generation, InferenceParams, PostProcessor,
+        ResponseFormat, TokenLogprob, TokenStream, logprobs::MAX_TOP_LOGPROBS,
+    },
     cli::serve::ServerState,
+    metrics::{InferenceEvent, MetricsCollector, usage::UsageLedger},
+    models::{
+        ModelInfo, ModelManager,
+        chat_template::{BuiltinTemplate, ChatTemplate},
+    },
+    multi_tenancy::TenantConfig,
+    operations::queue::{DispatchPermit, Priority, QueuePlacement, RequestMetadata},
+    resilience::{CircuitBreaker, ModelSelection, ProtectedBackend},
 };
 use axum::{
-    extract::{Json, State},
-    http::StatusCode,
-    response::IntoResponse,
+    Extension,
+    extract::{FromRequest, Json, Path, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
 };
-use serde::{Deserialize, Serialize};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::collections::HashMap;
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -15,9 +44,11 @@ use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatCompletionRequest {
+    /// Model file or alias; the default alias when omitted
+    #[serde(default)]
     pub model: String,
     pub messages: Vec<ChatMessage>,
-    #[serde(default = "default_max_tokens")]
+    #[serde(default = "default_max_tokens", alias = "max_completion_tokens")]
     pub max_tokens: u32,
     #[serde(default = "default_temperature")]
     pub temperature: f32,
@@ -26,6 +57,12 @@ pub struct ChatCompletionRequest {
     #[serde(default = "default_top_p")]
     pub top_p: f32,
     #[serde(default)]
+    pub repeat_penalty: Option<f32>,
+    #[serde(default)]
+    pub repeat_last_n: Option<u32>,
+    #[serde(default)]
+    pub min_p: Option<f32>,
+    #[serde(default)]
     pub n: Option<u32>,
     #[serde(default)]
     pub stream: bool,
@@ -37,14 +74,75 @@ pub struct ChatCompletionRequest {
     pub frequency_penalty: Option<f32>,
     #[serde(default)]
     pub user: Option<String>,
+    /// Sampling seed; the same seed and parameters reproduce the same output
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Post-processor applied to the completion; overrides the server default
+    #[serde(default)]
+    pub post_process: Option<String>,
+    /// Framing of a streamed response
+    #[serde(default)]
+    pub stream_format: StreamFormat,
+    /// Return the log probability of each output token
+    #[serde(default)]
+    pub logprobs: bool,
+    /// Likeliest alternatives to report for each output token; needs `logprobs`
+    #[serde(default)]
+    pub top_logprobs: Option<u32>,
+    /// Functions the model may call
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<Tool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+    /// Shape the reply must take; a forced tool call takes precedence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ChatResponseFormat>,
+}
+
+/// OpenAI's `response_format` of a chat request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: JsonSchemaFormat },
+}
+
+/// The schema of a `json_schema` response format
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonSchemaFormat {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub schema: serde_json::Value,
+}
+
+impl ChatResponseFormat {
+    fn to_response_format(&self) -> ResponseFormat {
+        match self {
+            Self::Text => ResponseFormat::Text,
+            Self::JsonObject => ResponseFormat::JsonObject,
+            Self::JsonSchema { json_schema } => {
+                ResponseFormat::JsonSchema(json_schema.schema.clone())
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
-    pub content: String,
+    /// Null in assistant messages that only call tools
+    #[serde(default)]
+    pub content: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// Functions the assistant called
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// Call a `tool` message is the result of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,12 +153,17 @@ pub struct ChatCompletionResponse {
     pub model: String,
     pub choices: Vec<ChatChoice>,
     pub usage: Usage,
+    /// Seed the output was sampled with; an Inferno extension to the OpenAI schema
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatChoice {
     pub index: u32,
     pub message: ChatMessage,
+    #[serde(default)]
+    pub logprobs: Option<serde_json::Value>,
     pub finish_reason: String,
 }
 
@@ -73,6 +176,8 @@ pub struct Usage {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompletionRequest {
+    /// Model file or alias; the default alias when omitted
+    #[serde(default)]
     pub model: String,
     pub prompt: StringOrArray,
     #[serde(default = "default_max_tokens")]
@@ -84,9 +189,17 @@ pub struct CompletionRequest {
     #[serde(default = "default_top_p")]
     pub top_p: f32,
     #[serde(default)]
+    pub repeat_penalty: Option<f32>,
+    #[serde(default)]
+    pub repeat_last_n: Option<u32>,
+    #[serde(default)]
+    pub min_p: Option<f32>,
+    #[serde(default)]
     pub n: Option<u32>,
     #[serde(default)]
     pub stream: bool,
+    /// Return the log probability of each output token with this many
+    /// alternatives, up to 5
     #[serde(default)]
     pub logprobs: Option<u32>,
     #[serde(default)]
@@ -101,6 +214,27 @@ pub struct CompletionRequest {
     pub best_of: Option<u32>,
     #[serde(default)]
     pub user: Option<String>,
+    /// Sampling seed; the same seed and parameters reproduce the same output
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Post-processor applied to the completion; overrides the server default
+    #[serde(default)]
+    pub post_process: Option<String>,
+    /// Framing of a streamed response
+    #[serde(default)]
+    pub stream_format: StreamFormat,
+}
+
+/// How the tokens of a streamed response are framed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamFormat {
+    /// OpenAI chunk objects carrying each token in a delta
+    #[default]
+    OpenAI,
+    /// SSE events whose data is the bare token text, for clients that do not
+    /// parse OpenAI chunks
+    Raw,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -118,6 +252,12 @@ pub struct CompletionResponse {
     pub model: String,
     pub choices: Vec<CompletionChoice>,
     pub usage: Usage,
+    /// Seed the output was sampled with; an Inferno extension to the OpenAI schema
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Why a stream ended early; the text sent before it is kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<OpenAIError>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -130,8 +270,13 @@ pub struct CompletionChoice {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingRequest {
+    /// Model file or alias; the default alias when omitted
+    #[serde(default)]
     pub model: String,
     pub input: StringOrArray,
+    /// Length to truncate each embedding to, at most the model's dimension
+    #[serde(default)]
+    pub dimensions: Option<usize>,
     #[serde(default)]
     pub user: Option<String>,
 }
@@ -166,6 +311,24 @@ pub struct ModelObject {
     pub permission: Vec<serde_json::Value>,
     pub root: String,
     pub parent: Option<String>,
+    /// Backend that serves the model; an Inferno extension to the OpenAI schema
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend_type: Option<String>,
+}
+
+impl From<ModelInfo> for ModelObject {
+    fn from(model: ModelInfo) -> Self {
+        Self {
+            id: model.name.clone(),
+            object: "model".to_string(),
+            created: model.modified.timestamp(),
+            owned_by: "inferno".to_string(),
+            permission: vec![],
+            root: model.name,
+            parent: None,
+            backend_type: Some(model.backend_type),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -183,12 +346,22 @@ pub struct ChatCompletionChunk {
     pub created: i64,
     pub model: String,
     pub choices: Vec<ChatChunkChoice>,
+    /// Seed the output is sampled with, sent on the first chunk; an Inferno
+    /// extension to the OpenAI schema
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Set on the final chunk of a stream that failed; the content sent before it
+    /// is kept and `finish_reason` is `"error"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<OpenAIError>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatChunkChoice {
     pub index: u32,
     pub delta: ChatDelta,
+    #[serde(default)]
+    pub logprobs: Option<serde_json::Value>,
     pub finish_reason: Option<String>,
 }
 
@@ -200,9 +373,13 @@ pub struct ChatDelta {
     pub content: Option<String>,
 }
 
+/// Most alternatives the legacy completions endpoint reports per token
+const MAX_COMPLETION_LOGPROBS: u32 = 5;
+
 // Default values
 
-fn default_max_tokens() -> u32 {
+/// `max_tokens` of requests that do not set it
+pub(crate) fn default_max_tokens() -> u32 {
     512
 }
 
@@ -218,6 +395,261 @@ fn default_top_p() -> f32 {
     0.9
 }
 
+// Request extraction
+
+/// Request bodies accepted by the OpenAI-compatible endpoints
+pub trait OpenAIRequest: DeserializeOwned + Send {
+    const ENDPOINT: OpenAIEndpoint;
+
+    /// Prompts and messages checked against the server's request limits
+    fn inputs(&self) -> RequestInputs<'_>;
+
+    /// The requested model, filled in with the default alias when empty
+    fn model_mut(&mut self) -> &mut String;
+
+    /// Range checks applied in strict mode
+    fn validate(&self) -> ValidationResult {
+        ValidationResult::valid()
+    }
+
+    /// Generation parameters checked in every mode, before the request waits
+    /// for a model to be loaded
+    fn validate_params(&self) -> Result<(), InfernoError> {
+        Ok(())
+    }
+}
+
+impl OpenAIRequest for ChatCompletionRequest {
+    const ENDPOINT: OpenAIEndpoint = OpenAIEndpoint::ChatCompletions;
+
+    fn model_mut(&mut self) -> &mut String {
+        &mut self.model
+    }
+
+    fn inputs(&self) -> RequestInputs<'_> {
+        RequestInputs::Messages(
+            self.messages
+                .iter()
+                .map(|m| m.content.as_deref().unwrap_or_default())
+                .collect(),
+        )
+    }
+
+    fn validate(&self) -> ValidationResult {
+        let result = ComplianceValidator::validate_chat_completion_request(
+            &self.model,
+            Some(self.max_tokens.min(i32::MAX as u32) as i32),
+            Some(self.temperature),
+            Some(self.top_p),
+        );
+        match self.top_logprobs {
+            Some(top) if top > MAX_TOP_LOGPROBS => {
+                result.with_error(format!("top_logprobs must be at most {}", MAX_TOP_LOGPROBS))
+            }
+            _ => result,
+        }
+    }
+
+    fn validate_params(&self) -> Result<(), InfernoError> {
+        self.inference_params().validate()
+    }
+}
+
+impl OpenAIRequest for CompletionRequest {
+    const ENDPOINT: OpenAIEndpoint = OpenAIEndpoint::Completions;
+
+    fn model_mut(&mut self) -> &mut String {
+        &mut self.model
+    }
+
+    fn inputs(&self) -> RequestInputs<'_> {
+        self.prompt.as_inputs("prompt")
+    }
+
+    fn validate(&self) -> ValidationResult {
+        let result = ComplianceValidator::validate_completion_request(
+            &self.model,
+            Some(self.max_tokens.min(i32::MAX as u32) as i32),
+        );
+        match self.logprobs {
+            Some(top) if top > MAX_COMPLETION_LOGPROBS => result.with_error(format!(
+                "logprobs must be at most {}",
+                MAX_COMPLETION_LOGPROBS
+            )),
+            _ => result,
+        }
+    }
+
+    fn validate_params(&self) -> Result<(), InfernoError> {
+        self.inference_params().validate()
+    }
+}
+
+impl ChatCompletionRequest {
+    /// Generation parameters the request sets; the handler adds the chat
+    /// template's stop sequences and any tool-call format
+    fn inference_params(&self) -> InferenceParams {
+        InferenceParams {
+            max_tokens: self.max_tokens,
+            temperature: self.temperature,
+            top_k: self.top_k,
+            repeat_penalty: self.repeat_penalty,
+            repeat_last_n: self.repeat_last_n,
+            min_p: self.min_p,
+            top_p: self.top_p,
+            stream: self.stream,
+            stop_sequences: self.stop.clone().unwrap_or_default(),
+            stop_regex: None,
+            max_generation_ms: None,
+            seed: self.seed,
+            response_format: self
+                .response_format
+                .as_ref()
+                .map(ChatResponseFormat::to_response_format),
+            logprobs: self.logprobs.then(|| self.top_logprobs.unwrap_or(0)),
+        }
+    }
+}
+
+impl CompletionRequest {
+    fn inference_params(&self) -> InferenceParams {
+        InferenceParams {
+            max_tokens: self.max_tokens,
+            temperature: self.temperature,
+            top_k: self.top_k,
+            repeat_penalty: self.repeat_penalty,
+            repeat_last_n: self.repeat_last_n,
+            min_p: self.min_p,
+            top_p: self.top_p,
+            stream: self.stream,
+            stop_sequences: self.stop.clone().unwrap_or_default(),
+            stop_regex: None,
+            max_generation_ms: None,
+            seed: self.seed,
+            response_format: None,
+            logprobs: self.logprobs,
+        }
+    }
+}
+
+impl OpenAIRequest for EmbeddingRequest {
+    const ENDPOINT: OpenAIEndpoint = OpenAIEndpoint::Embeddings;
+
+    fn model_mut(&mut self) -> &mut String {
+        &mut self.model
+    }
+
+    fn inputs(&self) -> RequestInputs<'_> {
+        self.input.as_inputs("input")
+    }
+}
+
+impl StringOrArray {
+    fn as_inputs(&self, param: &'static str) -> RequestInputs<'_> {
+        let items = match self {
+            StringOrArray::String(s) => vec![s.as_str()],
+            StringOrArray::Array(arr) => arr.iter().map(String::as_str).collect(),
+        };
+        RequestInputs::Batch { param, items }
+    }
+}
+
+/// JSON body extractor that enforces the server's request limits and, with
+/// `--openai-compat-strict`, rejects fields the OpenAI API does not document and
+/// reports every problem as an `invalid_request_error`
+pub struct OpenAIJson<T>(pub T);
+
+#[axum::async_trait]
+impl<T: OpenAIRequest> FromRequest<Arc<ServerState>> for OpenAIJson<T> {
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &Arc<ServerState>) -> Result<Self, Self::Rejection> {
+        let strict = state.openai_compat_strict;
+        let request = if strict {
+            parse_strict::<T>(req, state).await?
+        } else {
+            let Json(mut request) = Json::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            apply_default_model(&mut request, state);
+            request
+        };
+
+        let limits = state.config.server.request_limits.for_endpoint(T::ENDPOINT);
+        if let Err(exceeded) = limits.check(&request.inputs()) {
+            return Err(api_error(
+                strict,
+                StatusCode::BAD_REQUEST,
+                exceeded.to_string(),
+                "invalid_request_error",
+                Some(exceeded.param.as_str()),
+            ));
+        }
+        if let Err(e) = request.validate_params() {
+            let message = match e {
+                InfernoError::Validation(message) => message,
+                other => other.to_string(),
+            };
+            return Err(api_error(
+                strict,
+                StatusCode::BAD_REQUEST,
+                message,
+                "invalid_request_error",
+                None,
+            ));
+        }
+        Ok(OpenAIJson(request))
+    }
+}
+
+/// Point requests that name no model at the configured default alias
+fn apply_default_model<T: OpenAIRequest>(request: &mut T, state: &ServerState) {
+    let model = request.model_mut();
+    if model.is_empty()
+        && let Some(default) = &state.model_manager.aliases().default
+    {
+        *model = default.clone();
+    }
+}
+
+/// Parse a body in strict mode, reporting problems as OpenAI errors
+async fn parse_strict<T: OpenAIRequest>(
+    req: Request,
+    state: &Arc<ServerState>,
+) -> Result<T, Response> {
+    let invalid = |message: String, param: Option<&str>| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::for_status(400, message, param)),
+        )
+            .into_response()
+    };
+
+    let Json(body) = Json::<serde_json::Value>::from_request(req, state)
+        .await
+        .map_err(|rejection| invalid(rejection.body_text(), None))?;
+
+    let unknown = ComplianceValidator::unknown_fields(T::ENDPOINT, &body);
+    if let Some(first) = unknown.first() {
+        return Err(invalid(
+            format!(
+                "Unrecognized request argument supplied: {}",
+                unknown.join(", ")
+            ),
+            Some(first.as_str()),
+        ));
+    }
+
+    let mut request: T = serde_json::from_value(body).map_err(|e| invalid(e.to_string(), None))?;
+    // Before validation, which requires a model
+    apply_default_model(&mut request, state);
+    let validation = request.validate();
+    if !validation.is_valid {
+        return Err(invalid(validation.errors.join("; "), None));
+    }
+    Ok(request)
+}
+
 // API State
 
 // Note: We use the ServerState from cli::serve module
@@ -226,165 +658,290 @@ fn default_top_p() -> f32 {
 
 pub async fn chat_completions(
     State(state): State<Arc<ServerState>>,
-    Json(request): Json<ChatCompletionRequest>,
+    headers: HeaderMap,
+    tenant: Option<Extension<Arc<TenantConfig>>>,
+    authenticated: Option<Extension<AuthenticatedKey>>,
+    OpenAIJson(mut request): OpenAIJson<ChatCompletionRequest>,
 ) -> impl IntoResponse {
-    // Convert chat messages to a single prompt
-    let prompt = format_chat_messages(&request.messages);
+    let strict = state.openai_compat_strict;
+    // Streams are only resumed for the tenant and key that started them
+    let requester = requester(tenant.as_deref(), authenticated.as_deref());
+    // A reconnecting client picks up its stream where it left off
+    if request.stream
+        && request.stream_format == StreamFormat::OpenAI
+        && let Some(response) = resume_stream(&state, &headers, &requester)
+    {
+        return response;
+    }
+    let post_processor = match post_processor(&state, request.post_process.as_deref()) {
+        Ok(post_processor) => post_processor,
+        Err(response) => return response,
+    };
+
+    let (permit, placement) =
+        match wait_for_dispatch(&state, &headers, &request.model, request.max_tokens).await {
+            Ok(dispatched) => dispatched,
+            Err(response) => return response,
+        };
+
+    // Responses name the model that served the request, which may be a fallback
+    let breaker = match select_model(&state, &request.model) {
+        Ok(selection) => {
+            request.model = selection.model;
+            selection.breaker
+        }
+        Err(response) => return with_queue_headers(response, placement),
+    };
 
     // Get or load the backend
-    let backend = match get_or_load_backend(&state, &request.model).await {
-        Ok(backend) => backend,
+    let backend = match get_or_load_backend(&state, tenant.as_deref(), &request.model).await {
+        Ok(backend) => ServingBackend::new(backend, breaker)
+            .with_post_processor(post_processor)
+            .with_metrics(state.metrics.clone())
+            .with_requester(requester.clone())
+            .with_usage(state.usage_ledger.clone(), authenticated),
+        Err(e) => return model_error(strict, e),
+    };
+
+    let tool_mode = match ToolMode::for_request(&request.tools, request.tool_choice.as_ref()) {
+        Ok(tool_mode) => tool_mode,
+        Err((message, param)) => {
+            let response = api_error(
+                strict,
+                StatusCode::BAD_REQUEST,
+                message,
+                "invalid_request_error",
+                Some(param),
+            );
+            return with_queue_headers(response, placement);
+        }
+    };
+    let offered_tools = tool_mode.offered(&request.tools);
+
+    let template = chat_template(&state, &backend.backend, &request.model).await;
+    let messages = tools::prompt_messages(
+        &request.messages,
+        tool_mode.instructions(&request.tools),
+        template.renders_tools(),
+    );
+    let tool_definitions: Vec<serde_json::Value> = offered_tools
+        .iter()
+        .map(|tool| serde_json::to_value(tool).unwrap_or_default())
+        .collect();
+    let prompt = match template.render_with_tools(&messages, &tool_definitions) {
+        Ok(prompt) => prompt,
         Err(e) => {
-            return (
+            let response = api_error(
+                strict,
                 StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({
-                    "error": {
-                        "message": format!("Failed to load model: {}", e),
-                        "type": "invalid_request_error",
-                        "param": "model",
-                        "code": null
-                    }
-                })),
-            )
-                .into_response();
+                format!("{:#}", e),
+                "invalid_request_error",
+                Some("messages"),
+            );
+            return with_queue_headers(response, placement);
         }
     };
 
     let stream = request.stream;
-    let stop_sequences = request.stop.clone().unwrap_or_default();
-    let inference_params = InferenceParams {
-        max_tokens: request.max_tokens,
-        temperature: request.temperature,
-        top_k: request.top_k,
-        top_p: request.top_p,
-        stream: request.stream,
-        stop_sequences,
-        seed: None,
-    };
+    let mut inference_params = request.inference_params();
+    for stop in template.stop_sequences() {
+        if !inference_params.stop_sequences.contains(stop) {
+            inference_params.stop_sequences.push(stop.clone());
+        }
+    }
+    if let Some(format) = tool_mode.response_format(&request.tools) {
+        inference_params.response_format = Some(format);
+    }
 
-    if stream {
+    let response = if stream && request.stream_format == StreamFormat::Raw {
+        handle_raw_stream(
+            request.model.clone(),
+            backend,
+            prompt,
+            inference_params,
+            permit,
+            streaming_config(&state),
+        )
+        .await
+    } else if stream {
         // Handle streaming response
-        handle_streaming_chat(&request, backend, prompt, inference_params)
-            .await
-            .into_response()
+        handle_streaming_chat(
+            &request,
+            backend,
+            prompt,
+            inference_params,
+            permit,
+            strict,
+            streaming_config(&state),
+            &state.stream_sessions,
+        )
+        .await
     } else {
         // Handle non-streaming response
-        handle_non_streaming_chat(&request, backend, prompt, inference_params)
-            .await
-            .into_response()
-    }
+        handle_non_streaming_chat(
+            &request,
+            &offered_tools,
+            backend,
+            coalescer(&state),
+            prompt,
+            inference_params,
+            permit,
+            strict,
+        )
+        .await
+        .into_response()
+    };
+    with_queue_headers(response, placement)
 }
 
 pub async fn completions(
     State(state): State<Arc<ServerState>>,
-    Json(request): Json<CompletionRequest>,
+    headers: HeaderMap,
+    tenant: Option<Extension<Arc<TenantConfig>>>,
+    authenticated: Option<Extension<AuthenticatedKey>>,
+    OpenAIJson(mut request): OpenAIJson<CompletionRequest>,
 ) -> impl IntoResponse {
+    let strict = state.openai_compat_strict;
+    // Streams are only resumed for the tenant and key that started them
+    let requester = requester(tenant.as_deref(), authenticated.as_deref());
+    // A reconnecting client picks up its stream where it left off
+    if request.stream
+        && request.stream_format == StreamFormat::OpenAI
+        && let Some(response) = resume_stream(&state, &headers, &requester)
+    {
+        return response;
+    }
+    let post_processor = match post_processor(&state, request.post_process.as_deref()) {
+        Ok(post_processor) => post_processor,
+        Err(response) => return response,
+    };
     // Extract prompt
     let prompt = match &request.prompt {
         StringOrArray::String(s) => s.clone(),
         StringOrArray::Array(arr) => arr.join("\n"),
     };
 
-    // Get or load the backend
-    let backend = match get_or_load_backend(&state, &request.model).await {
-        Ok(backend) => backend,
-        Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({
-                    "error": {
-                        "message": format!("Failed to load model: {}", e),
-                        "type": "invalid_request_error",
-                        "param": "model",
-                        "code": null
-                    }
-                })),
-            )
-                .into_response();
+    let (permit, placement) =
+        match wait_for_dispatch(&state, &headers, &request.model, request.max_tokens).await {
+            Ok(dispatched) => dispatched,
+            Err(response) => return response,
+        };
+
+    // Responses name the model that served the request, which may be a fallback
+    let breaker = match select_model(&state, &request.model) {
+        Ok(selection) => {
+            request.model = selection.model;
+            selection.breaker
         }
+        Err(response) => return with_queue_headers(response, placement),
     };
 
-    let stream = request.stream;
-    let stop_sequences = request.stop.clone().unwrap_or_default();
-    let inference_params = InferenceParams {
-        max_tokens: request.max_tokens,
-        temperature: request.temperature,
-        top_k: request.top_k,
-        top_p: request.top_p,
-        stream: request.stream,
-        stop_sequences,
-        seed: None,
+    // Get or load the backend
+    let backend = match get_or_load_backend(&state, tenant.as_deref(), &request.model).await {
+        Ok(backend) => ServingBackend::new(backend, breaker)
+            .with_post_processor(post_processor)
+            .with_metrics(state.metrics.clone())
+            .with_requester(requester.clone())
+            .with_usage(state.usage_ledger.clone(), authenticated),
+        Err(e) => return model_error(strict, e),
     };
 
-    if stream {
+    let stream = request.stream;
+    let inference_params = request.inference_params();
+
+    let response = if stream && request.stream_format == StreamFormat::Raw {
+        handle_raw_stream(
+            request.model.clone(),
+            backend,
+            prompt,
+            inference_params,
+            permit,
+            streaming_config(&state),
+        )
+        .await
+    } else if stream {
         // Handle streaming response
-        handle_streaming_completion(&request, backend, prompt, inference_params)
-            .await
-            .into_response()
+        handle_streaming_completion(
+            &request,
+            backend,
+            prompt,
+            inference_params,
+            permit,
+            strict,
+            streaming_config(&state),
+            &state.stream_sessions,
+        )
+        .await
     } else {
         // Handle non-streaming response
-        handle_non_streaming_completion(&request, backend, prompt, inference_params)
-            .await
-            .into_response()
-    }
+        handle_non_streaming_completion(
+            &request,
+            backend,
+            coalescer(&state),
+            prompt,
+            inference_params,
+            permit,
+            strict,
+        )
+        .await
+        .into_response()
+    };
+    with_queue_headers(response, placement)
 }
 
 pub async fn embeddings(
     State(state): State<Arc<ServerState>>,
-    Json(request): Json<EmbeddingRequest>,
+    headers: HeaderMap,
+    tenant: Option<Extension<Arc<TenantConfig>>>,
+    OpenAIJson(request): OpenAIJson<EmbeddingRequest>,
 ) -> impl IntoResponse {
+    let strict = state.openai_compat_strict;
     // Extract input
     let inputs = match request.input {
         StringOrArray::String(s) => vec![s],
         StringOrArray::Array(arr) => arr,
     };
 
+    let estimated_tokens: usize = inputs.iter().map(|input| input.len() / 4 + 1).sum();
+    let (mut permit, placement) =
+        match wait_for_dispatch(&state, &headers, &request.model, estimated_tokens as u32).await {
+            Ok(dispatched) => dispatched,
+            Err(response) => return response,
+        };
+
     // Get or load the backend
-    let backend = match get_or_load_backend(&state, &request.model).await {
+    let backend = match get_or_load_backend(&state, tenant.as_deref(), &request.model).await {
         Ok(backend) => backend,
-        Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({
-                    "error": {
-                        "message": format!("Failed to load model: {}", e),
-                        "type": "invalid_request_error",
-                        "param": "model",
-                        "code": null
-                    }
-                })),
-            )
-                .into_response();
-        }
+        Err(e) => return model_error(strict, e),
     };
 
+    let options = state
+        .config
+        .server
+        .embeddings
+        .clone()
+        .with_dimensions(request.dimensions);
     let mut embeddings_data = Vec::new();
     let mut total_tokens = 0u32;
 
     for (index, input) in inputs.iter().enumerate() {
         // BackendHandle already provides async methods, no need for explicit locking
-        match backend.get_embeddings(input).await {
+        let embedding = backend
+            .get_embeddings(input)
+            .await
+            .and_then(|embedding| Ok(options.apply(embedding)?));
+        match embedding {
             Ok(embedding) => {
                 embeddings_data.push(EmbeddingData {
                     object: "embedding".to_string(),
                     embedding,
                     index: index as u32,
                 });
-                total_tokens += estimate_tokens(input);
+                total_tokens += backend.count_tokens(input).await;
             }
             Err(e) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({
-                        "error": {
-                            "message": format!("Failed to generate embeddings: {}", e),
-                            "type": "internal_error",
-                            "param": null,
-                            "code": null
-                        }
-                    })),
-                )
-                    .into_response();
+                permit.mark_failed();
+                return backend_error(strict, "Failed to generate embeddings", e);
             }
         }
     }
@@ -399,107 +956,690 @@ pub async fn embeddings(
         },
     };
 
-    Json(response).into_response()
+    with_queue_headers(Json(response).into_response(), placement)
 }
 
-pub async fn list_models(State(state): State<Arc<ServerState>>) -> impl IntoResponse {
-    match state.model_manager.list_models().await {
+pub async fn list_models(
+    State(state): State<Arc<ServerState>>,
+    tenant: Option<Extension<Arc<TenantConfig>>>,
+) -> impl IntoResponse {
+    let strict = state.openai_compat_strict;
+    match model_manager(&state, tenant.as_deref()).list_models().await {
         Ok(models) => {
-            let model_objects: Vec<ModelObject> = models
-                .into_iter()
-                .map(|model| ModelObject {
-                    id: model.name.clone(),
-                    object: "model".to_string(),
-                    created: model.modified.timestamp(),
-                    owned_by: "inferno".to_string(),
-                    permission: vec![],
-                    root: model.name,
-                    parent: None,
-                })
-                .collect();
-
             let response = ModelListResponse {
                 object: "list".to_string(),
-                data: model_objects,
+                data: models.into_iter().map(ModelObject::from).collect(),
             };
 
             Json(response).into_response()
         }
-        Err(e) => (
+        Err(e) => api_error(
+            strict,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to list models: {}", e),
+            "internal_error",
+            None,
+        ),
+    }
+}
+
+pub async fn retrieve_model(
+    State(state): State<Arc<ServerState>>,
+    tenant: Option<Extension<Arc<TenantConfig>>>,
+    Path(model_id): Path<String>,
+) -> impl IntoResponse {
+    let strict = state.openai_compat_strict;
+    match model_manager(&state, tenant.as_deref()).list_models().await {
+        Ok(models) => match models.into_iter().find(|model| model.name == model_id) {
+            Some(model) => Json(ModelObject::from(model)).into_response(),
+            None => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new(
+                    format!("The model '{}' does not exist", model_id),
+                    "invalid_request_error",
+                    Some("model"),
+                    Some("model_not_found"),
+                )),
+            )
+                .into_response(),
+        },
+        Err(e) => api_error(
+            strict,
             StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to list models: {}", e),
+            "internal_error",
+            None,
+        ),
+    }
+}
+
+// Helper functions
+
+/// Queue priority for a request
+///
+/// A configured API key tier wins; the `x-priority` header can lower it but never
+/// raise it. Once any tiers are configured, keys without one are capped at normal
+/// priority, so only trusted deployments let the header alone pick a level.
+/// Without either the request is normal priority; a header naming no priority
+/// level is an error.
+pub fn request_priority(
+    headers: &HeaderMap,
+    tiers: &HashMap<String, Priority>,
+) -> Result<Priority, String> {
+    let requested = match headers.get("x-priority") {
+        Some(value) => Some(
+            value
+                .to_str()
+                .map_err(|_| "x-priority must be vip, high, normal or low".to_string())?
+                .parse::<Priority>()
+                .map_err(|e| format!("{}; x-priority must be vip, high, normal or low", e))?,
+        ),
+        None => None,
+    };
+    let tier = api_key(headers).and_then(|key| tiers.get(key)).copied();
+    let cap = tier.or((!tiers.is_empty()).then_some(Priority::Normal));
+
+    Ok(match (cap, requested) {
+        (Some(cap), Some(requested)) if (requested as u8) < (cap as u8) => requested,
+        (Some(cap), _) => cap,
+        (None, requested) => requested.unwrap_or(Priority::Normal),
+    })
+}
+
+/// API key of a request's `Authorization: Bearer` header
+fn api_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Queue the request behind the fair scheduler and wait for a worker slot
+async fn wait_for_dispatch(
+    state: &Arc<ServerState>,
+    headers: &HeaderMap,
+    model: &str,
+    estimated_tokens: u32,
+) -> Result<(DispatchPermit, QueuePlacement), Response> {
+    let strict = state.openai_compat_strict;
+    let priority =
+        request_priority(headers, &state.config.server.api_key_tiers).map_err(|message| {
+            api_error(
+                strict,
+                StatusCode::BAD_REQUEST,
+                message,
+                "invalid_request_error",
+                Some("x-priority"),
+            )
+        })?;
+    let metadata = RequestMetadata::new(
+        queue_request_id(headers),
+        "api".to_string(),
+        priority,
+        model.to_string(),
+    )
+    .with_estimated_tokens(estimated_tokens);
+
+    let ticket = state.dispatcher.submit(metadata);
+    let placement = ticket.placement();
+    match ticket.dispatched().await {
+        Ok(permit) => Ok((permit, placement)),
+        Err(e) => Err(api_error(
+            strict,
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("Request could not be scheduled: {}", e),
+            "server_error",
+            None,
+        )),
+    }
+}
+
+/// Id a request waits in the queue under: the caller's `x-request-id`, so they
+/// can follow it at `/v1/queue/{id}`
+///
+/// The dispatcher re-ids a request whose id is already waiting.
+fn queue_request_id(headers: &HeaderMap) -> String {
+    headers
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Current queue position and estimated wait of a waiting request
+pub async fn queue_position(
+    State(state): State<Arc<ServerState>>,
+    Path(request_id): Path<String>,
+) -> Response {
+    match state.dispatcher.placement(&request_id) {
+        Some(placement) => Json(serde_json::json!({
+            "request_id": request_id,
+            "position": placement.position,
+            "estimated_wait_ms": placement.estimated_wait_ms,
+        }))
+        .into_response(),
+        None => api_error(
+            state.openai_compat_strict,
+            StatusCode::NOT_FOUND,
+            format!("No request '{}' is waiting in the queue", request_id),
+            "invalid_request_error",
+            None,
+        ),
+    }
+}
+
+/// The post-processor a request names, or the server's default
+fn post_processor(state: &ServerState, requested: Option<&str>) -> Result<PostProcessor, Response> {
+    let name = requested.or(state.config.server.post_processor.as_deref());
+    PostProcessor::from_option(name).map_err(|e| {
+        api_error(
+            state.openai_compat_strict,
+            StatusCode::BAD_REQUEST,
+            e.to_string(),
+            "invalid_request_error",
+            Some("post_process"),
+        )
+    })
+}
+
+/// Error response in the shape the server's compatibility mode calls for
+///
+/// Strict mode uses the documented OpenAI error types and field order.
+pub(crate) fn api_error(
+    strict: bool,
+    status: StatusCode,
+    message: String,
+    error_type: &str,
+    param: Option<&str>,
+) -> Response {
+    if strict {
+        (
+            status,
+            Json(ErrorResponse::for_status(status.as_u16(), message, param)),
+        )
+            .into_response()
+    } else {
+        (
+            status,
             Json(serde_json::json!({
                 "error": {
-                    "message": format!("Failed to list models: {}", e),
-                    "type": "internal_error",
-                    "param": null,
+                    "message": message,
+                    "type": error_type,
+                    "param": param,
                     "code": null
                 }
             })),
         )
-            .into_response(),
+            .into_response()
     }
 }
 
-// Helper functions
+/// Error event sent when a stream fails before it starts
+fn stream_error(strict: bool, message: String) -> String {
+    serde_json::to_string(&ErrorResponse {
+        error: stream_error_detail(strict, message),
+    })
+    .unwrap_or_default()
+}
 
-async fn get_or_load_backend(
-    state: &Arc<ServerState>,
-    model_name: &str,
-) -> anyhow::Result<BackendHandle> {
-    // If distributed inference is available, we don't need a direct backend
-    // This function should not be called when using distributed inference
-    if state.distributed.is_some() {
-        return Err(anyhow::anyhow!(
-            "Cannot load direct backend when using distributed inference"
-        ));
+/// Error response for a failed backend call
+///
+/// Errors the backend classified as an [`InfernoError`] get the status and error
+/// code documented for that variant; anything else is a 500.
+fn backend_error(strict: bool, context: &str, error: anyhow::Error) -> Response {
+    match classified_error(context, &error) {
+        Some((status, body)) => (status, Json(body)).into_response(),
+        None => api_error(
+            strict,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("{}: {}", context, error),
+            "internal_error",
+            None,
+        ),
     }
+}
 
-    // Check if we have a loaded backend and if it matches the requested model
-    if let Some(ref loaded_model) = state.loaded_model {
-        if loaded_model == model_name {
-            if let Some(ref backend) = state.backend {
-                return Ok(backend.clone());
-            }
-        }
+/// Error event for a stream that failed before its first token
+fn stream_start_error(strict: bool, error: anyhow::Error) -> String {
+    match classified_error("Stream failed", &error) {
+        Some((_, body)) => serde_json::to_string(&body).unwrap_or_default(),
+        None => stream_error(strict, format!("Stream failed: {}", error)),
     }
+}
 
-    // For now, if the model doesn't match, we load a new one
-    // In a more sophisticated implementation, we'd cache multiple backends
-    let model_info = state.model_manager.resolve_model(model_name).await?;
-    let backend_type = BackendType::from_model_path(&model_info.path).ok_or_else(|| {
-        anyhow::anyhow!(
-            "No suitable backend found for model: {}",
-            model_info.path.display()
-        )
-    })?;
-    let backend_handle = BackendHandle::new_shared(backend_type, &state.config.backend_config)?;
-    backend_handle.load_model(&model_info).await?;
+/// Status and body for an error that is an [`InfernoError`], with `context`
+/// prefixed to the message
+fn classified_error(context: &str, error: &anyhow::Error) -> Option<(StatusCode, ErrorResponse)> {
+    // Coalesced requests see the backend's error as the source of a shared one
+    let error = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<InfernoError>())?;
+    let (status, _) = ComplianceValidator::map_status_code(error);
+    let mut body = ErrorResponse::from_inferno_error(error);
+    body.error.message = format!("{}: {}", context, body.error.message);
+    Some((
+        StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        body,
+    ))
+}
 
-    Ok(backend_handle)
+/// `finish_reason` of a stream that ended normally or with `failure`
+pub(crate) fn finish_reason(failure: &Option<String>) -> &'static str {
+    if failure.is_some() { "error" } else { "stop" }
 }
 
-fn format_chat_messages(messages: &[ChatMessage]) -> String {
-    messages
-        .iter()
-        .map(|msg| format!("{}: {}", msg.role, msg.content))
-        .collect::<Vec<_>>()
-        .join("\n")
+/// Detail of a failure after a stream has started
+pub(crate) fn stream_error_detail(strict: bool, message: String) -> OpenAIError {
+    let r#type = if strict {
+        ErrorResponse::error_type_for_status(500)
+    } else {
+        "internal_error"
+    };
+    OpenAIError {
+        message,
+        r#type: r#type.to_string(),
+        param: None,
+        code: None,
+    }
 }
 
-fn estimate_tokens(text: &str) -> u32 {
-    (text.len() as f32 / 4.0).ceil() as u32
+/// Report where the request was queued on submission
+fn with_queue_headers(mut response: Response, placement: QueuePlacement) -> Response {
+    let headers = response.headers_mut();
+    headers.insert("x-queue-position", HeaderValue::from(placement.position));
+    headers.insert(
+        "x-queue-estimated-wait-ms",
+        HeaderValue::from(placement.estimated_wait_ms),
+    );
+    response
 }
 
-async fn handle_non_streaming_chat(
-    request: &ChatCompletionRequest,
-    backend: BackendHandle,
+/// Resolve a fallback chain to the model that will serve the request
+fn select_model(state: &Arc<ServerState>, model: &str) -> Result<ModelSelection, Response> {
+    state.model_fallbacks.resolve(model).map_err(|e| {
+        api_error(
+            state.openai_compat_strict,
+            StatusCode::SERVICE_UNAVAILABLE,
+            e.to_string(),
+            "server_error",
+            Some("model"),
+        )
+    })
+}
+
+/// Backend for a request, with the circuit breaker of the model if it has one
+/// and the post-processor for its non-streaming output
+///
+/// Dropping it cancels its inference, so generation stops once the client
+/// disconnects and axum drops the handler or response stream that owns it. A
+/// resumable stream owns it instead, and drops it once no client has followed
+/// the stream for `stream_resume_ttl_secs`.
+struct ServingBackend {
+    backend: BackendHandle,
+    breaker: Option<Arc<CircuitBreaker>>,
+    cancel: CancellationToken,
+    post_processor: PostProcessor,
+    metrics: Option<MetricsCollector>,
+    usage: Option<(Arc<UsageLedger>, String)>,
+    /// Tenant and API key digest the request is served for
+    requester: String,
+}
+
+impl ServingBackend {
+    fn new(backend: BackendHandle, breaker: Option<Arc<CircuitBreaker>>) -> Self {
+        Self {
+            backend,
+            breaker,
+            cancel: CancellationToken::new(),
+            post_processor: PostProcessor::none(),
+            metrics: None,
+            usage: None,
+            requester: String::new(),
+        }
+    }
+
+    /// Serve the request for `requester`, so its output is only ever shared with,
+    /// or resumed by, requests from the same tenant and key
+    fn with_requester(mut self, requester: String) -> Self {
+        self.requester = requester;
+        self
+    }
+
+    fn with_post_processor(mut self, post_processor: PostProcessor) -> Self {
+        self.post_processor = post_processor;
+        self
+    }
+
+    /// Record each request served in `metrics`
+    fn with_metrics(mut self, metrics: MetricsCollector) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Count the tokens of each request in `ledger` under the API key it was
+    /// authenticated with; requests no key file vouched for are not counted
+    fn with_usage(
+        mut self,
+        ledger: Option<Arc<UsageLedger>>,
+        authenticated: Option<Extension<AuthenticatedKey>>,
+    ) -> Self {
+        self.usage = ledger.zip(authenticated.map(|Extension(AuthenticatedKey(key))| key));
+        self
+    }
+
+    async fn recorder(&self, model: &str, prompt: &str) -> Option<InferenceRecorder> {
+        if self.metrics.is_none() && self.usage.is_none() {
+            return None;
+        }
+        Some(InferenceRecorder {
+            metrics: self.metrics.clone(),
+            usage: self.usage.clone(),
+            model: model.to_string(),
+            started: std::time::Instant::now(),
+            input_length: self.count_tokens(prompt).await,
+            output_length: 0,
+            success: false,
+        })
+    }
+
+    async fn generate(&self, prompt: &str, params: &InferenceParams) -> anyhow::Result<Generation> {
+        match &self.breaker {
+            Some(breaker) => {
+                ProtectedBackend::new(self.backend.clone(), breaker.clone())
+                    .generate(prompt, params, &self.cancel)
+                    .await
+            }
+            None => self.backend.generate(prompt, params, &self.cancel).await,
+        }
+    }
+
+    /// Stream tokens for `prompt`, recording the request once the stream ends or
+    /// is dropped
+    async fn infer_stream(
+        &self,
+        model: &str,
+        prompt: &str,
+        params: &InferenceParams,
+    ) -> anyhow::Result<TokenStream> {
+        let recorder = self.recorder(model, prompt).await;
+        let stream = self.infer_stream_unrecorded(prompt, params).await?;
+        let Some(recorder) = recorder else {
+            return Ok(stream);
+        };
+        let state = (stream, recorder, false);
+        Ok(Box::pin(futures::stream::unfold(
+            state,
+            |(mut stream, mut recorder, mut failed)| async move {
+                let token = futures::StreamExt::next(&mut stream).await;
+                match &token {
+                    Some(Ok(_)) => recorder.output_length += 1,
+                    Some(Err(_)) => failed = true,
+                    None => recorder.success = !failed,
+                }
+                token.map(|token| (token, (stream, recorder, failed)))
+            },
+        )))
+    }
+
+    async fn infer_stream_unrecorded(
+        &self,
+        prompt: &str,
+        params: &InferenceParams,
+    ) -> anyhow::Result<TokenStream> {
+        match &self.breaker {
+            Some(breaker) => {
+                ProtectedBackend::new(self.backend.clone(), breaker.clone())
+                    .infer_stream_cancellable(prompt, params, &self.cancel)
+                    .await
+            }
+            None => {
+                self.backend
+                    .infer_stream_cancellable(prompt, params, &self.cancel)
+                    .await
+            }
+        }
+    }
+
+    /// Share the output with identical requests in flight when `coalescer` is set
+    ///
+    /// Only deterministic requests are coalesced; sampled ones without a seed
+    /// each expect output of their own.
+    async fn generate_coalesced(
+        &self,
+        coalescer: Option<&RequestCoalescer<Generation>>,
+        model: &str,
+        prompt: &str,
+        params: &InferenceParams,
+    ) -> anyhow::Result<Generation> {
+        let mut recorder = self.recorder(model, prompt).await;
+        let result = match coalescer {
+            Some(coalescer) if params.is_deterministic() => {
+                let key = RequestCoalescer::<Generation>::key(
+                    self.backend.id(),
+                    &self.requester,
+                    prompt,
+                    params,
+                );
+                coalescer.run(key, || self.generate(prompt, params)).await
+            }
+            _ => self.generate(prompt, params).await,
+        };
+        if let (Some(recorder), Ok(generation)) = (&mut recorder, &result) {
+            recorder.output_length = self.count_tokens(&generation.text).await;
+            recorder.success = true;
+        }
+        result
+    }
+
+    async fn count_tokens(&self, text: &str) -> u32 {
+        self.backend.count_tokens(text).await
+    }
+}
+
+impl Drop for ServingBackend {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+/// Tenant and API key digest a request is served for
+fn requester(
+    tenant: Option<&Arc<TenantConfig>>,
+    authenticated: Option<&AuthenticatedKey>,
+) -> String {
+    format!(
+        "{}/{}",
+        tenant.map_or("", |tenant| tenant.id.as_str()),
+        authenticated.map_or_else(String::new, |AuthenticatedKey(key)| ApiKeys::digest(key))
+    )
+}
+
+/// Records one request in the server's metrics and usage ledger when dropped,
+/// so requests abandoned part way are counted as failed
+struct InferenceRecorder {
+    metrics: Option<MetricsCollector>,
+    usage: Option<(Arc<UsageLedger>, String)>,
+    model: String,
+    started: std::time::Instant,
+    input_length: u32,
+    output_length: u32,
+    success: bool,
+}
+
+impl Drop for InferenceRecorder {
+    fn drop(&mut self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_inference(InferenceEvent {
+                model_name: std::mem::take(&mut self.model),
+                input_length: self.input_length,
+                output_length: self.output_length,
+                duration: self.started.elapsed(),
+                success: self.success,
+            });
+        }
+        if let Some((ledger, api_key)) = &self.usage {
+            ledger.record(api_key, self.input_length, self.output_length);
+        }
+    }
+}
+
+fn coalescer(state: &ServerState) -> Option<&RequestCoalescer<Generation>> {
+    state
+        .config
+        .server
+        .coalesce_requests
+        .then_some(&state.coalescer)
+}
+
+async fn get_or_load_backend(
+    state: &Arc<ServerState>,
+    tenant: Option<&Arc<TenantConfig>>,
+    model_name: &str,
+) -> anyhow::Result<BackendHandle> {
+    let models = model_manager(state, tenant);
+    // If distributed inference is available, we don't need a direct backend
+    // This function should not be called when using distributed inference
+    if state.distributed.is_some() {
+        return Err(anyhow::anyhow!(
+            "Cannot load direct backend when using distributed inference"
+        ));
+    }
+
+    // Check if we have a loaded backend and if it matches the requested model
+    if let Some(ref loaded_model) = state.loaded_model {
+        let aliased_file = state
+            .model_manager
+            .aliases()
+            .get(model_name)
+            .map(|target| target.file());
+        if loaded_model == model_name || aliased_file == Some(loaded_model.as_str()) {
+            if let Some(ref backend) = state.backend {
+                if let Some(info) = backend.get_model_info().await
+                    && !models.is_visible(&info.path)
+                {
+                    return Err(InfernoError::SecurityValidation(format!(
+                        "Model '{}' belongs to another tenant",
+                        model_name
+                    ))
+                    .into());
+                }
+                // Failed to preload, or unloaded by the model watcher after its file was deleted
+                if !backend.is_loaded().await {
+                    return Err(InfernoError::ModelNotFound(format!(
+                        "The model '{}' is not loaded: it failed to load at startup or was removed from disk",
+                        model_name
+                    ))
+                    .into());
+                }
+                return Ok(backend.clone());
+            }
+        }
+    }
+
+    // For now, if the model doesn't match, we load a new one
+    // In a more sophisticated implementation, we'd cache multiple backends
+    let model_info = models.resolve_model(model_name).await?;
+    // An alias may pin the backend; otherwise the file extension decides
+    let backend_type = BackendType::from_str(&model_info.backend_type, true)
+        .ok()
+        .or_else(|| BackendType::from_model_path(&model_info.path))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "No suitable backend found for model: {}",
+                model_info.path.display()
+            )
+        })?;
+    let backend_handle = BackendHandle::new_shared(backend_type, &state.config.backend_config)?;
+    backend_handle.load_model(&model_info).await?;
+
+    Ok(backend_handle)
+}
+
+/// Models a request may use: those of its tenant and the shared ones
+pub(crate) fn model_manager(
+    state: &ServerState,
+    tenant: Option<&Arc<TenantConfig>>,
+) -> ModelManager {
+    match tenant {
+        Some(tenant) => state.model_manager.for_tenant(&tenant.id),
+        None => state.model_manager.clone(),
+    }
+}
+
+/// Error response for a model that could not be made ready to serve a request
+fn model_error(strict: bool, error: anyhow::Error) -> Response {
+    match error.downcast_ref::<InfernoError>() {
+        Some(error @ InfernoError::ModelNotFound(_)) => (
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::from_inferno_error(error)),
+        )
+            .into_response(),
+        Some(error @ InfernoError::SecurityValidation(_)) => (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse::from_inferno_error(error)),
+        )
+            .into_response(),
+        _ => api_error(
+            strict,
+            StatusCode::BAD_REQUEST,
+            format!("Failed to load model: {}", error),
+            "invalid_request_error",
+            Some("model"),
+        ),
+    }
+}
+
+/// Chat template of the loaded model, or a built-in one guessed from the model name
+async fn chat_template(
+    state: &Arc<ServerState>,
+    backend: &BackendHandle,
+    model: &str,
+) -> Arc<ChatTemplate> {
+    let builtin = || {
+        Arc::new(ChatTemplate::builtin(BuiltinTemplate::for_model_name(
+            model,
+        )))
+    };
+    let Some(model_info) = backend.get_model_info().await else {
+        return builtin();
+    };
+    let state = state.clone();
+    tokio::task::spawn_blocking(move || state.chat_templates.get(&model_info))
+        .await
+        .unwrap_or_else(|_| builtin())
+}
+
+/// Complete a chat, answering with `tool_calls` when the model calls any of
+/// `offered_tools`
+async fn handle_non_streaming_chat(
+    request: &ChatCompletionRequest,
+    offered_tools: &[&Tool],
+    backend: ServingBackend,
+    coalescer: Option<&RequestCoalescer<Generation>>,
     prompt: String,
     params: InferenceParams,
+    mut permit: DispatchPermit,
+    strict: bool,
 ) -> impl IntoResponse {
     // BackendHandle already provides async methods, no need for explicit locking
 
-    match backend.infer(&prompt, &params).await {
-        Ok(output) => {
+    match backend
+        .generate_coalesced(coalescer, &request.model, &prompt, &params)
+        .await
+    {
+        Ok(generation) => {
+            let prompt_tokens = backend.count_tokens(&prompt).await;
+            let completion_tokens = backend.count_tokens(&generation.text).await;
+            let tool_calls = tools::parse_tool_calls(&generation.text, offered_tools);
+            let (content, finish_reason) = match &tool_calls {
+                Some(_) => (None, "tool_calls"),
+                None => (
+                    Some(backend.post_processor.apply(&generation.text)),
+                    generation.finish_reason.as_openai_str(),
+                ),
+            };
             let response = ChatCompletionResponse {
                 id: format!("chatcmpl-{}", Uuid::new_v4()),
                 object: "chat.completion".to_string(),
@@ -509,52 +1649,122 @@ async fn handle_non_streaming_chat(
                     index: 0,
                     message: ChatMessage {
                         role: "assistant".to_string(),
-                        content: output.clone(),
+                        content,
                         name: None,
+                        tool_calls,
+                        tool_call_id: None,
                     },
-                    finish_reason: "stop".to_string(),
+                    logprobs: generation.logprobs.as_deref().map(chat_logprobs),
+                    finish_reason: finish_reason.to_string(),
                 }],
                 usage: Usage {
-                    prompt_tokens: estimate_tokens(&prompt),
-                    completion_tokens: estimate_tokens(&output),
-                    total_tokens: estimate_tokens(&prompt) + estimate_tokens(&output),
+                    prompt_tokens,
+                    completion_tokens,
+                    total_tokens: prompt_tokens + completion_tokens,
                 },
+                seed: Some(generation.seed),
             };
 
             Json(response).into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "error": {
-                    "message": format!("Inference failed: {}", e),
-                    "type": "internal_error",
-                    "param": null,
-                    "code": null
-                }
-            })),
-        )
-            .into_response(),
+        Err(e) => {
+            permit.mark_failed();
+            backend_error(strict, "Inference failed", e)
+        }
+    }
+}
+
+/// Chat `logprobs`: one entry per output token with its alternatives
+fn chat_logprobs(tokens: &[TokenLogprob]) -> serde_json::Value {
+    let entry = |token: &str, logprob: f32| {
+        serde_json::json!({
+            "token": token,
+            "logprob": logprob,
+            "bytes": token.as_bytes(),
+        })
+    };
+    let content: Vec<serde_json::Value> = tokens
+        .iter()
+        .map(|token| {
+            let mut value = entry(&token.token, token.logprob);
+            value["top_logprobs"] = token
+                .top_logprobs
+                .iter()
+                .map(|top| entry(&top.token, top.logprob))
+                .collect();
+            value
+        })
+        .collect();
+    serde_json::json!({ "content": content })
+}
+
+/// Legacy completions `logprobs`: parallel arrays over the output tokens
+fn completion_logprobs(tokens: &[TokenLogprob]) -> serde_json::Value {
+    let mut offset = 0;
+    let text_offset: Vec<usize> = tokens
+        .iter()
+        .map(|token| {
+            let start = offset;
+            offset += token.token.chars().count();
+            start
+        })
+        .collect();
+    let top_logprobs: Vec<serde_json::Map<String, serde_json::Value>> = tokens
+        .iter()
+        .map(|token| {
+            token
+                .top_logprobs
+                .iter()
+                .map(|top| (top.token.clone(), top.logprob.into()))
+                .collect()
+        })
+        .collect();
+    serde_json::json!({
+        "tokens": tokens.iter().map(|token| &token.token).collect::<Vec<_>>(),
+        "token_logprobs": tokens.iter().map(|token| token.logprob).collect::<Vec<_>>(),
+        "top_logprobs": top_logprobs,
+        "text_offset": text_offset,
+    })
+}
+
+/// Keep-alive, timeout and token batching settings for a streamed response
+fn streaming_config(state: &ServerState) -> StreamingOptimizationConfig {
+    let server = &state.config.server;
+    StreamingOptimizationConfig {
+        batch_size: server.stream_batch_tokens,
+        batch_max_wait_ms: server.stream_batch_max_wait_ms,
+        inference_timeout_secs: server.request_timeout_seconds,
+        token_timeout_secs: server.stream_token_timeout_secs,
+        keepalive_interval_secs: server.sse_keepalive_interval_secs,
+        ..Default::default()
     }
 }
 
 async fn handle_streaming_chat(
     request: &ChatCompletionRequest,
-    backend: BackendHandle,
+    backend: ServingBackend,
     prompt: String,
     params: InferenceParams,
-) -> impl IntoResponse {
-    use axum::response::sse::{Event, Sse};
+    permit: DispatchPermit,
+    strict: bool,
+    streaming: StreamingOptimizationConfig,
+    sessions: &StreamSessions,
+) -> Response {
     use futures::stream::StreamExt;
 
     let model = request.model.clone();
     let request_id = format!("chatcmpl-{}", Uuid::new_v4());
+    let owner = backend.requester.clone();
+    let params = params.with_effective_seed();
+    let keepalive_interval = std::time::Duration::from_secs(streaming.keepalive_interval_secs);
 
     let stream = async_stream::stream! {
+        // The worker slot stays taken until the client has the whole response
+        let mut permit = permit;
         // BackendHandle already provides async methods, no need for explicit locking
 
-        match backend.infer_stream(&prompt, &params).await {
-            Ok(mut token_stream) => {
+        match backend.infer_stream(&model, &prompt, &params).await {
+            Ok(token_stream) => {
                 // Send initial chunk with role
                 let initial_chunk = ChatCompletionChunk {
                     id: request_id.clone(),
@@ -567,16 +1777,32 @@ async fn handle_streaming_chat(
                             role: Some("assistant".to_string()),
                             content: None,
                         },
+                        logprobs: None,
                         finish_reason: None,
                     }],
+                    seed: params.seed,
+                    error: None,
                 };
 
-                yield Ok::<axum::response::sse::Event, axum::Error>(Event::default().data(serde_json::to_string(&initial_chunk).unwrap()));
-
-                // Stream tokens
-                while let Some(token_result) = token_stream.next().await {
-                    match token_result {
-                        Ok(token) => {
+                yield StreamItem::Data(serde_json::to_string(&initial_chunk).unwrap());
+
+                // Stream tokens, with keep-alive comments while generation is slow
+                let batched = Box::pin(batch_token_stream(
+                    token_stream,
+                    TokenBatcher::new(streaming.batch_size, streaming.batch_max_wait_ms),
+                ));
+                let mut tokens = std::pin::pin!(watch_token_stream(
+                    batched,
+                    KeepAlive::new(streaming.keepalive_interval_secs),
+                    TimeoutManager::new(
+                        streaming.inference_timeout_secs,
+                        streaming.token_timeout_secs,
+                    ),
+                ));
+                let mut failure = None;
+                while let Some(watched) = tokens.next().await {
+                    match watched {
+                        WatchedToken::Token(Ok(token)) => {
                             let chunk = ChatCompletionChunk {
                                 id: request_id.clone(),
                                 object: "chat.completion.chunk".to_string(),
@@ -588,20 +1814,34 @@ async fn handle_streaming_chat(
                                         role: None,
                                         content: Some(token),
                                     },
+                                    logprobs: None,
                                     finish_reason: None,
                                 }],
+                                seed: None,
+                                error: None,
                             };
 
-                            yield Ok(Event::default().data(serde_json::to_string(&chunk).unwrap()));
+                            yield StreamItem::Data(serde_json::to_string(&chunk).unwrap());
                         }
-                        Err(e) => {
+                        WatchedToken::Token(Err(e)) => {
                             tracing::error!("Stream error: {}", e);
+                            permit.mark_failed();
+                            failure = Some(format!("Stream failed: {}", e));
+                            break;
+                        }
+                        WatchedToken::KeepAlive(_) => {
+                            yield StreamItem::KeepAlive;
+                        }
+                        WatchedToken::TimedOut(timeout) => {
+                            tracing::warn!("Aborting stream {}: {}", request_id, timeout);
+                            permit.mark_failed();
+                            failure = Some(timeout.to_string());
                             break;
                         }
                     }
                 }
 
-                // Send final chunk
+                // A failed stream still finishes, so clients keep the content so far
                 let final_chunk = ChatCompletionChunk {
                     id: request_id.clone(),
                     object: "chat.completion.chunk".to_string(),
@@ -613,95 +1853,192 @@ async fn handle_streaming_chat(
                             role: None,
                             content: None,
                         },
-                        finish_reason: Some("stop".to_string()),
+                        logprobs: None,
+                        finish_reason: Some(finish_reason(&failure).to_string()),
                     }],
+                    seed: None,
+                    error: failure.map(|message| stream_error_detail(strict, message)),
                 };
 
-                yield Ok(Event::default().data(serde_json::to_string(&final_chunk).unwrap()));
-                yield Ok(Event::default().data("[DONE]"));
+                yield StreamItem::Data(serde_json::to_string(&final_chunk).unwrap());
+                yield StreamItem::Data("[DONE]".to_string());
             }
             Err(e) => {
-                let error_msg = serde_json::json!({
-                    "error": {
-                        "message": format!("Stream failed: {}", e),
-                        "type": "internal_error"
+                permit.mark_failed();
+                yield StreamItem::Data(stream_start_error(strict, e));
+            }
+        }
+    };
+
+    // Keep-alives also cover the wait before the backend starts producing tokens
+    sse_response(stream, &request_id, &owner, sessions, keepalive_interval)
+}
+
+/// Stream bare token text for `stream_format: "raw"`
+///
+/// Every event's data is one batch of tokens, with carriage returns turned into
+/// newlines since SSE cannot carry them. The stream ends with `[DONE]`, preceded
+/// by an `error` event with the message if generation fails.
+async fn handle_raw_stream(
+    model: String,
+    backend: ServingBackend,
+    prompt: String,
+    params: InferenceParams,
+    permit: DispatchPermit,
+    streaming: StreamingOptimizationConfig,
+) -> Response {
+    use axum::response::sse::{Event, Sse};
+    use futures::stream::StreamExt;
+
+    let keepalive_interval = std::time::Duration::from_secs(streaming.keepalive_interval_secs);
+
+    let stream = async_stream::stream! {
+        // The worker slot stays taken until the client has the whole response
+        let mut permit = permit;
+
+        match backend.infer_stream(&model, &prompt, &params).await {
+            Ok(token_stream) => {
+                let batched = Box::pin(batch_token_stream(
+                    token_stream,
+                    TokenBatcher::new(streaming.batch_size, streaming.batch_max_wait_ms),
+                ));
+                let mut tokens = std::pin::pin!(watch_token_stream(
+                    batched,
+                    KeepAlive::new(streaming.keepalive_interval_secs),
+                    TimeoutManager::new(
+                        streaming.inference_timeout_secs,
+                        streaming.token_timeout_secs,
+                    ),
+                ));
+                while let Some(watched) = tokens.next().await {
+                    match watched {
+                        WatchedToken::Token(Ok(token)) => {
+                            let token = token.replace("\r\n", "\n").replace('\r', "\n");
+                            yield Ok::<Event, axum::Error>(Event::default().data(token));
+                        }
+                        WatchedToken::Token(Err(e)) => {
+                            tracing::error!("Stream error: {}", e);
+                            permit.mark_failed();
+                            yield Ok(Event::default().event("error").data(format!("Stream failed: {}", e)));
+                            break;
+                        }
+                        WatchedToken::KeepAlive(_) => {
+                            yield Ok(Event::default().comment("keep-alive"));
+                        }
+                        WatchedToken::TimedOut(timeout) => {
+                            permit.mark_failed();
+                            yield Ok(Event::default().event("error").data(timeout.to_string()));
+                            break;
+                        }
                     }
-                });
-                yield Ok(Event::default().data(serde_json::to_string(&error_msg).unwrap()));
+                }
+            }
+            Err(e) => {
+                permit.mark_failed();
+                yield Ok(Event::default().event("error").data(format!("Stream failed: {}", e)));
             }
         }
+        yield Ok(Event::default().data("[DONE]"));
     };
 
     Sse::new(stream)
-        .keep_alive(axum::response::sse::KeepAlive::default())
+        .keep_alive(axum::response::sse::KeepAlive::new().interval(keepalive_interval))
         .into_response()
 }
 
 async fn handle_non_streaming_completion(
     request: &CompletionRequest,
-    backend: BackendHandle,
+    backend: ServingBackend,
+    coalescer: Option<&RequestCoalescer<Generation>>,
     prompt: String,
     params: InferenceParams,
+    mut permit: DispatchPermit,
+    strict: bool,
 ) -> impl IntoResponse {
     // BackendHandle already provides async methods, no need for explicit locking
 
-    match backend.infer(&prompt, &params).await {
-        Ok(output) => {
+    match backend
+        .generate_coalesced(coalescer, &request.model, &prompt, &params)
+        .await
+    {
+        Ok(generation) => {
+            let prompt_tokens = backend.count_tokens(&prompt).await;
+            let completion_tokens = backend.count_tokens(&generation.text).await;
             let response = CompletionResponse {
                 id: format!("cmpl-{}", Uuid::new_v4()),
                 object: "text_completion".to_string(),
                 created: chrono::Utc::now().timestamp(),
                 model: request.model.clone(),
                 choices: vec![CompletionChoice {
-                    text: output.clone(),
+                    text: backend.post_processor.apply(&generation.text),
                     index: 0,
-                    logprobs: None,
-                    finish_reason: "stop".to_string(),
+                    logprobs: generation.logprobs.as_deref().map(completion_logprobs),
+                    finish_reason: generation.finish_reason.as_openai_str().to_string(),
                 }],
                 usage: Usage {
-                    prompt_tokens: estimate_tokens(&prompt),
-                    completion_tokens: estimate_tokens(&output),
-                    total_tokens: estimate_tokens(&prompt) + estimate_tokens(&output),
+                    prompt_tokens,
+                    completion_tokens,
+                    total_tokens: prompt_tokens + completion_tokens,
                 },
+                seed: Some(generation.seed),
+                error: None,
             };
 
             Json(response).into_response()
         }
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "error": {
-                    "message": format!("Inference failed: {}", e),
-                    "type": "internal_error",
-                    "param": null,
-                    "code": null
-                }
-            })),
-        )
-            .into_response(),
+        Err(e) => {
+            permit.mark_failed();
+            backend_error(strict, "Inference failed", e)
+        }
     }
 }
 
 async fn handle_streaming_completion(
     request: &CompletionRequest,
-    backend: BackendHandle,
+    backend: ServingBackend,
     prompt: String,
     params: InferenceParams,
-) -> impl IntoResponse {
-    use axum::response::sse::{Event, Sse};
+    permit: DispatchPermit,
+    strict: bool,
+    streaming: StreamingOptimizationConfig,
+    sessions: &StreamSessions,
+) -> Response {
     use futures::stream::StreamExt;
 
     let model = request.model.clone();
     let request_id = format!("cmpl-{}", Uuid::new_v4());
+    let owner = backend.requester.clone();
+    let params = params.with_effective_seed();
+    let keepalive_interval = std::time::Duration::from_secs(streaming.keepalive_interval_secs);
 
     let stream = async_stream::stream! {
+        // The worker slot stays taken until the client has the whole response
+        let mut permit = permit;
         // BackendHandle already provides async methods, no need for explicit locking
 
-        match backend.infer_stream(&prompt, &params).await {
-            Ok(mut token_stream) => {
+        match backend.infer_stream(&model, &prompt, &params).await {
+            Ok(token_stream) => {
+                // Usage is the running total of tokens the backend has sent, counted
+                // before batching joins several into one chunk
+                let generated = Arc::new(std::sync::atomic::AtomicU32::new(0));
+                let token_stream = token_stream.inspect({
+                    let generated = Arc::clone(&generated);
+                    move |token| {
+                        if token.is_ok() {
+                            generated.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        }
+                    }
+                });
+                let mut token_stream = std::pin::pin!(batch_token_stream(
+                    token_stream,
+                    TokenBatcher::new(streaming.batch_size, streaming.batch_max_wait_ms),
+                ));
+                let mut failure = None;
+                let mut completion_tokens = 0;
                 while let Some(token_result) = token_stream.next().await {
                     match token_result {
                         Ok(token) => {
+                            completion_tokens = generated.load(std::sync::atomic::Ordering::Relaxed);
                             let response = CompletionResponse {
                                 id: request_id.clone(),
                                 object: "text_completion".to_string(),
@@ -715,35 +2052,1254 @@ async fn handle_streaming_completion(
                                 }],
                                 usage: Usage {
                                     prompt_tokens: 0,
-                                    completion_tokens: 1,
-                                    total_tokens: 1,
+                                    completion_tokens,
+                                    total_tokens: completion_tokens,
                                 },
+                                seed: params.seed,
+                                error: None,
                             };
 
-                            yield Ok::<axum::response::sse::Event, axum::Error>(Event::default().data(serde_json::to_string(&response).unwrap()));
+                            yield StreamItem::Data(serde_json::to_string(&response).unwrap());
                         }
                         Err(e) => {
                             tracing::error!("Stream error: {}", e);
+                            permit.mark_failed();
+                            failure = Some(format!("Stream failed: {}", e));
                             break;
                         }
                     }
                 }
 
-                yield Ok(Event::default().data("[DONE]"));
+                if let Some(message) = failure {
+                    let response = CompletionResponse {
+                        id: request_id.clone(),
+                        object: "text_completion".to_string(),
+                        created: chrono::Utc::now().timestamp(),
+                        model: model.clone(),
+                        choices: vec![CompletionChoice {
+                            text: String::new(),
+                            index: 0,
+                            logprobs: None,
+                            finish_reason: "error".to_string(),
+                        }],
+                        usage: Usage {
+                            prompt_tokens: 0,
+                            completion_tokens,
+                            total_tokens: completion_tokens,
+                        },
+                        seed: params.seed,
+                        error: Some(stream_error_detail(strict, message)),
+                    };
+                    yield StreamItem::Data(serde_json::to_string(&response).unwrap());
+                }
+                yield StreamItem::Data("[DONE]".to_string());
             }
             Err(e) => {
-                let error_msg = serde_json::json!({
-                    "error": {
-                        "message": format!("Stream failed: {}", e),
-                        "type": "internal_error"
-                    }
-                });
-                yield Ok(Event::default().data(serde_json::to_string(&error_msg).unwrap()));
+                permit.mark_failed();
+                yield StreamItem::Data(stream_start_error(strict, e));
             }
         }
     };
 
-    Sse::new(stream)
-        .keep_alive(axum::response::sse::KeepAlive::default())
+    sse_response(stream, &request_id, &owner, sessions, keepalive_interval)
+}
+
+/// Data of one event of a chat or completion stream, or a keep-alive
+enum StreamItem {
+    Data(String),
+    KeepAlive,
+}
+
+/// Send `items` as server-sent events
+///
+/// With stream resumption enabled, generation runs in its own task and events go
+/// through a [`StreamSession`] under `id`, so clients can reconnect to them.
+fn sse_response(
+    items: impl futures::Stream<Item = StreamItem> + Send + 'static,
+    id: &str,
+    owner: &str,
+    sessions: &StreamSessions,
+    keepalive_interval: std::time::Duration,
+) -> Response {
+    use axum::response::sse::{Event, KeepAlive, Sse};
+    use futures::stream::StreamExt;
+
+    if !sessions.enabled() {
+        let events = items.map(|item| {
+            Ok::<Event, axum::Error>(match item {
+                StreamItem::Data(data) => Event::default().data(data),
+                StreamItem::KeepAlive => Event::default().comment("keep-alive"),
+            })
+        });
+        return Sse::new(events)
+            .keep_alive(KeepAlive::new().interval(keepalive_interval))
+            .into_response();
+    }
+
+    let session = sessions.start(id, owner);
+    tokio::spawn(buffer_stream(items, session.clone()));
+    follow_stream(session, None, keepalive_interval)
+}
+
+/// Run `items` to the end into `session`, unless no client follows it for the
+/// TTL, in which case dropping them cancels generation
+async fn buffer_stream(
+    items: impl futures::Stream<Item = StreamItem>,
+    session: Arc<StreamSession>,
+) {
+    use futures::stream::StreamExt;
+
+    let mut items = std::pin::pin!(items);
+    while let Some(item) = items.next().await {
+        if let StreamItem::Data(data) = item {
+            session.push(data);
+        }
+        if session.expired() {
+            tracing::debug!("Abandoning stream {}: no client reconnected", session.id());
+            break;
+        }
+    }
+    session.finish();
+}
+
+/// Events of `session` after number `after` as an SSE response, each with its id
+fn follow_stream(
+    session: Arc<StreamSession>,
+    after: Option<u64>,
+    keepalive_interval: std::time::Duration,
+) -> Response {
+    use axum::response::sse::{Event, KeepAlive, Sse};
+    use futures::stream::StreamExt;
+
+    let events = session.clone().follow(after).map(move |(seq, data)| {
+        Ok::<Event, axum::Error>(Event::default().id(session.event_id(seq)).data(data))
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::new().interval(keepalive_interval))
         .into_response()
 }
+
+/// The rest of an interrupted stream of `requester`, for a streaming request
+/// carrying a `Last-Event-ID`
+fn resume_stream(state: &ServerState, headers: &HeaderMap, requester: &str) -> Option<Response> {
+    let last_event_id = headers.get("last-event-id")?;
+    let resumed = last_event_id
+        .to_str()
+        .map_err(|_| ResumeError::Invalid("Last-Event-ID must be visible ASCII".to_string()))
+        .and_then(|id| state.stream_sessions.resume(id, requester));
+    Some(match resumed {
+        Ok((session, last)) => follow_stream(
+            session,
+            Some(last),
+            std::time::Duration::from_secs(state.config.server.sse_keepalive_interval_secs),
+        ),
+        Err(error) => {
+            let status = match error {
+                ResumeError::Invalid(_) => StatusCode::BAD_REQUEST,
+                ResumeError::NotFound(_) => StatusCode::NOT_FOUND,
+            };
+            api_error(
+                state.openai_compat_strict,
+                status,
+                error.to_string(),
+                "invalid_request_error",
+                Some("last-event-id"),
+            )
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backends::mock::{MockBackend, MockState},
+        config::Config,
+        metrics::MetricsCollector,
+        models::ModelManager,
+        operations::queue::{DispatcherConfig, RequestDispatcher},
+        resilience::{CircuitBreakerConfig, CircuitState, ModelFallbacks},
+    };
+    use axum::body::{Body, to_bytes};
+    use std::time::Duration;
+
+    fn server_state(strict: bool) -> Arc<ServerState> {
+        Arc::new(ServerState {
+            openai_compat_strict: strict,
+            ..ServerState::new(Config::default(), MetricsCollector::new().0)
+        })
+    }
+
+    async fn extract_chat(
+        strict: bool,
+        body: serde_json::Value,
+    ) -> Result<ChatCompletionRequest, Response> {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/v1/chat/completions")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        OpenAIJson::<ChatCompletionRequest>::from_request(request, &server_state(strict))
+            .await
+            .map(|OpenAIJson(request)| request)
+    }
+
+    async fn error_body(response: Response) -> serde_json::Value {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_unknown_fields_rejected_only_in_strict_mode() {
+        let body = serde_json::json!({
+            "model": "llama",
+            "messages": [{"role": "user", "content": "hi"}],
+            "top_k": 5
+        });
+
+        let lenient = extract_chat(false, body.clone()).await.unwrap();
+        assert_eq!(lenient.top_k, 5);
+
+        let response = extract_chat(true, body).await.unwrap_err();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            error_body(response).await,
+            serde_json::json!({
+                "error": {
+                    "message": "Unrecognized request argument supplied: top_k",
+                    "type": "invalid_request_error",
+                    "param": "top_k",
+                    "code": null
+                }
+            })
+        );
+
+        let documented = serde_json::json!({
+            "model": "llama",
+            "messages": [{"role": "user", "content": "hi"}],
+            "temperature": 0.2,
+            "seed": 7
+        });
+        assert!(extract_chat(true, documented).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_strict_mode_accepts_newer_documented_fields() {
+        let body = serde_json::json!({
+            "model": "llama",
+            "messages": [{"role": "user", "content": "hi"}],
+            "max_completion_tokens": 64,
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {"name": "answer", "schema": {"type": "object"}}
+            },
+            "stream_options": {"include_usage": true},
+            "parallel_tool_calls": false
+        });
+
+        let request = extract_chat(true, body).await.unwrap();
+        let params = request.inference_params();
+        assert_eq!(params.max_tokens, 64);
+        assert_eq!(
+            params.response_format,
+            Some(ResponseFormat::JsonSchema(
+                serde_json::json!({"type": "object"})
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_strict_mode_reports_invalid_values() {
+        let body = serde_json::json!({
+            "model": "llama",
+            "messages": [{"role": "user", "content": "hi"}],
+            "temperature": 3.0
+        });
+        // Out of range parameters are refused in either mode
+        let response = extract_chat(false, body.clone()).await.unwrap_err();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            error_body(response).await["error"]["message"],
+            "temperature must be between 0 and 2, got 3"
+        );
+
+        let response = extract_chat(true, body).await.unwrap_err();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error = error_body(response).await;
+        assert_eq!(error["error"]["type"], "invalid_request_error");
+        assert_eq!(
+            error["error"]["message"],
+            "temperature must be between 0 and 2"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_limits_enforced_in_both_modes() {
+        let messages: Vec<_> = (0..1_025)
+            .map(|_| serde_json::json!({"role": "user", "content": "hi"}))
+            .collect();
+        let body = serde_json::json!({"model": "llama", "messages": messages});
+
+        for strict in [false, true] {
+            let response = extract_chat(strict, body.clone()).await.unwrap_err();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let error = error_body(response).await;
+            assert_eq!(error["error"]["type"], "invalid_request_error");
+            assert_eq!(error["error"]["param"], "messages");
+            assert_eq!(
+                error["error"]["message"],
+                "'messages' has 1025 messages, exceeding the server limit max_messages = 1024"
+            );
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_request_priority() {
+        let no_tiers = HashMap::new();
+        assert_eq!(
+            request_priority(&headers(&[]), &no_tiers).unwrap(),
+            Priority::Normal
+        );
+        assert_eq!(
+            request_priority(&headers(&[("x-priority", "vip")]), &no_tiers).unwrap(),
+            Priority::VIP
+        );
+        assert!(request_priority(&headers(&[("x-priority", "bogus")]), &no_tiers).is_err());
+
+        let tiers = HashMap::from([
+            ("gold-key".to_string(), Priority::High),
+            ("batch-key".to_string(), Priority::Low),
+        ]);
+        let gold = ("authorization", "Bearer gold-key");
+        assert_eq!(
+            request_priority(&headers(&[gold]), &tiers).unwrap(),
+            Priority::High
+        );
+        // The header can lower a tier but not raise it
+        assert_eq!(
+            request_priority(&headers(&[gold, ("x-priority", "low")]), &tiers).unwrap(),
+            Priority::Low
+        );
+        assert_eq!(
+            request_priority(&headers(&[gold, ("x-priority", "4")]), &tiers).unwrap(),
+            Priority::High
+        );
+        assert_eq!(
+            request_priority(
+                &headers(&[
+                    ("authorization", "Bearer batch-key"),
+                    ("x-priority", "high")
+                ]),
+                &tiers
+            )
+            .unwrap(),
+            Priority::Low
+        );
+        // Unknown keys are capped once tiers are configured
+        assert_eq!(
+            request_priority(&headers(&[("x-priority", "vip")]), &tiers).unwrap(),
+            Priority::Normal
+        );
+    }
+
+    #[tokio::test]
+    async fn test_priority_header_jumps_queue_under_load() {
+        let mut state = server_state(false);
+        Arc::get_mut(&mut state).unwrap().dispatcher = RequestDispatcher::new(DispatcherConfig {
+            workers: 1,
+            max_active_per_worker: 1,
+            ..Default::default()
+        });
+
+        // The only worker slot is busy
+        let busy = wait_for_dispatch(&state, &headers(&[]), "llama", 1)
+            .await
+            .unwrap();
+
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut waiting = Vec::new();
+        for (priority, queued) in [("normal", 1), ("high", 2)] {
+            let state = state.clone();
+            let order_tx = order_tx.clone();
+            waiting.push(tokio::spawn(async move {
+                let request_headers = headers(&[("x-priority", priority)]);
+                let (permit, _) = wait_for_dispatch(&state, &request_headers, "llama", 1)
+                    .await
+                    .unwrap();
+                order_tx.send(priority).unwrap();
+                drop(permit);
+            }));
+            while state.dispatcher.queued() < queued {
+                tokio::task::yield_now().await;
+            }
+        }
+        drop(order_tx);
+
+        drop(busy);
+        for request in waiting {
+            request.await.unwrap();
+        }
+        let mut order = Vec::new();
+        while let Some(priority) = order_rx.recv().await {
+            order.push(priority);
+        }
+        assert_eq!(order, ["high", "normal"]);
+
+        let response = wait_for_dispatch(&state, &headers(&[("x-priority", "urgent")]), "llama", 1)
+            .await
+            .unwrap_err();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error_body(response).await["error"]["param"], "x-priority");
+    }
+
+    /// Loaded backend that answers every prompt with the same text a word at a
+    /// time, taking long enough for concurrent requests to overlap
+    fn fixed(reply: &str) -> MockBackend {
+        MockBackend::reply(reply).delay(Duration::from_millis(50))
+    }
+
+    fn fixed_backend(reply: &str) -> (BackendHandle, Arc<MockState>) {
+        let backend = fixed(reply);
+        let state = backend.state();
+        (backend.handle(), state)
+    }
+
+    /// Server with `backend` loaded as `model`
+    fn serving_state(
+        model: &str,
+        backend: BackendHandle,
+        model_fallbacks: ModelFallbacks,
+        metrics: MetricsCollector,
+    ) -> Arc<ServerState> {
+        Arc::new(ServerState {
+            model_fallbacks,
+            ..ServerState::new(Config::default(), metrics).with_backend(model, backend)
+        })
+    }
+
+    async fn chat(state: &Arc<ServerState>, model: &str) -> serde_json::Value {
+        chat_with(
+            state,
+            serde_json::json!({
+                "model": model,
+                "messages": [{"role": "user", "content": "hi"}]
+            }),
+        )
+        .await
+    }
+
+    async fn chat_with(state: &Arc<ServerState>, body: serde_json::Value) -> serde_json::Value {
+        let request: ChatCompletionRequest = serde_json::from_value(body).unwrap();
+        let response = chat_completions(
+            State(state.clone()),
+            HeaderMap::new(),
+            None,
+            None,
+            OpenAIJson(request),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_open_breaker_falls_back_to_next_model_in_chain() {
+        let (metrics, _processor) = MetricsCollector::new();
+        let chains = HashMap::from([(
+            "assistant".to_string(),
+            vec!["llama-70b".to_string(), "llama-8b".to_string()],
+        )]);
+        let breaker_config = CircuitBreakerConfig {
+            failure_threshold: 1,
+            ..Default::default()
+        };
+        let model_fallbacks =
+            ModelFallbacks::new(chains, breaker_config, Some(Arc::new(metrics.clone())));
+
+        // Trip the primary model's breaker
+        let primary = model_fallbacks.breaker("llama-70b").unwrap();
+        let _ = primary
+            .call(|| async { Err::<(), _>(anyhow::anyhow!("out of memory")) })
+            .await;
+        assert_eq!(primary.get_state(), CircuitState::Open);
+
+        let (backend, mock) = fixed_backend("from the small model");
+        let state = serving_state("llama-8b", backend, model_fallbacks, metrics.clone());
+
+        let body = chat(&state, "assistant").await;
+        assert_eq!(body["model"], "llama-8b");
+        assert_eq!(
+            body["choices"][0]["message"]["content"],
+            "from the small model"
+        );
+        let counters = metrics.get_counters();
+        assert_eq!(counters.get("model_fallback.assistant.llama-8b"), Some(&1));
+        assert_eq!(counters.get("model_fallback.assistant.llama-70b"), None);
+        assert_eq!(mock.calls(), 1);
+    }
+
+    /// Server with `backend` loaded as `model` that coalesces identical requests
+    fn coalescing_state(model: &str, backend: BackendHandle) -> Arc<ServerState> {
+        let mut config = Config::default();
+        config.server.coalesce_requests = true;
+        Arc::new(ServerState::new(config, MetricsCollector::new().0).with_backend(model, backend))
+    }
+
+    /// Greedy chat request, so identical ones may be coalesced
+    async fn greedy_chat(state: &Arc<ServerState>) -> serde_json::Value {
+        chat_with(
+            state,
+            serde_json::json!({
+                "model": "llama",
+                "messages": [{"role": "user", "content": "hi"}],
+                "temperature": 0
+            }),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_identical_concurrent_requests_share_one_inference() {
+        let (backend, mock) = fixed_backend("shared answer");
+        let state = coalescing_state("llama", backend);
+
+        let bodies = futures::future::join_all((0..8).map(|_| greedy_chat(&state))).await;
+        for body in &bodies {
+            assert_eq!(body["choices"][0]["message"]["content"], "shared answer");
+        }
+        // Each caller still gets its own response id
+        assert_ne!(bodies[0]["id"], bodies[1]["id"]);
+        assert_eq!(mock.calls(), 1);
+        assert_eq!(state.coalescer.coalesced_count(), 7);
+
+        // Once finished, the same request runs inference again
+        greedy_chat(&state).await;
+        assert_eq!(mock.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sampled_requests_are_coalesced_only_with_a_seed() {
+        let (backend, mock) = fixed_backend("sampled answer");
+        let state = coalescing_state("llama", backend);
+        let sampled = |seed: Option<u64>| {
+            chat_with(
+                &state,
+                serde_json::json!({
+                    "model": "llama",
+                    "messages": [{"role": "user", "content": "hi"}],
+                    "temperature": 0.8,
+                    "seed": seed
+                }),
+            )
+        };
+
+        futures::future::join_all((0..3).map(|_| sampled(None))).await;
+        assert_eq!(mock.calls(), 3);
+        assert_eq!(state.coalescer.coalesced_count(), 0);
+
+        futures::future::join_all((0..3).map(|_| sampled(Some(42)))).await;
+        assert_eq!(mock.calls(), 4);
+        assert_eq!(state.coalescer.coalesced_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_requests_from_other_tenants_are_not_coalesced() {
+        let (backend, mock) = fixed_backend("private answer");
+        let state = coalescing_state("llama", backend);
+        let chat_as = |tenant: &str| {
+            let state = state.clone();
+            let tenant = Arc::new(TenantConfig {
+                id: tenant.to_string(),
+                api_keys: Vec::new(),
+                quota: Default::default(),
+            });
+            async move {
+                let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+                    "model": "llama",
+                    "messages": [{"role": "user", "content": "hi"}],
+                    "temperature": 0
+                }))
+                .unwrap();
+                let response = chat_completions(
+                    State(state),
+                    HeaderMap::new(),
+                    Some(Extension(tenant)),
+                    None,
+                    OpenAIJson(request),
+                )
+                .await
+                .into_response();
+                assert_eq!(response.status(), StatusCode::OK);
+            }
+        };
+
+        tokio::join!(chat_as("a"), chat_as("b"), chat_as("a"));
+        assert_eq!(mock.calls(), 2);
+        assert_eq!(state.coalescer.coalesced_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_backend_errors_use_documented_status() {
+        let cases: [(fn() -> InfernoError, StatusCode, Option<&str>); 4] = [
+            (
+                || InfernoError::Validation("prompt is empty".to_string()),
+                StatusCode::BAD_REQUEST,
+                Some("invalid_value"),
+            ),
+            (
+                || InfernoError::ModelNotFound("llama was unloaded".to_string()),
+                StatusCode::NOT_FOUND,
+                Some("model_not_found"),
+            ),
+            (
+                || InfernoError::StreamingLimit("too many streams".to_string()),
+                StatusCode::TOO_MANY_REQUESTS,
+                Some("rate_limit_exceeded"),
+            ),
+            (
+                || InfernoError::Backend("GPU device lost".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Some("backend_error"),
+            ),
+        ];
+
+        for (error, status, code) in cases {
+            let backend = fixed("unused").error(error).handle();
+            let fallbacks = ModelFallbacks::new(HashMap::new(), Default::default(), None);
+            let state = serving_state("llama", backend, fallbacks, MetricsCollector::new().0);
+
+            let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+                "model": "llama",
+                "messages": [{"role": "user", "content": "hi"}]
+            }))
+            .unwrap();
+            let response = chat_completions(
+                State(state),
+                HeaderMap::new(),
+                None,
+                None,
+                OpenAIJson(request),
+            )
+            .await
+            .into_response();
+            assert_eq!(response.status(), status);
+            let body = error_body(response).await;
+            let validation = ComplianceValidator::validate_error_body(&body);
+            assert!(validation.is_valid, "{:?}: {}", validation.errors, body);
+            assert_eq!(body["error"]["code"].as_str(), code);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_other_tenants_models_are_forbidden() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = Arc::into_inner(server_state(false)).unwrap();
+        state.model_manager = ModelManager::new(dir.path());
+        let private = state.model_manager.tenant_dir("a").join("private.gguf");
+        std::fs::create_dir_all(private.parent().unwrap()).unwrap();
+        std::fs::write(&private, b"GGUF\x03\x00\x00\x00").unwrap();
+        std::fs::write(dir.path().join("shared.gguf"), b"GGUF\x03\x00\x00\x00").unwrap();
+        let state = Arc::new(state);
+        let tenant = |id: &str| {
+            Some(Extension(Arc::new(TenantConfig {
+                id: id.to_string(),
+                api_keys: Vec::new(),
+                quota: Default::default(),
+            })))
+        };
+
+        let mut listed = Vec::new();
+        for id in ["a", "b"] {
+            let response = list_models(State(state.clone()), tenant(id))
+                .await
+                .into_response();
+            let body = error_body(response).await;
+            let mut ids: Vec<String> = body["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|model| model["id"].as_str().unwrap().to_string())
+                .collect();
+            ids.sort();
+            listed.push(ids);
+        }
+        assert_eq!(
+            listed,
+            [vec!["private.gguf", "shared.gguf"], vec!["shared.gguf"]]
+        );
+
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "tenants/a/private.gguf",
+            "messages": [{"role": "user", "content": "hi"}]
+        }))
+        .unwrap();
+        let response = chat_completions(
+            State(state),
+            HeaderMap::new(),
+            tenant("b"),
+            None,
+            OpenAIJson(request),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = error_body(response).await;
+        assert!(ComplianceValidator::validate_error_body(&body).is_valid);
+    }
+
+    #[tokio::test]
+    async fn test_post_process_applies_to_non_streaming_output() {
+        let (backend, _) =
+            fixed_backend("Sure! ```json\n{\"city\": \"Paris\"}\n``` Anything else?");
+        let fallbacks = ModelFallbacks::new(HashMap::new(), Default::default(), None);
+        let state = serving_state("llama", backend, fallbacks, MetricsCollector::new().0);
+
+        let request = |post_process: &str| -> ChatCompletionRequest {
+            serde_json::from_value(serde_json::json!({
+                "model": "llama",
+                "messages": [{"role": "user", "content": "capital of France as JSON"}],
+                "post_process": post_process
+            }))
+            .unwrap()
+        };
+
+        let response = chat_completions(
+            State(state.clone()),
+            HeaderMap::new(),
+            None,
+            None,
+            OpenAIJson(request("json")),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            body["choices"][0]["message"]["content"],
+            r#"{"city": "Paris"}"#
+        );
+
+        let response = chat_completions(
+            State(state.clone()),
+            HeaderMap::new(),
+            None,
+            None,
+            OpenAIJson(request("no-such-processor")),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error_body(response).await["error"]["param"], "post_process");
+    }
+
+    #[tokio::test]
+    async fn test_failed_stream_keeps_partial_output() {
+        let backend = fixed("The quick brown fox").fail_stream_after(2).handle();
+        let fallbacks = ModelFallbacks::new(HashMap::new(), Default::default(), None);
+        let state = serving_state("llama", backend, fallbacks, MetricsCollector::new().0);
+
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "llama",
+            "messages": [{"role": "user", "content": "hi"}],
+            "stream": true
+        }))
+        .unwrap();
+        let response = chat_completions(
+            State(state),
+            HeaderMap::new(),
+            None,
+            None,
+            OpenAIJson(request),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+
+        let events: Vec<&str> = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .collect();
+        let (done, chunks) = events.split_last().unwrap();
+        assert_eq!(*done, "[DONE]");
+        let chunks: Vec<serde_json::Value> = chunks
+            .iter()
+            .map(|chunk| serde_json::from_str(chunk).unwrap())
+            .collect();
+
+        let content: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+            .collect();
+        assert_eq!(content, "The quick ");
+        let last = chunks.last().unwrap();
+        assert_eq!(last["choices"][0]["finish_reason"], "error");
+        assert!(
+            last["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("GPU device lost"),
+            "{}",
+            last
+        );
+        // Chunks before the failure carry no error
+        assert!(
+            chunks[..chunks.len() - 1]
+                .iter()
+                .all(|chunk| chunk.get("error").is_none())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_batching_sends_fewer_events() {
+        let reply = "The quick brown fox jumps over the lazy dog";
+        let token_count = reply.split_inclusive(' ').count();
+        let backend = fixed(reply).handle();
+        let fallbacks = ModelFallbacks::new(HashMap::new(), Default::default(), None);
+        let mut state = serving_state("llama", backend, fallbacks, MetricsCollector::new().0);
+        Arc::get_mut(&mut state)
+            .unwrap()
+            .config
+            .server
+            .stream_batch_tokens = 4;
+
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "llama",
+            "messages": [{"role": "user", "content": "hi"}],
+            "stream": true
+        }))
+        .unwrap();
+        let response = chat_completions(
+            State(state),
+            HeaderMap::new(),
+            None,
+            None,
+            OpenAIJson(request),
+        )
+        .await
+        .into_response();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+
+        let contents: Vec<String> = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter(|data| *data != "[DONE]")
+            .filter_map(|data| {
+                let chunk: serde_json::Value = serde_json::from_str(data).unwrap();
+                chunk["choices"][0]["delta"]["content"]
+                    .as_str()
+                    .map(str::to_string)
+            })
+            .collect();
+        assert!(
+            contents.len() < token_count,
+            "{} events for {} tokens",
+            contents.len(),
+            token_count
+        );
+        assert_eq!(contents.concat(), reply);
+    }
+
+    #[tokio::test]
+    async fn test_response_echoes_effective_seed() {
+        let (backend, _) = fixed_backend("Hello there");
+        let fallbacks = ModelFallbacks::new(HashMap::new(), Default::default(), None);
+        let state = serving_state("llama", backend, fallbacks, MetricsCollector::new().0);
+
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "llama",
+            "messages": [{"role": "user", "content": "hi"}],
+            "temperature": 0.8,
+            "seed": 1234
+        }))
+        .unwrap();
+        let response = chat_completions(
+            State(state.clone()),
+            HeaderMap::new(),
+            None,
+            None,
+            OpenAIJson(request),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["seed"], 1234);
+
+        // Without a seed one is drawn and reported so the output can be reproduced
+        let body = chat(&state, "llama").await;
+        assert!(body["seed"].is_u64());
+    }
+
+    async fn complete(state: &Arc<ServerState>, body: serde_json::Value) -> serde_json::Value {
+        let request: CompletionRequest = serde_json::from_value(body).unwrap();
+        let response = completions(
+            State(state.clone()),
+            HeaderMap::new(),
+            None,
+            None,
+            OpenAIJson(request),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_logprobs_report_top_alternatives_per_token() {
+        // Reports logprobs for a fixed three-token reply
+        let backend = MockBackend::tokens(&["Hello", " there", "!"])
+            .logprobs(&[" Hi", ".", " you", "?", ","])
+            .handle();
+        let fallbacks = ModelFallbacks::new(HashMap::new(), Default::default(), None);
+        let state = serving_state("llama", backend, fallbacks, MetricsCollector::new().0);
+
+        let body = complete(
+            &state,
+            serde_json::json!({"model": "llama", "prompt": "Say hi", "logprobs": 5}),
+        )
+        .await;
+        let logprobs = &body["choices"][0]["logprobs"];
+        assert_eq!(
+            logprobs["tokens"],
+            serde_json::json!(["Hello", " there", "!"])
+        );
+        assert_eq!(logprobs["text_offset"], serde_json::json!([0, 5, 11]));
+        let token_logprobs = logprobs["token_logprobs"].as_array().unwrap();
+        let top_logprobs = logprobs["top_logprobs"].as_array().unwrap();
+        assert_eq!(token_logprobs.len(), 3);
+        assert_eq!(top_logprobs.len(), 3);
+        for (logprob, top) in token_logprobs.iter().zip(top_logprobs) {
+            assert!(logprob.as_f64().unwrap() <= 0.0);
+            let top = top.as_object().unwrap();
+            assert!(!top.is_empty() && top.len() <= 5);
+        }
+
+        // Backends without logits answer with null logprobs instead of failing
+        let (backend, _) = fixed_backend("no logits here");
+        let fallbacks = ModelFallbacks::new(HashMap::new(), Default::default(), None);
+        let state = serving_state("llama", backend, fallbacks, MetricsCollector::new().0);
+        let body = complete(
+            &state,
+            serde_json::json!({"model": "llama", "prompt": "Say hi", "logprobs": 5}),
+        )
+        .await;
+        assert_eq!(body["choices"][0]["text"], "no logits here");
+        assert!(body["choices"][0]["logprobs"].is_null());
+    }
+
+    async fn stream_body(state: Arc<ServerState>, stream_format: &str) -> String {
+        let request: CompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "llama",
+            "prompt": "hi",
+            "stream": true,
+            "stream_format": stream_format
+        }))
+        .unwrap();
+        let response = completions(
+            State(state),
+            HeaderMap::new(),
+            None,
+            None,
+            OpenAIJson(request),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_raw_stream_format_sends_plain_token_lines() {
+        let (backend, _) = fixed_backend("Hello there world");
+        let fallbacks = ModelFallbacks::new(HashMap::new(), Default::default(), None);
+        let state = serving_state("llama", backend, fallbacks, MetricsCollector::new().0);
+
+        let data = |body: &str| -> Vec<String> {
+            body.lines()
+                .filter_map(|line| line.strip_prefix("data: "))
+                .map(str::to_string)
+                .collect()
+        };
+
+        let raw = data(&stream_body(state.clone(), "raw").await);
+        assert_eq!(raw, ["Hello ", "there ", "world", "[DONE]"]);
+
+        let openai = data(&stream_body(state, "openai").await);
+        assert_eq!(openai.last().unwrap(), "[DONE]");
+        let texts: Vec<String> = openai[..openai.len() - 1]
+            .iter()
+            .map(|data| {
+                let chunk: serde_json::Value = serde_json::from_str(data).unwrap();
+                assert_eq!(chunk["object"], "text_completion");
+                chunk["choices"][0]["text"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(texts, ["Hello ", "there ", "world"]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_usage_is_running_total() {
+        let (backend, _) = fixed_backend("Hello there world");
+        let fallbacks = ModelFallbacks::new(HashMap::new(), Default::default(), None);
+        let state = serving_state("llama", backend, fallbacks, MetricsCollector::new().0);
+
+        let body = stream_body(state, "openai").await;
+        let mut text = String::new();
+        let chunks = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter(|data| *data != "[DONE]");
+        // The mock sends a word per token
+        for (sent, data) in (1u32..).zip(chunks) {
+            let chunk: serde_json::Value = serde_json::from_str(data).unwrap();
+            text.push_str(chunk["choices"][0]["text"].as_str().unwrap());
+            assert_eq!(chunk["usage"]["completion_tokens"], sent, "{}", chunk);
+            assert_eq!(chunk["usage"]["total_tokens"], sent, "{}", chunk);
+        }
+        assert_eq!(text, "Hello there world");
+    }
+
+    /// Id and data of each complete event in an SSE body
+    fn sse_events(body: &str) -> Vec<(Option<String>, String)> {
+        let mut blocks: Vec<&str> = body.split("\n\n").collect();
+        // Whatever follows the last blank line is incomplete
+        blocks.pop();
+        blocks
+            .into_iter()
+            .filter_map(|block| {
+                let field = |name: &str| {
+                    block
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .map(str::to_string)
+                };
+                Some((field("id: "), field("data: ")?))
+            })
+            .collect()
+    }
+
+    fn chat_content(events: &[(Option<String>, String)]) -> String {
+        events
+            .iter()
+            .filter(|(_, data)| data != "[DONE]")
+            .filter_map(|(_, data)| {
+                let chunk: serde_json::Value = serde_json::from_str(data).unwrap();
+                chunk["choices"][0]["delta"]["content"]
+                    .as_str()
+                    .map(str::to_string)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_with_last_event_id_resumes_stream() {
+        use futures::StreamExt;
+
+        let (backend, mock) = fixed_backend("one two three four five");
+        let fallbacks = ModelFallbacks::new(HashMap::new(), Default::default(), None);
+        let state = serving_state("llama", backend, fallbacks, MetricsCollector::new().0);
+        let state = Arc::new(ServerState {
+            stream_sessions: StreamSessions::new(Duration::from_secs(30), 4096),
+            ..Arc::into_inner(state).unwrap()
+        });
+        let request = || -> ChatCompletionRequest {
+            serde_json::from_value(serde_json::json!({
+                "model": "llama",
+                "messages": [{"role": "user", "content": "hi"}],
+                "stream": true
+            }))
+            .unwrap()
+        };
+
+        // Disconnect once the role chunk and the first two words have arrived
+        let response = chat_completions(
+            State(state.clone()),
+            HeaderMap::new(),
+            None,
+            None,
+            OpenAIJson(request()),
+        )
+        .await
+        .into_response();
+        let mut body = response.into_body().into_data_stream();
+        let mut received = String::new();
+        while sse_events(&received).len() < 3 {
+            let frame = body.next().await.unwrap().unwrap();
+            received.push_str(std::str::from_utf8(&frame).unwrap());
+        }
+        drop(body);
+        let before = sse_events(&received);
+        let last_event_id = before.last().unwrap().0.clone().unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("last-event-id", last_event_id.parse().unwrap());
+        let response = chat_completions(
+            State(state.clone()),
+            headers,
+            None,
+            None,
+            OpenAIJson(request()),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let after = sse_events(std::str::from_utf8(&bytes).unwrap());
+
+        // Ids carry on from the last one received, with no event sent twice
+        let number = |id: &Option<String>| -> u64 {
+            id.as_deref()
+                .unwrap()
+                .rsplit_once(':')
+                .unwrap()
+                .1
+                .parse()
+                .unwrap()
+        };
+        let last = number(&Some(last_event_id));
+        let numbers: Vec<u64> = after.iter().map(|(id, _)| number(id)).collect();
+        let expected: Vec<u64> = (last + 1..last + 1 + after.len() as u64).collect();
+        assert_eq!(numbers, expected);
+        assert_eq!(after.last().unwrap().1, "[DONE]");
+        assert_eq!(
+            format!("{}{}", chat_content(&before), chat_content(&after)),
+            "one two three four five"
+        );
+        // The model ran once, for the original request
+        assert_eq!(mock.calls(), 1);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("last-event-id", "chatcmpl-unknown:3".parse().unwrap());
+        let response = chat_completions(State(state), headers, None, None, OpenAIJson(request()))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            error_body(response).await["error"]["param"],
+            "last-event-id"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_the_key_that_started_a_stream_may_resume_it() {
+        use futures::StreamExt;
+
+        let (backend, _) = fixed_backend("one two three four five");
+        let fallbacks = ModelFallbacks::new(HashMap::new(), Default::default(), None);
+        let state = serving_state("llama", backend, fallbacks, MetricsCollector::new().0);
+        let state = Arc::new(ServerState {
+            stream_sessions: StreamSessions::new(Duration::from_secs(30), 4096),
+            ..Arc::into_inner(state).unwrap()
+        });
+        let send = |key: &str, last_event_id: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(id) = last_event_id {
+                headers.insert("last-event-id", id.parse().unwrap());
+            }
+            let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+                "model": "llama",
+                "messages": [{"role": "user", "content": "hi"}],
+                "stream": true
+            }))
+            .unwrap();
+            chat_completions(
+                State(state.clone()),
+                headers,
+                None,
+                Some(Extension(AuthenticatedKey(key.to_string()))),
+                OpenAIJson(request),
+            )
+        };
+
+        let response = send("sk-first", None).await.into_response();
+        let mut body = response.into_body().into_data_stream();
+        let mut received = String::new();
+        while sse_events(&received).is_empty() {
+            let frame = body.next().await.unwrap().unwrap();
+            received.push_str(std::str::from_utf8(&frame).unwrap());
+        }
+        drop(body);
+        let last_event_id = sse_events(&received)[0].0.clone().unwrap();
+
+        let response = send("sk-second", Some(&last_event_id))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            error_body(response).await["error"]["param"],
+            "last-event-id"
+        );
+
+        let response = send("sk-first", Some(&last_event_id)).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_reply_returns_tool_calls() {
+        let (backend, _calls) =
+            fixed_backend(r#"{"name": "get_weather", "arguments": {"city": "Paris"}}"#);
+        let fallbacks = ModelFallbacks::new(HashMap::new(), Default::default(), None);
+        let state = serving_state("llama", backend, fallbacks, MetricsCollector::new().0);
+
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "llama",
+            "messages": [{"role": "user", "content": "What's the weather in Paris?"}],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Current weather in a city",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {"city": {"type": "string"}},
+                        "required": ["city"]
+                    }
+                }
+            }]
+        }))
+        .unwrap();
+        let response = chat_completions(
+            State(state),
+            HeaderMap::new(),
+            None,
+            None,
+            OpenAIJson(request),
+        )
+        .await
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        let choice = &body["choices"][0];
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert!(choice["message"]["content"].is_null());
+        let calls = choice["message"]["tool_calls"].as_array().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0]["id"].as_str().unwrap().starts_with("call_"));
+        assert_eq!(calls[0]["type"], "function");
+        assert_eq!(calls[0]["function"]["name"], "get_weather");
+        let arguments: serde_json::Value =
+            serde_json::from_str(calls[0]["function"]["arguments"].as_str().unwrap()).unwrap();
+        assert_eq!(arguments, serde_json::json!({"city": "Paris"}));
+    }
+}
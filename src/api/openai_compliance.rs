@@ -4,6 +4,7 @@
 
 use crate::InfernoError;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 /// Error response matching OpenAI format
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,39 +22,67 @@ pub struct ErrorResponse {
 }
 
 impl ErrorResponse {
-    /// Create from Inferno error
+    pub fn new(
+        message: impl Into<String>,
+        r#type: &str,
+        param: Option<&str>,
+        code: Option<&str>,
+    ) -> Self {
+        Self {
+            error: OpenAIError {
+                message: message.into(),
+                r#type: r#type.to_string(),
+                param: param.map(str::to_string),
+                code: code.map(str::to_string),
+            },
+        }
+    }
+
+    /// Create with the documented error type for an HTTP status
+    pub fn for_status(status: u16, message: impl Into<String>, param: Option<&str>) -> Self {
+        Self::new(message, Self::error_type_for_status(status), param, None)
+    }
+
+    /// OpenAI reports every client error, including bad API keys and unknown models,
+    /// as `invalid_request_error` and everything else as `server_error`
+    pub fn error_type_for_status(status: u16) -> &'static str {
+        if (400..500).contains(&status) {
+            "invalid_request_error"
+        } else {
+            "server_error"
+        }
+    }
+
+    /// Create from Inferno error, typed to match
+    /// [`ComplianceValidator::map_status_code`]
     pub fn from_inferno_error(error: &InfernoError) -> Self {
-        let (message, code, r#type) = match error {
-            InfernoError::ModelNotFound(msg) => (
-                msg.clone(),
-                Some("model_not_found"),
-                "invalid_request_error",
-            ),
+        let (message, code, param) = match error {
+            InfernoError::ModelNotFound(msg) => {
+                (msg.clone(), Some("model_not_found"), Some("model"))
+            }
             InfernoError::Config(_) => (
                 "Invalid configuration".to_string(),
                 Some("invalid_config"),
-                "invalid_request_error",
+                None,
             ),
-            InfernoError::Backend(msg) => (msg.clone(), Some("backend_error"), "server_error"),
-            InfernoError::Timeout(_) => (
-                "Request timeout".to_string(),
-                Some("timeout"),
-                "server_error",
-            ),
-            InfernoError::Validation(msg) => {
-                (msg.clone(), Some("invalid_value"), "invalid_request_error")
+            InfernoError::Backend(msg) => (msg.clone(), Some("backend_error"), None),
+            InfernoError::Timeout(_) => ("Request timeout".to_string(), Some("timeout"), None),
+            InfernoError::Validation(msg) | InfernoError::InvalidArgument(msg) => {
+                (msg.clone(), Some("invalid_value"), None)
             }
-            _ => (error.to_string(), None, "server_error"),
+            InfernoError::Auth(msg) => (msg.clone(), Some("invalid_api_key"), None),
+            InfernoError::StreamingLimit(msg) => (msg.clone(), Some("rate_limit_exceeded"), None),
+            _ => (error.to_string(), None, None),
         };
 
-        Self {
-            error: OpenAIError {
-                message,
-                r#type: r#type.to_string(),
-                param: None,
-                code: code.map(|s| s.to_string()),
-            },
-        }
+        let (status, _) = ComplianceValidator::map_status_code(error);
+        // OpenAI types rate limit errors by the limit that was hit
+        let r#type = if status == 429 {
+            "requests"
+        } else {
+            Self::error_type_for_status(status)
+        };
+        Self::new(message, r#type, param, code)
     }
 }
 
@@ -167,13 +196,69 @@ impl ComplianceValidator {
         result
     }
 
+    /// Request fields the OpenAI API does not document for `endpoint`, as paths
+    /// such as `top_k` or `messages[0].mood`
+    pub fn unknown_fields(endpoint: OpenAIEndpoint, body: &Value) -> Vec<String> {
+        let Some(body) = body.as_object() else {
+            return Vec::new();
+        };
+
+        let mut unknown: Vec<String> = body
+            .keys()
+            .filter(|key| !endpoint.documented_fields().contains(&key.as_str()))
+            .cloned()
+            .collect();
+
+        if endpoint == OpenAIEndpoint::ChatCompletions
+            && let Some(Value::Array(messages)) = body.get("messages")
+        {
+            for (i, message) in messages.iter().enumerate() {
+                if let Some(message) = message.as_object() {
+                    unknown.extend(
+                        message
+                            .keys()
+                            .filter(|key| !CHAT_MESSAGE_FIELDS.contains(&key.as_str()))
+                            .map(|key| format!("messages[{}].{}", i, key)),
+                    );
+                }
+            }
+        }
+
+        unknown
+    }
+
+    /// Check that `body` has the OpenAI error shape: an `error` object with string
+    /// `message` and `type`, and `param` and `code` that are strings or null
+    pub fn validate_error_body(body: &Value) -> ValidationResult {
+        let Some(error) = body.get("error").and_then(Value::as_object) else {
+            return ValidationResult::invalid(vec!["error must be an object".to_string()]);
+        };
+
+        let mut result = ValidationResult::valid();
+        for field in ["message", "type"] {
+            if !error.get(field).is_some_and(Value::is_string) {
+                result = result.with_error(format!("error.{} must be a string", field));
+            }
+        }
+        for field in ["param", "code"] {
+            match error.get(field) {
+                Some(Value::String(_)) | Some(Value::Null) => {}
+                _ => {
+                    result = result.with_error(format!("error.{} must be a string or null", field))
+                }
+            }
+        }
+        result
+    }
+
     /// Map Inferno HTTP status code to OpenAI status code
     pub fn map_status_code(inferno_error: &InfernoError) -> (u16, &'static str) {
         match inferno_error {
-            InfernoError::Validation(_) => (400, "Bad Request"),
+            InfernoError::Validation(_) | InfernoError::InvalidArgument(_) => (400, "Bad Request"),
             InfernoError::Auth(_) => (401, "Unauthorized"),
             InfernoError::SecurityValidation(_) => (403, "Forbidden"),
             InfernoError::ModelNotFound(_) => (404, "Not Found"),
+            InfernoError::StreamingLimit(_) => (429, "Too Many Requests"),
             InfernoError::Timeout(_) => (504, "Gateway Timeout"),
             InfernoError::Resource(_) => (507, "Insufficient Storage"),
             _ => (500, "Internal Server Error"),
@@ -181,6 +266,71 @@ impl ComplianceValidator {
     }
 }
 
+/// Endpoints whose request bodies are checked in strict mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenAIEndpoint {
+    ChatCompletions,
+    Completions,
+    Embeddings,
+}
+
+impl OpenAIEndpoint {
+    /// Request fields documented by the OpenAI API for this endpoint
+    pub fn documented_fields(&self) -> &'static [&'static str] {
+        match self {
+            OpenAIEndpoint::ChatCompletions => &[
+                "model",
+                "messages",
+                "max_tokens",
+                "temperature",
+                "top_p",
+                "n",
+                "stream",
+                "stop",
+                "presence_penalty",
+                "frequency_penalty",
+                "logit_bias",
+                "logprobs",
+                "top_logprobs",
+                "seed",
+                "user",
+                "tools",
+                "tool_choice",
+                "parallel_tool_calls",
+                "response_format",
+                "stream_options",
+                "max_completion_tokens",
+            ],
+            OpenAIEndpoint::Completions => &[
+                "model",
+                "prompt",
+                "suffix",
+                "max_tokens",
+                "temperature",
+                "top_p",
+                "n",
+                "stream",
+                "stream_options",
+                "logprobs",
+                "echo",
+                "stop",
+                "presence_penalty",
+                "frequency_penalty",
+                "best_of",
+                "logit_bias",
+                "seed",
+                "user",
+            ],
+            OpenAIEndpoint::Embeddings => {
+                &["model", "input", "encoding_format", "dimensions", "user"]
+            }
+        }
+    }
+}
+
+/// Fields documented for each entry of a chat request's `messages`
+const CHAT_MESSAGE_FIELDS: &[&str] = &["role", "content", "name", "tool_calls", "tool_call_id"];
+
 /// OpenAI-compatible model info
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelInfo {
@@ -247,6 +397,7 @@ pub const INFERNO_VERSION_HEADER: &str = "Inferno/0.8.0 (OpenAI-compatible)";
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
 
     #[test]
     fn test_chat_completion_validation() {
@@ -323,6 +474,75 @@ mod tests {
         assert_eq!(err_response.error.r#type, "invalid_request_error");
     }
 
+    #[test]
+    fn test_every_error_variant_has_schema_valid_body() {
+        let cases = [
+            (
+                InfernoError::Validation("bad".into()),
+                400,
+                "invalid_request_error",
+            ),
+            (
+                InfernoError::InvalidArgument("bad".into()),
+                400,
+                "invalid_request_error",
+            ),
+            (
+                InfernoError::Auth("no key".into()),
+                401,
+                "invalid_request_error",
+            ),
+            (
+                InfernoError::SecurityValidation("no".into()),
+                403,
+                "invalid_request_error",
+            ),
+            (
+                InfernoError::ModelNotFound("gone".into()),
+                404,
+                "invalid_request_error",
+            ),
+            (
+                InfernoError::StreamingLimit("slow down".into()),
+                429,
+                "requests",
+            ),
+            (InfernoError::Backend("crashed".into()), 500, "server_error"),
+            (
+                InfernoError::Cancelled("stopped".into()),
+                500,
+                "server_error",
+            ),
+            (InfernoError::Timeout("slow".into()), 504, "server_error"),
+            (InfernoError::Resource("full".into()), 507, "server_error"),
+        ];
+
+        for (error, status, r#type) in cases {
+            assert_eq!(
+                ComplianceValidator::map_status_code(&error).0,
+                status,
+                "{error}"
+            );
+            let body = serde_json::to_value(ErrorResponse::from_inferno_error(&error)).unwrap();
+            let result = ComplianceValidator::validate_error_body(&body);
+            assert!(result.is_valid, "{error}: {:?}", result.errors);
+            assert_eq!(body["error"]["type"], r#type, "{error}");
+        }
+
+        let result = ComplianceValidator::validate_error_body(&json!({"error": "plain text"}));
+        assert!(!result.is_valid);
+        let result =
+            ComplianceValidator::validate_error_body(&json!({"error": {"message": "no type"}}));
+        assert_eq!(
+            result.errors,
+            vec![
+                "error.type must be a string",
+                "error.param must be a string or null",
+                "error.code must be a string or null"
+            ]
+        );
+    }
+
     #[test]
     fn test_status_code_mapping() {
         let validation_err = InfernoError::Validation("bad input".to_string());
@@ -333,4 +553,48 @@ mod tests {
         let (code, _) = ComplianceValidator::map_status_code(&auth_err);
         assert_eq!(code, 401);
     }
+
+    #[test]
+    fn test_unknown_fields() {
+        let body = json!({
+            "model": "llama",
+            "messages": [{"role": "user", "content": "hi", "mood": "calm"}],
+            "temperature": 0.5,
+            "top_k": 40
+        });
+        assert_eq!(
+            ComplianceValidator::unknown_fields(OpenAIEndpoint::ChatCompletions, &body),
+            vec!["top_k", "messages[0].mood"]
+        );
+
+        // Newer documented fields pass too
+        let body = json!({
+            "model": "llama",
+            "messages": [{"role": "user", "content": "hi"}],
+            "max_completion_tokens": 64,
+            "response_format": {"type": "json_object"},
+            "stream_options": {"include_usage": true},
+            "parallel_tool_calls": false
+        });
+        assert!(
+            ComplianceValidator::unknown_fields(OpenAIEndpoint::ChatCompletions, &body).is_empty()
+        );
+
+        let body = json!({"model": "embed", "input": "text", "dimensions": 256});
+        assert!(ComplianceValidator::unknown_fields(OpenAIEndpoint::Embeddings, &body).is_empty());
+    }
+
+    #[test]
+    fn test_error_response_field_order() {
+        let error = ErrorResponse::for_status(400, "bad", Some("top_k"));
+        assert_eq!(
+            serde_json::to_string(&error).unwrap(),
+            r#"{"error":{"message":"bad","type":"invalid_request_error","param":"top_k","code":null}}"#
+        );
+        assert_eq!(
+            ErrorResponse::error_type_for_status(401),
+            "invalid_request_error"
+        );
+        assert_eq!(ErrorResponse::error_type_for_status(503), "server_error");
+    }
 }
@@ -0,0 +1,292 @@
+//! Resuming interrupted streams
+//!
+//! Chat and text completion streams run apart from the connection that started
+//! them. Their events are numbered, and each SSE event's id is
+//! `<completion id>:<number>`. A client that loses its connection can send the
+//! request again with the last id it received in a `Last-Event-ID` header. The
+//! server then sends the events after that id, including any generated while the
+//! client was away, without running the model again.
+//!
+//! Only the requester that started a stream, the same tenant and API key, may
+//! resume it; to anyone else it does not exist.
+//!
+//! Only the latest `stream_resume_buffer_events` events of a stream are kept. A
+//! stream is dropped, and its generation cancelled if it is still running, once no
+//! client has followed it for `stream_resume_ttl_secs`.
+
+use crate::config::ServerConfig;
+use futures::Stream;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Why a stream cannot be resumed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResumeError {
+    /// The `Last-Event-ID` cannot be resumed from, or resumption is off
+    Invalid(String),
+    /// The requester has no stream with that id
+    NotFound(String),
+}
+
+impl fmt::Display for ResumeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResumeError::Invalid(message) | ResumeError::NotFound(message) => f.write_str(message),
+        }
+    }
+}
+
+/// Streams that can be resumed, by completion id
+#[derive(Clone)]
+pub struct StreamSessions {
+    sessions: Arc<Mutex<HashMap<String, Arc<StreamSession>>>>,
+    ttl: Duration,
+    max_events: usize,
+}
+
+impl StreamSessions {
+    pub fn new(ttl: Duration, max_events: usize) -> Self {
+        Self {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            ttl,
+            max_events,
+        }
+    }
+
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self::new(
+            Duration::from_secs(config.stream_resume_ttl_secs),
+            config.stream_resume_buffer_events,
+        )
+    }
+
+    /// Whether streams are kept for resuming at all
+    pub fn enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.max_events > 0
+    }
+
+    /// Start keeping the events of the stream `id`, which only `owner` may resume
+    pub fn start(&self, id: &str, owner: &str) -> Arc<StreamSession> {
+        let session = Arc::new(StreamSession {
+            id: id.to_string(),
+            owner: owner.to_string(),
+            ttl: self.ttl,
+            max_events: self.max_events,
+            buffer: Mutex::new(Buffer {
+                first: 0,
+                events: VecDeque::new(),
+                finished: false,
+                followers: 0,
+                unfollowed_since: Instant::now(),
+            }),
+            changed: Notify::new(),
+        });
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| !session.expired());
+        sessions.insert(id.to_string(), session.clone());
+        session
+    }
+
+    /// The stream of `owner` a `Last-Event-ID` belongs to and the number of the
+    /// last event the client received
+    ///
+    /// Another requester's stream is reported as not found, exactly as a stream
+    /// that never existed, so its id reveals nothing.
+    pub fn resume(
+        &self,
+        last_event_id: &str,
+        owner: &str,
+    ) -> Result<(Arc<StreamSession>, u64), ResumeError> {
+        if !self.enabled() {
+            return Err(ResumeError::Invalid(
+                "Stream resumption is disabled on this server".to_string(),
+            ));
+        }
+        let (id, last) = last_event_id
+            .rsplit_once(':')
+            .and_then(|(id, last)| Some((id, last.parse::<u64>().ok()?)))
+            .ok_or_else(|| {
+                ResumeError::Invalid(format!("Malformed Last-Event-ID '{}'", last_event_id))
+            })?;
+        let session = {
+            let mut sessions = self.sessions.lock().unwrap();
+            sessions.retain(|_, session| !session.expired());
+            sessions.get(id).cloned()
+        };
+        let session = session
+            .filter(|session| session.owner == owner)
+            .ok_or_else(|| {
+                ResumeError::NotFound(format!(
+                    "Stream '{}' has expired or never existed and cannot be resumed",
+                    id
+                ))
+            })?;
+
+        let buffer = session.buffer.lock().unwrap();
+        let produced = buffer.first + buffer.events.len() as u64;
+        if last >= produced {
+            return Err(ResumeError::Invalid(format!(
+                "Stream '{}' has no event {}",
+                id, last
+            )));
+        }
+        if last + 1 < buffer.first {
+            return Err(ResumeError::Invalid(format!(
+                "Events after {} of stream '{}' are no longer buffered",
+                last, id
+            )));
+        }
+        drop(buffer);
+        Ok((session, last))
+    }
+}
+
+/// Events of one stream, kept for clients to follow and resume
+pub struct StreamSession {
+    id: String,
+    /// Tenant and API key digest of the request that started the stream
+    owner: String,
+    ttl: Duration,
+    max_events: usize,
+    buffer: Mutex<Buffer>,
+    changed: Notify,
+}
+
+struct Buffer {
+    /// Number of the oldest event kept
+    first: u64,
+    events: VecDeque<String>,
+    finished: bool,
+    followers: usize,
+    /// When the last client left, or the stream started if none has come yet
+    unfollowed_since: Instant,
+}
+
+impl StreamSession {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// SSE id of event number `seq`
+    pub fn event_id(&self, seq: u64) -> String {
+        format!("{}:{}", self.id, seq)
+    }
+
+    /// Add the next event's data
+    pub fn push(&self, data: String) {
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.events.push_back(data);
+        if buffer.events.len() > self.max_events {
+            buffer.events.pop_front();
+            buffer.first += 1;
+        }
+        drop(buffer);
+        self.changed.notify_waiters();
+    }
+
+    /// Mark the stream complete, ending it for its followers
+    pub fn finish(&self) {
+        self.buffer.lock().unwrap().finished = true;
+        self.changed.notify_waiters();
+    }
+
+    /// Whether no client has followed the stream for the TTL
+    pub fn expired(&self) -> bool {
+        let buffer = self.buffer.lock().unwrap();
+        buffer.followers == 0 && buffer.unfollowed_since.elapsed() >= self.ttl
+    }
+
+    /// Events after number `after`, or all of them, as they arrive until the
+    /// stream finishes
+    pub fn follow(self: Arc<Self>, after: Option<u64>) -> impl Stream<Item = (u64, String)> {
+        async_stream::stream! {
+            let _follower = Follower::new(self.clone());
+            let mut next = after.map_or(0, |seq| seq + 1);
+            loop {
+                // Created before reading, so no event pushed in between is missed
+                let changed = self.changed.notified();
+                let (events, finished) = {
+                    let buffer = self.buffer.lock().unwrap();
+                    // A follower too slow for the buffer skips what it lost
+                    next = next.max(buffer.first);
+                    let events: Vec<String> = buffer
+                        .events
+                        .iter()
+                        .skip((next - buffer.first) as usize)
+                        .cloned()
+                        .collect();
+                    (events, buffer.finished)
+                };
+                for data in events {
+                    yield (next, data);
+                    next += 1;
+                }
+                if finished {
+                    break;
+                }
+                changed.await;
+            }
+        }
+    }
+}
+
+/// Counts a client as following a stream while it lives
+struct Follower(Arc<StreamSession>);
+
+impl Follower {
+    fn new(session: Arc<StreamSession>) -> Self {
+        session.buffer.lock().unwrap().followers += 1;
+        Self(session)
+    }
+}
+
+impl Drop for Follower {
+    fn drop(&mut self) {
+        let mut buffer = self.0.buffer.lock().unwrap();
+        buffer.followers -= 1;
+        buffer.unfollowed_since = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_rejects_unknown_and_lost_events() {
+        let sessions = StreamSessions::new(Duration::from_secs(60), 3);
+        let session = sessions.start("chatcmpl-1", "acme/");
+        for n in 0..5 {
+            session.push(format!("event {}", n));
+        }
+
+        // Events 2 to 4 are kept, so a client that saw event 1 can continue
+        let (_, last) = sessions.resume("chatcmpl-1:1", "acme/").unwrap();
+        assert_eq!(last, 1);
+        assert_eq!(session.event_id(4), "chatcmpl-1:4");
+
+        for (last_event_id, expected) in [
+            ("chatcmpl-1:0", "no longer buffered"),
+            ("chatcmpl-1:5", "no event 5"),
+            ("chatcmpl-1", "Malformed"),
+        ] {
+            let error = sessions.resume(last_event_id, "acme/").err().unwrap();
+            assert!(matches!(error, ResumeError::Invalid(_)), "{:?}", error);
+            assert!(error.to_string().contains(expected), "{}", error);
+        }
+
+        // Another requester's stream looks like one that never existed
+        let unknown = sessions.resume("chatcmpl-2:0", "acme/").err().unwrap();
+        let foreign = sessions.resume("chatcmpl-1:1", "other/").err().unwrap();
+        assert!(matches!(unknown, ResumeError::NotFound(_)));
+        assert!(matches!(foreign, ResumeError::NotFound(_)));
+        assert!(foreign.to_string().contains("cannot be resumed"));
+
+        let disabled = StreamSessions::new(Duration::ZERO, 3);
+        assert!(!disabled.enabled());
+        assert!(disabled.resume("chatcmpl-1:1", "acme/").is_err());
+    }
+}
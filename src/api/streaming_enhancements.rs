@@ -2,7 +2,9 @@
 //!
 //! Provides Server-Sent Events, compression, token batching, keep-alive, and timeout handling
 
+use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::time::{Duration, Instant};
 
 /// Compression format options
@@ -167,6 +169,11 @@ impl TokenBatcher {
         batched
     }
 
+    /// Time left before a non-empty buffer is due to be flushed
+    pub fn time_until_flush(&self) -> Duration {
+        self.max_wait_ms.saturating_sub(self.last_flush.elapsed())
+    }
+
     /// Get buffer size
     pub fn len(&self) -> usize {
         self.buffer.len()
@@ -194,10 +201,18 @@ pub struct TimeoutManager {
 impl TimeoutManager {
     /// Create new timeout manager
     pub fn new(inference_timeout_secs: u64, token_timeout_secs: u64) -> Self {
+        Self::with_durations(
+            Duration::from_secs(inference_timeout_secs),
+            Duration::from_secs(token_timeout_secs),
+        )
+    }
+
+    /// Create timeout manager with sub-second precision
+    pub fn with_durations(inference_timeout: Duration, token_timeout: Duration) -> Self {
         let now = Instant::now();
         Self {
-            inference_timeout: Duration::from_secs(inference_timeout_secs),
-            token_timeout: Duration::from_secs(token_timeout_secs),
+            inference_timeout,
+            token_timeout,
             start_time: now,
             last_token_time: now,
         }
@@ -227,6 +242,17 @@ impl TimeoutManager {
     pub fn time_since_last_token_ms(&self) -> u64 {
         self.last_token_time.elapsed().as_millis() as u64
     }
+
+    /// Time left before either timeout is exceeded
+    pub fn remaining(&self) -> Duration {
+        let inference = self
+            .inference_timeout
+            .saturating_sub(self.start_time.elapsed());
+        let token = self
+            .token_timeout
+            .saturating_sub(self.last_token_time.elapsed());
+        inference.min(token)
+    }
 }
 
 /// Keep-alive mechanism for detecting dead connections
@@ -243,13 +269,23 @@ pub struct KeepAlive {
 impl KeepAlive {
     /// Create new keep-alive manager
     pub fn new(interval_secs: u64) -> Self {
+        Self::with_interval(Duration::from_secs(interval_secs))
+    }
+
+    /// Create keep-alive manager with sub-second precision
+    pub fn with_interval(interval: Duration) -> Self {
         Self {
-            interval: Duration::from_secs(interval_secs),
+            interval,
             last_sent: Instant::now(),
             count: 0,
         }
     }
 
+    /// Time left until the next keep-alive is due
+    pub fn time_until_next(&self) -> Duration {
+        self.interval.saturating_sub(self.last_sent.elapsed())
+    }
+
     /// Check if keep-alive should be sent
     pub fn should_send_keepalive(&self) -> bool {
         self.last_sent.elapsed() > self.interval
@@ -273,6 +309,130 @@ impl KeepAlive {
     }
 }
 
+/// Which deadline a stalled stream missed
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum StreamTimeout {
+    /// No token arrived within the token timeout
+    Token,
+    /// The whole stream ran past the inference timeout
+    Inference,
+}
+
+impl fmt::Display for StreamTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamTimeout::Token => write!(f, "Stream timed out waiting for the next token"),
+            StreamTimeout::Inference => write!(f, "Stream exceeded the inference timeout"),
+        }
+    }
+}
+
+/// Item of a stream watched by [`watch_token_stream`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchedToken<T> {
+    Token(T),
+    /// No token arrived for a keep-alive interval; carries the keep-alive count
+    KeepAlive(u32),
+    /// The stream stalled past a deadline and was abandoned
+    TimedOut(StreamTimeout),
+}
+
+/// Interleave keep-alives into a slow token stream and end it once it stalls
+///
+/// A keep-alive is emitted whenever no token has arrived for the keep-alive interval.
+/// Once the token or inference timeout passes, a single [`WatchedToken::TimedOut`] is
+/// emitted and `tokens` is not polled again.
+pub fn watch_token_stream<S>(
+    mut tokens: S,
+    mut keepalive: KeepAlive,
+    mut timeouts: TimeoutManager,
+) -> impl Stream<Item = WatchedToken<S::Item>>
+where
+    S: Stream + Unpin,
+{
+    async_stream::stream! {
+        loop {
+            let wait = keepalive.time_until_next().min(timeouts.remaining());
+            let next = tokio::select! {
+                item = tokens.next() => Some(item),
+                _ = tokio::time::sleep(wait) => None,
+            };
+
+            match next {
+                Some(Some(item)) => {
+                    timeouts.record_token();
+                    keepalive.reset();
+                    yield WatchedToken::Token(item);
+                }
+                Some(None) => break,
+                None if timeouts.is_inference_timeout() => {
+                    yield WatchedToken::TimedOut(StreamTimeout::Inference);
+                    break;
+                }
+                None if timeouts.is_token_timeout() => {
+                    yield WatchedToken::TimedOut(StreamTimeout::Token);
+                    break;
+                }
+                None => {
+                    if keepalive.should_send_keepalive() {
+                        yield WatchedToken::KeepAlive(keepalive.send_keepalive());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Coalesce a token stream into batches of up to the batcher's size
+///
+/// A batch is sent once it is full or the batcher's wait has passed since the last
+/// one, so tokens trickling in after a pause go out straight away. Whatever is
+/// buffered is sent before an error or the end of the stream, so the concatenated
+/// batches always equal the unbatched text.
+pub fn batch_token_stream<S, E>(
+    mut tokens: S,
+    mut batcher: TokenBatcher,
+) -> impl Stream<Item = Result<String, E>>
+where
+    S: Stream<Item = Result<String, E>> + Unpin,
+{
+    async_stream::stream! {
+        loop {
+            let next = if batcher.is_empty() {
+                Some(tokens.next().await)
+            } else {
+                tokio::select! {
+                    item = tokens.next() => Some(item),
+                    _ = tokio::time::sleep(batcher.time_until_flush()) => None,
+                }
+            };
+
+            match next {
+                Some(Some(Ok(token))) => {
+                    batcher.add_token(token);
+                    if batcher.should_flush() {
+                        yield Ok(batcher.flush());
+                    }
+                }
+                Some(Some(Err(e))) => {
+                    if !batcher.is_empty() {
+                        yield Ok(batcher.flush());
+                    }
+                    yield Err(e);
+                    break;
+                }
+                Some(None) => {
+                    if !batcher.is_empty() {
+                        yield Ok(batcher.flush());
+                    }
+                    break;
+                }
+                None => yield Ok(batcher.flush()),
+            }
+        }
+    }
+}
+
 /// Streaming configuration combining all enhancements
 #[derive(Debug, Clone)]
 pub struct StreamingOptimizationConfig {
@@ -380,6 +540,98 @@ mod tests {
         assert!(!ka.should_send_keepalive());
     }
 
+    /// A token stream that produces nothing for `stall` before its only token
+    fn stalled_stream(stall: Duration) -> impl Stream<Item = &'static str> + Unpin {
+        Box::pin(async_stream::stream! {
+            tokio::time::sleep(stall).await;
+            yield "hello";
+        })
+    }
+
+    #[tokio::test]
+    async fn test_keepalives_sent_before_first_token() {
+        use axum::response::{
+            IntoResponse,
+            sse::{Event, Sse},
+        };
+
+        let watched = watch_token_stream(
+            stalled_stream(Duration::from_millis(150)),
+            KeepAlive::with_interval(Duration::from_millis(30)),
+            TimeoutManager::with_durations(Duration::from_secs(5), Duration::from_secs(1)),
+        );
+        let items: Vec<_> = watched.collect().await;
+
+        let (last, keepalives) = items.split_last().unwrap();
+        assert_eq!(last, &WatchedToken::Token("hello"));
+        assert!(keepalives.len() >= 2, "{:?}", items);
+        assert!(
+            keepalives
+                .iter()
+                .all(|item| matches!(item, WatchedToken::KeepAlive(_)))
+        );
+
+        // As SSE, keep-alives are comment lines that leave the data events intact
+        let watched = watch_token_stream(
+            stalled_stream(Duration::from_millis(100)),
+            KeepAlive::with_interval(Duration::from_millis(30)),
+            TimeoutManager::with_durations(Duration::from_secs(5), Duration::from_secs(1)),
+        );
+        let events = watched.map(|item| {
+            Ok::<_, std::convert::Infallible>(match item {
+                WatchedToken::Token(token) => Event::default().data(token),
+                WatchedToken::KeepAlive(_) => Event::default().comment("keep-alive"),
+                WatchedToken::TimedOut(timeout) => Event::default().data(timeout.to_string()),
+            })
+        });
+        let body = axum::body::to_bytes(Sse::new(events).into_response().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.starts_with(": keep-alive\n\n"), "{:?}", body);
+        assert!(body.ends_with("data: hello\n\n"), "{:?}", body);
+        assert!(
+            body.lines()
+                .all(|line| line.is_empty() || line == ": keep-alive" || line == "data: hello"),
+            "{:?}",
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stalled_stream_times_out() {
+        let watched = watch_token_stream(
+            futures::stream::pending::<&str>(),
+            KeepAlive::with_interval(Duration::from_millis(20)),
+            TimeoutManager::with_durations(Duration::from_secs(5), Duration::from_millis(70)),
+        );
+        let items: Vec<_> = watched.collect().await;
+
+        assert_eq!(
+            items.last(),
+            Some(&WatchedToken::TimedOut(StreamTimeout::Token))
+        );
+        assert!(items.len() >= 3, "{:?}", items);
+
+        // Tokens that keep arriving never hit the token timeout, but the overall
+        // inference deadline still applies
+        let steady = Box::pin(futures::stream::repeat("tok").then(|token| async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            token
+        }));
+        let items: Vec<_> = watch_token_stream(
+            steady,
+            KeepAlive::with_interval(Duration::from_secs(1)),
+            TimeoutManager::with_durations(Duration::from_millis(100), Duration::from_millis(50)),
+        )
+        .collect()
+        .await;
+        assert_eq!(
+            items.last(),
+            Some(&WatchedToken::TimedOut(StreamTimeout::Inference))
+        );
+    }
+
     #[test]
     fn test_default_config() {
         let config = StreamingOptimizationConfig::default();
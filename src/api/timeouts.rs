@@ -0,0 +1,69 @@
+//! Per-route request timeouts
+//!
+//! Each inference endpoint gets its own limit under `[server.timeouts]`, so a slow
+//! embeddings call can be aborted quickly while long chat generations still finish.
+//! A request over its limit gets an OpenAI-shaped 504, and dropping its handler
+//! cancels the inference behind it. Streaming responses are only bounded until
+//! their headers are sent; after that `stream_token_timeout_secs` applies.
+
+use crate::api::openai::api_error;
+use axum::{
+    BoxError, error_handling::HandleErrorLayer, http::StatusCode, response::Response,
+    routing::MethodRouter,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tower::{ServiceBuilder, timeout::TimeoutLayer};
+
+/// Seconds each inference endpoint may take to respond
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteTimeouts {
+    pub chat: u64,
+    pub completions: u64,
+    pub embeddings: u64,
+}
+
+impl Default for RouteTimeouts {
+    fn default() -> Self {
+        Self {
+            chat: 300,
+            completions: 300,
+            embeddings: 30,
+        }
+    }
+}
+
+/// Abort requests to `route` that take longer than `secs`
+pub fn with_timeout<S>(route: MethodRouter<S>, secs: u64, strict: bool) -> MethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    route.layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(move |error: BoxError| async move {
+                timeout_error(strict, error)
+            }))
+            .layer(TimeoutLayer::new(Duration::from_secs(secs))),
+    )
+}
+
+fn timeout_error(strict: bool, error: BoxError) -> Response {
+    if error.is::<tower::timeout::error::Elapsed>() {
+        api_error(
+            strict,
+            StatusCode::GATEWAY_TIMEOUT,
+            "Request timed out".to_string(),
+            "timeout",
+            None,
+        )
+    } else {
+        api_error(
+            strict,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Request failed: {}", error),
+            "internal_error",
+            None,
+        )
+    }
+}
@@ -0,0 +1,367 @@
+//! OpenAI function calling
+//!
+//! The `tools` of a chat request are described to the model, by the chat template
+//! when it renders a `tools` variable as templates written for tool use do, and in
+//! a system message otherwise. The model calls functions by replying with JSON
+//! naming the function and its arguments, which [`parse_tool_calls`] turns into
+//! the response's `tool_calls`. When `tool_choice` makes a call mandatory, the
+//! output is constrained by a grammar so it is always a well-formed call.
+
+use crate::{backends::ResponseFormat, models::chat_template::Message};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+/// A tool the model may call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tool {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionDefinition,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON Schema of the arguments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+}
+
+/// `tool_choice`: `"none"`, `"auto"`, `"required"` or a named function
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ToolChoice {
+    Mode(String),
+    Function {
+        #[serde(rename = "type")]
+        kind: String,
+        function: FunctionName,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionName {
+    pub name: String,
+}
+
+/// A function call made by the model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// Arguments as a JSON-encoded object
+    pub arguments: String,
+}
+
+/// Whether and which tools the model may call, from a request's `tool_choice`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolMode {
+    /// No tools are offered
+    None,
+    /// The model decides whether to call a tool
+    Auto,
+    /// The model must call a tool
+    Required,
+    /// The model must call this function
+    Function(String),
+}
+
+impl ToolMode {
+    /// Mode for a request offering `tools`, or the message and parameter of the
+    /// problem with them
+    pub fn for_request(
+        tools: &[Tool],
+        choice: Option<&ToolChoice>,
+    ) -> Result<Self, (String, &'static str)> {
+        for tool in tools {
+            if tool.kind != "function" {
+                return Err((
+                    format!("Unsupported tool type '{}'; only 'function' is", tool.kind),
+                    "tools",
+                ));
+            }
+            let name = &tool.function.name;
+            let valid_name = !name.is_empty()
+                && name.len() <= 64
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !valid_name {
+                return Err((
+                    format!(
+                        "Invalid function name '{}': use up to 64 letters, digits, \
+                         underscores and dashes",
+                        name
+                    ),
+                    "tools",
+                ));
+            }
+        }
+
+        let mode = match choice {
+            None if tools.is_empty() => Self::None,
+            None => Self::Auto,
+            Some(ToolChoice::Mode(mode)) => match mode.as_str() {
+                "none" => Self::None,
+                "auto" => Self::Auto,
+                "required" => Self::Required,
+                other => {
+                    return Err((
+                        format!(
+                            "Invalid tool_choice '{}': expected 'none', 'auto', 'required' \
+                             or a function",
+                            other
+                        ),
+                        "tool_choice",
+                    ));
+                }
+            },
+            Some(ToolChoice::Function { function, .. }) => {
+                if !tools.iter().any(|tool| tool.function.name == function.name) {
+                    return Err((
+                        format!("tool_choice names unknown function '{}'", function.name),
+                        "tool_choice",
+                    ));
+                }
+                Self::Function(function.name.clone())
+            }
+        };
+        if tools.is_empty() && mode != Self::None {
+            return Err((
+                "tool_choice requires tools to choose from".to_string(),
+                "tool_choice",
+            ));
+        }
+        Ok(mode)
+    }
+
+    /// Tools the model is offered
+    pub fn offered<'a>(&self, tools: &'a [Tool]) -> Vec<&'a Tool> {
+        match self {
+            Self::None => Vec::new(),
+            Self::Auto | Self::Required => tools.iter().collect(),
+            Self::Function(name) => tools
+                .iter()
+                .filter(|tool| &tool.function.name == name)
+                .collect(),
+        }
+    }
+
+    /// Format constraining the output to a call when one is mandatory
+    pub fn response_format(&self, tools: &[Tool]) -> Option<ResponseFormat> {
+        if matches!(self, Self::None | Self::Auto) {
+            return None;
+        }
+        let calls: Vec<Value> = self
+            .offered(tools)
+            .into_iter()
+            .map(|tool| {
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "name": {"const": tool.function.name},
+                        "arguments": tool
+                            .function
+                            .parameters
+                            .clone()
+                            .unwrap_or_else(|| json!({"type": "object"})),
+                    },
+                    "required": ["name", "arguments"],
+                })
+            })
+            .collect();
+        Some(ResponseFormat::JsonSchema(json!({ "anyOf": calls })))
+    }
+
+    /// System message describing the offered tools to a model whose template
+    /// does not render them
+    pub fn instructions(&self, tools: &[Tool]) -> Option<String> {
+        let offered = self.offered(tools);
+        if offered.is_empty() {
+            return None;
+        }
+        let definitions: Vec<String> = offered
+            .iter()
+            .map(|tool| serde_json::to_string(&tool.function).unwrap_or_default())
+            .collect();
+        let when = match self {
+            Self::Function(name) => format!("You must call the function {}.", name),
+            Self::Required => "You must call one of these functions.".to_string(),
+            _ => "Call them when they help answer; otherwise reply normally.".to_string(),
+        };
+        Some(format!(
+            "You can call these functions:\n{}\n\n{} To call a function, reply with only a \
+             JSON object of the form {{\"name\": \"<function name>\", \"arguments\": \
+             {{<arguments>}}}}, or a JSON array of such objects to call several, and nothing \
+             else.",
+            definitions.join("\n"),
+            when
+        ))
+    }
+}
+
+/// Messages to render for `messages`, with tool calls and results written out as
+/// text and, unless the template renders tools itself, the tool instructions
+/// added to the system message
+pub fn prompt_messages(
+    messages: &[crate::api::openai::ChatMessage],
+    instructions: Option<String>,
+    template_renders_tools: bool,
+) -> Vec<Message> {
+    let mut rendered: Vec<Message> = messages
+        .iter()
+        .map(|message| {
+            let content = message.content.clone().unwrap_or_default();
+            match (message.role.as_str(), &message.tool_calls) {
+                ("assistant", Some(calls)) if !calls.is_empty() => {
+                    let calls: Vec<Value> = calls
+                        .iter()
+                        .map(|call| {
+                            let arguments = serde_json::from_str(&call.function.arguments)
+                                .unwrap_or_else(|_| Value::String(call.function.arguments.clone()));
+                            json!({"name": call.function.name, "arguments": arguments})
+                        })
+                        .collect();
+                    Message::new("assistant", Value::Array(calls).to_string())
+                }
+                ("tool", _) if !template_renders_tools => {
+                    let call = message.tool_call_id.as_deref().unwrap_or("a function call");
+                    Message::new("user", format!("Result of {}: {}", call, content))
+                }
+                (role, _) => Message::new(role, content),
+            }
+        })
+        .collect();
+
+    if let Some(instructions) = instructions.filter(|_| !template_renders_tools) {
+        match rendered.first_mut() {
+            Some(system) if system.role == "system" => {
+                system.content = format!("{}\n\n{}", system.content, instructions);
+            }
+            _ => rendered.insert(0, Message::new("system", instructions)),
+        }
+    }
+    rendered
+}
+
+/// Function calls in a model's reply, if it consists of nothing but calls to
+/// functions among `tools`
+///
+/// Accepts a call object, an array of them or `{"tool_calls": [...]}`, bare, in
+/// a fenced code block or in `<tool_call>` tags. Calls may give their arguments
+/// as `arguments` or `parameters`.
+pub fn parse_tool_calls(text: &str, tools: &[&Tool]) -> Option<Vec<ToolCall>> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("```json")
+        .or_else(|| text.strip_prefix("```"))
+        .and_then(|rest| rest.trim_end().strip_suffix("```"))
+        .unwrap_or(text)
+        .trim();
+
+    let blocks: Vec<&str> = if text.starts_with("<tool_call>") {
+        text.split("<tool_call>")
+            .map(|block| block.trim())
+            .filter(|block| !block.is_empty())
+            .map(|block| block.strip_suffix("</tool_call>").unwrap_or(block).trim())
+            .collect()
+    } else {
+        vec![text]
+    };
+
+    let mut calls = Vec::new();
+    for block in blocks {
+        let value: Value = serde_json::from_str(block).ok()?;
+        let items = match value {
+            Value::Array(items) => items,
+            Value::Object(mut object) => match object.remove("tool_calls") {
+                Some(Value::Array(items)) => items,
+                Some(_) => return None,
+                None => vec![Value::Object(object)],
+            },
+            _ => return None,
+        };
+        for item in items {
+            calls.push(tool_call(item, tools)?);
+        }
+    }
+    (!calls.is_empty()).then_some(calls)
+}
+
+fn tool_call(item: Value, tools: &[&Tool]) -> Option<ToolCall> {
+    // OpenAI-shaped calls nest the function under `function`
+    let call = match item.get("function") {
+        Some(function @ Value::Object(_)) => function,
+        _ => &item,
+    };
+    let name = call.get("name")?.as_str()?;
+    if !tools.iter().any(|tool| tool.function.name == name) {
+        return None;
+    }
+    let arguments = match call.get("arguments").or_else(|| call.get("parameters")) {
+        Some(Value::String(arguments)) => arguments.clone(),
+        Some(arguments @ Value::Object(_)) => arguments.to_string(),
+        None => "{}".to_string(),
+        Some(_) => return None,
+    };
+    Some(ToolCall {
+        id: format!("call_{}", Uuid::new_v4().simple()),
+        kind: "function".to_string(),
+        function: FunctionCall {
+            name: name.to_string(),
+            arguments,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weather_tool() -> Tool {
+        serde_json::from_value(json!({
+            "type": "function",
+            "function": {
+                "name": "get_weather",
+                "parameters": {
+                    "type": "object",
+                    "properties": {"city": {"type": "string"}},
+                    "required": ["city"]
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_tool_calls_in_common_shapes() {
+        let tool = weather_tool();
+        let tools = [&tool];
+        for reply in [
+            r#"{"name": "get_weather", "arguments": {"city": "Paris"}}"#,
+            r#"[{"name": "get_weather", "parameters": {"city": "Paris"}}]"#,
+            "```json\n{\"tool_calls\": [{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Paris\"}}]}\n```",
+            "<tool_call>\n{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Paris\"}}\n</tool_call>",
+        ] {
+            let calls = parse_tool_calls(reply, &tools).unwrap();
+            assert_eq!(calls.len(), 1, "{}", reply);
+            assert_eq!(calls[0].function.name, "get_weather");
+            let arguments: Value = serde_json::from_str(&calls[0].function.arguments).unwrap();
+            assert_eq!(arguments, json!({"city": "Paris"}));
+        }
+
+        // Prose and calls to functions that were not offered are left as text
+        assert!(parse_tool_calls("It is sunny in Paris.", &tools).is_none());
+        assert!(parse_tool_calls(r#"{"name": "rm_rf", "arguments": {}}"#, &tools).is_none());
+    }
+}
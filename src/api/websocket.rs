@@ -1,17 +1,20 @@
 #![allow(dead_code, unused_imports, unused_variables)]
 use crate::{
     InfernoError,
+    api::key_concurrency::HeldSlot,
     api::openai::{
-        ChatChunkChoice, ChatCompletionChunk, ChatCompletionRequest, ChatDelta, ChatMessage,
+        self, ChatChunkChoice, ChatCompletionChunk, ChatCompletionRequest, ChatDelta, ChatMessage,
+        OpenAIRequest,
     },
     backends::{Backend, InferenceParams},
     cli::serve::ServerState,
+    multi_tenancy::TenantConfig,
     streaming::{StreamingConfig, StreamingManager},
     upgrade::{ApplicationVersion, UpgradeEvent, UpgradeStatus},
 };
 use axum::{
     extract::{
-        State,
+        Extension, State,
         ws::{Message, WebSocket, WebSocketUpgrade},
     },
     response::Response,
@@ -81,14 +84,25 @@ pub enum WSMessage {
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<ServerState>>,
+    tenant: Option<Extension<Arc<TenantConfig>>>,
+    slot: Option<Extension<HeldSlot>>,
 ) -> Response {
     info!("New WebSocket connection for streaming");
 
-    ws.on_upgrade(move |socket| handle_websocket(socket, state))
+    let tenant = tenant.map(|Extension(tenant)| tenant);
+    ws.on_upgrade(move |socket| async move {
+        // The connection counts against its client's concurrency limit until it closes
+        let _slot = slot;
+        handle_websocket(socket, state, tenant).await
+    })
 }
 
 /// Handle individual WebSocket connection
-async fn handle_websocket(socket: WebSocket, state: Arc<ServerState>) {
+async fn handle_websocket(
+    socket: WebSocket,
+    state: Arc<ServerState>,
+    tenant: Option<Arc<TenantConfig>>,
+) {
     let connection_id = Uuid::new_v4().to_string();
     info!("WebSocket connection established: {}", connection_id);
 
@@ -171,6 +185,7 @@ async fn handle_websocket(socket: WebSocket, state: Arc<ServerState>) {
                     if let Err(e) = handle_ws_message(
                         ws_message,
                         &state,
+                        tenant.as_ref(),
                         &streaming_manager,
                         &sender,
                         &connection_id,
@@ -234,6 +249,7 @@ async fn handle_websocket(socket: WebSocket, state: Arc<ServerState>) {
 async fn handle_ws_message(
     message: WSMessage,
     state: &Arc<ServerState>,
+    tenant: Option<&Arc<TenantConfig>>,
     streaming_manager: &Arc<StreamingManager>,
     sender: &Arc<Mutex<futures::stream::SplitSink<WebSocket, Message>>>,
     connection_id: &str,
@@ -245,21 +261,37 @@ async fn handle_ws_message(
                 id, connection_id
             );
 
-            // Get or load backend
-            let backend = get_or_load_backend_for_ws(state, &data.model).await?;
-
-            // Convert chat messages to prompt
-            let prompt = format_chat_messages(&data.messages);
+            state
+                .config
+                .server
+                .request_limits
+                .chat_completions
+                .check(&data.inputs())
+                .map_err(|e| InfernoError::Validation(e.to_string()))?;
 
             let inference_params = InferenceParams {
                 max_tokens: data.max_tokens,
                 temperature: data.temperature,
                 top_k: 40,
+                repeat_penalty: None,
+                repeat_last_n: None,
+                min_p: None,
                 top_p: data.top_p,
                 stream: true, // Always stream for WebSocket
                 stop_sequences: data.stop.unwrap_or_default(),
-                seed: None,
+                stop_regex: None,
+                max_generation_ms: None,
+                seed: data.seed,
+                response_format: None,
+                logprobs: None,
             };
+            inference_params.validate()?;
+
+            // Get or load backend
+            let backend = get_or_load_backend_for_ws(state, tenant, &data.model).await?;
+
+            // Convert chat messages to prompt
+            let prompt = format_chat_messages(&data.messages);
 
             // Create streaming session
             let mut stream = streaming_manager
@@ -285,8 +317,11 @@ async fn handle_ws_message(
                             role: Some("assistant".to_string()),
                             content: None,
                         },
+                        logprobs: None,
                         finish_reason: None,
                     }],
+                    seed: None,
+                    error: None,
                 };
 
                 let initial_ws_msg = WSMessage::ChatChunk {
@@ -303,6 +338,7 @@ async fn handle_ws_message(
                 }
 
                 // Stream tokens
+                let mut failure = None;
                 while let Some(token_result) = stream.next().await {
                     match token_result {
                         Ok(streaming_token) => {
@@ -318,8 +354,11 @@ async fn handle_ws_message(
                                             role: None,
                                             content: Some(streaming_token.content),
                                         },
+                                        logprobs: None,
                                         finish_reason: None,
                                     }],
+                                    seed: None,
+                                    error: None,
                                 };
 
                                 let ws_msg = WSMessage::ChatChunk {
@@ -356,6 +395,7 @@ async fn handle_ws_message(
                                     .send(Message::Text(error_json))
                                     .await;
                             }
+                            failure = Some(e.to_string());
                             break;
                         }
                     }
@@ -373,8 +413,11 @@ async fn handle_ws_message(
                             role: None,
                             content: None,
                         },
-                        finish_reason: Some("stop".to_string()),
+                        logprobs: None,
+                        finish_reason: Some(openai::finish_reason(&failure).to_string()),
                     }],
+                    seed: None,
+                    error: failure.map(|message| openai::stream_error_detail(false, message)),
                 };
 
                 let final_ws_msg = WSMessage::ChatChunk {
@@ -544,11 +587,14 @@ async fn handle_ws_message(
     }
 }
 
-/// Get or load backend for WebSocket connection
+/// Get or load backend for WebSocket connection, limited to the models the
+/// connection's tenant may use
 async fn get_or_load_backend_for_ws(
     state: &Arc<ServerState>,
+    tenant: Option<&Arc<TenantConfig>>,
     model_name: &str,
 ) -> Result<Arc<tokio::sync::Mutex<Backend>>, InfernoError> {
+    let models = openai::model_manager(state, tenant);
     // Similar to the HTTP API version but optimized for WebSocket
     if let Some(ref _distributed) = state.distributed {
         return Err(InfernoError::WebSocket(
@@ -560,14 +606,27 @@ async fn get_or_load_backend_for_ws(
     if let Some(ref loaded_model) = state.loaded_model {
         if loaded_model == model_name {
             if let Some(ref backend) = state.backend {
+                if let Some(info) = backend.get_model_info().await
+                    && !models.is_visible(&info.path)
+                {
+                    return Err(InfernoError::SecurityValidation(format!(
+                        "Model '{}' belongs to another tenant",
+                        model_name
+                    )));
+                }
+                if !backend.is_loaded().await {
+                    return Err(InfernoError::ModelNotFound(format!(
+                        "The model '{}' is not loaded: it failed to load at startup or was removed from disk",
+                        model_name
+                    )));
+                }
                 return Ok(backend.inner().clone());
             }
         }
     }
 
     // Load new backend for this model
-    let model_info = state
-        .model_manager
+    let model_info = models
         .resolve_model(model_name)
         .await
         .map_err(|e| InfernoError::WebSocket(format!("Model resolution failed: {}", e)))?;
@@ -594,7 +653,13 @@ async fn get_or_load_backend_for_ws(
 fn format_chat_messages(messages: &[ChatMessage]) -> String {
     messages
         .iter()
-        .map(|msg| format!("{}: {}", msg.role, msg.content))
+        .map(|msg| {
+            format!(
+                "{}: {}",
+                msg.role,
+                msg.content.as_deref().unwrap_or_default()
+            )
+        })
         .collect::<Vec<_>>()
         .join("\n")
 }
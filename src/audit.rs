@@ -18,10 +18,12 @@ use lettre::{
 };
 use ring::rand::{SecureRandom, SystemRandom};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{collections::HashMap, io::Write, path::PathBuf, sync::Arc, time::SystemTime};
 use tokio::{
     fs,
-    sync::{RwLock, mpsc},
+    io::AsyncWriteExt,
+    sync::{Mutex, RwLock, mpsc},
     time::interval,
 };
 use tracing::{debug, error, info, warn};
@@ -41,6 +43,9 @@ pub struct AuditEvent {
     pub context: EventContext,
     pub outcome: EventOutcome,
     pub metadata: HashMap<String, serde_json::Value>,
+    /// Link to the previous entry when the log is hash-chained; see [`GENESIS_HASH`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_hash: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -242,6 +247,7 @@ pub struct AuditConfiguration {
     pub enabled: bool,
     pub log_level: LogLevel,
     pub storage_path: PathBuf,
+    /// Size at which the active log segment is rotated
     pub max_file_size_mb: u64,
     pub max_files: u32,
     pub compression_enabled: bool,
@@ -259,6 +265,23 @@ pub struct AuditConfiguration {
     pub alert_on_critical: bool,
     pub alerting: AlertConfiguration,
     pub export_format: ExportFormat,
+    /// Chain each entry to the one before it so edits to the log can be detected
+    #[serde(default)]
+    pub hash_chain: bool,
+    /// Age at which the active log segment is rotated; 0 rotates by size only
+    #[serde(default = "default_rotation_interval_secs")]
+    pub rotation_interval_secs: u64,
+    /// Gzip rotated log segments
+    #[serde(default = "default_compress_rotated")]
+    pub compress_rotated: bool,
+}
+
+fn default_rotation_interval_secs() -> u64 {
+    24 * 3600
+}
+
+fn default_compress_rotated() -> bool {
+    true
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -366,6 +389,9 @@ impl Default for AuditConfiguration {
             alert_on_critical: true,
             alerting: AlertConfiguration::default(),
             export_format: ExportFormat::JsonLines,
+            hash_chain: false,
+            rotation_interval_secs: default_rotation_interval_secs(),
+            compress_rotated: default_compress_rotated(),
         }
     }
 }
@@ -441,6 +467,175 @@ impl Default for SlackConfig {
     }
 }
 
+/// Hash the first entry of a hash-chained audit log links to
+///
+/// With [`AuditConfiguration::hash_chain`] set, every entry stores in `chain_hash`
+/// the hex SHA-256 of the previous entry's `chain_hash` (this genesis hash for the
+/// first entry) followed by the entry itself serialized as JSON with sorted keys
+/// and without its `chain_hash`. Entries stay ordinary JSON records, but editing,
+/// removing or reordering one leaves a stored hash that no longer matches, which
+/// [`verify_chain`] reports.
+///
+/// Pruning old log segments removes the start of the chain, so the hash of the
+/// last pruned entry is kept in [`CHAIN_ANCHOR_FILE`] and the remaining log is
+/// verified from there instead of from genesis.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// File in `storage_path` holding the `chain_hash` of the last pruned entry
+pub const CHAIN_ANCHOR_FILE: &str = "chain_anchor";
+
+/// Compute the `chain_hash` of `event` following an entry whose hash is `previous_hash`
+pub fn chain_hash(previous_hash: &str, event: &AuditEvent) -> Result<String> {
+    let mut value = serde_json::to_value(event)?;
+    if let serde_json::Value::Object(map) = &mut value {
+        map.remove("chain_hash");
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(previous_hash.as_bytes());
+    hasher.update(sort_keys(value).to_string().as_bytes());
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Rebuild objects with their keys in order so `metadata` maps hash the same on
+/// every run
+fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, sort_keys(v)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(sort_keys).collect())
+        }
+        other => other,
+    }
+}
+
+/// First entry of a hash-chained log that does not link to the one before it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainBreak {
+    /// Position of the entry in the log, counting from 0
+    pub position: usize,
+    pub event_id: String,
+    pub reason: String,
+}
+
+/// Walk `events` in log order from [`GENESIS_HASH`] and return the first broken link
+///
+/// Each entry is checked against the hash stored in the entry before it, so a
+/// single edited entry is reported at its own position and not at every later one.
+pub fn verify_chain(events: &[AuditEvent]) -> Result<Option<ChainBreak>> {
+    verify_chain_from(GENESIS_HASH, events)
+}
+
+/// Like [`verify_chain`], for a log whose first entry links to `anchor`
+pub fn verify_chain_from(anchor: &str, events: &[AuditEvent]) -> Result<Option<ChainBreak>> {
+    let mut previous = anchor;
+    for (position, event) in events.iter().enumerate() {
+        let broken = |reason: &str| ChainBreak {
+            position,
+            event_id: event.id.clone(),
+            reason: reason.to_string(),
+        };
+        let Some(stored) = event.chain_hash.as_deref() else {
+            return Ok(Some(broken("entry has no chain hash")));
+        };
+        if chain_hash(previous, event)? != stored {
+            return Ok(Some(broken(
+                "stored hash does not match the entry and the previous link",
+            )));
+        }
+        previous = stored;
+    }
+    Ok(None)
+}
+
+/// Start time in the names of the log segments written by [`AuditLogger`]
+const LOG_FILE_TIME_FORMAT: &str = "%Y%m%d_%H%M%S_%9f";
+
+/// Held while a log segment is appended to, rotated or pruned, so writers never
+/// append to a segment that is being rolled over
+static SEGMENT_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// One file of the audit log
+///
+/// Flushed batches are appended to the newest uncompressed segment,
+/// `audit_<start time>_<sequence>.log`, until it reaches `max_file_size_mb` or
+/// is older than `rotation_interval_secs`. It is then rotated: gzipped to
+/// `.log.gz` when `compress_rotated` is set, and the next flush starts a new
+/// segment. Names sort in write order.
+#[derive(Debug, Clone)]
+struct LogSegment {
+    path: PathBuf,
+    started: DateTime<Utc>,
+    bytes: u64,
+    compressed: bool,
+}
+
+impl LogSegment {
+    /// Segment named `name`, if it is one
+    fn parse(path: PathBuf, name: &str, bytes: u64) -> Option<Self> {
+        let rest = name.strip_prefix("audit_")?;
+        let (stem, compressed) = match rest.strip_suffix(".log.gz") {
+            Some(stem) => (stem, true),
+            None => (rest.strip_suffix(".log")?, false),
+        };
+        let (stamp, _sequence) = stem.rsplit_once('_')?;
+        let started = chrono::NaiveDateTime::parse_from_str(stamp, LOG_FILE_TIME_FORMAT)
+            .ok()?
+            .and_utc();
+        Some(Self {
+            path,
+            started,
+            bytes,
+            compressed,
+        })
+    }
+
+    fn rotation_due(&self, config: &AuditConfiguration) -> bool {
+        let too_big = self.bytes >= config.max_file_size_mb.saturating_mul(1024 * 1024);
+        let too_old = config.rotation_interval_secs > 0
+            && Utc::now().signed_duration_since(self.started).num_seconds()
+                >= config.rotation_interval_secs as i64;
+        self.bytes > 0 && (too_big || too_old)
+    }
+}
+
+/// Size of the active audit log segment and of the rotated ones
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRotationStats {
+    /// Bytes in the segment flushes currently append to
+    pub active_file_bytes: u64,
+    /// Rotated segments still kept
+    pub archive_count: usize,
+    /// Bytes in the rotated segments, compressed or not
+    pub archive_bytes: u64,
+}
+
+/// Replace the segment at `path` with a gzipped `<path>.gz`
+fn gzip_segment(path: &std::path::Path) -> Result<()> {
+    let mut compressed = path.as_os_str().to_owned();
+    compressed.push(".gz");
+    let compressed = PathBuf::from(compressed);
+
+    let mut source = std::fs::File::open(path)?;
+    crate::io::atomic::write_file_with(&compressed, |file| {
+        let mut encoder = GzEncoder::new(file, GzCompression::default());
+        std::io::copy(&mut source, &mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    })?;
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
 pub struct AuditLogger {
     config: AuditConfiguration,
     event_buffer: Arc<RwLock<Vec<AuditEvent>>>,
@@ -449,6 +644,8 @@ pub struct AuditLogger {
     context: EventContext,
     encryption_key: Option<Arc<[u8; 32]>>,
     alert_rate_tracker: Arc<RwLock<HashMap<String, Vec<SystemTime>>>>,
+    /// `chain_hash` of the last entry sent to the log
+    chain_head: Arc<Mutex<String>>,
 }
 
 impl AuditLogger {
@@ -456,6 +653,26 @@ impl AuditLogger {
         // Ensure audit directory exists
         fs::create_dir_all(&config.storage_path).await?;
 
+        // Continue the chain from the newest entry already on disk
+        let chain_head = if config.hash_chain {
+            if matches!(config.export_format, ExportFormat::Csv) {
+                return Err(anyhow::anyhow!(
+                    "Hash-chained audit logs need a JSON export format"
+                ));
+            }
+            match Self::read_logged_events(&config)
+                .await?
+                .iter()
+                .rev()
+                .find_map(|event| event.chain_hash.clone())
+            {
+                Some(head) => head,
+                None => Self::read_chain_anchor(&config).await?,
+            }
+        } else {
+            GENESIS_HASH.to_string()
+        };
+
         let (event_sender, event_receiver) = mpsc::channel::<AuditEvent>(config.batch_size * 2);
 
         let context = EventContext {
@@ -514,6 +731,7 @@ impl AuditLogger {
             context,
             encryption_key,
             alert_rate_tracker: Arc::new(RwLock::new(HashMap::new())),
+            chain_head: Arc::new(Mutex::new(chain_head)),
         };
 
         // Start background processor
@@ -567,11 +785,6 @@ impl AuditLogger {
                             }
                             events_batch.clear();
                         }
-
-                        // Cleanup old files
-                        if let Err(e) = Self::cleanup_old_files(&config).await {
-                            error!("Failed to cleanup old audit files: {}", e);
-                        }
                     }
                 }
             }
@@ -594,9 +807,6 @@ impl AuditLogger {
             return Ok(());
         }
 
-        let filename = format!("audit_{}.log", Utc::now().format("%Y%m%d_%H%M%S"));
-        let filepath = config.storage_path.join(filename);
-
         let content = match config.export_format {
             ExportFormat::Json => serde_json::to_string_pretty(events)?,
             ExportFormat::JsonLines => events
@@ -613,8 +823,6 @@ impl AuditLogger {
             }
         };
 
-        fs::write(&filepath, &content).await?;
-
         let mut final_content = content.into_bytes();
 
         // Apply compression if enabled
@@ -633,12 +841,204 @@ impl AuditLogger {
             }
         }
 
-        fs::write(&filepath, final_content).await?;
+        // Batches share a segment, so binary ones go one per line
+        if Self::batches_are_binary(config) {
+            final_content = general_purpose::STANDARD
+                .encode(&final_content)
+                .into_bytes();
+        }
+        final_content.push(b'\n');
 
-        debug!("Flushed {} audit events to {:?}", events.len(), filepath);
+        let _segments = SEGMENT_LOCK.lock().await;
+        let active = match Self::list_segments(config).await?.pop() {
+            Some(segment) if !segment.compressed && !segment.rotation_due(config) => segment.path,
+            Some(segment) => {
+                if !segment.compressed {
+                    Self::rotate_segment(config, segment).await?;
+                }
+                Self::create_log_file(config).await?
+            }
+            None => Self::create_log_file(config).await?,
+        };
+        let mut file = fs::OpenOptions::new().append(true).open(&active).await?;
+        file.write_all(&final_content).await?;
+        file.flush().await?;
+        debug!("Flushed {} audit events to {:?}", events.len(), active);
+
+        // Roll the segment over as soon as it is full, rather than on the next flush
+        let bytes = file.metadata().await?.len();
+        if let Some(segment) = Self::segment_at(active, bytes)
+            && segment.rotation_due(config)
+        {
+            Self::rotate_segment(config, segment).await?;
+        }
+
+        if let Err(e) = Self::cleanup_old_files(config).await {
+            error!("Failed to cleanup old audit files: {}", e);
+        }
         Ok(())
     }
 
+    /// Whether flushed batches are compressed or encrypted bytes rather than text
+    fn batches_are_binary(config: &AuditConfiguration) -> bool {
+        config.compression_enabled || config.encryption_enabled
+    }
+
+    fn segment_at(path: PathBuf, bytes: u64) -> Option<LogSegment> {
+        let name = path.file_name()?.to_string_lossy().to_string();
+        LogSegment::parse(path, &name, bytes)
+    }
+
+    /// Every log segment in `storage_path`, oldest first
+    async fn list_segments(config: &AuditConfiguration) -> Result<Vec<LogSegment>> {
+        let mut segments = Vec::new();
+        let mut entries = fs::read_dir(&config.storage_path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let bytes = entry.metadata().await?.len();
+            if let Some(segment) = LogSegment::parse(entry.path(), &name, bytes) {
+                segments.push(segment);
+            }
+        }
+        segments.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(segments)
+    }
+
+    /// Close `segment` to further writes, gzipping it if `compress_rotated` is set
+    ///
+    /// An uncompressed segment stays due for rotation, so the next flush starts
+    /// a new one after it either way.
+    async fn rotate_segment(config: &AuditConfiguration, segment: LogSegment) -> Result<()> {
+        if config.compress_rotated {
+            let source = segment.path.clone();
+            tokio::task::spawn_blocking(move || gzip_segment(&source)).await??;
+            info!("Rotated audit log segment {:?}", segment.path);
+        }
+        Ok(())
+    }
+
+    /// Size of the active segment and of the rotated ones
+    pub async fn rotation_stats(&self) -> Result<AuditRotationStats> {
+        let _segments = SEGMENT_LOCK.lock().await;
+        let mut segments = Self::list_segments(&self.config).await?;
+        let mut stats = AuditRotationStats::default();
+        if segments
+            .last()
+            .is_some_and(|segment| !segment.compressed && !segment.rotation_due(&self.config))
+        {
+            stats.active_file_bytes = segments.pop().map_or(0, |segment| segment.bytes);
+        }
+        stats.archive_count = segments.len();
+        stats.archive_bytes = segments.iter().map(|segment| segment.bytes).sum();
+        Ok(stats)
+    }
+
+    /// Create a new log segment named for the current time; names sort in write
+    /// order and a file that already exists is never overwritten
+    async fn create_log_file(config: &AuditConfiguration) -> Result<PathBuf> {
+        let stamp = Utc::now().format(LOG_FILE_TIME_FORMAT);
+        for sequence in 0u32.. {
+            let filepath = config
+                .storage_path
+                .join(format!("audit_{}_{:04}.log", stamp, sequence));
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&filepath)
+                .await
+            {
+                Ok(_) => return Ok(filepath),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        unreachable!("every audit log file name is taken")
+    }
+
+    /// Read back every entry written to `storage_path`, oldest file first
+    pub async fn read_log_events(&self) -> Result<Vec<AuditEvent>> {
+        Self::read_logged_events(&self.config).await
+    }
+
+    /// Hash the oldest entry still in the log links to: the last pruned entry's,
+    /// or [`GENESIS_HASH`] if nothing has been pruned
+    pub async fn chain_anchor(&self) -> Result<String> {
+        Self::read_chain_anchor(&self.config).await
+    }
+
+    async fn read_chain_anchor(config: &AuditConfiguration) -> Result<String> {
+        match fs::read_to_string(config.storage_path.join(CHAIN_ANCHOR_FILE)).await {
+            Ok(anchor) => Ok(anchor.trim().to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(GENESIS_HASH.to_string()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn read_logged_events(config: &AuditConfiguration) -> Result<Vec<AuditEvent>> {
+        let mut events = Vec::new();
+        for segment in Self::list_segments(config).await? {
+            events.extend(Self::read_segment(config, &segment).await?);
+        }
+        Ok(events)
+    }
+
+    /// Entries of one log segment, in write order
+    async fn read_segment(
+        config: &AuditConfiguration,
+        segment: &LogSegment,
+    ) -> Result<Vec<AuditEvent>> {
+        let mut content = fs::read(&segment.path).await?;
+        if segment.compressed {
+            content = Self::decompress_data(&content, &CompressionMethod::Gzip)?;
+        }
+        if !Self::batches_are_binary(config) {
+            return Self::parse_batch(config, &String::from_utf8(content)?);
+        }
+
+        let mut events = Vec::new();
+        for line in String::from_utf8(content)?.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut batch = general_purpose::STANDARD.decode(line.trim())?;
+            if config.encryption_enabled {
+                if let Some(key) = &Self::get_encryption_key(&config.encryption_key_env)? {
+                    batch = Self::decrypt_data(&batch, key)?;
+                }
+            }
+            if config.compression_enabled {
+                batch = Self::decompress_data(&batch, &config.compression_method)?;
+            }
+            events.extend(Self::parse_batch(config, &String::from_utf8(batch)?)?);
+        }
+        Ok(events)
+    }
+
+    /// Entries of flushed batches in the export format
+    fn parse_batch(config: &AuditConfiguration, content: &str) -> Result<Vec<AuditEvent>> {
+        match config.export_format {
+            // Each batch is one array, and a segment holds several
+            ExportFormat::Json => {
+                let mut events = Vec::new();
+                for batch in
+                    serde_json::Deserializer::from_str(content).into_iter::<Vec<AuditEvent>>()
+                {
+                    events.extend(batch?);
+                }
+                Ok(events)
+            }
+            ExportFormat::JsonLines => content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| serde_json::from_str(line).map_err(Into::into))
+                .collect(),
+            _ => Err(anyhow::anyhow!(
+                "Cannot read back audit logs in {:?} format",
+                config.export_format
+            )),
+        }
+    }
+
     fn events_to_csv(events: &[AuditEvent]) -> Result<String> {
         let mut csv = String::new();
         csv.push_str("timestamp,event_type,severity,actor,resource,action,success,duration_ms\n");
@@ -663,42 +1063,48 @@ impl AuditLogger {
         Ok(csv)
     }
 
+    /// Delete rotated segments started more than `retention_days` ago, then the
+    /// oldest of the rest beyond `max_files`; the newest segment is always kept
+    ///
+    /// With `hash_chain` set, the hash of the last entry pruned is recorded in
+    /// [`CHAIN_ANCHOR_FILE`] first, and nothing is pruned if that fails.
     async fn cleanup_old_files(config: &AuditConfiguration) -> Result<()> {
-        let mut entries = fs::read_dir(&config.storage_path).await?;
-        let mut files = Vec::new();
+        let mut segments = Self::list_segments(config).await?;
+        segments.pop();
 
-        while let Some(entry) = entries.next_entry().await? {
-            if let Ok(metadata) = entry.metadata().await {
-                if metadata.is_file() {
-                    if let Ok(modified) = metadata.modified() {
-                        files.push((entry.path(), modified));
-                    }
+        let cutoff = Utc::now() - chrono::Duration::days(i64::from(config.retention_days));
+        let expired = segments
+            .iter()
+            .take_while(|segment| segment.started < cutoff)
+            .count();
+        let kept = (config.max_files as usize).saturating_sub(1);
+        let excess = segments.len().saturating_sub(kept);
+        let pruned = &segments[..expired.max(excess)];
+
+        if config.hash_chain {
+            let mut anchor = None;
+            for segment in pruned.iter().rev() {
+                anchor = Self::read_segment(config, segment)
+                    .await?
+                    .into_iter()
+                    .rev()
+                    .find_map(|event| event.chain_hash);
+                if anchor.is_some() {
+                    break;
                 }
             }
-        }
-
-        // Sort by modification time (oldest first)
-        files.sort_by_key(|(_, modified)| *modified);
-
-        // Remove files older than retention period
-        let cutoff = SystemTime::now()
-            - std::time::Duration::from_secs(config.retention_days as u64 * 24 * 3600);
-
-        for (path, modified) in &files {
-            if *modified < cutoff {
-                if let Err(e) = fs::remove_file(path).await {
-                    warn!("Failed to remove old audit file {:?}: {}", path, e);
-                }
+            if let Some(anchor) = anchor {
+                crate::io::atomic::write_file_async(
+                    &config.storage_path.join(CHAIN_ANCHOR_FILE),
+                    anchor,
+                )
+                .await?;
             }
         }
 
-        // Remove excess files if we have too many
-        if files.len() > config.max_files as usize {
-            let excess_count = files.len() - config.max_files as usize;
-            for (path, _) in files.iter().take(excess_count) {
-                if let Err(e) = fs::remove_file(path).await {
-                    warn!("Failed to remove excess audit file {:?}: {}", path, e);
-                }
+        for segment in pruned {
+            if let Err(e) = fs::remove_file(&segment.path).await {
+                warn!("Failed to remove old audit file {:?}: {}", segment.path, e);
             }
         }
 
@@ -730,9 +1136,18 @@ impl AuditLogger {
             event.id = Uuid::new_v4().to_string();
         }
 
-        // Send to background processor
-        if let Err(e) = self.event_sender.send(event.clone()).await {
-            error!("Failed to send audit event to processor: {}", e);
+        // Send to background processor, holding the chain head so entries reach
+        // the log in the order they were chained
+        {
+            let mut chain_head = self.chain_head.lock().await;
+            if self.config.hash_chain {
+                let hash = chain_hash(&chain_head, &event)?;
+                event.chain_hash = Some(hash.clone());
+                *chain_head = hash;
+            }
+            if let Err(e) = self.event_sender.send(event.clone()).await {
+                error!("Failed to send audit event to processor: {}", e);
+            }
         }
 
         // Add to buffer for immediate queries
@@ -2355,6 +2770,7 @@ macro_rules! audit_info {
                     records_affected: None,
                 },
                 metadata: HashMap::new(),
+                chain_hash: None,
             })
             .await
     };
@@ -2401,6 +2817,7 @@ macro_rules! audit_error {
                     records_affected: None,
                 },
                 metadata: HashMap::new(),
+                chain_hash: None,
             })
             .await
     };
@@ -2490,6 +2907,7 @@ mod tests {
                 records_affected: None,
             },
             metadata: HashMap::new(),
+            chain_hash: None,
         };
 
         logger
@@ -2948,6 +3366,7 @@ mod tests {
                 records_affected: None,
             },
             metadata: HashMap::new(),
+            chain_hash: None,
         }
     }
 
@@ -3031,6 +3450,7 @@ mod tests {
                 records_affected: None,
             },
             metadata: HashMap::new(),
+            chain_hash: None,
         };
 
         logger
@@ -3058,4 +3478,275 @@ mod tests {
         }
         assert!(found_file);
     }
+
+    #[tokio::test]
+    async fn test_hash_chain_verify_flags_tampered_entry() {
+        let temp_dir = tempdir().unwrap();
+        let config = AuditConfiguration {
+            log_level: LogLevel::All,
+            storage_path: temp_dir.path().to_path_buf(),
+            compression_enabled: false,
+            batch_size: 5,
+            hash_chain: true,
+            ..Default::default()
+        };
+        let logger = AuditLogger::new(config).await.unwrap();
+
+        for i in 0..5 {
+            let event = create_test_event(
+                &format!("event-{}", i),
+                EventType::UserAction,
+                Severity::Info,
+                true,
+                Some(100),
+            );
+            logger.log_event(event).await.unwrap();
+        }
+
+        // The full batch is flushed in the background
+        let mut events = Vec::new();
+        for _ in 0..100 {
+            // A read can race the write, so retry until the whole batch parses
+            if let Ok(logged) = logger.read_log_events().await {
+                events = logged;
+                if events.len() == 5 {
+                    break;
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(events.len(), 5);
+        assert_eq!(verify_chain(&events).unwrap(), None);
+
+        // Rewrite the action of the middle entry in place
+        let path = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.extension().is_some_and(|ext| ext == "log"))
+            .unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let mut entry: serde_json::Value = serde_json::from_str(&lines[2]).unwrap();
+        entry["action"] = serde_json::Value::String("nothing happened".to_string());
+        lines[2] = entry.to_string();
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let events = logger.read_log_events().await.unwrap();
+        let broken = verify_chain(&events)
+            .unwrap()
+            .expect("tampering is detected");
+        assert_eq!(broken.position, 2);
+        assert_eq!(broken.event_id, "event-2");
+
+        logger.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_flushes_in_the_same_second_keep_every_batch() {
+        let temp_dir = tempdir().unwrap();
+        let config = AuditConfiguration {
+            storage_path: temp_dir.path().to_path_buf(),
+            compression_enabled: false,
+            ..Default::default()
+        };
+        let batches: Vec<Vec<AuditEvent>> = (0..3)
+            .map(|batch| {
+                vec![create_test_event(
+                    &format!("event-{}", batch),
+                    EventType::UserAction,
+                    Severity::Info,
+                    true,
+                    None,
+                )]
+            })
+            .collect();
+        for batch in &batches {
+            AuditLogger::flush_events(&config, batch).await.unwrap();
+        }
+
+        let ids: Vec<String> = AuditLogger::read_logged_events(&config)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| event.id)
+            .collect();
+        assert_eq!(ids, ["event-0", "event-1", "event-2"]);
+    }
+
+    /// Write `events` as a JSON-lines segment started at `started`
+    fn write_segment(
+        dir: &std::path::Path,
+        started: DateTime<Utc>,
+        events: &[AuditEvent],
+    ) -> PathBuf {
+        let path = dir.join(format!(
+            "audit_{}_0000.log",
+            started.format(LOG_FILE_TIME_FORMAT)
+        ));
+        let content: String = events
+            .iter()
+            .map(|event| format!("{}\n", serde_json::to_string(event).unwrap()))
+            .collect();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn logged_ids(events: Vec<AuditEvent>) -> Vec<String> {
+        events.into_iter().map(|event| event.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_flush_prunes_expired_and_excess_files() {
+        let temp_dir = tempdir().unwrap();
+        let config = AuditConfiguration {
+            storage_path: temp_dir.path().to_path_buf(),
+            compression_enabled: false,
+            retention_days: 30,
+            max_files: 3,
+            ..Default::default()
+        };
+        let expired = temp_dir
+            .path()
+            .join("audit_20000101_000000_000000000_0000.log");
+        std::fs::write(&expired, b"").unwrap();
+        let unrelated = temp_dir.path().join("notes.txt");
+        std::fs::write(&unrelated, b"").unwrap();
+        for batch in 0..3 {
+            let event = create_test_event(
+                &format!("event-{}", batch),
+                EventType::UserAction,
+                Severity::Info,
+                true,
+                None,
+            );
+            let started = Utc::now() - chrono::Duration::hours(4 - batch);
+            write_segment(temp_dir.path(), started, &[event]);
+        }
+
+        // Appended to the newest segment, which is not yet due for rotation
+        let event = create_test_event("event-3", EventType::UserAction, Severity::Info, true, None);
+        AuditLogger::flush_events(&config, &[event]).await.unwrap();
+
+        assert!(!expired.exists());
+        assert!(unrelated.exists());
+        let events = AuditLogger::read_logged_events(&config).await.unwrap();
+        assert_eq!(logged_ids(events), ["event-1", "event-2", "event-3"]);
+    }
+
+    #[tokio::test]
+    async fn test_pruned_chain_verifies_from_its_anchor() {
+        let temp_dir = tempdir().unwrap();
+        let config = AuditConfiguration {
+            storage_path: temp_dir.path().to_path_buf(),
+            compression_enabled: false,
+            max_files: 2,
+            hash_chain: true,
+            ..Default::default()
+        };
+        let mut previous = GENESIS_HASH.to_string();
+        let events: Vec<AuditEvent> = (0..4)
+            .map(|i| {
+                let mut event = create_test_event(
+                    &format!("event-{}", i),
+                    EventType::UserAction,
+                    Severity::Info,
+                    true,
+                    None,
+                );
+                event.chain_hash = Some(chain_hash(&previous, &event).unwrap());
+                previous = event.chain_hash.clone().unwrap();
+                event
+            })
+            .collect();
+        for (hours_ago, event) in (1..=3).rev().zip(&events[..3]) {
+            let started = Utc::now() - chrono::Duration::hours(hours_ago);
+            write_segment(temp_dir.path(), started, std::slice::from_ref(event));
+        }
+
+        // Two segments are kept, so the one holding event-0 is pruned
+        AuditLogger::flush_events(&config, &events[3..])
+            .await
+            .unwrap();
+
+        let logger = AuditLogger::new(config).await.unwrap();
+        let logged = logger.read_log_events().await.unwrap();
+        assert_eq!(
+            logged_ids(logged.clone()),
+            ["event-1", "event-2", "event-3"]
+        );
+        let anchor = logger.chain_anchor().await.unwrap();
+        assert_eq!(Some(anchor.as_str()), events[0].chain_hash.as_deref());
+        assert_eq!(verify_chain_from(&anchor, &logged).unwrap(), None);
+        assert_eq!(verify_chain(&logged).unwrap().unwrap().position, 0);
+        logger.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_segment_rotates_once_it_reaches_the_size_limit() {
+        let temp_dir = tempdir().unwrap();
+        let config = AuditConfiguration {
+            storage_path: temp_dir.path().to_path_buf(),
+            compression_enabled: false,
+            max_file_size_mb: 1,
+            ..Default::default()
+        };
+        let logger = AuditLogger::new(config.clone()).await.unwrap();
+
+        let mut large =
+            create_test_event("large", EventType::UserAction, Severity::Info, true, None);
+        large.details.description = "x".repeat(1024 * 1024);
+        AuditLogger::flush_events(&config, &[large]).await.unwrap();
+
+        let rotated: Vec<PathBuf> = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(rotated.len(), 1);
+        assert!(rotated[0].to_string_lossy().ends_with(".log.gz"));
+        let stats = logger.rotation_stats().await.unwrap();
+        assert_eq!(stats.active_file_bytes, 0);
+        assert_eq!(stats.archive_count, 1);
+        assert!(stats.archive_bytes > 0 && stats.archive_bytes < 1024 * 1024);
+
+        let small = create_test_event("small", EventType::UserAction, Severity::Info, true, None);
+        AuditLogger::flush_events(&config, &[small]).await.unwrap();
+
+        let stats = logger.rotation_stats().await.unwrap();
+        assert!(stats.active_file_bytes > 0);
+        assert_eq!(stats.archive_count, 1);
+        let events = AuditLogger::read_logged_events(&config).await.unwrap();
+        assert_eq!(logged_ids(events), ["large", "small"]);
+
+        logger.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_segment_rotates_once_it_reaches_the_age_limit() {
+        let temp_dir = tempdir().unwrap();
+        let config = AuditConfiguration {
+            storage_path: temp_dir.path().to_path_buf(),
+            compression_enabled: false,
+            rotation_interval_secs: 3600,
+            ..Default::default()
+        };
+        let old = create_test_event("old", EventType::UserAction, Severity::Info, true, None);
+        let started = Utc::now() - chrono::Duration::hours(2);
+        let segment = write_segment(temp_dir.path(), started, &[old]);
+
+        let new = create_test_event("new", EventType::UserAction, Severity::Info, true, None);
+        AuditLogger::flush_events(&config, &[new]).await.unwrap();
+
+        assert!(!segment.exists());
+        let mut rotated = segment.into_os_string();
+        rotated.push(".gz");
+        assert!(PathBuf::from(rotated).exists());
+        let events = AuditLogger::read_logged_events(&config).await.unwrap();
+        assert_eq!(logged_ids(events), ["old", "new"]);
+
+        let logger = AuditLogger::new(config).await.unwrap();
+        let stats = logger.rotation_stats().await.unwrap();
+        assert!(stats.active_file_bytes > 0);
+        assert_eq!(stats.archive_count, 1);
+        logger.shutdown().await;
+    }
 }
@@ -0,0 +1,184 @@
+//! Pool for blocking backend work
+//!
+//! Model loading, tokenization and generation call into native libraries that block
+//! the calling thread. Backends run that work through [`run`] or [`spawn`], which
+//! hand it to tokio's blocking threads but cap how many calls run at once, so a burst
+//! of inference requests cannot occupy every blocking thread and the async runtime
+//! keeps serving health checks, metrics and streaming responses. Calls over the cap
+//! wait asynchronously for a slot.
+//!
+//! Tokenization is quick but stands in front of every request, so it runs through
+//! [`run_tokenization`] on slots of its own and never queues behind generation.
+
+use std::sync::{Arc, OnceLock};
+use tokio::sync::Semaphore;
+use tokio::task::JoinError;
+use tracing::{debug, warn};
+
+static POOL: OnceLock<BlockingPool> = OnceLock::new();
+static TOKENIZATION_POOL: OnceLock<BlockingPool> = OnceLock::new();
+
+/// Bound on concurrent blocking backend calls
+#[derive(Debug, Clone)]
+pub struct BlockingPool {
+    permits: Arc<Semaphore>,
+    size: usize,
+}
+
+impl BlockingPool {
+    pub fn new(size: usize) -> Self {
+        let size = size.max(1);
+        Self {
+            permits: Arc::new(Semaphore::new(size)),
+            size,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Slots not taken by a running call
+    pub fn available(&self) -> usize {
+        self.permits.available_permits()
+    }
+
+    /// Run `f` on a blocking thread once a slot is free
+    ///
+    /// The slot is held until `f` returns, even if the caller stops waiting.
+    pub async fn run<F, T>(&self, f: F) -> Result<T, JoinError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("blocking pool semaphore is never closed");
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            f()
+        })
+        .await
+    }
+
+    /// Run `f` on a blocking thread once a slot is free, without waiting for it
+    pub fn spawn<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = pool.run(f).await {
+                warn!("Blocking backend task failed: {}", e);
+            }
+        });
+    }
+}
+
+impl Default for BlockingPool {
+    fn default() -> Self {
+        Self::new(default_size())
+    }
+}
+
+/// One slot per CPU
+pub fn default_size() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+/// Size the process-wide pool, and the tokenization slots beside it; `None`
+/// uses [`default_size`]
+///
+/// Only the first call before any backend work takes effect, so call this at
+/// startup. Returns whether the pool was configured.
+pub fn configure(size: Option<usize>) -> bool {
+    let size = size.unwrap_or_else(default_size);
+    let configured = POOL.set(BlockingPool::new(size)).is_ok();
+    if configured {
+        let _ = TOKENIZATION_POOL.set(BlockingPool::new(size));
+        debug!("Blocking backend pool sized to {} threads", size.max(1));
+    }
+    configured
+}
+
+/// The process-wide pool backends run blocking work on
+pub fn pool() -> &'static BlockingPool {
+    POOL.get_or_init(BlockingPool::default)
+}
+
+/// The slots tokenization runs on, apart from [`pool`]
+pub fn tokenization_pool() -> &'static BlockingPool {
+    TOKENIZATION_POOL.get_or_init(BlockingPool::default)
+}
+
+/// Run `f` on the process-wide pool
+pub async fn run<F, T>(f: F) -> Result<T, JoinError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    pool().run(f).await
+}
+
+/// Start `f` on the process-wide pool without waiting for it
+pub fn spawn<F>(f: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    pool().spawn(f)
+}
+
+/// Run tokenization `f` without waiting behind other blocking calls
+pub async fn run_tokenization<F, T>(f: F) -> Result<T, JoinError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    tokenization_pool().run(f).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_tokenization_runs_while_the_pool_is_saturated() {
+        // Fill every slot of the shared pool until released
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let released = Arc::new(std::sync::Mutex::new(released));
+        let calls: Vec<_> = (0..pool().size())
+            .map(|_| {
+                let released = released.clone();
+                tokio::spawn(run(move || {
+                    let _ = released.lock().unwrap().recv();
+                }))
+            })
+            .collect();
+        // One more call queues for a slot
+        let queued = tokio::spawn(run(|| ()));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(pool().available(), 0);
+        assert!(!queued.is_finished());
+
+        let tokens = tokio::time::timeout(
+            Duration::from_secs(1),
+            run_tokenization(|| "hello world".split(' ').count()),
+        )
+        .await
+        .expect("tokenization waited for the saturated pool")
+        .unwrap();
+        assert_eq!(tokens, 2);
+
+        drop(release);
+        for call in calls {
+            call.await.unwrap().unwrap();
+        }
+        queued.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+//! Fitting prompts into the model's context window
+//!
+//! A prompt plus the `max_tokens` reserved for its completion has to fit in the
+//! context the model was loaded with. Rather than letting the backend fail part
+//! way through evaluating an over-long prompt, [`fit_prompt`] checks it up front
+//! and, depending on `context_policy` in the backend config, rejects it with a
+//! [`InfernoError::Validation`] or cuts it down to size.
+
+use crate::{
+    InfernoError,
+    backends::tokenizer::{self, Tokenizer},
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use tracing::debug;
+
+/// What to do with a prompt that does not fit the context window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextPolicy {
+    /// Reject the request
+    #[default]
+    Error,
+    /// Drop the start of the prompt, keeping the most recent text
+    TruncateHead,
+    /// Drop the middle, keeping the instructions at the start and the latest text
+    TruncateMiddle,
+}
+
+/// The prompt to run in a `context_length`-token context with room for
+/// `max_tokens` of completion
+///
+/// Tokens are counted with `tokenizer`, or estimated without one.
+pub fn fit_prompt<'a>(
+    prompt: &'a str,
+    max_tokens: u32,
+    context_length: u32,
+    policy: ContextPolicy,
+    tokenizer: Option<&dyn Tokenizer>,
+) -> Result<Cow<'a, str>, InfernoError> {
+    let count = |text: &str| tokenizer::count_tokens(tokenizer, text);
+
+    let Some(budget) = context_length.checked_sub(max_tokens).filter(|&b| b > 0) else {
+        return Err(InfernoError::Validation(format!(
+            "max_tokens ({}) leaves no room for a prompt in the model's {}-token context",
+            max_tokens, context_length
+        )));
+    };
+    let prompt_tokens = count(prompt);
+    if prompt_tokens <= budget {
+        return Ok(Cow::Borrowed(prompt));
+    }
+
+    let fitted = match policy {
+        ContextPolicy::Error => {
+            return Err(InfernoError::Validation(format!(
+                "Prompt is {} tokens, but the model's {}-token context only has room for {} \
+                 once {} are reserved for max_tokens",
+                prompt_tokens, context_length, budget, max_tokens
+            )));
+        }
+        ContextPolicy::TruncateHead => longest_suffix(prompt, budget, &count).to_string(),
+        ContextPolicy::TruncateMiddle => {
+            let head = longest_prefix(prompt, budget / 2, &count);
+            let tail = longest_suffix(&prompt[head.len()..], budget - count(head), &count);
+            format!("{}{}", head, tail)
+        }
+    };
+    debug!(
+        "Truncated a {}-token prompt to fit a {}-token budget ({:?})",
+        prompt_tokens, budget, policy
+    );
+    Ok(Cow::Owned(fitted))
+}
+
+/// Longest start of `text` that is at most `budget` tokens
+fn longest_prefix<'a>(text: &'a str, budget: u32, count: &dyn Fn(&str) -> u32) -> &'a str {
+    let ends: Vec<usize> = text
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(text.len()))
+        .collect();
+    // The empty prefix always fits, so at least one end does
+    let fitting = ends.partition_point(|&end| count(&text[..end]) <= budget);
+    &text[..ends[fitting - 1]]
+}
+
+/// Longest end of `text` that is at most `budget` tokens
+fn longest_suffix<'a>(text: &'a str, budget: u32, count: &dyn Fn(&str) -> u32) -> &'a str {
+    let starts: Vec<usize> = text
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(text.len()))
+        .collect();
+    let first_fitting = starts.partition_point(|&start| count(&text[start..]) > budget);
+    &text[starts[first_fitting]..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::mock::MockBackend;
+    use crate::backends::{Backend, InferenceParams};
+    use anyhow::Result;
+
+    /// One token per character
+    struct CharTokenizer;
+
+    impl Tokenizer for CharTokenizer {
+        fn count_tokens(&self, text: &str) -> Result<usize> {
+            Ok(text.chars().count())
+        }
+    }
+
+    /// A model with a 64-token context that echoes the prompt it was given
+    fn backend(policy: ContextPolicy) -> Backend {
+        MockBackend::prompt(str::to_string)
+            .tokenizer(CharTokenizer)
+            .context_length(64)
+            .backend()
+            .with_context_policy(policy)
+    }
+
+    /// 100 characters, so 100 tokens: "000000000011111..."
+    fn long_prompt() -> String {
+        (0..10).map(|digit| digit.to_string().repeat(10)).collect()
+    }
+
+    fn params() -> InferenceParams {
+        InferenceParams {
+            max_tokens: 24,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_policy_rejects_long_prompt() {
+        let err = backend(ContextPolicy::Error)
+            .infer(&long_prompt(), &params())
+            .await
+            .unwrap_err();
+        match err.downcast_ref::<InfernoError>() {
+            Some(InfernoError::Validation(message)) => {
+                assert!(message.contains("100 tokens"), "{}", message);
+                assert!(message.contains("64-token context"), "{}", message);
+                assert!(message.contains("room for 40"), "{}", message);
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+
+        // Prompts that fit are left alone
+        let reply = backend(ContextPolicy::Error)
+            .infer("short", &params())
+            .await
+            .unwrap();
+        assert_eq!(reply, "short");
+    }
+
+    #[tokio::test]
+    async fn test_truncate_head_keeps_latest_text() {
+        let reply = backend(ContextPolicy::TruncateHead)
+            .infer(&long_prompt(), &params())
+            .await
+            .unwrap();
+        assert_eq!(reply.len(), 40);
+        assert_eq!(reply, long_prompt()[60..]);
+    }
+
+    #[tokio::test]
+    async fn test_truncate_middle_keeps_both_ends() {
+        let mut stream = backend(ContextPolicy::TruncateMiddle)
+            .infer_stream(&long_prompt(), &params())
+            .await
+            .unwrap();
+        let reply = futures::StreamExt::next(&mut stream)
+            .await
+            .unwrap()
+            .unwrap();
+        let prompt = long_prompt();
+        assert_eq!(reply.len(), 40);
+        assert_eq!(&reply[..20], &prompt[..20]);
+        assert_eq!(&reply[20..], &prompt[80..]);
+    }
+
+    #[test]
+    fn test_max_tokens_must_leave_room_for_prompt() {
+        let err = fit_prompt(
+            "hi",
+            64,
+            64,
+            ContextPolicy::TruncateHead,
+            Some(&CharTokenizer),
+        )
+        .unwrap_err();
+        assert!(matches!(err, InfernoError::Validation(_)));
+    }
+}
@@ -0,0 +1,113 @@
+//! Shaping embeddings before they are returned
+//!
+//! Backends return the raw vector the model produces. [`EmbeddingOptions`] can
+//! L2-normalize it, so cosine similarity becomes a dot product, and shorten it to
+//! its first `dimensions` values. Truncation only keeps quality for models trained
+//! Matryoshka-style, which front-load the information into the leading values;
+//! normalize as well so the shortened vectors are unit length again.
+//!
+//! Defaults come from `[server.embeddings]` and the OpenAI `dimensions` request
+//! field overrides the configured dimension.
+
+use crate::InfernoError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingOptions {
+    /// Scale vectors to unit length
+    pub normalize: bool,
+    /// Keep only the first this many values; `None` keeps the model's dimension
+    pub dimensions: Option<usize>,
+}
+
+impl EmbeddingOptions {
+    /// These options with `dimensions` taking the place of the configured one
+    pub fn with_dimensions(mut self, dimensions: Option<usize>) -> Self {
+        if dimensions.is_some() {
+            self.dimensions = dimensions;
+        }
+        self
+    }
+
+    /// Truncate and normalize `embedding` as configured
+    ///
+    /// Fails with [`InfernoError::Validation`] when more dimensions are asked for
+    /// than the model produces.
+    pub fn apply(&self, mut embedding: Vec<f32>) -> Result<Vec<f32>, InfernoError> {
+        if let Some(dimensions) = self.dimensions {
+            if dimensions == 0 || dimensions > embedding.len() {
+                return Err(InfernoError::Validation(format!(
+                    "dimensions must be between 1 and the model's embedding size of {}, got {}",
+                    embedding.len(),
+                    dimensions
+                )));
+            }
+            embedding.truncate(dimensions);
+        }
+        if self.normalize {
+            l2_normalize(&mut embedding);
+        }
+        Ok(embedding)
+    }
+}
+
+/// Scale `vector` to unit length; an all-zero vector is left as is
+pub fn l2_normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn norm(vector: &[f32]) -> f32 {
+        vector.iter().map(|x| x * x).sum::<f32>().sqrt()
+    }
+
+    #[test]
+    fn test_normalized_embeddings_have_unit_norm() {
+        let options = EmbeddingOptions {
+            normalize: true,
+            dimensions: None,
+        };
+        let embedding = options.apply(vec![3.0, 4.0, 0.0, -12.0]).unwrap();
+        assert_eq!(embedding.len(), 4);
+        assert!((norm(&embedding) - 1.0).abs() < 1e-6);
+        assert!((embedding[0] - 3.0 / 13.0).abs() < 1e-6);
+
+        // Zero vectors have no direction to keep
+        assert_eq!(options.apply(vec![0.0; 3]).unwrap(), vec![0.0; 3]);
+    }
+
+    #[test]
+    fn test_truncation_returns_requested_dimension() {
+        let embedding: Vec<f32> = (1..=8).map(|x| x as f32).collect();
+        let options = EmbeddingOptions {
+            normalize: true,
+            dimensions: Some(8),
+        }
+        .with_dimensions(Some(3));
+        let truncated = options.apply(embedding.clone()).unwrap();
+        assert_eq!(truncated.len(), 3);
+        assert!((norm(&truncated) - 1.0).abs() < 1e-6);
+
+        // Without normalization the leading values are kept unchanged
+        let raw = EmbeddingOptions::default()
+            .with_dimensions(Some(2))
+            .apply(embedding.clone())
+            .unwrap();
+        assert_eq!(raw, [1.0, 2.0]);
+
+        for dimensions in [0, 9] {
+            let err = EmbeddingOptions::default()
+                .with_dimensions(Some(dimensions))
+                .apply(embedding.clone())
+                .unwrap_err();
+            assert!(matches!(err, InfernoError::Validation(_)));
+        }
+    }
+}
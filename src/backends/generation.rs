@@ -0,0 +1,141 @@
+//! Finished generations and the limits that end them early
+//!
+//! Besides `max_tokens`, generation ends when the output matches
+//! [`InferenceParams::stop_regex`] or once [`InferenceParams::max_generation_ms`]
+//! has passed. [`Backend::generate`](super::Backend::generate) reports which limit
+//! ended it as a [`FinishReason`].
+
+use super::{InferenceParams, StopRegex, TokenLogprob, TokenStream};
+use crate::InfernoError;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Why generation ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// The model finished, or the output matched a stop sequence or `stop_regex`
+    Stop,
+    /// `max_tokens` were generated
+    Length,
+    /// `max_generation_ms` passed first
+    TimeLimit,
+}
+
+impl FinishReason {
+    /// `finish_reason` in OpenAI responses, which report a time limit as `length`
+    pub fn as_openai_str(&self) -> &'static str {
+        match self {
+            Self::Stop => "stop",
+            Self::Length | Self::TimeLimit => "length",
+        }
+    }
+}
+
+/// Output of a finished generation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Generation {
+    pub text: String,
+    pub finish_reason: FinishReason,
+    /// Seed the sampler used; sending it back with the same params reproduces the text
+    pub seed: u64,
+    /// Per-token log probabilities, when requested and the backend can report them
+    pub logprobs: Option<Vec<TokenLogprob>>,
+}
+
+/// When generation started now must stop under `max_generation_ms`
+pub fn deadline(params: &InferenceParams) -> Option<Instant> {
+    params
+        .max_generation_ms
+        .map(|ms| Instant::now() + Duration::from_millis(ms))
+}
+
+/// End `stream` once `deadline` passes
+pub fn with_deadline(stream: TokenStream, deadline: Instant) -> TokenStream {
+    Box::pin(stream.take_until(tokio::time::sleep_until(deadline)))
+}
+
+/// Apply `stop` and `deadline` to a generation a backend returned whole along
+/// with its logprobs, cutting the tokens where the text is cut
+pub(crate) fn limit_logprobs(
+    mut text: String,
+    mut logprobs: Vec<TokenLogprob>,
+    stop: Option<&StopRegex>,
+    deadline: Option<Instant>,
+    max_tokens: u32,
+) -> (String, Vec<TokenLogprob>, FinishReason) {
+    if let Some(start) = stop.and_then(|stop| stop.find(&text)) {
+        text.truncate(start);
+        let mut offset = 0;
+        logprobs.retain_mut(|token| {
+            if offset >= start {
+                return false;
+            }
+            let mut kept = token.token.len().min(start - offset);
+            while !token.token.is_char_boundary(kept) {
+                kept -= 1;
+            }
+            offset += token.token.len();
+            token.token.truncate(kept);
+            true
+        });
+        return (text, logprobs, FinishReason::Stop);
+    }
+
+    let finish_reason = if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        FinishReason::TimeLimit
+    } else if logprobs.len() >= max_tokens as usize {
+        FinishReason::Length
+    } else {
+        FinishReason::Stop
+    };
+    (text, logprobs, finish_reason)
+}
+
+/// Read `stream` until it ends, `stop` matches or `deadline` passes, returning
+/// the text and which of them ended it
+///
+/// Dropping the stream at a limit stops the backend generating.
+pub(crate) async fn collect(
+    mut stream: TokenStream,
+    stop: Option<&StopRegex>,
+    deadline: Option<Instant>,
+    max_tokens: u32,
+) -> Result<(String, FinishReason), InfernoError> {
+    let expired = async {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(expired);
+
+    let mut text = String::new();
+    let mut tokens = 0u32;
+    loop {
+        let token = tokio::select! {
+            biased;
+            _ = &mut expired => return Ok((text, FinishReason::TimeLimit)),
+            token = stream.next() => token,
+        };
+        let Some(token) = token else { break };
+        text.push_str(&token?);
+        tokens += 1;
+        if let Some(start) = stop.and_then(|stop| stop.find(&text)) {
+            text.truncate(start);
+            return Ok((text, FinishReason::Stop));
+        }
+    }
+
+    // Backends also check the budget as they decode and may end the stream first
+    let finish_reason = if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        FinishReason::TimeLimit
+    } else if tokens >= max_tokens {
+        FinishReason::Length
+    } else {
+        FinishReason::Stop
+    };
+    Ok((text, finish_reason))
+}
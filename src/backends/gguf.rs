@@ -9,25 +9,30 @@ use crate::{
     ai_features::sampling::{Sampler, SamplingConfig, SamplingStrategy},
     ai_features::streaming::{StreamConfig, StreamToken, create_stream_channel},
     backends::{
-        BackendConfig, BackendType, InferenceBackend, InferenceMetrics, InferenceParams,
-        TokenStream,
+        BackendConfig, BackendType, CancellationToken, InferenceBackend, InferenceMetrics,
+        InferenceParams, RopeScaling, TokenLogprob, TokenStream, Tokenizer, blocking,
+        cancellable_stream, mmap::ModelMapping,
     },
     models::ModelInfo,
 };
 use anyhow::Result;
 use async_stream::stream;
 use llama_cpp_2::{
-    context::{LlamaContext, params::LlamaContextParams},
+    LlamaModelLoadError,
+    context::{
+        LlamaContext,
+        params::{LlamaContextParams, RopeScalingType},
+    },
     llama_backend::LlamaBackend,
     llama_batch::LlamaBatch,
     model::{AddBos, LlamaModel, Special, params::LlamaModelParams},
     sampling::LlamaSampler,
-    token::LlamaToken,
+    token::{LlamaToken, data::LlamaTokenData, data_array::LlamaTokenDataArray},
 };
 use std::{
     num::NonZeroU32,
     sync::{Arc, OnceLock},
-    time::Instant,
+    time::{Duration, Instant},
 };
 use tracing::{debug, info, warn};
 
@@ -53,6 +58,21 @@ fn shared_llama_backend() -> Result<Arc<LlamaBackend>> {
         })
 }
 
+/// Counts tokens with a loaded model's vocabulary
+struct GgufTokenizer {
+    model: Arc<LlamaModel>,
+}
+
+impl Tokenizer for GgufTokenizer {
+    fn count_tokens(&self, text: &str) -> Result<usize> {
+        let tokens = self
+            .model
+            .str_to_token(text, AddBos::Never)
+            .map_err(|e| InfernoError::Backend(format!("Tokenization failed: {}", e)))?;
+        Ok(tokens.len())
+    }
+}
+
 // Real GGUF implementation using llama-cpp-2
 pub struct GgufBackend {
     config: BackendConfig,
@@ -60,6 +80,12 @@ pub struct GgufBackend {
     model: Option<Arc<LlamaModel>>,
     model_info: Option<ModelInfo>,
     metrics: Option<InferenceMetrics>,
+    /// Mapping of the loaded model's file, kept for prefetching and for
+    /// reporting how much of it is resident
+    mapping: Option<ModelMapping>,
+    /// Tokens that end generation: end-of-sequence and, for chat models, the
+    /// end-of-turn token from the model's metadata
+    end_tokens: Vec<i32>,
 }
 
 impl GgufBackend {
@@ -72,6 +98,8 @@ impl GgufBackend {
             model: None,
             model_info: None,
             metrics: None,
+            mapping: None,
+            end_tokens: Vec::new(),
         })
     }
 
@@ -87,7 +115,43 @@ impl GgufBackend {
                 InfernoError::Backend("Context size too small (minimum 256)".to_string()).into(),
             );
         }
-        Ok(())
+        // llama.cpp maps GGUF weights whatever is asked, so a copy on the heap
+        // cannot be had
+        if !self.config.memory_map {
+            return Err(InfernoError::Validation(
+                "memory_map = false is not supported by the GGUF backend, which always maps the model file"
+                    .to_string(),
+            )
+            .into());
+        }
+        self.config.rope.validate()
+    }
+
+    /// Context parameters for an inference session, including any RoPE scaling
+    fn context_params(config: &BackendConfig) -> LlamaContextParams {
+        let rope = &config.rope;
+        let mut params = LlamaContextParams::default()
+            .with_n_ctx(NonZeroU32::new(config.context_size))
+            .with_n_batch(config.batch_size);
+        if let Some(threads) = config.cpu_threads {
+            params = params
+                .with_n_threads(threads as i32)
+                .with_n_threads_batch(threads as i32);
+        }
+        if let Some(scaling) = rope.scaling {
+            params = params.with_rope_scaling_type(match scaling {
+                RopeScaling::None => RopeScalingType::None,
+                RopeScaling::Linear => RopeScalingType::Linear,
+                RopeScaling::Yarn => RopeScalingType::Yarn,
+            });
+        }
+        if let Some(base) = rope.freq_base {
+            params = params.with_rope_freq_base(base);
+        }
+        if let Some(scale) = rope.freq_scale {
+            params = params.with_rope_freq_scale(scale);
+        }
+        params
     }
 
     async fn real_tokenize(&self, text: &str) -> Result<Vec<i32>> {
@@ -101,7 +165,7 @@ impl GgufBackend {
             text.len()
         );
 
-        let tokens = tokio::task::spawn_blocking({
+        let tokens = blocking::run_tokenization({
             let model = model.clone();
             let text = text.to_string();
             move || {
@@ -127,7 +191,7 @@ impl GgufBackend {
 
         debug!("Detokenizing {} tokens with real llama.cpp", tokens.len());
 
-        let text = tokio::task::spawn_blocking({
+        let text = blocking::run_tokenization({
             let model = model.clone();
             let tokens = tokens.to_vec();
             move || {
@@ -165,7 +229,14 @@ impl GgufBackend {
         char_based.max(word_based).max(1)
     }
 
-    async fn generate_response(&mut self, input: &str, params: &InferenceParams) -> Result<String> {
+    /// Generate a completion, with each token's log probability when
+    /// `params.logprobs` asks for them
+    async fn generate_response(
+        &mut self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<(String, Vec<TokenLogprob>)> {
         debug!(
             "🔥 Generating response for input of length: {} with Metal GPU acceleration",
             input.len()
@@ -184,22 +255,27 @@ impl GgufBackend {
             .clone();
 
         let input_str = input.to_string();
-        let context_size = self.config.context_size;
-        let batch_size = self.config.batch_size;
+        let ctx_params = Self::context_params(&self.config);
         let max_tokens = params.max_tokens;
         let temperature = params.temperature;
         let top_k = params.top_k;
         let top_p = params.top_p;
+        let repeat_penalty = params.repeat_penalty;
+        let repeat_last_n = params.repeat_last_n;
+        let min_p = params.min_p;
         let seed = params.seed;
         let stop_sequences = params.stop_sequences.clone();
-
-        // Perform inference in spawn_blocking since LlamaContext is !Send
-        let response = tokio::task::spawn_blocking(move || {
+        let end_tokens = self.end_tokens.clone();
+        let deadline = params
+            .max_generation_ms
+            .map(|ms| Instant::now() + Duration::from_millis(ms));
+        let grammar = Self::grammar_for(params)?;
+        let top_logprobs = params.logprobs;
+        let cancel = cancel.clone();
+
+        // Perform inference on the blocking pool since LlamaContext is !Send
+        let response = blocking::run(move || {
             // Create context for this inference session
-            let ctx_params = LlamaContextParams::default()
-                .with_n_ctx(NonZeroU32::new(context_size))
-                .with_n_batch(batch_size);
-
             let mut context = model
                 .new_context(&backend, ctx_params)
                 .map_err(|e| InfernoError::Backend(format!("Failed to create context: {}", e)))?;
@@ -254,7 +330,12 @@ impl GgufBackend {
                 temperature: temperature.max(0.1).min(2.0),
                 top_k: top_k.max(1),
                 top_p: top_p.max(0.0).min(1.0),
-                repeat_penalty: 1.1,
+                // Unset means no penalty, which is how generation has always behaved
+                repeat_penalty: repeat_penalty.unwrap_or(1.0),
+                repeat_last_n: repeat_last_n
+                    .map(|n| n as usize)
+                    .unwrap_or(SamplingConfig::default().repeat_last_n),
+                min_p: min_p.unwrap_or(0.0).clamp(0.0, 1.0),
                 seed,
             };
 
@@ -263,9 +344,13 @@ impl GgufBackend {
             let temperature = sampling_config.temperature;
 
             let mut sampler = Sampler::new(sampling_config);
+            let mut grammar = grammar
+                .map(|gbnf| Self::grammar_sampler(&model, &gbnf))
+                .transpose()?;
 
             // Generate tokens one by one
             let mut output_tokens = Vec::new();
+            let mut logprobs = Vec::new();
             let mut generated_text = String::new();
 
             // The KV cache holds the prompt plus every generated token, so
@@ -280,8 +365,25 @@ impl GgufBackend {
             );
 
             for _ in 0..max_new_tokens {
-                // Get logits for sampling - collect iterator to vec
-                let candidates_llama: Vec<_> = context.candidates().collect();
+                // The budget first, so a cancellation at the deadline keeps the output
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    debug!(
+                        "⏱️ Generation time budget spent after {} tokens",
+                        output_tokens.len()
+                    );
+                    break;
+                }
+                // Checked between decode steps, so cancelling waits for at most one
+                if cancel.is_cancelled() {
+                    debug!(
+                        "🛑 Generation cancelled after {} tokens",
+                        output_tokens.len()
+                    );
+                    return Err(InfernoError::Cancelled("Inference cancelled".to_string()));
+                }
+
+                // Get logits for sampling, restricted to what the grammar allows
+                let candidates_llama = Self::next_candidates(&context, grammar.as_ref());
 
                 // Compute softmax probabilities from raw logits
                 let logits: Vec<f32> = candidates_llama.iter().map(|c| c.logit()).collect();
@@ -298,9 +400,12 @@ impl GgufBackend {
                 let next_token = sampler.sample_from_candidates(&candidates).ok_or_else(|| {
                     InfernoError::Backend("No candidates available for sampling".to_string())
                 })?;
+                if let Some(grammar) = grammar.as_mut() {
+                    grammar.accept(LlamaToken(next_token));
+                }
 
-                // Check for end of sequence - use model's token methods
-                if next_token == model.token_eos().0 {
+                // Check for end of sequence or end of turn
+                if end_tokens.contains(&next_token) {
                     debug!("🏁 End of generation token encountered");
                     break;
                 }
@@ -319,6 +424,22 @@ impl GgufBackend {
                 }
 
                 output_tokens.push(next_token);
+                if let Some(top_n) = top_logprobs {
+                    let logits: Vec<(i32, f32)> = candidates
+                        .iter()
+                        .map(|&(id, logit, _)| (id, logit))
+                        .collect();
+                    logprobs.push(TokenLogprob::from_logits(
+                        &logits,
+                        next_token,
+                        top_n,
+                        |id| {
+                            model
+                                .token_to_str(LlamaToken(id), Special::Tokenize)
+                                .unwrap_or_default()
+                        },
+                    ));
+                }
 
                 // Prepare next batch with the sampled token
                 batch.clear();
@@ -347,7 +468,7 @@ impl GgufBackend {
                 .map_err(|e| InfernoError::Backend(format!("Failed to detokenize: {}", e)))?;
 
             debug!("✅ Generated {} tokens via Metal GPU", output_tokens.len());
-            Ok::<String, InfernoError>(response)
+            Ok::<_, InfernoError>((response, logprobs))
         })
         .await
         .map_err(|e| InfernoError::Backend(format!("Inference task failed: {}", e)))??;
@@ -359,6 +480,7 @@ impl GgufBackend {
         &mut self,
         input: &str,
         params: &InferenceParams,
+        cancel: &CancellationToken,
     ) -> Result<TokenStream> {
         info!("🌊 Starting GGUF streaming inference with Metal GPU");
 
@@ -375,14 +497,21 @@ impl GgufBackend {
             .clone();
 
         let input_str = input.to_string();
-        let context_size = self.config.context_size;
-        let batch_size = self.config.batch_size;
+        let ctx_params = Self::context_params(&self.config);
         let max_tokens = params.max_tokens;
         let temperature = params.temperature;
         let top_k = params.top_k;
         let top_p = params.top_p;
+        let repeat_penalty = params.repeat_penalty;
+        let repeat_last_n = params.repeat_last_n;
+        let min_p = params.min_p;
         let seed = params.seed;
         let stop_sequences = params.stop_sequences.clone();
+        let end_tokens = self.end_tokens.clone();
+        let deadline = params
+            .max_generation_ms
+            .map(|ms| Instant::now() + Duration::from_millis(ms));
+        let grammar = Self::grammar_for(params)?;
 
         // Create streaming channel
         let stream_config = StreamConfig {
@@ -391,16 +520,13 @@ impl GgufBackend {
             max_tokens_per_sec: 0,
         };
         let (tx, rx) = create_stream_channel(stream_config);
+        let generation_cancel = cancel.clone();
 
         // Spawn blocking task for inference with token streaming
-        tokio::task::spawn_blocking(move || {
+        blocking::spawn(move || {
             let start_time = std::time::Instant::now();
 
             // Create context for this inference session
-            let ctx_params = LlamaContextParams::default()
-                .with_n_ctx(std::num::NonZeroU32::new(context_size))
-                .with_n_batch(batch_size);
-
             let mut context = match model.new_context(&backend, ctx_params) {
                 Ok(ctx) => ctx,
                 Err(e) => {
@@ -490,7 +616,12 @@ impl GgufBackend {
                 temperature: temperature.max(0.1).min(2.0),
                 top_k: top_k.max(1),
                 top_p: top_p.max(0.0).min(1.0),
-                repeat_penalty: 1.1,
+                // Unset means no penalty, which is how generation has always behaved
+                repeat_penalty: repeat_penalty.unwrap_or(1.0),
+                repeat_last_n: repeat_last_n
+                    .map(|n| n as usize)
+                    .unwrap_or(SamplingConfig::default().repeat_last_n),
+                min_p: min_p.unwrap_or(0.0).clamp(0.0, 1.0),
                 seed,
             };
 
@@ -498,6 +629,21 @@ impl GgufBackend {
             let temp = sampling_config.temperature;
 
             let mut sampler = Sampler::new(sampling_config);
+            let mut grammar = match grammar
+                .map(|gbnf| Self::grammar_sampler(&model, &gbnf))
+                .transpose()
+            {
+                Ok(grammar) => grammar,
+                Err(e) => {
+                    let _ = tx.blocking_send(StreamToken {
+                        content: format!("Error: {}", e),
+                        sequence: 0,
+                        is_valid: false,
+                        timestamp_ms: Some(start_time.elapsed().as_millis() as u64),
+                    });
+                    return;
+                }
+            };
 
             // Generate tokens and stream them one by one. As in `generate_response`,
             // the KV cache holds the prompt plus every generated token, so the
@@ -512,8 +658,23 @@ impl GgufBackend {
             );
 
             for _ in 0..max_new_tokens {
-                // Get logits for sampling
-                let candidates_llama: Vec<_> = context.candidates().collect();
+                if generation_cancel.is_cancelled() {
+                    debug!(
+                        "🛑 Streaming generation cancelled after {} tokens",
+                        sequence
+                    );
+                    break;
+                }
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    debug!(
+                        "⏱️ Streaming generation time budget spent after {} tokens",
+                        sequence
+                    );
+                    break;
+                }
+
+                // Get logits for sampling, restricted to what the grammar allows
+                let candidates_llama = Self::next_candidates(&context, grammar.as_ref());
 
                 // Compute softmax probabilities from raw logits
                 let logits: Vec<f32> = candidates_llama.iter().map(|c| c.logit()).collect();
@@ -539,9 +700,12 @@ impl GgufBackend {
                         break;
                     }
                 };
+                if let Some(grammar) = grammar.as_mut() {
+                    grammar.accept(LlamaToken(next_token));
+                }
 
-                // Check for end of sequence
-                if next_token == model.token_eos().0 {
+                // Check for end of sequence or end of turn
+                if end_tokens.contains(&next_token) {
                     debug!("🏁 End of generation token encountered");
                     break;
                 }
@@ -635,7 +799,39 @@ impl GgufBackend {
             }
         };
 
-        Ok(Box::pin(result_stream))
+        Ok(cancellable_stream(Box::pin(result_stream), cancel.clone()))
+    }
+
+    /// GBNF grammar for the requested response format, if output is constrained
+    fn grammar_for(params: &InferenceParams) -> Result<Option<String>> {
+        match &params.response_format {
+            Some(format) => Ok(format.to_grammar()?),
+            None => Ok(None),
+        }
+    }
+
+    fn grammar_sampler(model: &LlamaModel, gbnf: &str) -> Result<LlamaSampler, InfernoError> {
+        LlamaSampler::grammar(model, gbnf, "root")
+            .map_err(|e| InfernoError::Validation(format!("Invalid grammar: {}", e)))
+    }
+
+    /// Next-token candidates, dropping any the grammar rules out
+    fn next_candidates(
+        context: &LlamaContext<'_>,
+        grammar: Option<&LlamaSampler>,
+    ) -> Vec<LlamaTokenData> {
+        match grammar {
+            Some(grammar) => {
+                let mut candidates = LlamaTokenDataArray::from_iter(context.candidates(), false);
+                candidates.apply_sampler(grammar);
+                candidates
+                    .data
+                    .into_iter()
+                    .filter(|c| c.logit().is_finite())
+                    .collect()
+            }
+            None => context.candidates().collect(),
+        }
     }
 
     fn softmax(logits: &[f32]) -> Vec<f32> {
@@ -680,22 +876,14 @@ impl InferenceBackend for GgufBackend {
             .into());
         }
 
-        // Read the first few bytes to check for GGUF magic
-        let mut file = std::fs::File::open(&model_info.path)
-            .map_err(|e| InfernoError::Backend(format!("Cannot open model file: {}", e)))?;
-
-        let mut magic = [0u8; 4];
-        use std::io::Read;
-        file.read_exact(&mut magic)
-            .map_err(|e| InfernoError::Backend(format!("Cannot read model file header: {}", e)))?;
-
-        // Check for GGUF magic bytes
-        if &magic != b"GGUF" {
-            return Err(InfernoError::Backend(
-                "File is not a valid GGUF model (missing GGUF magic bytes)".to_string(),
-            )
-            .into());
-        }
+        // A truncated or corrupt file is reported here rather than failing somewhere
+        // inside llama.cpp; the tensor table of a large model takes a moment to read
+        blocking::run({
+            let path = model_info.path.clone();
+            move || crate::optimization::gguf::check_integrity(&path)
+        })
+        .await
+        .map_err(|e| InfernoError::Backend(format!("Model validation task failed: {}", e)))??;
 
         debug!("GGUF file validation passed");
         debug!("Model file size: {} bytes", file_size);
@@ -711,11 +899,9 @@ impl InferenceBackend for GgufBackend {
         );
 
         // Get the process-wide llama backend, initializing it on first use.
-        let backend = tokio::task::spawn_blocking(shared_llama_backend)
-            .await
-            .map_err(|e| {
-                InfernoError::Backend(format!("Backend initialization task failed: {}", e))
-            })??;
+        let backend = blocking::run(shared_llama_backend).await.map_err(|e| {
+            InfernoError::Backend(format!("Backend initialization task failed: {}", e))
+        })??;
 
         // Configure model parameters with GPU support
         // On macOS, Metal is automatically used when n_gpu_layers > 0
@@ -730,21 +916,86 @@ impl InferenceBackend for GgufBackend {
             self.config.gpu_enabled, n_gpu_layers
         );
 
-        let model_params = LlamaModelParams::default()
-            .with_n_gpu_layers(n_gpu_layers)
-            .with_use_mlock(false);
+        // llama.cpp maps the file itself by default. Mapping it here too shares the
+        // same page cache, and lets the file be prefetched and its resident share
+        // reported
+        let mapping = ModelMapping::open(&model_info.path, self.config.prefetch_model)?;
+        debug!("Mapped {} bytes of model weights", mapping.mapped_bytes());
+
+        // Load the model; reading gigabytes of weights blocks, so it runs on the pool.
+        // GPU failures are reported as GpuInit, which the caller may answer by
+        // loading on the CPU
+        let model = blocking::run({
+            let backend = backend.clone();
+            let path = model_info.path.clone();
+            let use_mlock = self.config.lock_memory;
+            move || {
+                if n_gpu_layers > 0 && !backend.supports_gpu_offload() {
+                    return Err(InfernoError::GpuInit(
+                        "this llama.cpp build cannot offload layers to a GPU".to_string(),
+                    ));
+                }
+                let model_params = LlamaModelParams::default()
+                    .with_n_gpu_layers(n_gpu_layers)
+                    .with_use_mlock(use_mlock);
+                LlamaModel::load_from_file(&backend, path, &model_params).map_err(|e| match e {
+                    // llama.cpp returns no model without saying why. The file passed
+                    // the integrity check above, so with layers offloaded the GPU is
+                    // the suspect; a CPU retry reports the real error if it is not
+                    LlamaModelLoadError::NullResult if n_gpu_layers > 0 => InfernoError::GpuInit(
+                        format!("Failed to load GGUF model with GPU offload: {}", e),
+                    ),
+                    e => InfernoError::Backend(format!("Failed to load GGUF model: {}", e)),
+                })
+            }
+        })
+        .await
+        .map_err(|e| InfernoError::Backend(format!("Model loading task failed: {}", e)))??;
+
+        let trained_context = model.n_ctx_train();
+        let effective_context = self.config.rope.effective_context(trained_context);
+        if effective_context != trained_context {
+            info!(
+                "RoPE scaling extends the context from {} to {} tokens",
+                trained_context, effective_context
+            );
+        }
+        if self.config.context_size > effective_context {
+            warn!(
+                "Context size {} exceeds the {} tokens the model supports; set rope scaling to extend it",
+                self.config.context_size, effective_context
+            );
+        }
 
-        // Load the model
-        let model = {
-            let path = &model_info.path;
-            LlamaModel::load_from_file(&backend, path, &model_params)
-                .map_err(|e| InfernoError::Backend(format!("Failed to load GGUF model: {}", e)))?
-        };
+        let mut end_tokens = vec![model.token_eos().0];
+        let metadata_tokens = blocking::run({
+            let path = model_info.path.clone();
+            move || crate::models::chat_template::read_end_tokens(&path)
+        })
+        .await
+        .map_err(|e| InfernoError::Backend(format!("Metadata reading task failed: {}", e)))?;
+        match metadata_tokens {
+            Ok(tokens) => {
+                for token in tokens {
+                    if !end_tokens.contains(&(token.id as i32)) {
+                        end_tokens.push(token.id as i32);
+                    }
+                }
+            }
+            Err(e) => warn!("Cannot read end-of-turn token from metadata: {}", e),
+        }
+        debug!("Generation ends at tokens {:?}", end_tokens);
 
         // Store backend and model (context will be created per-inference to avoid Send/Sync issues)
         self.backend = Some(backend);
+        self.end_tokens = end_tokens;
         self.model = Some(Arc::new(model));
-        self.model_info = Some(model_info.clone());
+        self.mapping = Some(mapping);
+        let mut model_info = model_info.clone();
+        model_info
+            .metadata
+            .insert("context_length".to_string(), effective_context.to_string());
+        self.model_info = Some(model_info);
 
         info!("✅ GGUF model loaded successfully with Metal GPU support");
 
@@ -757,6 +1008,8 @@ impl InferenceBackend for GgufBackend {
         self.model = None;
         self.model_info = None;
         self.metrics = None;
+        self.mapping = None;
+        self.end_tokens.clear();
         Ok(())
     }
 
@@ -769,6 +1022,21 @@ impl InferenceBackend for GgufBackend {
     }
 
     async fn infer(&mut self, input: &str, params: &InferenceParams) -> Result<String> {
+        self.infer_cancellable(input, params, &CancellationToken::new())
+            .await
+    }
+
+    async fn infer_stream(&mut self, input: &str, params: &InferenceParams) -> Result<TokenStream> {
+        self.infer_stream_cancellable(input, params, &CancellationToken::new())
+            .await
+    }
+
+    async fn infer_cancellable(
+        &mut self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<String> {
         if !self.is_loaded().await {
             return Err(InfernoError::Backend("Model not loaded".to_string()).into());
         }
@@ -787,12 +1055,13 @@ impl InferenceBackend for GgufBackend {
         let prompt_time = start_time.elapsed();
 
         // Generate response
-        let response = self.generate_response(input, params).await?;
+        let (response, _) = self.generate_response(input, params, cancel).await?;
 
         let completion_time = start_time.elapsed() - prompt_time;
         let total_time = start_time.elapsed();
 
-        let completion_tokens = self.estimate_token_count(&response);
+        let completion_tokens =
+            crate::backends::tokenizer::count_tokens_blocking(self.tokenizer(), &response).await;
         let total_tokens = prompt_tokens + completion_tokens;
 
         self.metrics = Some(InferenceMetrics {
@@ -819,7 +1088,12 @@ impl InferenceBackend for GgufBackend {
         Ok(response)
     }
 
-    async fn infer_stream(&mut self, input: &str, params: &InferenceParams) -> Result<TokenStream> {
+    async fn infer_stream_cancellable(
+        &mut self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<TokenStream> {
         if !self.is_loaded().await {
             return Err(InfernoError::Backend("Model not loaded".to_string()).into());
         }
@@ -830,7 +1104,7 @@ impl InferenceBackend for GgufBackend {
         }
 
         info!("Starting GGUF streaming inference");
-        self.generate_stream(input, params).await
+        self.generate_stream(input, params, cancel).await
     }
 
     async fn get_embeddings(&mut self, input: &str) -> Result<Vec<f32>> {
@@ -873,11 +1147,60 @@ impl InferenceBackend for GgufBackend {
     fn get_metrics(&self) -> Option<InferenceMetrics> {
         self.metrics.as_ref().cloned()
     }
+
+    fn tokenizer(&self) -> Option<Arc<dyn Tokenizer>> {
+        let model = self.model.clone()?;
+        Some(Arc::new(GgufTokenizer { model }))
+    }
+
+    async fn infer_logprobs(
+        &mut self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<Option<(String, Vec<TokenLogprob>)>> {
+        if !self.is_loaded().await {
+            return Err(InfernoError::Backend("Model not loaded".to_string()).into());
+        }
+        Ok(Some(self.generate_response(input, params, cancel).await?))
+    }
+
+    fn context_length(&self) -> Option<u32> {
+        let trained = self.model.as_ref()?.n_ctx_train();
+        Some(
+            self.config
+                .context_size
+                .min(self.config.rope.effective_context(trained)),
+        )
+    }
+
+    fn reconfigure(&mut self, config: &BackendConfig) -> Result<bool> {
+        let in_place = Self::applies_in_place(&self.config, config);
+        let previous = std::mem::replace(&mut self.config, config.clone());
+        if let Err(e) = self.validate_config() {
+            self.config = previous;
+            return Err(e);
+        }
+        Ok(in_place || self.model.is_none())
+    }
+}
+
+impl GgufBackend {
+    /// Whether switching from `current` to `config` keeps the loaded model.
+    /// Threads and batch size are read when each request creates its context;
+    /// the rest shape how the model is loaded or checked against its context
+    fn applies_in_place(current: &BackendConfig, config: &BackendConfig) -> bool {
+        current
+            .changed_settings(config)
+            .iter()
+            .all(|setting| BackendConfig::PER_REQUEST_SETTINGS.contains(setting))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backends::RopeConfig;
     use crate::models::ModelInfo;
     use chrono::Utc;
     use std::path::PathBuf;
@@ -904,6 +1227,81 @@ mod tests {
         assert!(backend.is_err());
     }
 
+    #[test]
+    fn test_rope_config_maps_to_context_params() {
+        let config = BackendConfig {
+            context_size: 16384,
+            batch_size: 64,
+            rope: RopeConfig {
+                freq_base: Some(500_000.0),
+                freq_scale: Some(0.25),
+                scaling: Some(RopeScaling::Yarn),
+            },
+            ..Default::default()
+        };
+        let params = GgufBackend::context_params(&config);
+        assert_eq!(params.n_ctx(), NonZeroU32::new(16384));
+        assert_eq!(params.n_batch(), 64);
+        assert_eq!(params.rope_scaling_type(), RopeScalingType::Yarn);
+        assert_eq!(params.rope_freq_base(), 500_000.0);
+        assert_eq!(params.rope_freq_scale(), 0.25);
+
+        // Unset values leave llama.cpp's defaults alone
+        let defaults = LlamaContextParams::default();
+        let params = GgufBackend::context_params(&BackendConfig::default());
+        assert_eq!(params.rope_scaling_type(), defaults.rope_scaling_type());
+        assert_eq!(params.rope_freq_base(), defaults.rope_freq_base());
+        assert_eq!(params.rope_freq_scale(), defaults.rope_freq_scale());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_rope_config_rejected_on_load() {
+        let config = BackendConfig {
+            rope: RopeConfig {
+                freq_scale: Some(0.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let backend = GgufBackend::new(config).expect("Failed to create GgufBackend for test");
+        let err = backend.validate_config().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InfernoError>(),
+            Some(InfernoError::Validation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unmapped_load_rejected() {
+        let config = BackendConfig {
+            memory_map: false,
+            ..Default::default()
+        };
+        let mut backend = GgufBackend::new(config).expect("Failed to create GgufBackend for test");
+        let dir = tempdir().expect("Failed to create temporary directory for test");
+        let model_path = dir.path().join("model.gguf");
+        std::fs::write(&model_path, b"GGUF").expect("Failed to write model file for test");
+        let model_info = ModelInfo {
+            path: model_path.clone(),
+            name: "model".to_string(),
+            file_path: model_path,
+            backend_type: "gguf".to_string(),
+            format: "gguf".to_string(),
+            size: 4,
+            size_bytes: 4,
+            checksum: None,
+            modified: Utc::now(),
+            metadata: std::collections::HashMap::new(),
+        };
+
+        let err = backend.load_model(&model_info).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InfernoError>(),
+            Some(InfernoError::Validation(_))
+        ));
+        assert!(backend.mapping.is_none());
+    }
+
     #[tokio::test]
     async fn test_gguf_tokenization() {
         let config = BackendConfig::default();
@@ -1004,6 +1402,46 @@ mod tests {
         assert!(!backend.is_loaded().await);
     }
 
+    #[tokio::test]
+    async fn test_tokenizer_counts_with_the_model_vocabulary() {
+        // The vocabulary comes from a model llama.cpp can load, so this needs a
+        // real one and skips without it
+        let Some(model_path) = std::env::var_os("INFERNO_TEST_MODEL").map(PathBuf::from) else {
+            return;
+        };
+        let size = std::fs::metadata(&model_path)
+            .expect("INFERNO_TEST_MODEL is not a file")
+            .len();
+        let model_info = ModelInfo {
+            path: model_path.clone(),
+            name: "vocabulary".to_string(),
+            file_path: model_path,
+            backend_type: "gguf".to_string(),
+            format: "gguf".to_string(),
+            size,
+            size_bytes: size,
+            checksum: None,
+            modified: Utc::now(),
+            metadata: std::collections::HashMap::new(),
+        };
+        let mut backend = GgufBackend::new(BackendConfig::default())
+            .expect("Failed to create GgufBackend for test");
+        backend.load_model(&model_info).await.unwrap();
+
+        let tokenizer = backend.tokenizer().expect("a loaded model has a tokenizer");
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let count = tokenizer.count_tokens(text).unwrap();
+        assert_eq!(tokenizer.count_tokens("").unwrap(), 0);
+
+        // The same vocabulary as prompt tokenization, less the BOS token it may add
+        let with_bos = backend.real_tokenize(text).await.unwrap().len();
+        assert!(with_bos == count || with_bos == count + 1);
+        assert_eq!(
+            crate::backends::tokenizer::count_tokens_blocking(Some(tokenizer), text).await,
+            count as u32
+        );
+    }
+
     #[tokio::test]
     async fn test_gguf_inference_without_model() {
         let config = BackendConfig::default();
@@ -1027,4 +1465,37 @@ mod tests {
         let count_empty = backend.estimate_token_count("");
         assert_eq!(count_empty, 1); // Minimum count
     }
+
+    #[test]
+    fn test_reconfigure_keeps_the_model_only_for_per_request_settings() {
+        let current = BackendConfig::default();
+        let threads = BackendConfig {
+            cpu_threads: Some(4),
+            batch_size: 64,
+            ..current.clone()
+        };
+        assert!(GgufBackend::applies_in_place(&current, &threads));
+        let context = BackendConfig {
+            context_size: 8192,
+            ..threads.clone()
+        };
+        assert!(!GgufBackend::applies_in_place(&current, &context));
+        let mapping = BackendConfig {
+            memory_map: !current.memory_map,
+            ..current.clone()
+        };
+        assert!(!GgufBackend::applies_in_place(&current, &mapping));
+
+        // Invalid settings are refused and the previous ones kept
+        let mut backend = GgufBackend::new(current.clone()).unwrap();
+        let invalid = BackendConfig {
+            rope: RopeConfig {
+                freq_scale: Some(0.0),
+                ..Default::default()
+            },
+            ..current.clone()
+        };
+        assert!(backend.reconfigure(&invalid).is_err());
+        assert_eq!(backend.config, current);
+    }
 }
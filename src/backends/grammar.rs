@@ -0,0 +1,472 @@
+//! GBNF grammars for constrained decoding
+//!
+//! Translates [`ResponseFormat`](super::ResponseFormat) requests into the GBNF grammar
+//! format understood by llama.cpp's grammar sampler. JSON Schema support covers the
+//! subset useful for structured extraction: `type`, `properties`, `required`, `items`,
+//! `enum`, `const`, `anyOf` and `oneOf`.
+
+use crate::InfernoError;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashSet};
+
+/// Shared JSON rules every generated grammar can reference
+const JSON_PRIMITIVES: &str = r#"value ::= object | array | string | number | boolean | null
+object ::= "{" ws ( string ":" ws value ( "," ws string ":" ws value )* )? "}" ws
+array ::= "[" ws ( value ( "," ws value )* )? "]" ws
+string ::= "\"" ( [^"\\\x7F\x00-\x1F] | "\\" ( ["\\/bfnrt] | "u" [0-9a-fA-F]{4} ) )* "\"" ws
+number ::= "-"? ( "0" | [1-9] [0-9]{0,15} ) ( "." [0-9]+ )? ( [eE] [-+]? [0-9]+ )? ws
+integer ::= "-"? ( "0" | [1-9] [0-9]{0,15} ) ws
+boolean ::= ( "true" | "false" ) ws
+null ::= "null" ws
+ws ::= | " " | "\n" [ \t]{0,20}
+"#;
+
+/// Grammar accepting any syntactically valid JSON object
+pub fn json_object_grammar() -> String {
+    format!("root ::= object\n{}", JSON_PRIMITIVES)
+}
+
+/// Grammar accepting only JSON documents that match `schema`
+pub fn json_schema_grammar(schema: &Value) -> Result<String, InfernoError> {
+    let mut converter = SchemaConverter::default();
+    let root = converter.visit(schema, "root")?;
+
+    let mut grammar = format!("root ::= {}\n", root);
+    for (name, body) in &converter.rules {
+        grammar.push_str(&format!("{} ::= {}\n", name, body));
+    }
+    grammar.push_str(JSON_PRIMITIVES);
+    Ok(grammar)
+}
+
+/// Check that `gbnf` parses: every rule has a name, literals, character classes
+/// and groups are closed, `root` is defined and every rule referenced is too
+///
+/// This catches mistakes in a request's grammar before a model is loaded for it,
+/// rather than when llama.cpp rejects it mid-request.
+pub fn check_gbnf(gbnf: &str) -> Result<(), InfernoError> {
+    let syntax =
+        |message: String| InfernoError::Validation(format!("Invalid grammar: {}", message));
+
+    // A line with `::=` starts a rule; any other line continues the one before
+    let mut rules: Vec<(&str, String)> = Vec::new();
+    for line in gbnf.lines() {
+        match line.split_once("::=") {
+            Some((name, body)) if !line.trim_start().starts_with('#') => {
+                let name = name.trim();
+                if name.is_empty() || !name.chars().all(is_rule_char) {
+                    return Err(syntax(format!("'{}' is not a valid rule name", name)));
+                }
+                rules.push((name, body.to_string()));
+            }
+            _ => match rules.last_mut() {
+                Some((_, body)) => {
+                    body.push('\n');
+                    body.push_str(line);
+                }
+                None if line.trim().is_empty() || line.trim_start().starts_with('#') => {}
+                None => return Err(syntax(format!("'{}' is not part of a rule", line.trim()))),
+            },
+        }
+    }
+
+    let defined: HashSet<&str> = rules.iter().map(|(name, _)| *name).collect();
+    if !defined.contains("root") {
+        return Err(syntax("no 'root' rule".to_string()));
+    }
+    let mut undefined = BTreeSet::new();
+    for (name, body) in &rules {
+        let mut depth = 0usize;
+        let mut chars = body.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' | '[' => {
+                    let close = if c == '"' { '"' } else { ']' };
+                    loop {
+                        match chars.next() {
+                            Some('\\') => {
+                                chars.next();
+                            }
+                            Some(c) if c == close => break,
+                            Some('\n') | None => {
+                                return Err(syntax(format!(
+                                    "unclosed {} in rule '{}'",
+                                    if close == '"' {
+                                        "literal"
+                                    } else {
+                                        "character class"
+                                    },
+                                    name
+                                )));
+                            }
+                            Some(_) => {}
+                        }
+                    }
+                }
+                '{' => {
+                    if !chars.by_ref().any(|c| c == '}') {
+                        return Err(syntax(format!("unclosed '{{' in rule '{}'", name)));
+                    }
+                }
+                '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+                '(' => depth += 1,
+                ')' => {
+                    depth = depth
+                        .checked_sub(1)
+                        .ok_or_else(|| syntax(format!("unmatched ')' in rule '{}'", name)))?;
+                }
+                c if is_rule_char(c) => {
+                    let mut reference = c.to_string();
+                    while let Some(c) = chars.next_if(|&c| is_rule_char(c)) {
+                        reference.push(c);
+                    }
+                    if !defined.contains(reference.as_str()) {
+                        undefined.insert(reference);
+                    }
+                }
+                '|' | '*' | '+' | '?' | '.' => {}
+                c if c.is_whitespace() => {}
+                c => {
+                    return Err(syntax(format!("unexpected '{}' in rule '{}'", c, name)));
+                }
+            }
+        }
+        if depth > 0 {
+            return Err(syntax(format!("unclosed '(' in rule '{}'", name)));
+        }
+    }
+    if !undefined.is_empty() {
+        return Err(syntax(format!(
+            "undefined rules: {}",
+            undefined.into_iter().collect::<Vec<_>>().join(", ")
+        )));
+    }
+    Ok(())
+}
+
+fn is_rule_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+#[derive(Default)]
+struct SchemaConverter {
+    rules: Vec<(String, String)>,
+    names: HashSet<String>,
+}
+
+impl SchemaConverter {
+    /// Build the expression for `schema`, adding a named rule for each nested schema
+    fn visit(&mut self, schema: &Value, name: &str) -> Result<String, InfernoError> {
+        let schema = schema
+            .as_object()
+            .ok_or_else(|| invalid(format!("schema at '{}' must be an object", name)))?;
+
+        if schema.contains_key("$ref") {
+            return Err(invalid("$ref is not supported"));
+        }
+
+        if let Some(value) = schema.get("const") {
+            return Ok(json_literal(value));
+        }
+
+        if let Some(values) = schema.get("enum") {
+            let values = values
+                .as_array()
+                .filter(|values| !values.is_empty())
+                .ok_or_else(|| invalid(format!("enum at '{}' must be a non-empty array", name)))?;
+            return Ok(alternatives(values.iter().map(json_literal)));
+        }
+
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(options) = schema.get(keyword) {
+                let options = options
+                    .as_array()
+                    .filter(|o| !o.is_empty())
+                    .ok_or_else(|| {
+                        invalid(format!(
+                            "{} at '{}' must be a non-empty array",
+                            keyword, name
+                        ))
+                    })?;
+                let mut branches = Vec::with_capacity(options.len());
+                for (i, option) in options.iter().enumerate() {
+                    branches.push(self.nested(option, &format!("{}-{}", name, i))?);
+                }
+                return Ok(alternatives(branches));
+            }
+        }
+
+        match schema.get("type") {
+            Some(Value::String(ty)) => self.visit_type(ty, schema, name),
+            Some(Value::Array(types)) => {
+                let mut branches = Vec::with_capacity(types.len());
+                for ty in types {
+                    let ty = ty
+                        .as_str()
+                        .ok_or_else(|| invalid(format!("type at '{}' must be a string", name)))?;
+                    branches.push(self.visit_type(ty, schema, name)?);
+                }
+                Ok(alternatives(branches))
+            }
+            Some(_) => Err(invalid(format!(
+                "type at '{}' must be a string or array",
+                name
+            ))),
+            None if schema.contains_key("properties") => self.visit_type("object", schema, name),
+            None => Ok("value".to_string()),
+        }
+    }
+
+    fn visit_type(
+        &mut self,
+        ty: &str,
+        schema: &serde_json::Map<String, Value>,
+        name: &str,
+    ) -> Result<String, InfernoError> {
+        match ty {
+            "object" => self.visit_object(schema, name),
+            "array" => match schema.get("items") {
+                Some(items) => {
+                    let item = self.nested(items, &format!("{}-item", name))?;
+                    Ok(format!(
+                        r#""[" ws ( {item} ( "," ws {item} )* )? "]" ws"#,
+                        item = item
+                    ))
+                }
+                None => Ok("array".to_string()),
+            },
+            "string" | "number" | "integer" | "boolean" | "null" => Ok(ty.to_string()),
+            other => Err(invalid(format!("unknown type '{}' at '{}'", other, name))),
+        }
+    }
+
+    fn visit_object(
+        &mut self,
+        schema: &serde_json::Map<String, Value>,
+        name: &str,
+    ) -> Result<String, InfernoError> {
+        let properties = match schema.get("properties") {
+            Some(Value::Object(properties)) => properties,
+            Some(_) => {
+                return Err(invalid(format!(
+                    "properties at '{}' must be an object",
+                    name
+                )));
+            }
+            None => return Ok("object".to_string()),
+        };
+        if properties.is_empty() {
+            return Ok(r#""{" ws "}" ws"#.to_string());
+        }
+
+        let required: Vec<&str> = match schema.get("required") {
+            Some(Value::Array(required)) => required
+                .iter()
+                .map(|key| {
+                    key.as_str()
+                        .filter(|key| properties.contains_key(*key))
+                        .ok_or_else(|| {
+                            invalid(format!(
+                                "required key {} at '{}' is not a declared property",
+                                key, name
+                            ))
+                        })
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => {
+                return Err(invalid(format!("required at '{}' must be an array", name)));
+            }
+            None => Vec::new(),
+        };
+
+        // Required properties come first and are always emitted; optional ones follow
+        let mut members = Vec::with_capacity(properties.len());
+        for (key, property) in properties {
+            let value = self.nested(property, &format!("{}-{}", name, sanitize(key)))?;
+            let member = format!(
+                r#"{} ":" ws {}"#,
+                json_literal(&Value::String(key.clone())),
+                value
+            );
+            members.push((member, required.contains(&key.as_str())));
+        }
+
+        let (required_members, optional_members): (Vec<_>, Vec<_>) =
+            members.into_iter().partition(|(_, required)| *required);
+        let required_members: Vec<String> = required_members.into_iter().map(|(m, _)| m).collect();
+        let optional_members: Vec<String> = optional_members.into_iter().map(|(m, _)| m).collect();
+
+        let body = if required_members.is_empty() {
+            format!("( {} )?", first_of(&optional_members))
+        } else {
+            let mut body = required_members.join(r#" "," ws "#);
+            for member in &optional_members {
+                body.push_str(&format!(r#" ( "," ws {} )?"#, member));
+            }
+            body
+        };
+
+        Ok(format!(r#""{{" ws {} "}}" ws"#, body))
+    }
+
+    /// Place a nested schema in its own rule and return the rule name
+    fn nested(&mut self, schema: &Value, name: &str) -> Result<String, InfernoError> {
+        let mut rule = name.to_string();
+        let mut suffix = 1;
+        while !self.names.insert(rule.clone()) {
+            suffix += 1;
+            rule = format!("{}{}", name, suffix);
+        }
+
+        let body = self.visit(schema, &rule)?;
+        self.rules.push((rule.clone(), body));
+        Ok(rule)
+    }
+}
+
+/// Any ordered, non-empty subset of `members`, comma separated
+fn first_of(members: &[String]) -> String {
+    match members {
+        [] => String::new(),
+        [member] => member.clone(),
+        [member, rest @ ..] => {
+            let tail: String = rest
+                .iter()
+                .map(|m| format!(r#" ( "," ws {} )?"#, m))
+                .collect();
+            format!("( {}{} | {} )", member, tail, first_of(rest))
+        }
+    }
+}
+
+fn alternatives(branches: impl IntoIterator<Item = String>) -> String {
+    let branches: Vec<String> = branches.into_iter().collect();
+    format!("( {} )", branches.join(" | "))
+}
+
+/// GBNF literal matching the JSON encoding of `value`, followed by whitespace
+fn json_literal(value: &Value) -> String {
+    let json = value.to_string();
+    let mut literal = String::with_capacity(json.len() + 2);
+    literal.push('"');
+    for c in json.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c => literal.push(c),
+        }
+    }
+    literal.push_str("\" ws");
+    literal
+}
+
+/// Rule names may only contain ASCII letters, digits and dashes
+fn sanitize(key: &str) -> String {
+    let name: String = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    if name.is_empty() {
+        "prop".to_string()
+    } else {
+        name
+    }
+}
+
+fn invalid(message: impl Into<String>) -> InfernoError {
+    InfernoError::Validation(format!("Invalid JSON schema: {}", message.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_object_schema_requires_keys_in_order() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "age": {"type": "integer"},
+                "name": {"type": "string"},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["age", "name"]
+        });
+
+        let grammar = json_schema_grammar(&schema).unwrap();
+        let root = grammar.lines().next().unwrap();
+        assert_eq!(
+            root,
+            r#"root ::= "{" ws "\"age\"" ws ":" ws root-age "," ws "\"name\"" ws ":" ws root-name ( "," ws "\"tags\"" ws ":" ws root-tags )? "}" ws"#
+        );
+        assert!(grammar.contains("root-name ::= string\n"));
+        assert!(grammar.contains("root-age ::= integer\n"));
+        assert!(grammar.contains(
+            r#"root-tags ::= "[" ws ( root-tags-item ( "," ws root-tags-item )* )? "]" ws"#
+        ));
+    }
+
+    #[test]
+    fn test_enum_and_optional_only_object() {
+        let schema = json!({
+            "properties": {
+                "mood": {"enum": ["happy", "sad"]},
+                "score": {"type": ["number", "null"]}
+            }
+        });
+
+        let grammar = json_schema_grammar(&schema).unwrap();
+        assert!(grammar.contains(r#"root-mood ::= ( "\"happy\"" ws | "\"sad\"" ws )"#));
+        assert!(grammar.contains("root-score ::= ( number | null )"));
+        assert!(grammar.starts_with(r#"root ::= "{" ws ( ( "\"mood\"" ws ":" ws root-mood ( "," ws "\"score\"" ws ":" ws root-score )? | "\"score\"" ws ":" ws root-score ) )? "}" ws"#));
+    }
+
+    #[test]
+    fn test_invalid_schemas_are_validation_errors() {
+        for schema in [
+            json!("object"),
+            json!({"type": "tuple"}),
+            json!({"type": "object", "properties": []}),
+            json!({"type": "object", "properties": {"a": {}}, "required": ["b"]}),
+            json!({"enum": []}),
+            json!({"$ref": "#/definitions/thing"}),
+        ] {
+            let err = json_schema_grammar(&schema).unwrap_err();
+            assert!(matches!(err, InfernoError::Validation(_)), "{:?}", schema);
+        }
+    }
+
+    #[test]
+    fn test_json_object_grammar() {
+        let grammar = json_object_grammar();
+        assert!(grammar.starts_with("root ::= object\n"));
+        assert!(grammar.contains("ws ::= "));
+    }
+
+    #[test]
+    fn test_check_gbnf() {
+        check_gbnf(&json_object_grammar()).unwrap();
+        let schema = json!({"type": "object", "properties": {"tags": {"type": "array", "items": {"enum": ["a", "b"]}}}});
+        check_gbnf(&json_schema_grammar(&schema).unwrap()).unwrap();
+        check_gbnf("# yes or no\nroot ::= answer\nanswer ::= (\"yes\" |\n    \"no\") [.!]?\n")
+            .unwrap();
+
+        for (gbnf, problem) in [
+            ("answer ::= \"yes\"", "no 'root' rule"),
+            ("root ::= \"yes", "unclosed literal"),
+            ("root ::= [a-z", "unclosed character class"),
+            ("root ::= (\"a\" | \"b\"", "unclosed '('"),
+            ("root ::= \"a\")", "unmatched ')'"),
+            ("root ::= answer", "undefined rules: answer"),
+            ("root ::= \"a\" ; \"b\"", "unexpected ';'"),
+            ("root answer ::= \"a\"", "not a valid rule name"),
+        ] {
+            let err = check_gbnf(gbnf).unwrap_err().to_string();
+            assert!(err.contains(problem), "{}: {}", gbnf, err);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Token log probabilities
+//!
+//! With [`InferenceParams::logprobs`](super::InferenceParams::logprobs) set, backends
+//! that can see the model's logits report the log probability of every token they
+//! generate along with the likeliest alternatives at that position. Backends that
+//! cannot leave [`Generation::logprobs`](super::Generation::logprobs) unset.
+
+use serde::{Deserialize, Serialize};
+
+/// Most alternatives reported per token, as in the OpenAI API
+pub const MAX_TOP_LOGPROBS: u32 = 20;
+
+/// A generated token and how likely the model found it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub logprob: f32,
+    /// Likeliest tokens at this position, most likely first
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopLogprob {
+    pub token: String,
+    pub logprob: f32,
+}
+
+impl TokenLogprob {
+    /// Log probability of `chosen` and the `top_n` likeliest of `candidates`, given
+    /// as token id and logit pairs over the vocabulary
+    ///
+    /// `token_text` renders a token id.
+    pub fn from_logits(
+        candidates: &[(i32, f32)],
+        chosen: i32,
+        top_n: u32,
+        token_text: impl Fn(i32) -> String,
+    ) -> Self {
+        let max_logit = candidates
+            .iter()
+            .map(|&(_, logit)| logit)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let log_sum = max_logit
+            + candidates
+                .iter()
+                .map(|&(_, logit)| (logit - max_logit).exp())
+                .sum::<f32>()
+                .ln();
+        let logprob_of = |logit: f32| logit - log_sum;
+
+        let logprob = candidates
+            .iter()
+            .find(|&&(id, _)| id == chosen)
+            .map_or(f32::NEG_INFINITY, |&(_, logit)| logprob_of(logit));
+
+        let mut ranked: Vec<(i32, f32)> = candidates.to_vec();
+        let top_n = top_n.min(MAX_TOP_LOGPROBS) as usize;
+        if top_n < ranked.len() {
+            ranked.select_nth_unstable_by(top_n, |a, b| b.1.total_cmp(&a.1));
+            ranked.truncate(top_n);
+        }
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        Self {
+            token: token_text(chosen),
+            logprob,
+            top_logprobs: ranked
+                .into_iter()
+                .map(|(id, logit)| TopLogprob {
+                    token: token_text(id),
+                    logprob: logprob_of(logit),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logprobs_from_logits() {
+        // Probabilities 0.5, 0.25, 0.125, 0.125
+        let candidates: Vec<(i32, f32)> = [0.5f32, 0.25, 0.125, 0.125]
+            .iter()
+            .enumerate()
+            .map(|(id, p)| (id as i32, p.ln() + 3.0))
+            .collect();
+        let logprob = TokenLogprob::from_logits(&candidates, 1, 2, |id| format!("t{}", id));
+
+        assert_eq!(logprob.token, "t1");
+        assert!((logprob.logprob - 0.25f32.ln()).abs() < 1e-5);
+        let top: Vec<&str> = logprob
+            .top_logprobs
+            .iter()
+            .map(|top| top.token.as_str())
+            .collect();
+        assert_eq!(top, ["t0", "t1"]);
+        assert!((logprob.top_logprobs[0].logprob - 0.5f32.ln()).abs() < 1e-5);
+
+        // More alternatives than the vocabulary returns the whole vocabulary
+        let all = TokenLogprob::from_logits(&candidates, 0, 10, |id| id.to_string());
+        assert_eq!(all.top_logprobs.len(), 4);
+    }
+}
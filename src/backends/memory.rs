@@ -0,0 +1,205 @@
+//! Working-set memory ceiling
+//!
+//! With `memory_limit_mb` set in the backend config, model loads and requests are
+//! refused with [`InfernoError::Resource`] when the process's resident memory plus
+//! what they are expected to need would exceed it, rather than leaving the kernel to
+//! OOM-kill the server. A load is expected to need the size of the model file; a
+//! request the KV cache for its prompt and `max_tokens`, at
+//! [`REQUEST_BYTES_PER_TOKEN`].
+
+use crate::{InfernoError, backends::tokenizer::estimate_tokens};
+use std::sync::{Arc, OnceLock};
+use tracing::debug;
+
+static GUARD: OnceLock<MemoryGuard> = OnceLock::new();
+
+const MB: u64 = 1024 * 1024;
+
+/// Rough KV cache footprint of one token of context
+pub const REQUEST_BYTES_PER_TOKEN: u64 = 512 * 1024;
+
+/// Reports how much memory the process is using
+pub trait MemoryProbe: Send + Sync {
+    fn used_bytes(&self) -> u64;
+}
+
+/// Resident set size of this process
+struct ProcessMemory;
+
+impl MemoryProbe for ProcessMemory {
+    fn used_bytes(&self) -> u64 {
+        use sysinfo::{ProcessExt, System, SystemExt};
+        let Ok(pid) = sysinfo::get_current_pid() else {
+            return 0;
+        };
+        let mut system = System::new();
+        system.refresh_process(pid);
+        // sysinfo 0.29+ returns memory in bytes
+        system.process(pid).map_or(0, |process| process.memory())
+    }
+}
+
+/// Refuses work that would take the process over a memory ceiling
+#[derive(Clone)]
+pub struct MemoryGuard {
+    limit_bytes: Option<u64>,
+    probe: Arc<dyn MemoryProbe>,
+}
+
+impl std::fmt::Debug for MemoryGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryGuard")
+            .field("limit_bytes", &self.limit_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MemoryGuard {
+    /// Guard the process's resident memory; `None` allows everything
+    pub fn new(limit_bytes: Option<u64>) -> Self {
+        Self::with_probe(limit_bytes, Arc::new(ProcessMemory))
+    }
+
+    /// Guard the usage `probe` reports
+    pub fn with_probe(limit_bytes: Option<u64>, probe: Arc<dyn MemoryProbe>) -> Self {
+        Self { limit_bytes, probe }
+    }
+
+    pub fn limit_bytes(&self) -> Option<u64> {
+        self.limit_bytes
+    }
+
+    /// Fail when `additional_bytes` on top of current usage would exceed the limit
+    ///
+    /// `what` names the work being refused in the error.
+    pub fn check(&self, additional_bytes: u64, what: &str) -> Result<(), InfernoError> {
+        let Some(limit) = self.limit_bytes else {
+            return Ok(());
+        };
+        let used = self.probe.used_bytes();
+        if used.saturating_add(additional_bytes) > limit {
+            return Err(InfernoError::Resource(format!(
+                "Refusing to {}: needs about {} MB with {} MB of the {} MB memory limit in use",
+                what,
+                additional_bytes / MB,
+                used / MB,
+                limit / MB
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check a request with `prompt` that may generate `max_tokens`
+    pub fn check_request(&self, prompt: &str, max_tokens: u32) -> Result<(), InfernoError> {
+        if self.limit_bytes.is_none() {
+            return Ok(());
+        }
+        let tokens = estimate_tokens(prompt) as u64 + max_tokens as u64;
+        self.check(tokens * REQUEST_BYTES_PER_TOKEN, "start the request")
+    }
+}
+
+impl Default for MemoryGuard {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// Set the process-wide limit in megabytes; `None` leaves memory unlimited
+///
+/// Only the first call before any backend is created takes effect, so call this
+/// at startup. Returns whether the guard was configured.
+pub fn configure(limit_mb: Option<u64>) -> bool {
+    let configured = GUARD
+        .set(MemoryGuard::new(limit_mb.map(|mb| mb * MB)))
+        .is_ok();
+    if configured && let Some(limit_mb) = limit_mb {
+        debug!("Working-set memory limited to {} MB", limit_mb);
+    }
+    configured
+}
+
+/// The process-wide guard backends check before loading models and serving requests
+pub fn guard() -> &'static MemoryGuard {
+    GUARD.get_or_init(MemoryGuard::default)
+}
+
+/// Resident set size of this process
+pub fn resident_bytes() -> u64 {
+    ProcessMemory.used_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::InferenceParams;
+    use crate::backends::mock::MockBackend;
+    use crate::models::ModelInfo;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    const GB: u64 = 1024 * MB;
+
+    /// Reports whatever usage the test sets
+    struct FakeUsage(AtomicU64);
+
+    impl MemoryProbe for FakeUsage {
+        fn used_bytes(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    fn model(size: u64) -> ModelInfo {
+        ModelInfo {
+            name: "model.gguf".to_string(),
+            path: "model.gguf".into(),
+            file_path: "model.gguf".into(),
+            size,
+            size_bytes: size,
+            modified: chrono::Utc::now(),
+            backend_type: "gguf".to_string(),
+            format: "gguf".to_string(),
+            checksum: None,
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_rejected_over_memory_limit() {
+        let usage = Arc::new(FakeUsage(AtomicU64::new(7 * GB)));
+        let mock = MockBackend::tokens(&["ok"]);
+        let state = mock.state();
+        let mut backend = mock
+            .backend()
+            .with_memory_guard(MemoryGuard::with_probe(Some(8 * GB), usage.clone()));
+
+        let err = backend.load_model(&model(2 * GB)).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InfernoError>(),
+            Some(InfernoError::Resource(_))
+        ));
+        assert_eq!(state.loads(), 0);
+
+        // The same model fits once usage drops
+        usage.0.store(4 * GB, Ordering::SeqCst);
+        backend.load_model(&model(2 * GB)).await.unwrap();
+        assert_eq!(state.loads(), 1);
+
+        // A request whose context would not fit is refused, a small one is served
+        usage.0.store(8 * GB - 100 * MB, Ordering::SeqCst);
+        let params = InferenceParams {
+            max_tokens: 4096,
+            ..Default::default()
+        };
+        let err = backend.infer("prompt", &params).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InfernoError>(),
+            Some(InfernoError::Resource(_))
+        ));
+        let params = InferenceParams {
+            max_tokens: 16,
+            ..Default::default()
+        };
+        assert_eq!(backend.infer("prompt", &params).await.unwrap(), "ok");
+    }
+}
@@ -0,0 +1,124 @@
+//! Memory-mapped model files
+//!
+//! With `memory_map` on, a model's weights are mapped from its file instead of
+//! being read into the heap. Mapped pages live in the page cache: they are shared
+//! with every other mapping of the file and the kernel can reclaim them, so a
+//! large model costs its size once rather than twice. `prefetch_model` asks the
+//! kernel to start reading the file in ahead of the first inference, and
+//! `lock_memory` pins the weights so they are never swapped out.
+//!
+//! Live mappings are counted process-wide; metrics report their total next to the
+//! process's resident memory.
+
+use crate::InfernoError;
+use memmap2::Mmap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static MAPPED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// A model file mapped into memory, read-only
+#[derive(Debug)]
+pub struct ModelMapping {
+    map: Mmap,
+}
+
+impl ModelMapping {
+    /// Map the file at `path`, asking the kernel to read it in when `prefetch`
+    pub fn open(path: &Path, prefetch: bool) -> Result<Self, InfernoError> {
+        let file = std::fs::File::open(path).map_err(|e| {
+            InfernoError::Backend(format!("Cannot open model file {}: {}", path.display(), e))
+        })?;
+        // Safety: the mapping is read-only. Model files are not modified while
+        // they are loaded, the same assumption llama.cpp makes for its own mapping
+        let map = unsafe { Mmap::map(&file) }.map_err(|e| {
+            InfernoError::Backend(format!("Cannot map model file {}: {}", path.display(), e))
+        })?;
+
+        if prefetch && let Err(e) = will_need(&map) {
+            tracing::warn!("Cannot prefetch {}: {}", path.display(), e);
+        }
+        MAPPED_BYTES.fetch_add(map.len() as u64, Ordering::Relaxed);
+        Ok(Self { map })
+    }
+
+    pub fn mapped_bytes(&self) -> u64 {
+        self.map.len() as u64
+    }
+
+    /// Bytes of the mapping currently in memory, where the platform can tell
+    pub fn resident_bytes(&self) -> Option<u64> {
+        resident_bytes(&self.map)
+    }
+}
+
+impl Drop for ModelMapping {
+    fn drop(&mut self) {
+        MAPPED_BYTES.fetch_sub(self.map.len() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Bytes of model files mapped by this process
+pub fn mapped_bytes() -> u64 {
+    MAPPED_BYTES.load(Ordering::Relaxed)
+}
+
+#[cfg(unix)]
+fn will_need(map: &Mmap) -> std::io::Result<()> {
+    map.advise(memmap2::Advice::WillNeed)
+}
+
+#[cfg(not(unix))]
+fn will_need(_map: &Mmap) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn resident_bytes(map: &Mmap) -> Option<u64> {
+    if map.is_empty() {
+        return Some(0);
+    }
+    // Safety: sysconf has no memory-safety preconditions
+    let page_size = usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).ok()?;
+    let mut pages = vec![0u8; map.len().div_ceil(page_size)];
+    // Safety: the range is exactly the live mapping and `pages` has one entry per
+    // page of it, as mincore requires
+    let status = unsafe {
+        libc::mincore(
+            map.as_ptr() as *mut libc::c_void,
+            map.len(),
+            pages.as_mut_ptr().cast(),
+        )
+    };
+    if status != 0 {
+        return None;
+    }
+    let resident = pages.iter().filter(|&&page| page & 1 == 1).count();
+    Some((resident * page_size).min(map.len()) as u64)
+}
+
+#[cfg(not(unix))]
+fn resident_bytes(_map: &Mmap) -> Option<u64> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_mapped_model_reports_file_size() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"GGUF").unwrap();
+        file.write_all(&vec![7u8; 64 * 1024]).unwrap();
+        file.flush().unwrap();
+        let file_size = std::fs::metadata(file.path()).unwrap().len();
+
+        let mapping = ModelMapping::open(file.path(), true).unwrap();
+        assert_eq!(mapping.mapped_bytes(), file_size);
+        assert!(mapped_bytes() >= file_size);
+        let resident = mapping.resident_bytes().unwrap();
+        assert!(resident <= file_size, "{} of {}", resident, file_size);
+    }
+}
@@ -0,0 +1,387 @@
+//! Configurable [`InferenceBackend`] for tests
+//!
+//! [`MockBackend`] starts out loaded and replies with fixed tokens, text made
+//! from the prompt, or the contents of the model file it loaded. Builder
+//! methods add delays, failures and a tokenizer, and [`MockBackend::state`]
+//! hands out counters tests can watch after the backend is boxed.
+
+use super::{
+    Backend, BackendConfig, BackendHandle, BackendType, CancellationToken, InferenceBackend,
+    InferenceMetrics, InferenceParams, TokenLogprob, TokenStream, Tokenizer,
+};
+use crate::{InfernoError, models::ModelInfo};
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use futures::StreamExt;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use std::time::Duration;
+
+/// What a [`MockBackend`] says
+enum Reply {
+    Tokens(Vec<String>),
+    /// Text made from the prompt, streamed as one token
+    Prompt(fn(&str) -> String),
+    /// Text made from the backend's config, streamed as one token
+    Config(fn(&BackendConfig) -> String),
+    /// The text of the loaded model file, streamed as one token
+    ModelFile(Option<String>),
+}
+
+/// Counters and the loaded flag of a [`MockBackend`], shared with the test
+#[derive(Debug, Default)]
+pub(crate) struct MockState {
+    /// Inference calls, streaming or not
+    pub calls: AtomicUsize,
+    pub loads: AtomicUsize,
+    /// Tokens generated; streamed tokens count as they are polled
+    pub tokens: AtomicUsize,
+    pub embeddings: AtomicUsize,
+    pub loaded: AtomicBool,
+}
+
+impl MockState {
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    pub fn loads(&self) -> usize {
+        self.loads.load(Ordering::SeqCst)
+    }
+
+    pub fn tokens(&self) -> usize {
+        self.tokens.load(Ordering::SeqCst)
+    }
+
+    pub fn embeddings(&self) -> usize {
+        self.embeddings.load(Ordering::SeqCst)
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.loaded.load(Ordering::SeqCst)
+    }
+}
+
+pub(crate) struct MockBackend {
+    reply: Reply,
+    state: Arc<MockState>,
+    config: BackendConfig,
+    model: Option<ModelInfo>,
+    backend_type: BackendType,
+    /// Wait before replying, then fail if the model was unloaded meanwhile
+    delay: Duration,
+    /// Wait before each token, by the 1-based number of the call
+    token_delay: fn(usize) -> Duration,
+    embed_delay: Duration,
+    error: Option<fn() -> InfernoError>,
+    fail_stream_after: Option<usize>,
+    /// Streams for prompts ending with this never produce a token
+    stall_on: Option<&'static str>,
+    fail_gpu_init: bool,
+    tokenizer: Option<Arc<dyn Tokenizer>>,
+    context_length: Option<u32>,
+    /// Tokens after the reply's that logprobs report as alternatives
+    logprob_alternatives: Option<&'static [&'static str]>,
+}
+
+impl MockBackend {
+    fn with_reply(reply: Reply) -> Self {
+        let state = MockState::default();
+        state.loaded.store(true, Ordering::SeqCst);
+        Self {
+            reply,
+            state: Arc::new(state),
+            config: BackendConfig::default(),
+            model: None,
+            backend_type: BackendType::value_variants()[0],
+            delay: Duration::ZERO,
+            token_delay: |_| Duration::ZERO,
+            embed_delay: Duration::ZERO,
+            error: None,
+            fail_stream_after: None,
+            stall_on: None,
+            fail_gpu_init: false,
+            tokenizer: None,
+            context_length: None,
+            logprob_alternatives: None,
+        }
+    }
+
+    /// Replies with `tokens`
+    pub fn tokens(tokens: &[&str]) -> Self {
+        Self::with_reply(Reply::Tokens(
+            tokens.iter().map(|token| token.to_string()).collect(),
+        ))
+    }
+
+    /// Replies with `text`, a word at a time
+    pub fn reply(text: &str) -> Self {
+        Self::tokens(&text.split_inclusive(' ').collect::<Vec<_>>())
+    }
+
+    /// Replies with `respond(prompt)`
+    pub fn prompt(respond: fn(&str) -> String) -> Self {
+        Self::with_reply(Reply::Prompt(respond))
+    }
+
+    /// Replies with `respond(config)` for its current config
+    pub fn config_reply(respond: fn(&BackendConfig) -> String) -> Self {
+        Self::with_reply(Reply::Config(respond))
+    }
+
+    /// Starts unloaded; loading reads the model file, whose text is the reply
+    pub fn model_file() -> Self {
+        Self::with_reply(Reply::ModelFile(None)).unloaded()
+    }
+
+    pub fn unloaded(self) -> Self {
+        self.state.loaded.store(false, Ordering::SeqCst);
+        self
+    }
+
+    pub fn config(mut self, config: &BackendConfig) -> Self {
+        self.config = config.clone();
+        self
+    }
+
+    pub fn backend_type(mut self, backend_type: BackendType) -> Self {
+        self.backend_type = backend_type;
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Waits `token_delay(call)` before each token of the `call`th request
+    pub fn token_delay(mut self, token_delay: fn(usize) -> Duration) -> Self {
+        self.token_delay = token_delay;
+        self
+    }
+
+    pub fn embed_delay(mut self, delay: Duration) -> Self {
+        self.embed_delay = delay;
+        self
+    }
+
+    /// Fails every request with `error()`
+    pub fn error(mut self, error: fn() -> InfernoError) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    /// Ends streams with a backend error after `tokens` tokens
+    pub fn fail_stream_after(mut self, tokens: usize) -> Self {
+        self.fail_stream_after = Some(tokens);
+        self
+    }
+
+    pub fn stall_on(mut self, prompt_suffix: &'static str) -> Self {
+        self.stall_on = Some(prompt_suffix);
+        self
+    }
+
+    /// Fails to load with [`InfernoError::GpuInit`] while the GPU is enabled
+    pub fn fail_gpu_init(mut self) -> Self {
+        self.fail_gpu_init = true;
+        self
+    }
+
+    pub fn tokenizer(mut self, tokenizer: impl Tokenizer + 'static) -> Self {
+        self.tokenizer = Some(Arc::new(tokenizer));
+        self
+    }
+
+    pub fn context_length(mut self, context_length: u32) -> Self {
+        self.context_length = Some(context_length);
+        self
+    }
+
+    /// Reports logprobs for the reply's tokens, ranking each ahead of the
+    /// tokens after it and then `alternatives`
+    pub fn logprobs(mut self, alternatives: &'static [&'static str]) -> Self {
+        self.logprob_alternatives = Some(alternatives);
+        self
+    }
+
+    pub fn state(&self) -> Arc<MockState> {
+        Arc::clone(&self.state)
+    }
+
+    pub fn backend(self) -> Backend {
+        Backend::from_impl(Box::new(self))
+    }
+
+    pub fn handle(self) -> BackendHandle {
+        BackendHandle::new(self.backend())
+    }
+
+    fn reply_tokens(&self, input: &str) -> Result<Vec<String>> {
+        Ok(match &self.reply {
+            Reply::Tokens(tokens) => tokens.clone(),
+            Reply::Prompt(respond) => vec![respond(input)],
+            Reply::Config(respond) => vec![respond(&self.config)],
+            Reply::ModelFile(text) => vec![text.clone().ok_or_else(|| anyhow!("No model loaded"))?],
+        })
+    }
+
+    /// Start a request: count it, wait out the delay, and fail as configured
+    async fn begin(&self, input: &str) -> Result<(usize, Vec<String>)> {
+        let call = self.state.calls.fetch_add(1, Ordering::SeqCst) + 1;
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay).await;
+        }
+        if !self.state.is_loaded() {
+            return Err(anyhow!("No model loaded"));
+        }
+        if let Some(error) = self.error {
+            return Err(error().into());
+        }
+        Ok((call, self.reply_tokens(input)?))
+    }
+}
+
+#[async_trait::async_trait]
+impl InferenceBackend for MockBackend {
+    async fn load_model(&mut self, model_info: &ModelInfo) -> Result<()> {
+        self.state.loads.fetch_add(1, Ordering::SeqCst);
+        if self.fail_gpu_init && self.config.gpu_enabled {
+            return Err(InfernoError::GpuInit("no Vulkan device found".to_string()).into());
+        }
+        if let Reply::ModelFile(text) = &mut self.reply {
+            *text = Some(std::fs::read_to_string(&model_info.path)?);
+        }
+        self.model = Some(model_info.clone());
+        self.state.loaded.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn unload_model(&mut self) -> Result<()> {
+        if let Reply::ModelFile(text) = &mut self.reply {
+            *text = None;
+        }
+        self.model = None;
+        self.state.loaded.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn is_loaded(&self) -> bool {
+        self.state.is_loaded()
+    }
+
+    async fn get_model_info(&self) -> Option<ModelInfo> {
+        self.model.clone()
+    }
+
+    async fn infer(&mut self, input: &str, _params: &InferenceParams) -> Result<String> {
+        let (call, tokens) = self.begin(input).await?;
+        let token_delay = (self.token_delay)(call);
+        for _ in &tokens {
+            if !token_delay.is_zero() {
+                tokio::time::sleep(token_delay).await;
+            }
+            self.state.tokens.fetch_add(1, Ordering::SeqCst);
+        }
+        Ok(tokens.concat())
+    }
+
+    async fn infer_stream(
+        &mut self,
+        input: &str,
+        _params: &InferenceParams,
+    ) -> Result<TokenStream> {
+        if self.stall_on.is_some_and(|suffix| input.ends_with(suffix)) {
+            self.state.calls.fetch_add(1, Ordering::SeqCst);
+            return Ok(Box::pin(futures::stream::pending()));
+        }
+        let (call, tokens) = self.begin(input).await?;
+        let mut tokens: Vec<_> = tokens.into_iter().map(Ok).collect();
+        if let Some(count) = self.fail_stream_after {
+            tokens.truncate(count);
+            tokens.push(Err(InfernoError::Backend("GPU device lost".to_string())));
+        }
+
+        let token_delay = (self.token_delay)(call);
+        let state = Arc::clone(&self.state);
+        Ok(Box::pin(futures::stream::iter(tokens).then(move |token| {
+            let state = Arc::clone(&state);
+            async move {
+                if !token_delay.is_zero() {
+                    tokio::time::sleep(token_delay).await;
+                }
+                if token.is_ok() {
+                    state.tokens.fetch_add(1, Ordering::SeqCst);
+                }
+                token
+            }
+        })))
+    }
+
+    async fn get_embeddings(&mut self, _input: &str) -> Result<Vec<f32>> {
+        if !self.embed_delay.is_zero() {
+            tokio::time::sleep(self.embed_delay).await;
+        }
+        self.state.embeddings.fetch_add(1, Ordering::SeqCst);
+        Ok(vec![0.0])
+    }
+
+    fn get_backend_type(&self) -> BackendType {
+        self.backend_type
+    }
+
+    fn get_metrics(&self) -> Option<InferenceMetrics> {
+        None
+    }
+
+    fn tokenizer(&self) -> Option<Arc<dyn Tokenizer>> {
+        self.tokenizer.clone()
+    }
+
+    fn context_length(&self) -> Option<u32> {
+        self.context_length
+    }
+
+    /// Applies per-request settings to a loaded model in place, like GGUF
+    fn reconfigure(&mut self, config: &BackendConfig) -> Result<bool> {
+        let in_place = !self.state.is_loaded()
+            || self
+                .config
+                .changed_settings(config)
+                .iter()
+                .all(|setting| BackendConfig::PER_REQUEST_SETTINGS.contains(setting));
+        self.config = config.clone();
+        Ok(in_place)
+    }
+
+    async fn infer_logprobs(
+        &mut self,
+        input: &str,
+        params: &InferenceParams,
+        _cancel: &CancellationToken,
+    ) -> Result<Option<(String, Vec<TokenLogprob>)>> {
+        let Some(alternatives) = self.logprob_alternatives else {
+            return Ok(None);
+        };
+        let (_, tokens) = self.begin(input).await?;
+        let vocabulary: Vec<&str> = tokens
+            .iter()
+            .map(String::as_str)
+            .chain(alternatives.iter().copied())
+            .collect();
+        let candidates: Vec<(i32, f32)> = (0..vocabulary.len() as i32)
+            .map(|id| (id, -(id as f32)))
+            .collect();
+        let logprobs = (0..tokens.len() as i32)
+            .map(|chosen| {
+                TokenLogprob::from_logits(&candidates, chosen, params.logprobs.unwrap_or(0), |id| {
+                    vocabulary[id as usize].to_string()
+                })
+            })
+            .collect();
+        self.state.tokens.fetch_add(tokens.len(), Ordering::SeqCst);
+        Ok(Some((tokens.concat(), logprobs)))
+    }
+}
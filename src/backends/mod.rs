@@ -1,18 +1,48 @@
 #![allow(dead_code, unused_imports, unused_variables, clippy::needless_return)]
+pub mod blocking;
+pub mod context;
+pub mod embeddings;
+pub mod generation;
 #[cfg(feature = "gguf")]
 mod gguf;
+pub mod grammar;
+pub mod logprobs;
+pub mod memory;
 #[cfg(all(feature = "gpu-metal", target_os = "macos"))]
 mod metal;
+pub mod mmap;
+#[cfg(test)]
+pub(crate) mod mock;
 #[cfg(feature = "onnx")]
 mod onnx;
+#[cfg(feature = "onnx")]
+mod onnx_speech;
+pub mod postprocess;
+pub mod preflight;
+pub mod rope;
+pub mod stop;
+pub mod tokenizer;
+
+pub use context::ContextPolicy;
+pub use embeddings::EmbeddingOptions;
+pub use generation::{FinishReason, Generation};
+pub use logprobs::{TokenLogprob, TopLogprob};
+pub use memory::MemoryGuard;
+pub use postprocess::PostProcessor;
+pub use preflight::Preflight;
+pub use rope::{RopeConfig, RopeScaling};
+pub use stop::StopRegex;
+pub use tokenizer::Tokenizer;
 
 use crate::{InfernoError, models::ModelInfo};
 use anyhow::{Result, anyhow};
 use clap::ValueEnum;
 use futures::Stream;
 use serde::{Deserialize, Serialize};
-use std::{path::Path, pin::Pin, sync::Arc};
-use tokio::sync::Mutex;
+use std::{borrow::Cow, path::Path, pin::Pin, sync::Arc};
+use tokio::sync::{Mutex, watch};
+pub use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
 pub enum BackendType {
@@ -88,14 +118,46 @@ impl std::fmt::Display for BackendType {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BackendConfig {
     pub gpu_enabled: bool,
+    /// Fail model loads when the GPU cannot be initialized, instead of falling
+    /// back to the CPU
+    #[serde(default)]
+    pub require_gpu: bool,
     pub gpu_device: Option<String>,
     pub cpu_threads: Option<u32>,
     pub context_size: u32,
     pub batch_size: u32,
+    /// Map model weights from their file rather than reading them into the heap;
+    /// the GGUF backend refuses to load with this off
     pub memory_map: bool,
+    /// Ask the kernel to read a mapped model in as soon as it is loaded
+    #[serde(default)]
+    pub prefetch_model: bool,
+    /// Lock model weights in memory (mlock) so they are never swapped out
+    #[serde(default)]
+    pub lock_memory: bool,
+    /// Backend calls that may block a thread at once; unset allows one per CPU
+    #[serde(default)]
+    pub blocking_threads: Option<usize>,
+    /// Refuse model loads and requests that would take the process's resident
+    /// memory over this many megabytes; unset leaves memory unlimited
+    #[serde(default)]
+    pub memory_limit_mb: Option<u64>,
+    /// RoPE frequency scaling for running GGUF models past their trained context
+    #[serde(default)]
+    pub rope: RopeConfig,
+    /// Whether prompts too long for the context window are rejected or truncated
+    #[serde(default)]
+    pub context_policy: ContextPolicy,
+    /// Check models against the host's memory and build features before loading
+    #[serde(default = "default_preflight")]
+    pub preflight: bool,
+}
+
+fn default_preflight() -> bool {
+    true
 }
 
 impl Default for BackendConfig {
@@ -103,11 +165,19 @@ impl Default for BackendConfig {
         Self {
             // Enable GPU by default on macOS (Metal), disable on other platforms
             gpu_enabled: cfg!(target_os = "macos"),
+            require_gpu: false,
             gpu_device: None,
             cpu_threads: None,
             context_size: 2048,
             batch_size: 32,
             memory_map: true,
+            prefetch_model: false,
+            lock_memory: false,
+            blocking_threads: None,
+            memory_limit_mb: None,
+            rope: RopeConfig::default(),
+            context_policy: ContextPolicy::default(),
+            preflight: true,
         }
     }
 }
@@ -120,11 +190,19 @@ impl BackendConfig {
     pub fn with_metal_acceleration() -> Self {
         Self {
             gpu_enabled: true,
+            require_gpu: false,
             gpu_device: None,   // Auto-detect Metal GPU
             cpu_threads: None,  // Let the backend choose optimal thread count
             context_size: 4096, // Larger context for Metal (unified memory)
             batch_size: 64,     // Larger batch size for GPU
             memory_map: true,
+            prefetch_model: false,
+            lock_memory: false,
+            blocking_threads: None,
+            memory_limit_mb: None,
+            rope: RopeConfig::default(),
+            context_policy: ContextPolicy::default(),
+            preflight: true,
         }
     }
 
@@ -135,6 +213,91 @@ impl BackendConfig {
             ..Default::default()
         }
     }
+
+    /// Settings that backends creating an inference context for each request
+    /// pick up without reloading the model
+    pub const PER_REQUEST_SETTINGS: &'static [&'static str] =
+        &["cpu_threads", "batch_size", "context_policy", "preflight"];
+
+    /// Names of the settings that differ between `self` and `other`
+    pub fn changed_settings(&self, other: &BackendConfig) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        let mut compare = |name, differs: bool| {
+            if differs {
+                changed.push(name);
+            }
+        };
+        compare("gpu_enabled", self.gpu_enabled != other.gpu_enabled);
+        compare("require_gpu", self.require_gpu != other.require_gpu);
+        compare("gpu_device", self.gpu_device != other.gpu_device);
+        compare("cpu_threads", self.cpu_threads != other.cpu_threads);
+        compare("context_size", self.context_size != other.context_size);
+        compare("batch_size", self.batch_size != other.batch_size);
+        compare("memory_map", self.memory_map != other.memory_map);
+        compare(
+            "prefetch_model",
+            self.prefetch_model != other.prefetch_model,
+        );
+        compare("lock_memory", self.lock_memory != other.lock_memory);
+        compare(
+            "blocking_threads",
+            self.blocking_threads != other.blocking_threads,
+        );
+        compare(
+            "memory_limit_mb",
+            self.memory_limit_mb != other.memory_limit_mb,
+        );
+        compare("rope", self.rope != other.rope);
+        compare(
+            "context_policy",
+            self.context_policy != other.context_policy,
+        );
+        compare("preflight", self.preflight != other.preflight);
+        changed
+    }
+}
+
+/// Where a backend runs its model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComputeDevice {
+    Cpu,
+    Gpu,
+}
+
+impl std::fmt::Display for ComputeDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cpu => write!(f, "cpu"),
+            Self::Gpu => write!(f, "gpu"),
+        }
+    }
+}
+
+/// Why the GPU failed to initialize the last time a model load fell back to
+/// the CPU
+static GPU_FALLBACK: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+
+/// Why this process runs models on the CPU although the GPU was enabled, if a
+/// model load has had to fall back to it
+pub fn gpu_fallback() -> Option<String> {
+    GPU_FALLBACK
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// How [`Backend::reload`] applied a new configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reload {
+    /// The configuration was the same
+    Unchanged,
+    /// The loaded model was kept; the changed settings apply from the next
+    /// request
+    InPlace { changed: Vec<&'static str> },
+    /// The model was loaded again, as some of the changed settings only apply
+    /// when it is loaded
+    Full { changed: Vec<&'static str> },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -143,9 +306,146 @@ pub struct InferenceParams {
     pub temperature: f32,
     pub top_p: f32,
     pub top_k: u32,
+    /// Penalty for repeating recent tokens; unset applies none
+    #[serde(default)]
+    pub repeat_penalty: Option<f32>,
+    /// How many recent tokens the repeat penalty covers; unset uses the sampler default
+    #[serde(default)]
+    pub repeat_last_n: Option<u32>,
+    /// Drop tokens less likely than this fraction of the top token; unset disables it
+    #[serde(default)]
+    pub min_p: Option<f32>,
     pub stream: bool,
     pub stop_sequences: Vec<String>,
+    /// Stop once the output matches this regex, cutting it where the match begins
+    #[serde(default)]
+    pub stop_regex: Option<String>,
+    /// Stop once generation has run this many milliseconds, even short of `max_tokens`
+    #[serde(default)]
+    pub max_generation_ms: Option<u64>,
     pub seed: Option<u64>,
+    /// Constrain output to a format; only the GGUF backend enforces it
+    #[serde(default)]
+    pub response_format: Option<ResponseFormat>,
+    /// Report each generated token's log probability with this many likeliest
+    /// alternatives; only honoured by [`Backend::generate`] on backends that can
+    #[serde(default)]
+    pub logprobs: Option<u32>,
+}
+
+/// Shape generated output must take
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Unconstrained text
+    Text,
+    /// Any syntactically valid JSON object
+    JsonObject,
+    /// JSON matching the given JSON Schema
+    JsonSchema(serde_json::Value),
+    /// A raw GBNF grammar with a `root` rule
+    Grammar(String),
+}
+
+impl ResponseFormat {
+    /// GBNF grammar enforcing this format, or `None` when output is unconstrained
+    pub fn to_grammar(&self) -> Result<Option<String>, InfernoError> {
+        match self {
+            Self::Text => Ok(None),
+            Self::JsonObject => Ok(Some(grammar::json_object_grammar())),
+            Self::JsonSchema(schema) => grammar::json_schema_grammar(schema).map(Some),
+            Self::Grammar(gbnf) if gbnf.trim().is_empty() => Err(InfernoError::Validation(
+                "Grammar must not be empty".to_string(),
+            )),
+            Self::Grammar(gbnf) => {
+                grammar::check_gbnf(gbnf)?;
+                Ok(Some(gbnf.clone()))
+            }
+        }
+    }
+}
+
+/// Most tokens a single request may ask for
+pub const MAX_TOKENS_LIMIT: u32 = 2_000_000;
+
+/// Most stop sequences a single request may set
+pub const MAX_STOP_SEQUENCES: usize = 16;
+
+impl InferenceParams {
+    /// Check every setting is in range before a model is loaded or a request
+    /// dispatched for these params, so a bad request fails fast with an
+    /// [`InfernoError::Validation`] naming the setting
+    ///
+    /// The CLI, the API and batch runs all call this on the caller's params.
+    /// [`Backend`] checks them again before dispatching, all but the number of
+    /// stop sequences, since chat templates add their end-of-turn markers to the
+    /// caller's.
+    pub fn validate(&self) -> Result<(), InfernoError> {
+        if self.stop_sequences.len() > MAX_STOP_SEQUENCES {
+            return Err(InfernoError::Validation(format!(
+                "At most {} stop sequences are allowed, got {}",
+                MAX_STOP_SEQUENCES,
+                self.stop_sequences.len()
+            )));
+        }
+        self.validate_settings()
+    }
+
+    /// Whether repeating a request gives the same output: it samples greedily or
+    /// fixes the seed
+    pub fn is_deterministic(&self) -> bool {
+        self.temperature <= 0.0 || self.seed.is_some()
+    }
+
+    /// [`Self::validate`] without the limit on the number of stop sequences
+    fn validate_settings(&self) -> Result<(), InfernoError> {
+        let invalid = |message: String| Err(InfernoError::Validation(message));
+        if self.max_tokens == 0 || self.max_tokens > MAX_TOKENS_LIMIT {
+            return invalid(format!(
+                "max_tokens must be between 1 and {}, got {}",
+                MAX_TOKENS_LIMIT, self.max_tokens
+            ));
+        }
+        if !(0.0..=2.0).contains(&self.temperature) {
+            return invalid(format!(
+                "temperature must be between 0 and 2, got {}",
+                self.temperature
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.top_p) {
+            return invalid(format!("top_p must be between 0 and 1, got {}", self.top_p));
+        }
+        if let Some(min_p) = self.min_p
+            && !(0.0..=1.0).contains(&min_p)
+        {
+            return invalid(format!("min_p must be between 0 and 1, got {}", min_p));
+        }
+        if let Some(penalty) = self.repeat_penalty
+            && !(penalty.is_finite() && penalty > 0.0)
+        {
+            return invalid(format!(
+                "repeat_penalty must be greater than 0, got {}",
+                penalty
+            ));
+        }
+        if self.stop_sequences.iter().any(String::is_empty) {
+            return invalid("Stop sequences must not be empty".to_string());
+        }
+        StopRegex::from_params(self)?;
+        if let Some(format @ ResponseFormat::Grammar(_)) = &self.response_format {
+            format.to_grammar()?;
+        }
+        Ok(())
+    }
+
+    /// These params with `seed` set, drawn at random when unset, so the output can
+    /// be reproduced by sending the seed back
+    pub fn with_effective_seed(&self) -> Self {
+        Self {
+            seed: Some(self.seed.unwrap_or_else(rand::random)),
+            ..self.clone()
+        }
+    }
 }
 
 impl Default for InferenceParams {
@@ -155,9 +455,16 @@ impl Default for InferenceParams {
             temperature: 0.7,
             top_p: 0.9,
             top_k: 40,
+            repeat_penalty: None,
+            repeat_last_n: None,
+            min_p: None,
             stream: false,
             stop_sequences: vec![],
+            stop_regex: None,
+            max_generation_ms: None,
             seed: None,
+            response_format: None,
+            logprobs: None,
         }
     }
 }
@@ -186,12 +493,190 @@ pub trait InferenceBackend: Send + Sync {
     async fn infer_stream(&mut self, input: &str, params: &InferenceParams) -> Result<TokenStream>;
     async fn get_embeddings(&mut self, input: &str) -> Result<Vec<f32>>;
 
+    /// Like [`infer`](Self::infer), but gives up with [`InfernoError::Cancelled`]
+    /// once `cancel` fires
+    ///
+    /// The default stops awaiting `infer`; backends that generate on another
+    /// thread override it to stop generating as well.
+    async fn infer_cancellable(
+        &mut self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(cancelled().into()),
+            result = self.infer(input, params) => result,
+        }
+    }
+
+    /// Like [`infer_stream`](Self::infer_stream), but the stream ends with
+    /// [`InfernoError::Cancelled`] once `cancel` fires
+    async fn infer_stream_cancellable(
+        &mut self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<TokenStream> {
+        let stream = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => return Err(cancelled().into()),
+            stream = self.infer_stream(input, params) => stream?,
+        };
+        Ok(cancellable_stream(stream, cancel.clone()))
+    }
+
     fn get_backend_type(&self) -> BackendType;
     fn get_metrics(&self) -> Option<InferenceMetrics>;
+
+    /// Tokenizer of the loaded model, if the backend exposes one
+    fn tokenizer(&self) -> Option<Arc<dyn Tokenizer>> {
+        None
+    }
+
+    /// Tokens the loaded model can attend over, if the backend knows
+    fn context_length(&self) -> Option<u32> {
+        None
+    }
+
+    /// Switch to `config` for later requests and model loads, returning whether
+    /// the loaded model runs with it as it is; `false` means a changed setting
+    /// only takes effect once the model is loaded again
+    ///
+    /// The default refuses, leaving the backend to be recreated.
+    fn reconfigure(&mut self, _config: &BackendConfig) -> Result<bool> {
+        Err(InfernoError::Backend(
+            "This backend cannot change its configuration in place".to_string(),
+        )
+        .into())
+    }
+
+    /// Generate like [`infer_cancellable`](Self::infer_cancellable), also reporting
+    /// every output token's log probability and `params.logprobs` alternatives
+    ///
+    /// Backends that cannot see the model's logits return `None`.
+    async fn infer_logprobs(
+        &mut self,
+        _input: &str,
+        _params: &InferenceParams,
+        _cancel: &CancellationToken,
+    ) -> Result<Option<(String, Vec<TokenLogprob>)>> {
+        Ok(None)
+    }
+}
+
+fn cancelled() -> InfernoError {
+    InfernoError::Cancelled("Inference cancelled".to_string())
+}
+
+/// End `stream` with [`InfernoError::Cancelled`] as soon as `cancel` fires
+pub fn cancellable_stream(stream: TokenStream, cancel: CancellationToken) -> TokenStream {
+    use futures::StreamExt;
+
+    Box::pin(futures::stream::unfold(Some(stream), move |stream| {
+        let cancel = cancel.clone();
+        async move {
+            let mut stream = stream?;
+            tokio::select! {
+                biased;
+                _ = cancel.cancelled() => Some((Err(cancelled()), None)),
+                token = stream.next() => token.map(|token| (token, Some(stream))),
+            }
+        }
+    }))
+}
+
+/// Load the ONNX speech recognition model at `path` for transcription
+pub async fn load_speech_model(
+    path: &Path,
+    config: &BackendConfig,
+) -> Result<Box<dyn crate::ai_features::transcription::SpeechModel>> {
+    #[cfg(feature = "onnx")]
+    {
+        let path = path.to_path_buf();
+        let config = config.clone();
+        let model = blocking::run(move || onnx_speech::OnnxSpeechModel::load(&path, &config))
+            .await
+            .map_err(|e| InfernoError::Backend(format!("Model loading task failed: {}", e)))??;
+        Ok(Box::new(model))
+    }
+
+    #[cfg(not(feature = "onnx"))]
+    {
+        let _ = config;
+        Err(anyhow!(
+            "Transcribing {} needs the ONNX backend. Enable the 'onnx' feature.",
+            path.display()
+        ))
+    }
+}
+
+/// Count of streams still running on a backend's model
+///
+/// A stream hands out tokens after the backend lock is released, so unloading
+/// waits on this count rather than the lock.
+#[derive(Debug, Clone)]
+struct StreamLeases(Arc<watch::Sender<usize>>);
+
+impl StreamLeases {
+    fn new() -> Self {
+        Self(Arc::new(watch::channel(0).0))
+    }
+
+    /// Hold a lease on the model until `stream` is dropped
+    fn lease(&self, stream: TokenStream) -> TokenStream {
+        self.0.send_modify(|streams| *streams += 1);
+        let lease = StreamLease(Arc::clone(&self.0));
+        Box::pin(futures::StreamExt::map(stream, move |token| {
+            let _lease = &lease;
+            token
+        }))
+    }
+
+    fn is_idle(&self) -> bool {
+        *self.0.borrow() == 0
+    }
+
+    async fn wait_idle(&self) {
+        // The sender is held here, so the channel cannot close
+        let _ = self.0.subscribe().wait_for(|&streams| streams == 0).await;
+    }
+}
+
+struct StreamLease(Arc<watch::Sender<usize>>);
+
+impl Drop for StreamLease {
+    fn drop(&mut self) {
+        self.0.send_modify(|streams| *streams -= 1);
+    }
+}
+
+/// Apply the `stop_regex` and `max_generation_ms` of a request to its stream
+fn limit_stream(
+    stream: TokenStream,
+    stop: Option<StopRegex>,
+    deadline: Option<tokio::time::Instant>,
+) -> TokenStream {
+    let stream = match stop {
+        Some(stop) => stop.apply_to_stream(stream),
+        None => stream,
+    };
+    match deadline {
+        Some(deadline) => generation::with_deadline(stream, deadline),
+        None => stream,
+    }
 }
 
 pub struct Backend {
     backend_impl: Box<dyn InferenceBackend>,
+    config: BackendConfig,
+    memory: MemoryGuard,
+    context_policy: ContextPolicy,
+    preflight: Option<Preflight>,
+    /// Where the loaded model runs
+    device: Option<ComputeDevice>,
+    streams: StreamLeases,
 }
 
 impl Backend {
@@ -225,7 +710,13 @@ impl Backend {
                 }
             };
 
-            return Ok(Self { backend_impl });
+            let mut backend =
+                Self::from_impl(backend_impl).with_context_policy(config.context_policy);
+            if config.preflight {
+                backend = backend.with_preflight(Preflight::new(config));
+            }
+            backend.config = config.clone();
+            return Ok(backend);
         }
 
         #[cfg(not(any(
@@ -242,20 +733,208 @@ impl Backend {
         }
     }
 
+    /// Wrap a backend implementation provided outside this module
+    pub fn from_impl(backend_impl: Box<dyn InferenceBackend>) -> Self {
+        Self {
+            backend_impl,
+            config: BackendConfig::default(),
+            memory: memory::guard().clone(),
+            context_policy: ContextPolicy::default(),
+            preflight: None,
+            device: None,
+            streams: StreamLeases::new(),
+        }
+    }
+
+    /// Handle prompts too long for the model's context according to `policy`
+    pub fn with_context_policy(mut self, policy: ContextPolicy) -> Self {
+        self.context_policy = policy;
+        self
+    }
+
+    /// Check loads and requests against `memory` instead of the process-wide guard
+    pub fn with_memory_guard(mut self, memory: MemoryGuard) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    /// Check models against the host with `preflight` before loading them
+    pub fn with_preflight(mut self, preflight: Preflight) -> Self {
+        self.preflight = Some(preflight);
+        self
+    }
+
     /// Create a new shared backend instance wrapped in Arc<Mutex<_>>
     pub fn new_shared(backend_type: BackendType, config: &BackendConfig) -> Result<BackendHandle> {
         let backend = Self::new(backend_type, config)?;
         Ok(BackendHandle::new(backend))
     }
 
+    /// Load a model, refusing with [`InfernoError::Resource`] when its file would
+    /// not fit under the memory limit or the host falls short of its requirements
+    ///
+    /// When the backend reports [`InfernoError::GpuInit`] the model is loaded on
+    /// the CPU instead, unless `require_gpu` is set.
     pub async fn load_model(&mut self, model_info: &ModelInfo) -> Result<()> {
-        self.backend_impl.load_model(model_info).await
+        if let Some(preflight) = &self.preflight {
+            preflight.check(model_info)?;
+        }
+        self.memory.check(
+            model_info.size_bytes.max(model_info.size),
+            &format!("load model '{}'", model_info.name),
+        )?;
+        match self.backend_impl.load_model(model_info).await {
+            Ok(()) => {
+                self.device = Some(if self.config.gpu_enabled {
+                    ComputeDevice::Gpu
+                } else {
+                    ComputeDevice::Cpu
+                });
+            }
+            Err(e) => match e.downcast_ref::<InfernoError>() {
+                Some(InfernoError::GpuInit(reason)) if !self.config.require_gpu => {
+                    let reason = reason.clone();
+                    self.load_on_cpu(model_info, reason, e).await?;
+                }
+                _ => return Err(e),
+            },
+        }
+        Ok(())
     }
 
+    /// Load `model_info` on the CPU after the GPU failed to initialize with
+    /// `gpu_error`, which is returned if the backend cannot switch
+    ///
+    /// If the CPU load fails too the backend is switched back to the GPU config,
+    /// so the next load tries the GPU again.
+    async fn load_on_cpu(
+        &mut self,
+        model_info: &ModelInfo,
+        reason: String,
+        gpu_error: anyhow::Error,
+    ) -> Result<()> {
+        warn!(
+            "⚠️  GPU initialization failed: {}. Falling back to the CPU for '{}', which will be slower; \
+             set backend_config.require_gpu to fail instead",
+            reason, model_info.name
+        );
+        let cpu = BackendConfig {
+            gpu_enabled: false,
+            ..self.config.clone()
+        };
+        if self.backend_impl.reconfigure(&cpu).is_err() {
+            return Err(gpu_error);
+        }
+        if let Err(e) = self.backend_impl.load_model(model_info).await {
+            if let Err(restore) = self.backend_impl.reconfigure(&self.config) {
+                warn!("Failed to restore the GPU backend config: {}", restore);
+            }
+            return Err(e.context(format!(
+                "Failed to load '{}' on the CPU after GPU initialization failed: {}",
+                model_info.name, reason
+            )));
+        }
+        if self.preflight.is_some() {
+            self.preflight = Some(Preflight::new(&cpu));
+        }
+        self.config = cpu;
+        self.device = Some(ComputeDevice::Cpu);
+        *GPU_FALLBACK.lock().unwrap_or_else(|e| e.into_inner()) = Some(reason);
+        Ok(())
+    }
+
+    /// Unload the model once the streams running on it have been dropped
     pub async fn unload_model(&mut self) -> Result<()> {
+        self.streams.wait_idle().await;
+        self.unload_now().await
+    }
+
+    async fn unload_now(&mut self) -> Result<()> {
+        self.device = None;
         self.backend_impl.unload_model().await
     }
 
+    /// Where the loaded model runs, or `None` when no model is loaded; the CPU
+    /// when the GPU failed to initialize
+    pub fn device(&self) -> Option<ComputeDevice> {
+        self.device
+    }
+
+    /// Switch to `config`, keeping the loaded model when the backend can apply
+    /// the changed settings to it and loading it again when it cannot
+    pub async fn reload(&mut self, config: &BackendConfig) -> Result<Reload> {
+        let changed = self.config.changed_settings(config);
+        if changed.is_empty() {
+            return Ok(Reload::Unchanged);
+        }
+        let in_place = self.backend_impl.reconfigure(config)?;
+        let previous = self.config.clone();
+        self.apply_config(config);
+
+        if in_place {
+            info!("Applied backend settings in place: {}", changed.join(", "));
+            return Ok(Reload::InPlace { changed });
+        }
+        if let Some(model_info) = self.backend_impl.get_model_info().await {
+            info!(
+                "Reloading model '{}' for backend settings: {}",
+                model_info.name,
+                changed.join(", ")
+            );
+            self.unload_now().await?;
+            if let Err(e) = self.load_model(&model_info).await {
+                warn!(
+                    "Failed to reload '{}', restoring the previous backend settings: {}",
+                    model_info.name, e
+                );
+                self.restore(&previous, &model_info).await;
+                return Err(e);
+            }
+        }
+        Ok(Reload::Full { changed })
+    }
+
+    fn apply_config(&mut self, config: &BackendConfig) {
+        self.config = config.clone();
+        self.context_policy = config.context_policy;
+        if !config.preflight {
+            self.preflight = None;
+        } else if self.preflight.is_some() {
+            self.preflight = Some(Preflight::new(config));
+        }
+    }
+
+    /// Go back to `config` and the model loaded with it after a failed reload
+    async fn restore(&mut self, config: &BackendConfig, model_info: &ModelInfo) {
+        if let Err(e) = self.backend_impl.reconfigure(config) {
+            warn!("Failed to restore the previous backend settings: {}", e);
+        }
+        self.apply_config(config);
+        if let Err(e) = self.load_model(model_info).await {
+            warn!(
+                "Failed to load '{}' again with the previous settings: {}",
+                model_info.name, e
+            );
+        }
+    }
+
+    /// `input` checked against the memory limit and fitted into the model's
+    /// context with room for `max_tokens`, once `params` are validated
+    fn admit<'a>(&self, input: &'a str, params: &InferenceParams) -> Result<Cow<'a, str>> {
+        params.validate_settings()?;
+        self.memory.check_request(input, params.max_tokens)?;
+        let Some(context_length) = self.backend_impl.context_length() else {
+            return Ok(Cow::Borrowed(input));
+        };
+        Ok(context::fit_prompt(
+            input,
+            params.max_tokens,
+            context_length,
+            self.context_policy,
+            self.tokenizer().as_deref(),
+        )?)
+    }
+
     pub async fn is_loaded(&self) -> bool {
         self.backend_impl.is_loaded().await
     }
@@ -264,8 +943,108 @@ impl Backend {
         self.backend_impl.get_model_info().await
     }
 
+    /// Run inference to completion, reporting why it ended and the seed it sampled
+    /// with, which is drawn at random when `params` has none
+    ///
+    /// Requests with a `stop_regex` or `max_generation_ms` are served from the token
+    /// stream, so generation halts at the match or the deadline; the regex is
+    /// compiled before generation starts. Requests for logprobs are generated
+    /// whole, then cut at the match along with their tokens.
+    pub async fn generate(
+        &mut self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<Generation> {
+        let input = &*self.admit(input, params)?;
+        let params = &params.with_effective_seed();
+        let seed = params.seed.unwrap_or_default();
+        let stop = StopRegex::from_params(params)?;
+        let deadline = generation::deadline(params);
+        if params.logprobs.is_some() {
+            // Backends stop at `max_generation_ms` themselves where they can;
+            // cancelling at the deadline bounds those that do not
+            let limit = cancel.child_token();
+            let timer = deadline.map(|deadline| {
+                let limit = limit.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep_until(deadline).await;
+                    limit.cancel();
+                })
+            });
+            let output = self
+                .backend_impl
+                .infer_logprobs(input, params, &limit)
+                .await;
+            if let Some(timer) = timer {
+                timer.abort();
+            }
+            match output {
+                Ok(Some((text, logprobs))) => {
+                    let (text, logprobs, finish_reason) = generation::limit_logprobs(
+                        text,
+                        logprobs,
+                        stop.as_ref(),
+                        deadline,
+                        params.max_tokens,
+                    );
+                    return Ok(Generation {
+                        text,
+                        finish_reason,
+                        seed,
+                        logprobs: Some(logprobs),
+                    });
+                }
+                Ok(None) => warn!(
+                    "The {} backend cannot report logprobs; generating without them",
+                    self.get_backend_type()
+                ),
+                Err(_) if limit.is_cancelled() && !cancel.is_cancelled() => {
+                    return Ok(Generation {
+                        text: String::new(),
+                        finish_reason: FinishReason::TimeLimit,
+                        seed,
+                        logprobs: Some(Vec::new()),
+                    });
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if stop.is_none() && deadline.is_none() {
+            let text = self
+                .backend_impl
+                .infer_cancellable(input, params, cancel)
+                .await?;
+            return Ok(Generation {
+                text,
+                finish_reason: FinishReason::Stop,
+                seed,
+                logprobs: None,
+            });
+        }
+        let stream = self
+            .backend_impl
+            .infer_stream_cancellable(input, params, cancel)
+            .await?;
+        let (text, finish_reason) =
+            generation::collect(stream, stop.as_ref(), deadline, params.max_tokens).await?;
+        Ok(Generation {
+            text,
+            finish_reason,
+            seed,
+            logprobs: None,
+        })
+    }
+
     pub async fn infer(&mut self, input: &str, params: &InferenceParams) -> Result<String> {
-        self.backend_impl.infer(input, params).await
+        if params.stop_regex.is_none() && params.max_generation_ms.is_none() {
+            let input = self.admit(input, params)?;
+            return self.backend_impl.infer(&input, params).await;
+        }
+        let generation = self
+            .generate(input, params, &CancellationToken::new())
+            .await?;
+        Ok(generation.text)
     }
 
     pub async fn infer_stream(
@@ -273,7 +1052,37 @@ impl Backend {
         input: &str,
         params: &InferenceParams,
     ) -> Result<TokenStream> {
-        self.backend_impl.infer_stream(input, params).await
+        let input = self.admit(input, params)?;
+        let stop = StopRegex::from_params(params)?;
+        let deadline = generation::deadline(params);
+        let stream = self.backend_impl.infer_stream(&input, params).await?;
+        Ok(self.streams.lease(limit_stream(stream, stop, deadline)))
+    }
+
+    pub async fn infer_cancellable(
+        &mut self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        let generation = self.generate(input, params, cancel).await?;
+        Ok(generation.text)
+    }
+
+    pub async fn infer_stream_cancellable(
+        &mut self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<TokenStream> {
+        let input = self.admit(input, params)?;
+        let stop = StopRegex::from_params(params)?;
+        let deadline = generation::deadline(params);
+        let stream = self
+            .backend_impl
+            .infer_stream_cancellable(&input, params, cancel)
+            .await?;
+        Ok(self.streams.lease(limit_stream(stream, stop, deadline)))
     }
 
     pub async fn get_embeddings(&mut self, input: &str) -> Result<Vec<f32>> {
@@ -287,6 +1096,15 @@ impl Backend {
     pub fn get_metrics(&self) -> Option<InferenceMetrics> {
         self.backend_impl.get_metrics()
     }
+
+    pub fn tokenizer(&self) -> Option<Arc<dyn Tokenizer>> {
+        self.backend_impl.tokenizer()
+    }
+
+    /// Count tokens with the loaded model's tokenizer, estimating when it has none
+    pub fn count_tokens(&self, text: &str) -> u32 {
+        tokenizer::count_tokens(self.tokenizer().as_deref(), text)
+    }
 }
 
 /// Thread-safe, cloneable handle to a shared Backend instance
@@ -306,6 +1124,12 @@ impl BackendHandle {
         }
     }
 
+    /// Identifies the backend behind this handle; every clone of the handle has
+    /// the same id, and no two live backends do
+    pub fn id(&self) -> usize {
+        Arc::as_ptr(&self.inner) as usize
+    }
+
     /// Create a new shared backend handle
     pub fn new_shared(backend_type: BackendType, config: &BackendConfig) -> Result<Self> {
         let backend = Backend::new(backend_type, config)?;
@@ -318,10 +1142,33 @@ impl BackendHandle {
         backend.load_model(model_info).await
     }
 
-    /// Unload the current model from this backend
+    /// Unload the current model from this backend once requests in flight,
+    /// streams included, finish
     pub async fn unload_model(&self) -> Result<()> {
+        loop {
+            let mut backend = self.inner.lock().await;
+            if backend.streams.is_idle() {
+                return backend.unload_now().await;
+            }
+            // Streams may need the lock to finish, so wait for them without it
+            let streams = backend.streams.clone();
+            drop(backend);
+            streams.wait_idle().await;
+        }
+    }
+
+    /// Switch the backend to `config` once requests in flight finish; see
+    /// [`Backend::reload`]
+    pub async fn reload(&self, config: &BackendConfig) -> Result<Reload> {
         let mut backend = self.inner.lock().await;
-        backend.unload_model().await
+        backend.reload(config).await
+    }
+
+    /// Swap in another backend, returning the previous one. Waits for requests
+    /// currently holding the backend to finish.
+    pub async fn replace(&self, backend: Backend) -> Backend {
+        let mut current = self.inner.lock().await;
+        std::mem::replace(&mut *current, backend)
     }
 
     /// Check if a model is currently loaded
@@ -330,6 +1177,20 @@ impl BackendHandle {
         backend.is_loaded().await
     }
 
+    /// Where the loaded model runs; see [`Backend::device`]
+    pub async fn device(&self) -> Option<ComputeDevice> {
+        self.inner.lock().await.device()
+    }
+
+    /// Like [`is_loaded`](Self::is_loaded) without waiting for requests in flight;
+    /// a backend that is busy serving one is taken to have a model loaded
+    pub async fn is_loaded_or_busy(&self) -> bool {
+        match self.inner.try_lock() {
+            Ok(backend) => backend.is_loaded().await,
+            Err(_) => true,
+        }
+    }
+
     /// Get information about the currently loaded model
     pub async fn get_model_info(&self) -> Option<ModelInfo> {
         let backend = self.inner.lock().await;
@@ -348,6 +1209,43 @@ impl BackendHandle {
         backend.infer_stream(input, params).await
     }
 
+    /// Perform inference, reporting why generation ended; see [`Backend::generate`]
+    pub async fn generate(
+        &self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<Generation> {
+        let mut backend = self.inner.lock().await;
+        backend.generate(input, params, cancel).await
+    }
+
+    /// Perform inference, stopping early with [`InfernoError::Cancelled`] once
+    /// `cancel` fires
+    pub async fn infer_cancellable(
+        &self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        let mut backend = self.inner.lock().await;
+        backend.infer_cancellable(input, params, cancel).await
+    }
+
+    /// Perform streaming inference that ends with [`InfernoError::Cancelled`] once
+    /// `cancel` fires
+    pub async fn infer_stream_cancellable(
+        &self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<TokenStream> {
+        let mut backend = self.inner.lock().await;
+        backend
+            .infer_stream_cancellable(input, params, cancel)
+            .await
+    }
+
     /// Get embeddings from the loaded model
     pub async fn get_embeddings(&self, input: &str) -> Result<Vec<f32>> {
         let mut backend = self.inner.lock().await;
@@ -365,6 +1263,12 @@ impl BackendHandle {
         backend.get_metrics()
     }
 
+    /// Count tokens with the loaded model's tokenizer, estimating when it has none
+    pub async fn count_tokens(&self, text: &str) -> u32 {
+        let tokenizer = self.inner.lock().await.tokenizer();
+        tokenizer::count_tokens_blocking(tokenizer, text).await
+    }
+
     /// Get a reference to the underlying Arc<Mutex<Backend>> for advanced usage
     pub fn inner(&self) -> &Arc<Mutex<Backend>> {
         &self.inner
@@ -378,3 +1282,610 @@ impl std::fmt::Debug for BackendHandle {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::mock::MockBackend;
+    use futures::StreamExt;
+    use std::time::Duration;
+
+    const FULL_OUTPUT_TOKENS: usize = 100;
+
+    /// Generates one "x" every 10ms, up to `FULL_OUTPUT_TOKENS`
+    fn slow_backend() -> BackendHandle {
+        MockBackend::tokens(&["x"; FULL_OUTPUT_TOKENS])
+            .token_delay(|_| Duration::from_millis(10))
+            .handle()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancel_stops_inference_mid_generation() {
+        let backend = slow_backend();
+        let cancel = CancellationToken::new();
+        tokio::spawn({
+            let cancel = cancel.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                cancel.cancel();
+            }
+        });
+
+        let start = tokio::time::Instant::now();
+        let err = backend
+            .infer_cancellable("prompt", &InferenceParams::default(), &cancel)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InfernoError>(),
+            Some(InfernoError::Cancelled(_))
+        ));
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        // The stream ends with the cancellation instead of the remaining tokens
+        let cancel = CancellationToken::new();
+        let mut stream = backend
+            .infer_stream_cancellable("prompt", &InferenceParams::default(), &cancel)
+            .await
+            .unwrap();
+        let mut tokens = 0;
+        while let Some(token) = stream.next().await {
+            match token {
+                Ok(_) => {
+                    tokens += 1;
+                    if tokens == 3 {
+                        cancel.cancel();
+                    }
+                }
+                Err(e) => {
+                    assert!(matches!(e, InfernoError::Cancelled(_)));
+                    break;
+                }
+            }
+        }
+        assert_eq!(tokens, 3);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unload_waits_for_running_streams() {
+        let backend = slow_backend();
+        let mut stream = backend
+            .infer_stream("prompt", &InferenceParams::default())
+            .await
+            .unwrap();
+        stream.next().await.unwrap().unwrap();
+
+        let unload = tokio::spawn({
+            let backend = backend.clone();
+            async move { backend.unload_model().await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!unload.is_finished());
+        // The backend lock stays free for the stream's own calls
+        assert!(backend.is_loaded().await);
+
+        let mut tokens = 1;
+        while let Some(token) = stream.next().await {
+            token.unwrap();
+            tokens += 1;
+        }
+        assert_eq!(tokens, FULL_OUTPUT_TOKENS);
+        drop(stream);
+        unload.await.unwrap().unwrap();
+        assert!(!backend.is_loaded().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_generation_stops_at_time_budget() {
+        let backend = slow_backend();
+        let params = InferenceParams {
+            max_generation_ms: Some(55),
+            ..Default::default()
+        };
+
+        let start = tokio::time::Instant::now();
+        let generation = backend
+            .generate("prompt", &params, &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(generation.finish_reason, FinishReason::TimeLimit);
+        assert_eq!(generation.finish_reason.as_openai_str(), "length");
+        assert_eq!(generation.text, "xxxxx");
+        assert!(start.elapsed() < Duration::from_millis(60));
+
+        // Streams end at the budget too
+        let start = tokio::time::Instant::now();
+        let stream = backend.infer_stream("prompt", &params).await.unwrap();
+        let tokens: Vec<_> = stream.collect().await;
+        assert_eq!(tokens.len(), 5);
+        assert!(start.elapsed() < Duration::from_millis(60));
+
+        // Without a budget the backend runs to max_tokens
+        let params = InferenceParams {
+            max_tokens: FULL_OUTPUT_TOKENS as u32,
+            stop_regex: Some("never".to_string()),
+            ..Default::default()
+        };
+        let generation = backend
+            .generate("prompt", &params, &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(generation.finish_reason, FinishReason::Length);
+        assert_eq!(generation.text.len(), FULL_OUTPUT_TOKENS);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_logprobs_generation_honors_stop_regex_and_time_budget() {
+        let backend = MockBackend::tokens(&["Hello", " there", "!"])
+            .logprobs(&[" Hi", "."])
+            .delay(Duration::from_millis(50))
+            .handle();
+        let params = |stop_regex: &str| InferenceParams {
+            logprobs: Some(1),
+            stop_regex: Some(stop_regex.to_string()),
+            ..Default::default()
+        };
+        let tokens = |generation: &Generation| -> Vec<String> {
+            let logprobs = generation.logprobs.as_ref().unwrap();
+            logprobs.iter().map(|token| token.token.clone()).collect()
+        };
+
+        let generation = backend
+            .generate("prompt", &params(r"\s+there"), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(generation.text, "Hello");
+        assert_eq!(tokens(&generation), ["Hello"]);
+        assert_eq!(generation.finish_reason, FinishReason::Stop);
+
+        // A match inside a token cuts the token too
+        let generation = backend
+            .generate("prompt", &params("ere"), &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(generation.text, "Hello th");
+        assert_eq!(tokens(&generation), ["Hello", " th"]);
+
+        let params = InferenceParams {
+            max_generation_ms: Some(10),
+            ..params("never")
+        };
+        let generation = backend
+            .generate("prompt", &params, &CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(generation.finish_reason, FinishReason::TimeLimit);
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_threads_in_place_and_reloads_for_context_size() {
+        let mock = MockBackend::config_reply(|config| format!("{:?} threads", config.cpu_threads))
+            .unloaded();
+        let state = mock.state();
+        let backend = mock.handle();
+        let model_info = ModelInfo {
+            name: "model.gguf".to_string(),
+            path: "model.gguf".into(),
+            file_path: "model.gguf".into(),
+            size: 0,
+            size_bytes: 0,
+            modified: chrono::Utc::now(),
+            backend_type: "gguf".to_string(),
+            format: "gguf".to_string(),
+            checksum: None,
+            metadata: std::collections::HashMap::new(),
+        };
+        backend.load_model(&model_info).await.unwrap();
+        let loads = || state.loads();
+        assert_eq!(loads(), 1);
+
+        let mut config = BackendConfig::default();
+        assert_eq!(backend.reload(&config).await.unwrap(), Reload::Unchanged);
+
+        config.cpu_threads = Some(4);
+        assert_eq!(
+            backend.reload(&config).await.unwrap(),
+            Reload::InPlace {
+                changed: vec!["cpu_threads"]
+            }
+        );
+        assert_eq!(loads(), 1);
+        let output = backend
+            .infer("prompt", &InferenceParams::default())
+            .await
+            .unwrap();
+        assert_eq!(output, "Some(4) threads");
+
+        config.context_size *= 2;
+        config.batch_size *= 2;
+        assert_eq!(
+            backend.reload(&config).await.unwrap(),
+            Reload::Full {
+                changed: vec!["context_size", "batch_size"]
+            }
+        );
+        assert_eq!(loads(), 2);
+        assert!(backend.is_loaded().await);
+    }
+
+    #[tokio::test]
+    async fn test_failed_reload_restores_previous_config_and_model() {
+        let cpu = BackendConfig::cpu_only();
+        let mock = MockBackend::tokens(&["ok"])
+            .unloaded()
+            .config(&cpu)
+            .fail_gpu_init();
+        let mut backend = mock.backend();
+        backend.config = cpu.clone();
+        let model_info = ModelInfo {
+            name: "model.gguf".to_string(),
+            path: "model.gguf".into(),
+            file_path: "model.gguf".into(),
+            size: 0,
+            size_bytes: 0,
+            modified: chrono::Utc::now(),
+            backend_type: "gguf".to_string(),
+            format: "gguf".to_string(),
+            checksum: None,
+            metadata: std::collections::HashMap::new(),
+        };
+        backend.load_model(&model_info).await.unwrap();
+
+        // The GPU cannot be initialized and is required, so the reload fails
+        let gpu = BackendConfig {
+            gpu_enabled: true,
+            require_gpu: true,
+            ..cpu.clone()
+        };
+        let err = backend.reload(&gpu).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InfernoError>(),
+            Some(InfernoError::GpuInit(_))
+        ));
+        assert_eq!(backend.config, cpu);
+        assert!(backend.is_loaded().await);
+        assert_eq!(
+            backend
+                .infer("prompt", &InferenceParams::default())
+                .await
+                .unwrap(),
+            "ok"
+        );
+    }
+
+    #[test]
+    fn test_changed_settings_names_each_setting() {
+        let base = BackendConfig::default();
+        let changes = [
+            (
+                BackendConfig {
+                    gpu_enabled: !base.gpu_enabled,
+                    ..base.clone()
+                },
+                "gpu_enabled",
+            ),
+            (
+                BackendConfig {
+                    require_gpu: true,
+                    ..base.clone()
+                },
+                "require_gpu",
+            ),
+            (
+                BackendConfig {
+                    gpu_device: Some("1".to_string()),
+                    ..base.clone()
+                },
+                "gpu_device",
+            ),
+            (
+                BackendConfig {
+                    cpu_threads: Some(4),
+                    ..base.clone()
+                },
+                "cpu_threads",
+            ),
+            (
+                BackendConfig {
+                    context_size: 8192,
+                    ..base.clone()
+                },
+                "context_size",
+            ),
+            (
+                BackendConfig {
+                    batch_size: 64,
+                    ..base.clone()
+                },
+                "batch_size",
+            ),
+            (
+                BackendConfig {
+                    memory_map: false,
+                    ..base.clone()
+                },
+                "memory_map",
+            ),
+            (
+                BackendConfig {
+                    prefetch_model: true,
+                    ..base.clone()
+                },
+                "prefetch_model",
+            ),
+            (
+                BackendConfig {
+                    lock_memory: true,
+                    ..base.clone()
+                },
+                "lock_memory",
+            ),
+            (
+                BackendConfig {
+                    blocking_threads: Some(2),
+                    ..base.clone()
+                },
+                "blocking_threads",
+            ),
+            (
+                BackendConfig {
+                    memory_limit_mb: Some(1024),
+                    ..base.clone()
+                },
+                "memory_limit_mb",
+            ),
+            (
+                BackendConfig {
+                    rope: RopeConfig {
+                        freq_scale: Some(0.5),
+                        ..Default::default()
+                    },
+                    ..base.clone()
+                },
+                "rope",
+            ),
+            (
+                BackendConfig {
+                    context_policy: ContextPolicy::TruncateHead,
+                    ..base.clone()
+                },
+                "context_policy",
+            ),
+            (
+                BackendConfig {
+                    preflight: false,
+                    ..base.clone()
+                },
+                "preflight",
+            ),
+        ];
+        assert!(base.changed_settings(&base).is_empty());
+        for (changed, name) in &changes {
+            assert_eq!(base.changed_settings(changed), [*name]);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_invalid_params_are_refused_before_dispatch() {
+        assert!(InferenceParams::default().validate().is_ok());
+        let valid = InferenceParams {
+            max_tokens: MAX_TOKENS_LIMIT,
+            temperature: 0.0,
+            top_p: 1.0,
+            min_p: Some(0.05),
+            repeat_penalty: Some(1.1),
+            stop_sequences: vec!["\n".to_string(); MAX_STOP_SEQUENCES],
+            stop_regex: Some("^END$".to_string()),
+            response_format: Some(ResponseFormat::Grammar(
+                "root ::= \"yes\" | \"no\"".to_string(),
+            )),
+            ..Default::default()
+        };
+        assert!(valid.validate().is_ok());
+
+        let cases = [
+            (
+                InferenceParams {
+                    max_tokens: 0,
+                    ..Default::default()
+                },
+                "max_tokens",
+            ),
+            (
+                InferenceParams {
+                    max_tokens: MAX_TOKENS_LIMIT + 1,
+                    ..Default::default()
+                },
+                "max_tokens",
+            ),
+            (
+                InferenceParams {
+                    temperature: -0.1,
+                    ..Default::default()
+                },
+                "temperature",
+            ),
+            (
+                InferenceParams {
+                    temperature: f32::NAN,
+                    ..Default::default()
+                },
+                "temperature",
+            ),
+            (
+                InferenceParams {
+                    top_p: 1.5,
+                    ..Default::default()
+                },
+                "top_p",
+            ),
+            (
+                InferenceParams {
+                    min_p: Some(2.0),
+                    ..Default::default()
+                },
+                "min_p",
+            ),
+            (
+                InferenceParams {
+                    repeat_penalty: Some(0.0),
+                    ..Default::default()
+                },
+                "repeat_penalty",
+            ),
+            (
+                InferenceParams {
+                    stop_sequences: vec!["\n".to_string(); MAX_STOP_SEQUENCES + 1],
+                    ..Default::default()
+                },
+                "stop sequences",
+            ),
+            (
+                InferenceParams {
+                    stop_sequences: vec![String::new()],
+                    ..Default::default()
+                },
+                "Stop sequences",
+            ),
+            (
+                InferenceParams {
+                    stop_regex: Some("(unclosed".to_string()),
+                    ..Default::default()
+                },
+                "stop_regex",
+            ),
+            (
+                InferenceParams {
+                    response_format: Some(ResponseFormat::Grammar("root ::= answer".to_string())),
+                    ..Default::default()
+                },
+                "grammar",
+            ),
+        ];
+
+        let backend = slow_backend();
+        for (params, field) in cases {
+            let err = params.validate().unwrap_err();
+            assert!(
+                matches!(&err, InfernoError::Validation(message) if message.contains(field)),
+                "{}: {}",
+                field,
+                err
+            );
+
+            // The backend refuses them too, without starting generation, except
+            // for extra stop sequences: templates add theirs to the caller's
+            let served = backend.infer("prompt", &params).await;
+            if field == "stop sequences" {
+                assert!(served.is_ok());
+                continue;
+            }
+            assert!(matches!(
+                served.unwrap_err().downcast_ref::<InfernoError>(),
+                Some(InfernoError::Validation(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gpu_init_failure_falls_back_to_cpu() {
+        let gpu = BackendConfig {
+            gpu_enabled: true,
+            ..BackendConfig::default()
+        };
+        let no_gpu_backend = |config: &BackendConfig| {
+            let mut backend = MockBackend::tokens(&["ok"])
+                .unloaded()
+                .config(config)
+                .fail_gpu_init()
+                .backend();
+            backend.config = config.clone();
+            backend
+        };
+        let model_info = ModelInfo {
+            name: "model.gguf".to_string(),
+            path: "model.gguf".into(),
+            file_path: "model.gguf".into(),
+            size: 0,
+            size_bytes: 0,
+            modified: chrono::Utc::now(),
+            backend_type: "gguf".to_string(),
+            format: "gguf".to_string(),
+            checksum: None,
+            metadata: std::collections::HashMap::new(),
+        };
+
+        let mut backend = no_gpu_backend(&gpu);
+        backend.load_model(&model_info).await.unwrap();
+        assert!(backend.is_loaded().await);
+        assert_eq!(backend.device(), Some(ComputeDevice::Cpu));
+        assert!(!backend.config.gpu_enabled);
+        assert_eq!(
+            backend
+                .infer("prompt", &InferenceParams::default())
+                .await
+                .unwrap(),
+            "ok"
+        );
+        assert_eq!(gpu_fallback().as_deref(), Some("no Vulkan device found"));
+        let platform = crate::PlatformInfo::new();
+        assert!(
+            platform
+                .to_string()
+                .contains("GPU unavailable: no Vulkan device found")
+        );
+
+        // Unless the GPU is required
+        let mut backend = no_gpu_backend(&BackendConfig {
+            require_gpu: true,
+            ..gpu
+        });
+        let err = backend.load_model(&model_info).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InfernoError>(),
+            Some(InfernoError::GpuInit(_))
+        ));
+        assert!(!backend.is_loaded().await);
+        assert_eq!(backend.device(), None);
+    }
+
+    #[tokio::test]
+    async fn test_failed_cpu_fallback_keeps_the_gpu_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.gguf");
+        let gpu = BackendConfig {
+            gpu_enabled: true,
+            ..BackendConfig::default()
+        };
+        let mock = MockBackend::model_file().config(&gpu).fail_gpu_init();
+        let state = mock.state();
+        let mut backend = mock.backend();
+        backend.config = gpu.clone();
+        let model_info = ModelInfo {
+            name: "model.gguf".to_string(),
+            path: path.clone(),
+            file_path: path.clone(),
+            size: 0,
+            size_bytes: 0,
+            modified: chrono::Utc::now(),
+            backend_type: "gguf".to_string(),
+            format: "gguf".to_string(),
+            checksum: None,
+            metadata: std::collections::HashMap::new(),
+        };
+
+        // The file is missing, so the CPU retry fails as well
+        backend.load_model(&model_info).await.unwrap_err();
+        assert!(backend.config.gpu_enabled);
+        assert_eq!(backend.device(), None);
+
+        // The next load tries the GPU again before falling back
+        std::fs::write(&path, "weights").unwrap();
+        let loads = state.loads();
+        backend.load_model(&model_info).await.unwrap();
+        assert_eq!(state.loads(), loads + 2);
+        assert_eq!(backend.device(), Some(ComputeDevice::Cpu));
+        assert!(!backend.config.gpu_enabled);
+    }
+}
@@ -14,7 +14,7 @@ use crate::{
     },
     backends::{
         BackendConfig, BackendType, InferenceBackend, InferenceMetrics, InferenceParams,
-        TokenStream,
+        TokenStream, blocking,
     },
     models::ModelInfo,
 };
@@ -26,7 +26,7 @@ use ort::{
     value::Tensor,
 };
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokenizers::Tokenizer;
 use tracing::{debug, info, warn};
 
@@ -325,7 +325,12 @@ impl OnnxBackend {
             temperature: params.temperature.max(0.1).min(2.0),
             top_k: params.top_k.max(1),
             top_p: params.top_p.max(0.0).min(1.0),
-            repeat_penalty: 1.1,
+            repeat_penalty: params.repeat_penalty.unwrap_or(1.0),
+            repeat_last_n: params
+                .repeat_last_n
+                .map(|n| n as usize)
+                .unwrap_or(SamplingConfig::default().repeat_last_n),
+            min_p: params.min_p.unwrap_or(0.0).clamp(0.0, 1.0),
             seed: params.seed,
         }
     }
@@ -420,7 +425,7 @@ impl OnnxBackend {
         Ok(last_logits)
     }
 
-    /// Autoregressive text generation (blocking, meant for spawn_blocking)
+    /// Autoregressive text generation (blocking, meant for the blocking pool)
     fn generate_text_blocking(
         session: &mut Session,
         initial_tokens: Vec<i64>,
@@ -431,8 +436,15 @@ impl OnnxBackend {
     ) -> Result<Vec<u32>> {
         let mut all_tokens = initial_tokens.clone();
         let mut sampler = Sampler::new(Self::build_sampling_config(params));
+        let deadline = params
+            .max_generation_ms
+            .map(|ms| Instant::now() + Duration::from_millis(ms));
 
         for _ in 0..params.max_tokens {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                debug!("Generation time budget spent, stopping generation");
+                break;
+            }
             let logits = Self::forward_pass(session, &all_tokens, input_names)?;
 
             // Compute softmax probabilities so sampling strategies (greedy, top-k, top-p) work correctly
@@ -517,7 +529,7 @@ impl InferenceBackend for OnnxBackend {
         let cpu_threads = self.config.cpu_threads;
         let model_path = model_info.path.clone();
 
-        let session = tokio::task::spawn_blocking(move || -> Result<Session> {
+        let session = blocking::run(move || -> Result<Session> {
             let mut builder = Session::builder()
                 .map_err(|e| {
                     InfernoError::Backend(format!("Failed to create session builder: {}", e))
@@ -534,8 +546,9 @@ impl InferenceBackend for OnnxBackend {
             }
 
             if !providers.is_empty() {
+                // Only GPU providers are ever added
                 builder = builder.with_execution_providers(providers).map_err(|e| {
-                    InfernoError::Backend(format!("Failed to set execution providers: {}", e))
+                    InfernoError::GpuInit(format!("Failed to set execution providers: {}", e))
                 })?;
             }
 
@@ -590,6 +603,12 @@ impl InferenceBackend for OnnxBackend {
         self.model_info.as_ref().cloned()
     }
 
+    fn reconfigure(&mut self, config: &BackendConfig) -> Result<bool> {
+        let in_place = Self::applies_in_place(&self.config, config);
+        self.config = config.clone();
+        Ok(in_place || self.session.is_none())
+    }
+
     async fn infer(&mut self, input: &str, params: &InferenceParams) -> Result<String> {
         if !self.is_loaded().await {
             return Err(InfernoError::Backend("Model not loaded".to_string()).into());
@@ -619,7 +638,7 @@ impl InferenceBackend for OnnxBackend {
                 let eos_token_id = self.eos_token_id;
                 let tokenizer = self.tokenizer.clone();
 
-                let generated_tokens = tokio::task::spawn_blocking(move || {
+                let generated_tokens = blocking::run(move || {
                     let mut session = session
                         .lock()
                         .map_err(|e| anyhow!("Session lock poisoned: {}", e))?;
@@ -669,7 +688,7 @@ impl InferenceBackend for OnnxBackend {
                 let prompt_tokens = token_ids.len() as u32;
                 let input_i64: Vec<i64> = token_ids.iter().map(|&t| t as i64).collect();
 
-                let scores = tokio::task::spawn_blocking(move || {
+                let scores = blocking::run(move || {
                     let mut session = session
                         .lock()
                         .map_err(|e| anyhow!("Session lock poisoned: {}", e))?;
@@ -734,6 +753,9 @@ impl InferenceBackend for OnnxBackend {
         let max_tokens = params.max_tokens;
         let sampling_config = Self::build_sampling_config(params);
         let stop_sequences = params.stop_sequences.clone();
+        let deadline = params
+            .max_generation_ms
+            .map(|ms| Instant::now() + Duration::from_millis(ms));
         let eos_token_id = self.eos_token_id;
 
         let tokenizer = self
@@ -750,7 +772,7 @@ impl InferenceBackend for OnnxBackend {
         let (tx, rx) = create_stream_channel(stream_config);
         let metrics = self.metrics.clone();
 
-        tokio::task::spawn_blocking(move || {
+        blocking::spawn(move || {
             let start_time = Instant::now();
             let prompt_time = start_time.elapsed();
             let mut all_tokens = initial_tokens.clone();
@@ -773,6 +795,10 @@ impl InferenceBackend for OnnxBackend {
             let mut completion_tokens = 0u32;
 
             for seq in 0..max_tokens {
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    debug!("Generation time budget spent, stopping streaming generation");
+                    break;
+                }
                 let logits = match Self::forward_pass(&mut session_guard, &all_tokens, &input_names)
                 {
                     Ok(l) => l,
@@ -904,7 +930,7 @@ impl InferenceBackend for OnnxBackend {
         let seq_len = token_ids.len();
         let input_i64: Vec<i64> = token_ids.iter().map(|&t| t as i64).collect();
 
-        let embeddings = tokio::task::spawn_blocking(move || -> Result<Vec<f32>> {
+        let embeddings = blocking::run(move || -> Result<Vec<f32>> {
             let mut session_guard = session
                 .lock()
                 .map_err(|e| anyhow!("Session lock poisoned: {}", e))?;
@@ -981,6 +1007,17 @@ impl InferenceBackend for OnnxBackend {
     }
 }
 
+impl OnnxBackend {
+    /// Whether switching from `current` to `config` keeps the loaded session.
+    /// The thread count is fixed when the session is built at load time
+    fn applies_in_place(current: &BackendConfig, config: &BackendConfig) -> bool {
+        current
+            .changed_settings(config)
+            .iter()
+            .all(|setting| matches!(*setting, "batch_size" | "context_policy" | "preflight"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1107,6 +1144,9 @@ mod tests {
         let params = InferenceParams {
             temperature: 0.8,
             top_k: 50,
+            repeat_penalty: None,
+            repeat_last_n: None,
+            min_p: None,
             top_p: 0.95,
             ..InferenceParams::default()
         };
@@ -1122,4 +1162,23 @@ mod tests {
         assert_eq!(names.input_ids, "input_ids");
         assert_eq!(names.attention_mask, Some("attention_mask".to_string()));
     }
+
+    #[test]
+    fn test_reconfigure_rebuilds_the_session_for_threads() {
+        let current = BackendConfig::default();
+        let batch = BackendConfig {
+            batch_size: 64,
+            ..current.clone()
+        };
+        assert!(OnnxBackend::applies_in_place(&current, &batch));
+        let threads = BackendConfig {
+            cpu_threads: Some(4),
+            ..batch.clone()
+        };
+        assert!(!OnnxBackend::applies_in_place(&current, &threads));
+
+        // Without a session there is nothing to rebuild
+        let mut backend = OnnxBackend::new(current).unwrap();
+        assert!(backend.reconfigure(&threads).unwrap());
+    }
 }
@@ -0,0 +1,117 @@
+//! ONNX speech recognition models
+//!
+//! Runs CTC acoustic models such as wav2vec2 exported to ONNX. The model takes
+//! a `[1, samples]` float tensor and returns `[1, frames, vocabulary]` logits,
+//! decoded with the `vocab.json` next to the model file. The expected sample
+//! rate is read from a `preprocessor_config.json` beside it, 16 kHz otherwise.
+
+use crate::{
+    InfernoError,
+    ai_features::transcription::{CtcVocabulary, SpeechModel},
+    backends::BackendConfig,
+};
+use anyhow::{Result, anyhow};
+use ort::{inputs, session::Session, value::Tensor};
+use std::path::Path;
+use tracing::info;
+
+const DEFAULT_SAMPLE_RATE: u32 = 16_000;
+
+pub struct OnnxSpeechModel {
+    session: Session,
+    input_name: String,
+    vocabulary: CtcVocabulary,
+    sample_rate: u32,
+    normalize: bool,
+}
+
+impl OnnxSpeechModel {
+    pub fn load(path: &Path, config: &BackendConfig) -> Result<Self> {
+        let model_dir = path.parent().unwrap_or(path);
+        let vocabulary = CtcVocabulary::load(&model_dir.join("vocab.json"))?;
+        let preprocessor = std::fs::read_to_string(model_dir.join("preprocessor_config.json"))
+            .ok()
+            .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok());
+        let sample_rate = preprocessor
+            .as_ref()
+            .and_then(|config| config.get("sampling_rate")?.as_u64())
+            .and_then(|rate| u32::try_from(rate).ok())
+            .unwrap_or(DEFAULT_SAMPLE_RATE);
+        let normalize = preprocessor
+            .as_ref()
+            .and_then(|config| config.get("do_normalize")?.as_bool())
+            .unwrap_or(true);
+
+        let mut builder = Session::builder().map_err(|e| {
+            InfernoError::Backend(format!("Failed to create session builder: {}", e))
+        })?;
+        if let Some(threads) = config.cpu_threads {
+            builder = builder
+                .with_intra_threads(threads as usize)
+                .map_err(|e| InfernoError::Backend(format!("Failed to set thread count: {}", e)))?;
+        }
+        let session = builder.commit_from_file(path).map_err(|e| {
+            InfernoError::Backend(format!("Failed to load ONNX speech model: {}", e))
+        })?;
+        let input_name = session
+            .inputs()
+            .first()
+            .map(|input| input.name().to_string())
+            .ok_or_else(|| anyhow!("Speech model {} has no inputs", path.display()))?;
+
+        info!(
+            "Loaded ONNX speech model {} ({} Hz, {} tokens)",
+            path.display(),
+            sample_rate,
+            vocabulary.len()
+        );
+        Ok(Self {
+            session,
+            input_name,
+            vocabulary,
+            sample_rate,
+            normalize,
+        })
+    }
+}
+
+impl SpeechModel for OnnxSpeechModel {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn transcribe(&mut self, samples: &[f32]) -> Result<String> {
+        let mut input = samples.to_vec();
+        if self.normalize {
+            // Zero mean and unit variance, as the feature extractor does in training
+            let mean = input.iter().sum::<f32>() / input.len().max(1) as f32;
+            let variance =
+                input.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / input.len().max(1) as f32;
+            let scale = (variance + 1e-7).sqrt();
+            for sample in &mut input {
+                *sample = (*sample - mean) / scale;
+            }
+        }
+
+        let tensor = Tensor::from_array(([1usize, input.len()], input))
+            .map_err(|e| anyhow!("Failed to create audio tensor: {}", e))?;
+        let outputs = self
+            .session
+            .run(inputs![self.input_name.as_str() => tensor])
+            .map_err(|e| InfernoError::Backend(format!("ONNX speech inference failed: {}", e)))?;
+        let (shape, logits) = outputs[0usize]
+            .try_extract_tensor::<f32>()
+            .map_err(|e| anyhow!("Failed to extract logits tensor: {}", e))?;
+
+        let vocabulary_size = shape.last().copied().unwrap_or(0) as usize;
+        if vocabulary_size != self.vocabulary.len() {
+            return Err(InfernoError::Backend(format!(
+                "Speech model outputs {} tokens per frame but vocab.json has {}",
+                vocabulary_size,
+                self.vocabulary.len()
+            ))
+            .into());
+        }
+        Ok(self.vocabulary.decode(logits))
+    }
+}
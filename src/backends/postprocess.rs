@@ -0,0 +1,226 @@
+//! Post-processing of finished completions
+//!
+//! A [`PostProcessor`] rewrites generated text before it is returned or saved by
+//! `inferno run`, batch processing and the non-streaming API endpoints. Processors
+//! are chosen by name:
+//!
+//! - `none`: leave the text as generated (the default)
+//! - `trim`: strip leading and trailing whitespace
+//! - `json`: keep only the first JSON object or array, preferring a fenced
+//!   ```` ```json ```` block
+//! - `regex:<pattern>`: keep the first match, or its first capture group if the
+//!   pattern has one
+//!
+//! Applications embedding inferno can add their own with [`register`]. Streamed
+//! output is sent as it is generated and is never post-processed.
+
+use crate::InfernoError;
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+type ProcessFn = dyn Fn(&str) -> String + Send + Sync;
+
+static REGISTRY: OnceLock<RwLock<HashMap<String, Arc<ProcessFn>>>> = OnceLock::new();
+
+fn registry() -> &'static RwLock<HashMap<String, Arc<ProcessFn>>> {
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Make `f` selectable as a post-processor called `name`
+///
+/// Built-in names take precedence; registering a name again replaces the earlier
+/// function.
+pub fn register<F>(name: impl Into<String>, f: F)
+where
+    F: Fn(&str) -> String + Send + Sync + 'static,
+{
+    registry()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(name.into(), Arc::new(f));
+}
+
+/// A named transformation applied to each completion
+#[derive(Clone)]
+pub struct PostProcessor {
+    name: String,
+    process: Option<Arc<ProcessFn>>,
+}
+
+impl PostProcessor {
+    pub fn new<F>(name: impl Into<String>, f: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            process: Some(Arc::new(f)),
+        }
+    }
+
+    /// Leaves completions untouched
+    pub fn none() -> Self {
+        Self {
+            name: "none".to_string(),
+            process: None,
+        }
+    }
+
+    /// Look up a built-in or registered post-processor
+    pub fn from_name(name: &str) -> Result<Self, InfernoError> {
+        match name {
+            "none" => Ok(Self::none()),
+            "trim" => Ok(Self::new(name, |text| text.trim().to_string())),
+            "json" => Ok(Self::new(name, |text| {
+                extract_json(text).unwrap_or(text).to_string()
+            })),
+            _ => {
+                if let Some(pattern) = name.strip_prefix("regex:") {
+                    let regex = Regex::new(pattern).map_err(|e| {
+                        InfernoError::InvalidArgument(format!(
+                            "Invalid post-processor pattern '{}': {}",
+                            pattern, e
+                        ))
+                    })?;
+                    return Ok(Self::new(name, move |text| extract_match(&regex, text)));
+                }
+                let registered = registry()
+                    .read()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .get(name)
+                    .cloned();
+                registered
+                    .map(|process| Self {
+                        name: name.to_string(),
+                        process: Some(process),
+                    })
+                    .ok_or_else(|| {
+                        InfernoError::InvalidArgument(format!(
+                            "Unknown post-processor '{}'; expected none, trim, json, regex:<pattern> or a registered name",
+                            name
+                        ))
+                    })
+            }
+        }
+    }
+
+    /// Resolve an optional name, falling back to [`PostProcessor::none`]
+    pub fn from_option(name: Option<&str>) -> Result<Self, InfernoError> {
+        name.map_or_else(|| Ok(Self::none()), Self::from_name)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        match &self.process {
+            Some(process) => process(text),
+            None => text.to_string(),
+        }
+    }
+}
+
+impl Default for PostProcessor {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl fmt::Debug for PostProcessor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PostProcessor").field(&self.name).finish()
+    }
+}
+
+/// First JSON object or array in `text`, or `None` if it holds no valid JSON
+fn extract_json(text: &str) -> Option<&str> {
+    if let Some(start) = text.find("```json") {
+        let body = &text[start + "```json".len()..];
+        if let Some(end) = body.find("```")
+            && let Some(json) = extract_json_value(&body[..end])
+        {
+            return Some(json);
+        }
+    }
+    extract_json_value(text)
+}
+
+fn extract_json_value(text: &str) -> Option<&str> {
+    text.char_indices()
+        .filter(|(_, c)| matches!(c, '{' | '['))
+        .find_map(|(start, _)| {
+            let mut values = serde_json::Deserializer::from_str(&text[start..])
+                .into_iter::<serde::de::IgnoredAny>();
+            match values.next() {
+                Some(Ok(_)) => Some(&text[start..start + values.byte_offset()]),
+                _ => None,
+            }
+        })
+}
+
+fn extract_match(regex: &Regex, text: &str) -> String {
+    match regex.captures(text) {
+        Some(captures) => captures
+            .get(1)
+            .or_else(|| captures.get(0))
+            .map_or_else(String::new, |m| m.as_str().to_string()),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_extracts_first_value() {
+        let json = PostProcessor::from_name("json").unwrap();
+
+        let fenced =
+            "Here you go:\n```json\n{\"name\": \"inferno\", \"tags\": [\"a\", \"}\"]}\n```\nDone.";
+        assert_eq!(
+            json.apply(fenced),
+            r#"{"name": "inferno", "tags": ["a", "}"]}"#
+        );
+
+        let bare = "The answer is [1, 2, {\"x\": 3}] as requested {not json";
+        assert_eq!(json.apply(bare), r#"[1, 2, {"x": 3}]"#);
+
+        // Braces that never form valid JSON leave the text as generated
+        let prose = "Use {braces} carefully";
+        assert_eq!(json.apply(prose), prose);
+    }
+
+    #[test]
+    fn test_none_is_default_and_leaves_text_unchanged() {
+        let text = "  untouched output \n";
+        assert_eq!(PostProcessor::default().apply(text), text);
+        assert_eq!(PostProcessor::from_option(None).unwrap().name(), "none");
+        assert_eq!(
+            PostProcessor::from_option(Some("none"))
+                .unwrap()
+                .apply(text),
+            text
+        );
+    }
+
+    #[test]
+    fn test_regex_registered_and_unknown_names() {
+        let regex = PostProcessor::from_name(r"regex:Answer: (\d+)").unwrap();
+        assert_eq!(regex.apply("Thinking... Answer: 42."), "42");
+
+        register("shout", |text| text.to_uppercase());
+        assert_eq!(PostProcessor::from_name("shout").unwrap().apply("hi"), "HI");
+
+        assert!(matches!(
+            PostProcessor::from_name("does-not-exist"),
+            Err(InfernoError::InvalidArgument(_))
+        ));
+        assert!(PostProcessor::from_name("regex:(").is_err());
+    }
+}
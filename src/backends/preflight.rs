@@ -0,0 +1,282 @@
+//! Checking a model against the host before loading it
+//!
+//! Loading a model the machine cannot hold fails late, often as an OOM kill part
+//! way through reading the weights. Backends created from a [`BackendConfig`] run
+//! a [`Preflight`] first: the model's estimated [`ModelRequirements`] are compared
+//! with the [`HostCapabilities`] and the load is refused with an
+//! [`InfernoError::Resource`] listing every shortfall, such as
+//! `needs 16.0 GB of RAM, 8.0 GB available`.
+//!
+//! Weights and the KV cache for `context_size` tokens, at
+//! [`REQUEST_BYTES_PER_TOKEN`], are counted against RAM, or against VRAM when GPU
+//! offload is enabled. Free VRAM is read from `nvidia-smi`, and on macOS the GPU
+//! shares RAM; on other hosts the VRAM check is skipped. Set
+//! `backend_config.preflight = false` to skip the checks altogether.
+
+use crate::{
+    InfernoError,
+    backends::{BackendConfig, memory::REQUEST_BYTES_PER_TOKEN},
+    models::ModelInfo,
+};
+use std::sync::Arc;
+
+const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+/// What a model needs from the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelRequirements {
+    pub memory_bytes: u64,
+    pub vram_bytes: u64,
+    /// Build features that must be compiled in, such as `gguf` or `onnx`
+    pub features: Vec<String>,
+}
+
+/// What the host has to offer
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostCapabilities {
+    pub available_memory_bytes: u64,
+    /// Free VRAM, when the host can tell
+    pub available_vram_bytes: Option<u64>,
+    /// Build features this binary was compiled with
+    pub features: Vec<String>,
+}
+
+/// Reports the host's current capabilities
+pub trait HostProbe: Send + Sync {
+    fn capabilities(&self) -> HostCapabilities;
+}
+
+/// The machine this process runs on
+struct SystemHost;
+
+impl HostProbe for SystemHost {
+    fn capabilities(&self) -> HostCapabilities {
+        use sysinfo::{System, SystemExt};
+        let mut system = System::new();
+        system.refresh_memory();
+        // Apple GPUs share the system's unified memory
+        let available_vram_bytes = if cfg!(target_os = "macos") {
+            Some(system.available_memory())
+        } else {
+            nvidia_free_vram()
+        };
+        let features = [
+            ("gguf", cfg!(feature = "gguf")),
+            ("onnx", cfg!(feature = "onnx")),
+            ("gpu-metal", cfg!(feature = "gpu-metal")),
+            ("cuda", cfg!(feature = "cuda")),
+            ("rocm", cfg!(feature = "rocm")),
+        ];
+        HostCapabilities {
+            // sysinfo 0.29+ returns memory in bytes
+            available_memory_bytes: system.available_memory(),
+            available_vram_bytes,
+            features: features
+                .into_iter()
+                .filter(|&(_, enabled)| enabled)
+                .map(|(feature, _)| feature.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Free memory summed over the NVIDIA GPUs `nvidia-smi` lists, as llama.cpp
+/// splits offloaded layers across all of them
+fn nvidia_free_vram() -> Option<u64> {
+    let output = std::process::Command::new("nvidia-smi")
+        .args(["--query-gpu=memory.free", "--format=csv,noheader,nounits"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let free_mb = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().parse::<u64>().ok())
+        .sum::<Option<u64>>()?;
+    (free_mb > 0).then_some(free_mb * 1024 * 1024)
+}
+
+/// Compares models with the host before they are loaded
+#[derive(Clone)]
+pub struct Preflight {
+    gpu_offload: bool,
+    context_size: u32,
+    host: Arc<dyn HostProbe>,
+}
+
+impl std::fmt::Debug for Preflight {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Preflight")
+            .field("gpu_offload", &self.gpu_offload)
+            .field("context_size", &self.context_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Preflight {
+    /// Check models loaded with `config` against this machine
+    pub fn new(config: &BackendConfig) -> Self {
+        Self {
+            gpu_offload: config.gpu_enabled,
+            context_size: config.context_size,
+            host: Arc::new(SystemHost),
+        }
+    }
+
+    /// Check against what `host` reports instead of this machine
+    pub fn with_host(mut self, host: Arc<dyn HostProbe>) -> Self {
+        self.host = host;
+        self
+    }
+
+    /// Estimated needs of `model` with this configuration
+    pub fn requirements(&self, model: &ModelInfo) -> ModelRequirements {
+        let weights = model.size_bytes.max(model.size);
+        let kv_cache = self.context_size as u64 * REQUEST_BYTES_PER_TOKEN;
+        let on_gpu = self.gpu_offload;
+        let features = match model.format.to_lowercase().as_str() {
+            format @ ("gguf" | "onnx") => vec![format.to_string()],
+            _ => Vec::new(),
+        };
+        ModelRequirements {
+            memory_bytes: if on_gpu { 0 } else { weights + kv_cache },
+            vram_bytes: if on_gpu { weights + kv_cache } else { 0 },
+            features,
+        }
+    }
+
+    /// Fail with every way `model` falls short of the host
+    pub fn check(&self, model: &ModelInfo) -> Result<(), InfernoError> {
+        let host = self.host.capabilities();
+        let needs = self.requirements(model);
+
+        let mut shortfalls = Vec::new();
+        if needs.memory_bytes > host.available_memory_bytes {
+            shortfalls.push(format!(
+                "needs {} of RAM, {} available",
+                gigabytes(needs.memory_bytes),
+                gigabytes(host.available_memory_bytes)
+            ));
+        }
+        if let Some(vram) = host.available_vram_bytes
+            && needs.vram_bytes > vram
+        {
+            shortfalls.push(format!(
+                "needs {} of VRAM, {} available; lower context_size or disable gpu_enabled",
+                gigabytes(needs.vram_bytes),
+                gigabytes(vram)
+            ));
+        }
+        for feature in &needs.features {
+            if !host.features.contains(feature) {
+                shortfalls.push(format!(
+                    "needs the '{}' feature, which this build was compiled without",
+                    feature
+                ));
+            }
+        }
+
+        if shortfalls.is_empty() {
+            return Ok(());
+        }
+        Err(InfernoError::Resource(format!(
+            "Model '{}' cannot run on this host: {}",
+            model.name,
+            shortfalls.join("; ")
+        )))
+    }
+}
+
+fn gigabytes(bytes: u64) -> String {
+    format!("{:.1} GB", bytes as f64 / GB)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    struct FixedHost(HostCapabilities);
+
+    impl HostProbe for FixedHost {
+        fn capabilities(&self) -> HostCapabilities {
+            self.0.clone()
+        }
+    }
+
+    fn model(size: u64, format: &str) -> ModelInfo {
+        ModelInfo {
+            name: "model".to_string(),
+            path: "model".into(),
+            file_path: "model".into(),
+            size,
+            size_bytes: size,
+            modified: chrono::Utc::now(),
+            backend_type: format.to_string(),
+            format: format.to_string(),
+            checksum: None,
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn preflight(gpu_enabled: bool, host: HostCapabilities) -> Preflight {
+        let config = BackendConfig {
+            gpu_enabled,
+            // 2048 tokens of KV cache: 1 GB
+            context_size: 2048,
+            ..Default::default()
+        };
+        Preflight::new(&config).with_host(Arc::new(FixedHost(host)))
+    }
+
+    #[test]
+    fn test_preflight_reports_each_shortfall() {
+        let host = HostCapabilities {
+            available_memory_bytes: 8 * GIB,
+            available_vram_bytes: None,
+            features: vec!["gguf".to_string()],
+        };
+
+        // 7 GB of weights plus the KV cache fits in 8 GB
+        preflight(false, host.clone())
+            .check(&model(7 * GIB, "gguf"))
+            .unwrap();
+
+        let err = preflight(false, host.clone())
+            .check(&model(15 * GIB, "onnx"))
+            .unwrap_err();
+        let InfernoError::Resource(message) = err else {
+            panic!("expected a resource error, got {:?}", err);
+        };
+        assert!(
+            message.contains("needs 16.0 GB of RAM, 8.0 GB available"),
+            "{}",
+            message
+        );
+        assert!(message.contains("'onnx' feature"), "{}", message);
+
+        // With GPU offload the model is checked against VRAM instead
+        let gpu_host = HostCapabilities {
+            available_vram_bytes: Some(24 * GIB),
+            ..host.clone()
+        };
+        preflight(true, gpu_host.clone())
+            .check(&model(15 * GIB, "gguf"))
+            .unwrap();
+        let err = preflight(true, gpu_host)
+            .check(&model(31 * GIB, "gguf"))
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("needs 32.0 GB of VRAM, 24.0 GB available"),
+            "{}",
+            err
+        );
+
+        // Without a VRAM reading offloaded models are not checked against RAM
+        preflight(true, host)
+            .check(&model(15 * GIB, "gguf"))
+            .unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+//! RoPE frequency scaling
+//!
+//! Models using rotary position embeddings can run past the context they were
+//! trained on by stretching the rotation frequencies. These settings live under
+//! `[backend_config.rope]` and are passed to llama.cpp when a GGUF context is
+//! created:
+//!
+//! ```toml
+//! [backend_config.rope]
+//! scaling = "yarn"
+//! freq_scale = 0.25   # 4x the trained context
+//! ```
+//!
+//! Unset values keep what the model file specifies.
+
+use crate::InfernoError;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// How positions past the trained context are mapped back into it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RopeScaling {
+    /// No scaling; the model only sees its trained context
+    None,
+    /// Positions are divided by the inverse of `freq_scale`
+    Linear,
+    /// YaRN, which keeps high frequencies intact and degrades less than linear
+    Yarn,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RopeConfig {
+    /// Base frequency; raising it (NTK-aware scaling) also extends the context
+    pub freq_base: Option<f32>,
+    /// Frequency scale factor; 0.5 doubles the usable context
+    pub freq_scale: Option<f32>,
+    pub scaling: Option<RopeScaling>,
+}
+
+impl RopeConfig {
+    /// Reject values llama.cpp would silently misbehave with
+    pub fn validate(&self) -> Result<()> {
+        let invalid =
+            |message: String| -> Result<()> { Err(InfernoError::Validation(message).into()) };
+        if let Some(base) = self.freq_base
+            && !(base.is_finite() && base > 0.0)
+        {
+            return invalid(format!("rope freq_base must be positive, got {}", base));
+        }
+        if let Some(scale) = self.freq_scale {
+            if !(scale.is_finite() && scale > 0.0 && scale <= 1.0) {
+                return invalid(format!("rope freq_scale must be in (0, 1], got {}", scale));
+            }
+            if self.scaling == Some(RopeScaling::None) && scale != 1.0 {
+                return invalid(format!(
+                    "rope freq_scale {} has no effect with scaling = \"none\"",
+                    scale
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Context the model can attend over once scaling is applied to the
+    /// `trained` context it was built for
+    pub fn effective_context(&self, trained: u32) -> u32 {
+        if self.scaling == Some(RopeScaling::None) {
+            return trained;
+        }
+        let scale = self.freq_scale.unwrap_or(1.0);
+        (trained as f64 / scale as f64).round() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_and_effective_context() {
+        let yarn = RopeConfig {
+            freq_base: Some(10_000.0),
+            freq_scale: Some(0.25),
+            scaling: Some(RopeScaling::Yarn),
+        };
+        yarn.validate().unwrap();
+        assert_eq!(yarn.effective_context(4096), 16384);
+        assert_eq!(RopeConfig::default().effective_context(4096), 4096);
+
+        for bad in [
+            RopeConfig {
+                freq_base: Some(0.0),
+                ..Default::default()
+            },
+            RopeConfig {
+                freq_scale: Some(2.0),
+                ..Default::default()
+            },
+            RopeConfig {
+                freq_scale: Some(f32::NAN),
+                ..Default::default()
+            },
+            RopeConfig {
+                freq_scale: Some(0.5),
+                scaling: Some(RopeScaling::None),
+                ..Default::default()
+            },
+        ] {
+            let err = bad.validate().unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<InfernoError>(),
+                Some(InfernoError::Validation(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Stopping generation on a regular expression
+//!
+//! [`InferenceParams::stop_regex`] ends generation as soon as the output so far
+//! matches, and the output is cut where the match begins. [`Backend`](super::Backend)
+//! applies it for every backend by watching the token stream, so a match spread
+//! over several tokens is still found.
+//!
+//! Streams hold back the most recent token until the next one arrives, so a match
+//! beginning in it can still be cut; text of a match that began earlier has
+//! already been sent.
+
+use super::{InferenceParams, TokenStream};
+use crate::InfernoError;
+use futures::StreamExt;
+use regex::Regex;
+use std::collections::VecDeque;
+
+/// Compiled `stop_regex` of a request
+#[derive(Debug, Clone)]
+pub struct StopRegex(Regex);
+
+impl StopRegex {
+    pub fn new(pattern: &str) -> Result<Self, InfernoError> {
+        Regex::new(pattern).map(Self).map_err(|e| {
+            InfernoError::Validation(format!("Invalid stop_regex '{}': {}", pattern, e))
+        })
+    }
+
+    /// Compile the `stop_regex` of `params`, or `None` when it has none
+    pub fn from_params(params: &InferenceParams) -> Result<Option<Self>, InfernoError> {
+        params.stop_regex.as_deref().map(Self::new).transpose()
+    }
+
+    /// Byte offset in `text` where the first match begins
+    pub fn find(&self, text: &str) -> Option<usize> {
+        self.0.find(text).map(|m| m.start())
+    }
+
+    /// End `stream` at the first match, dropping the match and everything after it
+    pub fn apply_to_stream(self, stream: TokenStream) -> TokenStream {
+        let state = StreamState {
+            stop: self,
+            stream: Some(stream),
+            text: String::new(),
+            sent: 0,
+            pending: VecDeque::new(),
+        };
+        Box::pin(futures::stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(item) = state.pending.pop_front() {
+                    return Some((item, state));
+                }
+                let stream = state.stream.as_mut()?;
+                match stream.next().await {
+                    Some(Ok(token)) => state.push_token(token),
+                    Some(Err(e)) => {
+                        state.flush();
+                        state.pending.push_back(Err(e));
+                    }
+                    None => state.flush(),
+                }
+            }
+        }))
+    }
+}
+
+struct StreamState {
+    stop: StopRegex,
+    /// Cleared once the stream ends or the output matches
+    stream: Option<TokenStream>,
+    text: String,
+    /// Bytes of `text` already sent
+    sent: usize,
+    pending: VecDeque<Result<String, InfernoError>>,
+}
+
+impl StreamState {
+    fn push_token(&mut self, token: String) {
+        self.text.push_str(&token);
+        match self.stop.find(&self.text) {
+            Some(start) => {
+                self.send_until(start);
+                self.stream = None;
+            }
+            // Hold back the newest token in case a match begins in it
+            None => self.send_until(self.text.len() - token.len()),
+        }
+    }
+
+    fn flush(&mut self) {
+        self.send_until(self.text.len());
+        self.stream = None;
+    }
+
+    fn send_until(&mut self, end: usize) {
+        if end > self.sent {
+            self.pending
+                .push_back(Ok(self.text[self.sent..end].to_string()));
+            self.sent = end;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::Backend;
+    use crate::backends::mock::{MockBackend, MockState};
+    use std::sync::Arc;
+
+    const TOKENS: [&str; 6] = ["The answer", " is 4", "2.", " <e", "nd>", " More text"];
+
+    /// Streams `TOKENS`, counting how many were generated
+    fn token_backend() -> (Backend, Arc<MockState>) {
+        backend_with_tokens(&TOKENS)
+    }
+
+    fn backend_with_tokens(tokens: &[&str]) -> (Backend, Arc<MockState>) {
+        let backend = MockBackend::tokens(tokens);
+        let state = backend.state();
+        (backend.backend(), state)
+    }
+
+    fn stop_params(pattern: &str) -> InferenceParams {
+        InferenceParams {
+            stop_regex: Some(pattern.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_halts_on_match_split_across_tokens() {
+        // "<end>" arrives as " <e" and "nd>"
+        let params = stop_params(r"\s*<end>");
+
+        let (mut backend, generated) = token_backend();
+        let output = backend.infer("prompt", &params).await.unwrap();
+        assert_eq!(output, "The answer is 42.");
+        assert_eq!(generated.tokens(), 5);
+
+        let (mut backend, generated) = token_backend();
+        let stream = backend.infer_stream("prompt", &params).await.unwrap();
+        let tokens: Vec<String> = stream.map(|token| token.unwrap()).collect().await;
+        assert_eq!(tokens.concat(), "The answer is 42.");
+        assert_eq!(generated.tokens(), 5);
+
+        // Without a match the whole output comes through
+        let (mut backend, _) = token_backend();
+        let stream = backend
+            .infer_stream("prompt", &stop_params("never"))
+            .await
+            .unwrap();
+        let tokens: Vec<String> = stream.map(|token| token.unwrap()).collect().await;
+        assert_eq!(tokens.concat(), TOKENS.concat());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_regex_fails_before_generation() {
+        let (mut backend, generated) = token_backend();
+        let params = stop_params("(unclosed");
+
+        let err = backend.infer("prompt", &params).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InfernoError>(),
+            Some(InfernoError::Validation(_))
+        ));
+        assert!(backend.infer_stream("prompt", &params).await.is_err());
+        assert_eq!(generated.tokens(), 0);
+    }
+}
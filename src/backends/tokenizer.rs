@@ -0,0 +1,107 @@
+//! Token counting for usage statistics
+//!
+//! Backends that load a real tokenizer expose it through
+//! [`InferenceBackend::tokenizer`](super::InferenceBackend::tokenizer); everything else
+//! falls back to a character and word based estimate.
+
+use super::blocking;
+use anyhow::Result;
+use std::sync::Arc;
+use tracing::debug;
+
+/// Counts tokens in text
+pub trait Tokenizer: Send + Sync {
+    fn count_tokens(&self, text: &str) -> Result<usize>;
+}
+
+/// Estimate used when no model tokenizer is available
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn count_tokens(&self, text: &str) -> Result<usize> {
+        Ok(estimate_tokens(text) as usize)
+    }
+}
+
+/// Estimate a token count from character and word counts
+///
+/// English averages 3-4 characters per token, and subword tokenization splits many
+/// words, so the larger of the two estimates is used.
+pub fn estimate_tokens(text: &str) -> u32 {
+    if text.is_empty() {
+        return 0;
+    }
+
+    let char_based = (text.len() as f32 / 3.5).ceil() as u32;
+    let word_based = (text.split_whitespace().count() as f32 * 1.3).ceil() as u32;
+    char_based.max(word_based).max(1)
+}
+
+/// Count tokens with the given tokenizer, falling back to the estimate without one
+pub fn count_tokens(tokenizer: Option<&dyn Tokenizer>, text: &str) -> u32 {
+    match tokenizer.map(|tokenizer| tokenizer.count_tokens(text)) {
+        Some(Ok(count)) => count as u32,
+        Some(Err(e)) => {
+            debug!("Tokenizer failed, estimating token count: {}", e);
+            estimate_tokens(text)
+        }
+        None => estimate_tokens(text),
+    }
+}
+
+/// [`count_tokens`] on the tokenization pool, keeping vocabulary lookups off the
+/// async executor
+pub async fn count_tokens_blocking(tokenizer: Option<Arc<dyn Tokenizer>>, text: &str) -> u32 {
+    let Some(tokenizer) = tokenizer else {
+        return estimate_tokens(text);
+    };
+    let owned = text.to_string();
+    match blocking::run_tokenization(move || count_tokens(Some(tokenizer.as_ref()), &owned)).await {
+        Ok(count) => count,
+        Err(e) => {
+            debug!("Tokenization task failed, estimating token count: {}", e);
+            estimate_tokens(text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for a loaded model's tokenizer: one token per character
+    struct CharTokenizer;
+
+    impl Tokenizer for CharTokenizer {
+        fn count_tokens(&self, text: &str) -> Result<usize> {
+            Ok(text.chars().count())
+        }
+    }
+
+    struct FailingTokenizer;
+
+    impl Tokenizer for FailingTokenizer {
+        fn count_tokens(&self, _text: &str) -> Result<usize> {
+            anyhow::bail!("tokenizer unavailable")
+        }
+    }
+
+    #[test]
+    fn test_real_tokenizer_used_when_available() {
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let heuristic = estimate_tokens(text);
+        assert_eq!(heuristic, 13);
+
+        assert_eq!(count_tokens(Some(&CharTokenizer), text), 44);
+        assert_eq!(count_tokens(None, text), heuristic);
+        assert_eq!(count_tokens(Some(&FailingTokenizer), text), heuristic);
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("hi"), 1);
+        assert_eq!(HeuristicTokenizer.count_tokens("hello world").unwrap(), 4);
+    }
+}
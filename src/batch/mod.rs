@@ -1,21 +1,24 @@
 #![allow(dead_code, unused_imports, unused_variables)]
 pub mod queue;
 pub mod scheduler;
+pub mod template;
 
 use crate::{
-    backends::{Backend, InferenceParams},
+    backends::{Backend, InferenceParams, PostProcessor},
     metrics::{InferenceEvent, MetricsCollector},
 };
 use anyhow::Result;
+use template::PromptTemplate;
 // Futures support for parallel processing (if needed in future)
 use serde::{Deserialize, Serialize};
 use std::{
-    path::Path,
+    path::{Path, PathBuf},
     sync::{Arc, atomic::AtomicUsize},
     time::{Duration, Instant},
 };
+use tokio::io::AsyncWriteExt;
 // use tokio::sync::Semaphore; // Reserved for future concurrent processing
-use tracing::{info, warn};
+use tracing::{Instrument, info, info_span, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatchConfig {
@@ -26,6 +29,22 @@ pub struct BatchConfig {
     pub output_format: BatchOutputFormat,
     pub continue_on_error: bool,
     pub shuffle_inputs: bool,
+    /// Post-processor applied to each output before it is saved
+    #[serde(default)]
+    pub post_process: Option<String>,
+    /// CSV/TSV column or JSON field holding each prompt, instead of guessing
+    #[serde(default)]
+    pub input_column: Option<String>,
+    /// CSV/TSV column or JSON field holding each input's id
+    #[serde(default)]
+    pub id_column: Option<String>,
+    /// Template each input is rendered into before inference, with `{input}`
+    /// and `{metadata.<field>}` placeholders; see [`PromptTemplate`]
+    #[serde(default)]
+    pub prompt_template: Option<String>,
+    /// Render metadata fields an item lacks as empty instead of failing it
+    #[serde(default)]
+    pub lenient_template: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,6 +83,19 @@ pub struct BatchProgress {
     pub start_time: chrono::DateTime<chrono::Utc>,
     pub estimated_completion: Option<chrono::DateTime<chrono::Utc>>,
     pub current_rate: f64, // items per second
+    #[serde(default)]
+    pub timing: BatchTiming,
+}
+
+/// Time spent in each phase of a batch run
+///
+/// Each phase also runs inside a tracing span (`batch_load`, `batch_infer`,
+/// `batch_save`), so the same breakdown shows up in collected traces.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchTiming {
+    pub load: Duration,
+    pub inference: Duration,
+    pub save: Duration,
 }
 
 #[derive(Debug)]
@@ -85,6 +117,11 @@ impl Default for BatchConfig {
             output_format: BatchOutputFormat::JsonLines,
             continue_on_error: true,
             shuffle_inputs: false,
+            post_process: None,
+            input_column: None,
+            id_column: None,
+            prompt_template: None,
+            lenient_template: false,
         }
     }
 }
@@ -112,23 +149,56 @@ impl BatchProcessor {
         output_path: Option<&Path>,
         inference_params: &InferenceParams,
     ) -> Result<BatchProgress> {
-        let inputs = self.load_inputs(input_path).await?;
-        self.process_inputs(backend, inputs, output_path, inference_params)
+        let load_start = Instant::now();
+        let inputs = self
+            .load_inputs(input_path)
+            .instrument(info_span!("batch_load", path = %input_path.display()))
+            .await?;
+        let timing = BatchTiming {
+            load: load_start.elapsed(),
+            ..BatchTiming::default()
+        };
+        self.process_timed(backend, inputs, output_path, inference_params, timing)
             .await
     }
 
     pub async fn process_inputs(
+        &self,
+        backend: &mut Backend,
+        inputs: Vec<BatchInput>,
+        output_path: Option<&Path>,
+        inference_params: &InferenceParams,
+    ) -> Result<BatchProgress> {
+        self.process_timed(
+            backend,
+            inputs,
+            output_path,
+            inference_params,
+            BatchTiming::default(),
+        )
+        .await
+    }
+
+    async fn process_timed(
         &self,
         backend: &mut Backend,
         mut inputs: Vec<BatchInput>,
         output_path: Option<&Path>,
         inference_params: &InferenceParams,
+        mut timing: BatchTiming,
     ) -> Result<BatchProgress> {
         if self.config.shuffle_inputs {
             use rand::seq::SliceRandom;
             inputs.shuffle(&mut rand::rng());
         }
 
+        let post_processor = PostProcessor::from_option(self.config.post_process.as_deref())?;
+        let template = self
+            .config
+            .prompt_template
+            .as_deref()
+            .map(|template| PromptTemplate::parse(template, self.config.lenient_template))
+            .transpose()?;
         let total_items = inputs.len();
         info!(
             "Starting batch processing of {} items (sequential mode)",
@@ -136,25 +206,51 @@ impl BatchProcessor {
         );
 
         let mut results = Vec::new();
+        let mut checkpointed = 0;
         let start_time = chrono::Utc::now();
         let mut completed = 0;
         let mut failed = 0;
 
-        for (i, input) in inputs.into_iter().enumerate() {
+        for (i, mut input) in inputs.into_iter().enumerate() {
             if (i + 1) % 10 == 0 || i == 0 {
                 info!("Processing item {}/{}", i + 1, total_items);
             }
 
-            let result = Self::process_single_input_simple(
-                backend,
-                input,
-                inference_params,
-                self.metrics.clone(),
-                "batch_model".to_string(),
-                self.config.timeout_seconds,
-                self.config.retry_attempts,
-            )
-            .await;
+            let span = info_span!("batch_infer", item = %input.id);
+            let infer_start = Instant::now();
+            let rendered = match &template {
+                Some(template) => template.render(&input).map(|prompt| input.content = prompt),
+                None => Ok(()),
+            };
+            let mut result = match rendered {
+                Ok(()) => {
+                    Self::process_single_input_simple(
+                        backend,
+                        input,
+                        inference_params,
+                        self.metrics.clone(),
+                        "batch_model".to_string(),
+                        self.config.timeout_seconds,
+                        self.config.retry_attempts,
+                    )
+                    .instrument(span)
+                    .await
+                }
+                Err(e) => BatchResult {
+                    id: input.id,
+                    input: input.content,
+                    output: None,
+                    error: Some(e.to_string()),
+                    duration_ms: 0,
+                    tokens_generated: None,
+                    timestamp: chrono::Utc::now(),
+                    metadata: input.metadata,
+                },
+            };
+            timing.inference += infer_start.elapsed();
+            if let Some(output) = &mut result.output {
+                *output = post_processor.apply(output);
+            }
 
             if result.error.is_none() {
                 completed += 1;
@@ -171,14 +267,23 @@ impl BatchProcessor {
             // Checkpoint save
             if results.len() % self.config.checkpoint_interval as usize == 0 {
                 if let Some(output_path) = output_path {
-                    self.save_checkpoint(output_path, &results).await?;
+                    let save_start = Instant::now();
+                    self.save_checkpoint(output_path, &results[checkpointed..], checkpointed == 0)
+                        .instrument(info_span!("batch_save", checkpoint = true))
+                        .await?;
+                    timing.save += save_start.elapsed();
+                    checkpointed = results.len();
                 }
             }
         }
 
         // Final save
         if let Some(output_path) = output_path {
-            self.save_results(output_path, &results).await?;
+            let save_start = Instant::now();
+            self.save_results(output_path, &results)
+                .instrument(info_span!("batch_save", checkpoint = false))
+                .await?;
+            timing.save += save_start.elapsed();
         }
 
         let elapsed = chrono::Utc::now() - start_time;
@@ -200,6 +305,7 @@ impl BatchProcessor {
             start_time,
             estimated_completion: Some(chrono::Utc::now()),
             current_rate: completed as f64 / elapsed_seconds as f64,
+            timing,
         })
     }
 
@@ -214,6 +320,8 @@ impl BatchProcessor {
     ) -> BatchResult {
         let start_time = Instant::now();
         let timestamp = chrono::Utc::now();
+        // Retries reuse the seed, and results record it so an item can be reproduced
+        let params = &params.with_effective_seed();
 
         for attempt in 0..=retry_attempts {
             match tokio::time::timeout(
@@ -224,13 +332,15 @@ impl BatchProcessor {
             {
                 Ok(Ok(output)) => {
                     let duration = start_time.elapsed();
+                    let prompt_tokens = backend.count_tokens(&input.content);
+                    let tokens_generated = backend.count_tokens(&output);
 
                     // Record metrics
                     if let Some(metrics) = &metrics {
                         let event = InferenceEvent {
                             model_name: model_name.clone(),
-                            input_length: input.content.len() as u32,
-                            output_length: output.len() as u32, // Rough estimate
+                            input_length: prompt_tokens,
+                            output_length: tokens_generated,
                             duration,
                             success: true,
                         };
@@ -243,9 +353,9 @@ impl BatchProcessor {
                         output: Some(output.clone()),
                         error: None,
                         duration_ms: duration.as_millis() as u64,
-                        tokens_generated: Some((output.len() / 4) as u32), // Rough token estimate
+                        tokens_generated: Some(tokens_generated),
                         timestamp,
-                        metadata: input.metadata,
+                        metadata: with_seed(input.metadata, params.seed),
                     };
                 }
                 Ok(Err(e)) => {
@@ -261,7 +371,7 @@ impl BatchProcessor {
                         if let Some(metrics) = &metrics {
                             let event = InferenceEvent {
                                 model_name: model_name.clone(),
-                                input_length: input.content.len() as u32,
+                                input_length: backend.count_tokens(&input.content),
                                 output_length: 0,
                                 duration: start_time.elapsed(),
                                 success: false,
@@ -339,27 +449,12 @@ impl BatchProcessor {
                             });
                         }
                         serde_json::Value::Object(obj) => {
-                            let content = obj
-                                .get("content")
-                                .or_else(|| obj.get("text"))
-                                .or_else(|| obj.get("input"))
-                                .and_then(|v| v.as_str())
-                                .ok_or_else(|| {
-                                    anyhow::anyhow!("No content field found in JSON object")
-                                })?
-                                .to_string();
-
-                            let id = obj
-                                .get("id")
-                                .and_then(|v| v.as_str())
-                                .unwrap_or(&format!("item_{}", i))
-                                .to_string();
-
-                            inputs.push(BatchInput {
-                                id,
-                                content,
-                                metadata: Some(serde_json::Value::Object(obj)),
-                            });
+                            let location = format!("JSON object {}", i);
+                            inputs.push(self.object_input(
+                                obj,
+                                format!("item_{}", i),
+                                &location,
+                            )?);
                         }
                         _ => return Err(anyhow::anyhow!("Invalid JSON array item format")),
                     }
@@ -386,30 +481,8 @@ impl BatchProcessor {
                     });
                 }
                 serde_json::Value::Object(obj) => {
-                    let content = obj
-                        .get("content")
-                        .or_else(|| obj.get("text"))
-                        .or_else(|| obj.get("input"))
-                        .and_then(|v| v.as_str())
-                        .ok_or_else(|| {
-                            anyhow::anyhow!(
-                                "No content field found in JSONL object at line {}",
-                                i + 1
-                            )
-                        })?
-                        .to_string();
-
-                    let id = obj
-                        .get("id")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or(&format!("line_{}", i + 1))
-                        .to_string();
-
-                    inputs.push(BatchInput {
-                        id,
-                        content,
-                        metadata: Some(serde_json::Value::Object(obj)),
-                    });
+                    let location = format!("JSONL object at line {}", i + 1);
+                    inputs.push(self.object_input(obj, format!("line_{}", i + 1), &location)?);
                 }
                 _ => return Err(anyhow::anyhow!("Invalid JSONL format at line {}", i + 1)),
             }
@@ -417,6 +490,57 @@ impl BatchProcessor {
         Ok(inputs)
     }
 
+    /// Input from a JSON object: the prompt from `input_column` or else the
+    /// first of "content", "text" and "input", and the id from `id_column` or
+    /// else "id"
+    fn object_input(
+        &self,
+        obj: serde_json::Map<String, serde_json::Value>,
+        default_id: String,
+        location: &str,
+    ) -> Result<BatchInput> {
+        let field = |name: &str| {
+            obj.get(name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Field '{}' not found in {}; available fields: {}",
+                    name,
+                    location,
+                    obj.keys().cloned().collect::<Vec<_>>().join(", ")
+                )
+            })
+        };
+
+        let content = match &self.config.input_column {
+            Some(column) => field(column)?.as_str().ok_or_else(|| {
+                anyhow::anyhow!("Field '{}' in {} is not a string", column, location)
+            })?,
+            None => obj
+                .get("content")
+                .or_else(|| obj.get("text"))
+                .or_else(|| obj.get("input"))
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("No content field found in {}", location))?,
+        }
+        .to_string();
+
+        let id = match &self.config.id_column {
+            Some(column) => match field(column)? {
+                serde_json::Value::String(id) => id.clone(),
+                other => other.to_string(),
+            },
+            None => obj
+                .get("id")
+                .and_then(|v| v.as_str())
+                .map_or(default_id, str::to_string),
+        };
+
+        Ok(BatchInput {
+            id,
+            content,
+            metadata: Some(serde_json::Value::Object(obj)),
+        })
+    }
+
     async fn load_csv_inputs(&self, content: &str) -> Result<Vec<BatchInput>> {
         self.load_delimited_inputs(content, ',').await
     }
@@ -435,26 +559,37 @@ impl BatchProcessor {
             .from_reader(content.as_bytes());
 
         let headers = rdr.headers()?.clone();
+        let column = |name: &str| {
+            headers.iter().position(|h| h == name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Column '{}' not found; available columns: {}",
+                    name,
+                    headers.iter().collect::<Vec<_>>().join(", ")
+                )
+            })
+        };
+
+        // The named column, else one with a common prompt name, else the first
+        let content_column = match &self.config.input_column {
+            Some(name) => column(name)?,
+            None => headers
+                .iter()
+                .position(|h| {
+                    matches!(
+                        h.to_lowercase().as_str(),
+                        "content" | "text" | "input" | "prompt"
+                    )
+                })
+                .unwrap_or(0),
+        };
+        let id_column = self.config.id_column.as_deref().map(column).transpose()?;
         let mut inputs = Vec::new();
 
         for (i, result) in rdr.records().enumerate() {
             let record = result?;
 
-            // Look for content in common column names
             let content = record
-                .get(0)
-                .or_else(|| {
-                    headers
-                        .iter()
-                        .enumerate()
-                        .find(|(_, h)| {
-                            matches!(
-                                h.to_lowercase().as_str(),
-                                "content" | "text" | "input" | "prompt"
-                            )
-                        })
-                        .and_then(|(idx, _)| record.get(idx))
-                })
+                .get(content_column)
                 .ok_or_else(|| anyhow::anyhow!("No content column found in CSV row {}", i + 1))?
                 .to_string();
 
@@ -469,11 +604,11 @@ impl BatchProcessor {
                 }
             }
 
-            let id = metadata
-                .get("id")
-                .and_then(|v| v.as_str())
-                .unwrap_or(&format!("row_{}", i + 1))
-                .to_string();
+            let id = match id_column {
+                Some(idx) => record.get(idx),
+                None => metadata.get("id").and_then(|v| v.as_str()),
+            }
+            .map_or_else(|| format!("row_{}", i + 1), str::to_string);
 
             inputs.push(BatchInput {
                 id,
@@ -505,15 +640,37 @@ impl BatchProcessor {
         Ok(inputs)
     }
 
-    async fn save_checkpoint(&self, output_path: &Path, results: &[BatchResult]) -> Result<()> {
-        let checkpoint_path = output_path.with_extension(format!(
-            "checkpoint.{}",
-            output_path
-                .extension()
-                .and_then(|s| s.to_str())
-                .unwrap_or("json")
-        ));
-        self.save_results(&checkpoint_path, results).await
+    /// Where checkpoints for `output_path` are written
+    ///
+    /// Checkpoints are always JSON Lines, whatever the output format, so each one
+    /// only appends the results finished since the last and a crash mid-write leaves
+    /// at most one partial trailing line.
+    pub fn checkpoint_path(output_path: &Path) -> PathBuf {
+        output_path.with_extension("checkpoint.jsonl")
+    }
+
+    async fn save_checkpoint(
+        &self,
+        output_path: &Path,
+        new_results: &[BatchResult],
+        truncate: bool,
+    ) -> Result<()> {
+        let mut content = String::new();
+        for result in new_results {
+            content.push_str(&serde_json::to_string(result)?);
+            content.push('\n');
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(!truncate)
+            .truncate(truncate)
+            .open(Self::checkpoint_path(output_path))
+            .await?;
+        file.write_all(content.as_bytes()).await?;
+        file.sync_data().await?;
+        Ok(())
     }
 
     async fn save_results(&self, output_path: &Path, results: &[BatchResult]) -> Result<()> {
@@ -528,8 +685,7 @@ impl BatchProcessor {
             BatchOutputFormat::Tsv => self.results_to_tsv(results)?,
         };
 
-        tokio::fs::write(output_path, content).await?;
-        Ok(())
+        crate::io::atomic::write_file_async(output_path, content).await
     }
 
     fn results_to_csv(&self, results: &[BatchResult]) -> Result<String> {
@@ -602,3 +758,246 @@ impl BatchProcessor {
         Ok(data)
     }
 }
+
+/// Record `seed` in an item's metadata, creating an object when it has none;
+/// metadata that is not an object is left as it is
+fn with_seed(metadata: Option<serde_json::Value>, seed: Option<u64>) -> Option<serde_json::Value> {
+    let Some(seed) = seed else {
+        return metadata;
+    };
+    match metadata {
+        Some(serde_json::Value::Object(mut obj)) => {
+            obj.insert("seed".to_string(), seed.into());
+            Some(serde_json::Value::Object(obj))
+        }
+        None => Some(serde_json::json!({ "seed": seed })),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::mock::MockBackend;
+
+    #[tokio::test]
+    async fn test_checkpoints_are_jsonl_for_json_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_path = dir.path().join("results.json");
+        let inputs: Vec<BatchInput> = (0..5)
+            .map(|i| BatchInput {
+                id: i.to_string(),
+                content: format!("prompt {i}"),
+                metadata: None,
+            })
+            .collect();
+        let config = BatchConfig {
+            checkpoint_interval: 2,
+            output_format: BatchOutputFormat::Json,
+            ..BatchConfig::default()
+        };
+
+        let mut backend = MockBackend::prompt(str::to_uppercase).backend();
+        let processor = BatchProcessor::new(config, inputs.len());
+        processor
+            .process_inputs(
+                &mut backend,
+                inputs,
+                Some(&output_path),
+                &InferenceParams::default(),
+            )
+            .await
+            .unwrap();
+
+        let checkpoint =
+            std::fs::read_to_string(BatchProcessor::checkpoint_path(&output_path)).unwrap();
+        let checkpointed: Vec<BatchResult> = checkpoint
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let ids: Vec<&str> = checkpointed.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["0", "1", "2", "3"]);
+
+        let output: Vec<BatchResult> =
+            serde_json::from_str(&std::fs::read_to_string(&output_path).unwrap()).unwrap();
+        assert_eq!(output.len(), 5);
+        assert_eq!(output[4].output.as_deref(), Some("PROMPT 4"));
+        let seed = &output[4].metadata.as_ref().unwrap()["seed"];
+        assert!(seed.is_u64());
+    }
+
+    #[tokio::test]
+    async fn test_timing_covers_load_and_inference() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_path = dir.path().join("prompts.txt");
+        std::fs::write(&input_path, "first\nsecond\nthird\n").unwrap();
+        let output_path = dir.path().join("results.jsonl");
+
+        let mut backend = MockBackend::prompt(str::to_uppercase).backend();
+        let processor = BatchProcessor::new(BatchConfig::default(), 3);
+        let progress = processor
+            .process_file(
+                &mut backend,
+                &input_path,
+                Some(&output_path),
+                &InferenceParams::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(progress.completed_items, 3);
+        assert!(progress.timing.load > Duration::ZERO);
+        assert!(progress.timing.inference > Duration::ZERO);
+        assert!(progress.timing.save > Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_input_column_overrides_guessed_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("inputs.csv");
+        std::fs::write(
+            &csv_path,
+            "ticket,language,question\nT-1,en,How do I reset it?\nT-2,de,Wo ist das Menü?\n",
+        )
+        .unwrap();
+
+        // Without an override the first column is taken as the prompt
+        let processor = BatchProcessor::new(BatchConfig::default(), 0);
+        let inputs = processor.load_inputs(&csv_path).await.unwrap();
+        assert_eq!(inputs[0].content, "T-1");
+        assert_eq!(inputs[0].id, "row_1");
+
+        let config = BatchConfig {
+            input_column: Some("question".to_string()),
+            id_column: Some("ticket".to_string()),
+            ..BatchConfig::default()
+        };
+        let processor = BatchProcessor::new(config, 0);
+        let inputs = processor.load_inputs(&csv_path).await.unwrap();
+        let loaded: Vec<(&str, &str)> = inputs
+            .iter()
+            .map(|input| (input.id.as_str(), input.content.as_str()))
+            .collect();
+        assert_eq!(
+            loaded,
+            [("T-1", "How do I reset it?"), ("T-2", "Wo ist das Menü?")]
+        );
+
+        // JSON Lines fields are picked the same way
+        let jsonl_path = dir.path().join("inputs.jsonl");
+        std::fs::write(&jsonl_path, "{\"ticket\": 7, \"question\": \"Why?\"}\n").unwrap();
+        let inputs = processor.load_inputs(&jsonl_path).await.unwrap();
+        assert_eq!(
+            (inputs[0].id.as_str(), inputs[0].content.as_str()),
+            ("7", "Why?")
+        );
+
+        // A missing column names the ones there are
+        let config = BatchConfig {
+            input_column: Some("body".to_string()),
+            ..BatchConfig::default()
+        };
+        let err = BatchProcessor::new(config, 0)
+            .load_inputs(&csv_path)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("'body'"), "{}", err);
+        assert!(err.contains("ticket, language, question"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_default_columns_still_guessed() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("inputs.csv");
+        std::fs::write(&csv_path, "id,prompt\na,first\nb,second\n").unwrap();
+        let jsonl_path = dir.path().join("inputs.jsonl");
+        std::fs::write(&jsonl_path, "{\"id\": \"c\", \"text\": \"third\"}\n").unwrap();
+
+        let processor = BatchProcessor::new(BatchConfig::default(), 0);
+        let inputs = processor.load_inputs(&csv_path).await.unwrap();
+        assert_eq!(
+            (inputs[1].id.as_str(), inputs[1].content.as_str()),
+            ("b", "second")
+        );
+        let inputs = processor.load_inputs(&jsonl_path).await.unwrap();
+        assert_eq!(
+            (inputs[0].id.as_str(), inputs[0].content.as_str()),
+            ("c", "third")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prompt_template_renders_input_and_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_path = dir.path().join("questions.jsonl");
+        std::fs::write(
+            &input_path,
+            concat!(
+                "{\"id\": \"q1\", \"text\": \"what is rust\", \"topic\": {\"name\": \"languages\"}, \"level\": 2}\n",
+                "{\"id\": \"q2\", \"text\": \"what is tokio\"}\n",
+            ),
+        )
+        .unwrap();
+        let output_path = dir.path().join("answers.jsonl");
+        let config = BatchConfig {
+            retry_attempts: 0,
+            prompt_template: Some(
+                "Answer concisely ({metadata.topic.name}, level {metadata.level}): {input} {{ok}}"
+                    .to_string(),
+            ),
+            ..BatchConfig::default()
+        };
+
+        let run = |config: BatchConfig| {
+            let input_path = input_path.clone();
+            let output_path = output_path.clone();
+            async move {
+                let mut backend = MockBackend::prompt(str::to_uppercase).backend();
+                let progress = BatchProcessor::new(config, 2)
+                    .process_file(
+                        &mut backend,
+                        &input_path,
+                        Some(&output_path),
+                        &InferenceParams::default(),
+                    )
+                    .await
+                    .unwrap();
+                let results: Vec<BatchResult> = std::fs::read_to_string(&output_path)
+                    .unwrap()
+                    .lines()
+                    .map(|line| serde_json::from_str(line).unwrap())
+                    .collect();
+                (progress, results)
+            }
+        };
+
+        // Strict: an item missing a referenced field fails
+        let (progress, results) = run(config.clone()).await;
+        assert_eq!((progress.completed_items, progress.failed_items), (1, 1));
+        assert_eq!(
+            results[0].output.as_deref(),
+            Some("ANSWER CONCISELY (LANGUAGES, LEVEL 2): WHAT IS RUST {OK}")
+        );
+        assert!(
+            results[1].error.as_deref().unwrap().contains("topic.name"),
+            "{:?}",
+            results[1].error
+        );
+
+        // Lenient: missing fields render empty
+        let (progress, results) = run(BatchConfig {
+            lenient_template: true,
+            ..config
+        })
+        .await;
+        assert_eq!(progress.completed_items, 2);
+        assert_eq!(
+            results[1].output.as_deref(),
+            Some("ANSWER CONCISELY (, LEVEL ): WHAT IS TOKIO {OK}")
+        );
+
+        assert!(PromptTemplate::parse("{question}", false).is_err());
+        assert!(PromptTemplate::parse("{input", false).is_err());
+    }
+}
@@ -14,7 +14,6 @@ use std::{
     time::{Duration, SystemTime},
 };
 use tokio::{
-    fs,
     sync::{Mutex, RwLock, mpsc},
     time::sleep,
 };
@@ -1254,7 +1253,7 @@ impl JobQueueManager {
             let serializable_queue = queue.to_serializable().await;
             let json_data = serde_json::to_string_pretty(&serializable_queue)?;
 
-            fs::write(&queue_file, json_data).await?;
+            crate::io::atomic::write_file_async(&queue_file, json_data).await?;
             debug!("Saved queue '{}' to persistent storage", queue_id);
         }
         Ok(())
@@ -1570,7 +1569,7 @@ impl Worker {
 
         let filename = format!("{}/{}.json", storage_path.display(), job_id);
         let json = serde_json::to_string_pretty(result)?;
-        tokio::fs::write(&filename, json).await?;
+        crate::io::atomic::write_file_async(std::path::Path::new(&filename), json).await?;
 
         info!("Saved job result to {}", filename);
         Ok(())
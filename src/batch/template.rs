@@ -0,0 +1,116 @@
+//! Prompt templates wrapping each batch input
+//!
+//! A template such as `Answer concisely: {input}` is rendered for every item
+//! before inference. `{input}` is the item's content and `{metadata.field}` a
+//! field of its metadata, the other columns of a CSV row or fields of a JSON
+//! object; nested fields are reached with further dots. `{{` and `}}` are
+//! literal braces.
+
+use super::BatchInput;
+use anyhow::{Result, anyhow, bail};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Input,
+    Metadata(Vec<String>),
+}
+
+/// A parsed prompt template
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    parts: Vec<Part>,
+    /// Render fields missing from an item as empty instead of failing it
+    lenient: bool,
+}
+
+impl PromptTemplate {
+    pub fn parse(template: &str, lenient: bool) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => bail!("Unclosed '{{' in prompt template"),
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Self::placeholder(name.trim())?);
+                }
+                '}' => bail!("Unmatched '}}' in prompt template; write '}}}}' for a brace"),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self { parts, lenient })
+    }
+
+    fn placeholder(name: &str) -> Result<Part> {
+        if name == "input" {
+            return Ok(Part::Input);
+        }
+        match name.strip_prefix("metadata.") {
+            Some(path) if path.split('.').all(|field| !field.is_empty()) => Ok(Part::Metadata(
+                path.split('.').map(str::to_string).collect(),
+            )),
+            _ => bail!(
+                "Unknown placeholder '{{{}}}' in prompt template; use {{input}} or {{metadata.<field>}}",
+                name
+            ),
+        }
+    }
+
+    /// The prompt for `input`
+    pub fn render(&self, input: &BatchInput) -> Result<String> {
+        let mut prompt = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => prompt.push_str(text),
+                Part::Input => prompt.push_str(&input.content),
+                Part::Metadata(path) => match lookup(input.metadata.as_ref(), path) {
+                    Some(value) => prompt.push_str(&value),
+                    None if self.lenient => {}
+                    None => {
+                        return Err(anyhow!(
+                            "Item {} has no metadata field '{}' for the prompt template",
+                            input.id,
+                            path.join(".")
+                        ));
+                    }
+                },
+            }
+        }
+        Ok(prompt)
+    }
+}
+
+/// Text of the field at `path` in `metadata`; null counts as missing
+fn lookup(metadata: Option<&serde_json::Value>, path: &[String]) -> Option<String> {
+    let mut value = metadata?;
+    for field in path {
+        value = value.get(field)?;
+    }
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(text) => Some(text.clone()),
+        other => Some(other.to_string()),
+    }
+}
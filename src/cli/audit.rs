@@ -3,7 +3,7 @@ use crate::{
     audit::{
         Actor, ActorType, AlertConfiguration, AuditConfiguration, AuditEvent, AuditLogger,
         AuditQuery, CompressionMethod, EventType, ExportFormat, LogLevel, Resource, ResourceType,
-        Severity, SortField, SortOrder,
+        Severity, SortField, SortOrder, verify_chain_from,
     },
     config::Config,
 };
@@ -153,6 +153,9 @@ pub enum AuditCommand {
         verify_timestamps: bool,
     },
 
+    #[command(about = "Verify the audit log hash chain and report the first broken link")]
+    Verify,
+
     #[command(about = "Archive old audit logs")]
     Archive {
         #[arg(long, help = "Archive logs older than N days", default_value = "90")]
@@ -503,7 +506,13 @@ fn count_by_severity(events: &[AuditEvent]) -> Vec<(String, usize)> {
 // ============================================================================
 
 pub async fn execute(args: AuditArgs, config: &Config) -> Result<()> {
-    let audit_config = AuditConfiguration::default();
+    let audit = &config.logging_audit.audit;
+    let audit_config = AuditConfiguration {
+        hash_chain: audit.hash_chain,
+        max_file_size_mb: audit.max_file_size.div_ceil(1024 * 1024).max(1),
+        rotation_interval_secs: parse_rotation_interval(&audit.rotation_interval)?,
+        ..AuditConfiguration::default()
+    };
     let logger = AuditLogger::new(audit_config).await?;
 
     match args.command {
@@ -554,7 +563,8 @@ pub async fn execute(args: AuditArgs, config: &Config) -> Result<()> {
             validate_stats_range(range_hours)?;
 
             let stats = logger.get_statistics().await?;
-            display_statistics(&stats, group_by, format);
+            let rotation = logger.rotation_stats().await?;
+            display_statistics(&stats, &rotation, group_by, format);
         }
 
         AuditCommand::Export {
@@ -749,6 +759,9 @@ pub async fn execute(args: AuditArgs, config: &Config) -> Result<()> {
                 alert_on_critical: true,
                 alerting: AlertConfiguration::default(),
                 export_format: ExportFormat::Json,
+                hash_chain: false,
+                rotation_interval_secs: 24 * 3600,
+                compress_rotated: true,
             };
 
             let audit_logger = AuditLogger::new(audit_config).await?;
@@ -906,6 +919,21 @@ pub async fn execute(args: AuditArgs, config: &Config) -> Result<()> {
             }
         }
 
+        AuditCommand::Verify => {
+            let events = logger.read_log_events().await?;
+            let anchor = logger.chain_anchor().await?;
+            match verify_chain_from(&anchor, &events)? {
+                None => println!("✓ Audit log hash chain intact ({} entries)", events.len()),
+                Some(broken) => {
+                    println!(
+                        "✗ Hash chain broken at entry {} (id {}): {}",
+                        broken.position, broken.event_id, broken.reason
+                    );
+                    anyhow::bail!("Audit log failed hash chain verification");
+                }
+            }
+        }
+
         AuditCommand::Archive {
             older_than_days,
             destination,
@@ -1201,6 +1229,20 @@ fn parse_time(time_str: &str) -> Result<SystemTime> {
     Ok(SystemTime::from(datetime.with_timezone(&Utc)))
 }
 
+/// Seconds between audit log rotations: "hourly", "daily", "weekly", "never",
+/// or a duration such as "6h"
+fn parse_rotation_interval(interval: &str) -> Result<u64> {
+    match interval.trim().to_lowercase().as_str() {
+        "hourly" => Ok(3600),
+        "daily" => Ok(24 * 3600),
+        "weekly" => Ok(7 * 24 * 3600),
+        "never" => Ok(0),
+        other => humantime::parse_duration(other)
+            .map(|duration| duration.as_secs())
+            .map_err(|e| anyhow::anyhow!("Invalid audit rotation_interval '{}': {}", interval, e)),
+    }
+}
+
 fn display_events(events: &[AuditEvent], format: OutputFormat) {
     match format {
         OutputFormat::Table => {
@@ -1260,6 +1302,7 @@ fn display_events(events: &[AuditEvent], format: OutputFormat) {
 
 fn display_statistics(
     stats: &crate::audit::AuditStatistics,
+    rotation: &crate::audit::AuditRotationStats,
     _group_by: Option<GroupByField>,
     format: OutputFormat,
 ) {
@@ -1286,9 +1329,18 @@ fn display_statistics(
                     println!("  {}: {}", severity, count);
                 }
             }
+
+            println!("\nLog Files:");
+            println!("  Active Segment: {} bytes", rotation.active_file_bytes);
+            println!(
+                "  Rotated Segments: {} ({} bytes)",
+                rotation.archive_count, rotation.archive_bytes
+            );
         }
         OutputFormat::Json => {
-            println!("{}", serde_json::to_string_pretty(stats).unwrap());
+            let mut value = serde_json::to_value(stats).unwrap();
+            value["rotation"] = serde_json::to_value(rotation).unwrap();
+            println!("{}", serde_json::to_string_pretty(&value).unwrap());
         }
         _ => {
             println!("Format {:?} not yet implemented for statistics", format);
@@ -1359,6 +1411,7 @@ fn create_audit_event(
             records_affected: None,
         },
         metadata: HashMap::new(),
+        chain_hash: None,
     }
 }
 
@@ -1370,6 +1423,15 @@ fn create_audit_event(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_rotation_interval() {
+        assert_eq!(parse_rotation_interval("daily").unwrap(), 24 * 3600);
+        assert_eq!(parse_rotation_interval("Hourly").unwrap(), 3600);
+        assert_eq!(parse_rotation_interval("6h").unwrap(), 6 * 3600);
+        assert_eq!(parse_rotation_interval("never").unwrap(), 0);
+        assert!(parse_rotation_interval("fortnightly").is_err());
+    }
+
     #[test]
     fn test_validate_query_params_zero_limit() {
         let result = validate_query_params(0, None, None);
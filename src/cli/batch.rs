@@ -1,6 +1,6 @@
 use crate::{
     backends::{Backend, BackendType, InferenceParams},
-    batch::{BatchConfig, BatchOutputFormat, BatchProcessor},
+    batch::{BatchConfig, BatchOutputFormat, BatchProcessor, template::PromptTemplate},
     config::Config,
     metrics::MetricsCollector,
     models::ModelManager,
@@ -70,6 +70,41 @@ pub struct BatchArgs {
     #[arg(long, help = "Backend to use", value_enum)]
     pub backend: Option<BackendType>,
 
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Post-process each output: none, trim, json or regex:<pattern>"
+    )]
+    pub post_process: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "COLUMN",
+        help = "CSV/TSV column or JSON field holding the prompt (default: content, text, input or prompt, else the first column)"
+    )]
+    pub input_column: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "COLUMN",
+        help = "CSV/TSV column or JSON field holding each input's id (default: id)"
+    )]
+    pub id_column: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        help = "Render each input into this prompt, e.g. 'Answer concisely: {input}'; {metadata.<field>} inserts another field of the input"
+    )]
+    pub prompt_template: Option<String>,
+
+    #[arg(
+        long,
+        requires = "prompt_template",
+        help = "Render fields an input lacks as empty instead of failing it"
+    )]
+    pub lenient_template: bool,
+
     #[arg(short, long, help = "Verbose output")]
     pub verbose: bool,
 }
@@ -126,21 +161,11 @@ fn validate_parameters(args: &BatchArgs) -> Result<()> {
     }
 
     // Validate parameter ranges
-    if args.max_tokens == 0 {
-        anyhow::bail!("Max tokens must be greater than 0");
-    }
-
     if args.max_tokens > 32768 {
         anyhow::bail!("Max tokens cannot exceed 32768");
     }
 
-    if args.temperature < 0.0 || args.temperature > 2.0 {
-        anyhow::bail!("Temperature must be between 0.0 and 2.0");
-    }
-
-    if args.top_p < 0.0 || args.top_p > 1.0 {
-        anyhow::bail!("Top-p must be between 0.0 and 1.0");
-    }
+    inference_params(args).validate()?;
 
     if args.concurrency == 0 {
         anyhow::bail!("Concurrency must be at least 1");
@@ -161,6 +186,26 @@ fn validate_parameters(args: &BatchArgs) -> Result<()> {
     Ok(())
 }
 
+/// Generation parameters for every item of the batch
+fn inference_params(args: &BatchArgs) -> InferenceParams {
+    InferenceParams {
+        max_tokens: args.max_tokens,
+        temperature: args.temperature,
+        top_k: args.top_k,
+        repeat_penalty: None,
+        repeat_last_n: None,
+        min_p: None,
+        top_p: args.top_p,
+        stream: false, // Batch processing uses non-streaming
+        stop_sequences: vec![],
+        stop_regex: None,
+        max_generation_ms: None,
+        seed: None,
+        response_format: None,
+        logprobs: None,
+    }
+}
+
 pub async fn execute(args: BatchArgs, config: &Config) -> Result<()> {
     info!("Starting batch processing with model: {}", args.model);
 
@@ -189,10 +234,15 @@ pub async fn execute(args: BatchArgs, config: &Config) -> Result<()> {
         output_format: args.output_format.clone().into(),
         continue_on_error: args.continue_on_error,
         shuffle_inputs: args.shuffle,
+        post_process: args.post_process.clone(),
+        input_column: args.input_column.clone(),
+        id_column: args.id_column.clone(),
+        prompt_template: args.prompt_template.clone(),
+        lenient_template: args.lenient_template,
     };
 
     // Load and validate model
-    let model_manager = ModelManager::new(&config.models_dir);
+    let model_manager = ModelManager::from_config(config);
     let model_info = model_manager.resolve_model(&args.model).await?;
 
     info!("Validating model: {}", model_info.name);
@@ -235,18 +285,12 @@ pub async fn execute(args: BatchArgs, config: &Config) -> Result<()> {
             load_duration,
             backend_type.to_string(),
         );
+        if let Some(device) = backend.device() {
+            metrics.record_model_device(&model_info.name, device);
+        }
     }
 
-    // Create inference parameters
-    let inference_params = InferenceParams {
-        max_tokens: args.max_tokens,
-        temperature: args.temperature,
-        top_k: args.top_k,
-        top_p: args.top_p,
-        stream: false, // Batch processing uses non-streaming
-        stop_sequences: vec![],
-        seed: None,
-    };
+    let inference_params = inference_params(&args);
 
     // Estimate total items for progress tracking
     let total_items = estimate_batch_size(&args.input).await?;
@@ -283,7 +327,16 @@ pub async fn execute(args: BatchArgs, config: &Config) -> Result<()> {
 async fn validate_batch_inputs(args: &BatchArgs) -> Result<()> {
     info!("Validating batch inputs (dry run mode)");
 
-    let batch_config = BatchConfig::default();
+    let batch_config = BatchConfig {
+        input_column: args.input_column.clone(),
+        id_column: args.id_column.clone(),
+        ..BatchConfig::default()
+    };
+    let template = args
+        .prompt_template
+        .as_deref()
+        .map(|template| PromptTemplate::parse(template, args.lenient_template))
+        .transpose()?;
     let processor = BatchProcessor::new(batch_config, 0);
 
     match processor.load_inputs(&args.input).await {
@@ -309,6 +362,24 @@ async fn validate_batch_inputs(args: &BatchArgs) -> Result<()> {
                 }
             }
 
+            if let Some(template) = &template {
+                let unrenderable: Vec<_> = inputs
+                    .iter()
+                    .filter_map(|input| template.render(input).err())
+                    .collect();
+                if unrenderable.is_empty() {
+                    info!("✓ Prompt template renders for every input");
+                } else {
+                    for e in unrenderable.iter().take(3) {
+                        warn!("{}", e);
+                    }
+                    warn!(
+                        "{} inputs would fail the prompt template; pass --lenient-template to render missing fields as empty",
+                        unrenderable.len()
+                    );
+                }
+            }
+
             // Validate output path
             if let Some(output_path) = &args.output {
                 if let Some(parent) = output_path.parent() {
@@ -385,6 +456,10 @@ fn print_batch_summary(progress: &crate::batch::BatchProgress, args: &BatchArgs)
     }
 
     println!("Average rate: {:.2} items/second", progress.current_rate);
+    println!(
+        "Phase timing: load {:.2?}, inference {:.2?}, save {:.2?}",
+        progress.timing.load, progress.timing.inference, progress.timing.save
+    );
 
     if let Some(output) = &args.output {
         println!("Output saved to: {}", output.display());
@@ -429,6 +504,11 @@ mod tests {
             resume: None,
             dry_run: false,
             backend: None,
+            post_process: None,
+            input_column: None,
+            id_column: None,
+            prompt_template: None,
+            lenient_template: false,
             verbose: false,
         }
     }
@@ -463,7 +543,7 @@ mod tests {
             result
                 .unwrap_err()
                 .to_string()
-                .contains("Max tokens must be greater than 0")
+                .contains("max_tokens must be between 1 and")
         );
     }
 
@@ -512,7 +592,7 @@ mod tests {
             result
                 .unwrap_err()
                 .to_string()
-                .contains("Temperature must be between 0.0 and 2.0")
+                .contains("temperature must be between 0 and 2")
         );
     }
 
@@ -529,7 +609,7 @@ mod tests {
             result
                 .unwrap_err()
                 .to_string()
-                .contains("Temperature must be between 0.0 and 2.0")
+                .contains("temperature must be between 0 and 2")
         );
     }
 
@@ -561,7 +641,7 @@ mod tests {
             result
                 .unwrap_err()
                 .to_string()
-                .contains("Top-p must be between 0.0 and 1.0")
+                .contains("top_p must be between 0 and 1")
         );
     }
 
@@ -578,7 +658,7 @@ mod tests {
             result
                 .unwrap_err()
                 .to_string()
-                .contains("Top-p must be between 0.0 and 1.0")
+                .contains("top_p must be between 0 and 1")
         );
     }
 
@@ -723,6 +803,11 @@ mod tests {
             resume: None,
             dry_run: false,
             backend: None,
+            post_process: None,
+            input_column: None,
+            id_column: None,
+            prompt_template: None,
+            lenient_template: false,
             verbose: false,
         };
 
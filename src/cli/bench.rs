@@ -1,17 +1,45 @@
-use crate::backends::{Backend, BackendType, InferenceParams};
+use crate::backends::{Backend, BackendType, InferenceParams, memory};
 use crate::config::Config;
-use crate::models::ModelManager;
+use crate::infrastructure::profiling::DurationStats;
+use crate::models::{ModelInfo, ModelManager};
 use anyhow::Result;
-use clap::Args;
+use clap::{Args, ValueEnum};
+use futures::StreamExt;
 use std::path::PathBuf;
-use std::time::{Duration, Instant};
-use tracing::info;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{info, warn};
 
 #[derive(Args)]
 pub struct BenchArgs {
-    #[arg(short, long, help = "Model file path or name")]
+    #[arg(
+        short,
+        long,
+        help = "Model file path or name",
+        default_value = "",
+        hide_default_value = true
+    )]
     pub model: String,
 
+    #[arg(
+        long,
+        value_name = "MODELS",
+        num_args = 0..,
+        value_delimiter = ',',
+        help = "Benchmark these models one after another (all models when none are given) and compare them"
+    )]
+    pub model_sweep: Option<Vec<String>>,
+
+    #[arg(long, help = "In a model sweep, stop at the first model that fails")]
+    pub fail_fast: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "fail_fast",
+        help = "In a model sweep, benchmark every model and report all failures at the end (default)"
+    )]
+    pub keep_going: bool,
+
     #[arg(short, long, help = "Number of iterations", default_value = "10")]
     pub iterations: u32,
 
@@ -36,12 +64,27 @@ pub struct BenchArgs {
         help = "Write results to JSON file for comparison tracking"
     )]
     pub output_json: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Output format; json prints only the results",
+        value_enum,
+        default_value = "text"
+    )]
+    pub format: BenchFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BenchFormat {
+    Text,
+    Json,
 }
 
 #[derive(serde::Serialize)]
 struct BenchmarkJsonResult {
     model: String,
     backend: String,
+    warmup_iterations: u32,
     iterations: u32,
     max_tokens: u32,
     throughput_tokens_per_sec: f64,
@@ -49,6 +92,9 @@ struct BenchmarkJsonResult {
     min_latency_ms: f64,
     max_latency_ms: f64,
     median_latency_ms: f64,
+    latency_ms: DurationStats,
+    first_token_latency_ms: DurationStats,
+    tokens_per_sec: DurationStats,
     total_tokens: u32,
     load_time_ms: u64,
     memory_used_gb: Option<f64>,
@@ -58,13 +104,103 @@ struct BenchmarkJsonResult {
     timestamp: String,
 }
 
+/// One model's line in the `--model-sweep` comparison
+#[derive(Debug, Clone, serde::Serialize)]
+struct SweepRow {
+    model: String,
+    backend: String,
+    throughput_tokens_per_sec: f64,
+    median_latency_ms: f64,
+    median_first_token_ms: f64,
+    load_time_ms: u64,
+    /// Growth of this process's resident memory over the model's load and run
+    rss_delta_gb: Option<f64>,
+}
+
+/// A model left out of the `--model-sweep` comparison
+#[derive(Debug, Clone)]
+struct SweepFailure {
+    model: String,
+    error: String,
+}
+
+/// Columns of the comparison table, in order
+const SWEEP_COLUMNS: [&str; 7] = [
+    "Model",
+    "Backend",
+    "Tokens/sec",
+    "p50 latency (ms)",
+    "p50 first token (ms)",
+    "Load (ms)",
+    "RSS delta (GB)",
+];
+
+/// Timings of one iteration
+struct Sample {
+    latency: Duration,
+    first_token: Duration,
+    tokens: u32,
+}
+
+impl Sample {
+    fn tokens_per_sec(&self) -> f64 {
+        self.tokens as f64 / self.latency.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Statistics over the measured iterations
+#[derive(Debug, Clone)]
+struct BenchStats {
+    total_time_ms: f64,
+    total_tokens: u32,
+    throughput_tokens_per_sec: f64,
+    latency_ms: DurationStats,
+    first_token_latency_ms: DurationStats,
+    tokens_per_sec: DurationStats,
+}
+
+impl BenchStats {
+    fn from_samples(samples: &[Sample], total_time: Duration) -> Self {
+        let millis = |duration: Duration| duration.as_secs_f32() * 1000.0;
+        let total_tokens = samples.iter().map(|sample| sample.tokens).sum();
+        Self {
+            total_time_ms: total_time.as_secs_f64() * 1000.0,
+            total_tokens,
+            throughput_tokens_per_sec: total_tokens as f64
+                / total_time.as_secs_f64().max(f64::EPSILON),
+            latency_ms: DurationStats::from_durations(
+                &samples
+                    .iter()
+                    .map(|s| millis(s.latency))
+                    .collect::<Vec<_>>(),
+            ),
+            first_token_latency_ms: DurationStats::from_durations(
+                &samples
+                    .iter()
+                    .map(|s| millis(s.first_token))
+                    .collect::<Vec<_>>(),
+            ),
+            tokens_per_sec: DurationStats::from_durations(
+                &samples
+                    .iter()
+                    .map(|s| s.tokens_per_sec() as f32)
+                    .collect::<Vec<_>>(),
+            ),
+        }
+    }
+}
+
 pub async fn execute(args: BenchArgs, config: &Config) -> Result<()> {
     // Pre-execution validation
     validate_args(&args)?;
 
+    if let Some(models) = &args.model_sweep {
+        return execute_sweep(models, &args, config).await;
+    }
+
     info!("Starting benchmark for model: {}", args.model);
 
-    let model_manager = ModelManager::new(&config.models_dir);
+    let model_manager = ModelManager::from_config(config);
     let model_info = model_manager.resolve_model(&args.model).await?;
 
     let backend_type = args
@@ -78,166 +214,417 @@ pub async fn execute(args: BenchArgs, config: &Config) -> Result<()> {
         })?;
 
     let mut backend = Backend::new(backend_type, &config.backend_config)?;
+    let text = args.format == BenchFormat::Text;
 
-    println!("Loading model: {}", model_info.name);
+    if text {
+        println!("Loading model: {}", model_info.name);
+    }
     let load_start = Instant::now();
     backend.load_model(&model_info).await?;
     let load_time = load_start.elapsed();
 
-    println!("Model loaded in: {:?}", load_time);
-    println!();
+    if text {
+        println!("Model loaded in: {:?}", load_time);
+        println!();
+    }
 
-    let prompt = args
-        .prompt
-        .unwrap_or_else(|| "The quick brown fox jumps over the lazy dog.".to_string());
+    let prompt = bench_prompt(&args);
+    let inference_params = bench_params(&args);
 
-    let inference_params = InferenceParams {
+    if text {
+        println!("Benchmark Configuration:");
+        println!("  Model: {}", model_info.name);
+        println!("  Backend: {}", backend_type);
+        println!("  Iterations: {}", args.iterations);
+        println!("  Warmup: {}", args.warmup);
+        println!("  Max tokens: {}", args.tokens);
+        println!(
+            "  Prompt: {}",
+            if prompt.len() > 50 {
+                format!("{}...", &prompt[..50])
+            } else {
+                prompt.clone()
+            }
+        );
+        println!();
+    }
+
+    let stats = run_benchmark(&mut backend, &prompt, &inference_params, &args).await?;
+
+    let memory_used_gb = get_memory_info().ok().map(|m| m.used_gb);
+    let hw = get_hardware_info();
+    let result = BenchmarkJsonResult {
+        model: model_info.name.clone(),
+        backend: backend_type.to_string(),
+        warmup_iterations: args.warmup,
+        iterations: args.iterations,
+        max_tokens: args.tokens,
+        throughput_tokens_per_sec: stats.throughput_tokens_per_sec,
+        mean_latency_ms: stats.latency_ms.mean as f64,
+        min_latency_ms: stats.latency_ms.min as f64,
+        max_latency_ms: stats.latency_ms.max as f64,
+        median_latency_ms: stats.latency_ms.p50 as f64,
+        latency_ms: stats.latency_ms.clone(),
+        first_token_latency_ms: stats.first_token_latency_ms.clone(),
+        tokens_per_sec: stats.tokens_per_sec.clone(),
+        total_tokens: stats.total_tokens,
+        load_time_ms: load_time.as_millis() as u64,
+        memory_used_gb,
+        total_memory_gb: hw.total_memory_gb,
+        hostname: hw.hostname,
+        os_version: hw.os_version,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+    };
+
+    match args.format {
+        BenchFormat::Text => print_results(&stats, memory_used_gb),
+        BenchFormat::Json => println!("{}", serde_json::to_string_pretty(&result)?),
+    }
+
+    // Write JSON results if requested
+    if let Some(json_path) = &args.output_json {
+        let json = serde_json::to_string_pretty(&result)?;
+        std::fs::write(json_path, json)?;
+        if text {
+            println!("\nResults written to {}", json_path.display());
+        }
+    }
+
+    Ok(())
+}
+
+fn bench_prompt(args: &BenchArgs) -> String {
+    args.prompt
+        .clone()
+        .unwrap_or_else(|| "The quick brown fox jumps over the lazy dog.".to_string())
+}
+
+fn bench_params(args: &BenchArgs) -> InferenceParams {
+    InferenceParams {
         max_tokens: args.tokens,
         temperature: 0.7,
         top_k: 40,
+        repeat_penalty: None,
+        repeat_last_n: None,
+        min_p: None,
         top_p: 0.9,
-        stream: false,
+        stream: true,
         stop_sequences: vec![],
+        stop_regex: None,
+        max_generation_ms: None,
         seed: None,
+        response_format: None,
+        logprobs: None,
+    }
+}
+
+/// Benchmark `models`, or every model in the models directory when it is empty,
+/// and print a comparison
+async fn execute_sweep(models: &[String], args: &BenchArgs, config: &Config) -> Result<()> {
+    let model_manager = ModelManager::from_config(config);
+    let model_infos = if models.is_empty() {
+        model_manager.list_models().await?
+    } else {
+        let mut infos = Vec::with_capacity(models.len());
+        for model in models {
+            infos.push(model_manager.resolve_model(model).await?);
+        }
+        infos
     };
+    if model_infos.is_empty() {
+        anyhow::bail!("No models to benchmark in {}", config.models_dir.display());
+    }
+    info!("Starting benchmark sweep over {} models", model_infos.len());
 
-    println!("Benchmark Configuration:");
-    println!("  Model: {}", model_info.name);
-    println!("  Backend: {}", backend_type);
-    println!("  Iterations: {}", args.iterations);
-    println!("  Warmup: {}", args.warmup);
-    println!("  Max tokens: {}", args.tokens);
-    println!(
-        "  Prompt: {}",
-        if prompt.len() > 50 {
-            format!("{}...", &prompt[..50])
-        } else {
-            prompt.clone()
+    let (rows, failures) = run_sweep(&model_infos, args, |model_info| {
+        let backend_type = args
+            .backend
+            .or_else(|| BackendType::from_model_path(&model_info.path))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No suitable backend found for model: {}",
+                    model_info.path.display()
+                )
+            })?;
+        Backend::new(backend_type, &config.backend_config)
+    })
+    .await;
+
+    match args.format {
+        BenchFormat::Text => print!("\n{}", comparison_table(&rows)),
+        BenchFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+    }
+
+    if let Some(json_path) = &args.output_json {
+        std::fs::write(json_path, serde_json::to_string_pretty(&rows)?)?;
+        if args.format == BenchFormat::Text {
+            println!("\nResults written to {}", json_path.display());
         }
-    );
-    println!();
+    }
 
-    // Warmup
-    if args.warmup > 0 {
-        println!("Warming up ({} iterations)...", args.warmup);
-        for i in 1..=args.warmup {
-            let start = Instant::now();
-            let _ = backend.infer(&prompt, &inference_params).await?;
-            let duration = start.elapsed();
-            if args.verbose {
-                println!("  Warmup {}: {:?}", i, duration);
+    if !failures.is_empty() {
+        let failed: Vec<String> = failures
+            .iter()
+            .map(|failure| format!("{}: {}", failure.model, failure.error))
+            .collect();
+        anyhow::bail!(
+            "{} of {} models failed to benchmark{}:\n  {}",
+            failures.len(),
+            model_infos.len(),
+            if args.fail_fast {
+                " (stopped at the first failure)"
+            } else {
+                ""
+            },
+            failed.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// Load and benchmark each model in turn with a backend from `new_backend`,
+/// unloading it before the next so their memory use does not add up
+///
+/// A model that fails to load or run is reported and left out of the rows;
+/// with `--fail-fast` no further models are benchmarked after it. Rows are
+/// ordered fastest first.
+async fn run_sweep<F>(
+    models: &[ModelInfo],
+    args: &BenchArgs,
+    mut new_backend: F,
+) -> (Vec<SweepRow>, Vec<SweepFailure>)
+where
+    F: FnMut(&ModelInfo) -> Result<Backend>,
+{
+    let text = args.format == BenchFormat::Text;
+    let prompt = bench_prompt(args);
+    let params = bench_params(args);
+    let mut rows = Vec::with_capacity(models.len());
+    let mut failures = Vec::new();
+
+    for (i, model_info) in models.iter().enumerate() {
+        if text {
+            println!(
+                "[{}/{}] Benchmarking {}",
+                i + 1,
+                models.len(),
+                model_info.name
+            );
+        }
+        let result = async {
+            let mut backend = new_backend(model_info)?;
+            let rss_before = resident_bytes();
+            let load_start = Instant::now();
+            backend.load_model(model_info).await?;
+            let load_time = load_start.elapsed();
+            let stats = run_benchmark(&mut backend, &prompt, &params, args).await?;
+            let rss_delta_gb = rss_before
+                .zip(resident_bytes())
+                .map(|(before, after)| after.saturating_sub(before) as f64 / BYTES_PER_GB);
+            backend.unload_model().await?;
+            Ok::<_, anyhow::Error>(SweepRow {
+                model: model_info.name.clone(),
+                backend: backend.get_backend_type().to_string(),
+                throughput_tokens_per_sec: stats.throughput_tokens_per_sec,
+                median_latency_ms: stats.latency_ms.p50 as f64,
+                median_first_token_ms: stats.first_token_latency_ms.p50 as f64,
+                load_time_ms: load_time.as_millis() as u64,
+                rss_delta_gb,
+            })
+        }
+        .await;
+
+        match result {
+            Ok(row) => rows.push(row),
+            Err(e) => {
+                warn!("Skipping {} in benchmark sweep: {}", model_info.name, e);
+                if text {
+                    println!("  Failed: {}\n", e);
+                }
+                failures.push(SweepFailure {
+                    model: model_info.name.clone(),
+                    error: format!("{:#}", e),
+                });
+                if args.fail_fast {
+                    break;
+                }
             }
         }
-        println!("Warmup completed.\n");
     }
 
-    // Benchmark
-    println!("Running benchmark...");
-    let mut durations = Vec::new();
-    let mut total_tokens = 0u32;
+    rows.sort_by(|a, b| {
+        b.throughput_tokens_per_sec
+            .total_cmp(&a.throughput_tokens_per_sec)
+    });
+    (rows, failures)
+}
 
-    let bench_start = Instant::now();
+const BYTES_PER_GB: f64 = 1024.0 * 1024.0 * 1024.0;
 
-    for i in 1..=args.iterations {
-        let start = Instant::now();
-        let result = backend.infer(&prompt, &inference_params).await?;
-        let duration = start.elapsed();
+/// Resident memory of this process, if it can be read
+fn resident_bytes() -> Option<u64> {
+    Some(memory::resident_bytes()).filter(|&bytes| bytes > 0)
+}
 
-        let token_count = estimate_token_count(&result);
-        total_tokens += token_count;
-        durations.push(duration);
+/// Render sweep rows as an aligned table under [`SWEEP_COLUMNS`]
+fn comparison_table(rows: &[SweepRow]) -> String {
+    let cells: Vec<[String; 7]> = rows
+        .iter()
+        .map(|row| {
+            [
+                row.model.clone(),
+                row.backend.clone(),
+                format!("{:.1}", row.throughput_tokens_per_sec),
+                format!("{:.1}", row.median_latency_ms),
+                format!("{:.1}", row.median_first_token_ms),
+                row.load_time_ms.to_string(),
+                row.rss_delta_gb
+                    .map_or_else(|| "-".to_string(), |gb| format!("{:.1}", gb)),
+            ]
+        })
+        .collect();
+
+    let mut widths = SWEEP_COLUMNS.map(str::len);
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut table = table_line(SWEEP_COLUMNS.iter(), &widths);
+    for row in &cells {
+        table.push_str(&table_line(row.iter(), &widths));
+    }
+    table
+}
+
+/// One table line, names left-aligned and figures right-aligned
+fn table_line(cells: impl Iterator<Item = impl AsRef<str>>, widths: &[usize]) -> String {
+    let cells: Vec<String> = cells
+        .zip(widths)
+        .enumerate()
+        .map(|(column, (cell, &width))| {
+            if column < 2 {
+                format!("{:<width$}", cell.as_ref())
+            } else {
+                format!("{:>width$}", cell.as_ref())
+            }
+        })
+        .collect();
+    format!("{}\n", cells.join("  ").trim_end())
+}
 
-        if args.verbose {
+/// Run the warmup iterations, which are left out of the statistics, then the
+/// measured ones
+async fn run_benchmark(
+    backend: &mut Backend,
+    prompt: &str,
+    params: &InferenceParams,
+    args: &BenchArgs,
+) -> Result<BenchStats> {
+    let text = args.format == BenchFormat::Text;
+    let verbose = text && args.verbose;
+
+    if args.warmup > 0 {
+        if text {
+            println!("Warming up ({} iterations)...", args.warmup);
+        }
+        for i in 1..=args.warmup {
+            let sample = measure_iteration(backend, prompt, params).await?;
+            if verbose {
+                println!("  Warmup {}: {:?}", i, sample.latency);
+            }
+        }
+        if text {
+            println!("Warmup completed.\n");
+        }
+    }
+
+    if text {
+        println!("Running benchmark...");
+    }
+    let mut samples = Vec::with_capacity(args.iterations as usize);
+    let bench_start = Instant::now();
+    for i in 1..=args.iterations {
+        let sample = measure_iteration(backend, prompt, params).await?;
+        if verbose {
             println!(
-                "  Iteration {}: {:?} ({} tokens, {:.1} tok/s)",
+                "  Iteration {}: {:?} (first token {:?}, {} tokens, {:.1} tok/s)",
                 i,
-                duration,
-                token_count,
-                token_count as f64 / duration.as_secs_f64()
+                sample.latency,
+                sample.first_token,
+                sample.tokens,
+                sample.tokens_per_sec()
             );
         }
+        samples.push(sample);
     }
 
-    let total_time = bench_start.elapsed();
-
-    // Statistics
-    durations.sort();
-    let min = durations[0];
-    let max = durations[durations.len() - 1];
-    let median = durations[durations.len() / 2];
-    let mean = calculate_mean(&durations);
+    Ok(BenchStats::from_samples(&samples, bench_start.elapsed()))
+}
 
-    let total_tokens_per_sec = total_tokens as f64 / total_time.as_secs_f64();
-    let mean_tokens_per_sec = args.tokens as f64 / mean.as_secs_f64();
+/// Stream one completion, timing the first token and the whole response
+async fn measure_iteration(
+    backend: &mut Backend,
+    prompt: &str,
+    params: &InferenceParams,
+) -> Result<Sample> {
+    let start = Instant::now();
+    let mut stream = backend.infer_stream(prompt, params).await?;
+    let mut first_token = None;
+    let mut tokens = 0u32;
+    while let Some(token) = stream.next().await {
+        token?;
+        first_token.get_or_insert_with(|| start.elapsed());
+        tokens += 1;
+    }
+    let latency = start.elapsed();
+    Ok(Sample {
+        latency,
+        first_token: first_token.unwrap_or(latency),
+        tokens,
+    })
+}
 
+fn print_results(stats: &BenchStats, memory_used_gb: Option<f64>) {
     println!("\nBenchmark Results:");
     println!("==================");
-    println!("Total time: {:?}", total_time);
-    println!("Total tokens: {}", total_tokens);
-    println!("Throughput: {:.1} tokens/sec", total_tokens_per_sec);
-    println!();
-    println!("Per-iteration statistics:");
+    println!("Total time: {:.1} ms", stats.total_time_ms);
+    println!("Total tokens: {}", stats.total_tokens);
     println!(
-        "  Min:    {:?} ({:.1} tok/s)",
-        min,
-        args.tokens as f64 / min.as_secs_f64()
+        "Throughput: {:.1} tokens/sec",
+        stats.throughput_tokens_per_sec
     );
+    println!();
     println!(
-        "  Max:    {:?} ({:.1} tok/s)",
-        max,
-        args.tokens as f64 / max.as_secs_f64()
-    );
-    println!("  Mean:   {:?} ({:.1} tok/s)", mean, mean_tokens_per_sec);
-    println!(
-        "  Median: {:?} ({:.1} tok/s)",
-        median,
-        args.tokens as f64 / median.as_secs_f64()
+        "{:<20} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+        "Per-iteration", "mean", "p50", "p90", "p95", "p99", "max"
     );
+    for (label, row) in [
+        ("Latency (ms)", &stats.latency_ms),
+        ("First token (ms)", &stats.first_token_latency_ms),
+        ("Tokens/sec", &stats.tokens_per_sec),
+    ] {
+        println!(
+            "{:<20} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>9.1}",
+            label, row.mean, row.p50, row.p90, row.p95, row.p99, row.max
+        );
+    }
     println!();
 
     // Performance classification
-    let performance_rating = classify_performance(mean_tokens_per_sec);
+    let performance_rating = classify_performance(stats.tokens_per_sec.p50 as f64);
     println!("Performance: {}", performance_rating);
 
-    // Memory usage estimation
-    let memory_used_gb = get_memory_info().ok().map(|m| m.used_gb);
     if let Some(gb) = memory_used_gb {
         println!("Estimated memory usage: {:.1} GB", gb);
     }
-
-    // Write JSON results if requested
-    if let Some(json_path) = &args.output_json {
-        let hw = get_hardware_info();
-        let result = BenchmarkJsonResult {
-            model: model_info.name.clone(),
-            backend: backend_type.to_string(),
-            iterations: args.iterations,
-            max_tokens: args.tokens,
-            throughput_tokens_per_sec: total_tokens_per_sec,
-            mean_latency_ms: mean.as_secs_f64() * 1000.0,
-            min_latency_ms: min.as_secs_f64() * 1000.0,
-            max_latency_ms: max.as_secs_f64() * 1000.0,
-            median_latency_ms: median.as_secs_f64() * 1000.0,
-            total_tokens,
-            load_time_ms: load_time.as_millis() as u64,
-            memory_used_gb,
-            total_memory_gb: hw.total_memory_gb,
-            hostname: hw.hostname,
-            os_version: hw.os_version,
-            timestamp: chrono::Utc::now().to_rfc3339(),
-        };
-        let json = serde_json::to_string_pretty(&result)?;
-        std::fs::write(json_path, json)?;
-        println!("\nResults written to {}", json_path.display());
-    }
-
-    Ok(())
 }
 
 /// Validate benchmark arguments before execution
 fn validate_args(args: &BenchArgs) -> Result<()> {
-    // Validate model name
-    if args.model.is_empty() {
+    // Validate model name; a sweep names its models itself
+    if args.model.is_empty() && args.model_sweep.is_none() {
         anyhow::bail!("Model name cannot be empty");
     }
 
@@ -276,16 +663,6 @@ fn validate_args(args: &BenchArgs) -> Result<()> {
     Ok(())
 }
 
-fn estimate_token_count(text: &str) -> u32 {
-    // Rough estimation: ~4 characters per token for English text
-    (text.len() as f32 / 4.0).ceil() as u32
-}
-
-fn calculate_mean(durations: &[Duration]) -> Duration {
-    let total_nanos: u128 = durations.iter().map(|d| d.as_nanos()).sum();
-    Duration::from_nanos((total_nanos / durations.len() as u128) as u64)
-}
-
 fn classify_performance(tokens_per_sec: f64) -> String {
     if tokens_per_sec > 100.0 {
         "Excellent (>100 tok/s)".to_string()
@@ -335,23 +712,175 @@ fn get_hardware_info() -> HardwareInfo {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backends::mock::MockBackend;
+    use std::collections::HashMap;
 
-    #[test]
-    fn test_calculate_mean() {
-        let durations = vec![
-            Duration::from_millis(100),
-            Duration::from_millis(200),
-            Duration::from_millis(300),
-        ];
-        let mean = calculate_mean(&durations);
-        assert_eq!(mean.as_millis(), 200);
+    /// Streams two tokens per call; after two slow warmup calls, measured call `k`
+    /// sends its first token after `5k` ms and finishes after `10k` ms
+    fn delay_backend() -> Backend {
+        MockBackend::tokens(&["a", "b"])
+            .token_delay(|call| match call.checked_sub(2) {
+                Some(k) if k > 0 => Duration::from_millis(5 * k as u64),
+                _ => Duration::from_secs(1),
+            })
+            .backend()
     }
 
-    #[test]
-    fn test_estimate_token_count() {
-        let text = "Hello, world!"; // 13 characters
-        let count = estimate_token_count(text);
-        assert_eq!(count, 4); // 13 / 4 = 3.25, ceil = 4
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 0.01,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_percentiles_exclude_warmup() {
+        let args = BenchArgs {
+            model: "test-model".to_string(),
+            iterations: 20,
+            prompt: None,
+            tokens: 2,
+            warmup: 2,
+            backend: None,
+            model_sweep: None,
+            verbose: false,
+            output_json: None,
+            fail_fast: false,
+            keep_going: false,
+            format: BenchFormat::Json,
+        };
+        let mut backend = delay_backend();
+        let params = InferenceParams {
+            stream: true,
+            ..Default::default()
+        };
+        let stats = run_benchmark(&mut backend, "prompt", &params, &args)
+            .await
+            .unwrap();
+
+        // Latencies are 10, 20, ..., 200 ms; the 1s warmup calls are not among them
+        let latency = &stats.latency_ms;
+        assert_close(latency.min, 10.0);
+        assert_close(latency.p50, 105.0);
+        assert_close(latency.p90, 190.0);
+        assert_close(latency.p95, 200.0);
+        assert_close(latency.p99, 200.0);
+        assert_close(latency.max, 200.0);
+
+        let first_token = &stats.first_token_latency_ms;
+        assert_close(first_token.min, 5.0);
+        assert_close(first_token.p50, 52.5);
+        assert_close(first_token.p90, 95.0);
+
+        // Two tokens in 10 ms is 200 tok/s, two in 200 ms is 10 tok/s
+        assert_close(stats.tokens_per_sec.max, 200.0);
+        assert_close(stats.tokens_per_sec.min, 10.0);
+        assert_eq!(stats.total_tokens, 40);
+        assert!((stats.total_time_ms - 2100.0).abs() < 0.01);
+    }
+
+    fn model(name: &str) -> ModelInfo {
+        ModelInfo {
+            name: name.to_string(),
+            path: name.into(),
+            file_path: name.into(),
+            size: 0,
+            size_bytes: 0,
+            modified: chrono::Utc::now(),
+            backend_type: "gguf".to_string(),
+            format: "gguf".to_string(),
+            checksum: None,
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_model_sweep_compares_each_model() {
+        let args = BenchArgs {
+            model: String::new(),
+            model_sweep: Some(vec![]),
+            iterations: 2,
+            prompt: None,
+            tokens: 2,
+            warmup: 0,
+            backend: None,
+            verbose: false,
+            output_json: None,
+            fail_fast: false,
+            keep_going: false,
+            format: BenchFormat::Json,
+        };
+        validate_args(&args).unwrap();
+
+        let models = [model("small.gguf"), model("large.gguf")];
+        let (rows, failures) = run_sweep(&models, &args, |_| Ok(delay_backend())).await;
+
+        assert!(failures.is_empty());
+        assert_eq!(rows.len(), 2);
+        let mut names: Vec<&str> = rows.iter().map(|row| row.model.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["large.gguf", "small.gguf"]);
+        for row in &rows {
+            // Two tokens, one second apart
+            assert!((row.throughput_tokens_per_sec - 1.0).abs() < 0.01);
+            assert!((row.median_latency_ms - 2000.0).abs() < 0.1);
+        }
+
+        let table = comparison_table(&rows);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        // Every column, in order
+        let positions: Vec<usize> = SWEEP_COLUMNS
+            .iter()
+            .map(|column| lines[0].find(column).expect(column))
+            .collect();
+        assert!(positions.is_sorted());
+        assert!(lines[1..].iter().all(|line| line.contains(".gguf")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_model_sweep_fail_fast_and_keep_going() {
+        let mut args = BenchArgs {
+            model: String::new(),
+            model_sweep: Some(vec![]),
+            iterations: 1,
+            prompt: None,
+            tokens: 2,
+            warmup: 0,
+            backend: None,
+            verbose: false,
+            output_json: None,
+            fail_fast: false,
+            keep_going: false,
+            format: BenchFormat::Json,
+        };
+        let models = [
+            model("first.gguf"),
+            model("broken.gguf"),
+            model("missing.gguf"),
+            model("last.gguf"),
+        ];
+        let new_backend = |model_info: &ModelInfo| {
+            if model_info.name == "first.gguf" || model_info.name == "last.gguf" {
+                Ok(delay_backend())
+            } else {
+                Err(anyhow::anyhow!("cannot load {}", model_info.name))
+            }
+        };
+
+        // Keep going by default: every model is tried and all failures reported
+        let (rows, failures) = run_sweep(&models, &args, new_backend).await;
+        assert_eq!(rows.len(), 2);
+        let failed: Vec<&str> = failures.iter().map(|f| f.model.as_str()).collect();
+        assert_eq!(failed, ["broken.gguf", "missing.gguf"]);
+        assert!(failures[1].error.contains("cannot load missing.gguf"));
+
+        args.fail_fast = true;
+        let (rows, failures) = run_sweep(&models, &args, new_backend).await;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].model, "first.gguf");
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].model, "broken.gguf");
     }
 
     #[test]
@@ -371,8 +900,12 @@ mod tests {
             tokens: 100,
             warmup: 3,
             backend: None,
+            model_sweep: None,
             verbose: false,
             output_json: None,
+            fail_fast: false,
+            keep_going: false,
+            format: BenchFormat::Text,
         };
         let result = validate_args(&args);
         assert!(result.is_err());
@@ -393,8 +926,12 @@ mod tests {
             tokens: 100,
             warmup: 3,
             backend: None,
+            model_sweep: None,
             verbose: false,
             output_json: None,
+            fail_fast: false,
+            keep_going: false,
+            format: BenchFormat::Text,
         };
         let result = validate_args(&args);
         assert!(result.is_err());
@@ -415,8 +952,12 @@ mod tests {
             tokens: 100,
             warmup: 3,
             backend: None,
+            model_sweep: None,
             verbose: false,
             output_json: None,
+            fail_fast: false,
+            keep_going: false,
+            format: BenchFormat::Text,
         };
         let result = validate_args(&args);
         assert!(result.is_err());
@@ -432,8 +973,12 @@ mod tests {
             tokens: 0,
             warmup: 3,
             backend: None,
+            model_sweep: None,
             verbose: false,
             output_json: None,
+            fail_fast: false,
+            keep_going: false,
+            format: BenchFormat::Text,
         };
         let result = validate_args(&args);
         assert!(result.is_err());
@@ -454,8 +999,12 @@ mod tests {
             tokens: 10001,
             warmup: 3,
             backend: None,
+            model_sweep: None,
             verbose: false,
             output_json: None,
+            fail_fast: false,
+            keep_going: false,
+            format: BenchFormat::Text,
         };
         let result = validate_args(&args);
         assert!(result.is_err());
@@ -471,8 +1020,12 @@ mod tests {
             tokens: 100,
             warmup: 101,
             backend: None,
+            model_sweep: None,
             verbose: false,
             output_json: None,
+            fail_fast: false,
+            keep_going: false,
+            format: BenchFormat::Text,
         };
         let result = validate_args(&args);
         assert!(result.is_err());
@@ -493,8 +1046,12 @@ mod tests {
             tokens: 100,
             warmup: 3,
             backend: None,
+            model_sweep: None,
             verbose: true,
             output_json: None,
+            fail_fast: false,
+            keep_going: false,
+            format: BenchFormat::Text,
         };
         let result = validate_args(&args);
         assert!(result.is_ok());
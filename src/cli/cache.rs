@@ -185,7 +185,7 @@ pub async fn execute(args: CacheArgs, config: &Config) -> Result<()> {
 async fn show_cache_stats(config: &Config) -> Result<()> {
     info!("Initializing cache to show statistics...");
 
-    let model_manager = Arc::new(ModelManager::new(&config.models_dir));
+    let model_manager = Arc::new(ModelManager::from_config(config));
     let metrics = Some(Arc::new({
         let (collector, processor) = MetricsCollector::new();
         processor.start();
@@ -258,7 +258,7 @@ async fn warmup_models(
 ) -> Result<()> {
     info!("Starting model warmup process...");
 
-    let model_manager = Arc::new(ModelManager::new(&config.models_dir));
+    let model_manager = Arc::new(ModelManager::from_config(config));
     let metrics = Some(Arc::new({
         let (collector, processor) = MetricsCollector::new();
         processor.start();
@@ -340,7 +340,7 @@ async fn clear_cache(config: &Config, model: Option<String>, force: bool) -> Res
 
     info!("Clearing model cache...");
 
-    let model_manager = Arc::new(ModelManager::new(&config.models_dir));
+    let model_manager = Arc::new(ModelManager::from_config(config));
     let metrics = Some(Arc::new({
         let (collector, processor) = MetricsCollector::new();
         processor.start();
@@ -481,7 +481,7 @@ async fn benchmark_cache(
     println!("  Models: {:?}", models);
     println!("  Concurrent: {}", concurrent);
 
-    let model_manager = Arc::new(ModelManager::new(&config.models_dir));
+    let model_manager = Arc::new(ModelManager::from_config(config));
     let metrics = Some(Arc::new({
         let (collector, processor) = MetricsCollector::new();
         processor.start();
@@ -551,7 +551,7 @@ async fn benchmark_cache(
 async fn monitor_cache(config: &Config, interval: u64, detailed: bool) -> Result<()> {
     info!("Starting cache monitor...");
 
-    let model_manager = Arc::new(ModelManager::new(&config.models_dir));
+    let model_manager = Arc::new(ModelManager::from_config(config));
     let metrics = Some(Arc::new({
         let (collector, processor) = MetricsCollector::new();
         processor.start();
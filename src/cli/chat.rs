@@ -0,0 +1,463 @@
+//! Interactive chat for `inferno run --chat`
+//!
+//! Each user message is appended to the conversation, which is formatted with the
+//! model's chat template and streamed back token by token. The conversation is cut
+//! to the context-token budget by dropping the oldest exchanges. Ctrl-C cancels the
+//! reply being generated; at the prompt it ends the session.
+//!
+//! A conversation saved with `/save` or `--save-session` is a JSON file holding the
+//! system prompt and messages, which `/load` or `--load-session` continues from.
+
+use crate::backends::{Backend, CancellationToken, InferenceParams};
+use crate::models::chat_template::{ChatTemplate, Message};
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use tokio::sync::mpsc;
+
+const HELP: &str = "Commands:
+  /system <prompt>  Set the system prompt (no prompt clears it)
+  /reset            Forget the conversation so far
+  /save <file>      Save the conversation as JSON
+  /load <file>      Continue a saved conversation
+  /exit             End the session";
+
+/// Conversation state of a chat session
+pub struct ChatSession {
+    template: ChatTemplate,
+    system: Option<String>,
+    /// Messages after the system prompt, oldest first
+    history: Vec<Message>,
+    context_tokens: u32,
+}
+
+#[derive(Serialize)]
+struct Transcript<'a> {
+    system: Option<&'a str>,
+    messages: &'a [Message],
+}
+
+/// A transcript read back from a file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SavedTranscript {
+    #[serde(default)]
+    system: Option<String>,
+    messages: Vec<Message>,
+}
+
+impl ChatSession {
+    pub fn new(template: ChatTemplate, context_tokens: u32) -> Self {
+        Self {
+            template,
+            system: None,
+            history: Vec::new(),
+            context_tokens,
+        }
+    }
+
+    pub fn history(&self) -> &[Message] {
+        &self.history
+    }
+
+    pub fn set_system(&mut self, prompt: Option<String>) {
+        self.system = prompt;
+    }
+
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+
+    /// Prompt asking the model for the next assistant message
+    pub fn prompt(&self) -> Result<String> {
+        let mut messages = Vec::with_capacity(self.history.len() + 1);
+        if let Some(system) = &self.system {
+            messages.push(Message::new("system", system.clone()));
+        }
+        messages.extend(self.history.iter().cloned());
+        self.template.render(&messages)
+    }
+
+    /// Drop the oldest exchanges until the prompt fits the context budget, returning
+    /// how many messages were dropped
+    ///
+    /// The system prompt and the latest user message are always kept.
+    pub fn trim(&mut self, count_tokens: impl Fn(&str) -> u32) -> Result<usize> {
+        let mut dropped = 0;
+        while self.history.len() > 1 && count_tokens(&self.prompt()?) > self.context_tokens {
+            // A user message goes together with the reply that follows it
+            let end = if self.history.len() > 2 && self.history[1].role == "assistant" {
+                2
+            } else {
+                1
+            };
+            self.history.drain(..end);
+            dropped += end;
+        }
+        Ok(dropped)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let transcript = Transcript {
+            system: self.system.as_deref(),
+            messages: &self.history,
+        };
+        std::fs::write(path, serde_json::to_string_pretty(&transcript)?)?;
+        Ok(())
+    }
+
+    /// Replace the conversation with one saved by [`ChatSession::save`]
+    ///
+    /// The file must hold user and assistant messages taking turns from a user
+    /// message; the session is left as it was if it does not.
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read session file {}", path.display()))?;
+        let saved: SavedTranscript = serde_json::from_str(&content)
+            .with_context(|| format!("{} is not a saved chat session", path.display()))?;
+        for (i, message) in saved.messages.iter().enumerate() {
+            let expected = if i % 2 == 0 { "user" } else { "assistant" };
+            if message.role != expected {
+                anyhow::bail!(
+                    "Invalid session file {}: message {} is from '{}' where '{}' should take \
+                     its turn",
+                    path.display(),
+                    i + 1,
+                    message.role,
+                    expected
+                );
+            }
+        }
+        self.system = saved.system.filter(|system| !system.is_empty());
+        self.history = saved.messages;
+        Ok(())
+    }
+}
+
+/// Run the chat loop until `input` closes, `/exit` is entered or Ctrl-C is pressed
+/// at the prompt
+///
+/// `interrupts` receives one message per Ctrl-C.
+pub async fn run_repl(
+    backend: &mut Backend,
+    session: &mut ChatSession,
+    params: &InferenceParams,
+    input: &mut mpsc::UnboundedReceiver<String>,
+    interrupts: &mut mpsc::UnboundedReceiver<()>,
+    output: &mut impl Write,
+) -> Result<()> {
+    writeln!(output, "Type a message, or /help for commands.")?;
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+        let line = tokio::select! {
+            biased;
+            line = input.recv() => match line {
+                Some(line) => line,
+                None => break,
+            },
+            Some(()) = interrupts.recv() => break,
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(command) = line.strip_prefix('/') {
+            let (name, argument) = command
+                .split_once(char::is_whitespace)
+                .map(|(name, argument)| (name, argument.trim()))
+                .unwrap_or((command, ""));
+            match name {
+                "exit" | "quit" => break,
+                "reset" => {
+                    session.reset();
+                    writeln!(output, "Conversation cleared.")?;
+                }
+                "system" if argument.is_empty() => {
+                    session.set_system(None);
+                    writeln!(output, "System prompt cleared.")?;
+                }
+                "system" => {
+                    session.set_system(Some(argument.to_string()));
+                    writeln!(output, "System prompt set.")?;
+                }
+                "save" if argument.is_empty() => writeln!(output, "Usage: /save <file>")?,
+                "save" => match session.save(Path::new(argument)) {
+                    Ok(()) => writeln!(output, "Conversation saved to {}", argument)?,
+                    Err(e) => writeln!(output, "Failed to save conversation: {}", e)?,
+                },
+                "load" if argument.is_empty() => writeln!(output, "Usage: /load <file>")?,
+                "load" => match session.load(Path::new(argument)) {
+                    Ok(()) => writeln!(
+                        output,
+                        "Loaded {} messages from {}",
+                        session.history().len(),
+                        argument
+                    )?,
+                    Err(e) => writeln!(output, "Failed to load conversation: {:#}", e)?,
+                },
+                "help" => writeln!(output, "{}", HELP)?,
+                _ => writeln!(output, "Unknown command /{}. {}", name, HELP)?,
+            }
+            continue;
+        }
+
+        session.history.push(Message::new("user", line));
+        match session.trim(|text| backend.count_tokens(text)) {
+            Ok(0) => {}
+            Ok(dropped) => writeln!(
+                output,
+                "(dropped the {} oldest messages to fit the context budget)",
+                dropped
+            )?,
+            Err(e) => {
+                writeln!(output, "{}", e)?;
+                session.history.pop();
+                continue;
+            }
+        }
+
+        match generate(backend, session, params, interrupts, output).await? {
+            Some(reply) => session.history.push(Message::new("assistant", reply)),
+            // The unanswered message is forgotten so the next one is not a second user turn
+            None => {
+                session.history.pop();
+            }
+        }
+    }
+    writeln!(output)?;
+    Ok(())
+}
+
+/// Stream a reply to the session's prompt, or `None` if it failed or was cancelled
+async fn generate(
+    backend: &mut Backend,
+    session: &ChatSession,
+    params: &InferenceParams,
+    interrupts: &mut mpsc::UnboundedReceiver<()>,
+    output: &mut impl Write,
+) -> Result<Option<String>> {
+    // The template's end-of-turn markers stop generation alongside any the caller set
+    let mut stop_sequences = params.stop_sequences.clone();
+    for stop in session.template.stop_sequences() {
+        if !stop_sequences.contains(stop) {
+            stop_sequences.push(stop.clone());
+        }
+    }
+    let params = InferenceParams {
+        stream: true,
+        stop_sequences,
+        ..params.clone()
+    };
+    let prompt = match session.prompt() {
+        Ok(prompt) => prompt,
+        Err(e) => {
+            writeln!(output, "{}", e)?;
+            return Ok(None);
+        }
+    };
+    let cancel = CancellationToken::new();
+    let mut stream = match backend
+        .infer_stream_cancellable(&prompt, &params, &cancel)
+        .await
+    {
+        Ok(stream) => stream,
+        Err(e) => {
+            writeln!(output, "Generation failed: {}", e)?;
+            return Ok(None);
+        }
+    };
+
+    let mut reply = String::new();
+    loop {
+        tokio::select! {
+            biased;
+            token = stream.next() => match token {
+                Some(Ok(token)) => {
+                    write!(output, "{}", token)?;
+                    output.flush()?;
+                    reply.push_str(&token);
+                }
+                Some(Err(e)) => {
+                    writeln!(output, "\nGeneration failed: {}", e)?;
+                    return Ok(None);
+                }
+                None => break,
+            },
+            Some(()) = interrupts.recv() => {
+                cancel.cancel();
+                writeln!(output, "\n[generation cancelled]")?;
+                return Ok(None);
+            }
+        }
+    }
+    writeln!(output)?;
+
+    // Backends that do not apply stop sequences may run into the next turn
+    for stop in params.stop_sequences {
+        if let Some(end) = reply.find(&stop) {
+            reply.truncate(end);
+        }
+    }
+    Ok(Some(reply.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::Tokenizer;
+    use crate::backends::mock::MockBackend;
+
+    /// Counts whitespace-separated words as tokens
+    struct WordTokenizer;
+
+    impl Tokenizer for WordTokenizer {
+        fn count_tokens(&self, text: &str) -> Result<usize> {
+            Ok(text.split_whitespace().count())
+        }
+    }
+
+    /// Replies "ok" one letter at a time, and never finishes replying to "wait"
+    fn scripted_backend() -> Backend {
+        MockBackend::tokens(&["o", "k"])
+            .stall_on("user: wait\nassistant:")
+            .tokenizer(WordTokenizer)
+            .backend()
+    }
+
+    #[tokio::test]
+    async fn test_scripted_session_trims_oldest_exchanges() {
+        let dir = tempfile::tempdir().unwrap();
+        let transcript = dir.path().join("chat.json");
+        let script = [
+            "/system Be brief".to_string(),
+            "first question".to_string(),
+            "second question".to_string(),
+            format!("/save {}", transcript.display()),
+            "wait".to_string(),
+            "third question".to_string(),
+        ];
+        let (lines, mut input) = mpsc::unbounded_channel();
+        for line in script {
+            lines.send(line).unwrap();
+        }
+        drop(lines);
+        // One Ctrl-C, which only takes effect while "wait" is being answered
+        let (interrupt, mut interrupts) = mpsc::unbounded_channel();
+        interrupt.send(()).unwrap();
+
+        let mut backend = scripted_backend();
+        // Two exchanges with the system prompt come to 12 words; a third needs 17
+        let template = ChatTemplate::new(
+            "{% for m in messages %}{{ m.role }}: {{ m.content }}\n{% endfor %}assistant:",
+            "",
+        )
+        .unwrap();
+        let mut session = ChatSession::new(template, 14);
+        let mut output = Vec::new();
+        run_repl(
+            &mut backend,
+            &mut session,
+            &InferenceParams::default(),
+            &mut input,
+            &mut interrupts,
+            &mut output,
+        )
+        .await
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("[generation cancelled]"), "{}", output);
+        assert!(
+            output.contains("(dropped the 2 oldest messages"),
+            "{}",
+            output
+        );
+        assert_eq!(
+            session.history(),
+            [
+                Message::new("user", "second question"),
+                Message::new("assistant", "ok"),
+                Message::new("user", "third question"),
+                Message::new("assistant", "ok"),
+            ]
+        );
+        assert_eq!(
+            session.prompt().unwrap(),
+            "system: Be brief\nuser: second question\nassistant: ok\n\
+             user: third question\nassistant: ok\nassistant:"
+        );
+
+        // The transcript was saved before anything was dropped
+        let saved: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&transcript).unwrap()).unwrap();
+        assert_eq!(saved["system"], "Be brief");
+        assert_eq!(saved["messages"].as_array().unwrap().len(), 4);
+
+        session.reset();
+        assert!(session.history().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_saved_session_reloads_and_continues() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        let template = || {
+            ChatTemplate::new(
+                "{% for m in messages %}{{ m.role }}: {{ m.content }}\n{% endfor %}assistant:",
+                "",
+            )
+            .unwrap()
+        };
+
+        let mut session = ChatSession::new(template(), 1000);
+        session.set_system(Some("Be brief".to_string()));
+        session.history = vec![
+            Message::new("user", "first question"),
+            Message::new("assistant", "ok"),
+        ];
+        session.save(&path).unwrap();
+
+        let mut reloaded = ChatSession::new(template(), 1000);
+        reloaded.load(&path).unwrap();
+        assert_eq!(reloaded.history(), session.history());
+        assert_eq!(reloaded.prompt().unwrap(), session.prompt().unwrap());
+
+        // The conversation carries on from the saved history
+        let (lines, mut input) = mpsc::unbounded_channel();
+        lines.send("second question".to_string()).unwrap();
+        drop(lines);
+        let (_interrupt, mut interrupts) = mpsc::unbounded_channel();
+        let mut backend = scripted_backend();
+        run_repl(
+            &mut backend,
+            &mut reloaded,
+            &InferenceParams::default(),
+            &mut input,
+            &mut interrupts,
+            &mut Vec::<u8>::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            reloaded.prompt().unwrap(),
+            "system: Be brief\nuser: first question\nassistant: ok\n\
+             user: second question\nassistant: ok\nassistant:"
+        );
+
+        // Files that are not sessions, or whose turns are out of order, are refused
+        // without touching the conversation
+        for content in [
+            "not json",
+            r#"{"messages": [{"role": "assistant", "content": "hi"}]}"#,
+            r#"{"system": "x", "history": []}"#,
+        ] {
+            std::fs::write(&path, content).unwrap();
+            assert!(reloaded.load(&path).is_err(), "{}", content);
+            assert_eq!(reloaded.history().len(), 4);
+        }
+    }
+}
@@ -1,15 +1,20 @@
 #![allow(clippy::ptr_arg)]
 use crate::{
+    backends::{Backend, BackendType, InferenceParams},
     config::Config,
     conversion::{
-        ConversionConfig, ModelConverter, ModelFormat, OptimizationLevel, OptimizationOptions,
-        Precision, QuantizationType,
+        ConversionConfig, ConversionResult, ModelConverter, ModelFormat, OptimizationLevel,
+        OptimizationOptions, Precision, QuantizationType,
     },
     models::ModelManager,
+    optimization::gguf::{self, ShardReport},
 };
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail};
 use clap::{Args, Subcommand, ValueEnum};
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use tracing::warn;
 
 // ============================================================================
@@ -74,6 +79,28 @@ fn validate_input_directory(path: &PathBuf) -> Result<()> {
     Ok(())
 }
 
+/// Parse a size such as `500M`, `2G` or `2GB`, in powers of 1024; plain
+/// numbers are bytes
+fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let upper = size.to_ascii_uppercase();
+    let digits = upper.trim_end_matches('B');
+    let (number, multiplier) = match digits.chars().last() {
+        Some('K') => (&digits[..digits.len() - 1], 1u64 << 10),
+        Some('M') => (&digits[..digits.len() - 1], 1 << 20),
+        Some('G') => (&digits[..digits.len() - 1], 1 << 30),
+        Some('T') => (&digits[..digits.len() - 1], 1 << 40),
+        _ => (digits, 1),
+    };
+    let value = number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite() && *value > 0.0)
+        .ok_or_else(|| format!("Invalid size '{}'; use a size such as 500M or 2G", size))?;
+    Ok((value * multiplier as f64) as u64)
+}
+
 /// Configuration for model conversion operations
 /// Reduces function signature from 11 parameters to 2
 pub struct ConvertModelConfig {
@@ -180,6 +207,20 @@ pub enum ConvertCommand {
 
         #[arg(long, help = "Skip output verification")]
         no_verify: bool,
+
+        #[arg(
+            long,
+            help = "Load the converted model and run a short inference, failing if it cannot"
+        )]
+        validate_output: bool,
+
+        #[arg(
+            long,
+            help = "Summary format; json prints only the conversion summary",
+            value_enum,
+            default_value = "text"
+        )]
+        output_format: SummaryFormat,
     },
 
     #[command(about = "Optimize model for better performance")]
@@ -285,6 +326,38 @@ pub enum ConvertCommand {
         #[arg(long, help = "Test all quantization types")]
         all_quantizations: bool,
     },
+
+    #[command(
+        about = "Merge a sharded GGUF model, such as model-00001-of-00003.gguf, into one file"
+    )]
+    MergeShards {
+        #[arg(help = "Any one of the model's shards")]
+        shard: PathBuf,
+
+        #[arg(help = "Output model path")]
+        output: PathBuf,
+
+        #[arg(
+            long,
+            help = "Skip loading the merged model and running a short inference"
+        )]
+        no_validate: bool,
+    },
+
+    #[command(about = "Split a GGUF model into shards no larger than a given size")]
+    SplitShards {
+        #[arg(help = "Input model path")]
+        input: PathBuf,
+
+        #[arg(
+            long,
+            help = "Path the shards are named after, as in model-00001-of-00003.gguf; defaults to the input path"
+        )]
+        output: Option<PathBuf>,
+
+        #[arg(long, help = "Largest shard size, such as 500M or 2G", value_parser = parse_size)]
+        max_size: u64,
+    },
 }
 
 #[derive(Clone, Debug, ValueEnum)]
@@ -379,6 +452,52 @@ impl From<PrecisionArg> for Precision {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SummaryFormat {
+    Text,
+    Json,
+}
+
+/// What `convert model --output-format json` prints
+#[derive(Debug, serde::Serialize)]
+struct ConversionSummary {
+    success: bool,
+    input: PathBuf,
+    output: PathBuf,
+    source_format: Option<String>,
+    target_format: String,
+    quantization: Option<String>,
+    input_bytes: u64,
+    output_bytes: u64,
+    size_delta_bytes: i64,
+    duration_ms: u64,
+    warnings: Vec<String>,
+    errors: Vec<String>,
+}
+
+impl ConversionSummary {
+    fn new(
+        result: &ConversionResult,
+        source_format: Option<&ModelFormat>,
+        config: &ConversionConfig,
+    ) -> Self {
+        Self {
+            success: result.success,
+            input: result.input_path.clone(),
+            output: result.output_path.clone(),
+            source_format: source_format.map(|f| format!("{:?}", f).to_lowercase()),
+            target_format: format!("{:?}", config.output_format).to_lowercase(),
+            quantization: config.quantization.as_ref().map(|q| format!("{:?}", q)),
+            input_bytes: result.input_size,
+            output_bytes: result.output_size,
+            size_delta_bytes: result.output_size as i64 - result.input_size as i64,
+            duration_ms: result.conversion_time.as_millis() as u64,
+            warnings: result.warnings.clone(),
+            errors: result.errors.clone(),
+        }
+    }
+}
+
 #[derive(Clone, ValueEnum)]
 pub enum ExportFormat {
     Json,
@@ -387,7 +506,7 @@ pub enum ExportFormat {
 }
 
 pub async fn execute(args: ConvertArgs, config: &Config) -> Result<()> {
-    let model_manager = Arc::new(ModelManager::new(&config.models_dir));
+    let model_manager = Arc::new(ModelManager::from_config(config));
     let converter = ModelConverter::new(model_manager.clone(), config.clone());
 
     match args.command {
@@ -402,8 +521,11 @@ pub async fn execute(args: ConvertArgs, config: &Config) -> Result<()> {
             batch_size,
             preserve_metadata,
             no_verify,
+            validate_output,
+            output_format,
         } => {
-            let config = ConvertModelConfig {
+            let output_path = output.clone();
+            let convert_config = ConvertModelConfig {
                 input,
                 output,
                 format,
@@ -415,7 +537,21 @@ pub async fn execute(args: ConvertArgs, config: &Config) -> Result<()> {
                 preserve_metadata,
                 verify_output: !no_verify,
             };
-            convert_model(&converter, config).await
+            let result = convert_model(&converter, convert_config, output_format).await?;
+            if !result.success {
+                bail!("Conversion failed: {}", result.errors.join("; "));
+            }
+            if validate_output {
+                let backend_type = BackendType::from_model_path(&output_path).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "No suitable backend found for model: {}",
+                        output_path.display()
+                    )
+                })?;
+                let backend = Backend::new(backend_type, &config.backend_config)?;
+                validate_converted_model(&model_manager, backend, &output_path).await?;
+            }
+            Ok(())
         }
 
         ConvertCommand::Optimize {
@@ -494,26 +630,92 @@ pub async fn execute(args: ConvertArgs, config: &Config) -> Result<()> {
             )
             .await
         }
+
+        ConvertCommand::MergeShards {
+            shard,
+            output,
+            no_validate,
+        } => {
+            validate_output_directory(&output)?;
+            println!(
+                "Merging shards of {} -> {}",
+                shard.display(),
+                output.display()
+            );
+            let output_path = output.clone();
+            let report =
+                tokio::task::spawn_blocking(move || gguf::merge_shards(&shard, &output)).await??;
+            print_shard_report("Merged", &report);
+            println!("  Output: {}", output_path.display());
+            if !no_validate {
+                let backend = Backend::new(BackendType::Gguf, &config.backend_config)?;
+                validate_converted_model(&model_manager, backend, &output_path).await?;
+            }
+            Ok(())
+        }
+
+        ConvertCommand::SplitShards {
+            input,
+            output,
+            max_size,
+        } => {
+            validate_input_path(&input)?;
+            let output = output.unwrap_or_else(|| input.clone());
+            validate_output_directory(&output)?;
+            println!(
+                "Splitting model: {} into shards of at most {:.2} MB",
+                input.display(),
+                max_size as f64 / (1024.0 * 1024.0)
+            );
+            let report =
+                tokio::task::spawn_blocking(move || gguf::split_shards(&input, &output, max_size))
+                    .await??;
+            print_shard_report("Split", &report);
+            for shard in &report.shards {
+                println!("    {}", shard.display());
+            }
+            Ok(())
+        }
     }
 }
 
-async fn convert_model(converter: &ModelConverter, config: ConvertModelConfig) -> Result<()> {
+fn print_shard_report(action: &str, report: &ShardReport) {
+    println!(
+        "✓ {} {} tensors across {} shards",
+        action,
+        report.tensors,
+        report.shards.len()
+    );
+    println!(
+        "  Written: {:.2} MB",
+        report.bytes_written as f64 / (1024.0 * 1024.0)
+    );
+}
+
+async fn convert_model(
+    converter: &ModelConverter,
+    config: ConvertModelConfig,
+    output_format: SummaryFormat,
+) -> Result<ConversionResult> {
     // Pre-execution validation
     validate_input_path(&config.input)?;
     validate_output_directory(&config.output)?;
     validate_context_length(config.context_length)?;
     validate_batch_size(config.batch_size)?;
 
-    println!(
-        "Converting model: {} -> {}",
-        config.input.display(),
-        config.output.display()
-    );
-    println!("Target format: {:?}", config.format);
-    println!("Optimization: {:?}", config.optimization);
+    let text = output_format == SummaryFormat::Text;
+    if text {
+        println!(
+            "Converting model: {} -> {}",
+            config.input.display(),
+            config.output.display()
+        );
+        println!("Target format: {:?}", config.format);
+        println!("Optimization: {:?}", config.optimization);
 
-    if let Some(ref quant) = config.quantization {
-        println!("Quantization: {:?}", quant);
+        if let Some(ref quant) = config.quantization {
+            println!("Quantization: {:?}", quant);
+        }
     }
 
     // Store paths before moving config
@@ -526,6 +728,13 @@ async fn convert_model(converter: &ModelConverter, config: ConvertModelConfig) -
         .convert_model(&input_path, &output_path, &conversion_config)
         .await?;
 
+    if !text {
+        let source_format = converter.detect_format(&input_path).await.ok();
+        let summary = ConversionSummary::new(&result, source_format.as_ref(), &conversion_config);
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(result);
+    }
+
     if result.success {
         println!("✓ Conversion completed successfully!");
         println!(
@@ -553,6 +762,49 @@ async fn convert_model(converter: &ModelConverter, config: ConvertModelConfig) -
         }
     }
 
+    Ok(result)
+}
+
+/// Check that a converted model passes validation, loads and generates, so a
+/// broken conversion fails here rather than on the first request that uses it
+async fn validate_converted_model(
+    model_manager: &ModelManager,
+    mut backend: Backend,
+    path: &Path,
+) -> Result<()> {
+    println!("Validating output: {}", path.display());
+
+    let validation = model_manager
+        .validate_model_comprehensive(path, None)
+        .await?;
+    if !validation.is_valid {
+        bail!(
+            "Converted model failed validation: {}",
+            validation.errors.join("; ")
+        );
+    }
+    println!("  ✓ Validation passed");
+
+    let model_info = model_manager.create_model_info(path).await?;
+    backend
+        .load_model(&model_info)
+        .await
+        .context("Converted model failed to load")?;
+    println!("  ✓ Model loads");
+
+    let params = InferenceParams {
+        max_tokens: 8,
+        ..Default::default()
+    };
+    backend
+        .infer("Hello", &params)
+        .await
+        .context("Converted model failed to generate")?;
+    println!("  ✓ Model generates");
+
+    if let Err(e) = backend.unload_model().await {
+        warn!("Failed to unload validated model: {}", e);
+    }
     Ok(())
 }
 
@@ -1091,6 +1343,7 @@ async fn benchmark_conversion(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backends::mock::MockBackend;
 
     #[test]
     fn test_validate_input_path_nonexistent() {
@@ -1228,6 +1481,18 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("500M"), Ok(500 << 20));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert_eq!(parse_size("2gb"), Ok(2 << 30));
+        assert_eq!(parse_size("1.5K"), Ok(1536));
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("").is_err());
+    }
+
     #[test]
     fn test_model_format_conversion() {
         assert!(matches!(
@@ -1354,4 +1619,50 @@ mod tests {
         assert!(!options.graph_simplification);
         assert!(options.operator_fusion);
     }
+
+    #[tokio::test]
+    async fn test_validate_output_after_conversion() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let input = temp_dir.path().join("input.gguf");
+        let output = temp_dir.path().join("output.gguf");
+        std::fs::write(&input, b"GGUF\x03\x00\x00\x00\x00\x00\x00\x00").unwrap();
+
+        let mut config = Config::default();
+        config.models_dir = temp_dir.path().to_path_buf();
+        let model_manager = Arc::new(ModelManager::new(temp_dir.path()));
+        let converter = ModelConverter::new(model_manager.clone(), config);
+
+        let result = convert_model(
+            &converter,
+            ConvertModelConfig {
+                input,
+                output: output.clone(),
+                format: ModelFormatArg::Gguf,
+                optimization: OptimizationLevelArg::None,
+                quantization: None,
+                precision: None,
+                context_length: None,
+                batch_size: None,
+                preserve_metadata: true,
+                verify_output: false,
+            },
+            SummaryFormat::Text,
+        )
+        .await
+        .unwrap();
+        assert!(result.success);
+
+        let backend = MockBackend::prompt(str::to_string).unloaded().backend();
+        validate_converted_model(&model_manager, backend, &output)
+            .await
+            .unwrap();
+
+        // A corrupt output fails before the backend is asked to load it
+        std::fs::write(&output, b"not a model").unwrap();
+        let backend = MockBackend::prompt(str::to_string).unloaded().backend();
+        let err = validate_converted_model(&model_manager, backend, &output)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("failed validation"));
+    }
 }
@@ -6,8 +6,12 @@
 //! viewing worker statistics, and testing inference requests.
 
 use crate::{
-    backends::InferenceParams, config::Config, distributed::DistributedInference,
-    metrics::MetricsCollector, models::ModelManager,
+    api::auth::ApiKeys,
+    backends::InferenceParams,
+    config::Config,
+    distributed::{DistributedInference, stream_listen_addr},
+    metrics::MetricsCollector,
+    models::ModelManager,
 };
 use anyhow::{Result, bail};
 use clap::{Args, Subcommand};
@@ -40,6 +44,12 @@ pub enum DistributedCommand {
             default_value = "4"
         )]
         max_concurrent: usize,
+
+        #[arg(
+            long,
+            help = "Address, or port on loopback, to serve streaming requests from remote coordinators on; other interfaces need server.api_key_file"
+        )]
+        listen: Option<String>,
     },
 
     #[command(about = "Test distributed inference performance")]
@@ -89,6 +99,12 @@ pub enum DistributedCommand {
 
         #[arg(long, help = "Top-K sampling", default_value = "40")]
         top_k: u32,
+
+        #[arg(
+            long = "remote-worker",
+            help = "Stream from a remote worker (repeatable)"
+        )]
+        remote_workers: Vec<String>,
     },
 }
 
@@ -99,6 +115,7 @@ pub async fn execute(args: DistributedArgs, config: &Config) -> Result<()> {
             preload_model,
             load_balancing,
             max_concurrent,
+            listen,
         } => {
             start_distributed_server(
                 config,
@@ -106,6 +123,7 @@ pub async fn execute(args: DistributedArgs, config: &Config) -> Result<()> {
                 preload_model,
                 load_balancing,
                 max_concurrent,
+                listen,
             )
             .await
         }
@@ -123,6 +141,7 @@ pub async fn execute(args: DistributedArgs, config: &Config) -> Result<()> {
             max_tokens,
             temperature,
             top_k,
+            remote_workers,
         } => {
             test_inference(
                 config,
@@ -132,6 +151,7 @@ pub async fn execute(args: DistributedArgs, config: &Config) -> Result<()> {
                 max_tokens,
                 temperature,
                 top_k,
+                remote_workers,
             )
             .await
         }
@@ -220,13 +240,14 @@ async fn start_distributed_server(
     preload_model: Option<String>,
     load_balancing: bool,
     max_concurrent: usize,
+    listen: Option<String>,
 ) -> Result<()> {
     // Validate arguments
     validate_start_args(workers, max_concurrent)?;
 
     info!("Starting distributed inference server");
 
-    let model_manager = Arc::new(ModelManager::new(&config.models_dir));
+    let model_manager = Arc::new(ModelManager::from_config(config));
     let metrics = Some(Arc::new({
         let (collector, processor) = MetricsCollector::new();
         processor.start();
@@ -243,6 +264,10 @@ async fn start_distributed_server(
     if preload_model.is_some() {
         distributed_config.preload_models = true;
     }
+    if listen.is_some() {
+        distributed_config.stream_listen_addr = listen;
+    }
+    let listen_addr = distributed_config.stream_listen_addr.clone();
 
     info!(
         "Initializing {} workers with max {} concurrent requests each",
@@ -267,7 +292,21 @@ async fn start_distributed_server(
 
     // Keep the server running
     info!("Server is running. Press Ctrl+C to stop.");
-    tokio::signal::ctrl_c().await?;
+    match listen_addr {
+        Some(addr) => {
+            let api_keys = match &config.server.api_key_file {
+                Some(path) => Some(Arc::new(ApiKeys::load(path)?)),
+                None => None,
+            };
+            let listener = tokio::net::TcpListener::bind(stream_listen_addr(&addr)).await?;
+            distributed
+                .serve_streaming(listener, api_keys, async {
+                    let _ = tokio::signal::ctrl_c().await;
+                })
+                .await?;
+        }
+        None => tokio::signal::ctrl_c().await?,
+    }
 
     info!("Shutting down distributed inference system");
     distributed.shutdown().await?;
@@ -291,7 +330,7 @@ async fn benchmark_distributed_inference(
     info!("Requests per client: {}", requests_per_client);
     info!("Prompt: \"{}\"", prompt);
 
-    let model_manager = Arc::new(ModelManager::new(&config.models_dir));
+    let model_manager = Arc::new(ModelManager::from_config(config));
     let metrics = Some(Arc::new({
         let (collector, processor) = MetricsCollector::new();
         processor.start();
@@ -333,10 +372,17 @@ async fn benchmark_distributed_inference(
                     max_tokens: 50,
                     temperature: 0.7,
                     top_k: 40,
+                    repeat_penalty: None,
+                    repeat_last_n: None,
+                    min_p: None,
                     top_p: 0.9,
                     stream: false,
                     stop_sequences: vec![],
+                    stop_regex: None,
+                    max_generation_ms: None,
                     seed: None,
+                    response_format: None,
+                    logprobs: None,
                 };
 
                 match distributed_clone.infer(&model_name, &prompt, &params).await {
@@ -446,6 +492,7 @@ async fn test_inference(
     max_tokens: u32,
     temperature: f32,
     top_k: u32,
+    remote_workers: Vec<String>,
 ) -> Result<()> {
     // Validate arguments
     validate_test_args(model_name, input, max_tokens, temperature)?;
@@ -458,15 +505,20 @@ async fn test_inference(
     info!("Temperature: {}", temperature);
     info!("Top-K: {}", top_k);
 
-    let model_manager = Arc::new(ModelManager::new(&config.models_dir));
+    let model_manager = Arc::new(ModelManager::from_config(config));
     let metrics = Some(Arc::new({
         let (collector, processor) = MetricsCollector::new();
         processor.start();
         collector
     }));
 
+    let mut distributed_config = config.distributed.clone();
+    if !remote_workers.is_empty() {
+        distributed_config.remote_workers = remote_workers;
+    }
+
     let distributed = DistributedInference::new(
-        config.distributed.clone(),
+        distributed_config,
         config.backend_config.clone(),
         model_manager,
         metrics,
@@ -477,10 +529,17 @@ async fn test_inference(
         max_tokens,
         temperature,
         top_k,
+        repeat_penalty: None,
+        repeat_last_n: None,
+        min_p: None,
         top_p: 0.9,
         stream,
         stop_sequences: vec![],
+        stop_regex: None,
+        max_generation_ms: None,
         seed: None,
+        response_format: None,
+        logprobs: None,
     };
 
     let start_time = Instant::now();
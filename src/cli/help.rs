@@ -321,7 +321,11 @@ impl HelpSystem {
         # Inspect a specific model\n\
         inferno models info [model-name]\n\
         inferno models validate [model-file]\n\
-        inferno models stats\n"
+        inferno models stats\n\n\
+        # Upgrade installed models to their latest repo revision\n\
+        inferno models list --outdated\n\
+        inferno models upgrade --dry-run\n\
+        inferno models upgrade [model-name]\n"
             .to_string()
     }
 
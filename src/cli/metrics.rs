@@ -5,12 +5,22 @@
 //! HTTP server for production monitoring.
 
 use crate::interfaces::cli::{Command, CommandContext, CommandOutput};
-use crate::{config::Config, metrics::MetricsCollector};
+use crate::{
+    cli::models::format_size,
+    config::Config,
+    metrics::{
+        MetricsCollector, MetricsSnapshot,
+        usage::{self, UsageEntry, UsageLedger},
+    },
+};
 use anyhow::Result;
 use async_trait::async_trait;
 use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use chrono::NaiveDate;
 use clap::{Args, Subcommand};
 use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::signal;
 use tracing::info;
@@ -45,6 +55,61 @@ pub enum MetricsCommand {
         )]
         bind: String,
     },
+
+    #[command(about = "Show a human-readable overview of a running server's metrics")]
+    Summary {
+        #[arg(
+            short,
+            long,
+            help = "Address of the inferno or metrics server (format: host:port)",
+            default_value = "127.0.0.1:8080"
+        )]
+        server: String,
+
+        #[arg(
+            short,
+            long,
+            value_name = "SECONDS",
+            num_args = 0..=1,
+            default_missing_value = "2",
+            help = "Refresh the summary periodically (default every 2 seconds)"
+        )]
+        watch: Option<u64>,
+    },
+
+    #[command(about = "Reset all metrics on a running server")]
+    Reset {
+        #[arg(
+            short,
+            long,
+            help = "Address of the inferno or metrics server (format: host:port)",
+            default_value = "127.0.0.1:8080"
+        )]
+        server: String,
+    },
+
+    #[command(about = "Show tokens used per API key from the usage ledger")]
+    Usage {
+        #[arg(long, help = "Ledger file (default: server.usage_ledger_path)")]
+        ledger: Option<PathBuf>,
+
+        #[arg(long, help = "Only show usage of this API key")]
+        key: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "YYYY-MM-DD",
+            help = "Only show usage from this day on"
+        )]
+        since: Option<NaiveDate>,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            help = "Write the rows to a CSV file instead"
+        )]
+        csv: Option<PathBuf>,
+    },
 }
 
 // ============================================================================
@@ -256,6 +321,293 @@ impl Command for MetricsServerCommand {
     }
 }
 
+/// Show a human-readable overview of a running server's metrics
+pub struct MetricsSummaryCommand {
+    #[allow(dead_code)]
+    config: Config,
+    server: String,
+}
+
+impl MetricsSummaryCommand {
+    pub fn new(config: Config, server: String) -> Self {
+        Self { config, server }
+    }
+}
+
+#[async_trait]
+impl Command for MetricsSummaryCommand {
+    fn name(&self) -> &str {
+        "metrics summary"
+    }
+
+    fn description(&self) -> &str {
+        "Show a human-readable overview of a running server's metrics"
+    }
+
+    async fn validate(&self, _ctx: &CommandContext) -> Result<()> {
+        validate_bind_address(&self.server)?;
+        Ok(())
+    }
+
+    async fn execute(&self, ctx: &mut CommandContext) -> Result<CommandOutput> {
+        let snapshot = fetch_server_snapshot(&self.server).await?;
+
+        if !ctx.json_output {
+            print!("{}", render_summary(&snapshot, use_color()));
+        }
+
+        let inference = &snapshot.inference_metrics;
+        Ok(CommandOutput::success_with_data(
+            "Metrics summary",
+            json!({
+                "server": self.server,
+                "total_requests": inference.total_requests,
+                "success_rate": success_rate(&snapshot),
+                "average_latency_ms": inference.average_latency_ms,
+                "p95_latency_ms": inference.p95_latency_ms,
+                "tokens_per_second": inference.average_tokens_per_second,
+                "loaded_models": snapshot.model_metrics.loaded_models.len(),
+            }),
+        ))
+    }
+}
+
+/// Fraction of finished requests that succeeded, as a percentage
+fn success_rate(snapshot: &MetricsSnapshot) -> Option<f64> {
+    let inference = &snapshot.inference_metrics;
+    let finished = inference.successful_requests + inference.failed_requests;
+    (finished > 0).then(|| inference.successful_requests as f64 * 100.0 / finished as f64)
+}
+
+fn use_color() -> bool {
+    use std::io::IsTerminal;
+    std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+}
+
+fn format_latency(ms: f64) -> String {
+    if ms >= 1000.0 {
+        format!("{:.2} s", ms / 1000.0)
+    } else {
+        format!("{:.1} ms", ms)
+    }
+}
+
+fn format_uptime(seconds: u64) -> String {
+    match seconds {
+        s if s >= 86_400 => format!("{}d {}h", s / 86_400, s % 86_400 / 3600),
+        s if s >= 3600 => format!("{}h {:02}m", s / 3600, s % 3600 / 60),
+        s if s >= 60 => format!("{}m {:02}s", s / 60, s % 60),
+        s => format!("{}s", s),
+    }
+}
+
+/// Render a dashboard-style overview of `snapshot`
+fn render_summary(snapshot: &MetricsSnapshot, color: bool) -> String {
+    use crossterm::style::{Color, Stylize};
+
+    let paint = |text: String, fg: Color| {
+        if color {
+            text.with(fg).to_string()
+        } else {
+            text
+        }
+    };
+    let heading = |text: &str| {
+        let text = format!("{:<12}", text);
+        if color { text.bold().to_string() } else { text }
+    };
+
+    let inference = &snapshot.inference_metrics;
+    let mut out = String::new();
+    out.push_str(&format!(
+        "{} (uptime {})\n",
+        if color {
+            "Inferno metrics".bold().to_string()
+        } else {
+            "Inferno metrics".to_string()
+        },
+        format_uptime(snapshot.system_metrics.uptime_seconds)
+    ));
+
+    let rate = match success_rate(snapshot) {
+        Some(rate) => {
+            let fg = if rate >= 99.0 {
+                Color::Green
+            } else if rate >= 95.0 {
+                Color::Yellow
+            } else {
+                Color::Red
+            };
+            paint(format!("{:.1}% success", rate), fg)
+        }
+        None => "no requests yet".to_string(),
+    };
+    out.push_str(&format!(
+        "  {} {} total, {} failed, {}\n",
+        heading("Requests"),
+        inference.total_requests,
+        inference.failed_requests,
+        rate
+    ));
+
+    let p95 = inference
+        .p95_latency_ms
+        .map_or_else(|| "n/a".to_string(), format_latency);
+    out.push_str(&format!(
+        "  {} avg {}, p95 {}\n",
+        heading("Latency"),
+        format_latency(inference.average_latency_ms),
+        p95
+    ));
+    out.push_str(&format!(
+        "  {} {:.1} tokens/s, {} tokens generated\n",
+        heading("Throughput"),
+        inference.average_tokens_per_second,
+        inference.total_tokens_generated
+    ));
+    out.push_str(&format!(
+        "  {} {:.1}% CPU, {} memory\n",
+        heading("System"),
+        snapshot.system_metrics.cpu_usage_percent,
+        format_size(snapshot.system_metrics.memory_usage_bytes)
+    ));
+
+    let models = &snapshot.model_metrics;
+    out.push_str(&format!(
+        "  {} {} loaded ({})\n",
+        heading("Models"),
+        models.loaded_models.len(),
+        format_size(models.total_model_size_bytes)
+    ));
+    let mut loaded: Vec<_> = models.loaded_models.values().collect();
+    loaded.sort_by(|a, b| a.name.cmp(&b.name));
+    for model in loaded {
+        out.push_str(&format!(
+            "    {} {:<8} {}, {} inferences\n",
+            paint(format!("{:<24}", model.name), Color::Cyan),
+            model.backend_type,
+            format_size(model.size_bytes),
+            model.inference_count
+        ));
+    }
+
+    out
+}
+
+/// Print the summary every `interval_secs` until interrupted
+async fn watch_summary(server: &str, interval_secs: u64) -> Result<()> {
+    use crossterm::{
+        cursor::MoveTo,
+        execute,
+        terminal::{Clear, ClearType},
+    };
+
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval_secs.max(1)));
+    let color = use_color();
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = signal::ctrl_c() => return Ok(()),
+        }
+
+        let summary = match fetch_server_snapshot(server).await {
+            Ok(snapshot) => render_summary(&snapshot, color),
+            Err(e) => format!("{:#}\n", e),
+        };
+        execute!(std::io::stdout(), Clear(ClearType::All), MoveTo(0, 0))?;
+        print!("{}", summary);
+        println!(
+            "\nRefreshing every {}s from {}; press Ctrl+C to stop",
+            interval_secs.max(1),
+            server
+        );
+    }
+}
+
+/// Reset all metrics on a running server
+pub struct MetricsResetCommand {
+    #[allow(dead_code)]
+    config: Config,
+    server: String,
+}
+
+impl MetricsResetCommand {
+    pub fn new(config: Config, server: String) -> Self {
+        Self { config, server }
+    }
+}
+
+#[async_trait]
+impl Command for MetricsResetCommand {
+    fn name(&self) -> &str {
+        "metrics reset"
+    }
+
+    fn description(&self) -> &str {
+        "Reset all metrics on a running server"
+    }
+
+    async fn validate(&self, _ctx: &CommandContext) -> Result<()> {
+        validate_bind_address(&self.server)?;
+        Ok(())
+    }
+
+    async fn execute(&self, ctx: &mut CommandContext) -> Result<CommandOutput> {
+        info!("Resetting metrics on {}", self.server);
+        reset_server_metrics(&self.server).await?;
+
+        if !ctx.json_output {
+            println!("Metrics reset on {}", self.server);
+        }
+
+        Ok(CommandOutput::success_with_data(
+            "Metrics reset",
+            json!({
+                "server": self.server,
+                "status": "reset",
+            }),
+        ))
+    }
+}
+
+/// Ask the server at `addr` to reset its metrics through `POST /metrics/reset`
+async fn reset_server_metrics(addr: &str) -> Result<()> {
+    server_request(addr, "POST", "/metrics/reset").await?;
+    Ok(())
+}
+
+/// Fetch a metrics snapshot from the server at `addr`
+async fn fetch_server_snapshot(addr: &str) -> Result<MetricsSnapshot> {
+    let body = server_request(addr, "GET", "/metrics/snapshot").await?;
+    serde_json::from_str(&body)
+        .map_err(|e| anyhow::anyhow!("Invalid metrics snapshot from {}: {}", addr, e))
+}
+
+/// Send a bodiless HTTP/1.1 request and return the response body of a 200 response
+async fn server_request(addr: &str, method: &str, path: &str) -> Result<String> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(addr)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to connect to {}: {}", addr, e))?;
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        method, path, addr
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status_line = head.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(body.to_string()),
+        Some(status) => anyhow::bail!("{} {} on {} failed: HTTP {}", method, path, addr, status),
+        None => anyhow::bail!("Unexpected response from {}: {:?}", addr, status_line),
+    }
+}
+
 /// Validate a bind address format (host:port)
 fn validate_bind_address(bind: &str) -> Result<()> {
     let parts: Vec<&str> = bind.split(':').collect();
@@ -290,7 +642,7 @@ fn validate_bind_address(bind: &str) -> Result<()> {
     Ok(())
 }
 
-pub async fn execute(args: MetricsArgs, _config: &Config) -> Result<()> {
+pub async fn execute(args: MetricsArgs, config: &Config) -> Result<()> {
     match args.command {
         MetricsCommand::Json => {
             let (collector, processor) = MetricsCollector::new();
@@ -325,13 +677,88 @@ pub async fn execute(args: MetricsArgs, _config: &Config) -> Result<()> {
             info!("Starting standalone metrics server on {}", bind);
             start_metrics_server(&bind).await?;
         }
+
+        MetricsCommand::Summary { server, watch } => {
+            validate_bind_address(&server)?;
+            match watch {
+                Some(interval) => watch_summary(&server, interval).await?,
+                None => {
+                    let snapshot = fetch_server_snapshot(&server).await?;
+                    print!("{}", render_summary(&snapshot, use_color()));
+                }
+            }
+        }
+
+        MetricsCommand::Reset { server } => {
+            validate_bind_address(&server)?;
+            reset_server_metrics(&server).await?;
+            println!("Metrics reset on {}", server);
+        }
+
+        MetricsCommand::Usage {
+            ledger,
+            key,
+            since,
+            csv,
+        } => {
+            let path = ledger
+                .or_else(|| config.server.usage_ledger_path.clone())
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "No usage ledger; set server.usage_ledger_path or pass --ledger"
+                    )
+                })?;
+            let ledger = UsageLedger::open(&path)?;
+            let key_id = key.as_deref().map(UsageLedger::key_id);
+            let entries = ledger.entries(key_id.as_deref(), since);
+            match csv {
+                Some(csv) => {
+                    crate::io::atomic::write_file_async(&csv, usage::to_csv(&entries)?).await?;
+                    println!("Wrote {} usage rows to {}", entries.len(), csv.display());
+                }
+                None => print!("{}", render_usage(&entries)),
+            }
+        }
     }
 
     Ok(())
 }
 
+/// Usage rows as a table, with a total for each key
+fn render_usage(entries: &[UsageEntry]) -> String {
+    if entries.is_empty() {
+        return "No usage recorded\n".to_string();
+    }
+    let mut out = format!(
+        "{:<16}  {:<10}  {:>9}  {:>13}  {:>17}\n",
+        "KEY", "DATE", "REQUESTS", "PROMPT TOKENS", "COMPLETION TOKENS"
+    );
+    let mut totals: BTreeMap<&str, (u64, u64, u64)> = BTreeMap::new();
+    for entry in entries {
+        out.push_str(&format!(
+            "{:<16}  {:<10}  {:>9}  {:>13}  {:>17}\n",
+            entry.key_id, entry.date, entry.requests, entry.prompt_tokens, entry.completion_tokens
+        ));
+        let total = totals.entry(entry.key_id.as_str()).or_default();
+        total.0 += entry.requests;
+        total.1 += entry.prompt_tokens;
+        total.2 += entry.completion_tokens;
+    }
+    out.push('\n');
+    for (key_id, (requests, prompt_tokens, completion_tokens)) in totals {
+        out.push_str(&format!(
+            "{:<16}  {:<10}  {:>9}  {:>13}  {:>17}\n",
+            key_id, "total", requests, prompt_tokens, completion_tokens
+        ));
+    }
+    out
+}
+
 async fn start_metrics_server(bind_addr: &str) -> Result<()> {
-    use axum::{Router, routing::get};
+    use axum::{
+        Router,
+        routing::{get, post},
+    };
 
     use std::sync::Arc;
 
@@ -352,6 +779,7 @@ async fn start_metrics_server(bind_addr: &str) -> Result<()> {
         .route("/metrics", get(metrics_prometheus_handler))
         .route("/metrics/json", get(metrics_json_handler))
         .route("/metrics/snapshot", get(metrics_snapshot_handler))
+        .route("/metrics/reset", post(metrics_reset_handler))
         .route("/health", get(health_check))
         .layer(
             ServiceBuilder::new()
@@ -393,7 +821,8 @@ async fn metrics_root() -> impl IntoResponse {
             "/health": "Health check",
             "/metrics": "Prometheus metrics",
             "/metrics/json": "JSON formatted metrics",
-            "/metrics/snapshot": "Detailed metrics snapshot"
+            "/metrics/snapshot": "Detailed metrics snapshot",
+            "/metrics/reset": "Reset all metrics (POST)"
         }
     }))
 }
@@ -445,6 +874,14 @@ async fn metrics_json_handler(State(state): State<Arc<MetricsServerState>>) -> i
     }
 }
 
+async fn metrics_reset_handler(State(state): State<Arc<MetricsServerState>>) -> impl IntoResponse {
+    state.metrics.reset();
+    Json(json!({
+        "status": "reset",
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    }))
+}
+
 async fn metrics_snapshot_handler(
     State(state): State<Arc<MetricsServerState>>,
 ) -> impl IntoResponse {
@@ -683,4 +1120,103 @@ mod tests {
         assert_eq!(server_cmd.name(), "metrics server");
         assert_eq!(server_cmd.description(), "Start standalone metrics server");
     }
+
+    #[tokio::test]
+    async fn test_reset_server_metrics() {
+        use axum::{Router, routing::post};
+
+        let (collector, processor) = MetricsCollector::new();
+        processor.start();
+        collector.increment_counter("bench.runs");
+
+        let state = Arc::new(MetricsServerState {
+            metrics: collector.clone(),
+        });
+        let app = Router::new()
+            .route("/metrics/reset", post(metrics_reset_handler))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config = Config::default();
+        let cmd = MetricsResetCommand::new(config.clone(), addr);
+        let mut ctx = CommandContext::new(config);
+        ctx.json_output = true;
+        assert!(cmd.validate(&ctx).await.is_ok());
+
+        let output = cmd.execute(&mut ctx).await.unwrap();
+        assert!(output.success);
+        assert!(collector.get_counters().is_empty());
+    }
+
+    #[test]
+    fn test_summary_includes_key_figures() {
+        use crate::metrics::{InferenceMetrics, ModelMetrics, ModelStats, SystemMetrics};
+        use std::collections::HashMap;
+
+        let model = ModelStats {
+            name: "llama-7b".to_string(),
+            size_bytes: 4 * 1024 * 1024 * 1024,
+            load_time_ms: 1500,
+            inference_count: 190,
+            total_inference_time_ms: 47_500,
+            backend_type: "gguf".to_string(),
+            device: None,
+        };
+        let snapshot = MetricsSnapshot {
+            timestamp: 0,
+            inference_metrics: InferenceMetrics {
+                total_requests: 200,
+                successful_requests: 190,
+                failed_requests: 10,
+                total_tokens_generated: 25_000,
+                total_inference_time_ms: 50_000,
+                average_tokens_per_second: 500.0,
+                average_latency_ms: 250.0,
+                p95_latency_ms: Some(1200.0),
+            },
+            system_metrics: SystemMetrics {
+                memory_usage_bytes: 512 * 1024 * 1024,
+                process_resident_bytes: 256 * 1024 * 1024,
+                model_mapped_bytes: 0,
+                cpu_usage_percent: 12.5,
+                gpu_memory_usage_bytes: None,
+                gpu_utilization_percent: None,
+                uptime_seconds: 3725,
+            },
+            model_metrics: ModelMetrics {
+                total_model_size_bytes: model.size_bytes,
+                loaded_models: HashMap::from([(model.name.clone(), model)]),
+            },
+            custom_counters: HashMap::new(),
+            custom_gauges: HashMap::new(),
+        };
+
+        let summary = render_summary(&snapshot, false);
+        for figure in [
+            "uptime 1h 02m",
+            "200 total, 10 failed, 95.0% success",
+            "avg 250.0 ms, p95 1.20 s",
+            "500.0 tokens/s, 25000 tokens generated",
+            "12.5% CPU, 512.0 MB memory",
+            "1 loaded (4.0 GB)",
+            "llama-7b",
+            "gguf",
+            "190 inferences",
+        ] {
+            assert!(
+                summary.contains(figure),
+                "missing {:?} in:\n{}",
+                figure,
+                summary
+            );
+        }
+        assert!(!summary.contains('\x1b'));
+
+        // Colors only change the styling, not the figures
+        let colored = render_summary(&snapshot, true);
+        assert!(colored.contains('\x1b'));
+        assert!(colored.contains("95.0% success"));
+    }
 }
@@ -4,6 +4,7 @@ pub mod batch;
 pub mod batch_queue;
 pub mod bench;
 pub mod cache;
+pub mod chat;
 pub mod config;
 pub mod convert;
 pub mod deployment;
@@ -18,6 +19,7 @@ pub mod models;
 pub mod monitoring;
 pub mod observability;
 pub mod optimization;
+pub mod output;
 pub mod performance_benchmark;
 pub mod resilience;
 pub mod response_cache;
@@ -39,6 +41,13 @@ use clap::{Parser, Subcommand};
     author
 )]
 pub struct Cli {
+    #[arg(
+        long,
+        global = true,
+        help = "Log output format: pretty, compact, or json (overrides log_format in the config)"
+    )]
+    pub log_format: Option<crate::logging::LogFormat>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -1,8 +1,14 @@
 use crate::config::Config;
 use crate::models::ModelManager;
+use crate::models::package::{
+    AvailableUpgrade, HuggingFaceRepository, ModelSource, PackageUpgrader, UpgradeStatus,
+};
+use crate::models::verify::{self, ChecksumManifest, VerifyReport};
+use crate::optimization::gguf::{self, GgufInspection};
 use crate::resilience::{RetryConfig, RetryPolicy};
 use anyhow::Result;
-use clap::{Args, Subcommand};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use clap::{Args, Subcommand, ValueEnum};
 use std::path::PathBuf;
 use tracing::info;
 
@@ -15,7 +21,19 @@ pub struct ModelsArgs {
 #[derive(Subcommand)]
 pub enum ModelsCommand {
     #[command(about = "List all available models")]
-    List,
+    List {
+        #[arg(
+            long,
+            help = "Only show installed models with a newer revision in their source repo"
+        )]
+        outdated: bool,
+
+        #[arg(
+            long,
+            help = "Only show models modified after this time (RFC 3339 or YYYY-MM-DD)"
+        )]
+        since: Option<String>,
+    },
 
     #[command(about = "Show detailed information about a model")]
     Info {
@@ -29,12 +47,51 @@ pub enum ModelsCommand {
         path: PathBuf,
     },
 
+    #[command(about = "Verify models concurrently, exiting non-zero if any fails")]
+    Verify {
+        #[arg(
+            help = "Models to verify",
+            required_unless_present = "all",
+            conflicts_with = "all"
+        )]
+        models: Vec<String>,
+
+        #[arg(long, help = "Verify every model in the models directory")]
+        all: bool,
+
+        #[arg(
+            long,
+            help = "sha256sum-style file of checksums to verify models against"
+        )]
+        checksum_manifest: Option<PathBuf>,
+
+        #[arg(long, default_value = "4", help = "Models verified at once")]
+        concurrency: usize,
+
+        #[arg(long, value_enum, default_value = "text", help = "Output format")]
+        format: VerifyFormat,
+    },
+
     #[command(about = "Show model quantization information")]
     Quant {
         #[arg(help = "Model name or path")]
         model: String,
     },
 
+    #[command(about = "List the tensors and metadata of a GGUF model")]
+    Inspect {
+        #[arg(help = "Model name or path")]
+        model: String,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value = "text",
+            help = "Output format; json includes long values in full"
+        )]
+        format: InspectFormat,
+    },
+
     #[command(about = "Search HuggingFace for models")]
     Search {
         #[arg(help = "Search query (e.g. 'llama' or 'mistral instruct')")]
@@ -70,11 +127,34 @@ pub enum ModelsCommand {
 
     #[command(about = "Show usage statistics for local models")]
     Stats,
+
+    #[command(about = "Upgrade installed models to the latest revision in their source repo")]
+    Upgrade {
+        #[arg(help = "Only upgrade this model (default: all installed models)")]
+        model: Option<String>,
+
+        #[arg(long, help = "Show available upgrades without downloading anything")]
+        dry_run: bool,
+    },
+}
+
+/// How `models inspect` prints a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InspectFormat {
+    Text,
+    Json,
+}
+
+/// How `models verify` prints its report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum VerifyFormat {
+    Text,
+    Json,
 }
 
 fn validate_command(command: &ModelsCommand, config: &Config) -> Result<()> {
     match command {
-        ModelsCommand::List | ModelsCommand::Stats => {
+        ModelsCommand::List { .. } | ModelsCommand::Stats | ModelsCommand::Upgrade { .. } => {
             if !config.models_dir.exists() {
                 anyhow::bail!(
                     "Models directory does not exist: {}\nCreate it or configure models_dir.",
@@ -82,7 +162,9 @@ fn validate_command(command: &ModelsCommand, config: &Config) -> Result<()> {
                 );
             }
         }
-        ModelsCommand::Info { model } | ModelsCommand::Quant { model } => {
+        ModelsCommand::Info { model }
+        | ModelsCommand::Quant { model }
+        | ModelsCommand::Inspect { model, .. } => {
             if model.is_empty() {
                 anyhow::bail!("Model name or path cannot be empty.");
             }
@@ -95,6 +177,27 @@ fn validate_command(command: &ModelsCommand, config: &Config) -> Result<()> {
                 anyhow::bail!("Path is not a file: {}", path.display());
             }
         }
+        ModelsCommand::Verify {
+            all,
+            checksum_manifest,
+            concurrency,
+            ..
+        } => {
+            if *all && !config.models_dir.exists() {
+                anyhow::bail!(
+                    "Models directory does not exist: {}",
+                    config.models_dir.display()
+                );
+            }
+            if let Some(manifest) = checksum_manifest
+                && !manifest.is_file()
+            {
+                anyhow::bail!("Checksum manifest does not exist: {}", manifest.display());
+            }
+            if *concurrency == 0 {
+                anyhow::bail!("Concurrency must be at least 1.");
+            }
+        }
         ModelsCommand::Search { query, .. } => {
             if query.is_empty() {
                 anyhow::bail!("Search query cannot be empty.");
@@ -120,16 +223,51 @@ fn validate_command(command: &ModelsCommand, config: &Config) -> Result<()> {
 pub async fn execute(args: ModelsArgs, config: &Config) -> Result<()> {
     validate_command(&args.command, config)?;
 
-    let model_manager = ModelManager::new(&config.models_dir);
+    let model_manager = ModelManager::from_config(config);
 
     match args.command {
-        ModelsCommand::List => {
+        ModelsCommand::List { outdated: true, .. } => {
+            let upgrader =
+                PackageUpgrader::new(model_manager.clone(), HuggingFaceRepository::default());
+            println!("Checking installed models against their source repos...");
+            let upgrades = upgrader.check_upgrades().await?;
+
+            if upgrades.is_empty() {
+                println!("All installed models are up to date.");
+                return Ok(());
+            }
+
+            for line in render_outdated(&upgrades) {
+                println!("{}", line);
+            }
+            println!("\nRun `inferno models upgrade` to update them.");
+        }
+
+        ModelsCommand::List {
+            outdated: false,
+            since,
+        } => {
             info!("Scanning for models in: {}", config.models_dir.display());
-            let models = model_manager.list_models().await?;
+            let since = since.as_deref().map(parse_since).transpose()?;
+            let models = match since {
+                Some(since) => model_manager.list_models_modified_since(since).await?,
+                None => model_manager.list_models().await?,
+            };
 
             if models.is_empty() {
-                println!("No models found in: {}", config.models_dir.display());
-                println!("Place GGUF (*.gguf) or ONNX (*.onnx) models in the models directory.");
+                match since {
+                    Some(since) => println!(
+                        "No models modified since {} in: {}",
+                        since.to_rfc3339(),
+                        config.models_dir.display()
+                    ),
+                    None => {
+                        println!("No models found in: {}", config.models_dir.display());
+                        println!(
+                            "Place GGUF (*.gguf) or ONNX (*.onnx) models in the models directory."
+                        );
+                    }
+                }
                 return Ok(());
             }
 
@@ -236,6 +374,47 @@ pub async fn execute(args: ModelsArgs, config: &Config) -> Result<()> {
             }
         }
 
+        ModelsCommand::Verify {
+            models,
+            all,
+            checksum_manifest,
+            concurrency,
+            format,
+        } => {
+            let manifest = checksum_manifest
+                .as_deref()
+                .map(ChecksumManifest::load)
+                .transpose()?;
+            let mut model_infos = Vec::with_capacity(models.len());
+            for model in &models {
+                model_infos.push(model_manager.resolve_model(model).await?);
+            }
+            if all {
+                model_infos = model_manager.list_models().await?;
+                model_infos.sort_by(|a, b| a.name.cmp(&b.name));
+            }
+
+            let mut report = verify::verify_models(
+                &model_manager,
+                &model_infos,
+                manifest.as_ref(),
+                Some(config),
+                concurrency,
+            )
+            .await;
+            // Only a full run can tell that a listed model has gone missing
+            if all && let Some(manifest) = &manifest {
+                report.record_missing(manifest);
+            }
+            match format {
+                VerifyFormat::Text => print!("{}", render_verify_report(&report)),
+                VerifyFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            }
+            if report.exit_code() != 0 {
+                std::process::exit(report.exit_code());
+            }
+        }
+
         ModelsCommand::Quant { model } => {
             let model_info = model_manager.resolve_model(&model).await?;
             if model_info.backend_type == "gguf" {
@@ -250,6 +429,19 @@ pub async fn execute(args: ModelsArgs, config: &Config) -> Result<()> {
             }
         }
 
+        ModelsCommand::Inspect { model, format } => {
+            let model_info = model_manager.resolve_model(&model).await?;
+            if model_info.backend_type != "gguf" {
+                anyhow::bail!("Tensor inspection is only available for GGUF models");
+            }
+            let path = model_info.path.clone();
+            let inspection = tokio::task::spawn_blocking(move || gguf::inspect(&path)).await??;
+            match format {
+                InspectFormat::Text => print!("{}", render_inspection(&inspection)),
+                InspectFormat::Json => println!("{}", serde_json::to_string_pretty(&inspection)?),
+            }
+        }
+
         ModelsCommand::Search { query, task, limit } => {
             println!("Searching HuggingFace for '{}'...", query);
             match search_huggingface(&query, task.as_deref(), limit).await {
@@ -318,7 +510,7 @@ pub async fn execute(args: ModelsArgs, config: &Config) -> Result<()> {
             } else {
                 // HuggingFace repo ID
                 println!("Looking up '{}' on HuggingFace...", model);
-                let files = list_hf_gguf_files(&model).await?;
+                let (files, revision) = list_hf_gguf_files(&model).await?;
                 if files.is_empty() {
                     anyhow::bail!("No GGUF files found in repo '{}'.", model);
                 }
@@ -357,10 +549,29 @@ pub async fn execute(args: ModelsArgs, config: &Config) -> Result<()> {
                     anyhow::bail!("Invalid filename: path traversal not allowed");
                 }
                 let dest = config.models_dir.join(&out_name);
-                let url = format!("https://huggingface.co/{}/resolve/main/{}", model, fname);
+                let url = format!(
+                    "https://huggingface.co/{}/resolve/{}/{}",
+                    model,
+                    revision.as_deref().unwrap_or("main"),
+                    fname
+                );
                 println!("Downloading {}...", fname);
                 download_to_file(&url, &dest).await?;
                 post_install(&model_manager, &dest).await?;
+
+                // Remember where the file came from so `models upgrade` can find newer revisions
+                if let Some(revision) = revision {
+                    model_manager
+                        .set_model_source(
+                            &dest,
+                            ModelSource {
+                                repo_id: model.clone(),
+                                filename: fname.clone(),
+                                revision,
+                            },
+                        )
+                        .await?;
+                }
             }
         }
 
@@ -397,6 +608,49 @@ pub async fn execute(args: ModelsArgs, config: &Config) -> Result<()> {
                 );
             }
         }
+
+        ModelsCommand::Upgrade { model, dry_run } => {
+            let upgrader =
+                PackageUpgrader::new(model_manager.clone(), HuggingFaceRepository::default());
+            println!("Checking installed models for upgrades...");
+            let results = upgrader.upgrade(model.as_deref(), dry_run).await?;
+
+            if results.is_empty() {
+                println!("All installed models are up to date.");
+                return Ok(());
+            }
+
+            println!(
+                "\n{:<35} {:<30} {:<14} {:<14} Status",
+                "Model", "Repo", "Current", "Available"
+            );
+            println!("{}", "─".repeat(110));
+            let mut failed = 0;
+            for result in &results {
+                let status = match &result.status {
+                    UpgradeStatus::Planned => "would upgrade".to_string(),
+                    UpgradeStatus::Upgraded => "✓ upgraded".to_string(),
+                    UpgradeStatus::Failed(e) => {
+                        failed += 1;
+                        format!("✗ {}", e)
+                    }
+                };
+                println!(
+                    "{:<35} {:<30} {:<14} {:<14} {}",
+                    truncate(&result.upgrade.name, 34),
+                    truncate(&result.upgrade.current.repo_id, 29),
+                    short_revision(&result.upgrade.current.revision),
+                    short_revision(&result.upgrade.latest_revision),
+                    status
+                );
+            }
+
+            if dry_run {
+                println!("\nDry run: no files were changed. Re-run without --dry-run to upgrade.");
+            } else if failed > 0 {
+                anyhow::bail!("{} model upgrade(s) failed", failed);
+            }
+        }
     }
 
     Ok(())
@@ -484,8 +738,9 @@ async fn search_huggingface(
     Ok(results)
 }
 
-/// List `.gguf` files in a HuggingFace repo, returning (filename, optional_size).
-async fn list_hf_gguf_files(repo_id: &str) -> Result<Vec<(String, Option<u64>)>> {
+/// List `.gguf` files in a HuggingFace repo, returning (filename, optional_size) pairs
+/// along with the repo revision (commit SHA) the listing was taken from.
+async fn list_hf_gguf_files(repo_id: &str) -> Result<(Vec<(String, Option<u64>)>, Option<String>)> {
     let client = reqwest::Client::builder()
         .user_agent("inferno/1.0")
         .build()?;
@@ -512,8 +767,9 @@ async fn list_hf_gguf_files(repo_id: &str) -> Result<Vec<(String, Option<u64>)>>
             }
         })
         .collect();
+    let revision = raw["sha"].as_str().map(|s| s.to_string());
 
-    Ok(files)
+    Ok((files, revision))
 }
 
 /// Stream-download a URL to a local file with progress reporting.
@@ -572,6 +828,11 @@ async fn async_std_create_dir(path: &PathBuf) -> Result<()> {
 /// Validate newly installed model and register it.
 async fn post_install(manager: &ModelManager, path: &PathBuf) -> Result<()> {
     print!("Validating...");
+    if let Err(e) = crate::models::check_download(path).await {
+        println!(" ✗");
+        tokio::fs::remove_file(path).await.ok();
+        anyhow::bail!("{} — removed.", e);
+    }
     let valid = manager.validate_model(path).await?;
     if valid {
         println!(" ✓");
@@ -585,13 +846,27 @@ async fn post_install(manager: &ModelManager, path: &PathBuf) -> Result<()> {
     Ok(())
 }
 
+/// Parse a `--since` cutoff, either RFC 3339 or a bare date taken as midnight UTC
+pub(crate) fn parse_since(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        anyhow::anyhow!(
+            "Invalid --since time '{}': use RFC 3339 (2024-01-31T12:00:00Z) or YYYY-MM-DD",
+            value
+        )
+    })?;
+    Ok(date.and_time(NaiveTime::MIN).and_utc())
+}
+
 fn mb(bytes: u64) -> f64 {
     bytes as f64 / 1_048_576.0
 }
 
 // ── Formatting helpers ────────────────────────────────────────────────────────
 
-fn format_size(bytes: u64) -> String {
+pub(crate) fn format_size(bytes: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
     let mut size = bytes as f64;
     let mut unit_index = 0;
@@ -602,6 +877,101 @@ fn format_size(bytes: u64) -> String {
     format!("{:.1} {}", size, UNITS[unit_index])
 }
 
+/// `models inspect` text output: the metadata table, then the tensor table
+fn render_inspection(inspection: &GgufInspection) -> String {
+    let mut out = format!(
+        "GGUF v{}, {} metadata keys, {} tensors\n\nMetadata:\n",
+        inspection.version,
+        inspection.metadata.len(),
+        inspection.tensors.len()
+    );
+    let key_width = inspection
+        .metadata
+        .iter()
+        .map(|entry| entry.key.len())
+        .max()
+        .unwrap_or(0);
+    let type_width = inspection
+        .metadata
+        .iter()
+        .map(|entry| entry.value_type.len())
+        .max()
+        .unwrap_or(0);
+    for entry in &inspection.metadata {
+        out.push_str(&format!(
+            "  {:key_width$}  {:type_width$}  {}\n",
+            entry.key,
+            entry.value_type,
+            metadata_summary(&entry.value)
+        ));
+    }
+
+    let rows: Vec<[String; 4]> = inspection
+        .tensors
+        .iter()
+        .map(|tensor| {
+            let shape = tensor
+                .shape
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join(" x ");
+            let size = tensor.size_bytes.map_or("?".to_string(), format_size);
+            [tensor.name.clone(), shape, tensor.dtype.clone(), size]
+        })
+        .collect();
+    let header = ["NAME", "SHAPE", "TYPE", "SIZE"].map(str::to_string);
+    let widths: Vec<usize> = (0..4)
+        .map(|column| {
+            std::iter::once(&header)
+                .chain(&rows)
+                .map(|row| row[column].len())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    out.push_str("\nTensors:\n");
+    for row in std::iter::once(&header).chain(&rows) {
+        out.push_str(&format!(
+            "  {:w0$}  {:w1$}  {:w2$}  {:>w3$}\n",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3]
+        ));
+    }
+    out
+}
+
+/// A metadata value on one line: long strings are cut short and long arrays
+/// show their first few items
+fn metadata_summary(value: &serde_json::Value) -> String {
+    const MAX_CHARS: usize = 80;
+    const MAX_ITEMS: usize = 5;
+    match value {
+        serde_json::Value::String(text) => {
+            let text = text.replace('\n', "\\n");
+            if text.chars().count() > MAX_CHARS {
+                format!("{}…", text.chars().take(MAX_CHARS).collect::<String>())
+            } else {
+                text
+            }
+        }
+        serde_json::Value::Array(items) if items.len() > MAX_ITEMS => {
+            let shown: Vec<String> = items[..MAX_ITEMS]
+                .iter()
+                .map(|item| item.to_string())
+                .collect();
+            format!("[{}, … ({} items)]", shown.join(", "), items.len())
+        }
+        other => other.to_string(),
+    }
+}
+
 fn format_params(count: u64) -> String {
     if count >= 1_000_000_000 {
         format!("{:.1}B", count as f64 / 1_000_000_000.0)
@@ -629,6 +999,62 @@ fn estimate_vram_usage(metadata: &crate::models::GgufMetadata) -> String {
     format!("{:.1} GB", base_gb * 1.2)
 }
 
+/// Table of outdated models with their installed and available revisions.
+fn render_outdated(upgrades: &[AvailableUpgrade]) -> Vec<String> {
+    let mut lines = vec![
+        format!(
+            "{:<35} {:<35} {:<14} {:<14}",
+            "Name", "Repo", "Current", "Available"
+        ),
+        "─".repeat(100),
+    ];
+    for upgrade in upgrades {
+        lines.push(format!(
+            "{:<35} {:<35} {:<14} {:<14}",
+            truncate(&upgrade.name, 34),
+            truncate(&upgrade.current.repo_id, 34),
+            short_revision(&upgrade.current.revision),
+            short_revision(&upgrade.latest_revision)
+        ));
+    }
+    lines
+}
+
+fn short_revision(revision: &str) -> String {
+    revision.chars().take(12).collect()
+}
+
+fn render_verify_report(report: &VerifyReport) -> String {
+    if report.models.is_empty() {
+        return "No models to verify\n".to_string();
+    }
+    let name_width = report
+        .models
+        .iter()
+        .map(|model| model.name.len())
+        .chain(["Model".len()])
+        .max()
+        .unwrap_or(0);
+    let mut out = format!("{:name_width$}  {:6}  Failed checks\n", "Model", "Result");
+    for model in &report.models {
+        out.push_str(&format!(
+            "{:name_width$}  {:6}  {}\n",
+            model.name,
+            if model.passed { "pass" } else { "FAIL" },
+            model.failed_checks.join(", ")
+        ));
+        for error in &model.errors {
+            out.push_str(&format!("    ✗ {}\n", error));
+        }
+    }
+    out.push_str(&format!(
+        "\n{} of {} models passed\n",
+        report.models.len() - report.failed(),
+        report.models.len()
+    ));
+    out
+}
+
 fn truncate(s: &str, max: usize) -> String {
     if s.len() <= max {
         s.to_string()
@@ -640,6 +1066,8 @@ fn truncate(s: &str, max: usize) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::package::ModelRepository;
+    use std::path::Path;
 
     #[test]
     fn test_format_size() {
@@ -653,6 +1081,21 @@ mod tests {
         assert_eq!(format_size(1_610_612_736), "1.5 GB");
     }
 
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(
+            parse_since("2024-01-31T12:00:00+02:00")
+                .unwrap()
+                .to_rfc3339(),
+            "2024-01-31T10:00:00+00:00"
+        );
+        assert_eq!(
+            parse_since("2024-01-31").unwrap().to_rfc3339(),
+            "2024-01-31T00:00:00+00:00"
+        );
+        assert!(parse_since("yesterday").is_err());
+    }
+
     #[test]
     fn test_format_params() {
         assert_eq!(format_params(500), "500");
@@ -679,6 +1122,64 @@ mod tests {
         assert_eq!(format_params(1_000_000_000), "1.0B");
     }
 
+    /// Repository where every repo's latest revision is `rev2`
+    struct FixedRevisionRepository;
+
+    #[async_trait::async_trait]
+    impl ModelRepository for FixedRevisionRepository {
+        async fn latest_revision(&self, _repo_id: &str) -> Result<String> {
+            Ok("rev2".to_string())
+        }
+
+        async fn download(&self, _source: &ModelSource, _dest: &Path) -> Result<()> {
+            anyhow::bail!("downloads are not expected when listing")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_outdated_shows_only_outdated_models() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ModelManager::new(dir.path());
+        for (name, revision) in [("stale.gguf", "rev1"), ("fresh.gguf", "rev2")] {
+            let path = dir.path().join(name);
+            tokio::fs::write(&path, b"GGUF\x03\x00\x00\x00data")
+                .await
+                .unwrap();
+            manager
+                .set_model_source(
+                    &path,
+                    ModelSource {
+                        repo_id: format!("org/{}", name),
+                        filename: name.to_string(),
+                        revision: revision.to_string(),
+                    },
+                )
+                .await
+                .unwrap();
+        }
+        // A local model with no recorded source is never reported
+        tokio::fs::write(dir.path().join("local.gguf"), b"GGUF\x03\x00\x00\x00data")
+            .await
+            .unwrap();
+
+        let upgrader = PackageUpgrader::new(manager, FixedRevisionRepository);
+        let upgrades = upgrader.check_upgrades().await.unwrap();
+        let lines = render_outdated(&upgrades);
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].starts_with("stale.gguf"));
+        assert!(lines[2].contains("rev1"));
+        assert!(lines[2].contains("rev2"));
+        assert!(!lines.iter().any(|l| l.contains("fresh.gguf")));
+        assert!(!lines.iter().any(|l| l.contains("local.gguf")));
+    }
+
+    #[test]
+    fn test_short_revision() {
+        assert_eq!(short_revision("0123456789abcdef0123"), "0123456789ab");
+        assert_eq!(short_revision("main"), "main");
+    }
+
     #[test]
     fn test_truncate() {
         assert_eq!(truncate("hello", 10), "hello");
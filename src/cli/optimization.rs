@@ -38,7 +38,8 @@ pub enum OptimizationCommand {
         #[arg(short, long, default_value = "int8")]
         precision: String,
 
-        /// Target format for quantized model
+        /// Target format for quantized model; for GGUF models, the quantization
+        /// type to write (f32, f16, q4_0, q5_0, q8_0, q4_k_s, q4_k_m, q5_k_s, q5_k_m, q6_k)
         #[arg(short, long, default_value = "")]
         format: String,
 
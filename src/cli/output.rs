@@ -0,0 +1,214 @@
+//! Laying out generated text for the terminal
+//!
+//! Model output often comes as long unbroken paragraphs. When stdout is a
+//! terminal, `inferno run` wraps it at word boundaries to the terminal width and
+//! can cut it off after a number of lines, ending it with an ellipsis. Output
+//! piped elsewhere is never wrapped, so scripts get the text as generated.
+
+use std::io::IsTerminal;
+
+const ELLIPSIS: &str = "…";
+
+/// How text is laid out: the width lines wrap at and the lines kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextLayout {
+    pub width: Option<usize>,
+    pub max_lines: Option<usize>,
+}
+
+impl TextLayout {
+    /// Layout for stdout, wrapping to the terminal's width if `wrap` is set and
+    /// stdout is a terminal
+    pub fn for_stdout(wrap: bool, max_lines: Option<usize>) -> Self {
+        let columns = crossterm::terminal::size().ok().map(|(columns, _)| columns);
+        Self {
+            width: wrap_width(wrap, std::io::stdout().is_terminal(), columns),
+            max_lines,
+        }
+    }
+
+    pub fn writer(&self) -> TextWriter {
+        TextWriter {
+            layout: *self,
+            column: 0,
+            lines: 0,
+            spaces: 0,
+            word: String::new(),
+            word_len: 0,
+            truncated: false,
+        }
+    }
+
+    /// `text` laid out in full
+    pub fn apply(&self, text: &str) -> String {
+        let mut writer = self.writer();
+        let mut out = writer.push(text);
+        out.push_str(&writer.finish());
+        out
+    }
+}
+
+/// Width to wrap at: the terminal's, unless wrapping is off or the output does
+/// not go to a terminal
+pub fn wrap_width(wrap: bool, is_terminal: bool, columns: Option<u16>) -> Option<usize> {
+    if !wrap || !is_terminal {
+        return None;
+    }
+    columns.map(usize::from).filter(|&columns| columns > 0)
+}
+
+/// Lays out text that arrives in pieces, such as streamed tokens
+///
+/// The current word is held back until it ends, since only then is it known
+/// whether it fits on the line.
+pub struct TextWriter {
+    layout: TextLayout,
+    column: usize,
+    /// Lines ended so far
+    lines: usize,
+    /// Spaces before the held-back word, dropped if it wraps
+    spaces: usize,
+    word: String,
+    word_len: usize,
+    truncated: bool,
+}
+
+impl TextWriter {
+    /// Laid out text for the next piece; without a width or line limit the
+    /// text is passed through as is
+    pub fn push(&mut self, text: &str) -> String {
+        if self.layout == TextLayout::default() {
+            return text.to_string();
+        }
+        let mut out = String::new();
+        for c in text.chars() {
+            match c {
+                '\n' => {
+                    self.flush_word(&mut out);
+                    self.spaces = 0;
+                    self.end_line(&mut out);
+                }
+                '\r' => {}
+                ' ' | '\t' => {
+                    self.flush_word(&mut out);
+                    self.spaces += if c == '\t' { 4 } else { 1 };
+                }
+                _ => {
+                    self.word.push(c);
+                    self.word_len += 1;
+                    match self.layout.width {
+                        // A word as wide as the terminal is split
+                        Some(width) if self.word_len >= width => self.flush_word(&mut out),
+                        Some(_) => {}
+                        None => self.flush_word(&mut out),
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// The rest of the text, and the ellipsis if it was truncated
+    pub fn finish(&mut self) -> String {
+        let mut out = String::new();
+        self.flush_word(&mut out);
+        if self.truncated {
+            out.push_str(ELLIPSIS);
+        }
+        out
+    }
+
+    /// Whether lines were cut off
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn flush_word(&mut self, out: &mut String) {
+        if self.word.is_empty() {
+            return;
+        }
+        if let Some(width) = self.layout.width
+            && self.column > 0
+            && self.column + self.spaces + self.word_len > width
+        {
+            self.spaces = 0;
+            self.end_line(out);
+        }
+        if self.room_for_line() {
+            out.extend(std::iter::repeat_n(' ', self.spaces));
+            out.push_str(&self.word);
+            self.column += self.spaces + self.word_len;
+        }
+        self.spaces = 0;
+        self.word.clear();
+        self.word_len = 0;
+    }
+
+    fn end_line(&mut self, out: &mut String) {
+        if self.room_for_line() {
+            out.push('\n');
+            self.lines += 1;
+            self.column = 0;
+        }
+    }
+
+    /// Whether the current line may still be written to, marking the text
+    /// truncated when not
+    fn room_for_line(&mut self) -> bool {
+        if self
+            .layout
+            .max_lines
+            .is_some_and(|max_lines| self.lines >= max_lines)
+        {
+            self.truncated = true;
+        }
+        !self.truncated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrapping_follows_terminal_and_stops_when_piped() {
+        let text = "the quick brown fox jumps over the lazy dog";
+
+        let tty = TextLayout {
+            width: wrap_width(true, true, Some(16)),
+            max_lines: None,
+        };
+        assert_eq!(
+            tty.apply(text),
+            "the quick brown\nfox jumps over\nthe lazy dog"
+        );
+
+        // Streamed pieces break at the same places
+        let mut writer = tty.writer();
+        let mut streamed: String = ["the qu", "ick brown f", "ox jumps over the", " lazy dog"]
+            .into_iter()
+            .map(|token| writer.push(token))
+            .collect();
+        streamed.push_str(&writer.finish());
+        assert_eq!(streamed, tty.apply(text));
+
+        let piped = TextLayout {
+            width: wrap_width(true, false, Some(16)),
+            max_lines: None,
+        };
+        assert_eq!(piped.width, None);
+        assert_eq!(piped.apply(text), text);
+        let code = "fn main() {\r\n\tprintln!(\"hi\");  \r\n}\n";
+        assert_eq!(piped.apply(code), code);
+        assert_eq!(wrap_width(false, true, Some(16)), None);
+
+        let truncated = TextLayout {
+            max_lines: Some(2),
+            ..tty
+        };
+        let mut writer = truncated.writer();
+        let out = writer.push(text) + &writer.finish();
+        assert_eq!(out, "the quick brown\nfox jumps over\n…");
+        assert!(writer.truncated());
+    }
+}
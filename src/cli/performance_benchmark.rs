@@ -441,10 +441,17 @@ async fn run_inference_benchmark(
         max_tokens: 50,
         temperature: 0.7,
         top_k: 40,
+        repeat_penalty: None,
+        repeat_last_n: None,
+        min_p: None,
         top_p: 0.9,
         stream: false,
         stop_sequences: vec![],
+        stop_regex: None,
+        max_generation_ms: None,
         seed: None,
+        response_format: None,
+        logprobs: None,
     };
 
     let test_prompts = vec![
@@ -600,10 +607,17 @@ async fn run_memory_benchmark(
                 max_tokens: 20,
                 temperature: 0.7,
                 top_k: 40,
+                repeat_penalty: None,
+                repeat_last_n: None,
+                min_p: None,
                 top_p: 0.9,
                 stream: false,
                 stop_sequences: vec![],
+                stop_regex: None,
+                max_generation_ms: None,
                 seed: None,
+                response_format: None,
+                logprobs: None,
             };
 
             for _ in 0..5 {
@@ -725,10 +739,17 @@ async fn run_concurrent_benchmark(
             max_tokens: 30,
             temperature: 0.7,
             top_k: 40,
+            repeat_penalty: None,
+            repeat_last_n: None,
+            min_p: None,
             top_p: 0.9,
             stream: false,
             stop_sequences: vec![],
+            stop_regex: None,
+            max_generation_ms: None,
             seed: None,
+            response_format: None,
+            logprobs: None,
         };
 
         let start_time = Instant::now();
@@ -1263,10 +1284,17 @@ async fn memory_profile(
         max_tokens: 50,
         temperature: 0.7,
         top_k: 40,
+        repeat_penalty: None,
+        repeat_last_n: None,
+        min_p: None,
         top_p: 0.9,
         stream: false,
         stop_sequences: vec![],
+        stop_regex: None,
+        max_generation_ms: None,
         seed: Some(42),
+        response_format: None,
+        logprobs: None,
     };
 
     for cycle in 1..=cycles {
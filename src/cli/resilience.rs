@@ -320,6 +320,7 @@ async fn test_resilience_pattern(pattern: String, requests: u32, failure_rate: f
                     success_threshold: 2,
                     timeout_ms: 1000,
                     max_concurrent_requests: 10,
+                    ..Default::default()
                 },
             )?;
 
@@ -1,13 +1,27 @@
 #![allow(dead_code, unused_imports, unused_variables)]
-use crate::backends::{Backend, BackendType};
+use crate::ai_features::transcription::{DEFAULT_WINDOW_SECS, load_audio, transcribe};
+use crate::backends::{
+    Backend, BackendType, CancellationToken, PostProcessor, TokenStream, blocking,
+    load_speech_model,
+};
+use crate::cli::chat::{ChatSession, run_repl};
+use crate::cli::output::TextLayout;
 use crate::config::Config;
+use crate::infrastructure::profiling::{InferenceProfile, OperationProfile, PhaseTimer};
 use crate::io::{InputFormat, OutputFormat};
-use crate::models::ModelManager;
+use crate::models::{
+    ModelManager,
+    chat_template::{BuiltinTemplate, ChatTemplate},
+};
 use anyhow::Result;
-use clap::Args;
+use clap::{Args, ValueEnum};
 use futures::StreamExt;
-use std::path::PathBuf;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::io::{self, AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
 use tracing::{info, warn};
 
 #[derive(Args)]
@@ -58,6 +72,22 @@ pub struct RunArgs {
     #[arg(long, help = "Top-p for text generation", default_value = "0.9")]
     pub top_p: f32,
 
+    #[arg(long, help = "Penalty for repeating recent tokens (1.0 = none)")]
+    pub repeat_penalty: Option<f32>,
+
+    #[arg(long, help = "Number of recent tokens the repeat penalty covers")]
+    pub repeat_last_n: Option<u32>,
+
+    #[arg(long, help = "Min-p cutoff relative to the most likely token")]
+    pub min_p: Option<f32>,
+
+    #[arg(
+        long,
+        value_name = "TEXT",
+        help = "Stop when the model starts writing this text, such as the user turn marker; repeatable"
+    )]
+    pub antiprompt: Vec<String>,
+
     #[arg(long, help = "Enable streaming output")]
     pub stream: bool,
 
@@ -66,6 +96,118 @@ pub struct RunArgs {
 
     #[arg(long, help = "Backend to use", value_enum)]
     pub backend: Option<BackendType>,
+
+    #[arg(
+        long,
+        help = "Start an interactive chat session that keeps the conversation",
+        conflicts_with_all = ["batch", "prompt", "input"]
+    )]
+    pub chat: bool,
+
+    #[arg(
+        long,
+        help = "Token budget for the chat conversation; the oldest messages are dropped beyond it",
+        default_value = "4096"
+    )]
+    pub context_tokens: u32,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Save the chat conversation to this JSON file when the session ends",
+        requires = "chat"
+    )]
+    pub save_session: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Continue the chat conversation saved in this JSON file",
+        requires = "chat"
+    )]
+    pub load_session: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Post-process the completion: none, trim, json or regex:<pattern>",
+        conflicts_with_all = ["chat", "stream"]
+    )]
+    pub post_process: Option<String>,
+
+    #[arg(long, help = "Don't wrap long lines to the terminal width")]
+    pub no_wrap: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Cut the output off after N lines, ending it with an ellipsis"
+    )]
+    pub max_lines: Option<usize>,
+
+    #[arg(long, help = "Print the output in full, unwrapped and untruncated")]
+    pub full: bool,
+
+    #[arg(
+        long,
+        help = "Time each phase of the run and print the breakdown to stderr",
+        conflicts_with_all = ["chat", "batch"]
+    )]
+    pub profile: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Format of the --profile breakdown",
+        requires = "profile"
+    )]
+    pub format: Option<ProfileFormat>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "With --input-type audio, transcribe this many seconds at a time; --stream prints each as it is done",
+        default_value_t = DEFAULT_WINDOW_SECS
+    )]
+    pub window_secs: f32,
+}
+
+/// How `--profile` prints its breakdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProfileFormat {
+    Text,
+    /// The full profile as JSON
+    Json,
+}
+
+impl RunArgs {
+    /// Generation parameters from the command line, stopping at any antiprompt
+    fn inference_params(&self, stream: bool) -> crate::backends::InferenceParams {
+        crate::backends::InferenceParams {
+            max_tokens: self.max_tokens,
+            temperature: self.temperature,
+            top_k: self.top_k,
+            repeat_penalty: self.repeat_penalty,
+            repeat_last_n: self.repeat_last_n,
+            min_p: self.min_p,
+            top_p: self.top_p,
+            stream,
+            stop_sequences: self.antiprompt.clone(),
+            stop_regex: antiprompt_regex(&self.antiprompt),
+            max_generation_ms: None,
+            seed: None,
+            response_format: None,
+            logprobs: None,
+        }
+    }
+
+    /// Layout of text printed to stdout; output piped elsewhere is not wrapped
+    fn text_layout(&self) -> TextLayout {
+        if self.full {
+            return TextLayout::default();
+        }
+        TextLayout::for_stdout(!self.no_wrap, self.max_lines)
+    }
 }
 
 pub async fn execute(args: RunArgs, config: &Config) -> Result<()> {
@@ -73,21 +215,18 @@ pub async fn execute(args: RunArgs, config: &Config) -> Result<()> {
     if args.model.is_empty() {
         anyhow::bail!("Model name cannot be empty");
     }
-    if args.max_tokens == 0 {
-        anyhow::bail!("max_tokens must be greater than 0");
-    }
-    if !(0.0..=2.0).contains(&args.temperature) {
-        anyhow::bail!("temperature must be between 0.0 and 2.0");
-    }
-    if !(0.0..=1.0).contains(&args.top_p) {
-        anyhow::bail!("top_p must be between 0.0 and 1.0");
-    }
+    args.inference_params(args.stream).validate()?;
+    let post_processor = PostProcessor::from_option(args.post_process.as_deref())?;
 
     info!("Running inference with model: {}", args.model);
 
-    let model_manager = ModelManager::new(&config.models_dir);
+    let model_manager = ModelManager::from_config(config);
     let model_info = model_manager.resolve_model(&args.model).await?;
 
+    if matches!(args.input_type, InputFormat::Audio) {
+        return transcribe_audio(&args, config, &model_info.path).await;
+    }
+
     let backend_type = args
         .backend
         .or_else(|| BackendType::from_model_path(&model_info.path))
@@ -101,7 +240,9 @@ pub async fn execute(args: RunArgs, config: &Config) -> Result<()> {
     let mut backend = Backend::new(backend_type, &config.backend_config)?;
     backend.load_model(&model_info).await?;
 
-    if args.batch {
+    if args.chat {
+        run_chat(&mut backend, &args, &model_info.path).await?;
+    } else if args.batch {
         // Use enhanced batch processing
         use crate::batch::{BatchConfig, BatchProcessor};
 
@@ -113,6 +254,11 @@ pub async fn execute(args: RunArgs, config: &Config) -> Result<()> {
             output_format: crate::batch::BatchOutputFormat::JsonLines,
             continue_on_error: true,
             shuffle_inputs: false,
+            post_process: args.post_process.clone(),
+            input_column: None,
+            id_column: None,
+            prompt_template: None,
+            lenient_template: false,
         };
 
         let input_path = args
@@ -123,15 +269,7 @@ pub async fn execute(args: RunArgs, config: &Config) -> Result<()> {
         let total_items = estimate_batch_size(input_path).await?;
         let processor = BatchProcessor::new(batch_config, total_items);
 
-        let inference_params = crate::backends::InferenceParams {
-            max_tokens: args.max_tokens,
-            temperature: args.temperature,
-            top_k: args.top_k,
-            top_p: args.top_p,
-            stream: false,
-            stop_sequences: vec![],
-            seed: None,
-        };
+        let inference_params = args.inference_params(false);
 
         let progress = processor
             .process_file(
@@ -147,9 +285,101 @@ pub async fn execute(args: RunArgs, config: &Config) -> Result<()> {
             progress.completed_items, progress.total_items
         );
     } else {
-        process_single(&mut backend, &args, config).await?;
+        process_single(&mut backend, &args, &model_info.name, &post_processor).await?;
+    }
+
+    Ok(())
+}
+
+/// Transcribe the `--input` audio file with the speech model at `model_path`,
+/// printing each window's text as it is done when streaming
+async fn transcribe_audio(args: &RunArgs, config: &Config, model_path: &Path) -> Result<()> {
+    let input = args
+        .input
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Audio input needs --input with a WAV file"))?;
+    let audio = load_audio(input).await?;
+    let mut model = load_speech_model(model_path, &config.backend_config).await?;
+    info!(
+        "Transcribing {:.1}s of audio from {}",
+        audio.duration_secs(),
+        input.display()
+    );
+
+    let stream = args.stream;
+    let window_secs = args.window_secs;
+    let transcript = blocking::run(move || {
+        transcribe(model.as_mut(), &audio, window_secs, |segment| {
+            if stream && !segment.text.is_empty() {
+                println!("[{:7.1}s] {}", segment.start_secs, segment.text);
+            }
+        })
+    })
+    .await??;
+
+    if let Some(output_path) = &args.output {
+        tokio::fs::write(output_path, &transcript).await?;
+        info!("Transcript written to: {}", output_path.display());
+    } else if !stream {
+        println!("{}", transcript);
     }
+    Ok(())
+}
+
+async fn run_chat(backend: &mut Backend, args: &RunArgs, model_path: &Path) -> Result<()> {
+    let template = ChatTemplate::for_model(model_path).unwrap_or_else(|e| {
+        warn!(
+            "Could not read the model's chat template, using ChatML: {}",
+            e
+        );
+        ChatTemplate::builtin(BuiltinTemplate::ChatMl)
+    });
+    let mut session = ChatSession::new(template, args.context_tokens);
+    if let Some(path) = &args.load_session {
+        session.load(path)?;
+        println!(
+            "Continuing the conversation of {} messages saved in {}",
+            session.history().len(),
+            path.display()
+        );
+    }
+
+    let params = args.inference_params(true);
 
+    // A blocking stdin read cannot be cancelled, so lines come from a detached thread
+    // that does not hold up shutdown
+    let (lines, mut input) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    let (interrupt, mut interrupts) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if interrupt.send(()).is_err() {
+                break;
+            }
+        }
+    });
+
+    run_repl(
+        backend,
+        &mut session,
+        &params,
+        &mut input,
+        &mut interrupts,
+        &mut std::io::stdout(),
+    )
+    .await?;
+
+    if let Some(path) = &args.save_session {
+        session.save(path)?;
+        println!("Conversation saved to {}", path.display());
+    }
     Ok(())
 }
 
@@ -188,7 +418,12 @@ async fn estimate_batch_size(input_path: &std::path::Path) -> Result<usize> {
     Ok(count)
 }
 
-async fn process_single(backend: &mut Backend, args: &RunArgs, _config: &Config) -> Result<()> {
+async fn process_single(
+    backend: &mut Backend,
+    args: &RunArgs,
+    model_id: &str,
+    post_processor: &PostProcessor,
+) -> Result<()> {
     let input = if let Some(prompt) = &args.prompt {
         prompt.clone()
     } else if let Some(input_path) = &args.input {
@@ -213,26 +448,44 @@ async fn process_single(backend: &mut Backend, args: &RunArgs, _config: &Config)
         return Ok(());
     }
 
-    let inference_params = crate::backends::InferenceParams {
-        max_tokens: args.max_tokens,
-        temperature: args.temperature,
-        top_k: args.top_k,
-        top_p: args.top_p,
-        stream: args.stream,
-        stop_sequences: vec![],
-        seed: None,
-    };
+    let inference_params = args.inference_params(args.stream);
 
     let start = std::time::Instant::now();
+    let profiler = args.profile.then(|| RunProfiler::start(backend, &input));
+
+    // Ctrl-C stops generation instead of leaving it running on a blocking thread
+    let cancel = CancellationToken::new();
+    let interrupt = tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                cancel.cancel();
+            }
+        }
+    });
 
     if args.stream {
-        let mut stream = backend.infer_stream(&input, &inference_params).await?;
+        let generation_started = Instant::now();
+        let mut stream = backend
+            .infer_stream_cancellable(&input, &inference_params, &cancel)
+            .await?;
+        if let Some(profiler) = &profiler {
+            stream = profiler.watch(stream, generation_started);
+        }
+        let mut writer = args.text_layout().writer();
         while let Some(token) = stream.next().await {
             match token {
                 Ok(t) => {
-                    print!("{}", t);
+                    let text =
+                        RunProfiler::time_detokenization(profiler.as_ref(), || writer.push(&t));
+                    print!("{}", text);
                     use std::io::Write;
                     std::io::stdout().flush()?;
+                    if writer.truncated() {
+                        // Nothing more will be shown
+                        cancel.cancel();
+                        break;
+                    }
                 }
                 Err(e) => {
                     eprintln!("Stream error: {}", e);
@@ -240,23 +493,252 @@ async fn process_single(backend: &mut Backend, args: &RunArgs, _config: &Config)
                 }
             }
         }
-        println!();
+        println!("{}", writer.finish());
+        report_truncation(writer.truncated());
     } else {
-        let result = backend.infer(&input, &inference_params).await?;
+        let result = generate(
+            backend,
+            &input,
+            &inference_params,
+            &cancel,
+            profiler.as_ref(),
+        )
+        .await?;
+        let result =
+            RunProfiler::time_detokenization(profiler.as_ref(), || post_processor.apply(&result));
         if let Some(output_path) = &args.output {
             tokio::fs::write(output_path, &result).await?;
             info!("Output written to: {}", output_path.display());
         } else {
-            println!("{}", result);
+            let mut writer = args.text_layout().writer();
+            let text = writer.push(&result);
+            println!("{}{}", text, writer.finish());
+            report_truncation(writer.truncated());
         }
     }
 
+    interrupt.abort();
+
     let elapsed = start.elapsed();
     info!("Inference completed in {:.2}s", elapsed.as_secs_f64());
 
+    if let Some(profiler) = profiler {
+        let profile = profiler.finish(model_id);
+        match args.format.unwrap_or(ProfileFormat::Text) {
+            ProfileFormat::Text => eprint!("{}", profile.render()),
+            ProfileFormat::Json => eprintln!("{}", serde_json::to_string_pretty(&profile)?),
+        }
+    }
+
     Ok(())
 }
 
+/// Complete `input` without streaming it; with a profiler the tokens are still
+/// streamed internally, so the time to each one can be measured
+async fn generate(
+    backend: &mut Backend,
+    input: &str,
+    params: &crate::backends::InferenceParams,
+    cancel: &CancellationToken,
+    profiler: Option<&RunProfiler>,
+) -> Result<String> {
+    let Some(profiler) = profiler else {
+        return backend.infer_cancellable(input, params, cancel).await;
+    };
+    let generation_started = Instant::now();
+    let stream = backend
+        .infer_stream_cancellable(input, params, cancel)
+        .await?;
+    let mut stream = profiler.watch(stream, generation_started);
+    let mut pieces = Vec::new();
+    while let Some(token) = stream.next().await {
+        pieces.push(token?);
+    }
+    Ok(RunProfiler::time_detokenization(Some(profiler), || {
+        pieces.concat()
+    }))
+}
+
+/// Phase timings of one `run --profile`
+#[derive(Debug, Default)]
+struct RunTimings {
+    /// From the generation call to the first token, which needs the whole
+    /// prompt evaluated
+    prompt_eval: Option<Duration>,
+    /// Wait for each token after the first
+    token_decode: Vec<Duration>,
+    /// Turning the generated pieces into the text shown: joining, post-processing
+    /// and laying them out. Backends convert token ids to text while decoding
+    detokenization: Duration,
+}
+
+/// Times the phases of a run for `--profile`
+struct RunProfiler {
+    started: Instant,
+    tokenization: OperationProfile,
+    input_tokens: u32,
+    timings: Arc<Mutex<RunTimings>>,
+}
+
+impl RunProfiler {
+    /// Start the profile by tokenizing the prompt
+    fn start(backend: &Backend, input: &str) -> Self {
+        let started = Instant::now();
+        let timer = PhaseTimer::new("tokenization".to_string());
+        let input_tokens = backend.count_tokens(input);
+        Self {
+            started,
+            tokenization: timer.finish(),
+            input_tokens,
+            timings: Arc::default(),
+        }
+    }
+
+    /// `stream`, recording when its tokens arrive; `generation_started` is when it
+    /// was asked for
+    ///
+    /// Only the wait for each token is counted, not the time the caller spends
+    /// on it.
+    fn watch(&self, stream: TokenStream, generation_started: Instant) -> TokenStream {
+        let timings = Arc::clone(&self.timings);
+        Box::pin(futures::stream::unfold(stream, move |mut stream| {
+            let timings = Arc::clone(&timings);
+            async move {
+                let waiting = Instant::now();
+                let token = stream.next().await?;
+                if token.is_ok() {
+                    let mut timings = timings.lock().unwrap();
+                    match timings.prompt_eval {
+                        None => timings.prompt_eval = Some(generation_started.elapsed()),
+                        Some(_) => timings.token_decode.push(waiting.elapsed()),
+                    }
+                }
+                Some((token, stream))
+            }
+        }))
+    }
+
+    /// Run `f`, counting its time as detokenization when profiling
+    fn time_detokenization<T>(profiler: Option<&RunProfiler>, f: impl FnOnce() -> T) -> T {
+        let Some(profiler) = profiler else {
+            return f();
+        };
+        let started = Instant::now();
+        let value = f();
+        profiler.timings.lock().unwrap().detokenization += started.elapsed();
+        value
+    }
+
+    fn finish(self, model_id: &str) -> RunProfile {
+        let timings = std::mem::take(&mut *self.timings.lock().unwrap());
+        let prompt_eval = timings.prompt_eval.unwrap_or_default();
+        let decode: Duration = timings.token_decode.iter().sum();
+        let output_tokens = timings
+            .prompt_eval
+            .map_or(0, |_| 1 + timings.token_decode.len());
+
+        let mut profile = InferenceProfile::new(
+            uuid::Uuid::new_v4().to_string(),
+            model_id.to_string(),
+            self.input_tokens,
+            output_tokens as u32,
+        );
+        profile.add_phase(self.tokenization);
+        profile.add_phase(OperationProfile::new(
+            "prompt_eval".to_string(),
+            prompt_eval,
+        ));
+        profile.add_phase(OperationProfile::new("decode".to_string(), decode));
+        profile.add_phase(OperationProfile::new(
+            "detokenization".to_string(),
+            timings.detokenization,
+        ));
+        profile.set_total_time(self.started.elapsed());
+
+        RunProfile {
+            prompt_eval_tokens_per_sec: rate(self.input_tokens as usize, prompt_eval),
+            generation_tokens_per_sec: rate(timings.token_decode.len(), decode),
+            token_decode_ms: timings
+                .token_decode
+                .iter()
+                .map(|wait| wait.as_secs_f32() * 1000.0)
+                .collect(),
+            profile,
+        }
+    }
+}
+
+fn rate(tokens: usize, duration: Duration) -> f32 {
+    if duration.is_zero() {
+        return 0.0;
+    }
+    tokens as f32 / duration.as_secs_f32()
+}
+
+/// What `--profile` reports: the phases of the run and its throughput
+#[derive(Debug, Serialize)]
+struct RunProfile {
+    #[serde(flatten)]
+    profile: InferenceProfile,
+    /// Prompt tokens evaluated per second
+    prompt_eval_tokens_per_sec: f32,
+    /// Tokens generated per second after the first
+    generation_tokens_per_sec: f32,
+    /// Time taken by each token after the first
+    token_decode_ms: Vec<f32>,
+}
+
+impl RunProfile {
+    fn render(&self) -> String {
+        let profile = &self.profile;
+        let mut out = format!(
+            "\nProfile of {} ({} prompt tokens, {} generated)\n",
+            profile.model_id, profile.input_tokens, profile.output_tokens
+        );
+        for phase in &profile.phases {
+            let rate = match phase.phase.as_str() {
+                "prompt_eval" => format!("{:>10.1} tok/s", self.prompt_eval_tokens_per_sec),
+                "decode" => format!("{:>10.1} tok/s", self.generation_tokens_per_sec),
+                _ => String::new(),
+            };
+            out.push_str(&format!(
+                "  {:<16}{:>10.1} ms{}\n",
+                phase.phase, phase.duration_ms, rate
+            ));
+        }
+        if !self.token_decode_ms.is_empty() {
+            let mean = self.token_decode_ms.iter().sum::<f32>() / self.token_decode_ms.len() as f32;
+            let slowest = self.token_decode_ms.iter().copied().fold(0.0, f32::max);
+            out.push_str(&format!(
+                "  {:<16}{:>10.1} ms mean, {:.1} ms slowest\n",
+                "per token", mean, slowest
+            ));
+        }
+        out.push_str(&format!(
+            "  {:<16}{:>10.1} ms\n",
+            "total", profile.total_time_ms
+        ));
+        out
+    }
+}
+
+/// Pattern matching any of `antiprompts` as plain text, so generation halts at
+/// them whatever the backend; `None` without any
+fn antiprompt_regex(antiprompts: &[String]) -> Option<String> {
+    let antiprompts: Vec<String> = antiprompts
+        .iter()
+        .filter(|antiprompt| !antiprompt.is_empty())
+        .map(|antiprompt| regex::escape(antiprompt))
+        .collect();
+    (!antiprompts.is_empty()).then(|| antiprompts.join("|"))
+}
+
+fn report_truncation(truncated: bool) {
+    if truncated {
+        eprintln!("Output truncated; pass --full to see all of it");
+    }
+}
+
 async fn process_batch(backend: &mut Backend, args: &RunArgs, _config: &Config) -> Result<()> {
     let input_path = args
         .input
@@ -268,15 +750,7 @@ async fn process_batch(backend: &mut Backend, args: &RunArgs, _config: &Config)
 
     info!("Processing {} inputs in batch mode", lines.len());
 
-    let inference_params = crate::backends::InferenceParams {
-        max_tokens: args.max_tokens,
-        temperature: args.temperature,
-        top_k: args.top_k,
-        top_p: args.top_p,
-        stream: false, // No streaming in batch mode
-        stop_sequences: vec![],
-        seed: None,
-    };
+    let inference_params = args.inference_params(false);
 
     let mut results = Vec::new();
 
@@ -305,3 +779,97 @@ async fn process_batch(backend: &mut Backend, args: &RunArgs, _config: &Config)
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::InferenceParams;
+    use crate::backends::mock::MockBackend;
+
+    #[tokio::test]
+    async fn test_profile_reports_every_phase() {
+        let mut backend = MockBackend::tokens(&["one", " two", " three"]).backend();
+        let input = "count to three";
+        let profiler = RunProfiler::start(&backend, input);
+        let text = generate(
+            &mut backend,
+            input,
+            &InferenceParams::default(),
+            &CancellationToken::new(),
+            Some(&profiler),
+        )
+        .await
+        .unwrap();
+        assert_eq!(text, "one two three");
+
+        let profile = profiler.finish("words");
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["model_id"], "words");
+        assert_eq!(json["output_tokens"], 3);
+        assert!(json["input_tokens"].as_u64().unwrap() > 0);
+        let phases: Vec<&str> = json["phases"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|phase| {
+                assert!(phase["duration_ms"].as_f64().unwrap() >= 0.0);
+                phase["phase"].as_str().unwrap()
+            })
+            .collect();
+        assert_eq!(
+            phases,
+            ["tokenization", "prompt_eval", "decode", "detokenization"]
+        );
+        assert_eq!(json["token_decode_ms"].as_array().unwrap().len(), 2);
+        for rate in ["prompt_eval_tokens_per_sec", "generation_tokens_per_sec"] {
+            assert!(json[rate].is_number(), "{}", rate);
+        }
+        assert!(json["total_time_ms"].is_number());
+        assert!(profile.render().contains("prompt_eval"));
+    }
+
+    #[tokio::test]
+    async fn test_generation_halts_at_antiprompt() {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[command(flatten)]
+            run: RunArgs,
+        }
+        let cli = <Cli as clap::Parser>::try_parse_from([
+            "run",
+            "--model",
+            "words",
+            "--antiprompt",
+            " three",
+            "--antiprompt",
+            "User:",
+        ])
+        .unwrap();
+        let params = cli.run.inference_params(false);
+        assert_eq!(params.stop_sequences, [" three", "User:"]);
+
+        let mut backend = MockBackend::tokens(&["one", " two", " three"]).backend();
+        let text = generate(
+            &mut backend,
+            "count to three",
+            &params,
+            &CancellationToken::new(),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(text, "one two");
+
+        // Without one the model runs on
+        let text = generate(
+            &mut backend,
+            "count to three",
+            &InferenceParams::default(),
+            &CancellationToken::new(),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(text, "one two three");
+    }
+}
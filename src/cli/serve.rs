@@ -1,25 +1,52 @@
 #![allow(dead_code, unused_imports, unused_variables)]
 use crate::{
-    api::{openai, websocket},
-    backends::{BackendHandle, BackendType},
+    api::{
+        auth::{ApiKeys, require_api_key, require_loopback},
+        coalesce::RequestCoalescer,
+        key_concurrency::{KeyConcurrency, limit_key_concurrency},
+        openai,
+        resume::StreamSessions,
+        timeouts::{RouteTimeouts, with_timeout},
+        websocket,
+    },
+    backends::{Backend, BackendHandle, BackendType, Generation},
     config::Config,
-    distributed::DistributedInference,
-    metrics::MetricsCollector,
-    models::ModelManager,
+    distributed::{DistributedInference, WorkerRegistration, WorkerRegistry},
+    metrics::{
+        MetricsCollector,
+        statsd::StatsdExporter,
+        usage::{self, UsageLedger},
+    },
+    models::{
+        ModelInfo, ModelManager,
+        chat_template::ChatTemplateCache,
+        watch::{BackendFactory, ModelWatcher},
+    },
+    multi_tenancy::{TenantQuotaManager, enforce_tenant_quota, identify_tenant},
+    operations::queue::{DispatcherConfig, RequestDispatcher},
+    resilience::ModelFallbacks,
     upgrade::UpgradeManager,
 };
 use anyhow::Result;
 use axum::{
     Json, Router,
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
-    response::IntoResponse,
-    routing::{get, post},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
 };
 use clap::Args;
 use serde_json::json;
-use std::{net::SocketAddr, sync::Arc};
-use tokio::signal;
+use std::{
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{net::TcpListener, signal};
 use tower::ServiceBuilder;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing::{info, warn};
@@ -46,6 +73,36 @@ pub struct ServeArgs {
         default_value = "0"
     )]
     pub workers: usize,
+
+    #[arg(
+        long,
+        help = "Reject request fields the OpenAI API does not document and use its exact error types"
+    )]
+    pub openai_compat_strict: bool,
+
+    #[arg(
+        long,
+        help = "Reload the startup model when its file changes on disk, and unload it if the file is deleted"
+    )]
+    pub watch: bool,
+
+    #[arg(
+        long,
+        value_name = "BOOL",
+        action = clap::ArgAction::Set,
+        num_args = 0..=1,
+        default_value_t = true,
+        default_missing_value = "true",
+        help = "Only start accepting connections once the startup model has loaded; with false the model loads in the background while /readyz reports not ready"
+    )]
+    pub wait_for_preload: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Require an API key from this file of hashed keys (overrides server.api_key_file)"
+    )]
+    pub api_key_file: Option<std::path::PathBuf>,
 }
 
 /// Maximum allowed worker count for distributed mode
@@ -97,11 +154,20 @@ pub async fn execute(args: ServeArgs, config: &Config) -> Result<()> {
     info!("Starting HTTP server on {}", args.bind);
 
     // Initialize metrics collector
-    let (metrics_collector, processor) = MetricsCollector::new();
+    let (metrics_collector, processor) =
+        MetricsCollector::with_latency_buckets(&config.observability.histogram_buckets);
     processor.start();
 
+    if config.observability.statsd.enabled {
+        StatsdExporter::new(
+            config.observability.statsd.clone(),
+            metrics_collector.clone(),
+        )
+        .spawn();
+    }
+
     // Initialize model manager
-    let model_manager = Arc::new(ModelManager::new(&config.models_dir));
+    let model_manager = Arc::new(ModelManager::from_config(config));
 
     // Optionally initialize distributed inference
     let distributed = if args.distributed {
@@ -133,24 +199,43 @@ pub async fn execute(args: ServeArgs, config: &Config) -> Result<()> {
         None
     };
 
-    // Optionally load a model on startup (only if not using distributed)
-    let (backend, loaded_model) = if !args.distributed {
-        if let Some(model_name) = &args.model {
-            info!("Loading model on startup: {}", model_name);
-            match load_model_on_startup(model_name, &model_manager, config).await {
-                Ok((backend_handle, model_name)) => (Some(backend_handle), Some(model_name)),
+    // Optionally load a model on startup (only if not using distributed); the
+    // backend is created here and the model loaded by the preload below
+    let startup = match &args.model {
+        Some(model_name) if !args.distributed => {
+            match startup_backend(model_name, &model_manager, config).await {
+                Ok(startup) => Some(startup),
                 Err(e) => {
                     warn!("Failed to load startup model: {}", e);
-                    (None, None)
+                    None
                 }
             }
-        } else {
-            (None, None)
         }
-    } else {
-        (None, None)
+        _ => None,
+    };
+    let (backend, loaded_model) = match &startup {
+        Some((backend_handle, model_info)) => {
+            (Some(backend_handle.clone()), Some(model_info.name.clone()))
+        }
+        None => (None, None),
     };
 
+    if args.watch {
+        match (&backend, &args.model) {
+            (Some(backend), Some(model_name)) => {
+                spawn_model_watcher(
+                    model_name,
+                    backend,
+                    &model_manager,
+                    &metrics_collector,
+                    config,
+                )
+                .await?;
+            }
+            _ => warn!("--watch has no effect without a model loaded by --model"),
+        }
+    }
+
     // Initialize upgrade manager
     let upgrade_manager = match crate::upgrade::UpgradeConfig::from_config(config) {
         Ok(upgrade_config) => match UpgradeManager::new(upgrade_config).await {
@@ -169,6 +254,64 @@ pub async fn execute(args: ServeArgs, config: &Config) -> Result<()> {
         }
     };
 
+    // Inference requests wait here for a worker slot, highest priority first
+    let workers = args.workers.max(1);
+    let dispatcher = RequestDispatcher::new(DispatcherConfig {
+        workers,
+        max_active_per_worker: (config.server.max_concurrent_requests as usize)
+            .div_ceil(workers)
+            .max(1) as u32,
+        ..Default::default()
+    });
+
+    // Per-tenant quotas, reloaded when the tenants file changes
+    let tenant_quotas = match &config.server.tenant_quotas_path {
+        Some(path) => {
+            let quotas = Arc::new(TenantQuotaManager::load(path).await?);
+            Arc::clone(&quotas).spawn_hot_reload(path.clone(), Duration::from_secs(5));
+            info!("Tenant quotas loaded from {}", path.display());
+            Some(quotas)
+        }
+        None => None,
+    };
+
+    let api_keys = match args
+        .api_key_file
+        .as_ref()
+        .or(config.server.api_key_file.as_ref())
+    {
+        Some(path) => {
+            let keys = ApiKeys::load(path)?;
+            if keys.is_empty() {
+                warn!("API key file {} lists no keys", path.display());
+            }
+            info!(
+                "Requests need one of the {} API keys in {}",
+                keys.len(),
+                path.display()
+            );
+            Some(Arc::new(keys))
+        }
+        None => None,
+    };
+
+    let usage_ledger = match &config.server.usage_ledger_path {
+        Some(path) => {
+            let ledger = Arc::new(UsageLedger::open(path)?);
+            info!("Recording token usage per API key in {}", path.display());
+            ledger.clone().spawn_flush(usage::FLUSH_INTERVAL);
+            Some(ledger)
+        }
+        None => None,
+    };
+
+    // Requests naming a fallback chain go to its first model with a healthy breaker
+    let model_fallbacks = ModelFallbacks::new(
+        config.server.model_fallbacks.clone(),
+        config.server.model_circuit_breaker.clone(),
+        Some(Arc::new(metrics_collector.clone())),
+    );
+
     // Create shared application state
     let state = Arc::new(ServerState {
         config: config.clone(),
@@ -178,58 +321,106 @@ pub async fn execute(args: ServeArgs, config: &Config) -> Result<()> {
         model_manager: (*model_manager).clone(),
         distributed,
         upgrade_manager,
+        dispatcher,
+        model_fallbacks,
+        coalescer: RequestCoalescer::new(),
+        chat_templates: ChatTemplateCache::new(),
+        stream_sessions: StreamSessions::from_config(&config.server),
+        openai_compat_strict: args.openai_compat_strict,
+        draining: AtomicBool::new(false),
+        preloading: AtomicBool::new(startup.is_some()),
+        preload_failed: AtomicBool::new(
+            args.model.is_some() && !args.distributed && startup.is_none(),
+        ),
+        usage_ledger,
     });
 
-    // Build the router with all endpoints
-    let app = Router::new()
-        // Health and status endpoints
-        .route("/health", get(health_check))
-        .route("/", get(root_handler))
-        // Metrics endpoints
-        .route("/metrics", get(metrics_prometheus))
-        .route("/metrics/json", get(metrics_json))
-        .route("/metrics/snapshot", get(metrics_snapshot))
-        // OpenAI-compatible API endpoints
-        .route("/v1/models", get(openai::list_models))
-        .route("/v1/chat/completions", post(openai::chat_completions))
-        .route("/v1/completions", post(openai::completions))
-        .route("/v1/embeddings", post(openai::embeddings))
-        // WebSocket streaming endpoints
-        .route("/ws/stream", get(websocket::websocket_handler))
-        // API v1 endpoints
-        .route("/v1/status", get(server_status))
-        // Upgrade API endpoints
-        .route("/v1/upgrade/status", get(upgrade_status))
-        .route("/v1/upgrade/check", post(upgrade_check))
-        .route("/v1/upgrade/install", post(upgrade_install))
-        // Add middleware
-        .layer(
-            ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive()),
-        )
-        .with_state(state);
+    let preload = {
+        let state = state.clone();
+        async move {
+            if let Some((backend_handle, model_info)) = startup {
+                info!("Loading model on startup: {}", model_info.name);
+                match backend_handle.load_model(&model_info).await {
+                    Ok(()) => info!("Startup model {} loaded", model_info.name),
+                    Err(e) => {
+                        warn!("Failed to load startup model: {}", e);
+                        state.preload_failed.store(true, Ordering::SeqCst);
+                    }
+                }
+            }
+            state.preloading.store(false, Ordering::SeqCst);
+        }
+    };
+
+    let app = router(state.clone(), tenant_quotas, api_keys);
+    let metrics = &config.metrics;
+    let metrics_addr = format!("{}:{}", metrics.bind_address, metrics.port);
+    if metrics.enabled && metrics.separate_listener {
+        let listener = tokio::net::TcpListener::bind(&metrics_addr).await?;
+        // Not behind the API keys, so only this host may reset the metrics
+        let app = metrics_router(config, false).with_state(state.clone());
+        tokio::spawn(async move {
+            let app = app.into_make_service_with_connect_info::<SocketAddr>();
+            if let Err(e) = axum::serve(listener, app).await {
+                warn!("Metrics listener failed: {}", e);
+            }
+        });
+    }
 
     info!("HTTP API server is running on http://{}", args.bind);
+    if args.openai_compat_strict {
+        info!("Strict OpenAI compatibility: undocumented request fields are rejected");
+    }
     info!("Available endpoints:");
     info!("  GET  /             - Server information");
     info!("  GET  /health       - Health check");
-    info!("  GET  /metrics      - Prometheus metrics");
-    info!("  GET  /metrics/json - JSON metrics");
+    info!("  GET  /healthz      - Liveness probe");
+    info!("  GET  /readyz       - Readiness probe");
+    if !metrics.enabled {
+        info!("  Metrics endpoints are disabled");
+    } else if metrics.separate_listener {
+        info!(
+            "  GET  http://{}{} - Prometheus metrics",
+            metrics_addr, metrics.path
+        );
+    } else {
+        info!("  GET  {:<12} - Prometheus metrics", metrics.path);
+        info!("  GET  /metrics/json - JSON metrics");
+    }
     info!("  GET  /v1/models           - List available models (OpenAI-compatible)");
+    info!("  GET  /v1/models/:id       - Describe one model (OpenAI-compatible)");
     info!("  POST /v1/chat/completions - Chat completions (OpenAI-compatible)");
     info!("  POST /v1/completions      - Text completions (OpenAI-compatible)");
     info!("  POST /v1/embeddings       - Generate embeddings (OpenAI-compatible)");
     info!("  GET  /v1/status           - Server status");
+    if state.distributed.is_some() {
+        info!("  GET  /v1/admin/workers    - Registered workers (POST to register)");
+    }
+    info!("  GET  /v1/queue/:id        - Queue position of a waiting request");
+    info!("Inference requests are queued by x-priority header or API key tier");
     info!("  WS   /ws/stream           - WebSocket streaming inference");
 
     // Create the listener
-    let listener = tokio::net::TcpListener::bind(&args.bind).await?;
+    if args.wait_for_preload && state.preloading.load(Ordering::SeqCst) {
+        info!("Waiting for the startup model to load before accepting connections");
+    }
+    let listener = bind_with_preload(args.bind, preload, args.wait_for_preload).await?;
 
     // Run the server with graceful shutdown
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    let usage_ledger = state.usage_ledger.clone();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(drain_on_shutdown(state, shutdown_signal()))
+    .await?;
+
+    // Save the requests counted since the last periodic flush
+    if let Some(ledger) = usage_ledger
+        && let Err(e) = ledger.flush()
+    {
+        warn!("Failed to write usage ledger: {:#}", e);
+    }
 
     info!("Server shut down gracefully");
     Ok(())
@@ -243,15 +434,70 @@ pub struct ServerState {
     pub model_manager: ModelManager,
     pub distributed: Option<Arc<DistributedInference>>,
     pub upgrade_manager: Option<Arc<UpgradeManager>>,
+    pub dispatcher: RequestDispatcher,
+    pub model_fallbacks: ModelFallbacks,
+    /// Shares inference output between identical concurrent requests
+    pub coalescer: RequestCoalescer<Generation>,
+    /// Chat templates of the models served, read from their metadata
+    pub chat_templates: ChatTemplateCache,
+    /// Streams clients can reconnect to with `Last-Event-ID`
+    pub stream_sessions: StreamSessions,
+    /// Enforce the documented OpenAI request and error shapes
+    pub openai_compat_strict: bool,
+    /// Set once a shutdown signal arrives; `/readyz` then reports 503
+    pub draining: AtomicBool,
+    /// Set while the startup model loads; `/readyz` reports 503 until it clears
+    pub preloading: AtomicBool,
+    /// Set when the startup model fails to load; `/readyz` then reports 503
+    pub preload_failed: AtomicBool,
+    /// Requests and tokens served per API key, when `server.usage_ledger_path` is set
+    pub usage_ledger: Option<Arc<UsageLedger>>,
+}
+
+impl ServerState {
+    /// State of a server with no model loaded and the other features off
+    pub fn new(config: Config, metrics: MetricsCollector) -> Self {
+        Self {
+            backend: None,
+            loaded_model: None,
+            metrics,
+            model_manager: ModelManager::from_config(&config),
+            distributed: None,
+            upgrade_manager: None,
+            dispatcher: RequestDispatcher::new(DispatcherConfig::default()),
+            model_fallbacks: ModelFallbacks::new(
+                config.server.model_fallbacks.clone(),
+                config.server.model_circuit_breaker.clone(),
+                None,
+            ),
+            coalescer: RequestCoalescer::new(),
+            chat_templates: ChatTemplateCache::new(),
+            stream_sessions: StreamSessions::from_config(&config.server),
+            openai_compat_strict: false,
+            draining: AtomicBool::new(false),
+            preloading: AtomicBool::new(false),
+            preload_failed: AtomicBool::new(false),
+            usage_ledger: None,
+            config,
+        }
+    }
+
+    /// Serve `backend` as `model`
+    pub fn with_backend(mut self, model: &str, backend: BackendHandle) -> Self {
+        self.backend = Some(backend);
+        self.loaded_model = Some(model.to_string());
+        self
+    }
 }
 
 // Helper functions
 
-async fn load_model_on_startup(
+/// Backend for the startup model, not yet loaded
+async fn startup_backend(
     model_name: &str,
     model_manager: &ModelManager,
     config: &Config,
-) -> Result<(BackendHandle, String)> {
+) -> Result<(BackendHandle, ModelInfo)> {
     let model_info = model_manager.resolve_model(model_name).await?;
     let backend_type = BackendType::from_model_path(&model_info.path).ok_or_else(|| {
         anyhow::anyhow!(
@@ -260,8 +506,49 @@ async fn load_model_on_startup(
         )
     })?;
     let backend_handle = BackendHandle::new_shared(backend_type, &config.backend_config)?;
-    backend_handle.load_model(&model_info).await?;
-    Ok((backend_handle, model_info.name.clone()))
+    Ok((backend_handle, model_info))
+}
+
+/// Bind `addr` and run `preload`, which loads the startup model
+///
+/// With `wait_for_preload` nothing listens on `addr` until `preload` finishes, so
+/// load balancers cannot route to the server early; otherwise it runs in the
+/// background while `/readyz` reports not ready.
+async fn bind_with_preload(
+    addr: SocketAddr,
+    preload: impl Future<Output = ()> + Send + 'static,
+    wait_for_preload: bool,
+) -> Result<TcpListener> {
+    if wait_for_preload {
+        preload.await;
+        return Ok(TcpListener::bind(addr).await?);
+    }
+    let listener = TcpListener::bind(addr).await?;
+    tokio::spawn(preload);
+    Ok(listener)
+}
+
+/// Reload the startup model when its file changes
+async fn spawn_model_watcher(
+    model_name: &str,
+    backend: &BackendHandle,
+    model_manager: &ModelManager,
+    metrics: &MetricsCollector,
+    config: &Config,
+) -> Result<()> {
+    let model_info = model_manager.resolve_model(model_name).await?;
+    let backend_type = backend.get_backend_type();
+    let backend_config = config.backend_config.clone();
+    let factory: BackendFactory = Arc::new(move || Backend::new(backend_type, &backend_config));
+    ModelWatcher::new(
+        &model_info.path,
+        backend.clone(),
+        factory,
+        model_manager.clone(),
+    )?
+    .with_metrics(metrics.clone())
+    .spawn()?;
+    Ok(())
 }
 
 // Handler functions
@@ -273,14 +560,19 @@ async fn root_handler() -> impl IntoResponse {
         "description": "Offline AI/ML model runner for GGUF and ONNX models",
         "endpoints": {
             "/health": "Health check",
+            "/healthz": "Liveness probe",
+            "/readyz": "Readiness probe",
             "/metrics": "Prometheus metrics",
             "/metrics/json": "JSON formatted metrics",
             "/metrics/snapshot": "Detailed metrics snapshot",
+            "/metrics/reset": "Reset all metrics (POST)",
             "/v1/models": "List available models (OpenAI-compatible)",
+            "/v1/models/{id}": "Describe one model (OpenAI-compatible)",
             "/v1/chat/completions": "Chat completions (OpenAI-compatible)",
             "/v1/completions": "Text completions (OpenAI-compatible)",
             "/v1/embeddings": "Generate embeddings (OpenAI-compatible)",
             "/v1/status": "Server status",
+            "/v1/admin/workers": "Registered distributed workers (GET, POST to register)",
             "/ws/stream": "WebSocket streaming inference"
         }
     }))
@@ -294,6 +586,71 @@ async fn health_check() -> impl IntoResponse {
     }))
 }
 
+/// Liveness: 200 for as long as the process can answer at all
+async fn liveness() -> impl IntoResponse {
+    Json(json!({ "status": "alive" }))
+}
+
+/// Readiness: 503 while draining for shutdown, while the startup model is loading
+/// or after it failed to, while the models directory cannot be read, or while no
+/// backend is loaded or able to load one of its models
+async fn readiness(State(state): State<Arc<ServerState>>) -> impl IntoResponse {
+    match not_ready_reason(&state).await {
+        None => (StatusCode::OK, Json(json!({ "status": "ready" }))),
+        Some(reason) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "not_ready", "reason": reason })),
+        ),
+    }
+}
+
+async fn not_ready_reason(state: &ServerState) -> Option<String> {
+    if state.draining.load(Ordering::SeqCst) {
+        return Some("server is shutting down".to_string());
+    }
+    if state.preloading.load(Ordering::SeqCst) {
+        return Some("startup model is still loading".to_string());
+    }
+    if state.preload_failed.load(Ordering::SeqCst) {
+        return Some("startup model failed to load".to_string());
+    }
+
+    let models_dir = state.model_manager.models_dir();
+    if let Err(e) = tokio::fs::read_dir(models_dir).await {
+        return Some(format!(
+            "models directory {} is not accessible: {}",
+            models_dir.display(),
+            e
+        ));
+    }
+
+    if let Some(distributed) = &state.distributed {
+        return (!distributed.is_leader())
+            .then(|| "standby coordinator; the leader serves requests".to_string());
+    }
+    if let Some(backend) = &state.backend
+        && backend.is_loaded_or_busy().await
+    {
+        return None;
+    }
+    // Nothing loaded yet: ready if any model on disk has a backend compiled in
+    let loadable = state
+        .model_manager
+        .list_models()
+        .await
+        .map(|models| {
+            models
+                .iter()
+                .any(|model| BackendType::from_model_path(&model.path).is_some())
+        })
+        .unwrap_or(false);
+    if loadable {
+        None
+    } else {
+        Some("no backend is loaded and no model can be loaded".to_string())
+    }
+}
+
 async fn metrics_prometheus(State(state): State<Arc<ServerState>>) -> impl IntoResponse {
     use axum::http::header;
 
@@ -332,6 +689,14 @@ async fn metrics_json(State(state): State<Arc<ServerState>>) -> impl IntoRespons
     }
 }
 
+async fn metrics_reset(State(state): State<Arc<ServerState>>) -> impl IntoResponse {
+    state.metrics.reset();
+    Json(json!({
+        "status": "reset",
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    }))
+}
+
 async fn metrics_snapshot(State(state): State<Arc<ServerState>>) -> impl IntoResponse {
     match state.metrics.get_snapshot().await {
         Ok(snapshot) => Json(snapshot).into_response(),
@@ -377,6 +742,59 @@ async fn server_status(State(state): State<Arc<ServerState>>) -> impl IntoRespon
     .into_response()
 }
 
+// Worker registry handlers
+
+/// Registry of the distributed coordinator, or a 503 without distributed inference
+fn worker_registry(state: &ServerState) -> Result<&Arc<WorkerRegistry>, Response> {
+    match &state.distributed {
+        Some(distributed) => Ok(distributed.registry()),
+        None => Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({
+                "error": "Distributed inference is not enabled"
+            })),
+        )
+            .into_response()),
+    }
+}
+
+async fn list_workers(State(state): State<Arc<ServerState>>) -> impl IntoResponse {
+    match worker_registry(&state) {
+        Ok(registry) => Json(json!({ "workers": registry.roster().await })).into_response(),
+        Err(response) => response,
+    }
+}
+
+async fn register_worker(
+    State(state): State<Arc<ServerState>>,
+    Json(registration): Json<WorkerRegistration>,
+) -> impl IntoResponse {
+    match worker_registry(&state) {
+        Ok(registry) => {
+            let id = registry.register(registration).await;
+            (StatusCode::CREATED, Json(json!({ "id": id }))).into_response()
+        }
+        Err(response) => response,
+    }
+}
+
+async fn deregister_worker(
+    State(state): State<Arc<ServerState>>,
+    Path(id): Path<usize>,
+) -> impl IntoResponse {
+    match worker_registry(&state) {
+        Ok(registry) if registry.deregister(id).await => StatusCode::NO_CONTENT.into_response(),
+        Ok(_) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "error": format!("No worker {} is registered", id)
+            })),
+        )
+            .into_response(),
+        Err(response) => response,
+    }
+}
+
 // Upgrade API handlers
 
 async fn upgrade_status(State(state): State<Arc<ServerState>>) -> impl IntoResponse {
@@ -523,6 +941,18 @@ async fn upgrade_install(
     }
 }
 
+/// Wait for a shutdown signal, then report not ready for
+/// `shutdown_drain_secs` before letting the server stop accepting connections
+async fn drain_on_shutdown(state: Arc<ServerState>, signal: impl Future<Output = ()>) {
+    signal.await;
+    state.draining.store(true, Ordering::SeqCst);
+    let drain = Duration::from_secs(state.config.server.shutdown_drain_secs);
+    if !drain.is_zero() {
+        info!("Draining for {:?} before shutting down", drain);
+        tokio::time::sleep(drain).await;
+    }
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -549,9 +979,468 @@ async fn shutdown_signal() {
     info!("Shutdown signal received");
 }
 
+/// Every endpoint of the server, with the metrics endpoints unless they are
+/// disabled or served on their own listener, behind API key checks when
+/// `api_keys` is given
+pub fn router(
+    state: Arc<ServerState>,
+    tenant_quotas: Option<Arc<TenantQuotaManager>>,
+    api_keys: Option<Arc<ApiKeys>>,
+) -> Router {
+    let config = &state.config;
+    let mut inference_routes =
+        inference_routes(&config.server.timeouts, state.openai_compat_strict);
+    let mut stream_routes = Router::new().route("/ws/stream", get(websocket::websocket_handler));
+    if config.server.key_concurrency.is_enabled() {
+        let limits = Arc::new(KeyConcurrency::new(config.server.key_concurrency.clone()));
+        inference_routes = inference_routes.route_layer(middleware::from_fn_with_state(
+            Arc::clone(&limits),
+            limit_key_concurrency,
+        ));
+        stream_routes = stream_routes.route_layer(middleware::from_fn_with_state(
+            limits,
+            limit_key_concurrency,
+        ));
+    }
+    let mut model_routes = Router::new()
+        .route("/v1/models", get(openai::list_models))
+        .route("/v1/models/:id", get(openai::retrieve_model))
+        .merge(stream_routes);
+    if let Some(quotas) = tenant_quotas {
+        inference_routes = inference_routes.route_layer(middleware::from_fn_with_state(
+            Arc::clone(&quotas),
+            enforce_tenant_quota,
+        ));
+        // Tenants only see, and stream from, their own and the shared models
+        model_routes =
+            model_routes.route_layer(middleware::from_fn_with_state(quotas, identify_tenant));
+    }
+
+    let mut admin_routes = Router::new()
+        .route("/v1/admin/workers", get(list_workers).post(register_worker))
+        .route("/v1/admin/workers/:id", delete(deregister_worker));
+    // Without API keys to require an admin key, only this host may manage workers
+    if api_keys.is_none() {
+        admin_routes = admin_routes.route_layer(middleware::from_fn(require_loopback));
+    }
+
+    let mut app = Router::new()
+        // Health and status endpoints
+        .route("/health", get(health_check))
+        .route("/healthz", get(liveness))
+        .route("/readyz", get(readiness))
+        .route("/", get(root_handler))
+        // OpenAI-compatible API endpoints
+        .merge(model_routes)
+        .merge(inference_routes)
+        .route("/v1/queue/:request_id", get(openai::queue_position))
+        // API v1 endpoints
+        .route("/v1/status", get(server_status))
+        .merge(admin_routes)
+        // Upgrade API endpoints
+        .route("/v1/upgrade/status", get(upgrade_status))
+        .route("/v1/upgrade/check", post(upgrade_check))
+        .route("/v1/upgrade/install", post(upgrade_install));
+    if config.metrics.enabled && !config.metrics.separate_listener {
+        app = app.merge(metrics_router(config, api_keys.is_some()));
+    }
+    if let Some(keys) = api_keys {
+        app = app.layer(middleware::from_fn_with_state(keys, require_api_key));
+    }
+
+    app.layer(
+        ServiceBuilder::new()
+            .layer(TraceLayer::new_for_http().make_span_with(request_span))
+            .layer(CorsLayer::permissive()),
+    )
+    .with_state(state)
+}
+
+/// Span every request is handled in, carrying the caller's `x-request-id` so JSON
+/// logs can be grouped by request
+fn request_span<B>(request: &axum::http::Request<B>) -> tracing::Span {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok());
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id = request_id.map(tracing::field::display),
+    )
+}
+
+/// Prometheus exposition at the configured path, plus JSON views and reset
+///
+/// Resetting is an admin action: behind API keys it needs an admin key, and
+/// otherwise only this host may do it.
+fn metrics_router(config: &Config, behind_api_keys: bool) -> Router<Arc<ServerState>> {
+    let mut reset = Router::new().route("/metrics/reset", post(metrics_reset));
+    if !behind_api_keys {
+        reset = reset.route_layer(middleware::from_fn(require_loopback));
+    }
+    Router::new()
+        .route(&config.metrics.path, get(metrics_prometheus))
+        .route("/metrics/json", get(metrics_json))
+        .route("/metrics/snapshot", get(metrics_snapshot))
+        .merge(reset)
+}
+
+/// OpenAI-compatible inference endpoints, each aborted after its own timeout
+fn inference_routes(timeouts: &RouteTimeouts, strict: bool) -> Router<Arc<ServerState>> {
+    Router::new()
+        .route(
+            "/v1/chat/completions",
+            with_timeout(post(openai::chat_completions), timeouts.chat, strict),
+        )
+        .route(
+            "/v1/completions",
+            with_timeout(post(openai::completions), timeouts.completions, strict),
+        )
+        .route(
+            "/v1/embeddings",
+            with_timeout(post(openai::embeddings), timeouts.embeddings, strict),
+        )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backends::{blocking::BlockingPool, mock::MockBackend};
+    use crate::multi_tenancy::TenantConfig;
+    use axum::body::Body;
+    use axum::extract::ConnectInfo;
+    use axum::http::Request;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Instant;
+    use tower::ServiceExt;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_health_responds_during_blocking_backend_calls() {
+        let pool = BlockingPool::new(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let most_running = Arc::new(AtomicUsize::new(0));
+
+        // Four times as many blocking calls as runtime worker threads
+        let calls: Vec<_> = (0..8)
+            .map(|_| {
+                let pool = pool.clone();
+                let running = running.clone();
+                let most_running = most_running.clone();
+                tokio::spawn(async move {
+                    pool.run(move || {
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        most_running.fetch_max(now, Ordering::SeqCst);
+                        std::thread::sleep(Duration::from_millis(200));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    })
+                    .await
+                })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(pool.available(), 0);
+
+        let app = Router::new().route("/health", get(health_check));
+        for _ in 0..3 {
+            let started = Instant::now();
+            let request = Request::get("/health").body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(
+                started.elapsed() < Duration::from_millis(100),
+                "health check took {:?}",
+                started.elapsed()
+            );
+        }
+
+        for call in calls {
+            call.await.unwrap().unwrap();
+        }
+        assert_eq!(most_running.load(Ordering::SeqCst), 2);
+    }
+
+    fn probe_router(models_dir: &std::path::Path, drain_secs: u64) -> (Router, Arc<ServerState>) {
+        let mut config = Config {
+            models_dir: models_dir.to_path_buf(),
+            ..Config::default()
+        };
+        config.server.shutdown_drain_secs = drain_secs;
+        let state = Arc::new(
+            ServerState::new(config, MetricsCollector::new().0)
+                .with_backend("test-model", MockBackend::tokens(&[]).handle()),
+        );
+        let app = Router::new()
+            .route("/healthz", get(liveness))
+            .route("/readyz", get(readiness))
+            .with_state(state.clone());
+        (app, state)
+    }
+
+    async fn probe(app: &Router, uri: &str) -> StatusCode {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_readiness_fails_while_draining_and_liveness_stays_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, state) = probe_router(dir.path(), 60);
+        assert_eq!(probe(&app, "/healthz").await, StatusCode::OK);
+        assert_eq!(probe(&app, "/readyz").await, StatusCode::OK);
+
+        let (signal, received) = tokio::sync::oneshot::channel::<()>();
+        let shutdown = tokio::spawn(drain_on_shutdown(state, async {
+            let _ = received.await;
+        }));
+        signal.send(()).unwrap();
+        tokio::task::yield_now().await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // Still draining, so the server has not stopped accepting connections
+        assert!(!shutdown.is_finished());
+        assert_eq!(
+            probe(&app, "/readyz").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(probe(&app, "/healthz").await, StatusCode::OK);
+        shutdown.abort();
+    }
+
+    #[tokio::test]
+    async fn test_readiness_requires_models_dir_and_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let (app, _state) = probe_router(&missing, 0);
+        assert_eq!(
+            probe(&app, "/readyz").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(probe(&app, "/healthz").await, StatusCode::OK);
+
+        // An empty models directory with nothing loaded leaves nothing to serve
+        let config = Config {
+            models_dir: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        let state = Arc::new(ServerState::new(config, MetricsCollector::new().0));
+        assert!(not_ready_reason(&state).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_preload_delays_binding() {
+        let free_addr = || {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+
+        let addr = free_addr();
+        let (finish, finished) = tokio::sync::oneshot::channel::<()>();
+        let bind = tokio::spawn(bind_with_preload(
+            addr,
+            async {
+                let _ = finished.await;
+            },
+            true,
+        ));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+        assert!(!bind.is_finished());
+
+        finish.send(()).unwrap();
+        let _listener = bind.await.unwrap().unwrap();
+        assert!(tokio::net::TcpStream::connect(addr).await.is_ok());
+
+        // Without waiting the port is open while the preload is still running
+        let addr = free_addr();
+        let (_finish, finished) = tokio::sync::oneshot::channel::<()>();
+        let _listener = bind_with_preload(
+            addr,
+            async {
+                let _ = finished.await;
+            },
+            false,
+        )
+        .await
+        .unwrap();
+        assert!(tokio::net::TcpStream::connect(addr).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_readiness_fails_while_preloading() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, state) = probe_router(dir.path(), 0);
+        state.preloading.store(true, Ordering::SeqCst);
+        assert_eq!(
+            probe(&app, "/readyz").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        state.preloading.store(false, Ordering::SeqCst);
+        assert_eq!(probe(&app, "/readyz").await, StatusCode::OK);
+
+        // A model that failed to load is not served by falling back to the others
+        state.preload_failed.store(true, Ordering::SeqCst);
+        assert_eq!(
+            probe(&app, "/readyz").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[test]
+    fn test_wait_for_preload_is_the_default() {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[command(flatten)]
+            serve: ServeArgs,
+        }
+        let args = |argv: &[&str]| {
+            let argv = std::iter::once("serve").chain(argv.iter().copied());
+            <Cli as clap::Parser>::try_parse_from(argv)
+                .unwrap()
+                .serve
+                .wait_for_preload
+        };
+        assert!(args(&[]));
+        assert!(args(&["--wait-for-preload"]));
+        assert!(!args(&["--wait-for-preload=false"]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_route_timeouts_abort_only_slow_routes() {
+        // Takes 20s to generate and 20s to embed
+        let backend = MockBackend::tokens(&["done"])
+            .delay(Duration::from_secs(20))
+            .embed_delay(Duration::from_secs(20));
+        let mock = backend.state();
+        let state = Arc::new(
+            ServerState::new(Config::default(), MetricsCollector::new().0)
+                .with_backend("test-model", backend.handle()),
+        );
+        let timeouts = RouteTimeouts {
+            chat: 60,
+            completions: 60,
+            embeddings: 5,
+        };
+        let app = inference_routes(&timeouts, false).with_state(state);
+
+        let post = |uri: &str, body: serde_json::Value| {
+            Request::post(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(post(
+                "/v1/embeddings",
+                json!({"model": "test-model", "input": "hello"}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["type"], "timeout");
+
+        // The embedding was dropped rather than left running
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(mock.embeddings(), 0);
+
+        let response = app
+            .oneshot(post(
+                "/v1/chat/completions",
+                json!({
+                    "model": "test-model",
+                    "messages": [{"role": "user", "content": "hi"}]
+                }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_only_serve_this_host_without_api_keys() {
+        let state = Arc::new(ServerState::new(
+            Config::default(),
+            MetricsCollector::new().0,
+        ));
+        let app = router(state, None, None);
+        let list_workers = |peer: Option<&str>| {
+            let mut request = Request::get("/v1/admin/workers")
+                .body(Body::empty())
+                .unwrap();
+            if let Some(peer) = peer {
+                let peer: SocketAddr = peer.parse().unwrap();
+                request.extensions_mut().insert(ConnectInfo(peer));
+            }
+            app.clone().oneshot(request)
+        };
+
+        for peer in [Some("203.0.113.7:4000"), None] {
+            let response = list_workers(peer).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        // Reaches the handler, which reports distributed inference is off
+        let response = list_workers(Some("127.0.0.1:4000")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_reset_only_serves_this_host_without_api_keys() {
+        let state = Arc::new(ServerState::new(
+            Config::default(),
+            MetricsCollector::new().0,
+        ));
+        let app = router(state, None, None);
+        let send = |method: &str, uri: &str, peer: &str| {
+            let mut request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            let peer: SocketAddr = peer.parse().unwrap();
+            request.extensions_mut().insert(ConnectInfo(peer));
+            app.clone().oneshot(request)
+        };
+
+        let response = send("POST", "/metrics/reset", "203.0.113.7:4000")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = send("POST", "/metrics/reset", "127.0.0.1:4000")
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        // Reading metrics stays open
+        let response = send("GET", "/metrics/json", "203.0.113.7:4000")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_websocket_stream_requires_a_tenant_key_with_tenant_quotas() {
+        let state = Arc::new(ServerState::new(
+            Config::default(),
+            MetricsCollector::new().0,
+        ));
+        let tenant = TenantConfig {
+            id: "a".to_string(),
+            api_keys: vec![ApiKeys::digest("sk-a")],
+            quota: Default::default(),
+        };
+        let quotas = Arc::new(TenantQuotaManager::new(vec![tenant]).unwrap());
+        let app = router(state, Some(quotas), None);
+
+        let request = Request::get("/ws/stream").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
 
     fn create_test_args(bind: &str, distributed: bool, workers: usize) -> ServeArgs {
         ServeArgs {
@@ -559,6 +1448,9 @@ mod tests {
             model: None,
             distributed,
             workers,
+            openai_compat_strict: false,
+            watch: false,
+            wait_for_preload: true,
         }
     }
 
@@ -618,6 +1510,9 @@ mod tests {
             model: None,
             distributed: false,
             workers: 0,
+            openai_compat_strict: false,
+            watch: false,
+            wait_for_preload: true,
         };
         assert!(validate_args(&args).is_ok());
     }
@@ -629,6 +1524,9 @@ mod tests {
             model: Some("test-model".to_string()),
             distributed: false,
             workers: 0,
+            openai_compat_strict: false,
+            watch: false,
+            wait_for_preload: true,
         };
         assert!(validate_args(&args).is_ok());
     }
@@ -295,7 +295,7 @@ async fn execute_interactive(
     streaming_manager.start().await?;
 
     // Load model
-    let model_manager = ModelManager::new(&config.models_dir);
+    let model_manager = ModelManager::from_config(config);
     let model_info = model_manager.resolve_model(&model_name).await?;
     let backend_type = BackendType::from_model_path(&model_info.path).ok_or_else(|| {
         anyhow::anyhow!(
@@ -314,10 +314,17 @@ async fn execute_interactive(
         max_tokens,
         temperature,
         top_k,
+        repeat_penalty: None,
+        repeat_last_n: None,
+        min_p: None,
         top_p,
         stream: true,
         stop_sequences: vec![],
+        stop_regex: None,
+        max_generation_ms: None,
         seed: None,
+        response_format: None,
+        logprobs: None,
     };
 
     loop {
@@ -417,7 +424,7 @@ async fn execute_benchmark(
     streaming_manager.start().await?;
 
     // Load model
-    let model_manager = ModelManager::new(&config.models_dir);
+    let model_manager = ModelManager::from_config(config);
     let model_info = model_manager.resolve_model(&model_name).await?;
     let backend_type = BackendType::from_model_path(&model_info.path);
 
@@ -431,10 +438,17 @@ async fn execute_benchmark(
         max_tokens: 100, // Shorter responses for benchmarking
         temperature: 0.7,
         top_k: 40,
+        repeat_penalty: None,
+        repeat_last_n: None,
+        min_p: None,
         top_p: 0.9,
         stream: true,
         stop_sequences: vec![],
+        stop_regex: None,
+        max_generation_ms: None,
         seed: None,
+        response_format: None,
+        logprobs: None,
     };
 
     // Start concurrent streams
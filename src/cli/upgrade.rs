@@ -387,6 +387,33 @@ async fn execute_install(
     }
 
     if dry_run {
+        let report = upgrade_manager.dry_run_install(&update_info).await?;
+        println!("\n📋 Installation plan:");
+        println!("   Target version:  {}", report.target_version.to_string());
+        match &report.planned_backup {
+            Some(plan) => println!(
+                "   Backup:          {} (~{:.1} MB)",
+                plan.backup_path.display(),
+                plan.estimated_size_bytes as f64 / 1024.0 / 1024.0
+            ),
+            None => println!("   Backup:          disabled"),
+        }
+        println!(
+            "   Disk usage:      ~{:.1} MB",
+            report.estimated_disk_usage_bytes as f64 / 1024.0 / 1024.0
+        );
+        println!(
+            "   Elevated privileges required: {}",
+            if report.requires_elevated_privileges {
+                "yes"
+            } else {
+                "no"
+            }
+        );
+        println!("   Stages:");
+        for stage in &report.stages {
+            println!("     - {}", stage.description());
+        }
         println!("✅ Dry run completed - installation would proceed");
         return Ok(());
     }
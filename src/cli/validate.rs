@@ -4,6 +4,7 @@
 //! Validates model files, config files, and directories with optional deep validation.
 //! Supports pre-execution validation to catch errors early.
 
+use crate::cli::models::parse_since;
 use crate::config::Config;
 use crate::models::ModelManager;
 use anyhow::Result;
@@ -24,6 +25,30 @@ pub struct ValidateArgs {
 
     #[arg(short, long, help = "Verbose output")]
     pub verbose: bool,
+
+    #[arg(
+        long,
+        help = "In a directory, only validate models modified after this time (RFC 3339 or YYYY-MM-DD)"
+    )]
+    pub since: Option<String>,
+
+    #[arg(long, help = "In a directory, stop at the first model that fails")]
+    pub fail_fast: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "fail_fast",
+        help = "In a directory, validate every model and report all failures at the end (default)"
+    )]
+    pub keep_going: bool,
+}
+
+/// Outcome of validating the models in a directory
+#[derive(Debug, Default)]
+struct DirectoryValidation {
+    validated: usize,
+    skipped: usize,
+    failed: Vec<PathBuf>,
 }
 
 /// Pre-execution validation to catch errors early before running the command.
@@ -39,6 +64,10 @@ pub fn pre_validate(args: &ValidateArgs) -> Result<()> {
         anyhow::bail!("Path must be a file or directory: {}", args.path.display());
     }
 
+    if let Some(since) = &args.since {
+        parse_since(since)?;
+    }
+
     Ok(())
 }
 
@@ -53,7 +82,10 @@ pub async fn execute(args: ValidateArgs, config: &Config) -> Result<()> {
     if args.path.is_file() {
         validation_passed &= validate_file(&args.path, &args, config).await?;
     } else if args.path.is_dir() {
-        validation_passed &= validate_directory(&args.path, &args, config).await?;
+        validation_passed &= validate_directory(&args.path, &args, config)
+            .await?
+            .failed
+            .is_empty();
     }
 
     if validation_passed {
@@ -91,9 +123,16 @@ async fn validate_file(path: &PathBuf, args: &ValidateArgs, config: &Config) ->
     Ok(passed)
 }
 
-async fn validate_directory(path: &PathBuf, args: &ValidateArgs, config: &Config) -> Result<bool> {
-    let mut passed = true;
-    let mut model_count = 0;
+/// Validate each model file in `path`, continuing past failures unless
+/// `--fail-fast` is given
+async fn validate_directory(
+    path: &PathBuf,
+    args: &ValidateArgs,
+    config: &Config,
+) -> Result<DirectoryValidation> {
+    let mut report = DirectoryValidation::default();
+    let since = args.since.as_deref().map(parse_since).transpose()?;
+    let model_manager = ModelManager::new(path);
 
     println!("Validating directory: {}", path.display());
 
@@ -103,23 +142,56 @@ async fn validate_directory(path: &PathBuf, args: &ValidateArgs, config: &Config
         if entry_path.is_file() {
             if let Some(ext) = entry_path.extension() {
                 if matches!(ext.to_str().unwrap_or(""), "gguf" | "onnx") {
-                    model_count += 1;
+                    if let Some(since) = since
+                        && model_manager.create_model_info(&entry_path).await?.modified <= since
+                    {
+                        report.skipped += 1;
+                        continue;
+                    }
+                    report.validated += 1;
                     if args.verbose {
                         println!("  Validating model: {}", entry_path.display());
                     }
-                    passed &= validate_model_file(&entry_path, args, config).await?;
+                    let passed = match validate_model_file(&entry_path, args, config).await {
+                        Ok(passed) => passed,
+                        Err(e) => {
+                            println!("✗ Could not validate {}: {:#}", entry_path.display(), e);
+                            false
+                        }
+                    };
+                    if !passed {
+                        report.failed.push(entry_path);
+                        if args.fail_fast {
+                            println!("Stopping at the first failure (--fail-fast)");
+                            break;
+                        }
+                    }
                 }
             }
         }
     }
 
-    if model_count == 0 {
+    if report.validated == 0 && report.skipped > 0 {
+        println!("ℹ No model files modified since the cutoff");
+    } else if report.validated == 0 {
         println!("ℹ No model files found in directory");
+    } else if report.failed.is_empty() {
+        println!("✓ Validated {} model files", report.validated);
     } else {
-        println!("✓ Validated {} model files", model_count);
+        println!(
+            "✗ {} of {} model files failed validation:",
+            report.failed.len(),
+            report.validated
+        );
+        for failed in &report.failed {
+            println!("  {}", failed.display());
+        }
+    }
+    if report.skipped > 0 {
+        println!("ℹ Skipped {} unchanged model files", report.skipped);
     }
 
-    Ok(passed)
+    Ok(report)
 }
 
 async fn validate_model_file(path: &PathBuf, args: &ValidateArgs, config: &Config) -> Result<bool> {
@@ -278,7 +350,13 @@ async fn validate_onnx_file(
                 println!("    Version: {}", metadata.version);
                 println!("    Producer: {}", metadata.producer);
                 println!("    Inputs: {}", metadata.input_count);
+                for input in &metadata.inputs {
+                    println!("      {}", input);
+                }
                 println!("    Outputs: {}", metadata.output_count);
+                for output in &metadata.outputs {
+                    println!("      {}", output);
+                }
             }
             Ok(true)
         }
@@ -361,10 +439,17 @@ async fn deep_validate_model(path: &PathBuf, config: &Config) -> Result<bool> {
                 max_tokens: 10,
                 temperature: 0.7,
                 top_k: 40,
+                repeat_penalty: None,
+                repeat_last_n: None,
+                min_p: None,
                 top_p: 0.9,
                 stream: false,
                 stop_sequences: vec![],
+                stop_regex: None,
+                max_generation_ms: None,
                 seed: None,
+                response_format: None,
+                logprobs: None,
             };
 
             match backend.infer(test_input, &inference_params).await {
@@ -397,6 +482,9 @@ mod tests {
             checksum: false,
             deep: false,
             verbose: false,
+            since: None,
+            fail_fast: false,
+            keep_going: false,
         }
     }
 
@@ -440,6 +528,9 @@ mod tests {
             checksum: true,
             deep: false,
             verbose: false,
+            since: None,
+            fail_fast: false,
+            keep_going: false,
         };
         let result = pre_validate(&args);
         assert!(result.is_ok());
@@ -456,8 +547,36 @@ mod tests {
             checksum: false,
             deep: false,
             verbose: true,
+            since: None,
+            fail_fast: false,
+            keep_going: false,
         };
         let result = pre_validate(&args);
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_directory_fail_fast_and_keep_going() {
+        let temp_dir = TempDir::new().unwrap();
+        // Empty model files fail validation
+        for name in ["a.gguf", "b.gguf", "c.onnx"] {
+            fs::write(temp_dir.path().join(name), "").unwrap();
+        }
+        let config = Config::default();
+
+        // Keep going by default: every model is validated and all failures reported
+        let mut args = create_test_args(temp_dir.path().to_path_buf());
+        let report = validate_directory(&args.path, &args, &config)
+            .await
+            .unwrap();
+        assert_eq!(report.validated, 3);
+        assert_eq!(report.failed.len(), 3);
+
+        args.fail_fast = true;
+        let report = validate_directory(&args.path, &args, &config)
+            .await
+            .unwrap();
+        assert_eq!(report.validated, 1);
+        assert_eq!(report.failed.len(), 1);
+    }
 }
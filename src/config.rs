@@ -1,21 +1,39 @@
 use crate::{
-    backends::BackendConfig, cache::CacheConfig, deployment::DeploymentConfig,
-    distributed::DistributedConfig, logging_audit::LoggingAuditConfig,
-    model_versioning::ModelVersioningConfig, monitoring::MonitoringConfig,
-    observability::ObservabilityConfig, response_cache::ResponseCacheConfig,
+    api::{key_concurrency::KeyConcurrencyConfig, limits::RequestLimits, timeouts::RouteTimeouts},
+    backends::{BackendConfig, EmbeddingOptions},
+    cache::CacheConfig,
+    deployment::DeploymentConfig,
+    distributed::DistributedConfig,
+    logging_audit::LoggingAuditConfig,
+    model_versioning::ModelVersioningConfig,
+    monitoring::MonitoringConfig,
+    observability::ObservabilityConfig,
+    operations::queue::Priority,
+    resilience::CircuitBreakerConfig,
+    response_cache::ResponseCacheConfig,
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
+use deprecations::DeprecationWarning;
 use figment::{
     Figment,
-    providers::{Env, Format, Toml},
+    providers::{Env, Serialized},
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use tracing::info;
+use tracing::{info, warn};
+
+pub mod deprecations;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub models_dir: PathBuf,
+    /// Allow `--model` and API requests to load paths outside `models_dir`
+    #[serde(default)]
+    pub allow_external_paths: bool,
+    /// Friendly model ids accepted wherever a model is named
+    #[serde(default)]
+    pub model_aliases: ModelAliases,
     pub cache_dir: PathBuf,
     pub log_level: String,
     pub log_format: String,
@@ -41,6 +59,79 @@ pub struct ServerConfig {
     pub port: u16,
     pub max_concurrent_requests: u32,
     pub request_timeout_seconds: u64,
+    /// Seconds between SSE keep-alive comments while a stream waits for tokens
+    #[serde(default = "default_sse_keepalive_interval_secs")]
+    pub sse_keepalive_interval_secs: u64,
+    /// Abort a stream that produces no token for this many seconds
+    #[serde(default = "default_stream_token_timeout_secs")]
+    pub stream_token_timeout_secs: u64,
+    /// Tokens sent together in one SSE event of a chat or completion stream; 1
+    /// sends every token as it arrives
+    #[serde(default = "default_stream_batch_tokens")]
+    pub stream_batch_tokens: usize,
+    /// Longest a token is held back waiting for the rest of its batch
+    #[serde(default = "default_stream_batch_max_wait_ms")]
+    pub stream_batch_max_wait_ms: u64,
+    /// Seconds a chat or completion stream is kept for clients to resume with
+    /// `Last-Event-ID` after the last one disconnects; generation is cancelled once
+    /// it runs out. 0, the default, stops streams as soon as their client
+    /// disconnects
+    #[serde(default = "default_stream_resume_ttl_secs")]
+    pub stream_resume_ttl_secs: u64,
+    /// Latest events of each stream kept for resuming
+    #[serde(default = "default_stream_resume_buffer_events")]
+    pub stream_resume_buffer_events: usize,
+    /// Queue priority for requests carrying each API key; these take precedence
+    /// over the `x-priority` header, which can only lower them
+    #[serde(default)]
+    pub api_key_tiers: HashMap<String, Priority>,
+    /// Inference requests each API key, by its digest in `api_key_file`, or client
+    /// address without a valid key may have in flight at once, beyond which
+    /// requests are refused with a 429
+    #[serde(default)]
+    pub key_concurrency: KeyConcurrencyConfig,
+    /// TOML file of SHA-256 hashed API keys and their scopes; when set, requests
+    /// outside its public paths need one of the keys
+    #[serde(default)]
+    pub api_key_file: Option<PathBuf>,
+    /// TOML file of tenants, their API keys and quotas; reloaded when it changes
+    #[serde(default)]
+    pub tenant_quotas_path: Option<PathBuf>,
+    /// JSON file counting the requests and tokens served for each API key per
+    /// day; no ledger is kept when unset
+    #[serde(default)]
+    pub usage_ledger_path: Option<PathBuf>,
+    /// Prompt size, message count and batch size limits for each endpoint
+    #[serde(default)]
+    pub request_limits: RequestLimits,
+    /// Seconds the chat, completions and embeddings endpoints may take to respond
+    /// before the request is aborted with a 504
+    #[serde(default)]
+    pub timeouts: RouteTimeouts,
+    /// Models to try in order for each fallback chain; a request naming a chain is
+    /// served by the first model whose circuit breaker is closed and not saturated
+    #[serde(default)]
+    pub model_fallbacks: HashMap<String, Vec<String>>,
+    /// Circuit breaker guarding each model listed in a fallback chain; `timeout_ms`
+    /// bounds its non-streaming requests
+    #[serde(default = "default_model_circuit_breaker")]
+    pub model_circuit_breaker: CircuitBreakerConfig,
+    /// Run inference once for identical deterministic non-streaming requests from
+    /// the same tenant and API key that are in flight together, sharing the output
+    /// between them; off by default
+    #[serde(default = "default_coalesce_requests")]
+    pub coalesce_requests: bool,
+    /// Post-processor for non-streaming completions that do not name one; see
+    /// [`PostProcessor`](crate::backends::PostProcessor) for the names
+    #[serde(default)]
+    pub post_processor: Option<String>,
+    /// Normalization and truncation applied to `/v1/embeddings` vectors
+    #[serde(default)]
+    pub embeddings: EmbeddingOptions,
+    /// Seconds `/readyz` reports 503 after a shutdown signal before the server
+    /// stops accepting connections, so load balancers can stop routing to it
+    #[serde(default = "default_shutdown_drain_secs")]
+    pub shutdown_drain_secs: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,10 +144,16 @@ pub struct ModelSecurityConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MetricsConfig {
+    /// Serve the metrics endpoints from `inferno serve`
     pub enabled: bool,
     pub bind_address: String,
     pub port: u16,
+    /// Path of the Prometheus text exposition
     pub path: String,
+    /// Serve metrics on `bind_address:port` instead of the API port, so they can
+    /// be kept off the public interface
+    #[serde(default)]
+    pub separate_listener: bool,
     pub collection_interval_seconds: u64,
     pub retention_hours: u64,
     pub export_system_metrics: bool,
@@ -71,6 +168,8 @@ impl Default for Config {
 
         Self {
             models_dir: data_dir.join("models"),
+            allow_external_paths: false,
+            model_aliases: ModelAliases::default(),
             cache_dir: data_dir.join("cache"),
             log_level: "info".to_string(),
             log_format: "pretty".to_string(),
@@ -99,10 +198,69 @@ impl Default for ServerConfig {
             port: 8080,
             max_concurrent_requests: 10,
             request_timeout_seconds: 300,
+            sse_keepalive_interval_secs: default_sse_keepalive_interval_secs(),
+            stream_token_timeout_secs: default_stream_token_timeout_secs(),
+            stream_batch_tokens: default_stream_batch_tokens(),
+            stream_batch_max_wait_ms: default_stream_batch_max_wait_ms(),
+            stream_resume_ttl_secs: default_stream_resume_ttl_secs(),
+            stream_resume_buffer_events: default_stream_resume_buffer_events(),
+            api_key_tiers: HashMap::new(),
+            key_concurrency: KeyConcurrencyConfig::default(),
+            tenant_quotas_path: None,
+            usage_ledger_path: None,
+            api_key_file: None,
+            request_limits: RequestLimits::default(),
+            timeouts: RouteTimeouts::default(),
+            model_fallbacks: HashMap::new(),
+            model_circuit_breaker: default_model_circuit_breaker(),
+            coalesce_requests: default_coalesce_requests(),
+            post_processor: None,
+            embeddings: EmbeddingOptions::default(),
+            shutdown_drain_secs: default_shutdown_drain_secs(),
         }
     }
 }
 
+fn default_sse_keepalive_interval_secs() -> u64 {
+    15
+}
+
+fn default_coalesce_requests() -> bool {
+    false
+}
+
+fn default_shutdown_drain_secs() -> u64 {
+    5
+}
+
+fn default_model_circuit_breaker() -> CircuitBreakerConfig {
+    CircuitBreakerConfig {
+        // Generation can take as long as the server's request timeout
+        timeout_ms: 300_000,
+        ..Default::default()
+    }
+}
+
+fn default_stream_token_timeout_secs() -> u64 {
+    120
+}
+
+fn default_stream_batch_tokens() -> usize {
+    1
+}
+
+fn default_stream_batch_max_wait_ms() -> u64 {
+    50
+}
+
+fn default_stream_resume_ttl_secs() -> u64 {
+    0
+}
+
+fn default_stream_resume_buffer_events() -> usize {
+    4096
+}
+
 impl Default for ModelSecurityConfig {
     fn default() -> Self {
         Self {
@@ -121,6 +279,7 @@ impl Default for MetricsConfig {
             bind_address: "127.0.0.1".to_string(),
             port: 9090,
             path: "/metrics".to_string(),
+            separate_listener: false,
             collection_interval_seconds: 10,
             retention_hours: 24,
             export_system_metrics: true,
@@ -131,29 +290,47 @@ impl Default for MetricsConfig {
 
 impl Config {
     pub fn load() -> Result<Self> {
-        let config_paths = Self::get_config_paths();
+        let (config, warnings) = Self::load_from(&Self::get_config_paths())?;
+        for (path, warning) in &warnings {
+            warn!("{}: {}", path.display(), warning);
+        }
 
+        // Ensure directories exist
+        config.ensure_directories()?;
+
+        Ok(config)
+    }
+
+    /// Defaults overlaid with the files among `config_paths` that exist, in
+    /// order of precedence (lowest to highest), then `INFERNO_` environment
+    /// variables, along with each file's deprecated fields
+    fn load_from(config_paths: &[PathBuf]) -> Result<(Self, Vec<(PathBuf, DeprecationWarning)>)> {
         // Start with default configuration
         let default_config = Self::default();
-        let mut figment = Figment::from(figment::providers::Serialized::defaults(default_config));
+        let mut figment = Figment::from(Serialized::defaults(default_config));
+        let mut warnings = Vec::new();
 
-        // Load configuration files in order of precedence (lowest to highest)
-        for config_path in &config_paths {
+        for config_path in config_paths {
             if config_path.exists() {
                 info!("Loading config from: {}", config_path.display());
-                figment = figment.merge(Toml::file(config_path));
+                let contents = std::fs::read_to_string(config_path).with_context(|| {
+                    format!("Failed to read config file {}", config_path.display())
+                })?;
+                let mut table: toml::Table = toml::from_str(&contents)
+                    .with_context(|| format!("Invalid config file {}", config_path.display()))?;
+                warnings.extend(
+                    deprecations::apply(&mut table)
+                        .into_iter()
+                        .map(|warning| (config_path.clone(), warning)),
+                );
+                figment = figment.merge(Serialized::defaults(table));
             }
         }
 
         // Environment variables override config files
         figment = figment.merge(Env::prefixed("INFERNO_"));
 
-        let config: Config = figment.extract()?;
-
-        // Ensure directories exist
-        config.ensure_directories()?;
-
-        Ok(config)
+        Ok((figment.extract()?, warnings))
     }
 
     pub fn save(&self, path: Option<&Path>) -> Result<()> {
@@ -168,7 +345,7 @@ impl Config {
         }
 
         let toml_string = toml::to_string_pretty(self)?;
-        std::fs::write(&config_path, toml_string)?;
+        crate::io::atomic::write_file(&config_path, toml_string)?;
 
         info!("Configuration saved to: {}", config_path.display());
         Ok(())
@@ -293,6 +470,41 @@ impl Config {
             ));
         }
 
+        // A zero keep-alive interval panics, and a zero token timeout aborts every stream
+        if self.server.sse_keepalive_interval_secs == 0 {
+            return Err(anyhow::anyhow!(
+                "SSE keep-alive interval must be greater than 0"
+            ));
+        }
+
+        if self.server.stream_token_timeout_secs == 0 {
+            return Err(anyhow::anyhow!(
+                "Stream token timeout must be greater than 0"
+            ));
+        }
+
+        // A zero route timeout would fail every request on that route
+        for (route, secs) in [
+            ("chat", self.server.timeouts.chat),
+            ("completions", self.server.timeouts.completions),
+            ("embeddings", self.server.timeouts.embeddings),
+        ] {
+            if secs == 0 {
+                return Err(anyhow::anyhow!(
+                    "Timeout for {} must be greater than 0",
+                    route
+                ));
+            }
+        }
+
+        // Validate metrics config; the path is mounted as a route
+        if !self.metrics.path.starts_with('/') {
+            return Err(anyhow::anyhow!(
+                "Invalid metrics path: '{}'. Must start with '/'",
+                self.metrics.path
+            ));
+        }
+
         // Validate model security config if present
         if let Some(ref sec_config) = self.model_security {
             if sec_config.max_model_size_gb == 0.0 {
@@ -336,6 +548,27 @@ mod tests {
             .expect("Failed to create cache directory for test");
 
         assert!(config.validate().is_ok());
+
+        // The metrics path becomes a route, so it must be absolute
+        for path in ["", "metrics"] {
+            config.metrics.path = path.to_string();
+            assert!(config.validate().is_err(), "accepted {:?}", path);
+        }
+        config.metrics.path = "/internal/metrics".to_string();
+        assert!(config.validate().is_ok());
+
+        config.server.sse_keepalive_interval_secs = 0;
+        assert!(config.validate().is_err());
+        config.server.sse_keepalive_interval_secs = 15;
+        config.server.stream_token_timeout_secs = 0;
+        assert!(config.validate().is_err());
+        config.server.stream_token_timeout_secs = 120;
+        assert!(config.validate().is_ok());
+
+        config.server.timeouts.embeddings = 0;
+        assert!(config.validate().is_err());
+        config.server.timeouts.embeddings = 30;
+        assert!(config.validate().is_ok());
     }
 
     #[test]
@@ -358,4 +591,48 @@ mod tests {
         assert!(config.is_model_size_allowed(one_mb * 500)); // 500 MB - OK
         assert!(!config.is_model_size_allowed(one_mb * 2000)); // 2 GB - Too large
     }
+
+    #[test]
+    fn test_deprecated_fields_warn_and_map_forward() {
+        let temp_dir = tempdir().unwrap();
+        let global = temp_dir.path().join("config.toml");
+        let local = temp_dir.path().join(".inferno.toml");
+        // The old and the new name both set in one file
+        std::fs::write(
+            &global,
+            "[server]\nbind_address = \"10.0.0.1\"\nhost = \"::\"\ntimeout_ms = 5000\n",
+        )
+        .unwrap();
+        std::fs::write(&local, "[server]\nhost = \"0.0.0.0\"\n").unwrap();
+
+        let (config, warnings) = Config::load_from(&[global.clone(), local.clone()]).unwrap();
+        assert_eq!(config.server.bind_address, "0.0.0.0");
+        assert_eq!(
+            config.server.request_timeout_seconds,
+            Config::default().server.request_timeout_seconds
+        );
+
+        let fields: Vec<(&Path, &str, bool)> = warnings
+            .iter()
+            .map(|(path, warning)| (path.as_path(), warning.deprecation.field, warning.mapped))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                (global.as_path(), "server.host", false),
+                (global.as_path(), "server.timeout_ms", false),
+                (local.as_path(), "server.host", true),
+            ]
+        );
+        assert_eq!(
+            warnings[2].1.to_string(),
+            "'server.host' is deprecated and was read as 'server.bind_address'; rename it"
+        );
+        assert!(
+            warnings[1]
+                .1
+                .to_string()
+                .contains("server.request_timeout_seconds")
+        );
+    }
 }
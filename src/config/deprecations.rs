@@ -0,0 +1,128 @@
+//! Deprecated configuration fields
+//!
+//! Fields that have been renamed or removed stay listed in [`DEPRECATED_FIELDS`]
+//! so config files still using them get a warning naming the replacement rather
+//! than having the setting silently ignored. Where a field was only renamed its
+//! value is moved to the new name, unless the file sets that too.
+
+use std::fmt;
+use toml::Table;
+
+/// A config field that is no longer read under its own name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deprecation {
+    /// Dotted path of the old field, such as `server.host`
+    pub field: &'static str,
+    /// Dotted path of the field taking its value, when it was renamed
+    pub replacement: Option<&'static str>,
+    /// What to do instead, for fields without a direct replacement
+    pub note: &'static str,
+}
+
+/// Every deprecated field, checked whenever a config file is loaded
+pub const DEPRECATED_FIELDS: &[Deprecation] = &[
+    Deprecation {
+        field: "server.host",
+        replacement: Some("server.bind_address"),
+        note: "",
+    },
+    Deprecation {
+        field: "server.timeout_ms",
+        replacement: None,
+        note: "set server.request_timeout_seconds, in seconds, instead",
+    },
+    Deprecation {
+        field: "server.workers",
+        replacement: None,
+        note: "pass --workers to inferno serve instead",
+    },
+];
+
+/// A deprecated field found in a config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeprecationWarning {
+    pub deprecation: Deprecation,
+    /// Whether its value was moved to the replacement field; false when there
+    /// is none or the file already sets it
+    pub mapped: bool,
+}
+
+impl fmt::Display for DeprecationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Deprecation {
+            field,
+            replacement,
+            note,
+        } = self.deprecation;
+        match replacement {
+            Some(replacement) if self.mapped => write!(
+                f,
+                "'{}' is deprecated and was read as '{}'; rename it",
+                field, replacement
+            ),
+            Some(replacement) => write!(
+                f,
+                "'{}' is deprecated and ignored because '{}' is also set; remove it",
+                field, replacement
+            ),
+            None => write!(f, "'{}' is deprecated and ignored; {}", field, note),
+        }
+    }
+}
+
+/// Move the deprecated fields in `table`, a parsed config file, to their
+/// replacements and drop the rest, returning a warning for each
+pub fn apply(table: &mut Table) -> Vec<DeprecationWarning> {
+    let mut warnings = Vec::new();
+    for &deprecation in DEPRECATED_FIELDS {
+        let Some(value) = take(table, deprecation.field) else {
+            continue;
+        };
+        let mapped = match deprecation.replacement {
+            Some(replacement) => insert_absent(table, replacement, value),
+            None => false,
+        };
+        warnings.push(DeprecationWarning {
+            deprecation,
+            mapped,
+        });
+    }
+    warnings
+}
+
+/// Remove the value at dotted `path`
+fn take(table: &mut Table, path: &str) -> Option<toml::Value> {
+    let (parents, key) = match path.rsplit_once('.') {
+        Some((parents, key)) => (Some(parents), key),
+        None => (None, path),
+    };
+    let mut table = table;
+    for parent in parents.into_iter().flat_map(|p| p.split('.')) {
+        table = table.get_mut(parent)?.as_table_mut()?;
+    }
+    table.remove(key)
+}
+
+/// Set dotted `path` to `value` unless it is already set, creating tables on
+/// the way; returns whether it was set
+fn insert_absent(table: &mut Table, path: &str, value: toml::Value) -> bool {
+    let mut keys = path.split('.').peekable();
+    let mut table = table;
+    while let Some(key) = keys.next() {
+        if keys.peek().is_none() {
+            if table.contains_key(key) {
+                return false;
+            }
+            table.insert(key.to_string(), value);
+            return true;
+        }
+        let entry = table
+            .entry(key.to_string())
+            .or_insert_with(|| toml::Value::Table(Table::new()));
+        let Some(next) = entry.as_table_mut() else {
+            return false;
+        };
+        table = next;
+    }
+    false
+}
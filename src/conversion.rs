@@ -1,5 +1,9 @@
 #![allow(dead_code, unused_imports, unused_variables, unexpected_cfgs)]
-use crate::{config::Config, models::ModelManager};
+use crate::{
+    config::Config,
+    models::ModelManager,
+    optimization::gguf::{self, GgufQuantType},
+};
 use anyhow::{Result, anyhow};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use half::f16;
@@ -383,6 +387,23 @@ impl QuantizationType {
             QuantizationType::Int16 => GgmlType::I16,
         }
     }
+
+    /// The requantizer's name for this type, for GGUF to GGUF conversion
+    fn to_gguf_quant_type(&self) -> Result<GgufQuantType> {
+        Ok(match self {
+            QuantizationType::Q4_0 => GgufQuantType::Q4_0,
+            QuantizationType::Q5_0 => GgufQuantType::Q5_0,
+            QuantizationType::Q8_0 => GgufQuantType::Q8_0,
+            QuantizationType::F16 => GgufQuantType::F16,
+            QuantizationType::F32 => GgufQuantType::F32,
+            other => {
+                return Err(anyhow!(
+                    "GGUF requantization to {:?} is not supported; use q4_0, q5_0, q8_0, f16 or f32",
+                    other
+                ));
+            }
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -467,6 +488,165 @@ pub struct ModelAnalysis {
     pub tensor_count: usize,
 }
 
+/// Default-domain ONNX operator set versions the GGUF converter maps
+pub const ONNX_GGUF_OPSET_RANGE: std::ops::RangeInclusive<i64> = 7..=21;
+
+/// An `opset_import` entry from an ONNX model
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnnxOpset {
+    /// Empty for the default `ai.onnx` domain
+    pub domain: String,
+    pub version: i64,
+}
+
+/// Read the operator sets an ONNX model imports, without loading its graph
+///
+/// Walks the top-level `ModelProto` fields only; the graph and its weights are
+/// skipped by length, so this is cheap even for large models.
+pub fn onnx_opset_imports(data: &[u8]) -> Result<Vec<OnnxOpset>> {
+    const MODEL_OPSET_IMPORT: u64 = 8;
+    const OPSET_DOMAIN: u64 = 1;
+    const OPSET_VERSION: u64 = 2;
+
+    let mut opsets = Vec::new();
+    for field in ProtoFields::new(data) {
+        let (number, value) = field?;
+        if number != MODEL_OPSET_IMPORT {
+            continue;
+        }
+        let ProtoValue::Bytes(entry) = value else {
+            return Err(anyhow!("Malformed opset_import in ONNX model"));
+        };
+        let mut opset = OnnxOpset {
+            domain: String::new(),
+            version: 0,
+        };
+        for field in ProtoFields::new(entry) {
+            match field? {
+                (OPSET_DOMAIN, ProtoValue::Bytes(domain)) => {
+                    opset.domain = String::from_utf8(domain.to_vec())
+                        .map_err(|_| anyhow!("ONNX opset domain is not valid UTF-8"))?;
+                }
+                (OPSET_VERSION, ProtoValue::Varint(version)) => opset.version = version as i64,
+                _ => {}
+            }
+        }
+        opsets.push(opset);
+    }
+    Ok(opsets)
+}
+
+/// Reject operator sets the ONNX to GGUF converter cannot map
+pub fn check_onnx_opsets_for_gguf(opsets: &[OnnxOpset]) -> Result<()> {
+    if opsets.is_empty() {
+        return Err(anyhow!(
+            "ONNX model declares no operator set; cannot convert it to GGUF"
+        ));
+    }
+    for opset in opsets {
+        let default_domain = opset.domain.is_empty() || opset.domain == "ai.onnx";
+        if !default_domain {
+            return Err(anyhow!(
+                "ONNX to GGUF conversion supports only the default ai.onnx operator set, \
+                 but the model imports {} opset {}",
+                opset.domain,
+                opset.version
+            ));
+        }
+        if !ONNX_GGUF_OPSET_RANGE.contains(&opset.version) {
+            return Err(anyhow!(
+                "ONNX to GGUF conversion supports ai.onnx opset {} to {}, but the model uses opset {}",
+                ONNX_GGUF_OPSET_RANGE.start(),
+                ONNX_GGUF_OPSET_RANGE.end(),
+                opset.version
+            ));
+        }
+    }
+    Ok(())
+}
+
+enum ProtoValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Iterator over the top-level fields of a protobuf message
+struct ProtoFields<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ProtoFields<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| anyhow!("Truncated protobuf varint"))?;
+            self.pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(anyhow!("Protobuf varint is too long"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| anyhow!("Protobuf field runs past the end of the message"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn field(&mut self) -> Result<(u64, ProtoValue<'a>)> {
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            0 => ProtoValue::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                ProtoValue::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                ProtoValue::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                ProtoValue::Fixed
+            }
+            wire_type => return Err(anyhow!("Unsupported protobuf wire type {}", wire_type)),
+        };
+        Ok((key >> 3, value))
+    }
+}
+
+impl<'a> Iterator for ProtoFields<'a> {
+    type Item = Result<(u64, ProtoValue<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let field = self.field();
+        if field.is_err() {
+            // Stop after the first error rather than resyncing mid-field
+            self.pos = self.data.len();
+        }
+        Some(field)
+    }
+}
+
 #[derive(Clone)]
 pub struct ModelConverter {
     model_manager: Arc<ModelManager>,
@@ -1041,7 +1221,37 @@ impl ModelConverter {
             (ModelFormat::Gguf, ModelFormat::Onnx) => {
                 warnings.extend(self.convert_gguf_to_onnx(input_path, output_path).await?);
             }
+            (ModelFormat::Gguf, ModelFormat::Gguf) => {
+                // Requantize in a single pass rather than converting and then
+                // quantizing the result
+                let quantization = config.quantization.as_ref().ok_or_else(|| {
+                    anyhow!(
+                        "GGUF to GGUF conversion requires a quantization type; \
+                         use `inferno convert optimize` for optimization passes"
+                    )
+                })?;
+                let target = quantization.to_gguf_quant_type()?;
+                let (input, output) = (input_path.to_path_buf(), output_path.to_path_buf());
+                let report =
+                    tokio::task::spawn_blocking(move || gguf::requantize(&input, &output, target))
+                        .await??;
+                info!(
+                    "Requantized {} tensors to {}, copied {}, relative RMS error {:.5}",
+                    report.tensors_quantized,
+                    target,
+                    report.tensors_copied,
+                    report.relative_rms_error
+                );
+                if report.tensors_quantized == 0 {
+                    warnings.push(format!(
+                        "No tensor could be quantized to {}; the model was copied unchanged",
+                        target
+                    ));
+                }
+                return Ok(warnings);
+            }
             (ModelFormat::Onnx, ModelFormat::Gguf) => {
+                self.validate_onnx_for_gguf(input_path)?;
                 warnings.extend(self.convert_onnx_to_gguf(input_path, output_path).await?);
             }
             (ModelFormat::Pytorch, ModelFormat::Gguf) => {
@@ -1104,6 +1314,15 @@ impl ModelConverter {
         Ok(warnings)
     }
 
+    /// Fail before converting if the ONNX model uses operator sets GGUF cannot express
+    fn validate_onnx_for_gguf(&self, input_path: &Path) -> Result<()> {
+        let file = File::open(input_path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        let opsets =
+            onnx_opset_imports(&mmap).map_err(|e| anyhow!("Not a valid ONNX model: {}", e))?;
+        check_onnx_opsets_for_gguf(&opsets)
+    }
+
     // Real GGUF to ONNX conversion
     #[cfg(feature = "onnx")]
     async fn convert_gguf_to_onnx(
@@ -2453,13 +2672,12 @@ impl ModelConverter {
                 }
                 self.quantize_f32_to_q4_0(&f32_data, dimensions)
             }
-            _ => {
-                warn!(
-                    "Quantization from {:?} to {:?} not implemented",
-                    source_type, target_type
-                );
-                Ok(data.to_vec())
-            }
+            // Relabelling the unchanged bytes would write a file that cannot be loaded
+            _ => Err(anyhow!(
+                "Quantization from {:?} to {:?} is not supported",
+                source_type,
+                target_type
+            )),
         }
     }
 
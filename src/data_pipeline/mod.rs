@@ -0,0 +1,599 @@
+//! Checkpointed ETL pipelines over JSON Lines files
+//!
+//! A pipeline reads records from a JSONL input, runs each through its transforms in
+//! order and appends the survivors to a JSONL output. Every `checkpoint_interval`
+//! records it flushes the output and records how far both files have got, so a job
+//! interrupted midway resumes from the last checkpoint instead of starting over.
+//! Output written after that checkpoint is discarded on resume, which keeps records
+//! from being emitted twice.
+//!
+//! Transforms may also reject a record, for example [`SchemaValidator`] when a record
+//! does not match its schema. Rejected records go to the reject file, if one is
+//! configured, together with the reason they were rejected.
+//!
+//! A transform can also route records to named sinks registered with
+//! [`DataPipeline::with_sink`], which is how [`DatasetSplitter`] writes train,
+//! validation and test partitions to separate files.
+
+pub mod split;
+pub mod validation;
+
+pub use split::{DatasetSplit, DatasetSplitter, SplitRatios};
+pub use validation::{SchemaValidator, ValidationCounts};
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::{AsyncBufReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, BufWriter, SeekFrom},
+};
+use tracing::{debug, info};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineConfig {
+    /// Input records processed between checkpoints
+    pub checkpoint_interval: usize,
+    /// Where to keep the checkpoint; defaults to `<output>.checkpoint.json`
+    pub checkpoint_path: Option<PathBuf>,
+    /// JSONL file receiving rejected records; without one they are only counted
+    #[serde(default)]
+    pub reject_path: Option<PathBuf>,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            checkpoint_interval: 100,
+            checkpoint_path: None,
+            reject_path: None,
+        }
+    }
+}
+
+/// What a transform did with a record
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransformOutcome {
+    /// Pass the record on to the next transform
+    Emit(Value),
+    /// Leave the record out of the output
+    Drop,
+    /// Send the record to the reject sink instead of the output
+    Reject { record: Value, reason: String },
+    /// Write the record to the named sink instead of the output, skipping any
+    /// later transforms
+    Route { sink: String, record: Value },
+}
+
+/// A single step applied to every record
+pub trait Transform: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Transform one record; an error aborts the run, leaving the last checkpoint
+    fn apply(&self, record: Value) -> Result<TransformOutcome>;
+}
+
+/// Line written to the reject sink for each rejected record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RejectedRecord {
+    pub transform: String,
+    pub error: String,
+    pub record: Value,
+}
+
+/// How far a pipeline had got when it last checkpointed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PipelineCheckpoint {
+    pub pipeline: String,
+    /// Bytes of input consumed
+    pub input_offset: u64,
+    pub records_read: u64,
+    /// Bytes of output known to be complete
+    pub output_offset: u64,
+    pub records_emitted: u64,
+    /// Bytes of the reject file known to be complete
+    #[serde(default)]
+    pub reject_offset: u64,
+    #[serde(default)]
+    pub records_rejected: u64,
+    /// Bytes known to be complete in each named sink
+    #[serde(default)]
+    pub sink_offsets: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub records_routed: BTreeMap<String, u64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PipelineStats {
+    pub records_read: u64,
+    /// Records written to the output or to a named sink
+    pub records_emitted: u64,
+    pub records_dropped: u64,
+    pub records_rejected: u64,
+    /// Records written to each named sink
+    pub records_routed: BTreeMap<String, u64>,
+    /// Input records skipped because a checkpoint already covered them
+    pub resumed_from: Option<u64>,
+}
+
+pub struct DataPipeline {
+    name: String,
+    config: PipelineConfig,
+    transforms: Vec<Box<dyn Transform>>,
+    sinks: BTreeMap<String, PathBuf>,
+}
+
+/// Where a record ended up after every transform ran
+enum Disposition {
+    Output(Value),
+    Routed(String, Value),
+    Dropped,
+    Rejected(RejectedRecord),
+}
+
+impl DataPipeline {
+    pub fn new(name: impl Into<String>, config: PipelineConfig) -> Self {
+        Self {
+            name: name.into(),
+            config,
+            transforms: Vec::new(),
+            sinks: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_transform(mut self, transform: impl Transform + 'static) -> Self {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// Register a JSONL file that transforms can route records to by name
+    pub fn with_sink(mut self, name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.sinks.insert(name.into(), path.into());
+        self
+    }
+
+    pub fn checkpoint_path(&self, output_path: &Path) -> PathBuf {
+        self.config.checkpoint_path.clone().unwrap_or_else(|| {
+            let mut name = output_path.file_name().unwrap_or_default().to_os_string();
+            name.push(".checkpoint.json");
+            output_path.with_file_name(name)
+        })
+    }
+
+    /// Load the checkpoint left by an interrupted run, if there is one
+    pub async fn load_checkpoint(&self, output_path: &Path) -> Result<Option<PipelineCheckpoint>> {
+        let path = self.checkpoint_path(output_path);
+        if !fs::try_exists(&path).await? {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path).await?;
+        let checkpoint: PipelineCheckpoint = serde_json::from_str(&content)
+            .with_context(|| format!("Corrupt pipeline checkpoint {}", path.display()))?;
+        if checkpoint.pipeline != self.name {
+            bail!(
+                "Checkpoint {} belongs to pipeline '{}', not '{}'",
+                path.display(),
+                checkpoint.pipeline,
+                self.name
+            );
+        }
+        Ok(Some(checkpoint))
+    }
+
+    /// Run the pipeline, resuming from a checkpoint when one exists
+    ///
+    /// The checkpoint is removed once the whole input has been processed.
+    pub async fn run(&self, input_path: &Path, output_path: &Path) -> Result<PipelineStats> {
+        let checkpoint_path = self.checkpoint_path(output_path);
+        let checkpoint = self.load_checkpoint(output_path).await?;
+
+        let mut stats = PipelineStats::default();
+        let mut input_offset = 0;
+        if let Some(checkpoint) = &checkpoint {
+            input_offset = checkpoint.input_offset;
+            stats.records_read = checkpoint.records_read;
+            stats.records_emitted = checkpoint.records_emitted;
+            stats.records_rejected = checkpoint.records_rejected;
+            stats.records_routed = checkpoint.records_routed.clone();
+            stats.records_dropped =
+                checkpoint.records_read - checkpoint.records_emitted - checkpoint.records_rejected;
+            stats.resumed_from = Some(checkpoint.records_read);
+            info!(
+                "Resuming pipeline '{}' after {} records",
+                self.name, checkpoint.records_read
+            );
+        }
+
+        let mut output = Sink::open(
+            output_path,
+            checkpoint.as_ref().map(|c| c.output_offset),
+            &checkpoint_path,
+        )
+        .await?;
+        let mut rejects = match &self.config.reject_path {
+            Some(path) => Some(
+                Sink::open(
+                    path,
+                    checkpoint.as_ref().map(|c| c.reject_offset),
+                    &checkpoint_path,
+                )
+                .await?,
+            ),
+            None => None,
+        };
+        let mut sinks = BTreeMap::new();
+        for (name, path) in &self.sinks {
+            let offset = checkpoint
+                .as_ref()
+                .map(|c| c.sink_offsets.get(name).copied().unwrap_or(0));
+            sinks.insert(
+                name.clone(),
+                Sink::open(path, offset, &checkpoint_path).await?,
+            );
+        }
+
+        let mut input = File::open(input_path)
+            .await
+            .with_context(|| format!("Failed to open input {}", input_path.display()))?;
+        input.seek(SeekFrom::Start(input_offset)).await?;
+        let mut input = BufReader::new(input);
+
+        let interval = self.config.checkpoint_interval.max(1) as u64;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = input.read_line(&mut line).await?;
+            if read == 0 {
+                break;
+            }
+            input_offset += read as u64;
+
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+
+            let record: Value = serde_json::from_str(trimmed).with_context(|| {
+                format!("Invalid JSON at input record {}", stats.records_read + 1)
+            })?;
+            stats.records_read += 1;
+
+            match self.transform(record)? {
+                Disposition::Output(record) => {
+                    output.write(&record).await?;
+                    stats.records_emitted += 1;
+                }
+                Disposition::Routed(name, record) => {
+                    let sink = sinks.get_mut(&name).ok_or_else(|| {
+                        anyhow!("No sink named '{}' in pipeline '{}'", name, self.name)
+                    })?;
+                    sink.write(&record).await?;
+                    stats.records_emitted += 1;
+                    *stats.records_routed.entry(name).or_default() += 1;
+                }
+                Disposition::Dropped => stats.records_dropped += 1,
+                Disposition::Rejected(rejected) => {
+                    debug!(
+                        "Record {} rejected by '{}': {}",
+                        stats.records_read, rejected.transform, rejected.error
+                    );
+                    if let Some(rejects) = rejects.as_mut() {
+                        rejects.write(&rejected).await?;
+                    }
+                    stats.records_rejected += 1;
+                }
+            }
+
+            if stats.records_read % interval == 0 {
+                output.sync().await?;
+                if let Some(rejects) = rejects.as_mut() {
+                    rejects.sync().await?;
+                }
+                for sink in sinks.values_mut() {
+                    sink.sync().await?;
+                }
+                self.save_checkpoint(
+                    &checkpoint_path,
+                    PipelineCheckpoint {
+                        pipeline: self.name.clone(),
+                        input_offset,
+                        records_read: stats.records_read,
+                        output_offset: output.offset,
+                        records_emitted: stats.records_emitted,
+                        reject_offset: rejects.as_ref().map_or(0, |r| r.offset),
+                        records_rejected: stats.records_rejected,
+                        sink_offsets: sinks
+                            .iter()
+                            .map(|(name, sink)| (name.clone(), sink.offset))
+                            .collect(),
+                        records_routed: stats.records_routed.clone(),
+                        updated_at: Utc::now(),
+                    },
+                )
+                .await?;
+            }
+        }
+
+        output.sync().await?;
+        if let Some(rejects) = rejects.as_mut() {
+            rejects.sync().await?;
+        }
+        for sink in sinks.values_mut() {
+            sink.sync().await?;
+        }
+        if fs::try_exists(&checkpoint_path).await? {
+            fs::remove_file(&checkpoint_path).await?;
+        }
+
+        info!(
+            "Pipeline '{}' finished: {} read, {} emitted, {} dropped, {} rejected",
+            self.name,
+            stats.records_read,
+            stats.records_emitted,
+            stats.records_dropped,
+            stats.records_rejected
+        );
+        Ok(stats)
+    }
+
+    /// Run `record` through every transform, stopping at the first that drops,
+    /// rejects or routes it
+    fn transform(&self, record: Value) -> Result<Disposition> {
+        let mut record = record;
+        for transform in &self.transforms {
+            match transform
+                .apply(record)
+                .with_context(|| format!("Transform '{}' failed", transform.name()))?
+            {
+                TransformOutcome::Emit(next) => record = next,
+                TransformOutcome::Drop => return Ok(Disposition::Dropped),
+                TransformOutcome::Reject { record, reason } => {
+                    return Ok(Disposition::Rejected(RejectedRecord {
+                        transform: transform.name().to_string(),
+                        error: reason,
+                        record,
+                    }));
+                }
+                TransformOutcome::Route { sink, record } => {
+                    return Ok(Disposition::Routed(sink, record));
+                }
+            }
+        }
+        Ok(Disposition::Output(record))
+    }
+
+    /// Write the checkpoint atomically so a crash mid-write leaves the previous
+    /// checkpoint intact
+    async fn save_checkpoint(&self, path: &Path, checkpoint: PipelineCheckpoint) -> Result<()> {
+        crate::io::atomic::write_file_async(path, serde_json::to_vec_pretty(&checkpoint)?).await?;
+        debug!(
+            "Pipeline '{}' checkpointed after {} records",
+            self.name, checkpoint.records_read
+        );
+        Ok(())
+    }
+}
+
+/// JSONL file written by a pipeline, tracking how many bytes it holds
+struct Sink {
+    writer: BufWriter<File>,
+    offset: u64,
+}
+
+impl Sink {
+    /// Open `path` for writing, keeping the first `resume_offset` bytes when resuming
+    /// and discarding everything else
+    async fn open(path: &Path, resume_offset: Option<u64>, checkpoint_path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(resume_offset.is_none())
+            .open(path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        let offset = resume_offset.unwrap_or(0);
+        if offset > 0 {
+            if file.metadata().await?.len() < offset {
+                bail!(
+                    "{} is shorter than its checkpoint; remove {} to start over",
+                    path.display(),
+                    checkpoint_path.display()
+                );
+            }
+            // Anything past the checkpoint was written by the interrupted run and
+            // will be produced again
+            file.set_len(offset).await?;
+        }
+        file.seek(SeekFrom::Start(offset)).await?;
+
+        Ok(Self {
+            writer: BufWriter::new(file),
+            offset,
+        })
+    }
+
+    async fn write<T: Serialize>(&mut self, value: &T) -> Result<()> {
+        let mut encoded = serde_json::to_vec(value)?;
+        encoded.push(b'\n');
+        self.writer.write_all(&encoded).await?;
+        self.offset += encoded.len() as u64;
+        Ok(())
+    }
+
+    async fn sync(&mut self) -> Result<()> {
+        self.writer.flush().await?;
+        self.writer.get_ref().sync_data().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tempfile::tempdir;
+
+    struct Double;
+
+    impl Transform for Double {
+        fn name(&self) -> &str {
+            "double"
+        }
+
+        fn apply(&self, mut record: Value) -> Result<TransformOutcome> {
+            let n = record["n"].as_i64().unwrap();
+            record["doubled"] = Value::from(n * 2);
+            Ok(TransformOutcome::Emit(record))
+        }
+    }
+
+    /// Fails on one record while armed, standing in for a crash partway through
+    struct InterruptAt {
+        n: i64,
+        armed: AtomicBool,
+    }
+
+    impl Transform for InterruptAt {
+        fn name(&self) -> &str {
+            "interrupt"
+        }
+
+        fn apply(&self, record: Value) -> Result<TransformOutcome> {
+            if record["n"] == self.n && self.armed.swap(false, Ordering::SeqCst) {
+                bail!("interrupted at record {}", self.n);
+            }
+            Ok(TransformOutcome::Emit(record))
+        }
+    }
+
+    struct DropOdd;
+
+    impl Transform for DropOdd {
+        fn name(&self) -> &str {
+            "drop_odd"
+        }
+
+        fn apply(&self, record: Value) -> Result<TransformOutcome> {
+            if record["n"].as_i64().unwrap() % 2 == 0 {
+                Ok(TransformOutcome::Emit(record))
+            } else {
+                Ok(TransformOutcome::Drop)
+            }
+        }
+    }
+
+    async fn write_input(path: &Path, count: i64) {
+        let content: String = (0..count)
+            .map(|n| format!("{}\n", serde_json::json!({ "n": n })))
+            .collect();
+        fs::write(path, content).await.unwrap();
+    }
+
+    async fn read_output(path: &Path) -> Vec<Value> {
+        fs::read_to_string(path)
+            .await
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_pipeline_resumes_without_duplicates() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("input.jsonl");
+        let output = dir.path().join("output.jsonl");
+        write_input(&input, 50).await;
+
+        let config = PipelineConfig {
+            checkpoint_interval: 10,
+            ..Default::default()
+        };
+        let pipeline = DataPipeline::new("etl", config)
+            .with_transform(InterruptAt {
+                n: 27,
+                armed: AtomicBool::new(true),
+            })
+            .with_transform(Double);
+
+        assert!(pipeline.run(&input, &output).await.is_err());
+
+        // The run got past record 20 but not 30
+        let checkpoint = pipeline.load_checkpoint(&output).await.unwrap().unwrap();
+        assert_eq!(checkpoint.records_read, 20);
+
+        // A crash can also leave records written after the checkpoint, the last one torn
+        let mut leftover = OpenOptions::new().append(true).open(&output).await.unwrap();
+        leftover
+            .write_all(b"{\"n\":20,\"doubled\":40}\n{\"n\":2")
+            .await
+            .unwrap();
+        leftover.flush().await.unwrap();
+
+        let stats = pipeline.run(&input, &output).await.unwrap();
+        assert_eq!(stats.resumed_from, Some(20));
+        assert_eq!(stats.records_read, 50);
+        assert_eq!(stats.records_emitted, 50);
+        assert!(pipeline.load_checkpoint(&output).await.unwrap().is_none());
+
+        let records = read_output(&output).await;
+        let seen: Vec<i64> = records.iter().map(|r| r["n"].as_i64().unwrap()).collect();
+        assert_eq!(seen, (0..50).collect::<Vec<_>>());
+        assert!(
+            records
+                .iter()
+                .all(|r| r["doubled"] == r["n"].as_i64().unwrap() * 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dropped_records_and_fresh_run() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("input.jsonl");
+        let output = dir.path().join("output.jsonl");
+        write_input(&input, 7).await;
+        fs::write(&output, "stale\n").await.unwrap();
+
+        let pipeline =
+            DataPipeline::new("evens", PipelineConfig::default()).with_transform(DropOdd);
+        let stats = pipeline.run(&input, &output).await.unwrap();
+
+        assert_eq!(stats.records_emitted, 4);
+        assert_eq!(stats.records_dropped, 3);
+        assert_eq!(stats.resumed_from, None);
+        assert_eq!(read_output(&output).await.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_from_other_pipeline_is_rejected() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("output.jsonl");
+        let pipeline = DataPipeline::new("mine", PipelineConfig::default());
+        pipeline
+            .save_checkpoint(
+                &pipeline.checkpoint_path(&output),
+                PipelineCheckpoint {
+                    pipeline: "theirs".to_string(),
+                    input_offset: 0,
+                    records_read: 0,
+                    output_offset: 0,
+                    records_emitted: 0,
+                    reject_offset: 0,
+                    records_rejected: 0,
+                    sink_offsets: BTreeMap::new(),
+                    records_routed: BTreeMap::new(),
+                    updated_at: Utc::now(),
+                },
+            )
+            .await
+            .unwrap();
+
+        assert!(pipeline.load_checkpoint(&output).await.is_err());
+    }
+}
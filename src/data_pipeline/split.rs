@@ -0,0 +1,268 @@
+//! Deterministic train/validation/test splitting
+//!
+//! [`DatasetSplitter`] assigns each record to one partition from a seeded hash of the
+//! record, or of a key field within it, and routes it to the sink named after that
+//! partition. The assignment depends only on the seed and the record, so reruns put
+//! every record in the same place, and a record can never land in two partitions.
+
+use super::{Transform, TransformOutcome};
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatasetSplit {
+    Train,
+    Validation,
+    Test,
+}
+
+impl DatasetSplit {
+    /// Name of the sink records in this partition are routed to
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DatasetSplit::Train => "train",
+            DatasetSplit::Validation => "validation",
+            DatasetSplit::Test => "test",
+        }
+    }
+}
+
+/// Fraction of records in each partition; the three must add up to 1
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SplitRatios {
+    pub train: f64,
+    pub validation: f64,
+    pub test: f64,
+}
+
+impl Default for SplitRatios {
+    fn default() -> Self {
+        Self {
+            train: 0.8,
+            validation: 0.1,
+            test: 0.1,
+        }
+    }
+}
+
+impl SplitRatios {
+    fn validate(&self) -> Result<()> {
+        for (name, ratio) in [
+            ("train", self.train),
+            ("validation", self.validation),
+            ("test", self.test),
+        ] {
+            if !ratio.is_finite() || ratio < 0.0 {
+                bail!("Split ratio for {} must be a non-negative number", name);
+            }
+        }
+        let total = self.train + self.validation + self.test;
+        if (total - 1.0).abs() > 1e-6 {
+            bail!("Split ratios must add up to 1, got {}", total);
+        }
+        Ok(())
+    }
+}
+
+/// Transform routing each record to the `train`, `validation` or `test` sink
+pub struct DatasetSplitter {
+    ratios: SplitRatios,
+    seed: u64,
+    key_field: Option<String>,
+}
+
+impl DatasetSplitter {
+    pub fn new(ratios: SplitRatios, seed: u64) -> Result<Self> {
+        ratios.validate()?;
+        Ok(Self {
+            ratios,
+            seed,
+            key_field: None,
+        })
+    }
+
+    /// Split on one field instead of the whole record, so records sharing a key,
+    /// such as duplicates of one sample, always stay together
+    pub fn with_key_field(mut self, field: impl Into<String>) -> Self {
+        self.key_field = Some(field.into());
+        self
+    }
+
+    /// The partition `record` belongs to, or `None` when it lacks the key field
+    pub fn split_for(&self, record: &Value) -> Option<DatasetSplit> {
+        let key = match &self.key_field {
+            Some(field) => record.get(field)?,
+            None => record,
+        };
+        // Object keys serialize in sorted order, so equal records hash equally
+        let hash = xxhash_rust::xxh3::xxh3_64_with_seed(key.to_string().as_bytes(), self.seed);
+        let position = (hash >> 11) as f64 / (1u64 << 53) as f64;
+
+        Some(if position < self.ratios.train {
+            DatasetSplit::Train
+        } else if position < self.ratios.train + self.ratios.validation {
+            DatasetSplit::Validation
+        } else {
+            DatasetSplit::Test
+        })
+    }
+}
+
+impl Transform for DatasetSplitter {
+    fn name(&self) -> &str {
+        "dataset_split"
+    }
+
+    fn apply(&self, record: Value) -> Result<TransformOutcome> {
+        match self.split_for(&record) {
+            Some(split) => Ok(TransformOutcome::Route {
+                sink: split.as_str().to_string(),
+                record,
+            }),
+            None => Ok(TransformOutcome::Reject {
+                record,
+                reason: format!(
+                    "missing split key '{}'",
+                    self.key_field.as_deref().unwrap_or_default()
+                ),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_pipeline::{DataPipeline, PipelineConfig, PipelineStats};
+    use serde_json::json;
+    use std::collections::HashSet;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    const SPLITS: [DatasetSplit; 3] = [
+        DatasetSplit::Train,
+        DatasetSplit::Validation,
+        DatasetSplit::Test,
+    ];
+
+    async fn run_split(dir: &Path, input: &Path, seed: u64) -> PipelineStats {
+        let mut pipeline = DataPipeline::new("split", PipelineConfig::default())
+            .with_transform(DatasetSplitter::new(SplitRatios::default(), seed).unwrap());
+        for split in SPLITS {
+            pipeline = pipeline.with_sink(
+                split.as_str(),
+                dir.join(format!("{}.jsonl", split.as_str())),
+            );
+        }
+        pipeline
+            .run(input, &dir.join("output.jsonl"))
+            .await
+            .unwrap()
+    }
+
+    async fn read(dir: &Path, split: DatasetSplit) -> String {
+        tokio::fs::read_to_string(dir.join(format!("{}.jsonl", split.as_str())))
+            .await
+            .unwrap()
+    }
+
+    async fn read_output(dir: &Path) -> String {
+        tokio::fs::read_to_string(dir.join("output.jsonl"))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_split_ratios_and_determinism() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("input.jsonl");
+        let body: String = (0..2000)
+            .map(|n| format!("{}\n", json!({"n": n, "text": format!("sample {}", n)})))
+            .collect();
+        tokio::fs::write(&input, body).await.unwrap();
+
+        let first = dir.path().join("first");
+        let second = dir.path().join("second");
+        let other_seed = dir.path().join("other_seed");
+        for path in [&first, &second, &other_seed] {
+            tokio::fs::create_dir(path).await.unwrap();
+        }
+
+        let stats = run_split(&first, &input, 42).await;
+        assert_eq!(stats.records_read, 2000);
+        assert_eq!(stats.records_emitted, 2000);
+
+        let train = stats.records_routed["train"] as f64 / 2000.0;
+        let validation = stats.records_routed["validation"] as f64 / 2000.0;
+        let test = stats.records_routed["test"] as f64 / 2000.0;
+        assert!((train - 0.8).abs() < 0.03, "train ratio {}", train);
+        assert!(
+            (validation - 0.1).abs() < 0.03,
+            "validation ratio {}",
+            validation
+        );
+        assert!((test - 0.1).abs() < 0.03, "test ratio {}", test);
+
+        // Every record lands in exactly one partition
+        let mut seen = HashSet::new();
+        for split in SPLITS {
+            for line in read(&first, split).await.lines() {
+                assert!(seen.insert(line.to_string()), "{} appears twice", line);
+            }
+        }
+        assert_eq!(seen.len(), 2000);
+
+        // The output file itself stays empty when everything is routed
+        assert!(read_output(&first).await.is_empty());
+
+        assert_eq!(run_split(&second, &input, 42).await, stats);
+        for split in SPLITS {
+            assert_eq!(read(&first, split).await, read(&second, split).await);
+        }
+
+        run_split(&other_seed, &input, 7).await;
+        assert_ne!(
+            read(&first, DatasetSplit::Test).await,
+            read(&other_seed, DatasetSplit::Test).await
+        );
+    }
+
+    #[test]
+    fn test_key_field_keeps_duplicates_together() {
+        let splitter = DatasetSplitter::new(SplitRatios::default(), 1)
+            .unwrap()
+            .with_key_field("id");
+
+        for id in 0..100 {
+            let a = splitter.split_for(&json!({"id": id, "text": "original"}));
+            let b = splitter.split_for(&json!({"id": id, "text": "paraphrase"}));
+            assert_eq!(a, b);
+        }
+        assert_eq!(splitter.split_for(&json!({"text": "no id"})), None);
+    }
+
+    #[test]
+    fn test_invalid_ratios() {
+        for ratios in [
+            SplitRatios {
+                train: 0.8,
+                validation: 0.1,
+                test: 0.2,
+            },
+            SplitRatios {
+                train: 1.1,
+                validation: -0.1,
+                test: 0.0,
+            },
+            SplitRatios {
+                train: f64::NAN,
+                validation: 0.5,
+                test: 0.5,
+            },
+        ] {
+            assert!(DatasetSplitter::new(ratios, 0).is_err(), "{:?}", ratios);
+        }
+    }
+}
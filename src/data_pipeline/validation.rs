@@ -0,0 +1,552 @@
+//! Schema enforcement for pipeline records
+//!
+//! [`SchemaValidator`] checks each record against a JSON Schema and rejects the ones
+//! that do not match, so they end up in the pipeline's reject file with the reasons
+//! instead of in the output. Supported keywords: `type`, `enum`, `const`, `anyOf`,
+//! `oneOf`, `allOf`, `properties`, `required`, `additionalProperties`, `items`,
+//! `minItems`, `maxItems`, `minimum`, `maximum`, `exclusiveMinimum`,
+//! `exclusiveMaximum`, `minLength`, `maxLength` and `pattern`.
+
+use super::{Transform, TransformOutcome};
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const TYPES: [&str; 7] = [
+    "object", "array", "string", "number", "integer", "boolean", "null",
+];
+
+/// Valid and invalid record counts, shared with whoever holds them while the
+/// pipeline runs
+#[derive(Debug, Default)]
+pub struct ValidationCounts {
+    valid: AtomicU64,
+    invalid: AtomicU64,
+}
+
+impl ValidationCounts {
+    pub fn valid(&self) -> u64 {
+        self.valid.load(Ordering::Relaxed)
+    }
+
+    pub fn invalid(&self) -> u64 {
+        self.invalid.load(Ordering::Relaxed)
+    }
+}
+
+/// Transform rejecting records that do not match a JSON Schema
+pub struct SchemaValidator {
+    schema: Value,
+    patterns: HashMap<String, Regex>,
+    counts: Arc<ValidationCounts>,
+}
+
+impl SchemaValidator {
+    /// Check `schema` up front so a malformed one fails before any record is read
+    pub fn new(schema: Value) -> Result<Self> {
+        let mut patterns = HashMap::new();
+        check_schema(&schema, "$", &mut patterns)?;
+        Ok(Self {
+            schema,
+            patterns,
+            counts: Arc::new(ValidationCounts::default()),
+        })
+    }
+
+    pub fn counts(&self) -> Arc<ValidationCounts> {
+        Arc::clone(&self.counts)
+    }
+
+    /// Every way `record` violates the schema; empty when it is valid
+    pub fn validate(&self, record: &Value) -> Vec<String> {
+        let mut errors = Vec::new();
+        self.check(&self.schema, record, "$", &mut errors);
+        errors
+    }
+
+    fn check(&self, schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+        let Some(schema) = schema.as_object() else {
+            return;
+        };
+
+        if let Some(types) = schema.get("type") {
+            let matches = match types {
+                Value::String(ty) => has_type(value, ty),
+                Value::Array(types) => types
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .any(|ty| has_type(value, ty)),
+                _ => true,
+            };
+            if !matches {
+                errors.push(format!(
+                    "{}: expected {}, got {}",
+                    path,
+                    describe_types(types),
+                    type_name(value)
+                ));
+                // Further keywords would only repeat the mismatch
+                return;
+            }
+        }
+
+        if let Some(expected) = schema.get("const")
+            && value != expected
+        {
+            errors.push(format!("{}: expected {}", path, expected));
+        }
+
+        if let Some(Value::Array(values)) = schema.get("enum")
+            && !values.contains(value)
+        {
+            errors.push(format!(
+                "{}: {} is not one of {}",
+                path,
+                value,
+                Value::Array(values.clone())
+            ));
+        }
+
+        if let Some(Value::Array(options)) = schema.get("allOf") {
+            for option in options {
+                self.check(option, value, path, errors);
+            }
+        }
+
+        if let Some(Value::Array(options)) = schema.get("anyOf")
+            && !options
+                .iter()
+                .any(|option| self.is_valid(option, value, path))
+        {
+            errors.push(format!("{}: does not match any schema in anyOf", path));
+        }
+
+        if let Some(Value::Array(options)) = schema.get("oneOf") {
+            let matched = options
+                .iter()
+                .filter(|option| self.is_valid(option, value, path))
+                .count();
+            if matched != 1 {
+                errors.push(format!(
+                    "{}: matches {} schemas in oneOf, expected exactly 1",
+                    path, matched
+                ));
+            }
+        }
+
+        match value {
+            Value::Object(object) => self.check_object(schema, object, path, errors),
+            Value::Array(items) => self.check_array(schema, items, path, errors),
+            Value::String(s) => self.check_string(schema, s, path, errors),
+            Value::Number(n) => {
+                if let Some(n) = n.as_f64() {
+                    check_number(schema, n, path, errors);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn is_valid(&self, schema: &Value, value: &Value, path: &str) -> bool {
+        let mut errors = Vec::new();
+        self.check(schema, value, path, &mut errors);
+        errors.is_empty()
+    }
+
+    fn check_object(
+        &self,
+        schema: &Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &str,
+        errors: &mut Vec<String>,
+    ) {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    errors.push(format!("{}: missing required property '{}'", path, key));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (key, value) in object {
+            let child = format!("{}.{}", path, key);
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(property) => self.check(property, value, &child, errors),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        errors.push(format!("{}: unexpected property '{}'", path, key));
+                    }
+                    Some(additional @ Value::Object(_)) => {
+                        self.check(additional, value, &child, errors);
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    fn check_array(
+        &self,
+        schema: &Map<String, Value>,
+        items: &[Value],
+        path: &str,
+        errors: &mut Vec<String>,
+    ) {
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+            && (items.len() as u64) < min
+        {
+            errors.push(format!("{}: expected at least {} items", path, min));
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+            && items.len() as u64 > max
+        {
+            errors.push(format!("{}: expected at most {} items", path, max));
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                self.check(item_schema, item, &format!("{}[{}]", path, i), errors);
+            }
+        }
+    }
+
+    fn check_string(
+        &self,
+        schema: &Map<String, Value>,
+        s: &str,
+        path: &str,
+        errors: &mut Vec<String>,
+    ) {
+        let len = s.chars().count() as u64;
+        if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+            && len < min
+        {
+            errors.push(format!("{}: expected at least {} characters", path, min));
+        }
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+            && len > max
+        {
+            errors.push(format!("{}: expected at most {} characters", path, max));
+        }
+        if let Some(pattern) = schema.get("pattern").and_then(Value::as_str)
+            && let Some(regex) = self.patterns.get(pattern)
+            && !regex.is_match(s)
+        {
+            errors.push(format!("{}: does not match pattern '{}'", path, pattern));
+        }
+    }
+}
+
+impl Transform for SchemaValidator {
+    fn name(&self) -> &str {
+        "schema_validation"
+    }
+
+    fn apply(&self, record: Value) -> Result<TransformOutcome> {
+        let errors = self.validate(&record);
+        if errors.is_empty() {
+            self.counts.valid.fetch_add(1, Ordering::Relaxed);
+            Ok(TransformOutcome::Emit(record))
+        } else {
+            self.counts.invalid.fetch_add(1, Ordering::Relaxed);
+            Ok(TransformOutcome::Reject {
+                record,
+                reason: errors.join("; "),
+            })
+        }
+    }
+}
+
+fn check_number(schema: &Map<String, Value>, n: f64, path: &str, errors: &mut Vec<String>) {
+    let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+    if let Some(min) = bound("minimum")
+        && n < min
+    {
+        errors.push(format!("{}: {} is less than {}", path, n, min));
+    }
+    if let Some(max) = bound("maximum")
+        && n > max
+    {
+        errors.push(format!("{}: {} is greater than {}", path, n, max));
+    }
+    if let Some(min) = bound("exclusiveMinimum")
+        && n <= min
+    {
+        errors.push(format!("{}: {} is not greater than {}", path, n, min));
+    }
+    if let Some(max) = bound("exclusiveMaximum")
+        && n >= max
+    {
+        errors.push(format!("{}: {} is not less than {}", path, n, max));
+    }
+}
+
+/// Reject schemas the validator would silently misread
+fn check_schema(schema: &Value, path: &str, patterns: &mut HashMap<String, Regex>) -> Result<()> {
+    let Some(schema) = schema.as_object() else {
+        bail!("Invalid schema at {}: expected an object", path);
+    };
+
+    match schema.get("type") {
+        None => {}
+        Some(Value::String(ty)) => check_type_name(ty, path)?,
+        Some(Value::Array(types)) => {
+            for ty in types {
+                match ty.as_str() {
+                    Some(ty) => check_type_name(ty, path)?,
+                    None => bail!("Invalid schema at {}: type entries must be strings", path),
+                }
+            }
+        }
+        Some(_) => bail!("Invalid schema at {}: type must be a string or array", path),
+    }
+
+    if let Some(pattern) = schema.get("pattern") {
+        let Some(pattern) = pattern.as_str() else {
+            bail!("Invalid schema at {}: pattern must be a string", path);
+        };
+        let regex = Regex::new(pattern)
+            .with_context(|| format!("Invalid schema at {}: bad pattern", path))?;
+        patterns.insert(pattern.to_string(), regex);
+    }
+
+    if let Some(properties) = schema.get("properties") {
+        let Some(properties) = properties.as_object() else {
+            bail!("Invalid schema at {}: properties must be an object", path);
+        };
+        for (key, property) in properties {
+            check_schema(property, &format!("{}.{}", path, key), patterns)?;
+        }
+    }
+
+    if let Some(required) = schema.get("required")
+        && !required
+            .as_array()
+            .is_some_and(|keys| keys.iter().all(Value::is_string))
+    {
+        bail!(
+            "Invalid schema at {}: required must be an array of strings",
+            path
+        );
+    }
+
+    match schema.get("additionalProperties") {
+        None | Some(Value::Bool(_)) => {}
+        Some(additional) => check_schema(additional, &format!("{}.*", path), patterns)?,
+    }
+
+    if let Some(items) = schema.get("items") {
+        check_schema(items, &format!("{}[]", path), patterns)?;
+    }
+
+    if let Some(values) = schema.get("enum")
+        && !values.is_array()
+    {
+        bail!("Invalid schema at {}: enum must be an array", path);
+    }
+
+    for keyword in ["anyOf", "oneOf", "allOf"] {
+        if let Some(options) = schema.get(keyword) {
+            let Some(options) = options.as_array().filter(|o| !o.is_empty()) else {
+                bail!(
+                    "Invalid schema at {}: {} must be a non-empty array",
+                    path,
+                    keyword
+                );
+            };
+            for option in options {
+                check_schema(option, path, patterns)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_type_name(ty: &str, path: &str) -> Result<()> {
+    if !TYPES.contains(&ty) {
+        bail!("Invalid schema at {}: unknown type '{}'", path, ty);
+    }
+    Ok(())
+}
+
+fn has_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => match value {
+            Value::Number(n) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+            }
+            _ => false,
+        },
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => false,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn describe_types(types: &Value) -> String {
+    match types {
+        Value::Array(types) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" or "),
+        Value::String(ty) => ty.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_pipeline::{DataPipeline, PipelineConfig, RejectedRecord};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn user_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": {"type": "integer", "minimum": 1},
+                "email": {"type": "string", "pattern": "^[^@]+@[^@]+$"},
+                "role": {"enum": ["admin", "user"]},
+                "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 3}
+            },
+            "required": ["id", "email"],
+            "additionalProperties": false
+        })
+    }
+
+    async fn read_jsonl<T: serde::de::DeserializeOwned>(path: &std::path::Path) -> Vec<T> {
+        tokio::fs::read_to_string(path)
+            .await
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_reports_paths() {
+        let validator = SchemaValidator::new(user_schema()).unwrap();
+
+        assert!(
+            validator
+                .validate(&json!({"id": 1, "email": "a@b.c", "tags": ["x"]}))
+                .is_empty()
+        );
+        assert_eq!(
+            validator.validate(&json!({"id": "7", "email": "a@b.c"})),
+            vec!["$.id: expected integer, got string"]
+        );
+        assert_eq!(
+            validator.validate(&json!({"id": 0, "role": "root", "extra": true})),
+            vec![
+                "$: missing required property 'email'",
+                "$: unexpected property 'extra'",
+                "$.id: 0 is less than 1",
+                "$.role: \"root\" is not one of [\"admin\",\"user\"]",
+            ]
+        );
+        assert_eq!(
+            validator.validate(&json!({"id": 2, "email": "nope", "tags": ["a", 1]})),
+            vec![
+                "$.email: does not match pattern '^[^@]+@[^@]+$'",
+                "$.tags[1]: expected string, got number",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_schema_is_rejected() {
+        for schema in [
+            json!("object"),
+            json!({"type": "tuple"}),
+            json!({"properties": []}),
+            json!({"required": "id"}),
+            json!({"type": "string", "pattern": "("}),
+            json!({"anyOf": []}),
+        ] {
+            assert!(SchemaValidator::new(schema.clone()).is_err(), "{}", schema);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_records_routed_to_rejects() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("input.jsonl");
+        let output = dir.path().join("output.jsonl");
+        let rejects = dir.path().join("rejects.jsonl");
+
+        let records = [
+            json!({"id": 1, "email": "a@example.com"}),
+            json!({"id": 2, "email": "b@example.com", "role": "admin"}),
+            json!({"id": 3}),
+            json!({"id": 4, "email": "d@example.com", "tags": ["a", "b", "c", "d"]}),
+            json!({"id": 5, "email": "e@example.com", "role": "user"}),
+        ];
+        let body: String = records.iter().map(|r| format!("{}\n", r)).collect();
+        tokio::fs::write(&input, body).await.unwrap();
+
+        let validator = SchemaValidator::new(user_schema()).unwrap();
+        let counts = validator.counts();
+        let config = PipelineConfig {
+            checkpoint_interval: 2,
+            reject_path: Some(rejects.clone()),
+            ..Default::default()
+        };
+        let stats = DataPipeline::new("users", config)
+            .with_transform(validator)
+            .run(&input, &output)
+            .await
+            .unwrap();
+
+        assert_eq!(stats.records_read, 5);
+        assert_eq!(stats.records_emitted, 3);
+        assert_eq!(stats.records_rejected, 2);
+        assert_eq!(stats.records_dropped, 0);
+        assert_eq!(counts.valid(), 3);
+        assert_eq!(counts.invalid(), 2);
+
+        let valid: Vec<Value> = read_jsonl(&output).await;
+        assert_eq!(
+            valid,
+            vec![records[0].clone(), records[1].clone(), records[4].clone()]
+        );
+
+        let rejected: Vec<RejectedRecord> = read_jsonl(&rejects).await;
+        assert_eq!(
+            rejected,
+            vec![
+                RejectedRecord {
+                    transform: "schema_validation".to_string(),
+                    error: "$: missing required property 'email'".to_string(),
+                    record: records[2].clone(),
+                },
+                RejectedRecord {
+                    transform: "schema_validation".to_string(),
+                    error: "$.tags: expected at most 3 items".to_string(),
+                    record: records[3].clone(),
+                },
+            ]
+        );
+    }
+}
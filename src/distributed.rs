@@ -1,26 +1,33 @@
 #![allow(dead_code, unused_imports, unused_variables)]
 use crate::{
+    api::auth::{ApiKeyScope, ApiKeys},
     backends::{BackendConfig, BackendHandle, BackendType, InferenceParams},
     metrics::MetricsCollector,
     models::ModelManager,
 };
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use futures::{Stream, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    net::SocketAddr,
+    path::PathBuf,
     pin::Pin,
     sync::{
         Arc,
         atomic::{AtomicUsize, Ordering},
     },
-    time::{Duration, Instant},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::{
-    sync::{RwLock, Semaphore, mpsc, oneshot},
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, Lines},
+    net::{TcpListener, TcpStream},
+    sync::{RwLock, Semaphore, mpsc, oneshot, watch},
     task::JoinHandle,
-    time::timeout,
+    time::{Instant, timeout},
 };
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
@@ -41,6 +48,49 @@ pub struct DistributedConfig {
     pub preload_models: bool,
     /// Maximum models to keep loaded per worker
     pub max_models_per_worker: usize,
+    /// Address to accept streaming requests from remote coordinators on; a bare
+    /// port listens on loopback only. Any other interface needs an API key file.
+    #[serde(default)]
+    pub stream_listen_addr: Option<String>,
+    /// Remote workers that streaming requests are proxied to instead of local workers
+    #[serde(default)]
+    pub remote_workers: Vec<String>,
+    /// API key presented to remote workers that require one
+    #[serde(default)]
+    pub remote_worker_api_key: Option<String>,
+    /// Tokens a worker may send ahead of the client before it must wait for credit
+    #[serde(default = "default_stream_window")]
+    pub stream_window: u32,
+    /// Seconds between health checks of registered workers
+    #[serde(default = "default_worker_health_check_interval_secs")]
+    pub worker_health_check_interval_secs: u64,
+    /// Consecutive failed health checks after which a registered worker is evicted
+    #[serde(default = "default_worker_failure_threshold")]
+    pub worker_failure_threshold: u32,
+    /// Lease file, on storage every coordinator shares, through which they elect
+    /// the one that dispatches; unset, this coordinator always dispatches
+    #[serde(default)]
+    pub leader_lease_path: Option<PathBuf>,
+    /// Seconds a leader keeps the lease without renewing it, and so the longest a
+    /// standby waits to take over from a failed leader
+    #[serde(default = "default_leader_lease_secs")]
+    pub leader_lease_secs: u64,
+}
+
+fn default_stream_window() -> u32 {
+    32
+}
+
+fn default_worker_health_check_interval_secs() -> u64 {
+    10
+}
+
+fn default_worker_failure_threshold() -> u32 {
+    3
+}
+
+fn default_leader_lease_secs() -> u64 {
+    15
 }
 
 impl Default for DistributedConfig {
@@ -53,6 +103,14 @@ impl Default for DistributedConfig {
             pool_strategy: PoolStrategy::RoundRobin,
             preload_models: false,
             max_models_per_worker: 2,
+            stream_listen_addr: None,
+            remote_workers: Vec::new(),
+            remote_worker_api_key: None,
+            stream_window: default_stream_window(),
+            worker_health_check_interval_secs: default_worker_health_check_interval_secs(),
+            worker_failure_threshold: default_worker_failure_threshold(),
+            leader_lease_path: None,
+            leader_lease_secs: default_leader_lease_secs(),
         }
     }
 }
@@ -62,6 +120,8 @@ pub enum PoolStrategy {
     RoundRobin,
     LeastLoaded,
     Sticky, // Same model always goes to the same worker
+    /// Prefer workers that already hold the model, otherwise the least loaded one
+    ModelAffinity,
 }
 
 /// Request sent to workers
@@ -81,17 +141,77 @@ pub struct InferenceResponse {
     pub output: String,
     pub tokens_generated: u32,
     pub duration: Duration,
+    /// Local worker, or the registered worker when routed through the registry
     pub worker_id: usize,
 }
 
 /// Streaming request sent to workers
+///
+/// The response channel is bounded, so a worker generating faster than the client
+/// reads is held back, and dropping the receiver cancels generation.
 #[derive(Debug)]
 pub struct StreamingInferenceRequest {
     pub id: Uuid,
     pub model_name: String,
     pub input: String,
     pub params: InferenceParams,
-    pub response_tx: mpsc::UnboundedSender<Result<String>>,
+    pub response_tx: mpsc::Sender<Result<String>>,
+}
+
+/// Tokens streamed back from a local or remote worker
+pub type InferenceStream = Pin<Box<dyn Stream<Item = Result<String>> + Send>>;
+
+/// Frames sent from a coordinator to a remote streaming worker
+///
+/// Frames are newline-delimited JSON over a TCP connection carrying a single stream.
+/// The worker may only send as many tokens as it has been granted credit for: `window`
+/// up front, then whatever the coordinator returns in `Credit` frames as the client
+/// consumes them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CoordinatorFrame {
+    Start {
+        id: Uuid,
+        model_name: String,
+        input: String,
+        params: InferenceParams,
+        window: u32,
+        /// Key checked against the worker's API key file, when it has one
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        api_key: Option<String>,
+    },
+    Credit {
+        id: Uuid,
+        tokens: u32,
+    },
+    Cancel {
+        id: Uuid,
+    },
+}
+
+/// Frames sent from a remote streaming worker back to the coordinator
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerFrame {
+    Token { id: Uuid, seq: u64, text: String },
+    Done { id: Uuid, tokens: u64 },
+    Error { id: Uuid, message: String },
+}
+
+impl WorkerFrame {
+    fn id(&self) -> Uuid {
+        match self {
+            Self::Token { id, .. } | Self::Done { id, .. } | Self::Error { id, .. } => *id,
+        }
+    }
+}
+
+/// Coordinator-side client for a worker running on another node
+#[derive(Debug, Clone)]
+pub struct RemoteWorker {
+    addr: String,
+    window: u32,
+    api_key: Option<String>,
 }
 
 /// Worker statistics
@@ -115,11 +235,20 @@ pub struct DistributedInference {
     model_manager: Arc<ModelManager>,
     metrics: Option<Arc<MetricsCollector>>,
     workers: Vec<WorkerHandle>,
+    remote_workers: Vec<RemoteWorker>,
+    registry: Arc<WorkerRegistry>,
+    health_task: Option<JoinHandle<()>>,
     next_worker: Arc<AtomicUsize>,
     stats: Arc<RwLock<HashMap<usize, WorkerStats>>>,
+    resident_models: ResidentModels,
+    elector: Option<Arc<LeaderElector>>,
+    election_task: Option<JoinHandle<()>>,
     shutdown_tx: Option<mpsc::UnboundedSender<()>>,
 }
 
+/// Models each worker holds or has been assigned to load, keyed by worker id
+type ResidentModels = Arc<RwLock<HashMap<usize, HashSet<String>>>>;
+
 /// Handle to a worker thread
 struct WorkerHandle {
     worker_id: usize,
@@ -154,6 +283,7 @@ struct Worker {
     metrics: Option<Arc<MetricsCollector>>,
     stats: WorkerStats,
     max_models: usize,
+    resident_models: ResidentModels,
 }
 
 impl DistributedInference {
@@ -170,6 +300,7 @@ impl DistributedInference {
         );
 
         let stats = Arc::new(RwLock::new(HashMap::new()));
+        let resident_models: ResidentModels = Arc::new(RwLock::new(HashMap::new()));
         let next_worker = Arc::new(AtomicUsize::new(0));
         let mut workers = Vec::with_capacity(config.worker_count);
         let (shutdown_tx, _shutdown_rx) = mpsc::unbounded_channel();
@@ -184,6 +315,7 @@ impl DistributedInference {
                 config.max_concurrent_per_worker,
                 config.max_models_per_worker,
                 stats.clone(),
+                resident_models.clone(),
             )
             .await?;
 
@@ -192,17 +324,64 @@ impl DistributedInference {
 
         info!("Successfully spawned {} workers", workers.len());
 
-        let distributed = Self {
+        let remote_workers: Vec<RemoteWorker> = config
+            .remote_workers
+            .iter()
+            .map(|addr| {
+                RemoteWorker::new(addr.clone(), config.stream_window)
+                    .with_api_key(config.remote_worker_api_key.clone())
+            })
+            .collect();
+        if !remote_workers.is_empty() {
+            info!(
+                "Streaming requests will be proxied to {} remote workers",
+                remote_workers.len()
+            );
+        }
+
+        let registry = Arc::new(WorkerRegistry::new(
+            Arc::new(
+                TcpWorkerClient::new(config.stream_window)
+                    .with_api_key(config.remote_worker_api_key.clone()),
+            ),
+            config.worker_failure_threshold,
+        ));
+        let health_task = registry.clone().spawn_health_checks(Duration::from_secs(
+            config.worker_health_check_interval_secs.max(1),
+        ));
+
+        let mut distributed = Self {
             config,
             backend_config,
             model_manager,
             metrics,
             workers,
+            remote_workers,
+            registry,
+            health_task: Some(health_task),
             next_worker,
             stats,
+            resident_models,
+            elector: None,
+            election_task: None,
             shutdown_tx: Some(shutdown_tx),
         };
 
+        if let Some(path) = distributed.config.leader_lease_path.clone() {
+            let candidate_id = format!("coordinator-{}", Uuid::new_v4());
+            info!(
+                "Coordinator {} campaigning for leadership through {}",
+                candidate_id,
+                path.display()
+            );
+            let lease = Duration::from_secs(distributed.config.leader_lease_secs.max(1));
+            distributed.enable_leader_election(Arc::new(LeaderElector::new(
+                candidate_id,
+                Arc::new(FileLeaseStore::new(path)),
+                lease,
+            )));
+        }
+
         // Preload models if enabled
         if distributed.config.preload_models {
             distributed.preload_common_models().await?;
@@ -220,6 +399,7 @@ impl DistributedInference {
         max_concurrent: usize,
         max_models: usize,
         stats: Arc<RwLock<HashMap<usize, WorkerStats>>>,
+        resident_models: ResidentModels,
     ) -> Result<WorkerHandle> {
         let (request_tx, request_rx) = mpsc::unbounded_channel();
         let (streaming_tx, streaming_rx) = mpsc::unbounded_channel();
@@ -236,6 +416,7 @@ impl DistributedInference {
                 ..Default::default()
             },
             max_models,
+            resident_models,
         };
 
         let join_handle = tokio::spawn(Self::worker_loop(worker, request_rx, streaming_rx, stats));
@@ -299,13 +480,63 @@ impl DistributedInference {
         debug!("Worker {} finished", worker.worker_id);
     }
 
+    /// Only dispatch while `elector` holds the coordinator lease
+    ///
+    /// Starts campaigning immediately; requests submitted while this coordinator is a
+    /// standby are rejected so that exactly one coordinator dispatches at a time.
+    pub fn enable_leader_election(&mut self, elector: Arc<LeaderElector>) {
+        if let Some(task) = self.election_task.take() {
+            task.abort();
+        }
+        self.election_task = Some(elector.clone().spawn());
+        self.elector = Some(elector);
+    }
+
+    /// Whether this coordinator may dispatch requests
+    pub fn is_leader(&self) -> bool {
+        self.elector
+            .as_ref()
+            .is_none_or(|elector| elector.is_leader())
+    }
+
+    fn ensure_leader(&self) -> Result<()> {
+        match &self.elector {
+            Some(elector) if !elector.is_leader() => Err(anyhow!(
+                "Coordinator {} is a standby; requests must go to the leader",
+                elector.candidate_id()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Workers that have registered with this coordinator
+    pub fn registry(&self) -> &Arc<WorkerRegistry> {
+        &self.registry
+    }
+
     /// Submit an inference request
+    ///
+    /// Requests go to a registered worker when any have registered, otherwise to a
+    /// local one.
     pub async fn infer(
         &self,
         model_name: &str,
         input: &str,
         params: &InferenceParams,
     ) -> Result<InferenceResponse> {
+        self.ensure_leader()?;
+        if !self.registry.is_empty().await {
+            let started = Instant::now();
+            let routed = self.registry.infer(model_name, input, params).await?;
+            return Ok(InferenceResponse {
+                id: Uuid::new_v4(),
+                output: routed.output,
+                tokens_generated: routed.tokens_generated,
+                duration: started.elapsed(),
+                worker_id: routed.worker_id,
+            });
+        }
+
         let worker_id = self.select_worker(model_name).await?;
         let worker = &self.workers[worker_id];
 
@@ -344,16 +575,28 @@ impl DistributedInference {
     }
 
     /// Submit a streaming inference request
+    ///
+    /// Requests go to a remote worker when any are configured, otherwise to a local one.
+    /// Dropping the returned stream cancels generation on whichever worker serves it.
     pub async fn infer_stream(
         &self,
         model_name: &str,
         input: &str,
         params: &InferenceParams,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<String>> + Send>>> {
+    ) -> Result<InferenceStream> {
+        self.ensure_leader()?;
+        if !self.remote_workers.is_empty() {
+            let index =
+                self.next_worker.fetch_add(1, Ordering::Relaxed) % self.remote_workers.len();
+            return self.remote_workers[index]
+                .infer_stream(model_name, input, params)
+                .await;
+        }
+
         let worker_id = self.select_worker(model_name).await?;
         let worker = &self.workers[worker_id];
 
-        let (response_tx, response_rx) = mpsc::unbounded_channel();
+        let (response_tx, response_rx) = mpsc::channel(self.config.stream_window.max(1) as usize);
         let request = StreamingInferenceRequest {
             id: Uuid::new_v4(),
             model_name: model_name.to_string(),
@@ -367,10 +610,50 @@ impl DistributedInference {
             .send(request)
             .map_err(|_| anyhow!("Failed to send streaming request to worker"))?;
 
-        let stream = tokio_stream::wrappers::UnboundedReceiverStream::new(response_rx);
+        let stream = tokio_stream::wrappers::ReceiverStream::new(response_rx);
         Ok(Box::pin(stream))
     }
 
+    /// Serve streaming requests from remote coordinators until `shutdown` completes
+    ///
+    /// With `api_keys`, a stream is only started for a coordinator presenting an
+    /// `inference` or `admin` key. Without them the listener must be on loopback.
+    pub async fn serve_streaming(
+        &self,
+        listener: TcpListener,
+        api_keys: Option<Arc<ApiKeys>>,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<()> {
+        let local_addr = listener.local_addr()?;
+        check_stream_listener(local_addr, api_keys.as_deref())?;
+        info!("Accepting remote streaming requests on {}", local_addr);
+
+        let mut connections = futures::stream::FuturesUnordered::new();
+        tokio::pin!(shutdown);
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => break,
+                accepted = listener.accept() => match accepted {
+                    Ok((socket, peer)) => {
+                        debug!("Accepted streaming connection from {}", peer);
+                        connections.push(serve_stream_connection(socket, api_keys.as_deref(), |model_name, input, params| async move {
+                            self.infer_stream(&model_name, &input, &params).await
+                        }));
+                    }
+                    Err(e) => warn!("Failed to accept streaming connection: {}", e),
+                },
+                Some(result) = connections.next(), if !connections.is_empty() => {
+                    if let Err(e) = result {
+                        warn!("Streaming connection failed: {}", e);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Select the best worker for a request
     async fn select_worker(&self, model_name: &str) -> Result<usize> {
         match self.config.pool_strategy {
@@ -390,9 +673,49 @@ impl DistributedInference {
                 let worker_id = (hasher.finish() as usize) % self.workers.len();
                 Ok(worker_id)
             }
+            PoolStrategy::ModelAffinity => self.select_worker_with_model(model_name).await,
         }
     }
 
+    /// Prefer a worker that already holds the model, falling back to the least loaded one
+    ///
+    /// The fallback worker is recorded as holding the model straight away, so concurrent
+    /// requests for it follow the first one instead of loading it on several workers.
+    async fn select_worker_with_model(&self, model_name: &str) -> Result<usize> {
+        if self.workers.is_empty() {
+            return Err(anyhow!("No workers available"));
+        }
+
+        let mut resident = self.resident_models.write().await;
+        let holding = (0..self.workers.len())
+            .filter(|id| {
+                resident
+                    .get(id)
+                    .is_some_and(|models| models.contains(model_name))
+            })
+            .min_by_key(|id| self.in_flight_requests(*id));
+        if let Some(worker_id) = holding {
+            return Ok(worker_id);
+        }
+
+        let worker_id = (0..self.workers.len())
+            .min_by_key(|id| self.in_flight_requests(*id))
+            .unwrap_or(0);
+        resident
+            .entry(worker_id)
+            .or_default()
+            .insert(model_name.to_string());
+        debug!("Assigned model {} to worker {}", model_name, worker_id);
+        Ok(worker_id)
+    }
+
+    /// Requests currently holding one of the worker's concurrency permits
+    fn in_flight_requests(&self, worker_id: usize) -> usize {
+        self.config
+            .max_concurrent_per_worker
+            .saturating_sub(self.workers[worker_id].semaphore.available_permits())
+    }
+
     /// Find the worker with the least active requests
     async fn select_least_loaded_worker(&self) -> Result<usize> {
         let stats = self.stats.read().await;
@@ -421,6 +744,12 @@ impl DistributedInference {
             let worker_id = i % self.workers.len();
             let worker = &self.workers[worker_id];
 
+            self.resident_models
+                .write()
+                .await
+                .entry(worker_id)
+                .or_default()
+                .insert(model.name.clone());
             let _ = worker.request_tx.send(WorkerMessage::PreloadModel {
                 model_name: model.name.clone(),
             });
@@ -461,6 +790,19 @@ impl DistributedInference {
     pub async fn shutdown(&mut self) -> Result<()> {
         info!("Shutting down distributed inference system");
 
+        // Hand the lease to a standby now rather than after it expires
+        if let Some(task) = self.election_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.health_task.take() {
+            task.abort();
+        }
+        if let Some(elector) = &self.elector
+            && let Err(e) = elector.resign().await
+        {
+            warn!("Failed to release coordinator lease: {}", e);
+        }
+
         // Send shutdown signal to all workers
         for worker in &self.workers {
             let _ = worker.request_tx.send(WorkerMessage::Shutdown);
@@ -497,14 +839,14 @@ impl Worker {
         self.stats.active_requests = self.stats.active_requests.saturating_sub(1);
 
         match result {
-            Ok(output) => {
+            Ok((output, prompt_tokens, tokens_generated)) => {
                 self.stats.successful_requests += 1;
                 self.update_average_response_time(duration);
 
                 let response = InferenceResponse {
                     id: request.id,
                     output: output.clone(),
-                    tokens_generated: (output.len() / 4) as u32, // Rough estimate
+                    tokens_generated,
                     duration,
                     worker_id: self.worker_id,
                 };
@@ -513,7 +855,7 @@ impl Worker {
                     use crate::metrics::InferenceEvent;
                     metrics.record_inference(InferenceEvent {
                         model_name: request.model_name.clone(),
-                        input_length: request.input.len() as u32,
+                        input_length: prompt_tokens,
                         output_length: response.tokens_generated,
                         duration,
                         success: true,
@@ -554,7 +896,7 @@ impl Worker {
                     "Streaming inference failed on worker {}: {}",
                     self.worker_id, e
                 );
-                let _ = request.response_tx.send(Err(e));
+                let _ = request.response_tx.send(Err(e)).await;
             }
         }
     }
@@ -565,9 +907,12 @@ impl Worker {
         model_name: &str,
         input: &str,
         params: &InferenceParams,
-    ) -> Result<String> {
+    ) -> Result<(String, u32, u32)> {
         let backend = self.get_or_load_backend(model_name).await?;
-        backend.infer(input, params).await
+        let output = backend.infer(input, params).await?;
+        let prompt_tokens = backend.count_tokens(input).await;
+        let tokens_generated = backend.count_tokens(&output).await;
+        Ok((output, prompt_tokens, tokens_generated))
     }
 
     /// Process a streaming inference request
@@ -576,15 +921,17 @@ impl Worker {
         model_name: &str,
         input: &str,
         params: &InferenceParams,
-        response_tx: mpsc::UnboundedSender<Result<String>>,
+        response_tx: mpsc::Sender<Result<String>>,
     ) -> Result<()> {
         let backend = self.get_or_load_backend(model_name).await?;
         let mut stream = backend.infer_stream(input, params).await?;
 
         while let Some(token_result) = stream.next().await {
             let converted_result = token_result.map_err(|e| anyhow::anyhow!("{}", e));
-            if response_tx.send(converted_result).is_err() {
-                break; // Client disconnected
+            // Waits while the client's buffer is full
+            if response_tx.send(converted_result).await.is_err() {
+                debug!("Stream cancelled by client on worker {}", self.worker_id);
+                break;
             }
         }
 
@@ -599,18 +946,17 @@ impl Worker {
                 self.evict_least_used_model().await;
             }
 
-            let model_info = self.model_manager.resolve_model(model_name).await?;
-            let backend_type = BackendType::from_model_path(&model_info.path).ok_or_else(|| {
-                anyhow::anyhow!(
-                    "No suitable backend found for model: {}",
-                    model_info.path.display()
-                )
-            })?;
-            let backend_handle = BackendHandle::new_shared(backend_type, &self.backend_config)?;
-            backend_handle.load_model(&model_info).await?;
+            let backend_handle = match self.load_backend(model_name).await {
+                Ok(handle) => handle,
+                Err(e) => {
+                    self.set_resident(model_name, false).await;
+                    return Err(e);
+                }
+            };
 
             self.backends.insert(model_name.to_string(), backend_handle);
             self.stats.loaded_models.push(model_name.to_string());
+            self.set_resident(model_name, true).await;
 
             info!("Loaded model {} on worker {}", model_name, self.worker_id);
         }
@@ -618,6 +964,31 @@ impl Worker {
         Ok(self.backends.get(model_name).unwrap())
     }
 
+    /// Create a backend and load the model into it
+    async fn load_backend(&self, model_name: &str) -> Result<BackendHandle> {
+        let model_info = self.model_manager.resolve_model(model_name).await?;
+        let backend_type = BackendType::from_model_path(&model_info.path).ok_or_else(|| {
+            anyhow::anyhow!(
+                "No suitable backend found for model: {}",
+                model_info.path.display()
+            )
+        })?;
+        let backend_handle = BackendHandle::new_shared(backend_type, &self.backend_config)?;
+        backend_handle.load_model(&model_info).await?;
+        Ok(backend_handle)
+    }
+
+    /// Publish whether this worker holds a model, for affinity routing
+    async fn set_resident(&self, model_name: &str, resident: bool) {
+        let mut resident_models = self.resident_models.write().await;
+        let models = resident_models.entry(self.worker_id).or_default();
+        if resident {
+            models.insert(model_name.to_string());
+        } else {
+            models.remove(model_name);
+        }
+    }
+
     /// Preload a model
     async fn preload_model(&mut self, model_name: &str) {
         if let Err(e) = self.get_or_load_backend(model_name).await {
@@ -632,6 +1003,7 @@ impl Worker {
     async fn unload_model(&mut self, model_name: &str) {
         if self.backends.remove(model_name).is_some() {
             self.stats.loaded_models.retain(|m| m != model_name);
+            self.set_resident(model_name, false).await;
             info!(
                 "Unloaded model {} from worker {}",
                 model_name, self.worker_id
@@ -662,10 +1034,1423 @@ impl Worker {
     }
 }
 
+impl RemoteWorker {
+    /// Create a client for the worker at `addr` with the given credit window
+    pub fn new(addr: impl Into<String>, window: u32) -> Self {
+        Self {
+            addr: addr.into(),
+            window: window.max(1),
+            api_key: None,
+        }
+    }
+
+    /// Present `api_key` to a worker that requires one
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// Start a stream on the remote worker
+    ///
+    /// Tokens are forwarded in order. Credit is returned to the worker only as the
+    /// client consumes tokens, and dropping the stream sends a cancel frame.
+    pub async fn infer_stream(
+        &self,
+        model_name: &str,
+        input: &str,
+        params: &InferenceParams,
+    ) -> Result<InferenceStream> {
+        let socket = TcpStream::connect(&self.addr)
+            .await
+            .map_err(|e| anyhow!("Failed to connect to remote worker {}: {}", self.addr, e))?;
+        let (reader, mut writer) = socket.into_split();
+
+        let id = Uuid::new_v4();
+        write_frame(
+            &mut writer,
+            &CoordinatorFrame::Start {
+                id,
+                model_name: model_name.to_string(),
+                input: input.to_string(),
+                params: params.clone(),
+                window: self.window,
+                api_key: self.api_key.clone(),
+            },
+        )
+        .await?;
+
+        let (token_tx, token_rx) = mpsc::channel(self.window as usize);
+        tokio::spawn(relay_remote_stream(
+            id,
+            BufReader::new(reader).lines(),
+            writer,
+            token_tx,
+            self.window,
+        ));
+
+        Ok(Box::pin(tokio_stream::wrappers::ReceiverStream::new(
+            token_rx,
+        )))
+    }
+}
+
+/// Forward tokens from a remote worker to the client, returning credit as they are taken
+async fn relay_remote_stream<R, W>(
+    id: Uuid,
+    mut frames: Lines<R>,
+    mut writer: W,
+    token_tx: mpsc::Sender<Result<String>>,
+    window: u32,
+) where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let refill_at = (window / 2).max(1);
+    let mut pending_credit = 0;
+    let mut next_seq = 0;
+
+    let error = loop {
+        let frame = tokio::select! {
+            _ = token_tx.closed() => {
+                debug!("Client dropped remote stream {}, cancelling", id);
+                let _ = write_frame(&mut writer, &CoordinatorFrame::Cancel { id }).await;
+                return;
+            }
+            frame = read_frame::<_, WorkerFrame>(&mut frames) => frame,
+        };
+
+        let frame = match frame {
+            Ok(Some(frame)) if frame.id() != id => {
+                break anyhow!(
+                    "Remote worker sent a frame for unknown stream {}",
+                    frame.id()
+                );
+            }
+            Ok(Some(frame)) => frame,
+            Ok(None) => break anyhow!("Remote worker closed stream {} before completion", id),
+            Err(e) => break e,
+        };
+
+        match frame {
+            WorkerFrame::Token { seq, text, .. } => {
+                if seq != next_seq {
+                    break anyhow!(
+                        "Remote worker sent token {} out of order, expected {}",
+                        seq,
+                        next_seq
+                    );
+                }
+                next_seq += 1;
+
+                // Waits for the client to make room, which holds back the worker's credit
+                if token_tx.send(Ok(text)).await.is_err() {
+                    let _ = write_frame(&mut writer, &CoordinatorFrame::Cancel { id }).await;
+                    return;
+                }
+
+                pending_credit += 1;
+                if pending_credit >= refill_at {
+                    let credit = CoordinatorFrame::Credit {
+                        id,
+                        tokens: pending_credit,
+                    };
+                    if let Err(e) = write_frame(&mut writer, &credit).await {
+                        break e;
+                    }
+                    pending_credit = 0;
+                }
+            }
+            WorkerFrame::Done { .. } => return,
+            WorkerFrame::Error { message, .. } => {
+                break anyhow!("Remote worker error: {}", message);
+            }
+        }
+    };
+
+    warn!("Remote stream {} failed: {}", id, error);
+    let _ = token_tx.send(Err(error)).await;
+}
+
+/// Refuse to serve streams on an interface other hosts can reach without API keys
+pub fn check_stream_listener(addr: SocketAddr, api_keys: Option<&ApiKeys>) -> Result<()> {
+    if api_keys.is_none() && !addr.ip().is_loopback() {
+        bail!(
+            "Refusing to serve remote streams on {} without an API key file; listen on loopback or set server.api_key_file",
+            addr
+        );
+    }
+    Ok(())
+}
+
+/// Address to listen for remote streams on, where a bare port means loopback
+pub fn stream_listen_addr(addr: &str) -> String {
+    match addr.parse::<u16>() {
+        Ok(port) => format!("127.0.0.1:{}", port),
+        Err(_) => addr.to_string(),
+    }
+}
+
+/// Serve one remote stream on the worker side of a connection
+///
+/// `open` starts the local stream once the coordinator's start frame arrives, and with
+/// `api_keys` only if the frame carries an `inference` or `admin` key. Tokens are only
+/// written while credit remains, and the local stream is dropped, cancelling generation,
+/// as soon as the coordinator cancels or disconnects.
+pub async fn serve_stream_connection<F, Fut>(
+    socket: TcpStream,
+    api_keys: Option<&ApiKeys>,
+    open: F,
+) -> Result<()>
+where
+    F: FnOnce(String, String, InferenceParams) -> Fut,
+    Fut: Future<Output = Result<InferenceStream>>,
+{
+    let (reader, mut writer) = socket.into_split();
+    let mut frames = BufReader::new(reader).lines();
+
+    let (id, model_name, input, params, window, api_key) = match read_frame(&mut frames).await? {
+        Some(CoordinatorFrame::Start {
+            id,
+            model_name,
+            input,
+            params,
+            window,
+            api_key,
+        }) => (id, model_name, input, params, window, api_key),
+        Some(other) => return Err(anyhow!("Expected a start frame, got {:?}", other)),
+        None => return Ok(()),
+    };
+
+    if let Some(keys) = api_keys {
+        let authorized = api_key
+            .as_deref()
+            .and_then(|key| keys.authenticate(key))
+            .is_some_and(|entry| entry.scope >= ApiKeyScope::Inference);
+        if !authorized {
+            warn!("Refused remote stream {} without a valid API key", id);
+            let message = "Missing or invalid API key".to_string();
+            return write_frame(&mut writer, &WorkerFrame::Error { id, message }).await;
+        }
+    }
+
+    let mut tokens = match open(model_name, input, params).await {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            let message = e.to_string();
+            return write_frame(&mut writer, &WorkerFrame::Error { id, message }).await;
+        }
+    };
+
+    let mut credit = window;
+    let mut seq = 0;
+
+    loop {
+        tokio::select! {
+            frame = read_frame::<_, CoordinatorFrame>(&mut frames) => match frame? {
+                Some(CoordinatorFrame::Credit { tokens, .. }) => credit = credit.saturating_add(tokens),
+                Some(CoordinatorFrame::Cancel { .. }) | None => {
+                    debug!("Stream {} cancelled by coordinator", id);
+                    return Ok(());
+                }
+                Some(CoordinatorFrame::Start { .. }) => {
+                    return Err(anyhow!("Unexpected start frame on active stream {}", id));
+                }
+            },
+            token = tokens.next(), if credit > 0 => match token {
+                Some(Ok(text)) => {
+                    write_frame(&mut writer, &WorkerFrame::Token { id, seq, text }).await?;
+                    seq += 1;
+                    credit -= 1;
+                }
+                Some(Err(e)) => {
+                    let message = e.to_string();
+                    return write_frame(&mut writer, &WorkerFrame::Error { id, message }).await;
+                }
+                None => return write_frame(&mut writer, &WorkerFrame::Done { id, tokens: seq }).await,
+            },
+        }
+    }
+}
+
+async fn write_frame<W, T>(writer: &mut W, frame: &T) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let mut line = serde_json::to_vec(frame)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Read the next frame, or `None` once the peer closes the connection
+async fn read_frame<R, T>(frames: &mut Lines<R>) -> Result<Option<T>>
+where
+    R: AsyncBufRead + Unpin,
+    T: serde::de::DeserializeOwned,
+{
+    match frames.next_line().await? {
+        Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+        None => Ok(None),
+    }
+}
+
+/// What a worker announces when it registers with a coordinator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkerRegistration {
+    /// Address the worker accepts remote streaming requests on
+    pub endpoint: String,
+    /// Features the worker offers, such as `gpu` or `embeddings`
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Models the worker has loaded and will serve
+    #[serde(default)]
+    pub models: Vec<String>,
+}
+
+/// A registered worker as listed on the admin roster
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkerStatus {
+    pub id: usize,
+    #[serde(flatten)]
+    pub registration: WorkerRegistration,
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub in_flight: usize,
+    pub registered_at: chrono::DateTime<chrono::Utc>,
+    pub last_healthy: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// How a coordinator reaches its registered workers
+#[async_trait::async_trait]
+pub trait WorkerClient: Send + Sync {
+    /// Succeed if the worker at `endpoint` is up
+    async fn health_check(&self, endpoint: &str) -> Result<()>;
+
+    /// Start a generation on the worker at `endpoint`
+    async fn infer_stream(
+        &self,
+        endpoint: &str,
+        model_name: &str,
+        input: &str,
+        params: &InferenceParams,
+    ) -> Result<InferenceStream>;
+}
+
+/// Reaches workers over the remote streaming protocol; a worker is healthy while
+/// it accepts connections
+pub struct TcpWorkerClient {
+    window: u32,
+    connect_timeout: Duration,
+    api_key: Option<String>,
+}
+
+impl TcpWorkerClient {
+    pub fn new(window: u32) -> Self {
+        Self {
+            window,
+            connect_timeout: Duration::from_secs(5),
+            api_key: None,
+        }
+    }
+
+    /// Present `api_key` to workers that require one
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl WorkerClient for TcpWorkerClient {
+    async fn health_check(&self, endpoint: &str) -> Result<()> {
+        timeout(self.connect_timeout, TcpStream::connect(endpoint))
+            .await
+            .map_err(|_| anyhow!("Timed out connecting to {}", endpoint))??;
+        Ok(())
+    }
+
+    async fn infer_stream(
+        &self,
+        endpoint: &str,
+        model_name: &str,
+        input: &str,
+        params: &InferenceParams,
+    ) -> Result<InferenceStream> {
+        RemoteWorker::new(endpoint, self.window)
+            .with_api_key(self.api_key.clone())
+            .infer_stream(model_name, input, params)
+            .await
+    }
+}
+
+/// Output of a request routed to a registered worker
+#[derive(Debug, Clone)]
+pub struct RoutedInference {
+    pub worker_id: usize,
+    pub output: String,
+    pub tokens_generated: u32,
+}
+
+struct RegisteredWorker {
+    registration: WorkerRegistration,
+    healthy: bool,
+    consecutive_failures: u32,
+    in_flight: Arc<AtomicUsize>,
+    registered_at: chrono::DateTime<chrono::Utc>,
+    last_healthy: Option<chrono::DateTime<chrono::Utc>>,
+    /// Cancelled on eviction so requests in flight on the worker are retried elsewhere
+    evicted: CancellationToken,
+}
+
+/// Counts a request against a worker's load until dropped
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn new(count: &Arc<AtomicUsize>) -> Self {
+        count.fetch_add(1, Ordering::SeqCst);
+        Self(count.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Workers that registered with this coordinator and their health
+///
+/// Each request goes to the least loaded healthy worker serving its model. A
+/// worker that fails `failure_threshold` health checks in a row is evicted, and
+/// requests it was serving are retried on another worker.
+pub struct WorkerRegistry {
+    client: Arc<dyn WorkerClient>,
+    failure_threshold: u32,
+    next_id: AtomicUsize,
+    workers: RwLock<BTreeMap<usize, RegisteredWorker>>,
+}
+
+impl WorkerRegistry {
+    pub fn new(client: Arc<dyn WorkerClient>, failure_threshold: u32) -> Self {
+        Self {
+            client,
+            failure_threshold: failure_threshold.max(1),
+            next_id: AtomicUsize::new(0),
+            workers: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Add a worker, replacing any earlier registration of its endpoint, and
+    /// return its id
+    pub async fn register(&self, registration: WorkerRegistration) -> usize {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let mut workers = self.workers.write().await;
+        workers.retain(|_, worker| {
+            let replaced = worker.registration.endpoint == registration.endpoint;
+            if replaced {
+                worker.evicted.cancel();
+            }
+            !replaced
+        });
+        info!(
+            "Registered worker {} at {} serving {:?}",
+            id, registration.endpoint, registration.models
+        );
+        workers.insert(
+            id,
+            RegisteredWorker {
+                registration,
+                healthy: true,
+                consecutive_failures: 0,
+                in_flight: Arc::new(AtomicUsize::new(0)),
+                registered_at: chrono::Utc::now(),
+                last_healthy: None,
+                evicted: CancellationToken::new(),
+            },
+        );
+        id
+    }
+
+    /// Remove a worker; returns whether it was registered
+    pub async fn deregister(&self, id: usize) -> bool {
+        match self.workers.write().await.remove(&id) {
+            Some(worker) => {
+                worker.evicted.cancel();
+                info!("Deregistered worker {}", id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn is_empty(&self) -> bool {
+        self.workers.read().await.is_empty()
+    }
+
+    /// Every registered worker in id order
+    pub async fn roster(&self) -> Vec<WorkerStatus> {
+        self.workers
+            .read()
+            .await
+            .iter()
+            .map(|(&id, worker)| WorkerStatus {
+                id,
+                registration: worker.registration.clone(),
+                healthy: worker.healthy,
+                consecutive_failures: worker.consecutive_failures,
+                in_flight: worker.in_flight.load(Ordering::SeqCst),
+                registered_at: worker.registered_at,
+                last_healthy: worker.last_healthy,
+            })
+            .collect()
+    }
+
+    /// Check every worker once, evicting those that have failed too often
+    pub async fn check_health(&self) {
+        let endpoints: Vec<(usize, String)> = self
+            .workers
+            .read()
+            .await
+            .iter()
+            .map(|(&id, worker)| (id, worker.registration.endpoint.clone()))
+            .collect();
+        let results = futures::future::join_all(
+            endpoints
+                .iter()
+                .map(|(_, endpoint)| self.client.health_check(endpoint)),
+        )
+        .await;
+
+        let mut workers = self.workers.write().await;
+        for ((id, endpoint), result) in endpoints.into_iter().zip(results) {
+            // Deregistered while being checked
+            let Some(worker) = workers.get_mut(&id) else {
+                continue;
+            };
+            match result {
+                Ok(()) => {
+                    worker.healthy = true;
+                    worker.consecutive_failures = 0;
+                    worker.last_healthy = Some(chrono::Utc::now());
+                }
+                Err(e) => {
+                    worker.healthy = false;
+                    worker.consecutive_failures += 1;
+                    warn!(
+                        "Health check of worker {} at {} failed ({} in a row): {}",
+                        id, endpoint, worker.consecutive_failures, e
+                    );
+                    if worker.consecutive_failures >= self.failure_threshold {
+                        warn!("Evicting worker {} at {}", id, endpoint);
+                        worker.evicted.cancel();
+                        workers.remove(&id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check worker health every `interval` until the task is aborted
+    pub fn spawn_health_checks(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                self.check_health().await;
+            }
+        })
+    }
+
+    /// Run a request on the least loaded healthy worker serving `model_name`,
+    /// moving on to the next one if it fails or is evicted mid-request
+    pub async fn infer(
+        &self,
+        model_name: &str,
+        input: &str,
+        params: &InferenceParams,
+    ) -> Result<RoutedInference> {
+        let mut tried = HashSet::new();
+        let mut last_error = None;
+
+        while let Some((worker_id, endpoint, in_flight, evicted)) =
+            self.select(model_name, &tried).await
+        {
+            tried.insert(worker_id);
+            let _in_flight = InFlight::new(&in_flight);
+
+            let attempt = async {
+                let mut stream = match self
+                    .client
+                    .infer_stream(&endpoint, model_name, input, params)
+                    .await
+                {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        // Unreachable; keep other requests away until it passes a check
+                        self.mark_unhealthy(worker_id).await;
+                        return Err(e);
+                    }
+                };
+                let mut output = String::new();
+                let mut tokens_generated = 0;
+                while let Some(token) = stream.next().await {
+                    output.push_str(&token?);
+                    tokens_generated += 1;
+                }
+                Ok((output, tokens_generated))
+            };
+            let result = tokio::select! {
+                result = attempt => result,
+                _ = evicted.cancelled() => Err(anyhow!("Worker {} was evicted", worker_id)),
+            };
+
+            match result {
+                Ok((output, tokens_generated)) => {
+                    return Ok(RoutedInference {
+                        worker_id,
+                        output,
+                        tokens_generated,
+                    });
+                }
+                Err(e) => {
+                    warn!(
+                        "Request on worker {} at {} failed, retrying elsewhere: {}",
+                        worker_id, endpoint, e
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(match last_error {
+            Some(e) => anyhow!("Every worker serving {} failed: {}", model_name, e),
+            None => anyhow!("No healthy registered worker serves {}", model_name),
+        })
+    }
+
+    /// Least loaded healthy worker serving `model_name` that has not been tried
+    async fn select(
+        &self,
+        model_name: &str,
+        tried: &HashSet<usize>,
+    ) -> Option<(usize, String, Arc<AtomicUsize>, CancellationToken)> {
+        self.workers
+            .read()
+            .await
+            .iter()
+            .filter(|(id, worker)| {
+                worker.healthy
+                    && !tried.contains(*id)
+                    && worker
+                        .registration
+                        .models
+                        .iter()
+                        .any(|model| model == model_name)
+            })
+            .min_by_key(|(_, worker)| worker.in_flight.load(Ordering::SeqCst))
+            .map(|(&id, worker)| {
+                (
+                    id,
+                    worker.registration.endpoint.clone(),
+                    worker.in_flight.clone(),
+                    worker.evicted.clone(),
+                )
+            })
+    }
+
+    async fn mark_unhealthy(&self, worker_id: usize) {
+        if let Some(worker) = self.workers.write().await.get_mut(&worker_id) {
+            worker.healthy = false;
+        }
+    }
+}
+
+/// Lease granting one coordinator the right to dispatch requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    /// Candidate currently holding the lease
+    pub holder: String,
+    /// Incremented each time the lease changes hands
+    pub term: u64,
+    /// When the lease lapses unless renewed
+    pub expires_at: Instant,
+}
+
+/// Shared record of which coordinator holds the lease
+///
+/// Every candidate must talk to the same store. [`MemoryLeaseStore`] covers
+/// coordinators within one process and [`FileLeaseStore`] coordinators sharing a
+/// filesystem; other deployments implement this over a store with compare-and-set
+/// semantics.
+#[async_trait::async_trait]
+pub trait LeaseStore: Send + Sync {
+    /// Take the lease for `candidate` if it is free, expired or already theirs, and
+    /// return whichever lease is in force afterwards
+    async fn try_acquire(&self, candidate: &str, ttl: Duration) -> Result<Lease>;
+    /// Give up the lease early if `candidate` holds it
+    async fn release(&self, candidate: &str) -> Result<()>;
+}
+
+/// Lease store shared by coordinators in the same process
+#[derive(Debug, Default)]
+pub struct MemoryLeaseStore {
+    lease: std::sync::Mutex<Option<Lease>>,
+}
+
+impl MemoryLeaseStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl LeaseStore for MemoryLeaseStore {
+    async fn try_acquire(&self, candidate: &str, ttl: Duration) -> Result<Lease> {
+        let mut lease = self.lease.lock().unwrap_or_else(|e| e.into_inner());
+        let next = next_lease(lease.as_ref(), candidate, ttl, Instant::now());
+        *lease = Some(next.clone());
+        Ok(next)
+    }
+
+    async fn release(&self, candidate: &str) -> Result<()> {
+        let mut lease = self.lease.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(current) = lease.as_mut()
+            && current.holder == candidate
+        {
+            current.expires_at = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+/// The lease in force once `candidate` has tried to take `current` for `ttl`
+fn next_lease(current: Option<&Lease>, candidate: &str, ttl: Duration, now: Instant) -> Lease {
+    match current {
+        Some(current) if current.holder == candidate => Lease {
+            expires_at: now + ttl,
+            ..current.clone()
+        },
+        Some(current) if current.expires_at > now => current.clone(),
+        current => Lease {
+            holder: candidate.to_string(),
+            term: current.map_or(1, |lease| lease.term + 1),
+            expires_at: now + ttl,
+        },
+    }
+}
+
+/// Lease store kept in a file every candidate can reach, such as one on a shared
+/// volume
+///
+/// Each attempt holds `<path>.lock`, created exclusively, while it reads and
+/// rewrites the lease, so two candidates never both take it. A lock left by a
+/// candidate that crashed mid-attempt is broken once it is older than the lease.
+#[derive(Debug)]
+pub struct FileLeaseStore {
+    path: PathBuf,
+    lock_path: PathBuf,
+}
+
+/// A lease as written to the file, expiring at a wall-clock time other hosts
+/// can read
+#[derive(Debug, Serialize, Deserialize)]
+struct LeaseRecord {
+    holder: String,
+    term: u64,
+    expires_at_unix_ms: u64,
+}
+
+/// Removes the lease file's lock when dropped
+struct LeaseLock(PathBuf);
+
+impl Drop for LeaseLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+impl FileLeaseStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mut lock_path = path.clone().into_os_string();
+        lock_path.push(".lock");
+        Self {
+            path,
+            lock_path: lock_path.into(),
+        }
+    }
+
+    async fn lock(&self, ttl: Duration) -> Result<LeaseLock> {
+        let deadline = Instant::now() + ttl;
+        loop {
+            match tokio::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&self.lock_path)
+                .await
+            {
+                Ok(_) => return Ok(LeaseLock(self.lock_path.clone())),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(e) => {
+                    return Err(anyhow!(
+                        "Cannot lock lease file {}: {}",
+                        self.path.display(),
+                        e
+                    ));
+                }
+            }
+            let stale = tokio::fs::metadata(&self.lock_path)
+                .await
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified.elapsed().unwrap_or_default() > ttl);
+            if stale {
+                warn!("Breaking stale lease lock {}", self.lock_path.display());
+                let _ = tokio::fs::remove_file(&self.lock_path).await;
+                continue;
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "Timed out waiting for lease lock {}",
+                    self.lock_path.display()
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    /// The lease in the file, with its expiry brought onto this host's clock
+    async fn read(&self) -> Result<Option<Lease>> {
+        let contents = match tokio::fs::read(&self.path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let record: LeaseRecord = serde_json::from_slice(&contents)
+            .map_err(|e| anyhow!("Invalid lease file {}: {}", self.path.display(), e))?;
+        let remaining = Duration::from_millis(record.expires_at_unix_ms)
+            .saturating_sub(SystemTime::now().duration_since(UNIX_EPOCH)?);
+        Ok(Some(Lease {
+            holder: record.holder,
+            term: record.term,
+            expires_at: Instant::now() + remaining,
+        }))
+    }
+
+    async fn write(&self, lease: &Lease) -> Result<()> {
+        let expires_at = SystemTime::now().duration_since(UNIX_EPOCH)?
+            + lease.expires_at.saturating_duration_since(Instant::now());
+        let record = LeaseRecord {
+            holder: lease.holder.clone(),
+            term: lease.term,
+            expires_at_unix_ms: expires_at.as_millis() as u64,
+        };
+        let path = self.path.clone();
+        let contents = serde_json::to_vec(&record)?;
+        tokio::task::spawn_blocking(move || crate::io::atomic::write_file(&path, contents)).await?
+    }
+}
+
+#[async_trait::async_trait]
+impl LeaseStore for FileLeaseStore {
+    async fn try_acquire(&self, candidate: &str, ttl: Duration) -> Result<Lease> {
+        let _lock = self.lock(ttl).await?;
+        let current = self.read().await?;
+        let next = next_lease(current.as_ref(), candidate, ttl, Instant::now());
+        if current.as_ref() != Some(&next) {
+            self.write(&next).await?;
+        }
+        Ok(next)
+    }
+
+    async fn release(&self, candidate: &str) -> Result<()> {
+        let _lock = self.lock(Duration::from_secs(5)).await?;
+        if let Some(mut current) = self.read().await?
+            && current.holder == candidate
+        {
+            current.expires_at = Instant::now();
+            self.write(&current).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Campaigns for the coordinator lease and tracks whether this candidate holds it
+///
+/// The leader renews every third of the lease duration. It stops considering itself
+/// leader once its last renewal runs out, measured from before the renewal was sent,
+/// so it steps down no later than the store lets a standby take over.
+pub struct LeaderElector {
+    candidate_id: String,
+    store: Arc<dyn LeaseStore>,
+    lease_duration: Duration,
+    renew_interval: Duration,
+    leader_until: std::sync::Mutex<Option<Instant>>,
+    leader_tx: watch::Sender<bool>,
+}
+
+impl LeaderElector {
+    pub fn new(
+        candidate_id: impl Into<String>,
+        store: Arc<dyn LeaseStore>,
+        lease_duration: Duration,
+    ) -> Self {
+        let (leader_tx, _) = watch::channel(false);
+        Self {
+            candidate_id: candidate_id.into(),
+            store,
+            lease_duration,
+            renew_interval: lease_duration / 3,
+            leader_until: std::sync::Mutex::new(None),
+            leader_tx,
+        }
+    }
+
+    pub fn candidate_id(&self) -> &str {
+        &self.candidate_id
+    }
+
+    pub fn lease_duration(&self) -> Duration {
+        self.lease_duration
+    }
+
+    /// How often the lease is renewed, and how often standbys retry
+    pub fn renew_interval(&self) -> Duration {
+        self.renew_interval
+    }
+
+    /// Whether this candidate holds an unexpired lease
+    pub fn is_leader(&self) -> bool {
+        self.leader_until
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Watch leadership changes as seen by the campaign loop
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.leader_tx.subscribe()
+    }
+
+    /// Make one attempt to acquire or renew the lease, returning whether it is held
+    pub async fn campaign_once(&self) -> Result<bool> {
+        let attempted_at = Instant::now();
+        let result = self
+            .store
+            .try_acquire(&self.candidate_id, self.lease_duration)
+            .await;
+
+        let leader = match &result {
+            Ok(lease) => {
+                let held = lease.holder == self.candidate_id;
+                *self.leader_until.lock().unwrap_or_else(|e| e.into_inner()) =
+                    held.then(|| attempted_at + self.lease_duration);
+                if held && !*self.leader_tx.borrow() {
+                    info!(
+                        "Coordinator {} became leader for term {}",
+                        self.candidate_id, lease.term
+                    );
+                } else if !held && *self.leader_tx.borrow() {
+                    warn!(
+                        "Coordinator {} lost leadership to {}",
+                        self.candidate_id, lease.holder
+                    );
+                }
+                held
+            }
+            Err(e) => {
+                // Keep whatever time the last renewal bought, but no more
+                warn!(
+                    "Coordinator {} failed to reach lease store: {}",
+                    self.candidate_id, e
+                );
+                self.is_leader()
+            }
+        };
+
+        self.leader_tx.send_replace(leader);
+        result.map(|_| leader)
+    }
+
+    /// Give up the lease so a standby can take over without waiting for expiry
+    pub async fn resign(&self) -> Result<()> {
+        *self.leader_until.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.leader_tx.send_replace(false);
+        self.store.release(&self.candidate_id).await
+    }
+
+    /// Campaign until the returned task is aborted
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.renew_interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let _ = self.campaign_once().await;
+            }
+        })
+    }
+}
+
 impl Drop for DistributedInference {
     fn drop(&mut self) {
+        if let Some(task) = self.election_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.health_task.take() {
+            task.abort();
+        }
         if !self.workers.is_empty() {
             warn!("DistributedInference dropped without explicit shutdown");
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn spawn_mock_worker<F>(open: F) -> String
+    where
+        F: FnOnce() -> InferenceStream + Send + 'static,
+    {
+        spawn_mock_worker_with_keys(None, open).await
+    }
+
+    async fn spawn_mock_worker_with_keys<F>(api_keys: Option<ApiKeys>, open: F) -> String
+    where
+        F: FnOnce() -> InferenceStream + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            serve_stream_connection(
+                socket,
+                api_keys.as_ref(),
+                |_, _, _| async move { Ok(open()) },
+            )
+            .await
+            .unwrap();
+        });
+        addr
+    }
+
+    fn worker_keys(key: &str, scope: ApiKeyScope) -> ApiKeys {
+        ApiKeys::new(crate::api::auth::ApiKeysFile {
+            public_paths: Vec::new(),
+            keys: vec![crate::api::auth::ApiKeyEntry {
+                name: "coordinator".to_string(),
+                sha256: ApiKeys::digest(key),
+                scope,
+            }],
+        })
+        .unwrap()
+    }
+
+    fn hello_stream() -> InferenceStream {
+        Box::pin(futures::stream::iter([Ok("hello".to_string())]))
+    }
+
+    #[tokio::test]
+    async fn test_remote_stream_requires_a_worker_api_key() {
+        for (presented, scope, served) in [
+            (None, ApiKeyScope::Inference, false),
+            (Some("sk-wrong"), ApiKeyScope::Inference, false),
+            (Some("sk-worker"), ApiKeyScope::ReadOnly, false),
+            (Some("sk-worker"), ApiKeyScope::Inference, true),
+        ] {
+            let keys = worker_keys("sk-worker", scope);
+            let addr = spawn_mock_worker_with_keys(Some(keys), hello_stream).await;
+            let worker = RemoteWorker::new(addr, 4).with_api_key(presented.map(String::from));
+            let tokens: Vec<Result<String>> = worker
+                .infer_stream("mock", "hello", &InferenceParams::default())
+                .await
+                .unwrap()
+                .collect()
+                .await;
+            assert_eq!(tokens.len(), 1);
+            assert_eq!(tokens[0].is_ok(), served, "{:?} {:?}", presented, scope);
+        }
+    }
+
+    #[test]
+    fn test_stream_listener_outside_loopback_needs_api_keys() {
+        let keys = worker_keys("sk-worker", ApiKeyScope::Inference);
+        let loopback: SocketAddr = "127.0.0.1:7000".parse().unwrap();
+        let public: SocketAddr = "0.0.0.0:7000".parse().unwrap();
+
+        assert!(check_stream_listener(loopback, None).is_ok());
+        assert!(check_stream_listener(public, None).is_err());
+        assert!(check_stream_listener(public, Some(&keys)).is_ok());
+        assert_eq!(stream_listen_addr("7000"), "127.0.0.1:7000");
+        assert_eq!(stream_listen_addr("0.0.0.0:7000"), "0.0.0.0:7000");
+    }
+
+    #[tokio::test]
+    async fn test_remote_stream_tokens_arrive_in_order() {
+        let expected: Vec<String> = (0..50).map(|i| format!("token{} ", i)).collect();
+        let tokens = expected.clone();
+        let addr =
+            spawn_mock_worker(move || Box::pin(futures::stream::iter(tokens.into_iter().map(Ok))))
+                .await;
+
+        // A window much smaller than the stream only completes if credit flows back
+        let worker = RemoteWorker::new(addr, 4);
+        let stream = worker
+            .infer_stream("mock", "hello", &InferenceParams::default())
+            .await
+            .unwrap();
+
+        let received: Vec<String> = timeout(Duration::from_secs(5), stream.collect::<Vec<_>>())
+            .await
+            .unwrap()
+            .into_iter()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn test_remote_stream_cancellation_reaches_worker() {
+        let (token_tx, token_rx) = mpsc::channel::<Result<String>>(1);
+        let (cancelled_tx, cancelled_rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let mut count = 0;
+            while token_tx.send(Ok(format!("t{}", count))).await.is_ok() {
+                count += 1;
+            }
+            let _ = cancelled_tx.send(count);
+        });
+        let addr = spawn_mock_worker(move || {
+            Box::pin(tokio_stream::wrappers::ReceiverStream::new(token_rx))
+        })
+        .await;
+
+        let worker = RemoteWorker::new(addr, 4);
+        let mut stream = worker
+            .infer_stream("mock", "hello", &InferenceParams::default())
+            .await
+            .unwrap();
+        for i in 0..3 {
+            assert_eq!(stream.next().await.unwrap().unwrap(), format!("t{}", i));
+        }
+        drop(stream);
+
+        // Generation stops once the cancel frame reaches the worker
+        let produced = timeout(Duration::from_secs(5), cancelled_rx)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(produced < 1000);
+    }
+
+    #[tokio::test]
+    async fn test_affinity_routes_to_worker_holding_model() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = DistributedConfig {
+            worker_count: 2,
+            pool_strategy: PoolStrategy::ModelAffinity,
+            ..Default::default()
+        };
+        let mut distributed = DistributedInference::new(
+            config,
+            BackendConfig::default(),
+            Arc::new(ModelManager::new(temp_dir.path())),
+            None,
+        )
+        .await
+        .unwrap();
+
+        // Worker 1 holds the model and is busy; worker 0 is idle without it
+        distributed
+            .resident_models
+            .write()
+            .await
+            .entry(1)
+            .or_default()
+            .insert("llama".to_string());
+        let busy = distributed.workers[1].semaphore.acquire().await.unwrap();
+
+        assert_eq!(distributed.select_worker("llama").await.unwrap(), 1);
+
+        // A model nobody holds goes to the least loaded worker, and stays there
+        assert_eq!(distributed.select_worker("mistral").await.unwrap(), 0);
+        assert_eq!(distributed.select_worker("mistral").await.unwrap(), 0);
+
+        drop(busy);
+        distributed.shutdown().await.unwrap();
+    }
+
+    /// Registered workers that answer with their endpoint; tests decide which are
+    /// down and which never finish
+    #[derive(Default)]
+    struct MockWorkers {
+        down: std::sync::Mutex<HashSet<String>>,
+        hanging: std::sync::Mutex<HashSet<String>>,
+    }
+
+    impl MockWorkers {
+        fn fail(&self, endpoint: &str) {
+            self.down.lock().unwrap().insert(endpoint.to_string());
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl WorkerClient for MockWorkers {
+        async fn health_check(&self, endpoint: &str) -> Result<()> {
+            if self.down.lock().unwrap().contains(endpoint) {
+                return Err(anyhow!("Connection refused"));
+            }
+            Ok(())
+        }
+
+        async fn infer_stream(
+            &self,
+            endpoint: &str,
+            _model_name: &str,
+            _input: &str,
+            _params: &InferenceParams,
+        ) -> Result<InferenceStream> {
+            self.health_check(endpoint).await?;
+            if self.hanging.lock().unwrap().contains(endpoint) {
+                return Ok(Box::pin(futures::stream::pending()));
+            }
+            Ok(Box::pin(futures::stream::iter([Ok(endpoint.to_string())])))
+        }
+    }
+
+    fn registration(endpoint: &str, models: &[&str]) -> WorkerRegistration {
+        WorkerRegistration {
+            endpoint: endpoint.to_string(),
+            capabilities: vec!["gpu".to_string()],
+            models: models.iter().map(|model| model.to_string()).collect(),
+        }
+    }
+
+    async fn served_by(registry: &WorkerRegistry, model_name: &str) -> Result<String> {
+        let routed = registry
+            .infer(model_name, "hello", &InferenceParams::default())
+            .await?;
+        Ok(routed.output)
+    }
+
+    #[tokio::test]
+    async fn test_routing_avoids_worker_failing_health_checks() {
+        let workers = Arc::new(MockWorkers::default());
+        let registry = WorkerRegistry::new(workers.clone(), 2);
+        registry
+            .register(registration("worker-a", &["llama"]))
+            .await;
+        registry
+            .register(registration("worker-b", &["llama"]))
+            .await;
+        registry
+            .register(registration("worker-c", &["mistral"]))
+            .await;
+
+        assert_eq!(served_by(&registry, "llama").await.unwrap(), "worker-a");
+        assert_eq!(served_by(&registry, "mistral").await.unwrap(), "worker-c");
+        assert!(served_by(&registry, "phi").await.is_err());
+
+        workers.fail("worker-a");
+        registry.check_health().await;
+        let roster = registry.roster().await;
+        assert!(!roster[0].healthy);
+        assert_eq!(roster[0].consecutive_failures, 1);
+        for _ in 0..3 {
+            assert_eq!(served_by(&registry, "llama").await.unwrap(), "worker-b");
+        }
+
+        // Evicted once it fails the threshold number of checks in a row
+        registry.check_health().await;
+        let endpoints: Vec<String> = registry
+            .roster()
+            .await
+            .into_iter()
+            .map(|worker| worker.registration.endpoint)
+            .collect();
+        assert_eq!(endpoints, ["worker-b", "worker-c"]);
+        assert_eq!(served_by(&registry, "llama").await.unwrap(), "worker-b");
+    }
+
+    #[tokio::test]
+    async fn test_requests_on_evicted_worker_are_retried_elsewhere() {
+        let workers = Arc::new(MockWorkers::default());
+        workers
+            .hanging
+            .lock()
+            .unwrap()
+            .insert("worker-a".to_string());
+        let registry = Arc::new(WorkerRegistry::new(workers.clone(), 1));
+        let dead = registry
+            .register(registration("worker-a", &["llama"]))
+            .await;
+        registry
+            .register(registration("worker-b", &["llama"]))
+            .await;
+        // Keep the first request on worker-a by making worker-b look busier
+        let busy = InFlight::new(&registry.workers.read().await[&1].in_flight);
+
+        let request = tokio::spawn({
+            let registry = registry.clone();
+            async move {
+                registry
+                    .infer("llama", "hello", &InferenceParams::default())
+                    .await
+            }
+        });
+        while registry.roster().await[0].in_flight == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        drop(busy);
+
+        workers.fail("worker-a");
+        registry.check_health().await;
+        let routed = timeout(Duration::from_secs(5), request)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_ne!(routed.worker_id, dead);
+        assert_eq!(routed.output, "worker-b");
+        assert_eq!(registry.roster().await.len(), 1);
+    }
+
+    async fn wait_for_leader(electors: &[Arc<LeaderElector>], deadline: Duration) -> Option<usize> {
+        let start = Instant::now();
+        while start.elapsed() < deadline {
+            if let Some(index) = electors.iter().position(|elector| elector.is_leader()) {
+                return Some(index);
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        None
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_standby_takes_over_after_leader_failure() {
+        let store: Arc<dyn LeaseStore> = Arc::new(MemoryLeaseStore::new());
+        let lease = Duration::from_secs(15);
+        let electors: Vec<Arc<LeaderElector>> = ["coordinator-a", "coordinator-b"]
+            .into_iter()
+            .map(|id| Arc::new(LeaderElector::new(id, store.clone(), lease)))
+            .collect();
+        let mut tasks: Vec<_> = electors.iter().map(|e| Some(e.clone().spawn())).collect();
+
+        let leader = wait_for_leader(&electors, lease)
+            .await
+            .expect("no leader elected");
+        let standby = 1 - leader;
+
+        // Renewals keep the leader in place and the standby out
+        tokio::time::sleep(lease * 2).await;
+        assert!(electors[leader].is_leader());
+        assert!(!electors[standby].is_leader());
+
+        // Crash the leader: it stops renewing and never releases the lease
+        tasks[leader].take().unwrap().abort();
+        let failed_at = Instant::now();
+
+        let new_leader = wait_for_leader(&electors[standby..=standby], lease * 2)
+            .await
+            .map(|_| standby)
+            .expect("standby never took over");
+        let takeover = failed_at.elapsed();
+        assert_eq!(new_leader, standby);
+        // The old lease has at most `lease` left, then the standby's next retry wins
+        assert!(
+            takeover <= lease + electors[standby].renew_interval() + Duration::from_millis(5),
+            "takeover took {:?}",
+            takeover
+        );
+        assert!(!electors[leader].is_leader(), "two leaders at once");
+
+        for task in tasks.into_iter().flatten() {
+            task.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_lease_is_shared_between_stores() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("coordinator.lease");
+        // Two stores on one file stand in for coordinators on different hosts
+        let a = FileLeaseStore::new(&path);
+        let b = FileLeaseStore::new(&path);
+        let ttl = Duration::from_secs(60);
+
+        let lease = a.try_acquire("a", ttl).await.unwrap();
+        assert_eq!((lease.holder.as_str(), lease.term), ("a", 1));
+        let lease = b.try_acquire("b", ttl).await.unwrap();
+        assert_eq!((lease.holder.as_str(), lease.term), ("a", 1));
+
+        a.release("a").await.unwrap();
+        let lease = b.try_acquire("b", ttl).await.unwrap();
+        assert_eq!((lease.holder.as_str(), lease.term), ("b", 2));
+        assert!(!path.with_extension("lease.lock").exists());
+
+        // A lock left by a crashed attempt is broken once it is older than the lease
+        std::fs::write(path.with_extension("lease.lock"), "").unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let lease = a.try_acquire("a", Duration::from_millis(10)).await.unwrap();
+        assert_eq!(lease.holder, "b");
+    }
+
+    #[tokio::test]
+    async fn test_standby_coordinator_rejects_requests() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn LeaseStore> = Arc::new(MemoryLeaseStore::new());
+        store
+            .try_acquire("other", Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        let config = DistributedConfig {
+            worker_count: 1,
+            ..Default::default()
+        };
+        let mut distributed = DistributedInference::new(
+            config,
+            BackendConfig::default(),
+            Arc::new(ModelManager::new(temp_dir.path())),
+            None,
+        )
+        .await
+        .unwrap();
+        distributed.enable_leader_election(Arc::new(LeaderElector::new(
+            "standby",
+            store,
+            Duration::from_secs(60),
+        )));
+
+        let err = distributed
+            .infer("llama", "hello", &InferenceParams::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("standby"));
+        assert!(!distributed.is_leader());
+
+        distributed.shutdown().await.unwrap();
+    }
+
+    #[test]
+    fn test_worker_frame_wire_format() {
+        let id = Uuid::nil();
+        let frame = WorkerFrame::Token {
+            id,
+            seq: 3,
+            text: "hi".to_string(),
+        };
+        let json = serde_json::to_value(&frame).unwrap();
+        assert_eq!(json["type"], "token");
+        assert_eq!(json["seq"], 3);
+        assert_eq!(serde_json::from_value::<WorkerFrame>(json).unwrap(), frame);
+    }
+}
@@ -2,8 +2,12 @@
 //!
 //! This module contains enterprise-grade features:
 //! - Distributed inference across clusters
+//! - Checkpointed data pipelines for long ETL jobs, with schema validation and dataset splitting
+//! - Per-tenant request and token quotas for the HTTP API
 //!
 //! Enterprise modules provide advanced capabilities for production deployments.
 
 // Re-export from existing locations
+pub use crate::data_pipeline;
 pub use crate::distributed;
+pub use crate::multi_tenancy;
@@ -3,6 +3,7 @@
 //! This module consolidates all caching functionality:
 //! - Model caching (loading and warm-up)
 //! - Response caching (deduplication)
+//! - Tiered memory/disk caching ([`tiered`])
 //!
 //! Previously split across: cache.rs, response_cache.rs
 
@@ -10,6 +11,8 @@
 pub use crate::cache::*;
 pub use crate::response_cache;
 
+pub mod tiered;
+
 // Future: Will consolidate into unified API
 // pub mod model_cache;
 // pub mod response_cache;
@@ -0,0 +1,434 @@
+//! Two-tier cache: a bounded in-memory LRU backed by compressed files on disk
+//!
+//! Entries evicted from memory are zstd-compressed and written to the cache
+//! directory instead of being dropped. A lookup that misses memory but finds the
+//! entry on disk decompresses it, moves it back into memory and deletes the file.
+//! The disk tier has its own byte budget; a spill that would exceed it first
+//! deletes the least recently used files.
+//!
+//! Files are named after the blake3 hash of their key, so entries spilled by an
+//! earlier process are picked up again when the cache is reopened.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+const FILE_EXTENSION: &str = "zst";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TieredCacheConfig {
+    pub cache_dir: PathBuf,
+    /// Entries kept in memory before the least recently used one spills to disk
+    pub memory_capacity: usize,
+    /// Total size of the compressed files on disk
+    pub disk_budget_bytes: u64,
+    pub compression_level: i32,
+}
+
+impl TieredCacheConfig {
+    pub fn new(cache_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            cache_dir: cache_dir.into(),
+            memory_capacity: 1024,
+            disk_budget_bytes: 1024 * 1024 * 1024,
+            compression_level: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TieredCacheStats {
+    /// Lookups answered from memory
+    pub hits: u64,
+    /// Lookups answered from disk, which moves the entry back into memory
+    pub promotions: u64,
+    pub misses: u64,
+    /// Entries written to disk after being evicted from memory
+    pub spills: u64,
+    /// Files deleted to stay within the disk budget
+    pub disk_evictions: u64,
+    pub memory_entries: usize,
+    pub disk_entries: usize,
+    pub disk_bytes: u64,
+}
+
+/// Recency-ordered map; the oldest entry is the first in `order`
+struct Lru<V> {
+    entries: HashMap<String, (V, u64)>,
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl<V> Lru<V> {
+    fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn get(&mut self, key: &str) -> Option<&V> {
+        let tick = self.next_tick();
+        let (value, used) = self.entries.get_mut(key)?;
+        self.order.remove(used);
+        *used = tick;
+        self.order.insert(tick, key.to_string());
+        Some(value)
+    }
+
+    fn insert(&mut self, key: String, value: V) -> Option<V> {
+        let tick = self.next_tick();
+        let previous = self.remove(&key);
+        self.order.insert(tick, key.clone());
+        self.entries.insert(key, (value, tick));
+        previous
+    }
+
+    fn remove(&mut self, key: &str) -> Option<V> {
+        let (value, used) = self.entries.remove(key)?;
+        self.order.remove(&used);
+        Some(value)
+    }
+
+    fn pop_oldest(&mut self) -> Option<(String, V)> {
+        let (_, key) = self.order.pop_first()?;
+        let (value, _) = self.entries.remove(&key)?;
+        Some((key, value))
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+struct TierState {
+    memory: Lru<Vec<u8>>,
+    /// Compressed file size, keyed by file stem
+    disk: Lru<u64>,
+    stats: TieredCacheStats,
+}
+
+pub struct TieredCache {
+    config: TieredCacheConfig,
+    state: Mutex<TierState>,
+}
+
+impl TieredCache {
+    /// Open the cache, taking over any files already in `cache_dir`
+    pub async fn open(config: TieredCacheConfig) -> Result<Self> {
+        tokio::fs::create_dir_all(&config.cache_dir)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to create cache directory {}",
+                    config.cache_dir.display()
+                )
+            })?;
+
+        let mut existing = Vec::new();
+        let mut entries = tokio::fs::read_dir(&config.cache_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(FILE_EXTENSION) {
+                continue;
+            }
+            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let metadata = entry.metadata().await?;
+            let modified = metadata.modified().ok();
+            existing.push((modified, stem.to_string(), metadata.len()));
+        }
+        // Oldest first, so the most recently written files are the last evicted
+        existing.sort();
+
+        let mut state = TierState {
+            memory: Lru::new(),
+            disk: Lru::new(),
+            stats: TieredCacheStats::default(),
+        };
+        for (_, stem, size) in existing {
+            state.disk.insert(stem, size);
+            state.stats.disk_bytes += size;
+        }
+
+        let cache = Self {
+            config,
+            state: Mutex::new(state),
+        };
+        {
+            let mut state = cache.state.lock().await;
+            cache.evict_disk(&mut state, 0).await;
+        }
+        Ok(cache)
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let mut state = self.state.lock().await;
+        if let Some(value) = state.memory.get(key) {
+            let value = value.clone();
+            state.stats.hits += 1;
+            return Ok(Some(value));
+        }
+
+        let stem = Self::file_stem(key);
+        let Some(size) = state.disk.remove(&stem) else {
+            state.stats.misses += 1;
+            return Ok(None);
+        };
+        state.stats.disk_bytes -= size;
+
+        let path = self.file_path(&stem);
+        let value = match tokio::fs::read(&path).await {
+            Ok(compressed) => zstd::decode_all(&compressed[..])
+                .with_context(|| format!("Failed to decompress {}", path.display())),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = tokio::fs::remove_file(&path).await {
+            warn!(
+                "Failed to remove promoted cache file {}: {}",
+                path.display(),
+                e
+            );
+        }
+        let value = match value {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("Dropping unreadable cache entry {}: {:#}", key, e);
+                state.stats.misses += 1;
+                return Ok(None);
+            }
+        };
+
+        state.stats.promotions += 1;
+        self.insert_memory(&mut state, key.to_string(), value.clone())
+            .await?;
+        Ok(Some(value))
+    }
+
+    pub async fn put(&self, key: &str, value: Vec<u8>) -> Result<()> {
+        let mut state = self.state.lock().await;
+        self.remove_disk(&mut state, &Self::file_stem(key)).await;
+        self.insert_memory(&mut state, key.to_string(), value).await
+    }
+
+    pub async fn remove(&self, key: &str) -> bool {
+        let mut state = self.state.lock().await;
+        let in_memory = state.memory.remove(key).is_some();
+        let on_disk = self.remove_disk(&mut state, &Self::file_stem(key)).await;
+        in_memory || on_disk
+    }
+
+    pub async fn clear(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        state.memory.clear();
+        while let Some((stem, size)) = state.disk.pop_oldest() {
+            state.stats.disk_bytes -= size;
+            tokio::fs::remove_file(self.file_path(&stem)).await.ok();
+        }
+        Ok(())
+    }
+
+    pub async fn stats(&self) -> TieredCacheStats {
+        let state = self.state.lock().await;
+        TieredCacheStats {
+            memory_entries: state.memory.len(),
+            disk_entries: state.disk.len(),
+            ..state.stats.clone()
+        }
+    }
+
+    async fn insert_memory(
+        &self,
+        state: &mut TierState,
+        key: String,
+        value: Vec<u8>,
+    ) -> Result<()> {
+        state.memory.insert(key, value);
+        while state.memory.len() > self.config.memory_capacity {
+            let Some((key, value)) = state.memory.pop_oldest() else {
+                break;
+            };
+            self.spill(state, &key, &value).await?;
+        }
+        Ok(())
+    }
+
+    async fn spill(&self, state: &mut TierState, key: &str, value: &[u8]) -> Result<()> {
+        let compressed = zstd::encode_all(value, self.config.compression_level)
+            .context("Failed to compress cache entry")?;
+        let size = compressed.len() as u64;
+        if size > self.config.disk_budget_bytes {
+            debug!(
+                "Cache entry {} ({} bytes compressed) exceeds the disk budget, dropping it",
+                key, size
+            );
+            return Ok(());
+        }
+
+        self.evict_disk(state, size).await;
+        let stem = Self::file_stem(key);
+        crate::io::atomic::write_file_async(&self.file_path(&stem), compressed).await?;
+        state.disk.insert(stem, size);
+        state.stats.disk_bytes += size;
+        state.stats.spills += 1;
+        Ok(())
+    }
+
+    /// Delete least recently used files until `incoming` more bytes fit the budget
+    async fn evict_disk(&self, state: &mut TierState, incoming: u64) {
+        while state.stats.disk_bytes + incoming > self.config.disk_budget_bytes {
+            let Some((stem, size)) = state.disk.pop_oldest() else {
+                break;
+            };
+            state.stats.disk_bytes -= size;
+            state.stats.disk_evictions += 1;
+            let path = self.file_path(&stem);
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                warn!("Failed to evict cache file {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    async fn remove_disk(&self, state: &mut TierState, stem: &str) -> bool {
+        let Some(size) = state.disk.remove(stem) else {
+            return false;
+        };
+        state.stats.disk_bytes -= size;
+        tokio::fs::remove_file(self.file_path(stem)).await.ok();
+        true
+    }
+
+    fn file_stem(key: &str) -> String {
+        blake3::hash(key.as_bytes()).to_hex().to_string()
+    }
+
+    fn file_path(&self, stem: &str) -> PathBuf {
+        self.config
+            .cache_dir
+            .join(format!("{}.{}", stem, FILE_EXTENSION))
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.config.cache_dir
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(i: usize) -> Vec<u8> {
+        format!("cached response {} ", i).repeat(100).into_bytes()
+    }
+
+    fn spilled_files(dir: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().and_then(|e| e.to_str()) == Some(FILE_EXTENSION))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_overflow_spills_compressed_files_and_promotes_on_hit() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = TieredCacheConfig {
+            memory_capacity: 2,
+            ..TieredCacheConfig::new(dir.path())
+        };
+        let cache = TieredCache::open(config).await.unwrap();
+
+        for i in 0..4 {
+            cache.put(&format!("key-{}", i), value(i)).await.unwrap();
+        }
+
+        let stats = cache.stats().await;
+        assert_eq!(stats.memory_entries, 2);
+        assert_eq!(stats.disk_entries, 2);
+        assert_eq!(stats.spills, 2);
+
+        // The two oldest entries were written out compressed
+        let files = spilled_files(dir.path());
+        assert_eq!(files.len(), 2);
+        let spilled = cache.file_path(&TieredCache::file_stem("key-0"));
+        assert!(files.contains(&spilled));
+        let on_disk = std::fs::read(&spilled).unwrap();
+        assert!(on_disk.len() < value(0).len());
+        assert_eq!(zstd::decode_all(&on_disk[..]).unwrap(), value(0));
+
+        // A hit on disk decompresses, moves the entry to memory and spills the LRU one
+        assert_eq!(cache.get("key-0").await.unwrap(), Some(value(0)));
+        assert!(!spilled.exists());
+        assert!(cache.file_path(&TieredCache::file_stem("key-2")).exists());
+
+        assert_eq!(cache.get("key-0").await.unwrap(), Some(value(0)));
+        assert_eq!(cache.get("missing").await.unwrap(), None);
+
+        let stats = cache.stats().await;
+        assert_eq!(stats.promotions, 1);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.memory_entries, 2);
+        assert_eq!(stats.disk_entries, 2);
+    }
+
+    #[tokio::test]
+    async fn test_disk_budget_evicts_least_recently_used_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry_size = zstd::encode_all(&value(0)[..], 3).unwrap().len() as u64;
+        let config = TieredCacheConfig {
+            memory_capacity: 1,
+            disk_budget_bytes: entry_size * 2 + entry_size / 2,
+            ..TieredCacheConfig::new(dir.path())
+        };
+        let cache = TieredCache::open(config).await.unwrap();
+
+        for i in 0..5 {
+            cache.put(&format!("key-{}", i), value(i)).await.unwrap();
+        }
+
+        let stats = cache.stats().await;
+        assert_eq!(stats.disk_entries, 2);
+        assert_eq!(stats.disk_evictions, 2);
+        assert!(stats.disk_bytes <= entry_size * 2 + entry_size / 2);
+        assert_eq!(spilled_files(dir.path()).len(), 2);
+
+        assert_eq!(cache.get("key-0").await.unwrap(), None);
+        assert_eq!(cache.get("key-3").await.unwrap(), Some(value(3)));
+    }
+
+    #[tokio::test]
+    async fn test_reopen_finds_spilled_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = TieredCacheConfig {
+            memory_capacity: 1,
+            ..TieredCacheConfig::new(dir.path())
+        };
+        {
+            let cache = TieredCache::open(config.clone()).await.unwrap();
+            cache.put("first", value(1)).await.unwrap();
+            cache.put("second", value(2)).await.unwrap();
+        }
+
+        let cache = TieredCache::open(config).await.unwrap();
+        assert_eq!(cache.stats().await.disk_entries, 1);
+        assert_eq!(cache.get("first").await.unwrap(), Some(value(1)));
+    }
+}
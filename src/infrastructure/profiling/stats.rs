@@ -13,6 +13,8 @@ pub struct DurationStats {
     pub min: f32,
     /// 50th percentile (median)
     pub p50: f32,
+    /// 90th percentile
+    pub p90: f32,
     /// 95th percentile
     pub p95: f32,
     /// 99th percentile
@@ -30,6 +32,7 @@ impl Default for DurationStats {
         Self {
             min: 0.0,
             p50: 0.0,
+            p90: 0.0,
             p95: 0.0,
             p99: 0.0,
             max: 0.0,
@@ -62,6 +65,9 @@ impl DurationStats {
             sorted[sorted.len() / 2]
         };
 
+        let p90_idx = ((sorted.len() as f32 * 0.90) as usize).min(sorted.len() - 1);
+        let p90 = sorted[p90_idx];
+
         let p95_idx = ((sorted.len() as f32 * 0.95) as usize).min(sorted.len() - 1);
         let p95 = sorted[p95_idx];
 
@@ -75,6 +81,7 @@ impl DurationStats {
         Self {
             min,
             p50,
+            p90,
             p95,
             p99,
             max,
@@ -322,6 +329,7 @@ mod tests {
         let durations = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
         let stats = DurationStats::from_durations(&durations);
 
+        assert_eq!(stats.p90, 10.0);
         assert!(stats.p95 >= 9.0);
         assert!(stats.p99 >= 9.0);
     }
@@ -2,16 +2,18 @@
 
 use super::activity_logger::{ActivityLogger, ActivityStatus, ActivityType};
 use crate::backends::{
-    BackendConfig, BackendHandle, BackendType, InferenceParams as InfernoInferenceParams,
-    TokenStream,
+    BackendConfig, BackendHandle, BackendType, CancellationToken,
+    InferenceParams as InfernoInferenceParams, TokenStream, tokenizer::estimate_tokens,
 };
 use crate::models::{ModelInfo as CoreModelInfo, ModelManager};
 use anyhow::Result;
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use tokio::sync::RwLock;
+use tracing::warn;
 use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -38,9 +40,87 @@ pub struct InferenceParams {
 
 pub struct BackendManager {
     model_manager: Arc<RwLock<ModelManager>>,
-    loaded_backends: Arc<Mutex<HashMap<String, BackendHandle>>>,
+    loaded_backends: Arc<Mutex<HashMap<String, LoadedBackend>>>,
     global_metrics: Arc<Mutex<GlobalMetrics>>,
     activity_logger: Arc<ActivityLogger>,
+    /// Cancelled by "Stop All Inference"; replaced so later requests run normally
+    inference_cancel: Arc<Mutex<CancellationToken>>,
+}
+
+/// A backend with a model loaded, shared by every `load_model` call for the
+/// same model and backend type
+///
+/// The model stays loaded while any load has not been matched by an
+/// `unload_model` or any request is still running on it. Once the last load is
+/// unloaded no new requests are admitted, and the last request to finish frees
+/// the backend.
+struct LoadedBackend {
+    handle: BackendHandle,
+    model_path: PathBuf,
+    /// `load_model` calls not yet matched by `unload_model`
+    loads: usize,
+    /// Requests running on the backend
+    in_flight: usize,
+}
+
+impl LoadedBackend {
+    fn is_unloading(&self) -> bool {
+        self.loads == 0
+    }
+}
+
+/// A request's hold on a loaded backend, keeping its model loaded until the
+/// lease is dropped
+pub struct BackendLease {
+    backend_id: String,
+    handle: BackendHandle,
+    loaded_backends: Arc<Mutex<HashMap<String, LoadedBackend>>>,
+    metrics: Arc<Mutex<GlobalMetrics>>,
+}
+
+impl BackendLease {
+    pub fn handle(&self) -> &BackendHandle {
+        &self.handle
+    }
+}
+
+impl Drop for BackendLease {
+    fn drop(&mut self) {
+        let freed = {
+            let mut loaded_backends = self.loaded_backends.lock().unwrap();
+            match loaded_backends.get_mut(&self.backend_id) {
+                Some(backend) => {
+                    backend.in_flight = backend.in_flight.saturating_sub(1);
+                    if backend.in_flight == 0 && backend.is_unloading() {
+                        loaded_backends.remove(&self.backend_id)
+                    } else {
+                        None
+                    }
+                }
+                None => None,
+            }
+        };
+
+        // Unloaded while this request ran; it was the last one using the model
+        if let Some(backend) = freed {
+            decrement_models_loaded(&self.metrics);
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                let backend_id = self.backend_id.clone();
+                runtime.spawn(async move {
+                    if let Err(e) = backend.handle.unload_model().await {
+                        warn!("Failed to unload backend {}: {}", backend_id, e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+fn decrement_models_loaded(metrics: &Mutex<GlobalMetrics>) {
+    let mut metrics = metrics.lock().unwrap();
+    if metrics.models_loaded > 0 {
+        metrics.models_loaded -= 1;
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -116,6 +196,7 @@ impl BackendManager {
             loaded_backends: Arc::new(Mutex::new(HashMap::new())),
             global_metrics: Arc::new(Mutex::new(GlobalMetrics::default())),
             activity_logger,
+            inference_cancel: Arc::new(Mutex::new(CancellationToken::new())),
         })
     }
 
@@ -131,6 +212,7 @@ impl BackendManager {
             loaded_backends: Arc::new(Mutex::new(HashMap::new())),
             global_metrics: Arc::new(Mutex::new(GlobalMetrics::default())),
             activity_logger,
+            inference_cancel: Arc::new(Mutex::new(CancellationToken::new())),
         })
     }
 
@@ -191,6 +273,20 @@ impl BackendManager {
             _ => (BackendType::Gguf, BackendConfig::default()), // Default fallback
         };
 
+        // Share a backend that already has this model loaded
+        if let Some(backend_id) = self.share_loaded(&model.path, backend_type) {
+            self.activity_logger.log_model_operation(
+                ActivityType::ModelLoad,
+                &model_name,
+                ActivityStatus::Success,
+                Some(&format!(
+                    "Model already loaded, sharing backend ID: {}",
+                    backend_id
+                )),
+            );
+            return Ok(backend_id);
+        }
+
         // Create and load backend
         let backend_handle =
             BackendHandle::new_shared(backend_type, &backend_config).map_err(|e| {
@@ -213,18 +309,7 @@ impl BackendManager {
             e
         })?;
 
-        let backend_id = Uuid::new_v4().to_string();
-
-        {
-            let mut loaded_backends = self.loaded_backends.lock().unwrap();
-            loaded_backends.insert(backend_id.clone(), backend_handle);
-        }
-
-        // Update metrics
-        {
-            let mut metrics = self.global_metrics.lock().unwrap();
-            metrics.models_loaded += 1;
-        }
+        let backend_id = self.insert_loaded(model.path.clone(), backend_handle);
 
         // Log successful completion
         self.activity_logger.log_model_operation(
@@ -240,6 +325,67 @@ impl BackendManager {
         Ok(backend_id)
     }
 
+    /// Register a newly loaded backend for `model_path`, held by one load
+    fn insert_loaded(&self, model_path: PathBuf, handle: BackendHandle) -> String {
+        let backend_id = Uuid::new_v4().to_string();
+        {
+            let mut loaded_backends = self.loaded_backends.lock().unwrap();
+            loaded_backends.insert(
+                backend_id.clone(),
+                LoadedBackend {
+                    handle,
+                    model_path,
+                    loads: 1,
+                    in_flight: 0,
+                },
+            );
+        }
+
+        let mut metrics = self.global_metrics.lock().unwrap();
+        metrics.models_loaded += 1;
+        backend_id
+    }
+
+    /// Add a load to the backend already serving `model_path` with
+    /// `backend_type`, if there is one
+    fn share_loaded(
+        &self,
+        model_path: &std::path::Path,
+        backend_type: BackendType,
+    ) -> Option<String> {
+        let mut loaded_backends = self.loaded_backends.lock().unwrap();
+        let (backend_id, backend) = loaded_backends.iter_mut().find(|(_, backend)| {
+            !backend.is_unloading()
+                && backend.model_path == model_path
+                && backend.handle.get_backend_type() == backend_type
+        })?;
+        backend.loads += 1;
+        Some(backend_id.clone())
+    }
+
+    /// Hold `backend_id` for a request, so its model is not unloaded until the
+    /// returned lease is dropped
+    pub fn acquire(&self, backend_id: &str) -> Result<BackendLease> {
+        let mut loaded_backends = self.loaded_backends.lock().unwrap();
+        let backend = loaded_backends
+            .get_mut(backend_id)
+            .filter(|backend| !backend.is_unloading())
+            .ok_or_else(|| anyhow::anyhow!("Backend not found: {}", backend_id))?;
+        backend.in_flight += 1;
+        Ok(BackendLease {
+            backend_id: backend_id.to_string(),
+            handle: backend.handle.clone(),
+            loaded_backends: Arc::clone(&self.loaded_backends),
+            metrics: Arc::clone(&self.global_metrics),
+        })
+    }
+
+    /// Release one load of `backend_id`
+    ///
+    /// The model is freed once no other load shares it and no request is
+    /// running on it; until then it stays loaded, and after the last load is
+    /// released no new requests are admitted. Use
+    /// [`force_unload`](Self::force_unload) to free it regardless.
     pub async fn unload_model(&self, backend_id: String) -> Result<()> {
         // Log the start of unload operation
         self.activity_logger.log_model_operation(
@@ -249,52 +395,110 @@ impl BackendManager {
             Some("Unloading model from backend"),
         );
 
-        let backend_handle = {
+        let released = {
             let mut loaded_backends = self.loaded_backends.lock().unwrap();
-            loaded_backends.remove(&backend_id)
+            match loaded_backends
+                .get_mut(&backend_id)
+                .filter(|backend| !backend.is_unloading())
+            {
+                Some(backend) => {
+                    backend.loads -= 1;
+                    if backend.loads > 0 {
+                        Some(Err(format!(
+                            "Model still loaded for {} other user(s)",
+                            backend.loads
+                        )))
+                    } else if backend.in_flight > 0 {
+                        Some(Err(format!(
+                            "Model will be unloaded when its {} running request(s) finish",
+                            backend.in_flight
+                        )))
+                    } else {
+                        loaded_backends.remove(&backend_id).map(Ok)
+                    }
+                }
+                None => None,
+            }
         };
 
-        if let Some(handle) = backend_handle {
-            // Unload the model from the backend
-            handle.unload_model().await.map_err(|e| {
+        match released {
+            Some(Ok(backend)) => self.free_backend(&backend_id, backend).await,
+            Some(Err(deferred)) => {
                 self.activity_logger.log_model_operation(
                     ActivityType::ModelUnload,
                     &backend_id,
-                    ActivityStatus::Error,
-                    Some(&format!("Failed to unload model: {}", e)),
+                    ActivityStatus::Success,
+                    Some(&deferred),
                 );
-                e
-            })?;
-
-            // Update metrics
-            let mut metrics = self.global_metrics.lock().unwrap();
-            if metrics.models_loaded > 0 {
-                metrics.models_loaded -= 1;
+                Ok(())
             }
+            None => self.backend_not_found(&backend_id),
+        }
+    }
 
-            // Log successful completion
-            self.activity_logger.log_model_operation(
-                ActivityType::ModelUnload,
-                &backend_id,
-                ActivityStatus::Success,
-                Some("Model unloaded successfully"),
-            );
+    /// Unload `backend_id` now, whoever else has it loaded
+    ///
+    /// Requests already running on it finish first; requests waiting for it
+    /// fail because no model is loaded.
+    pub async fn force_unload(&self, backend_id: String) -> Result<()> {
+        self.activity_logger.log_model_operation(
+            ActivityType::ModelUnload,
+            &backend_id,
+            ActivityStatus::InProgress,
+            Some("Force unloading model from backend"),
+        );
+
+        let backend = {
+            let mut loaded_backends = self.loaded_backends.lock().unwrap();
+            loaded_backends.remove(&backend_id)
+        };
+        match backend {
+            Some(backend) => self.free_backend(&backend_id, backend).await,
+            None => self.backend_not_found(&backend_id),
+        }
+    }
 
-            Ok(())
-        } else {
+    async fn free_backend(&self, backend_id: &str, backend: LoadedBackend) -> Result<()> {
+        decrement_models_loaded(&self.global_metrics);
+
+        // Unload the model from the backend
+        backend.handle.unload_model().await.map_err(|e| {
             self.activity_logger.log_model_operation(
                 ActivityType::ModelUnload,
-                &backend_id,
+                backend_id,
                 ActivityStatus::Error,
-                Some("Backend not found"),
+                Some(&format!("Failed to unload model: {}", e)),
             );
-            Err(anyhow::anyhow!("Backend not found: {}", backend_id))
-        }
+            e
+        })?;
+
+        // Log successful completion
+        self.activity_logger.log_model_operation(
+            ActivityType::ModelUnload,
+            backend_id,
+            ActivityStatus::Success,
+            Some("Model unloaded successfully"),
+        );
+        Ok(())
+    }
+
+    fn backend_not_found(&self, backend_id: &str) -> Result<()> {
+        self.activity_logger.log_model_operation(
+            ActivityType::ModelUnload,
+            backend_id,
+            ActivityStatus::Error,
+            Some("Backend not found"),
+        );
+        Err(anyhow::anyhow!("Backend not found: {}", backend_id))
     }
 
     pub fn get_loaded_models(&self) -> Vec<String> {
         let loaded_backends = self.loaded_backends.lock().unwrap();
-        loaded_backends.keys().cloned().collect()
+        loaded_backends
+            .iter()
+            .filter(|(_, backend)| !backend.is_unloading())
+            .map(|(backend_id, _)| backend_id.clone())
+            .collect()
     }
 
     pub async fn infer(
@@ -305,8 +509,31 @@ impl BackendManager {
     ) -> Result<String> {
         let start_time = std::time::Instant::now();
 
+        // Hold the backend so it stays loaded until this request is done
+        let lease = self.acquire(&backend_id).inspect_err(|_| {
+            self.activity_logger.log_inference(
+                &backend_id,
+                estimate_tokens(&prompt),
+                0, // no completion tokens for error
+                start_time.elapsed().as_millis() as u64,
+                ActivityStatus::Error,
+            );
+        })?;
+        self.infer_leased(lease, prompt, params, start_time).await
+    }
+
+    async fn infer_leased(
+        &self,
+        lease: BackendLease,
+        prompt: String,
+        params: InferenceParams,
+        start_time: std::time::Instant,
+    ) -> Result<String> {
+        let backend_id = lease.backend_id.clone();
+        let backend_handle = lease.handle();
+
         // Log the start of inference
-        let prompt_tokens = prompt.split_whitespace().count() as u32;
+        let prompt_tokens = backend_handle.count_tokens(&prompt).await;
         self.activity_logger.log_inference(
             &backend_id,
             prompt_tokens,
@@ -315,33 +542,22 @@ impl BackendManager {
             ActivityStatus::InProgress,
         );
 
-        // Get the backend handle
-        let backend_handle = {
-            let loaded_backends = self.loaded_backends.lock().unwrap();
-            loaded_backends
-                .get(&backend_id)
-                .ok_or_else(|| {
-                    self.activity_logger.log_inference(
-                        &backend_id,
-                        prompt_tokens,
-                        0, // no completion tokens for error
-                        start_time.elapsed().as_millis() as u64,
-                        ActivityStatus::Error,
-                    );
-                    anyhow::anyhow!("Backend not found: {}", backend_id)
-                })?
-                .clone()
-        };
-
         // Convert parameters
         let inferno_params = InfernoInferenceParams {
             max_tokens: params.max_tokens.unwrap_or(512),
             temperature: params.temperature.unwrap_or(0.7),
             top_p: params.top_p.unwrap_or(0.9),
             top_k: params.top_k.unwrap_or(40),
+            repeat_penalty: None,
+            repeat_last_n: None,
+            min_p: None,
             stream: params.stream.unwrap_or(false),
             stop_sequences: params.stop_sequences.clone().unwrap_or_default(),
+            stop_regex: None,
+            max_generation_ms: None,
             seed: params.seed,
+            response_format: None,
+            logprobs: None,
         };
 
         // Track active inference count while the request is in-flight
@@ -351,13 +567,16 @@ impl BackendManager {
         }
 
         // Perform inference
-        let result = backend_handle.infer(&prompt, &inferno_params).await;
+        let cancel = self.inference_cancel.lock().unwrap().clone();
+        let result = backend_handle
+            .infer_cancellable(&prompt, &inferno_params, &cancel)
+            .await;
         let elapsed_ms = start_time.elapsed().as_millis() as u64;
 
         let (status, completion_tokens) = match &result {
             Ok(output) => (
                 ActivityStatus::Success,
-                output.split_whitespace().count() as u32,
+                backend_handle.count_tokens(output).await,
             ),
             Err(_) => (ActivityStatus::Error, 0),
         };
@@ -405,16 +624,12 @@ impl BackendManager {
     }
 
     pub async fn get_model_info(&self, backend_id: &str) -> Option<ModelInfo> {
-        let handle = {
-            let loaded_backends = self.loaded_backends.lock().unwrap();
-            loaded_backends.get(backend_id).cloned()
-        };
-
-        if let Some(handle) = handle {
-            handle.get_model_info().await.map(Self::map_core_model_info)
-        } else {
-            None
-        }
+        let lease = self.acquire(backend_id).ok()?;
+        lease
+            .handle()
+            .get_model_info()
+            .await
+            .map(Self::map_core_model_info)
     }
 
     fn map_core_model_info(model: CoreModelInfo) -> ModelInfo {
@@ -435,25 +650,42 @@ impl BackendManager {
         prompt: &str,
         params: &InferenceParams,
     ) -> Result<TokenStream> {
-        let backend_handle = {
-            let loaded_backends = self.loaded_backends.lock().unwrap();
-            loaded_backends
-                .get(backend_id)
-                .ok_or_else(|| anyhow::anyhow!("Backend not found: {}", backend_id))?
-                .clone()
-        };
+        let lease = self.acquire(backend_id)?;
 
         let inferno_params = InfernoInferenceParams {
             max_tokens: params.max_tokens.unwrap_or(512),
             temperature: params.temperature.unwrap_or(0.7),
             top_p: params.top_p.unwrap_or(0.9),
             top_k: params.top_k.unwrap_or(40),
+            repeat_penalty: None,
+            repeat_last_n: None,
+            min_p: None,
             stream: true,
             stop_sequences: params.stop_sequences.clone().unwrap_or_default(),
+            stop_regex: None,
+            max_generation_ms: None,
             seed: params.seed,
+            response_format: None,
+            logprobs: None,
         };
 
-        backend_handle.infer_stream(prompt, &inferno_params).await
+        let cancel = self.inference_cancel.lock().unwrap().clone();
+        let stream = lease
+            .handle()
+            .infer_stream_cancellable(prompt, &inferno_params, &cancel)
+            .await?;
+        // The stream keeps the backend loaded until it is dropped
+        Ok(Box::pin(stream.map(move |token| {
+            let _lease = &lease;
+            token
+        })))
+    }
+
+    /// Cancel every inference and stream in flight
+    pub fn stop_all_inference(&self) {
+        let mut cancel = self.inference_cancel.lock().unwrap();
+        cancel.cancel();
+        *cancel = CancellationToken::new();
     }
 
     pub fn get_metrics(&self) -> GlobalMetrics {
@@ -509,3 +741,84 @@ impl BackendManager {
         metrics.active_inferences
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::mock::{MockBackend, MockState};
+    use std::time::Duration;
+
+    /// Takes a while per request, and fails a request whose model is unloaded
+    /// before it finishes
+    fn slow_backend() -> (BackendHandle, Arc<MockState>) {
+        let backend = MockBackend::tokens(&["done"])
+            .delay(Duration::from_millis(20))
+            .backend_type(BackendType::Gguf);
+        let state = backend.state();
+        (backend.handle(), state)
+    }
+
+    #[tokio::test]
+    async fn test_shared_model_is_freed_after_its_last_request() {
+        let manager = Arc::new(
+            BackendManager::new(Arc::new(ActivityLogger::new(100)))
+                .await
+                .unwrap(),
+        );
+        let (handle, mock) = slow_backend();
+        let model_path = PathBuf::from("models/shared.gguf");
+        let backend_id = manager.insert_loaded(model_path.clone(), handle);
+
+        // A second load of the same model shares the backend
+        let shared = manager.share_loaded(&model_path, BackendType::Gguf);
+        assert_eq!(shared.as_deref(), Some(backend_id.as_str()));
+        assert_eq!(manager.get_metrics().models_loaded, 1);
+
+        // Overlapping requests, with both loads released while they run
+        let requests: Vec<_> = (0..4)
+            .map(|i| {
+                let lease = manager.acquire(&backend_id).unwrap();
+                let manager = Arc::clone(&manager);
+                tokio::spawn(async move {
+                    let prompt = format!("request {}", i);
+                    let params = InferenceParams::default();
+                    let started = std::time::Instant::now();
+                    manager.infer_leased(lease, prompt, params, started).await
+                })
+            })
+            .collect();
+        manager.unload_model(backend_id.clone()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        manager.unload_model(backend_id.clone()).await.unwrap();
+
+        // No new requests once every load is released, but the model stays
+        // loaded for the ones running
+        assert!(manager.get_loaded_models().is_empty());
+        assert!(manager.acquire(&backend_id).is_err());
+        assert!(mock.is_loaded());
+
+        for request in requests {
+            assert_eq!(request.await.unwrap().unwrap(), "done");
+        }
+        for _ in 0..100 {
+            if !mock.is_loaded() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert!(!mock.is_loaded());
+        assert_eq!(manager.get_metrics().models_loaded, 0);
+        assert!(manager.unload_model(backend_id).await.is_err());
+
+        // A forced unload frees the model despite other loads
+        let (handle, mock) = slow_backend();
+        let backend_id = manager.insert_loaded(model_path.clone(), handle);
+        manager
+            .share_loaded(&model_path, BackendType::Gguf)
+            .unwrap();
+        manager.force_unload(backend_id.clone()).await.unwrap();
+        assert!(!mock.is_loaded());
+        assert!(manager.get_loaded_models().is_empty());
+        assert_eq!(manager.get_metrics().models_loaded, 0);
+    }
+}
@@ -2,12 +2,12 @@
 
 //! Tauri Command Handlers
 //!
-//! This module contains all 51 Tauri command handlers migrated from dashboard/src-tauri.
+//! This module contains all 52 Tauri command handlers migrated from dashboard/src-tauri.
 //! Commands are organized by functionality for maintainability.
 //!
 //! ## Command Categories:
 //! - Core Model Operations (5 commands)
-//! - Inference Operations (2 commands)
+//! - Inference Operations (3 commands)
 //! - System Information (4 commands)
 //! - File Operations (2 commands)
 //! - Settings Management (2 commands)
@@ -37,7 +37,8 @@ use super::types::{
 use super::{
     ActivityLog, ActivityStats, ActivityType, ApiKey, AppState, CreateApiKeyRequest,
     CreateApiKeyResponse, DownloadProgress, ExternalModelInfo, InferenceParams, ModelInfo,
-    ModelSearchQuery, ModelSearchResponse, SecurityEvent, SecurityMetrics, SecurityScanResult,
+    ModelSearchQuery, ModelSearchResponse, STREAM_HEARTBEAT_INTERVAL, SecurityEvent,
+    SecurityMetrics, SecurityScanResult, StreamEvent, with_heartbeat,
 };
 
 // ============================================================================
@@ -107,7 +108,7 @@ pub async fn get_model_info(
 }
 
 // ============================================================================
-// Inference Operations (2 commands)
+// Inference Operations (3 commands)
 // ============================================================================
 
 #[command]
@@ -185,12 +186,23 @@ pub async fn infer_stream(
             .infer_stream(&backend_id_clone, &prompt_for_stream, &params_for_stream)
             .await
         {
-            Ok(mut stream) => {
+            Ok(stream) => {
                 use futures::StreamExt;
 
-                while let Some(result) = stream.next().await {
-                    match result {
-                        Ok(token) => {
+                let mut events = std::pin::pin!(with_heartbeat(stream, STREAM_HEARTBEAT_INTERVAL));
+                while let Some(event) = events.next().await {
+                    match event {
+                        StreamEvent::Heartbeat(heartbeat) => {
+                            let _ = app_clone.emit(
+                                "inference_heartbeat",
+                                serde_json::json!({
+                                    "inference_id": inference_id_clone,
+                                    "elapsed_ms": heartbeat.elapsed_ms,
+                                    "tokens": heartbeat.tokens
+                                }),
+                            );
+                        }
+                        StreamEvent::Token(Ok(token)) => {
                             let _ = app_clone.emit(
                                 "inference_token",
                                 serde_json::json!({
@@ -202,7 +214,7 @@ pub async fn infer_stream(
                             // Small delay to prevent overwhelming the frontend
                             sleep(Duration::from_millis(10)).await;
                         }
-                        Err(e) => {
+                        StreamEvent::Token(Err(e)) => {
                             let _ = app_clone.emit(
                                 "inference_error",
                                 serde_json::json!({
@@ -233,6 +245,13 @@ pub async fn infer_stream(
     Ok(inference_id)
 }
 
+/// Cancel every inference and stream in flight
+#[command]
+pub async fn stop_inference(state: State<'_, AppState>) -> Result<(), String> {
+    state.backend_manager.stop_all_inference();
+    Ok(())
+}
+
 // ============================================================================
 // System Information (4 commands)
 // ============================================================================
@@ -1065,11 +1084,12 @@ pub async fn search_models(
 #[command]
 pub async fn get_model_details(
     model_id: String,
+    revision: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<ExternalModelInfo, String> {
     state
         .model_repository
-        .get_model_details(&model_id)
+        .get_model_details(&model_id, revision.as_deref())
         .await
         .map_err(|e| e.to_string())
 }
@@ -1078,11 +1098,12 @@ pub async fn get_model_details(
 pub async fn download_model(
     app: AppHandle,
     model_id: String,
+    revision: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<String, String> {
     let model = state
         .model_repository
-        .get_model_details(&model_id)
+        .get_model_details(&model_id, revision.as_deref())
         .await
         .map_err(|e| e.to_string())?;
 
@@ -1093,7 +1114,7 @@ pub async fn download_model(
 
     let download_id = state
         .download_manager
-        .start_download(&model, &target_dir)
+        .start_download(&state.model_repository, &model, &target_dir)
         .await
         .map_err(|e| e.to_string())?;
 
@@ -1102,6 +1123,7 @@ pub async fn download_model(
         serde_json::json!({
             "download_id": download_id,
             "model_id": model_id,
+            "revision": model.pinned_revision(),
         }),
     );
 
@@ -2,9 +2,63 @@
 //!
 //! This module handles event emission from the Rust backend to the frontend.
 //! Events are used for real-time updates, notifications, and state changes.
+//!
+//! Streaming inference also sends `inference_heartbeat` events every
+//! [`STREAM_HEARTBEAT_INTERVAL`] whether or not tokens arrive, so the UI can
+//! tell a slow generation from a stalled one.
 
 use chrono::Utc;
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use std::time::Duration;
 use tauri::{AppHandle, Emitter};
+use tokio::time::{Instant, MissedTickBehavior};
+
+/// How often a streaming inference reports that it is still running
+pub const STREAM_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Progress of a streaming inference, sent as `inference_heartbeat`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StreamHeartbeat {
+    pub elapsed_ms: u64,
+    /// Tokens received so far
+    pub tokens: u64,
+}
+
+/// An item of a token stream, or a heartbeat sent between them
+#[derive(Debug)]
+pub enum StreamEvent<T> {
+    Token(T),
+    Heartbeat(StreamHeartbeat),
+}
+
+/// Interleave `stream` with a heartbeat every `period`, ending when it does
+pub fn with_heartbeat<S>(stream: S, period: Duration) -> impl Stream<Item = StreamEvent<S::Item>>
+where
+    S: Stream + Unpin,
+{
+    let start = Instant::now();
+    let mut ticks = tokio::time::interval_at(start + period, period);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    futures::stream::unfold(
+        (stream, ticks, 0u64),
+        move |(mut stream, mut ticks, tokens)| async move {
+            tokio::select! {
+                biased;
+                item = stream.next() => {
+                    item.map(|item| (StreamEvent::Token(item), (stream, ticks, tokens + 1)))
+                }
+                _ = ticks.tick() => {
+                    let heartbeat = StreamHeartbeat {
+                        elapsed_ms: start.elapsed().as_millis() as u64,
+                        tokens,
+                    };
+                    Some((StreamEvent::Heartbeat(heartbeat), (stream, ticks, tokens)))
+                }
+            }
+        },
+    )
+}
 
 /// Event manager for emitting events to the frontend
 pub struct EventManager {
@@ -188,3 +242,33 @@ impl EventManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeats_are_sent_while_tokens_stall() {
+        // Two tokens, then 3.5 seconds of nothing before the last
+        let tokens = futures::stream::iter(["a", "b", "c"]).then(|token| async move {
+            if token == "c" {
+                tokio::time::sleep(Duration::from_millis(3500)).await;
+            }
+            token
+        });
+        let events: Vec<_> = with_heartbeat(Box::pin(tokens), STREAM_HEARTBEAT_INTERVAL)
+            .collect()
+            .await;
+
+        let summary: Vec<String> = events
+            .iter()
+            .map(|event| match event {
+                StreamEvent::Token(token) => token.to_string(),
+                StreamEvent::Heartbeat(heartbeat) => {
+                    format!("{}ms/{}", heartbeat.elapsed_ms, heartbeat.tokens)
+                }
+            })
+            .collect();
+        assert_eq!(summary, ["a", "b", "1000ms/2", "2000ms/2", "3000ms/2", "c"]);
+    }
+}
@@ -26,6 +26,7 @@ pub use activity_logger::{
     ActivityLog, ActivityLogger, ActivityStats, ActivityStatus, ActivityType,
 };
 pub use backend_manager::{BackendManager, GlobalMetrics, InferenceParams, ModelInfo};
+pub use events::{STREAM_HEARTBEAT_INTERVAL, StreamEvent, StreamHeartbeat, with_heartbeat};
 pub use model_repository::{
     DownloadProgress, ExternalModelInfo, ModelDownloadManager, ModelRepositoryService,
     ModelSearchQuery, ModelSearchResponse,
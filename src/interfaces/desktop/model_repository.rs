@@ -8,8 +8,11 @@ use anyhow::{Result, anyhow};
 use reqwest;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 use uuid::Uuid;
 
+use crate::models::{ModelManager, ModelSource};
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ExternalModelInfo {
     pub id: String,
@@ -27,6 +30,19 @@ pub struct ExternalModelInfo {
     pub created_at: String,
     pub updated_at: String,
     pub file_info: Vec<ModelFileInfo>,
+    /// Requested revision (commit SHA, tag, or branch); `None` means the default branch
+    #[serde(default)]
+    pub revision: Option<String>,
+    /// Commit SHA the revision resolved to, when the API reported one
+    #[serde(default)]
+    pub sha: Option<String>,
+}
+
+impl ExternalModelInfo {
+    /// Revision that file downloads are pinned to
+    pub fn pinned_revision(&self) -> Option<&str> {
+        self.sha.as_deref().or(self.revision.as_deref())
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -46,6 +62,9 @@ pub struct ModelSearchQuery {
     pub direction: String, // "asc", "desc"
     pub limit: u32,
     pub offset: u32,
+    /// Revision to pin download URLs of the results to
+    #[serde(default)]
+    pub revision: Option<String>,
 }
 
 impl Default for ModelSearchQuery {
@@ -58,6 +77,7 @@ impl Default for ModelSearchQuery {
             direction: "desc".to_string(),
             limit: 20,
             offset: 0,
+            revision: None,
         }
     }
 }
@@ -83,6 +103,9 @@ pub struct DownloadProgress {
     pub eta_seconds: Option<u64>,
     pub started_at: String,
     pub completed_at: Option<String>,
+    /// Revision the file was fetched from, so the download can be reproduced
+    #[serde(default)]
+    pub revision: Option<String>,
 }
 
 pub struct ModelRepositoryService {
@@ -152,7 +175,7 @@ impl ModelRepositoryService {
 
         // Parse the response from Hugging Face API
         let raw_models: serde_json::Value = response.json().await?;
-        let models = self.parse_huggingface_models(raw_models)?;
+        let models = self.parse_huggingface_models(raw_models, query.revision.as_deref())?;
 
         Ok(ModelSearchResponse {
             total: models.len() as u32,
@@ -161,8 +184,20 @@ impl ModelRepositoryService {
         })
     }
 
-    pub async fn get_model_details(&self, model_id: &str) -> Result<ExternalModelInfo> {
-        let url = format!("{}/api/models/{}", self.base_url, model_id);
+    pub async fn get_model_details(
+        &self,
+        model_id: &str,
+        revision: Option<&str>,
+    ) -> Result<ExternalModelInfo> {
+        let url = match revision {
+            Some(revision) => format!(
+                "{}/api/models/{}/revision/{}",
+                self.base_url,
+                model_id,
+                urlencoding::encode(revision)
+            ),
+            None => format!("{}/api/models/{}", self.base_url, model_id),
+        };
 
         let mut request = self.client.get(&url);
 
@@ -180,7 +215,7 @@ impl ModelRepositoryService {
         }
 
         let raw_model: serde_json::Value = response.json().await?;
-        self.parse_huggingface_model(raw_model)
+        self.parse_huggingface_model(raw_model, revision)
     }
 
     pub async fn get_featured_models(&self) -> Result<Vec<ExternalModelInfo>> {
@@ -192,6 +227,7 @@ impl ModelRepositoryService {
             direction: "desc".to_string(),
             limit: 10,
             offset: 0,
+            revision: None,
         };
 
         let response = self.search_models(query).await?;
@@ -207,15 +243,28 @@ impl ModelRepositoryService {
             direction: "desc".to_string(),
             limit: 10,
             offset: 0,
+            revision: None,
         };
 
         let response = self.search_models(query).await?;
         Ok(response.models)
     }
 
+    /// Download URL for a file at the given revision, or the default branch
+    pub fn resolve_url(&self, model_id: &str, revision: Option<&str>, filename: &str) -> String {
+        format!(
+            "{}/{}/resolve/{}/{}",
+            self.base_url,
+            model_id,
+            urlencoding::encode(revision.unwrap_or("main")),
+            filename
+        )
+    }
+
     fn parse_huggingface_models(
         &self,
         raw_data: serde_json::Value,
+        revision: Option<&str>,
     ) -> Result<Vec<ExternalModelInfo>> {
         let models_array = raw_data
             .as_array()
@@ -223,7 +272,7 @@ impl ModelRepositoryService {
 
         let mut models = Vec::new();
         for model_data in models_array {
-            match self.parse_huggingface_model(model_data.clone()) {
+            match self.parse_huggingface_model(model_data.clone(), revision) {
                 Ok(model) => models.push(model),
                 Err(e) => {
                     // Log error but continue processing other models
@@ -236,7 +285,11 @@ impl ModelRepositoryService {
         Ok(models)
     }
 
-    fn parse_huggingface_model(&self, raw_model: serde_json::Value) -> Result<ExternalModelInfo> {
+    fn parse_huggingface_model(
+        &self,
+        raw_model: serde_json::Value,
+        revision: Option<&str>,
+    ) -> Result<ExternalModelInfo> {
         let id = raw_model["id"]
             .as_str()
             .ok_or_else(|| anyhow!("Missing model id"))?
@@ -281,8 +334,12 @@ impl ModelRepositoryService {
             .unwrap_or(&chrono::Utc::now().to_rfc3339())
             .to_string();
 
+        // The model endpoints report the commit the requested revision, or the
+        // default branch, resolved to
+        let sha = raw_model["sha"].as_str().map(|s| s.to_string());
+
         // Parse file information
-        let file_info = self.parse_model_files(&raw_model, &id)?;
+        let file_info = self.parse_model_files(&raw_model, &id, sha.as_deref().or(revision))?;
 
         let download_url = format!("{}/{}", self.base_url, id);
         let repository_url = format!("{}/{}", self.base_url, id);
@@ -310,6 +367,8 @@ impl ModelRepositoryService {
             created_at,
             updated_at,
             file_info,
+            revision: revision.map(|r| r.to_string()),
+            sha,
         })
     }
 
@@ -317,6 +376,7 @@ impl ModelRepositoryService {
         &self,
         raw_model: &serde_json::Value,
         model_id: &str,
+        revision: Option<&str>,
     ) -> Result<Vec<ModelFileInfo>> {
         let mut files = Vec::new();
 
@@ -331,8 +391,7 @@ impl ModelRepositoryService {
                         "gguf" | "onnx" | "safetensors" | "bin" | "pt"
                     ) {
                         let size_bytes = file_data["size"].as_u64().unwrap_or(0);
-                        let download_url =
-                            format!("{}/{}/resolve/main/{}", self.base_url, model_id, filename);
+                        let download_url = self.resolve_url(model_id, revision, filename);
 
                         files.push(ModelFileInfo {
                             filename: filename.to_string(),
@@ -350,7 +409,7 @@ impl ModelRepositoryService {
             files.push(ModelFileInfo {
                 filename: "model.bin".to_string(),
                 size_bytes: 0,
-                download_url: format!("{}/{}/resolve/main/model.bin", self.base_url, model_id),
+                download_url: self.resolve_url(model_id, revision, "model.bin"),
                 file_type: "bin".to_string(),
             });
         }
@@ -379,13 +438,35 @@ impl ModelDownloadManager {
         self
     }
 
+    /// Start downloading the first file of `model` into `target_dir`
+    ///
+    /// When `model` carries no commit, it is looked up through `repository`, the
+    /// service the app was configured with.
     pub async fn start_download(
         &self,
+        repository: &ModelRepositoryService,
         model: &ExternalModelInfo,
         target_dir: &str,
     ) -> Result<String> {
         let download_id = Uuid::new_v4().to_string();
 
+        // Record the commit the file comes from rather than a branch such as
+        // "main", which moves on and would make every upgrade check see a new
+        // version
+        let pinned;
+        let model = if model.sha.is_some() {
+            model
+        } else {
+            pinned = repository
+                .get_model_details(&model.id, model.revision.as_deref())
+                .await?;
+            &pinned
+        };
+        let revision = model
+            .sha
+            .clone()
+            .ok_or_else(|| anyhow!("Hugging Face reported no commit for model {}", model.id))?;
+
         // For now, download the first available file
         let file_to_download = model
             .file_info
@@ -405,6 +486,7 @@ impl ModelDownloadManager {
             eta_seconds: None,
             started_at: chrono::Utc::now().to_rfc3339(),
             completed_at: None,
+            revision: Some(revision.clone()),
         };
 
         // Store initial progress
@@ -419,17 +501,30 @@ impl ModelDownloadManager {
         let download_url = file_to_download.download_url.clone();
         let target_path = format!("{}/{}", target_dir, file_to_download.filename);
         let download_id_clone = download_id.clone();
+        let models_dir = target_dir.to_string();
+        let source = ModelSource {
+            repo_id: model.id.clone(),
+            filename: file_to_download.filename.clone(),
+            revision,
+        };
 
         tokio::spawn(async move {
-            let result = Self::download_file_with_progress(
+            let mut result = Self::download_file_with_progress(
                 client,
                 download_url,
-                target_path,
+                target_path.clone(),
                 download_id_clone.clone(),
                 downloads_ref.clone(),
             )
             .await;
 
+            if result.is_ok() {
+                // Remember where the file came from so the exact revision can be fetched again
+                result = ModelManager::new(Path::new(&models_dir))
+                    .set_model_source(Path::new(&target_path), source)
+                    .await;
+            }
+
             // Update final status
             if let Ok(mut downloads) = downloads_ref.lock() {
                 if let Some(progress) = downloads.get_mut(&download_id_clone) {
@@ -567,3 +662,62 @@ impl ModelDownloadManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_url_pins_revision() {
+        let service = ModelRepositoryService::new();
+        let url = service.resolve_url("org/model-GGUF", Some("a1b2c3d"), "model.Q4_K_M.gguf");
+        let path = reqwest::Url::parse(&url).unwrap().path().to_string();
+
+        assert_eq!(path, "/org/model-GGUF/resolve/a1b2c3d/model.Q4_K_M.gguf");
+        assert_eq!(
+            service.resolve_url("org/model-GGUF", None, "model.gguf"),
+            "https://huggingface.co/org/model-GGUF/resolve/main/model.gguf"
+        );
+    }
+
+    #[test]
+    fn test_parse_model_with_revision_pins_files_to_sha() {
+        let service = ModelRepositoryService::new();
+        let raw = serde_json::json!({
+            "id": "org/model-GGUF",
+            "sha": "0123456789abcdef",
+            "siblings": [{"rfilename": "model.gguf", "size": 42}],
+        });
+
+        let model = service.parse_huggingface_model(raw, Some("v1.0")).unwrap();
+
+        assert_eq!(model.revision.as_deref(), Some("v1.0"));
+        assert_eq!(model.pinned_revision(), Some("0123456789abcdef"));
+        assert!(
+            model.file_info[0]
+                .download_url
+                .ends_with("/resolve/0123456789abcdef/model.gguf")
+        );
+    }
+
+    #[test]
+    fn test_parse_model_on_default_branch_pins_files_to_sha() {
+        let service = ModelRepositoryService::new();
+        let raw = serde_json::json!({
+            "id": "org/model-GGUF",
+            "sha": "fedcba9876543210",
+            "siblings": [{"rfilename": "model.gguf", "size": 42}],
+        });
+
+        let model = service.parse_huggingface_model(raw, None).unwrap();
+
+        // Downloads record the commit, not "main", so upgrade checks compare SHAs
+        assert_eq!(model.revision, None);
+        assert_eq!(model.pinned_revision(), Some("fedcba9876543210"));
+        assert!(
+            model.file_info[0]
+                .download_url
+                .ends_with("/resolve/fedcba9876543210/model.gguf")
+        );
+    }
+}
@@ -46,6 +46,7 @@ impl std::fmt::Display for OutputFormat {
 
 pub mod text {
     use anyhow::Result;
+    use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE};
     use std::path::Path;
     use tokio::fs;
 
@@ -62,11 +63,174 @@ pub mod text {
     pub fn validate_utf8(data: &[u8]) -> Result<String> {
         String::from_utf8(data.to_vec()).map_err(|e| anyhow::anyhow!("Invalid UTF-8: {}", e))
     }
+
+    /// What to do with bytes or characters the encoding cannot represent
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum DecodeMode {
+        /// Fail on the first invalid sequence
+        #[default]
+        Strict,
+        /// Substitute U+FFFD when decoding and numeric character references when encoding
+        Lossy,
+    }
+
+    /// Character encoding for reading and writing text files
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TextEncoding {
+        pub encoding: &'static Encoding,
+        pub mode: DecodeMode,
+    }
+
+    impl Default for TextEncoding {
+        fn default() -> Self {
+            Self {
+                encoding: UTF_8,
+                mode: DecodeMode::Strict,
+            }
+        }
+    }
+
+    impl TextEncoding {
+        /// Look up an encoding by WHATWG label, such as `utf-16le`, `latin1` or `shift_jis`
+        ///
+        /// Labels for ISO-8859-1 resolve to windows-1252, its superset.
+        pub fn for_label(label: &str) -> Result<Self> {
+            let encoding = Encoding::for_label(label.trim().as_bytes())
+                .ok_or_else(|| anyhow::anyhow!("Unknown text encoding: {}", label))?;
+            Ok(Self {
+                encoding,
+                mode: DecodeMode::Strict,
+            })
+        }
+
+        pub fn with_mode(mut self, mode: DecodeMode) -> Self {
+            self.mode = mode;
+            self
+        }
+
+        /// Decode `data`, dropping a leading byte order mark for this encoding
+        pub fn decode(&self, data: &[u8]) -> Result<String> {
+            let data = match Encoding::for_bom(data) {
+                Some((encoding, bom_len)) if encoding == self.encoding => &data[bom_len..],
+                _ => data,
+            };
+
+            match self.mode {
+                DecodeMode::Strict => self
+                    .encoding
+                    .decode_without_bom_handling_and_without_replacement(data)
+                    .map(|text| text.into_owned())
+                    .ok_or_else(|| anyhow::anyhow!("Invalid {} text", self.encoding.name())),
+                DecodeMode::Lossy => {
+                    let (text, _) = self.encoding.decode_without_bom_handling(data);
+                    Ok(text.into_owned())
+                }
+            }
+        }
+
+        /// Encode `content`, without a byte order mark
+        pub fn encode(&self, content: &str) -> Result<Vec<u8>> {
+            // encoding_rs only decodes UTF-16; its encoder produces UTF-8 instead
+            if self.encoding == UTF_16LE {
+                return Ok(content.encode_utf16().flat_map(u16::to_le_bytes).collect());
+            }
+            if self.encoding == UTF_16BE {
+                return Ok(content.encode_utf16().flat_map(u16::to_be_bytes).collect());
+            }
+
+            let (bytes, _, had_errors) = self.encoding.encode(content);
+            if had_errors && self.mode == DecodeMode::Strict {
+                return Err(anyhow::anyhow!(
+                    "Text contains characters that cannot be encoded as {}",
+                    self.encoding.name()
+                ));
+            }
+            Ok(bytes.into_owned())
+        }
+    }
+
+    pub async fn read_text_file_with_encoding(
+        path: &Path,
+        encoding: TextEncoding,
+    ) -> Result<String> {
+        let data = fs::read(path).await?;
+        encoding.decode(&data)
+    }
+
+    pub async fn write_text_file_with_encoding(
+        path: &Path,
+        content: &str,
+        encoding: TextEncoding,
+    ) -> Result<()> {
+        fs::write(path, encoding.encode(content)?).await?;
+        Ok(())
+    }
+}
+
+pub mod atomic {
+    use anyhow::{Context, Result};
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::Path;
+
+    /// Replace `path` with what `write` produces, or leave it untouched
+    ///
+    /// Output goes to a temporary file in the same directory, which is synced to
+    /// disk and renamed over `path` only once `write` succeeds. An error or crash
+    /// before the rename leaves the previous file as it was.
+    pub fn write_file_with<F>(path: &Path, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut File) -> Result<()>,
+    {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut builder = tempfile::Builder::new();
+        builder.prefix(".inferno-").suffix(".tmp");
+        // Keep the permissions of the file being replaced; new files get the usual 0644
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let permissions = std::fs::metadata(path)
+                .map(|metadata| metadata.permissions())
+                .unwrap_or_else(|_| std::fs::Permissions::from_mode(0o644));
+            builder.permissions(permissions);
+        }
+        let mut tmp = builder
+            .tempfile_in(dir)
+            .with_context(|| format!("Failed to create temporary file in {}", dir.display()))?;
+
+        write(tmp.as_file_mut())?;
+        tmp.as_file_mut().flush()?;
+        tmp.as_file().sync_all()?;
+        tmp.persist(path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+
+        // Make the rename itself durable
+        #[cfg(unix)]
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    }
+
+    pub fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+        write_file_with(path, |file| Ok(file.write_all(contents.as_ref())?))
+    }
+
+    pub async fn write_file_async(path: &Path, contents: impl Into<Vec<u8>>) -> Result<()> {
+        let path = path.to_path_buf();
+        let contents = contents.into();
+        tokio::task::spawn_blocking(move || write_file(&path, contents)).await?
+    }
 }
 
 pub mod image {
-    use anyhow::Result;
+    use crate::models::onnx_graph::{self, TensorDim, TensorSpec};
+    use anyhow::{Context, Result};
     use image::{ImageBuffer, ImageFormat, Rgb};
+    use serde::{Deserialize, Serialize};
     use std::path::Path;
 
     pub async fn load_image(path: &Path) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
@@ -111,6 +275,258 @@ pub mod image {
             _ => Err(anyhow::anyhow!("Unsupported image format")),
         }
     }
+
+    /// How an image is fitted to the model's input size
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ResizeMode {
+        /// Scale the short side to fit and crop the middle, keeping the aspect ratio
+        #[default]
+        CenterCrop,
+        /// Scale each side to fit, distorting the aspect ratio
+        Stretch,
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum ChannelOrder {
+        #[default]
+        Rgb,
+        Bgr,
+    }
+
+    /// Axis order of the input tensor
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum TensorLayout {
+        /// Batch, channel, height, width: the PyTorch export convention
+        #[default]
+        Nchw,
+        /// Batch, height, width, channel: the TensorFlow convention
+        Nhwc,
+    }
+
+    /// How to turn an image into a vision model's input tensor
+    ///
+    /// Pixels are scaled to 0..=1, then each channel has its `mean` subtracted and
+    /// is divided by its `std`. `mean` and `std` are given in `channel_order`. The
+    /// defaults are the 224x224 ImageNet preprocessing most classifiers expect.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct ImagePreprocessConfig {
+        pub width: u32,
+        pub height: u32,
+        pub mean: [f32; 3],
+        pub std: [f32; 3],
+        pub resize: ResizeMode,
+        pub channel_order: ChannelOrder,
+        pub layout: TensorLayout,
+    }
+
+    impl Default for ImagePreprocessConfig {
+        fn default() -> Self {
+            Self {
+                width: 224,
+                height: 224,
+                mean: [0.485, 0.456, 0.406],
+                std: [0.229, 0.224, 0.225],
+                resize: ResizeMode::CenterCrop,
+                channel_order: ChannelOrder::Rgb,
+                layout: TensorLayout::Nchw,
+            }
+        }
+    }
+
+    impl ImagePreprocessConfig {
+        /// Preprocessing for the model at `model_path`, from the Hugging Face
+        /// `preprocessor_config.json` beside it where there is one
+        pub fn for_model(model_path: &Path) -> Result<Self> {
+            let path = model_path
+                .parent()
+                .unwrap_or(model_path)
+                .join("preprocessor_config.json");
+            let contents = match std::fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Cannot read {}", path.display()));
+                }
+            };
+            let hf: serde_json::Value = serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid preprocessor config {}", path.display()))?;
+
+            let mut config = Self::default();
+            let center_crop = hf["do_center_crop"].as_bool().unwrap_or(false);
+            config.resize = if center_crop {
+                ResizeMode::CenterCrop
+            } else {
+                ResizeMode::Stretch
+            };
+            let size = if center_crop {
+                &hf["crop_size"]
+            } else {
+                &hf["size"]
+            };
+            let side = |key: &str| {
+                size[key]
+                    .as_u64()
+                    .or_else(|| size["shortest_edge"].as_u64())
+                    .or_else(|| size.as_u64())
+            };
+            if let (Some(width), Some(height)) = (side("width"), side("height")) {
+                config.width = u32::try_from(width)?;
+                config.height = u32::try_from(height)?;
+            }
+            let triple = |value: &serde_json::Value| -> Option<[f32; 3]> {
+                let values: Vec<f32> = value
+                    .as_array()?
+                    .iter()
+                    .map(|v| v.as_f64().map(|v| v as f32))
+                    .collect::<Option<_>>()?;
+                values.try_into().ok()
+            };
+            if let Some(mean) = triple(&hf["image_mean"]) {
+                config.mean = mean;
+            }
+            if let Some(std) = triple(&hf["image_std"]) {
+                config.std = std;
+            }
+            config.validate()?;
+            Ok(config)
+        }
+
+        pub fn validate(&self) -> Result<()> {
+            if self.width == 0 || self.height == 0 {
+                anyhow::bail!(
+                    "Image preprocessing size {}x{} must not be empty",
+                    self.width,
+                    self.height
+                );
+            }
+            if self.std.iter().any(|&std| std.is_nan() || std <= 0.0) {
+                anyhow::bail!(
+                    "Image preprocessing std must be positive, got {:?}",
+                    self.std
+                );
+            }
+            Ok(())
+        }
+
+        /// Shape of the tensor [`preprocess`] produces, a batch of one
+        pub fn shape(&self) -> [usize; 4] {
+            let (height, width) = (self.height as usize, self.width as usize);
+            match self.layout {
+                TensorLayout::Nchw => [1, 3, height, width],
+                TensorLayout::Nhwc => [1, height, width, 3],
+            }
+        }
+
+        /// Check that the tensor [`preprocess`] produces fits the model input
+        /// `input`; sizes the model leaves open match anything
+        pub fn check_input(&self, input: &TensorSpec) -> Result<()> {
+            if !matches!(input.dtype.as_str(), "float32" | "unknown") {
+                anyhow::bail!(
+                    "Model input '{}' takes {} but images are preprocessed to float32",
+                    input.name,
+                    input.dtype
+                );
+            }
+            let Some(dims) = &input.shape else {
+                return Ok(());
+            };
+            let shape = self.shape();
+            let fits = dims.len() == shape.len()
+                && dims.iter().zip(shape).all(|(dim, size)| match dim {
+                    TensorDim::Fixed(expected) => *expected < 0 || *expected as usize == size,
+                    TensorDim::Symbolic(_) | TensorDim::Unknown => true,
+                });
+            if !fits {
+                let declared: Vec<String> = dims.iter().map(ToString::to_string).collect();
+                anyhow::bail!(
+                    "Model input '{}' has shape [{}] but images are preprocessed to {:?}; \
+                     check the preprocessing size and layout",
+                    input.name,
+                    declared.join(", "),
+                    shape
+                );
+            }
+            Ok(())
+        }
+    }
+
+    /// The input tensor for `img`, shaped as [`ImagePreprocessConfig::shape`]
+    pub fn preprocess(
+        img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+        config: &ImagePreprocessConfig,
+    ) -> Vec<f32> {
+        let (width, height) = (config.width, config.height);
+        let fitted = match config.resize {
+            ResizeMode::Stretch => resize_image(img, width, height),
+            ResizeMode::CenterCrop => {
+                let scale = f64::max(
+                    width as f64 / img.width().max(1) as f64,
+                    height as f64 / img.height().max(1) as f64,
+                );
+                let scaled_width = ((img.width() as f64 * scale).round() as u32).max(width);
+                let scaled_height = ((img.height() as f64 * scale).round() as u32).max(height);
+                let scaled = resize_image(img, scaled_width, scaled_height);
+                image::imageops::crop_imm(
+                    &scaled,
+                    (scaled_width - width) / 2,
+                    (scaled_height - height) / 2,
+                    width,
+                    height,
+                )
+                .to_image()
+            }
+        };
+
+        let channels = match config.channel_order {
+            ChannelOrder::Rgb => [0, 1, 2],
+            ChannelOrder::Bgr => [2, 1, 0],
+        };
+        let value = |pixel: &Rgb<u8>, channel: usize| {
+            (pixel[channels[channel]] as f32 / 255.0 - config.mean[channel]) / config.std[channel]
+        };
+        let mut tensor = Vec::with_capacity(3 * width as usize * height as usize);
+        match config.layout {
+            TensorLayout::Nchw => {
+                for channel in 0..3 {
+                    tensor.extend(fitted.pixels().map(|pixel| value(pixel, channel)));
+                }
+            }
+            TensorLayout::Nhwc => {
+                for pixel in fitted.pixels() {
+                    tensor.extend((0..3).map(|channel| value(pixel, channel)));
+                }
+            }
+        }
+        tensor
+    }
+
+    /// Load the image at `path` and preprocess it into the input tensor of the
+    /// ONNX model at `model_path`, checking it fits the model's first input
+    pub async fn preprocess_for_model(
+        path: &Path,
+        model_path: &Path,
+        config: &ImagePreprocessConfig,
+    ) -> Result<Vec<f32>> {
+        config.validate()?;
+        let model = model_path.to_path_buf();
+        let graph = tokio::task::spawn_blocking(move || onnx_graph::read_graph_info(&model))
+            .await?
+            .with_context(|| format!("Cannot read ONNX model {}", model_path.display()))?;
+        let input = graph
+            .inputs
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("Model {} has no inputs", model_path.display()))?;
+        config.check_input(input)?;
+
+        let img = load_image(path)
+            .await
+            .with_context(|| format!("Cannot load image {}", path.display()))?;
+        Ok(preprocess(&img, config))
+    }
 }
 
 pub mod audio {
@@ -118,16 +534,27 @@ pub mod audio {
     use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
     use std::path::Path;
 
+    /// Samples of the WAV file at `path` scaled to -1.0..=1.0, interleaved by channel
     pub async fn load_wav_file(path: &Path) -> Result<(Vec<f32>, WavSpec)> {
-        let mut reader = WavReader::open(path)?;
+        let mut reader = WavReader::open(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read WAV file {}: {}", path.display(), e))?;
         let spec = reader.spec();
 
-        let samples: Result<Vec<f32>, _> = match spec.sample_format {
-            SampleFormat::Float => reader.samples::<f32>().collect(),
-            SampleFormat::Int => reader
-                .samples::<i32>()
-                .map(|s| s.map(|s| s as f32 / i32::MAX as f32))
-                .collect(),
+        let samples: Result<Vec<f32>, _> = match (spec.sample_format, spec.bits_per_sample) {
+            (SampleFormat::Float, 32) => reader.samples::<f32>().collect(),
+            (SampleFormat::Int, bits @ 1..=32) => {
+                let scale = (1u64 << (bits - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 / scale))
+                    .collect()
+            }
+            (format, bits) => anyhow::bail!(
+                "Unsupported WAV sample format in {}: {}-bit {:?}",
+                path.display(),
+                bits,
+                format
+            ),
         };
 
         let samples = samples?;
@@ -288,6 +715,73 @@ mod tests {
         assert_eq!(test_content, read_content);
     }
 
+    #[tokio::test]
+    async fn test_text_io_with_encoding() {
+        let temp_dir = tempdir().unwrap();
+        let content = "Crème brûlée, naïve café";
+
+        for label in ["latin1", "utf-16le", "utf-16be"] {
+            let encoding = text::TextEncoding::for_label(label).unwrap();
+            let file_path = temp_dir.path().join(format!("{}.txt", label));
+            text::write_text_file_with_encoding(&file_path, content, encoding)
+                .await
+                .unwrap();
+
+            let raw = tokio::fs::read(&file_path).await.unwrap();
+            assert!(String::from_utf8(raw).is_err(), "{} wrote UTF-8", label);
+
+            let read_content = text::read_text_file_with_encoding(&file_path, encoding)
+                .await
+                .unwrap();
+            assert_eq!(content, read_content);
+        }
+    }
+
+    #[test]
+    fn test_text_decode_strict_and_lossy() {
+        let invalid = b"caf\xc3 ok";
+        let strict = text::TextEncoding::default();
+        assert!(strict.decode(invalid).is_err());
+
+        let lossy = strict.with_mode(text::DecodeMode::Lossy);
+        assert_eq!(lossy.decode(invalid).unwrap(), "caf\u{FFFD} ok");
+
+        // An unpaired surrogate is invalid UTF-16
+        let utf16 = text::TextEncoding::for_label("utf-16le").unwrap();
+        assert!(utf16.decode(&[0x00, 0xD8, 0x41, 0x00]).is_err());
+        assert_eq!(
+            utf16
+                .with_mode(text::DecodeMode::Lossy)
+                .decode(&[0x00, 0xD8, 0x41, 0x00])
+                .unwrap(),
+            "\u{FFFD}A"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_atomic_write_keeps_original_on_failure() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("results.json");
+        atomic::write_file_async(&file_path, "original")
+            .await
+            .unwrap();
+
+        let result = atomic::write_file_with(&file_path, |file| {
+            use std::io::Write;
+            file.write_all(b"{\"partial\":")?;
+            anyhow::bail!("crashed before rename")
+        });
+        assert!(result.is_err());
+
+        let content = std::fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "original");
+        let leftovers = std::fs::read_dir(temp_dir.path()).unwrap().count();
+        assert_eq!(leftovers, 1, "temporary file was not cleaned up");
+
+        atomic::write_file(&file_path, "replaced").unwrap();
+        assert_eq!(std::fs::read_to_string(&file_path).unwrap(), "replaced");
+    }
+
     #[tokio::test]
     async fn test_json_io() {
         use serde_json::json;
@@ -307,6 +801,61 @@ mod tests {
         assert_eq!(test_data, read_data);
     }
 
+    #[tokio::test]
+    async fn test_image_preprocessing_produces_model_input() {
+        use crate::models::onnx_graph::{TensorDim, TensorSpec};
+        use super::image::{ImagePreprocessConfig, TensorLayout};
+
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("red.png");
+        // A wide solid red image, cropped to a square
+        let red = ::image::ImageBuffer::from_pixel(64, 32, ::image::Rgb([255u8, 0, 0]));
+        image::save_image(&file_path, &red, ::image::ImageFormat::Png)
+            .await
+            .unwrap();
+
+        let config = ImagePreprocessConfig {
+            width: 16,
+            height: 16,
+            mean: [0.5; 3],
+            std: [0.5; 3],
+            ..Default::default()
+        };
+        let img = image::load_image(&file_path).await.unwrap();
+        let tensor = image::preprocess(&img, &config);
+        assert_eq!(tensor.len(), 3 * 16 * 16);
+        assert!(tensor.iter().all(|v| (-1.0..=1.0).contains(v)));
+        // Planar: all of red, then green, then blue
+        assert!(tensor[..256].iter().all(|&v| (v - 1.0).abs() < 1e-3));
+        assert!(tensor[256..].iter().all(|&v| (v + 1.0).abs() < 1e-3));
+
+        let bgr_hwc = ImagePreprocessConfig {
+            channel_order: image::ChannelOrder::Bgr,
+            layout: TensorLayout::Nhwc,
+            ..config.clone()
+        };
+        let tensor = image::preprocess(&img, &bgr_hwc);
+        assert_eq!(tensor.len(), 16 * 16 * 3);
+        assert!((tensor[0] + 1.0).abs() < 1e-3 && (tensor[2] - 1.0).abs() < 1e-3);
+
+        let input = TensorSpec {
+            name: "pixel_values".to_string(),
+            dtype: "float32".to_string(),
+            shape: Some(vec![
+                TensorDim::Symbolic("batch".to_string()),
+                TensorDim::Fixed(3),
+                TensorDim::Fixed(16),
+                TensorDim::Fixed(16),
+            ]),
+        };
+        config.check_input(&input).unwrap();
+        let err = bgr_hwc.check_input(&input).unwrap_err();
+        assert!(err.to_string().contains("[batch, 3, 16, 16]"), "{}", err);
+        let default = ImagePreprocessConfig::default();
+        assert_eq!(default.shape(), [1, 3, 224, 224]);
+        assert!(default.check_input(&input).is_err());
+    }
+
     #[test]
     fn test_format_display() {
         assert_eq!(InputFormat::Text.to_string(), "text");
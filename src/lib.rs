@@ -112,6 +112,7 @@ pub mod tui;
 pub mod audit;
 pub mod batch;
 pub mod cache;
+pub mod logging;
 pub mod logging_audit;
 pub mod metrics;
 pub mod monitoring;
@@ -119,9 +120,11 @@ pub mod observability;
 pub mod response_cache;
 
 // === Enterprise & Management (kept at root for now) ===
+pub mod data_pipeline;
 pub mod deployment;
 pub mod distributed;
 pub mod model_versioning;
+pub mod multi_tenancy;
 pub mod resilience;
 pub mod upgrade;
 pub mod versioning;
@@ -173,9 +176,15 @@ pub enum InfernoError {
     #[error("Resource error: {0}")]
     Resource(String),
 
+    #[error("GPU initialization failed: {0}")]
+    GpuInit(String),
+
     #[error("Timeout error: {0}")]
     Timeout(String),
 
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
     #[error("Concurrency error: {0}")]
     Concurrency(String),
 
@@ -237,16 +246,10 @@ pub type Result<T> = std::result::Result<T, InfernoError>;
 /// Initialize the Inferno platform with comprehensive logging and tracing
 pub fn init_platform() -> Result<()> {
     // Initialize tracing subscriber with environment filter
-    let subscriber = tracing_subscriber::FmtSubscriber::builder()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .with_target(false)
-        .with_thread_ids(true)
-        .with_file(true)
-        .with_line_number(true)
-        .finish();
-
-    tracing::subscriber::set_global_default(subscriber)
-        .map_err(|e| InfernoError::Unknown(format!("Failed to initialize tracing: {}", e)))?;
+    logging::init(
+        logging::LogFormat::default(),
+        tracing_subscriber::EnvFilter::from_default_env(),
+    )?;
 
     tracing::info!("🔥 Inferno platform initialized");
     Ok(())
@@ -258,6 +261,9 @@ pub struct PlatformInfo {
     pub backends: Vec<String>,
     pub features: Vec<String>,
     pub interfaces: Vec<String>,
+    /// Why models run on the CPU although the GPU was enabled, once a model
+    /// load has fallen back to it
+    pub gpu_fallback: Option<String>,
 }
 
 impl Default for PlatformInfo {
@@ -303,6 +309,7 @@ impl PlatformInfo {
             backends,
             features,
             interfaces,
+            gpu_fallback: crate::backends::gpu_fallback(),
         }
     }
 }
@@ -313,6 +320,9 @@ impl fmt::Display for PlatformInfo {
         writeln!(f, "   Backends: {}", self.backends.join(", "))?;
         writeln!(f, "   Features: {}", self.features.join(", "))?;
         writeln!(f, "   Interfaces: {}", self.interfaces.join(", "))?;
+        if let Some(reason) = &self.gpu_fallback {
+            writeln!(f, "   Device: CPU (GPU unavailable: {})", reason)?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,118 @@
+//! Log output
+//!
+//! Logs are written in the format set by `log_format` in the config or the global
+//! `--log-format` flag:
+//!
+//! - `pretty`: human-readable lines with the source location (the default)
+//! - `compact`: shorter human-readable lines
+//! - `json`: one object per line with `timestamp`, `level`, `target`, `message`,
+//!   the event's own fields, and the fields of the span it happened in under
+//!   `span`, such as the `request_id` of an API request, for log aggregators
+
+pub use crate::core::config::LogFormat;
+use crate::{InfernoError, Result};
+use tracing::Subscriber;
+use tracing_subscriber::{EnvFilter, fmt, fmt::MakeWriter};
+
+/// Subscriber writing the events `filter` lets through to `writer` in `format`
+pub fn subscriber<W>(
+    format: LogFormat,
+    filter: EnvFilter,
+    writer: W,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = fmt::Subscriber::builder()
+        .with_env_filter(filter)
+        .with_writer(writer);
+    match format {
+        LogFormat::Json => Box::new(
+            builder
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(false)
+                .finish(),
+        ),
+        LogFormat::Compact => Box::new(
+            builder
+                .compact()
+                .with_target(false)
+                .with_thread_ids(true)
+                .finish(),
+        ),
+        LogFormat::Pretty => Box::new(
+            builder
+                .with_target(false)
+                .with_thread_ids(true)
+                .with_file(true)
+                .with_line_number(true)
+                .finish(),
+        ),
+    }
+}
+
+/// Install a subscriber writing to stderr as the global default
+pub fn init(format: LogFormat, filter: EnvFilter) -> Result<()> {
+    tracing::subscriber::set_global_default(subscriber(format, filter, std::io::stderr))
+        .map_err(|e| InfernoError::Unknown(format!("Failed to initialize tracing: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Collects everything written to it
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Captured {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_json_format_includes_span_fields() {
+        let output = Captured::default();
+        let subscriber = subscriber(LogFormat::Json, EnvFilter::new("info"), output.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", request_id = "req-42");
+            let _entered = span.enter();
+            tracing::info!(model = "llama", "Serving completion");
+            tracing::debug!("Filtered out");
+        });
+
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1, "{}", output);
+
+        let event = &lines[0];
+        for key in ["timestamp", "level", "target", "message"] {
+            assert!(event.get(key).is_some(), "missing {} in {}", key, event);
+        }
+        assert_eq!(event["level"], "INFO");
+        assert_eq!(event["message"], "Serving completion");
+        assert_eq!(event["model"], "llama");
+        assert_eq!(event["span"]["name"], "request");
+        assert_eq!(event["span"]["request_id"], "req-42");
+    }
+}
@@ -27,6 +27,9 @@ pub struct AuditConfig {
     pub storage_path: String,
     pub max_file_size: u64,
     pub rotation_interval: String,
+    /// Chain audit entries together so `inferno audit verify` can detect edits
+    #[serde(default)]
+    pub hash_chain: bool,
 }
 
 impl Default for AuditConfig {
@@ -35,6 +38,7 @@ impl Default for AuditConfig {
             storage_path: "logs/audit".to_string(),
             max_file_size: 100 * 1024 * 1024, // 100MB
             rotation_interval: "daily".to_string(),
+            hash_chain: false,
         }
     }
 }
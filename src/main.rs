@@ -2,6 +2,7 @@ use anyhow::Result;
 use inferno::{
     cli::{Commands, enhanced_parser::EnhancedCliParser, help::HelpSystem},
     config::Config,
+    logging::{self, LogFormat},
     upgrade::{
         ApplicationVersion, background_service::BackgroundUpdateService, init_upgrade_system,
     },
@@ -9,7 +10,7 @@ use inferno::{
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use tracing::{error, info, warn};
-use tracing_subscriber::{EnvFilter, fmt};
+use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -39,7 +40,15 @@ async fn main() -> Result<()> {
         Config::default()
     });
 
-    setup_logging();
+    let log_format = cli.log_format.unwrap_or_else(|| {
+        config.log_format.parse().unwrap_or_else(|e| {
+            eprintln!("Warning: {}", e);
+            LogFormat::default()
+        })
+    });
+    setup_logging(log_format);
+    inferno::backends::blocking::configure(config.backend_config.blocking_threads);
+    inferno::backends::memory::configure(config.backend_config.memory_limit_mb);
     info!(
         "Starting Inferno AI/ML model runner v{}",
         std::env::var("CARGO_PKG_VERSION").unwrap_or_else(|_| "0.1.0".to_string())
@@ -176,20 +185,11 @@ async fn init_background_update_service(config: &Config) -> Result<BackgroundUpd
 }
 
 /// Set up comprehensive logging and tracing
-fn setup_logging() {
+fn setup_logging(format: LogFormat) {
     // Create a subscriber with environment filter support
-    let subscriber = fmt::Subscriber::builder()
-        .with_env_filter(
-            EnvFilter::from_default_env()
-                .add_directive("inferno=info".parse().unwrap())
-                .add_directive("warn".parse().unwrap()),
-        )
-        .with_target(false)
-        .with_thread_ids(true)
-        .with_file(true)
-        .with_line_number(true)
-        .finish();
-
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("Failed to initialize tracing subscriber");
+    let filter = EnvFilter::from_default_env()
+        .add_directive("inferno=info".parse().unwrap())
+        .add_directive("warn".parse().unwrap());
+
+    logging::init(format, filter).expect("Failed to initialize tracing subscriber");
 }
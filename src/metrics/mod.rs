@@ -1,9 +1,13 @@
+pub mod statsd;
+pub mod usage;
+
+use crate::backends::ComputeDevice;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     sync::{
-        Arc, RwLock,
+        Arc, Mutex, RwLock,
         atomic::{AtomicU64, Ordering},
     },
     time::{Duration, Instant, SystemTime, UNIX_EPOCH},
@@ -34,11 +38,21 @@ pub struct InferenceMetrics {
     pub total_inference_time_ms: u64,
     pub average_tokens_per_second: f64,
     pub average_latency_ms: f64,
+    /// Estimated from the latency histogram; `None` before any inference
+    #[serde(default)]
+    pub p95_latency_ms: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemMetrics {
     pub memory_usage_bytes: u64,
+    /// Resident memory of this process
+    #[serde(default)]
+    pub process_resident_bytes: u64,
+    /// Model weights mapped from their files; only the resident part of these
+    /// counts towards `process_resident_bytes`
+    #[serde(default)]
+    pub model_mapped_bytes: u64,
     pub cpu_usage_percent: f32,
     pub gpu_memory_usage_bytes: Option<u64>,
     pub gpu_utilization_percent: Option<f32>,
@@ -59,12 +73,230 @@ pub struct ModelStats {
     pub inference_count: u64,
     pub total_inference_time_ms: u64,
     pub backend_type: String,
+    /// `cpu` or `gpu`; `cpu` for a model meant for the GPU whose load fell back
+    #[serde(default)]
+    pub device: Option<String>,
+}
+
+/// Default latency histogram bucket bounds, in seconds
+pub const DEFAULT_LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Inference latency histogram with fixed bucket bounds in seconds
+///
+/// Exported in the Prometheus histogram format, so p50/p95/p99 can be computed
+/// with `histogram_quantile`.
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    bounds: Vec<f64>,
+    /// Observations per bucket, plus a final bucket for those above every bound
+    counts: Vec<AtomicU64>,
+    sum_micros: AtomicU64,
+}
+
+impl LatencyHistogram {
+    /// Non-finite bounds are ignored; the rest are sorted and deduplicated
+    pub fn new(bounds: &[f64]) -> Self {
+        let mut bounds: Vec<f64> = bounds.iter().copied().filter(|b| b.is_finite()).collect();
+        bounds.sort_by(f64::total_cmp);
+        bounds.dedup();
+
+        Self {
+            counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            bounds,
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = self.bounds.partition_point(|&bound| bound < seconds);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        for count in &self.counts {
+            count.store(0, Ordering::Relaxed);
+        }
+        self.sum_micros.store(0, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+    }
+
+    pub fn sum_seconds(&self) -> f64 {
+        self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+    }
+
+    /// Estimate the `q` quantile in seconds the way Prometheus' `histogram_quantile`
+    /// does, interpolating linearly within the bucket it falls in
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let buckets = self.cumulative_counts();
+        let total = buckets.last()?.1;
+        if total == 0 {
+            return None;
+        }
+
+        let rank = q.clamp(0.0, 1.0) * total as f64;
+        let mut lower = 0.0;
+        let mut below = 0;
+        for (upper, count) in buckets {
+            if count as f64 >= rank && count > below {
+                if upper.is_infinite() {
+                    // Above every bound; the highest bound is the best estimate
+                    return Some(lower);
+                }
+                let fraction = (rank - below as f64) / (count - below) as f64;
+                return Some(lower + (upper - lower) * fraction);
+            }
+            lower = upper;
+            below = count;
+        }
+        Some(lower)
+    }
+
+    /// Observations at or below each bound, ending with the `+Inf` bucket
+    pub fn cumulative_counts(&self) -> Vec<(f64, u64)> {
+        let mut total = 0;
+        self.bounds
+            .iter()
+            .copied()
+            .chain([f64::INFINITY])
+            .zip(&self.counts)
+            .map(|(bound, count)| {
+                total += count.load(Ordering::Relaxed);
+                (bound, total)
+            })
+            .collect()
+    }
+
+    /// Append the `_bucket`, `_sum` and `_count` series; `labels` is either empty
+    /// or a comma-terminated list such as `model="a",`
+    fn write_prometheus(&self, output: &mut String, name: &str, labels: &str) {
+        write_histogram(
+            output,
+            name,
+            labels,
+            &self.cumulative_counts(),
+            self.sum_seconds(),
+        );
+    }
+}
+
+/// Default token count histogram bucket bounds
+pub const DEFAULT_TOKEN_BUCKETS: [u64; 8] = [16, 32, 64, 128, 256, 512, 1024, 4096];
+
+/// Histogram of per-request token counts, such as prompt or completion length
+#[derive(Debug)]
+pub struct TokenHistogram {
+    bounds: Vec<u64>,
+    /// Observations per bucket, plus a final bucket for those above every bound
+    counts: Vec<AtomicU64>,
+    sum: AtomicU64,
+}
+
+impl TokenHistogram {
+    /// Bounds are sorted and deduplicated
+    pub fn new(bounds: &[u64]) -> Self {
+        let mut bounds = bounds.to_vec();
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        Self {
+            counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            bounds,
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, tokens: u64) {
+        let bucket = self.bounds.partition_point(|&bound| bound < tokens);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(tokens, Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        for count in &self.counts {
+            count.store(0, Ordering::Relaxed);
+        }
+        self.sum.store(0, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+    }
+
+    pub fn sum(&self) -> u64 {
+        self.sum.load(Ordering::Relaxed)
+    }
+
+    /// Observations at or below each bound, ending with the `+Inf` bucket
+    pub fn cumulative_counts(&self) -> Vec<(f64, u64)> {
+        let mut total = 0;
+        self.bounds
+            .iter()
+            .map(|&bound| bound as f64)
+            .chain([f64::INFINITY])
+            .zip(&self.counts)
+            .map(|(bound, count)| {
+                total += count.load(Ordering::Relaxed);
+                (bound, total)
+            })
+            .collect()
+    }
+
+    fn write_prometheus(&self, output: &mut String, name: &str) {
+        write_histogram(
+            output,
+            name,
+            "",
+            &self.cumulative_counts(),
+            self.sum() as f64,
+        );
+    }
+}
+
+/// Append the `_bucket`, `_sum` and `_count` series of a histogram with the
+/// given cumulative bucket counts
+fn write_histogram(
+    output: &mut String,
+    name: &str,
+    labels: &str,
+    buckets: &[(f64, u64)],
+    sum: f64,
+) {
+    for &(bound, count) in buckets {
+        let le = if bound.is_infinite() {
+            "+Inf".to_string()
+        } else {
+            bound.to_string()
+        };
+        output.push_str(&format!(
+            "{}_bucket{{{}le=\"{}\"}} {}\n",
+            name, labels, le, count
+        ));
+    }
+    let count = buckets.last().map_or(0, |&(_, count)| count);
+    let labels = labels.trim_end_matches(',');
+    let labels = if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels)
+    };
+    output.push_str(&format!("{}_sum{} {}\n", name, labels, sum));
+    output.push_str(&format!("{}_count{} {}\n", name, labels, count));
 }
 
 #[derive(Debug)]
 pub struct InferenceEvent {
     pub model_name: String,
+    /// Prompt length in tokens
     pub input_length: u32,
+    /// Completion length in tokens
     pub output_length: u32,
     pub duration: Duration,
     pub success: bool,
@@ -78,6 +310,12 @@ pub struct MetricsEventProcessor {
     receiver: mpsc::UnboundedReceiver<InferenceEvent>,
     counters: Arc<InferenceCounters>,
     model_stats: Arc<RwLock<HashMap<String, ModelStats>>>,
+    latency: Arc<LatencyHistogram>,
+    model_latency: Arc<RwLock<HashMap<String, LatencyHistogram>>>,
+    latency_buckets: Vec<f64>,
+    prompt_tokens: Arc<TokenHistogram>,
+    completion_tokens: Arc<TokenHistogram>,
+    update_lock: Arc<Mutex<()>>,
 }
 
 impl MetricsEventProcessor {
@@ -85,6 +323,9 @@ impl MetricsEventProcessor {
     pub fn start(mut self) {
         tokio::spawn(async move {
             while let Some(event) = self.receiver.recv().await {
+                // Apply the whole event before or after a reset, never across one
+                let _update = self.update_lock.lock().unwrap_or_else(|e| e.into_inner());
+
                 // Update global counters
                 self.counters.total_requests.fetch_add(1, Ordering::Relaxed);
 
@@ -105,6 +346,16 @@ impl MetricsEventProcessor {
                     .total_inference_time_ms
                     .fetch_add(event.duration.as_millis() as u64, Ordering::Relaxed);
 
+                self.latency.observe(event.duration);
+                if let Ok(mut histograms) = self.model_latency.write() {
+                    histograms
+                        .entry(event.model_name.clone())
+                        .or_insert_with(|| LatencyHistogram::new(&self.latency_buckets))
+                        .observe(event.duration);
+                }
+                self.prompt_tokens.observe(event.input_length as u64);
+                self.completion_tokens.observe(event.output_length as u64);
+
                 // Update model-specific stats
                 if let Ok(mut stats) = self.model_stats.write() {
                     let model_stat = stats.entry(event.model_name.clone()).or_insert_with(|| {
@@ -115,6 +366,7 @@ impl MetricsEventProcessor {
                             inference_count: 0,
                             total_inference_time_ms: 0,
                             backend_type: "unknown".to_string(),
+                            device: None,
                         }
                     });
 
@@ -146,6 +398,12 @@ pub struct MetricsCollector {
     generic_counters: Arc<RwLock<HashMap<String, AtomicU64>>>,
     /// Generic gauges for custom metrics (e.g., duration measurements)
     generic_gauges: Arc<RwLock<HashMap<String, f64>>>,
+    latency: Arc<LatencyHistogram>,
+    model_latency: Arc<RwLock<HashMap<String, LatencyHistogram>>>,
+    prompt_tokens: Arc<TokenHistogram>,
+    completion_tokens: Arc<TokenHistogram>,
+    /// Held while an event is applied, and by [`MetricsCollector::reset`]
+    update_lock: Arc<Mutex<()>>,
 }
 
 #[derive(Debug)]
@@ -184,9 +442,19 @@ impl MetricsCollector {
     /// // Use collector.record_inference(...) from any thread
     /// ```
     pub fn new() -> (Self, MetricsEventProcessor) {
+        Self::with_latency_buckets(&DEFAULT_LATENCY_BUCKETS)
+    }
+
+    /// Create a collector whose latency histograms use `buckets`, in seconds
+    pub fn with_latency_buckets(buckets: &[f64]) -> (Self, MetricsEventProcessor) {
         let (event_sender, event_receiver) = mpsc::unbounded_channel();
         let inference_counters = Arc::new(InferenceCounters::default());
         let model_stats = Arc::new(RwLock::new(HashMap::new()));
+        let latency = Arc::new(LatencyHistogram::new(buckets));
+        let model_latency = Arc::new(RwLock::new(HashMap::new()));
+        let prompt_tokens = Arc::new(TokenHistogram::new(&DEFAULT_TOKEN_BUCKETS));
+        let completion_tokens = Arc::new(TokenHistogram::new(&DEFAULT_TOKEN_BUCKETS));
+        let update_lock = Arc::new(Mutex::new(()));
 
         let collector = Self {
             start_time: Instant::now(),
@@ -195,12 +463,23 @@ impl MetricsCollector {
             event_sender,
             generic_counters: Arc::new(RwLock::new(HashMap::new())),
             generic_gauges: Arc::new(RwLock::new(HashMap::new())),
+            latency: Arc::clone(&latency),
+            model_latency: Arc::clone(&model_latency),
+            prompt_tokens: Arc::clone(&prompt_tokens),
+            completion_tokens: Arc::clone(&completion_tokens),
+            update_lock: Arc::clone(&update_lock),
         };
 
         let processor = MetricsEventProcessor {
             receiver: event_receiver,
             counters: inference_counters,
             model_stats,
+            latency,
+            model_latency,
+            latency_buckets: buckets.to_vec(),
+            prompt_tokens,
+            completion_tokens,
+            update_lock,
         };
 
         (collector, processor)
@@ -227,17 +506,72 @@ impl MetricsCollector {
                     inference_count: 0,
                     total_inference_time_ms: 0,
                     backend_type,
+                    device: None,
                 },
             );
         }
     }
 
+    /// Record where a loaded model runs, once [`record_model_loaded`](Self::record_model_loaded)
+    /// has listed it
+    pub fn record_model_device(&self, name: &str, device: ComputeDevice) {
+        if let Ok(mut stats) = self.model_stats.write()
+            && let Some(stats) = stats.get_mut(name)
+        {
+            stats.device = Some(device.to_string());
+        }
+    }
+
     pub fn record_inference(&self, event: InferenceEvent) {
         if self.event_sender.send(event).is_err() {
             tracing::warn!("Failed to send inference event - metrics collector may be shutdown");
         }
     }
 
+    /// Zero every counter, gauge and histogram, e.g. between benchmark runs
+    ///
+    /// Loaded models stay listed with their size and load time, but their inference
+    /// counts are cleared and models only seen through inference events are dropped.
+    /// Events being recorded concurrently land entirely before or after the reset.
+    pub fn reset(&self) {
+        let _update = self.update_lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        let counters = &self.inference_counters;
+        for counter in [
+            &counters.total_requests,
+            &counters.successful_requests,
+            &counters.failed_requests,
+            &counters.total_tokens_generated,
+            &counters.total_inference_time_ms,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+
+        self.latency.reset();
+        if let Ok(mut histograms) = self.model_latency.write() {
+            histograms.clear();
+        }
+        self.prompt_tokens.reset();
+        self.completion_tokens.reset();
+
+        if let Ok(mut stats) = self.model_stats.write() {
+            stats.retain(|_, stats| stats.size_bytes > 0);
+            for stats in stats.values_mut() {
+                stats.inference_count = 0;
+                stats.total_inference_time_ms = 0;
+            }
+        }
+
+        if let Ok(mut counters) = self.generic_counters.write() {
+            counters.clear();
+        }
+        if let Ok(mut gauges) = self.generic_gauges.write() {
+            gauges.clear();
+        }
+
+        info!("Metrics reset");
+    }
+
     /// Increment a named counter by 1
     ///
     /// Counters are useful for tracking totals like command executions,
@@ -354,6 +688,7 @@ impl MetricsCollector {
             total_inference_time_ms: total_time_ms,
             average_tokens_per_second,
             average_latency_ms,
+            p95_latency_ms: self.latency.quantile(0.95).map(|secs| secs * 1000.0),
         }
     }
 
@@ -373,6 +708,8 @@ impl MetricsCollector {
 
         Ok(SystemMetrics {
             memory_usage_bytes,
+            process_resident_bytes: crate::backends::memory::resident_bytes(),
+            model_mapped_bytes: crate::backends::mmap::mapped_bytes(),
             cpu_usage_percent,
             gpu_memory_usage_bytes,
             gpu_utilization_percent,
@@ -464,6 +801,25 @@ impl MetricsCollector {
             snapshot.inference_metrics.average_latency_ms
         ));
 
+        output.push_str("# HELP inferno_inference_latency_seconds Inference latency in seconds\n");
+        output.push_str("# TYPE inferno_inference_latency_seconds histogram\n");
+        self.latency
+            .write_prometheus(&mut output, "inferno_inference_latency_seconds", "");
+
+        output.push_str(
+            "# HELP inferno_prompt_tokens Prompt length of inference requests in tokens\n",
+        );
+        output.push_str("# TYPE inferno_prompt_tokens histogram\n");
+        self.prompt_tokens
+            .write_prometheus(&mut output, "inferno_prompt_tokens");
+
+        output.push_str(
+            "# HELP inferno_completion_tokens Completion length of inference requests in tokens\n",
+        );
+        output.push_str("# TYPE inferno_completion_tokens histogram\n");
+        self.completion_tokens
+            .write_prometheus(&mut output, "inferno_completion_tokens");
+
         // System metrics
         output.push_str("# HELP inferno_memory_usage_bytes Memory usage in bytes\n");
         output.push_str("# TYPE inferno_memory_usage_bytes gauge\n");
@@ -472,6 +828,24 @@ impl MetricsCollector {
             snapshot.system_metrics.memory_usage_bytes
         ));
 
+        output.push_str(
+            "# HELP inferno_process_resident_bytes Resident memory of the inferno process\n",
+        );
+        output.push_str("# TYPE inferno_process_resident_bytes gauge\n");
+        output.push_str(&format!(
+            "inferno_process_resident_bytes {}\n",
+            snapshot.system_metrics.process_resident_bytes
+        ));
+
+        output.push_str(
+            "# HELP inferno_model_mapped_bytes Model weights memory-mapped from their files\n",
+        );
+        output.push_str("# TYPE inferno_model_mapped_bytes gauge\n");
+        output.push_str(&format!(
+            "inferno_model_mapped_bytes {}\n",
+            snapshot.system_metrics.model_mapped_bytes
+        ));
+
         output.push_str("# HELP inferno_cpu_usage_percent CPU usage percentage\n");
         output.push_str("# TYPE inferno_cpu_usage_percent gauge\n");
         output.push_str(&format!(
@@ -542,6 +916,16 @@ impl MetricsCollector {
                 safe_model_name, stats.backend_type, stats.load_time_ms
             ));
 
+            if let Some(device) = &stats.device {
+                output
+                    .push_str("# HELP inferno_model_device Device the model runs on, as a label\n");
+                output.push_str("# TYPE inferno_model_device gauge\n");
+                output.push_str(&format!(
+                    "inferno_model_device{{model=\"{}\",backend=\"{}\",device=\"{}\"}} 1\n",
+                    safe_model_name, stats.backend_type, device
+                ));
+            }
+
             output.push_str("# HELP inferno_model_inference_duration_ms_total Total inference time per model in milliseconds\n");
             output.push_str("# TYPE inferno_model_inference_duration_ms_total counter\n");
             output.push_str(&format!(
@@ -550,6 +934,31 @@ impl MetricsCollector {
             ));
         }
 
+        if let Ok(histograms) = self.model_latency.read()
+            && !histograms.is_empty()
+        {
+            output.push_str("# HELP inferno_model_inference_latency_seconds Inference latency per model in seconds\n");
+            output.push_str("# TYPE inferno_model_inference_latency_seconds histogram\n");
+            let mut models: Vec<_> = histograms.iter().collect();
+            models.sort_by_key(|(name, _)| name.as_str());
+            for (model_name, histogram) in models {
+                let backend = snapshot
+                    .model_metrics
+                    .loaded_models
+                    .get(model_name)
+                    .map_or("unknown", |stats| stats.backend_type.as_str());
+                histogram.write_prometheus(
+                    &mut output,
+                    "inferno_model_inference_latency_seconds",
+                    &format!(
+                        "model=\"{}\",backend=\"{}\",",
+                        model_name.replace("\"", "\\\""),
+                        backend
+                    ),
+                );
+            }
+        }
+
         // Custom counters
         if !snapshot.custom_counters.is_empty() {
             output.push_str("\n# Custom counters\n");
@@ -700,4 +1109,221 @@ mod tests {
         assert!(prometheus_export.contains("inferno_command_duration_ms"));
         assert!(prometheus_export.contains("# TYPE inferno_command_duration_ms gauge"));
     }
+
+    #[tokio::test]
+    async fn test_latency_histogram_export() {
+        let (collector, processor) = MetricsCollector::with_latency_buckets(&[0.01, 0.1, 1.0]);
+        processor.start();
+
+        collector.record_model_loaded(
+            "fast".to_string(),
+            1024,
+            Duration::from_millis(10),
+            "gguf".to_string(),
+        );
+        for (model_name, millis) in [("fast", 3), ("fast", 20), ("slow", 200), ("slow", 2000)] {
+            collector.record_inference(InferenceEvent {
+                model_name: model_name.to_string(),
+                input_length: 10,
+                output_length: 10,
+                duration: Duration::from_millis(millis),
+                success: true,
+            });
+        }
+        sleep(Duration::from_millis(10)).await;
+
+        let export = collector.export_prometheus_format().await.unwrap();
+        for line in [
+            "# TYPE inferno_inference_latency_seconds histogram",
+            "inferno_inference_latency_seconds_bucket{le=\"0.01\"} 1",
+            "inferno_inference_latency_seconds_bucket{le=\"0.1\"} 2",
+            "inferno_inference_latency_seconds_bucket{le=\"1\"} 3",
+            "inferno_inference_latency_seconds_bucket{le=\"+Inf\"} 4",
+            "inferno_inference_latency_seconds_sum 2.223",
+            "inferno_inference_latency_seconds_count 4",
+            "inferno_model_inference_latency_seconds_bucket{model=\"fast\",backend=\"gguf\",le=\"0.1\"} 2",
+            "inferno_model_inference_latency_seconds_bucket{model=\"slow\",backend=\"unknown\",le=\"1\"} 1",
+            "inferno_model_inference_latency_seconds_count{model=\"slow\",backend=\"unknown\"} 2",
+        ] {
+            assert!(
+                export.lines().any(|l| l == line),
+                "missing `{}` in:\n{}",
+                line,
+                export
+            );
+        }
+        // The average gauge is still exported
+        assert!(export.contains("# TYPE inferno_latency_ms gauge"));
+
+        // p95 falls in the +Inf bucket, so it is capped at the highest bound
+        let snapshot = collector.get_snapshot().await.unwrap();
+        assert_eq!(snapshot.inference_metrics.p95_latency_ms, Some(1000.0));
+    }
+
+    #[tokio::test]
+    async fn test_token_count_histograms() {
+        let (collector, processor) = MetricsCollector::new();
+        processor.start();
+
+        for (input_length, output_length) in [(8, 16), (16, 100), (300, 600), (5000, 2)] {
+            collector.record_inference(InferenceEvent {
+                model_name: "model".to_string(),
+                input_length,
+                output_length,
+                duration: Duration::from_millis(5),
+                success: true,
+            });
+        }
+        sleep(Duration::from_millis(10)).await;
+
+        let export = collector.export_prometheus_format().await.unwrap();
+        for line in [
+            "# TYPE inferno_prompt_tokens histogram",
+            "inferno_prompt_tokens_bucket{le=\"16\"} 2",
+            "inferno_prompt_tokens_bucket{le=\"256\"} 2",
+            "inferno_prompt_tokens_bucket{le=\"512\"} 3",
+            "inferno_prompt_tokens_bucket{le=\"4096\"} 3",
+            "inferno_prompt_tokens_bucket{le=\"+Inf\"} 4",
+            "inferno_prompt_tokens_sum 5324",
+            "inferno_prompt_tokens_count 4",
+            "# TYPE inferno_completion_tokens histogram",
+            "inferno_completion_tokens_bucket{le=\"16\"} 2",
+            "inferno_completion_tokens_bucket{le=\"64\"} 2",
+            "inferno_completion_tokens_bucket{le=\"128\"} 3",
+            "inferno_completion_tokens_bucket{le=\"1024\"} 4",
+            "inferno_completion_tokens_sum 718",
+            "inferno_completion_tokens_count 4",
+        ] {
+            assert!(
+                export.lines().any(|l| l == line),
+                "missing `{}` in:\n{}",
+                line,
+                export
+            );
+        }
+
+        collector.reset();
+        let export = collector.export_prometheus_format().await.unwrap();
+        assert!(export.lines().any(|l| l == "inferno_prompt_tokens_count 0"));
+    }
+
+    #[test]
+    fn test_latency_quantiles() {
+        let histogram = LatencyHistogram::new(&[0.1, 0.2, 0.4]);
+        assert_eq!(histogram.quantile(0.5), None);
+
+        for millis in [50, 150, 150, 150, 300] {
+            histogram.observe(Duration::from_millis(millis));
+        }
+        // Rank 2.5 of 5 falls in the (0.1, 0.2] bucket, halfway through its 3 observations
+        let median = histogram.quantile(0.5).unwrap();
+        assert!((median - 0.15).abs() < 1e-9, "{}", median);
+        let p95 = histogram.quantile(0.95).unwrap();
+        assert!((p95 - 0.35).abs() < 1e-9, "{}", p95);
+    }
+
+    #[tokio::test]
+    async fn test_reset_returns_snapshot_to_zero() {
+        let (collector, processor) = MetricsCollector::new();
+        processor.start();
+
+        collector.record_model_loaded(
+            "loaded".to_string(),
+            2048,
+            Duration::from_millis(50),
+            "gguf".to_string(),
+        );
+        for (model_name, success) in [("loaded", true), ("loaded", false), ("adhoc", true)] {
+            collector.record_inference(InferenceEvent {
+                model_name: model_name.to_string(),
+                input_length: 10,
+                output_length: 20,
+                duration: Duration::from_millis(100),
+                success,
+            });
+        }
+        collector.increment_counter("bench.runs");
+        collector.record_gauge("bench.duration_ms", 12.0);
+        sleep(Duration::from_millis(10)).await;
+        assert_eq!(
+            collector
+                .get_snapshot()
+                .await
+                .unwrap()
+                .inference_metrics
+                .total_requests,
+            3
+        );
+
+        collector.reset();
+
+        let snapshot = collector.get_snapshot().await.unwrap();
+        let inference = &snapshot.inference_metrics;
+        assert_eq!(inference.total_requests, 0);
+        assert_eq!(inference.successful_requests, 0);
+        assert_eq!(inference.failed_requests, 0);
+        assert_eq!(inference.total_tokens_generated, 0);
+        assert_eq!(inference.total_inference_time_ms, 0);
+        assert_eq!(inference.average_latency_ms, 0.0);
+        assert!(snapshot.custom_counters.is_empty());
+        assert!(snapshot.custom_gauges.is_empty());
+
+        // The loaded model stays listed with zeroed stats
+        assert_eq!(snapshot.model_metrics.loaded_models.len(), 1);
+        let loaded = &snapshot.model_metrics.loaded_models["loaded"];
+        assert_eq!(loaded.inference_count, 0);
+        assert_eq!(loaded.total_inference_time_ms, 0);
+        assert_eq!(loaded.size_bytes, 2048);
+
+        let export = collector.export_prometheus_format().await.unwrap();
+        assert!(export.contains("inferno_inference_latency_seconds_count 0"));
+        assert!(!export.contains("inferno_model_inference_latency_seconds"));
+
+        // Recording continues normally after a reset
+        collector.record_inference(InferenceEvent {
+            model_name: "loaded".to_string(),
+            input_length: 1,
+            output_length: 5,
+            duration: Duration::from_millis(10),
+            success: true,
+        });
+        sleep(Duration::from_millis(10)).await;
+        let snapshot = collector.get_snapshot().await.unwrap();
+        assert_eq!(snapshot.inference_metrics.total_requests, 1);
+        assert_eq!(snapshot.inference_metrics.total_tokens_generated, 5);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_reset_during_concurrent_recording() {
+        let (collector, processor) = MetricsCollector::new();
+        processor.start();
+
+        let recorder = {
+            let collector = collector.clone();
+            tokio::spawn(async move {
+                for _ in 0..500 {
+                    collector.record_inference(InferenceEvent {
+                        model_name: "m".to_string(),
+                        input_length: 1,
+                        output_length: 1,
+                        duration: Duration::from_millis(1),
+                        success: true,
+                    });
+                    tokio::task::yield_now().await;
+                }
+            })
+        };
+        for _ in 0..20 {
+            collector.reset();
+            tokio::task::yield_now().await;
+        }
+        recorder.await.unwrap();
+        sleep(Duration::from_millis(50)).await;
+
+        // Every event was applied whole, so the counters still agree
+        let metrics = collector.get_snapshot().await.unwrap().inference_metrics;
+        assert_eq!(metrics.total_requests, metrics.successful_requests);
+        assert_eq!(metrics.total_requests, metrics.total_tokens_generated);
+        assert_eq!(metrics.total_requests, metrics.total_inference_time_ms);
+    }
 }
@@ -0,0 +1,382 @@
+//! StatsD exporter
+//!
+//! Pushes the collector's metrics to a StatsD agent over UDP on a fixed interval.
+//! Counters are sent as the change since the previous flush, and per-model metrics
+//! carry `model` and `backend` labels using the DogStatsD tag extension
+//! (`name:value|type|#key:value`).
+
+use super::{MetricsCollector, MetricsSnapshot};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Largest datagram sent, below the typical 1500-byte MTU
+const MAX_PACKET_BYTES: usize = 1432;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsdConfig {
+    pub enabled: bool,
+    /// `host:port` of the StatsD agent
+    pub address: String,
+    /// Prepended to every metric name, separated by a dot
+    pub prefix: String,
+    pub flush_interval_secs: u64,
+    /// Tags added to every metric, as `key:value`
+    pub tags: Vec<String>,
+}
+
+impl Default for StatsdConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1:8125".to_string(),
+            prefix: "inferno".to_string(),
+            flush_interval_secs: 10,
+            tags: Vec::new(),
+        }
+    }
+}
+
+/// Counter values at the previous flush, to send deltas from
+#[derive(Debug, Default)]
+struct Previous {
+    requests: u64,
+    successful: u64,
+    failed: u64,
+    tokens: u64,
+    /// Inference count and total inference time per model
+    models: HashMap<String, (u64, u64)>,
+    custom: HashMap<String, u64>,
+}
+
+pub struct StatsdExporter {
+    config: StatsdConfig,
+    collector: MetricsCollector,
+    previous: Previous,
+}
+
+impl StatsdExporter {
+    pub fn new(config: StatsdConfig, collector: MetricsCollector) -> Self {
+        Self {
+            config,
+            collector,
+            previous: Previous::default(),
+        }
+    }
+
+    /// Flush on the configured interval until the task is aborted
+    pub fn spawn(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let socket = match UdpSocket::bind("0.0.0.0:0").await {
+                Ok(socket) => socket,
+                Err(e) => {
+                    warn!("StatsD exporter disabled, failed to bind UDP socket: {}", e);
+                    return;
+                }
+            };
+            info!(
+                "Exporting metrics to StatsD at {} every {}s",
+                self.config.address, self.config.flush_interval_secs
+            );
+
+            let period = Duration::from_secs(self.config.flush_interval_secs.max(1));
+            let mut interval = tokio::time::interval(period);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.flush(&socket).await {
+                    warn!("Failed to export metrics to StatsD: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Send the current metrics, returning the number of lines sent
+    pub async fn flush(&mut self, socket: &UdpSocket) -> Result<usize> {
+        let snapshot = self.collector.get_snapshot().await?;
+        let lines = self.render(&snapshot);
+        for packet in pack_lines(&lines) {
+            socket
+                .send_to(packet.as_bytes(), &self.config.address)
+                .await?;
+        }
+        debug!("Sent {} StatsD metrics", lines.len());
+        Ok(lines.len())
+    }
+
+    /// StatsD lines for `snapshot`, with counters relative to the previous call
+    pub fn render(&mut self, snapshot: &MetricsSnapshot) -> Vec<String> {
+        let mut lines = Vec::new();
+        let inference = &snapshot.inference_metrics;
+        let mut previous = std::mem::take(&mut self.previous);
+
+        for (name, current, last) in [
+            ("requests", inference.total_requests, &mut previous.requests),
+            (
+                "requests.successful",
+                inference.successful_requests,
+                &mut previous.successful,
+            ),
+            (
+                "requests.failed",
+                inference.failed_requests,
+                &mut previous.failed,
+            ),
+            (
+                "tokens.generated",
+                inference.total_tokens_generated,
+                &mut previous.tokens,
+            ),
+        ] {
+            let delta = counter_delta(current, last);
+            if delta > 0 {
+                lines.push(self.line(name, &delta.to_string(), "c", &[]));
+            }
+        }
+
+        let models = &snapshot.model_metrics;
+        lines.push(self.line(
+            "models.loaded",
+            &models.loaded_models.len().to_string(),
+            "g",
+            &[],
+        ));
+        lines.push(self.line(
+            "models.size_bytes",
+            &models.total_model_size_bytes.to_string(),
+            "g",
+            &[],
+        ));
+        lines.push(self.line(
+            "inference.tokens_per_second",
+            &format_value(inference.average_tokens_per_second),
+            "g",
+            &[],
+        ));
+        lines.push(self.line(
+            "system.memory_bytes",
+            &snapshot.system_metrics.memory_usage_bytes.to_string(),
+            "g",
+            &[],
+        ));
+        lines.push(self.line(
+            "system.cpu_percent",
+            &format_value(snapshot.system_metrics.cpu_usage_percent as f64),
+            "g",
+            &[],
+        ));
+
+        let mut names: Vec<_> = models.loaded_models.keys().collect();
+        names.sort();
+        for name in names {
+            let stats = &models.loaded_models[name];
+            let tags = [
+                ("model", name.as_str()),
+                ("backend", stats.backend_type.as_str()),
+            ];
+            let (last_count, last_time) = previous.models.entry(name.clone()).or_default();
+            let time_delta = counter_delta(stats.total_inference_time_ms, last_time);
+            let count_delta = counter_delta(stats.inference_count, last_count);
+            if count_delta > 0 {
+                lines.push(self.line("model.inferences", &count_delta.to_string(), "c", &tags));
+                // Mean latency over the interval, as one timing sample
+                let mean_ms = time_delta as f64 / count_delta as f64;
+                lines.push(self.line("inference.latency", &format_value(mean_ms), "ms", &tags));
+            }
+        }
+
+        let mut counters: Vec<_> = snapshot.custom_counters.iter().collect();
+        counters.sort();
+        for (name, &value) in counters {
+            let last = previous.custom.entry(name.clone()).or_default();
+            let delta = counter_delta(value, last);
+            if delta > 0 {
+                lines.push(self.line(name, &delta.to_string(), "c", &[]));
+            }
+        }
+        let mut gauges: Vec<_> = snapshot.custom_gauges.iter().collect();
+        gauges.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, &value) in gauges {
+            lines.push(self.line(name, &format_value(value), "g", &[]));
+        }
+
+        self.previous = previous;
+        lines
+    }
+
+    fn line(&self, name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) -> String {
+        let mut line = String::new();
+        if !self.config.prefix.is_empty() {
+            line.push_str(&sanitize_name(&self.config.prefix));
+            line.push('.');
+        }
+        line.push_str(&sanitize_name(name));
+        line.push(':');
+        line.push_str(value);
+        line.push('|');
+        line.push_str(kind);
+
+        let tags: Vec<String> = self
+            .config
+            .tags
+            .iter()
+            .map(|tag| sanitize_tag(tag))
+            .chain(
+                tags.iter()
+                    .map(|(key, value)| format!("{}:{}", key, sanitize_tag(value))),
+            )
+            .collect();
+        if !tags.is_empty() {
+            line.push_str("|#");
+            line.push_str(&tags.join(","));
+        }
+        line
+    }
+}
+
+/// Change in a counter since `last`, updating it; a counter that went backwards
+/// was reset, so its whole value is new
+fn counter_delta(current: u64, last: &mut u64) -> u64 {
+    let delta = current.checked_sub(*last).unwrap_or(current);
+    *last = current;
+    delta
+}
+
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Tag values may not contain the separators of the line format
+fn sanitize_tag(tag: &str) -> String {
+    tag.chars()
+        .map(|c| {
+            if matches!(c, ',' | '|' | '#' | '\n') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Join lines into newline-separated datagrams of at most [`MAX_PACKET_BYTES`]
+fn pack_lines(lines: &[String]) -> Vec<String> {
+    let mut packets = Vec::new();
+    let mut packet = String::new();
+    for line in lines {
+        if !packet.is_empty() && packet.len() + 1 + line.len() > MAX_PACKET_BYTES {
+            packets.push(std::mem::take(&mut packet));
+        }
+        if !packet.is_empty() {
+            packet.push('\n');
+        }
+        packet.push_str(line);
+    }
+    if !packet.is_empty() {
+        packets.push(packet);
+    }
+    packets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::InferenceEvent;
+
+    #[tokio::test]
+    async fn test_flush_emits_dogstatsd_lines() {
+        let listener = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let (collector, processor) = MetricsCollector::new();
+        processor.start();
+        collector.record_model_loaded(
+            "llama-7b".to_string(),
+            4096,
+            Duration::from_millis(100),
+            "gguf".to_string(),
+        );
+        for duration in [100, 300] {
+            collector.record_inference(InferenceEvent {
+                model_name: "llama-7b".to_string(),
+                input_length: 10,
+                output_length: 20,
+                duration: Duration::from_millis(duration),
+                success: true,
+            });
+        }
+        collector.increment_counter("cli.run");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let config = StatsdConfig {
+            enabled: true,
+            address: listener.local_addr().unwrap().to_string(),
+            tags: vec!["env:test".to_string()],
+            ..Default::default()
+        };
+        let mut exporter = StatsdExporter::new(config, collector.clone());
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        exporter.flush(&socket).await.unwrap();
+
+        let mut buf = [0u8; MAX_PACKET_BYTES];
+        let len = listener.recv(&mut buf).await.unwrap();
+        let packet = String::from_utf8_lossy(&buf[..len]).to_string();
+        let lines: Vec<&str> = packet.lines().collect();
+        for expected in [
+            "inferno.requests:2|c|#env:test",
+            "inferno.requests.successful:2|c|#env:test",
+            "inferno.tokens.generated:40|c|#env:test",
+            "inferno.models.loaded:1|g|#env:test",
+            "inferno.models.size_bytes:4096|g|#env:test",
+            "inferno.model.inferences:2|c|#env:test,model:llama-7b,backend:gguf",
+            "inferno.inference.latency:200|ms|#env:test,model:llama-7b,backend:gguf",
+            "inferno.cli.run:1|c|#env:test",
+        ] {
+            assert!(lines.contains(&expected), "missing {expected} in {packet}");
+        }
+        assert!(!packet.contains("requests.failed"));
+
+        // Counters are sent as deltas, so an idle interval sends none
+        collector.increment_counter("cli.run");
+        exporter.flush(&socket).await.unwrap();
+        let len = listener.recv(&mut buf).await.unwrap();
+        let packet = String::from_utf8_lossy(&buf[..len]).to_string();
+        assert!(
+            packet
+                .lines()
+                .any(|line| line == "inferno.cli.run:1|c|#env:test")
+        );
+        assert!(!packet.contains("|c|#env:test,model"));
+        assert!(!packet.contains("inferno.requests:"));
+    }
+
+    #[test]
+    fn test_lines_are_packed_under_the_packet_limit() {
+        let lines: Vec<String> = (0..100)
+            .map(|i| format!("inferno.metric_{}:1|c", i))
+            .collect();
+        let packets = pack_lines(&lines);
+        assert!(packets.len() > 1);
+        assert!(packets.iter().all(|p| p.len() <= MAX_PACKET_BYTES));
+        assert_eq!(packets.join("\n").lines().count(), 100);
+    }
+}
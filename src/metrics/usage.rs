@@ -0,0 +1,267 @@
+//! Token usage ledger
+//!
+//! Counts the requests and the prompt and completion tokens served for each API
+//! key, one row per key and UTC day. Keys are stored as a short SHA-256
+//! fingerprint rather than in the clear, so the ledger file can be shared for
+//! billing without leaking credentials.
+//!
+//! Requests are counted in memory and the ledger, a JSON file, is written out
+//! every [`FLUSH_INTERVAL`] while there are new counts, and once more when the
+//! server shuts down. Each write goes through a temporary file renamed over the
+//! old one, so a crash leaves the previous totals intact and loses at most the
+//! last interval's requests.
+
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// How often the server writes new counts out
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Usage of one API key on one day
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageEntry {
+    pub key_id: String,
+    pub date: NaiveDate,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl UsageEntry {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LedgerFile {
+    entries: Vec<UsageEntry>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Totals {
+    requests: u64,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+/// Per-API-key token usage, persisted to a file
+#[derive(Debug)]
+pub struct UsageLedger {
+    path: PathBuf,
+    totals: Mutex<BTreeMap<(String, NaiveDate), Totals>>,
+    /// Whether requests were counted since the last flush
+    dirty: AtomicBool,
+}
+
+impl UsageLedger {
+    /// Open the ledger at `path`, starting empty if the file does not exist yet
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let file = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str::<LedgerFile>(&contents)
+                .with_context(|| format!("Invalid usage ledger {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => LedgerFile::default(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read usage ledger {}", path.display()));
+            }
+        };
+        let totals = file
+            .entries
+            .into_iter()
+            .map(|entry| {
+                let totals = Totals {
+                    requests: entry.requests,
+                    prompt_tokens: entry.prompt_tokens,
+                    completion_tokens: entry.completion_tokens,
+                };
+                ((entry.key_id, entry.date), totals)
+            })
+            .collect();
+        Ok(Self {
+            path,
+            totals: Mutex::new(totals),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Fingerprint `api_key` is recorded under
+    pub fn key_id(api_key: &str) -> String {
+        let digest = Sha256::digest(api_key.trim().as_bytes());
+        hex::encode(&digest[..8])
+    }
+
+    /// Add one request by `api_key` to today's row; [`Self::flush`] saves it
+    pub fn record(&self, api_key: &str, prompt_tokens: u32, completion_tokens: u32) {
+        let mut totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        let row = totals
+            .entry((Self::key_id(api_key), Utc::now().date_naive()))
+            .or_default();
+        row.requests += 1;
+        row.prompt_tokens += u64::from(prompt_tokens);
+        row.completion_tokens += u64::from(completion_tokens);
+        self.dirty.store(true, Ordering::SeqCst);
+    }
+
+    /// Write the ledger out if requests were counted since the last flush
+    ///
+    /// The file is written while the ledger is locked, so a flush never
+    /// overwrites a newer one with older totals.
+    pub fn flush(&self) -> Result<()> {
+        let totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let file = LedgerFile {
+            entries: Self::rows(&totals),
+        };
+        let written = serde_json::to_vec_pretty(&file)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| crate::io::atomic::write_file(&self.path, contents));
+        if written.is_err() {
+            // Try again on the next flush
+            self.dirty.store(true, Ordering::SeqCst);
+        }
+        written
+    }
+
+    /// Flush the ledger every `interval`; the returned task runs until it is
+    /// aborted
+    pub fn spawn_flush(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let ledger = self.clone();
+                // Writing the file blocks, so keep it off the async workers
+                let flushed = tokio::task::spawn_blocking(move || ledger.flush()).await;
+                if let Ok(Err(e)) = flushed {
+                    warn!(
+                        "Failed to write usage ledger {}: {:#}",
+                        self.path.display(),
+                        e
+                    );
+                }
+            }
+        })
+    }
+
+    /// Rows for the key with fingerprint `key_id`, or every key, from `since` on
+    pub fn entries(&self, key_id: Option<&str>, since: Option<NaiveDate>) -> Vec<UsageEntry> {
+        let totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        Self::rows(&totals)
+            .into_iter()
+            .filter(|entry| key_id.is_none_or(|key_id| entry.key_id == key_id))
+            .filter(|entry| since.is_none_or(|since| entry.date >= since))
+            .collect()
+    }
+
+    fn rows(totals: &BTreeMap<(String, NaiveDate), Totals>) -> Vec<UsageEntry> {
+        totals
+            .iter()
+            .map(|((key_id, date), totals)| UsageEntry {
+                key_id: key_id.clone(),
+                date: *date,
+                requests: totals.requests,
+                prompt_tokens: totals.prompt_tokens,
+                completion_tokens: totals.completion_tokens,
+            })
+            .collect()
+    }
+}
+
+/// `entries` as CSV with a header row
+pub fn to_csv(entries: &[UsageEntry]) -> Result<String> {
+    let mut wtr = csv::Writer::from_writer(vec![]);
+    wtr.write_record([
+        "key_id",
+        "date",
+        "requests",
+        "prompt_tokens",
+        "completion_tokens",
+        "total_tokens",
+    ])?;
+    for entry in entries {
+        wtr.write_record([
+            &entry.key_id,
+            &entry.date.to_string(),
+            &entry.requests.to_string(),
+            &entry.prompt_tokens.to_string(),
+            &entry.completion_tokens.to_string(),
+            &entry.total_tokens().to_string(),
+        ])?;
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ledger_accrues_per_key_and_exports_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("usage.json");
+
+        let ledger = UsageLedger::open(&path).unwrap();
+        ledger.record("key-a", 10, 20);
+        ledger.record("key-a", 5, 7);
+        ledger.record("key-b", 3, 4);
+        // Counts stay in memory until a flush writes them out
+        assert!(!path.exists());
+        ledger.flush().unwrap();
+
+        // Reopening reads back what the flush wrote
+        let ledger = UsageLedger::open(&path).unwrap();
+        let a = ledger.entries(Some(&UsageLedger::key_id("key-a")), None);
+        assert_eq!(a.len(), 1);
+        assert_eq!(
+            (a[0].requests, a[0].prompt_tokens, a[0].completion_tokens),
+            (2, 15, 27)
+        );
+        let b = ledger.entries(Some(&UsageLedger::key_id("key-b")), None);
+        assert_eq!(
+            (b[0].requests, b[0].prompt_tokens, b[0].completion_tokens),
+            (1, 3, 4)
+        );
+        assert!(
+            ledger
+                .entries(Some(&UsageLedger::key_id("key-c")), None)
+                .is_empty()
+        );
+
+        let raw = std::fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("key-a"), "keys are stored as fingerprints");
+
+        let tomorrow = Utc::now().date_naive().succ_opt().unwrap();
+        assert!(ledger.entries(None, Some(tomorrow)).is_empty());
+
+        let csv = to_csv(&a).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("key_id,date,requests,prompt_tokens,completion_tokens,total_tokens")
+        );
+        assert_eq!(
+            lines.next().unwrap(),
+            format!("{},{},2,15,27,42", a[0].key_id, a[0].date)
+        );
+        assert_eq!(lines.next(), None);
+    }
+}
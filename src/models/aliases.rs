@@ -0,0 +1,76 @@
+//! Friendly model ids
+//!
+//! API clients name models like `gpt-4` rather than by file. Aliases configured
+//! under `[model_aliases]` map such ids to a model file in `models_dir` and
+//! optionally the backend to run it with:
+//!
+//! ```toml
+//! [model_aliases]
+//! default = "chat"
+//!
+//! [model_aliases.aliases]
+//! chat = "Meta-Llama-3-8B-Instruct.Q4_K_M.gguf"
+//! embed = { file = "embeddings/minilm.onnx", backend = "onnx" }
+//! ```
+//!
+//! Names that are not aliases are still looked up as model files, so aliases
+//! can be added without breaking existing clients.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// What an alias points at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AliasTarget {
+    /// Model file name or path relative to `models_dir`
+    File(String),
+    Model {
+        file: String,
+        /// Backend to load the file with instead of the one its extension implies
+        #[serde(default)]
+        backend: Option<String>,
+    },
+}
+
+impl AliasTarget {
+    pub fn file(&self) -> &str {
+        match self {
+            Self::File(file) | Self::Model { file, .. } => file,
+        }
+    }
+
+    pub fn backend(&self) -> Option<&str> {
+        match self {
+            Self::File(_) => None,
+            Self::Model { backend, .. } => backend.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelAliases {
+    /// Alias used by requests that do not name a model
+    pub default: Option<String>,
+    pub aliases: BTreeMap<String, AliasTarget>,
+}
+
+impl ModelAliases {
+    pub fn get(&self, alias: &str) -> Option<&AliasTarget> {
+        self.aliases.get(alias)
+    }
+
+    /// Configured aliases in name order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.aliases.keys().map(String::as_str)
+    }
+
+    /// `name`, or the default alias when `name` is empty
+    pub fn or_default<'a>(&'a self, name: &'a str) -> &'a str {
+        match (name.is_empty(), &self.default) {
+            (true, Some(default)) => default,
+            _ => name,
+        }
+    }
+}
@@ -0,0 +1,577 @@
+//! Chat prompt formatting
+//!
+//! GGUF models carry their chat template as a Jinja program under
+//! `tokenizer.chat_template`, written for the Hugging Face `transformers` renderer.
+//! [`ChatTemplate`] renders it with minijinja, set up the way `transformers` does:
+//! blocks are trimmed, Python string methods are available and templates may call
+//! `raise_exception`. Models without a template use a built-in ChatML, Llama 2 or
+//! Mistral template, picked from the model's name.
+
+use super::ModelInfo;
+use anyhow::{Result, anyhow};
+use byteorder::{LittleEndian, ReadBytesExt};
+use minijinja::{Environment, Error, ErrorKind, context};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const CHAT_TEMPLATE_KEY: &str = "tokenizer.chat_template";
+const TOKENS_KEY: &str = "tokenizer.ggml.tokens";
+const EOS_TOKEN_ID_KEY: &str = "tokenizer.ggml.eos_token_id";
+const EOT_TOKEN_ID_KEY: &str = "tokenizer.ggml.eot_token_id";
+const TEMPLATE_NAME: &str = "chat";
+
+/// Markers that end an assistant turn in common templates
+const END_OF_TURN_MARKERS: &[&str] = &["<|im_end|>", "<|eot_id|>", "<end_of_turn>", "<|end|>"];
+
+const CHATML_TEMPLATE: &str = "{% for message in messages %}\
+{{ '<|im_start|>' + message['role'] + '\\n' + message['content'] + '<|im_end|>' + '\\n' }}\
+{% endfor %}\
+{% if add_generation_prompt %}{{ '<|im_start|>assistant\\n' }}{% endif %}";
+
+const LLAMA2_TEMPLATE: &str = "{% if messages[0]['role'] == 'system' %}\
+{% set system_message = '<<SYS>>\\n' + messages[0]['content'] + '\\n<</SYS>>\\n\\n' %}\
+{% set messages = messages[1:] %}\
+{% else %}{% set system_message = '' %}{% endif %}\
+{% for message in messages %}\
+{% if message['role'] == 'user' %}\
+{{ bos_token + '[INST] ' + (system_message if loop.first else '') + message['content'] + ' [/INST]' }}\
+{% elif message['role'] == 'assistant' %}\
+{{ ' ' + message['content'] + ' ' + eos_token }}\
+{% endif %}\
+{% endfor %}";
+
+const MISTRAL_TEMPLATE: &str = "{% if messages[0]['role'] == 'system' %}\
+{% set system_message = messages[0]['content'] + '\\n\\n' %}\
+{% set messages = messages[1:] %}\
+{% else %}{% set system_message = '' %}{% endif %}\
+{{ bos_token }}\
+{% for message in messages %}\
+{% if message['role'] == 'user' %}\
+{{ '[INST] ' + (system_message if loop.first else '') + message['content'] + ' [/INST]' }}\
+{% elif message['role'] == 'assistant' %}\
+{{ message['content'] + eos_token }}\
+{% endif %}\
+{% endfor %}";
+
+/// One message of a conversation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+}
+
+impl Message {
+    pub fn new(role: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            role: role.into(),
+            content: content.into(),
+        }
+    }
+}
+
+/// Templates used for models that do not carry one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinTemplate {
+    ChatMl,
+    Llama2,
+    Mistral,
+}
+
+impl BuiltinTemplate {
+    /// Guess the format from a model name, defaulting to ChatML
+    pub fn for_model_name(name: &str) -> Self {
+        let name = name.to_lowercase();
+        if name.contains("mistral") || name.contains("mixtral") {
+            Self::Mistral
+        } else if ["llama-2", "llama2", "llama_2"]
+            .iter()
+            .any(|llama2| name.contains(llama2))
+        {
+            Self::Llama2
+        } else {
+            Self::ChatMl
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            Self::ChatMl => CHATML_TEMPLATE,
+            Self::Llama2 => LLAMA2_TEMPLATE,
+            Self::Mistral => MISTRAL_TEMPLATE,
+        }
+    }
+
+    fn eos_token(&self) -> &'static str {
+        match self {
+            Self::ChatMl => "<|im_end|>",
+            Self::Llama2 | Self::Mistral => "</s>",
+        }
+    }
+}
+
+/// A compiled chat template
+#[derive(Debug, Clone)]
+pub struct ChatTemplate {
+    env: Environment<'static>,
+    eos_token: String,
+    stop_sequences: Vec<String>,
+    renders_tools: bool,
+}
+
+impl ChatTemplate {
+    /// Compile a Jinja chat template; `eos_token` is the text of the model's
+    /// end-of-sequence token
+    pub fn new(source: impl Into<String>, eos_token: impl Into<String>) -> Result<Self> {
+        let source = source.into();
+        let eos_token = eos_token.into();
+
+        let mut env = Environment::new();
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        env.set_unknown_method_callback(minijinja_contrib::pycompat::unknown_method_callback);
+        env.add_function(
+            "raise_exception",
+            |message: String| -> Result<String, Error> {
+                Err(Error::new(ErrorKind::InvalidOperation, message))
+            },
+        );
+
+        let mut stop_sequences: Vec<String> = END_OF_TURN_MARKERS
+            .iter()
+            .filter(|marker| source.contains(**marker))
+            .map(|marker| marker.to_string())
+            .collect();
+        if !eos_token.is_empty() && !stop_sequences.contains(&eos_token) {
+            stop_sequences.push(eos_token.clone());
+        }
+        env.add_template_owned(TEMPLATE_NAME, source)
+            .map_err(|e| anyhow!("Invalid chat template: {:#}", e))?;
+        // Only a template reading the `tools` variable describes them; the word in
+        // its text or a variable it sets itself does not count
+        let renders_tools = env
+            .get_template(TEMPLATE_NAME)?
+            .undeclared_variables(false)
+            .contains("tools");
+        Ok(Self {
+            env,
+            eos_token,
+            stop_sequences,
+            renders_tools,
+        })
+    }
+
+    pub fn builtin(template: BuiltinTemplate) -> Self {
+        Self::new(template.source(), template.eos_token()).expect("built-in chat templates compile")
+    }
+
+    /// Template of the model at `path`: the one in its GGUF metadata, or a built-in
+    /// one picked from its file name
+    pub fn for_model(path: &Path) -> Result<Self> {
+        let is_gguf = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("gguf"));
+        if is_gguf {
+            let metadata = read_gguf_chat_metadata(path)?;
+            if let Some(source) = metadata.chat_template {
+                let eos_token = metadata.eos().map(|token| token.text.clone());
+                let mut template = Self::new(source, eos_token.unwrap_or_default())?;
+                for token in &metadata.end_tokens {
+                    if !template.stop_sequences.contains(&token.text) {
+                        template.stop_sequences.push(token.text.clone());
+                    }
+                }
+                return Ok(template);
+            }
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        Ok(Self::builtin(BuiltinTemplate::for_model_name(&name)))
+    }
+
+    /// Prompt asking for the assistant's reply to `messages`
+    pub fn render(&self, messages: &[Message]) -> Result<String> {
+        self.render_with_tools(messages, &[])
+    }
+
+    /// Prompt asking for the assistant's reply to `messages`, offering `tools` in
+    /// the OpenAI format to templates that render a `tools` variable
+    pub fn render_with_tools(
+        &self,
+        messages: &[Message],
+        tools: &[serde_json::Value],
+    ) -> Result<String> {
+        let template = self.env.get_template(TEMPLATE_NAME)?;
+        template
+            .render(context! {
+                messages => messages,
+                // Templates test `tools is not none`, so leave it unset without any
+                tools => (!tools.is_empty()).then_some(tools),
+                add_generation_prompt => true,
+                // Backends prepend BOS when tokenizing, so rendering it would double it
+                bos_token => "",
+                eos_token => &self.eos_token,
+            })
+            .map_err(|e| anyhow!("Failed to render chat template: {:#}", e))
+    }
+
+    /// Whether the template describes tools to the model itself
+    pub fn renders_tools(&self) -> bool {
+        self.renders_tools
+    }
+
+    /// Text that ends the assistant's turn
+    pub fn stop_sequences(&self) -> &[String] {
+        &self.stop_sequences
+    }
+}
+
+/// Chat templates of models, read once for each version of a model file
+#[derive(Debug, Default)]
+pub struct ChatTemplateCache {
+    templates: Mutex<HashMap<(PathBuf, i64), Arc<ChatTemplate>>>,
+}
+
+impl ChatTemplateCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Template for `model`, falling back to a built-in one if its template cannot
+    /// be read or compiled
+    ///
+    /// Reading a GGUF template streams the model's metadata, so call this off the
+    /// async runtime.
+    pub fn get(&self, model: &ModelInfo) -> Arc<ChatTemplate> {
+        let key = (model.path.clone(), model.modified.timestamp());
+        if let Some(template) = self.lock().get(&key) {
+            return template.clone();
+        }
+
+        let template = Arc::new(ChatTemplate::for_model(&model.path).unwrap_or_else(|e| {
+            warn!(
+                "Using a built-in chat template for {}: {}",
+                model.path.display(),
+                e
+            );
+            ChatTemplate::builtin(BuiltinTemplate::for_model_name(&model.name))
+        }));
+        self.lock().insert(key, template.clone());
+        template
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(PathBuf, i64), Arc<ChatTemplate>>> {
+        self.templates.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A token of a model's vocabulary that ends generation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndToken {
+    pub id: u32,
+    pub text: String,
+}
+
+/// The end-of-sequence and end-of-turn tokens named in a GGUF file's metadata
+///
+/// Chat models often end a turn with an end-of-turn token such as Llama 3's
+/// `<|eot_id|>` rather than end-of-sequence, so generation has to stop on both.
+pub fn read_end_tokens(path: &Path) -> Result<Vec<EndToken>> {
+    Ok(read_gguf_chat_metadata(path)?.end_tokens)
+}
+
+/// Chat-related fields of a GGUF file's metadata
+#[derive(Debug, Default)]
+struct GgufChatMetadata {
+    chat_template: Option<String>,
+    /// End-of-sequence first, then end-of-turn if it is another token
+    end_tokens: Vec<EndToken>,
+    eos_token_id: Option<u32>,
+}
+
+impl GgufChatMetadata {
+    fn eos(&self) -> Option<&EndToken> {
+        let id = self.eos_token_id?;
+        self.end_tokens.iter().find(|token| token.id == id)
+    }
+}
+
+/// Read the chat template and end-of-sequence and end-of-turn tokens from a
+/// GGUF file's metadata
+///
+/// All follow the tokenizer vocabulary, which can run to megabytes, so the
+/// metadata is streamed rather than read from a fixed-size header buffer.
+fn read_gguf_chat_metadata(path: &Path) -> Result<GgufChatMetadata> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"GGUF" {
+        return Err(anyhow!("Not a GGUF file: invalid magic bytes"));
+    }
+    let _version = reader.read_u32::<LittleEndian>()?;
+    let _n_tensors = reader.read_u64::<LittleEndian>()?;
+    let n_kv = reader.read_u64::<LittleEndian>()?;
+
+    let mut chat_template = None;
+    let mut tokens = Vec::new();
+    let mut eos_token_id = None;
+    let mut eot_token_id = None;
+    for _ in 0..n_kv {
+        let key = read_string(&mut reader)?;
+        let value_type = reader.read_u32::<LittleEndian>()?;
+        match (key.as_str(), value_type) {
+            (CHAT_TEMPLATE_KEY, 8) => chat_template = Some(read_string(&mut reader)?),
+            (EOS_TOKEN_ID_KEY, 4) => eos_token_id = Some(reader.read_u32::<LittleEndian>()?),
+            (EOT_TOKEN_ID_KEY, 4) => eot_token_id = Some(reader.read_u32::<LittleEndian>()?),
+            (TOKENS_KEY, 9) => {
+                let element_type = reader.read_u32::<LittleEndian>()?;
+                let count = reader.read_u64::<LittleEndian>()?;
+                if element_type != 8 || count > 10_000_000 {
+                    return Err(anyhow!("Unexpected GGUF token list"));
+                }
+                tokens = (0..count)
+                    .map(|_| read_string(&mut reader))
+                    .collect::<Result<_>>()?;
+            }
+            _ => skip_value(&mut reader, value_type)?,
+        }
+    }
+
+    let mut end_tokens: Vec<EndToken> = Vec::new();
+    for id in [eos_token_id, eot_token_id].into_iter().flatten() {
+        if let Some(text) = tokens.get(id as usize)
+            && !end_tokens.iter().any(|token| token.id == id)
+        {
+            end_tokens.push(EndToken {
+                id,
+                text: text.clone(),
+            });
+        }
+    }
+    Ok(GgufChatMetadata {
+        chat_template,
+        end_tokens,
+        eos_token_id,
+    })
+}
+
+fn read_string(reader: &mut BufReader<File>) -> Result<String> {
+    let len = reader.read_u64::<LittleEndian>()?;
+    if len > 16 * 1024 * 1024 {
+        return Err(anyhow!("GGUF string length {} exceeds sanity limit", len));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn skip_value(reader: &mut BufReader<File>, value_type: u32) -> Result<()> {
+    let size: i64 = match value_type {
+        0 | 1 | 7 => 1,
+        2 | 3 => 2,
+        4..=6 => 4,
+        10..=12 => 8,
+        8 => reader.read_u64::<LittleEndian>()? as i64,
+        9 => {
+            let element_type = reader.read_u32::<LittleEndian>()?;
+            let count = reader.read_u64::<LittleEndian>()?;
+            if count > 100_000_000 {
+                return Err(anyhow!("GGUF array count {} exceeds sanity limit", count));
+            }
+            for _ in 0..count {
+                skip_value(reader, element_type)?;
+            }
+            return Ok(());
+        }
+        _ => return Err(anyhow!("Unknown GGUF value type: {}", value_type)),
+    };
+    reader.seek_relative(size)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+    use std::io::Write;
+
+    /// The template Qwen2 instruct models ship in their GGUF metadata
+    const QWEN_TEMPLATE: &str = "{% for message in messages %}\
+{% if loop.first and messages[0]['role'] != 'system' %}\
+{{ '<|im_start|>system\nYou are a helpful assistant.<|im_end|>\n' }}\
+{% endif %}\
+{{'<|im_start|>' + message['role'] + '\n' + message['content'] + '<|im_end|>' + '\n'}}\
+{% endfor %}\
+{% if add_generation_prompt %}{{ '<|im_start|>assistant\n' }}{% endif %}";
+
+    fn write_string(out: &mut Vec<u8>, value: &str) {
+        out.write_u64::<LittleEndian>(value.len() as u64).unwrap();
+        out.write_all(value.as_bytes()).unwrap();
+    }
+
+    fn conversation() -> Vec<Message> {
+        vec![
+            Message::new("system", "Be brief."),
+            Message::new("user", "Hi"),
+        ]
+    }
+
+    #[test]
+    fn test_chatml_template_from_gguf_metadata() {
+        let mut gguf = b"GGUF".to_vec();
+        gguf.write_u32::<LittleEndian>(3).unwrap();
+        gguf.write_u64::<LittleEndian>(0).unwrap();
+        gguf.write_u64::<LittleEndian>(4).unwrap();
+        write_string(&mut gguf, "general.architecture");
+        gguf.write_u32::<LittleEndian>(8).unwrap();
+        write_string(&mut gguf, "qwen2");
+        write_string(&mut gguf, TOKENS_KEY);
+        gguf.write_u32::<LittleEndian>(9).unwrap();
+        gguf.write_u32::<LittleEndian>(8).unwrap();
+        gguf.write_u64::<LittleEndian>(3).unwrap();
+        for token in ["hello", "<|im_end|>", "<|endoftext|>"] {
+            write_string(&mut gguf, token);
+        }
+        write_string(&mut gguf, EOS_TOKEN_ID_KEY);
+        gguf.write_u32::<LittleEndian>(4).unwrap();
+        gguf.write_u32::<LittleEndian>(2).unwrap();
+        write_string(&mut gguf, CHAT_TEMPLATE_KEY);
+        gguf.write_u32::<LittleEndian>(8).unwrap();
+        write_string(&mut gguf, QWEN_TEMPLATE);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("qwen2-7b-instruct.gguf");
+        std::fs::write(&path, gguf).unwrap();
+        let template = ChatTemplate::for_model(&path).unwrap();
+
+        assert_eq!(
+            template.render(&conversation()).unwrap(),
+            "<|im_start|>system\nBe brief.<|im_end|>\n\
+             <|im_start|>user\nHi<|im_end|>\n\
+             <|im_start|>assistant\n"
+        );
+        // Without a system message the template adds its default one
+        assert!(
+            template
+                .render(&[Message::new("user", "Hi")])
+                .unwrap()
+                .starts_with("<|im_start|>system\nYou are a helpful assistant.<|im_end|>\n")
+        );
+        assert_eq!(
+            template.stop_sequences(),
+            ["<|im_end|>".to_string(), "<|endoftext|>".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_end_tokens_from_gguf_metadata() {
+        // Llama 3 ends a turn with <|eot_id|>, not its end-of-sequence token
+        let mut gguf = b"GGUF".to_vec();
+        gguf.write_u32::<LittleEndian>(3).unwrap();
+        gguf.write_u64::<LittleEndian>(0).unwrap();
+        gguf.write_u64::<LittleEndian>(3).unwrap();
+        write_string(&mut gguf, TOKENS_KEY);
+        gguf.write_u32::<LittleEndian>(9).unwrap();
+        gguf.write_u32::<LittleEndian>(8).unwrap();
+        gguf.write_u64::<LittleEndian>(3).unwrap();
+        for token in ["Hello", "<|end_of_text|>", "<|eot_id|>"] {
+            write_string(&mut gguf, token);
+        }
+        write_string(&mut gguf, EOS_TOKEN_ID_KEY);
+        gguf.write_u32::<LittleEndian>(4).unwrap();
+        gguf.write_u32::<LittleEndian>(1).unwrap();
+        write_string(&mut gguf, EOT_TOKEN_ID_KEY);
+        gguf.write_u32::<LittleEndian>(4).unwrap();
+        gguf.write_u32::<LittleEndian>(2).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("llama-3-8b-instruct.gguf");
+        std::fs::write(&path, gguf).unwrap();
+
+        let end_tokens = read_end_tokens(&path).unwrap();
+        let ids: Vec<u32> = end_tokens.iter().map(|token| token.id).collect();
+        assert_eq!(ids, [1, 2]);
+        assert_eq!(end_tokens[1].text, "<|eot_id|>");
+    }
+
+    #[test]
+    fn test_builtin_templates() {
+        let mut messages = conversation();
+        messages.push(Message::new("assistant", "Hello!"));
+        messages.push(Message::new("user", "Bye"));
+
+        assert_eq!(
+            ChatTemplate::builtin(BuiltinTemplate::ChatMl)
+                .render(&messages[1..2])
+                .unwrap(),
+            "<|im_start|>user\nHi<|im_end|>\n<|im_start|>assistant\n"
+        );
+        assert_eq!(
+            ChatTemplate::builtin(BuiltinTemplate::Llama2)
+                .render(&messages)
+                .unwrap(),
+            "[INST] <<SYS>>\nBe brief.\n<</SYS>>\n\nHi [/INST] Hello! </s>[INST] Bye [/INST]"
+        );
+        assert_eq!(
+            ChatTemplate::builtin(BuiltinTemplate::Mistral)
+                .render(&messages)
+                .unwrap(),
+            "[INST] Be brief.\n\nHi [/INST]Hello!</s>[INST] Bye [/INST]"
+        );
+
+        assert_eq!(
+            BuiltinTemplate::for_model_name("Mistral-7B-Instruct-v0.2.Q4_K_M"),
+            BuiltinTemplate::Mistral
+        );
+        assert_eq!(
+            BuiltinTemplate::for_model_name("llama-2-13b-chat"),
+            BuiltinTemplate::Llama2
+        );
+        assert_eq!(
+            ChatTemplate::for_model(Path::new("phi-3.onnx"))
+                .unwrap()
+                .stop_sequences(),
+            ["<|im_end|>".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_renders_tools_only_when_reading_the_variable() {
+        let renders = |source: &str| ChatTemplate::new(source, "").unwrap().renders_tools();
+        assert!(renders(
+            "{% if tools is not none %}{{ tools | tojson }}{% endif %}{{ messages[0]['content'] }}"
+        ));
+        assert!(renders("{% for tool in tools %}{{ tool }}{% endfor %}"));
+        assert!(!renders(
+            "{{ 'You have no tools.' }}{{ messages[0]['content'] }}"
+        ));
+        assert!(!renders("{% set tools = [] %}{{ tools | length }}"));
+        assert!(!ChatTemplate::builtin(BuiltinTemplate::ChatMl).renders_tools());
+    }
+
+    #[test]
+    fn test_template_errors() {
+        assert!(ChatTemplate::new("{% for message in messages %}", "").is_err());
+
+        let strict = ChatTemplate::new(
+            "{% if messages[0]['role'] != 'user' %}\
+             {{ raise_exception('Conversations must start with a user message') }}\
+             {% endif %}{{ messages[0]['content'].strip() }}",
+            "</s>",
+        )
+        .unwrap();
+        assert_eq!(
+            strict.render(&[Message::new("user", "  hi  ")]).unwrap(),
+            "hi"
+        );
+        let error = strict.render(&conversation()).unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("Conversations must start with a user message"),
+            "{:#}",
+            error
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Process-wide cache of model file checksums
+//!
+//! Hashing a multi-gigabyte model takes seconds, and validation, conversion and
+//! the API all ask for the same files' checksums. Each checksum is kept with the
+//! size and modification time the file had when it was hashed, and is served
+//! again only while both are unchanged. Callers asking for the same file at the
+//! same time wait for a single hash rather than each reading the file.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use tokio::io::AsyncReadExt;
+
+static GLOBAL: OnceLock<ChecksumCache> = OnceLock::new();
+
+/// Size and modification time identifying one version of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    async fn of(path: &Path) -> Result<Self> {
+        let metadata = tokio::fs::metadata(path).await?;
+        Ok(Self {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct CachedChecksum {
+    stamp: FileStamp,
+    checksum: String,
+}
+
+type Slot = Arc<tokio::sync::Mutex<Option<CachedChecksum>>>;
+
+/// SHA-256 checksums of files, kept until the file changes
+#[derive(Debug, Default)]
+pub struct ChecksumCache {
+    slots: Mutex<HashMap<PathBuf, Slot>>,
+    hashed: AtomicU64,
+}
+
+impl ChecksumCache {
+    /// The cache shared by the whole process
+    pub fn global() -> &'static ChecksumCache {
+        GLOBAL.get_or_init(ChecksumCache::default)
+    }
+
+    /// Hex SHA-256 of the file at `path`, hashing it only if it changed since
+    /// it was last hashed
+    pub async fn checksum(&self, path: &Path) -> Result<String> {
+        self.lookup(path, true).await
+    }
+
+    /// Hex SHA-256 of the file at `path`, always read from disk
+    ///
+    /// The size and modification time can be restored after the contents are
+    /// changed, so integrity checks hash afresh; the result replaces the cached
+    /// checksum.
+    pub async fn rehash(&self, path: &Path) -> Result<String> {
+        self.lookup(path, false).await
+    }
+
+    async fn lookup(&self, path: &Path, use_cached: bool) -> Result<String> {
+        let key = tokio::fs::canonicalize(path)
+            .await
+            .unwrap_or_else(|_| path.to_path_buf());
+        let slot = {
+            let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+            Arc::clone(slots.entry(key).or_default())
+        };
+        let mut cached = slot.lock().await;
+
+        let stamp = FileStamp::of(path).await?;
+        if let Some(hit) = cached
+            .as_ref()
+            .filter(|hit| use_cached && hit.stamp == stamp)
+        {
+            return Ok(hit.checksum.clone());
+        }
+        let checksum = hash_file(path).await?;
+        self.hashed.fetch_add(1, Ordering::Relaxed);
+        // A file written to while it was read may not match either version
+        *cached = (FileStamp::of(path).await? == stamp).then(|| CachedChecksum {
+            stamp,
+            checksum: checksum.clone(),
+        });
+        Ok(checksum)
+    }
+
+    /// Times a file has been read and hashed rather than served from the cache
+    pub fn files_hashed(&self) -> u64 {
+        self.hashed.load(Ordering::Relaxed)
+    }
+}
+
+async fn hash_file(path: &Path) -> Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 8192];
+    loop {
+        let bytes_read = file.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_unchanged_file_is_hashed_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.gguf");
+        std::fs::write(&path, b"model weights").unwrap();
+        let cache = ChecksumCache::default();
+
+        let first = cache.checksum(&path).await.unwrap();
+        let second = cache.checksum(&path).await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(cache.files_hashed(), 1);
+
+        // Concurrent callers share one hash as well
+        let (a, b) = tokio::join!(cache.checksum(&path), cache.checksum(&path));
+        assert_eq!((a.unwrap(), b.unwrap()), (first.clone(), first.clone()));
+        assert_eq!(cache.files_hashed(), 1);
+
+        // Touching the file invalidates its checksum
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        drop(file);
+        assert_eq!(cache.checksum(&path).await.unwrap(), first);
+        assert_eq!(cache.files_hashed(), 2);
+
+        std::fs::write(&path, b"other weights").unwrap();
+        assert_ne!(cache.checksum(&path).await.unwrap(), first);
+        assert_eq!(cache.files_hashed(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rehash_reads_file_with_restored_stamp() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.gguf");
+        std::fs::write(&path, b"model weights").unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        let cache = ChecksumCache::default();
+        let original = cache.checksum(&path).await.unwrap();
+
+        // Same size, with the modification time put back
+        std::fs::write(&path, b"evil! weights").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(modified).unwrap();
+        drop(file);
+        assert_eq!(cache.checksum(&path).await.unwrap(), original);
+
+        let tampered = cache.rehash(&path).await.unwrap();
+        assert_ne!(tampered, original);
+        assert_eq!(cache.checksum(&path).await.unwrap(), tampered);
+        assert_eq!(cache.files_hashed(), 2);
+    }
+}
@@ -10,6 +10,17 @@ use std::path::{Path, PathBuf};
 use tokio::fs as async_fs;
 use tracing::{error, info, warn};
 
+pub mod aliases;
+pub mod chat_template;
+pub mod checksum;
+pub mod onnx_graph;
+pub mod package;
+pub mod verify;
+pub mod watch;
+
+pub use aliases::{AliasTarget, ModelAliases};
+pub use package::ModelSource;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelInfo {
     pub name: String,
@@ -38,6 +49,10 @@ pub struct OnnxMetadata {
     pub producer: String,
     pub input_count: u32,
     pub output_count: u32,
+    /// Tensors the model takes, with their element types and shapes
+    pub inputs: Vec<onnx_graph::TensorSpec>,
+    /// Tensors the model returns
+    pub outputs: Vec<onnx_graph::TensorSpec>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -118,17 +133,88 @@ pub struct RegistryEntry {
     pub use_count: u64,
     pub last_used: Option<chrono::DateTime<chrono::Utc>>,
     pub added_at: chrono::DateTime<chrono::Utc>,
+    /// Remote origin for models installed from a repository; `None` for local files.
+    #[serde(default)]
+    pub source: Option<ModelSource>,
 }
 
+/// Directory of `models_dir` holding each tenant's private models, one
+/// subdirectory per tenant id; every other model is shared by all tenants
+pub const TENANTS_DIR: &str = "tenants";
+
 #[derive(Clone)]
 pub struct ModelManager {
     models_dir: PathBuf,
+    allow_external_paths: bool,
+    aliases: std::sync::Arc<ModelAliases>,
+    /// Tenant whose namespace listing and resolution are limited to
+    tenant: Option<String>,
 }
 
 impl ModelManager {
     pub fn new(models_dir: &Path) -> Self {
         Self {
             models_dir: models_dir.to_path_buf(),
+            allow_external_paths: false,
+            aliases: Default::default(),
+            tenant: None,
+        }
+    }
+
+    pub fn from_config(config: &crate::config::Config) -> Self {
+        Self::new(&config.models_dir)
+            .with_external_paths(config.allow_external_paths)
+            .with_aliases(config.model_aliases.clone())
+    }
+
+    /// Let [`resolve_model`](Self::resolve_model) accept the ids in `aliases`
+    pub fn with_aliases(mut self, aliases: ModelAliases) -> Self {
+        self.aliases = std::sync::Arc::new(aliases);
+        self
+    }
+
+    pub fn aliases(&self) -> &ModelAliases {
+        &self.aliases
+    }
+
+    /// Let [`resolve_model`](Self::resolve_model) load paths and symlink
+    /// targets outside `models_dir`
+    pub fn with_external_paths(mut self, allow: bool) -> Self {
+        self.allow_external_paths = allow;
+        self
+    }
+
+    pub fn models_dir(&self) -> &Path {
+        &self.models_dir
+    }
+
+    /// This manager limited to `tenant`'s private models and the shared ones
+    ///
+    /// Other tenants' models are left out of listings, and resolving one fails
+    /// with [`InfernoError::SecurityValidation`]. Names are looked up in the
+    /// tenant's namespace before the shared models.
+    pub fn for_tenant(&self, tenant: &str) -> Self {
+        Self {
+            tenant: Some(tenant.to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// Directory of `tenant`'s private models
+    pub fn tenant_dir(&self, tenant: &str) -> PathBuf {
+        self.models_dir.join(TENANTS_DIR).join(tenant)
+    }
+
+    /// Whether the model at `path` is shared or in this manager's tenant's
+    /// namespace; an unscoped manager sees every model
+    pub fn is_visible(&self, path: &Path) -> bool {
+        self.may_access(namespace_of(&self.models_dir, path))
+    }
+
+    fn may_access(&self, namespace: Option<String>) -> bool {
+        match (&self.tenant, namespace) {
+            (Some(tenant), Some(namespace)) => *tenant == namespace,
+            _ => true,
         }
     }
 
@@ -160,12 +246,12 @@ impl ModelManager {
                 let path = entry.path();
 
                 if path.is_dir() {
-                    // Skip hidden directories (e.g. .inferno_cache)
+                    // Skip hidden directories (e.g. .inferno_cache) and other tenants'
                     let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-                    if !name.starts_with('.') {
+                    if !name.starts_with('.') && self.is_visible(&path) {
                         dirs_to_scan.push(path);
                     }
-                } else if path.is_file() {
+                } else if path.is_file() && self.is_visible(&path) {
                     if let Some(ext) = path.extension() {
                         let ext_lower = ext.to_string_lossy().to_lowercase();
                         if matches!(ext_lower.as_str(), "gguf" | "onnx") {
@@ -190,40 +276,141 @@ impl ModelManager {
         Ok(models)
     }
 
+    /// Models whose file changed after `since`, newest first
+    ///
+    /// Lets periodic scans of a large directory skip files already processed.
+    pub async fn list_models_modified_since(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<ModelInfo>> {
+        let mut models = self.list_models().await?;
+        models.retain(|model| model.modified > since);
+        Ok(models)
+    }
+
+    /// Find the model an alias, file name or path refers to; an empty name
+    /// resolves the default alias
+    ///
+    /// Aliases take precedence over model files of the same name. A name matching
+    /// neither fails with [`InfernoError::ModelNotFound`].
     pub async fn resolve_model(&self, model_name_or_path: &str) -> Result<ModelInfo> {
-        let path = if model_name_or_path.contains('/') || model_name_or_path.contains('\\') {
-            PathBuf::from(model_name_or_path)
-        } else {
-            self.find_model_by_name(model_name_or_path).await?
+        let name = self.aliases.or_default(model_name_or_path);
+        let Some(target) = self.aliases.get(name) else {
+            return self.resolve_file(name).await;
         };
+        let mut info = self.resolve_file(target.file()).await?;
+        if let Some(backend) = target.backend() {
+            info.backend_type = backend.to_string();
+        }
+        Ok(info)
+    }
+
+    async fn resolve_file(&self, model_name_or_path: &str) -> Result<ModelInfo> {
+        // Paths are taken as given when they exist, and otherwise from `models_dir`,
+        // which they may never leave. Anything outside `models_dir`, including
+        // symlinked names, is only loaded if external paths are allowed.
+        let (path, may_leave) =
+            if model_name_or_path.contains('/') || model_name_or_path.contains('\\') {
+                let requested = PathBuf::from(model_name_or_path);
+                if requested.is_absolute() || requested.exists() {
+                    (requested, self.allow_external_paths)
+                } else {
+                    if requested
+                        .components()
+                        .any(|c| matches!(c, std::path::Component::ParentDir))
+                    {
+                        return Err(InfernoError::SecurityValidation(format!(
+                            "Model path escapes the models directory: {}",
+                            model_name_or_path
+                        ))
+                        .into());
+                    }
+                    (self.models_dir.join(requested), false)
+                }
+            } else {
+                (
+                    self.find_model_by_name(model_name_or_path).await?,
+                    self.allow_external_paths,
+                )
+            };
 
         if !path.exists() {
             return Err(anyhow::anyhow!("Model not found: {}", path.display()));
         }
 
+        if !may_leave && !self.contains(&path).await {
+            return Err(InfernoError::SecurityValidation(format!(
+                "Model path is outside the models directory: {}",
+                path.display()
+            ))
+            .into());
+        }
+
+        // Checked on the real paths, so a symlink cannot reach into another namespace
+        if self.tenant.is_some() {
+            let (models_dir, real_path) = (
+                async_fs::canonicalize(&self.models_dir).await?,
+                async_fs::canonicalize(&path).await?,
+            );
+            if !self.is_visible(&path) || !self.may_access(namespace_of(&models_dir, &real_path)) {
+                return Err(InfernoError::SecurityValidation(format!(
+                    "Model '{}' belongs to another tenant",
+                    model_name_or_path
+                ))
+                .into());
+            }
+        }
+
         self.create_model_info(&path).await
     }
 
+    /// Whether `path` lies inside `models_dir` once both are canonicalized, so
+    /// symlinks cannot point a model name elsewhere.
+    async fn contains(&self, path: &Path) -> bool {
+        let (Ok(dir), Ok(path)) = (
+            async_fs::canonicalize(&self.models_dir).await,
+            async_fs::canonicalize(path).await,
+        ) else {
+            return false;
+        };
+        path.starts_with(dir)
+    }
+
     async fn find_model_by_name(&self, name: &str) -> Result<PathBuf> {
-        let models = self.list_models().await?;
+        let mut models = self.list_models().await?;
+        // The tenant's own models win over shared ones of the same name
+        if self.tenant.is_some() {
+            models.sort_by_key(|model| namespace_of(&self.models_dir, &model.path).is_none());
+        }
         for model in &models {
             if model.name == name || model.name.starts_with(name) {
                 return Ok(model.path.clone());
             }
         }
-        for ext in ["gguf", "onnx"] {
-            let p = self.models_dir.join(format!("{}.{}", name, ext));
-            if p.exists() {
-                return Ok(p);
+        let dirs = self
+            .tenant
+            .iter()
+            .map(|tenant| self.tenant_dir(tenant))
+            .chain([self.models_dir.clone()]);
+        for dir in dirs {
+            for ext in ["gguf", "onnx"] {
+                let p = dir.join(format!("{}.{}", name, ext));
+                if p.exists() {
+                    return Ok(p);
+                }
             }
         }
-        Err(anyhow::anyhow!(
-            "Model '{}' not found in models directory",
-            name
-        ))
+        let mut message = format!("Model '{}' not found in models directory", name);
+        if !self.aliases.aliases.is_empty() {
+            let names: Vec<&str> = self.aliases.names().collect();
+            message.push_str(&format!("; available aliases: {}", names.join(", ")));
+        }
+        Err(InfernoError::ModelNotFound(message).into())
     }
 
-    async fn create_model_info(&self, path: &Path) -> Result<ModelInfo> {
+    /// Describe the model file at `path` without the `models_dir` checks of
+    /// [`resolve_model`](Self::resolve_model)
+    pub async fn create_model_info(&self, path: &Path) -> Result<ModelInfo> {
         let metadata = async_fs::metadata(path).await?;
         let modified = chrono::DateTime::from(metadata.modified()?);
         let name = path
@@ -378,12 +565,21 @@ impl ModelManager {
 
     pub async fn get_onnx_metadata(&self, path: &Path) -> Result<OnnxMetadata> {
         info!("Reading ONNX metadata from: {}", path.display());
-        // ONNX metadata requires full protobuf parsing; return basic stub for now
+        // The graph's weights are skipped, but seeking past them is still file I/O
+        let path = path.to_path_buf();
+        let graph =
+            tokio::task::spawn_blocking(move || onnx_graph::read_graph_info(&path)).await??;
+        let producer = format!("{} {}", graph.producer_name, graph.producer_version);
         Ok(OnnxMetadata {
-            version: "1.13.0".to_string(),
-            producer: "unknown".to_string(),
-            input_count: 1,
-            output_count: 1,
+            version: format!("IR {}", graph.ir_version),
+            producer: match producer.trim() {
+                "" => "unknown".to_string(),
+                producer => producer.to_string(),
+            },
+            input_count: graph.inputs.len() as u32,
+            output_count: graph.outputs.len() as u32,
+            inputs: graph.inputs,
+            outputs: graph.outputs,
         })
     }
 
@@ -435,6 +631,7 @@ impl ModelManager {
                 use_count: 0,
                 last_used: None,
                 added_at: chrono::Utc::now(),
+                source: None,
             });
         entry.use_count += 1;
         entry.last_used = Some(chrono::Utc::now());
@@ -466,6 +663,7 @@ impl ModelManager {
                 use_count: 0,
                 last_used: None,
                 added_at: chrono::Utc::now(),
+                source: None,
             });
         for tag in tags {
             if !entry.tags.contains(tag) {
@@ -496,11 +694,50 @@ impl ModelManager {
                 use_count: 0,
                 last_used: None,
                 added_at: chrono::Utc::now(),
+                source: None,
             });
         self.save_registry(&registry).await?;
         Ok(())
     }
 
+    /// Record the remote origin of an installed model.
+    pub async fn set_model_source(&self, path: &Path, source: ModelSource) -> Result<()> {
+        let mut registry = self.load_registry().await.unwrap_or_default();
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let key = canonical.to_string_lossy().to_string();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("")
+            .to_string();
+        let entry = registry
+            .entries
+            .entry(key)
+            .or_insert_with(|| RegistryEntry {
+                name,
+                path: path.to_path_buf(),
+                tags: Vec::new(),
+                use_count: 0,
+                last_used: None,
+                added_at: chrono::Utc::now(),
+                source: None,
+            });
+        entry.source = Some(source);
+        self.save_registry(&registry).await?;
+        Ok(())
+    }
+
+    /// Look up the recorded remote origin of an installed model.
+    pub async fn model_source(&self, path: &Path) -> Result<Option<ModelSource>> {
+        let registry = self.load_registry().await?;
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let key = canonical.to_string_lossy().to_string();
+        Ok(registry
+            .entries
+            .get(&key)
+            .and_then(|entry| entry.source.clone()))
+    }
+
     // ── Compatibility ─────────────────────────────────────────────────────────
 
     /// Estimate whether the current system can run this model.
@@ -785,19 +1022,16 @@ impl ModelManager {
 
     // ── Checksum ─────────────────────────────────────────────────────────────
 
+    /// SHA-256 of the file at `path`, served from the process-wide
+    /// [`ChecksumCache`](checksum::ChecksumCache) while the file is unchanged
     pub async fn compute_checksum(&self, path: &Path) -> Result<String> {
-        let mut file = async_fs::File::open(path).await?;
-        let mut hasher = Sha256::new();
-        let mut buffer = vec![0u8; 8192];
-        use tokio::io::AsyncReadExt;
-        loop {
-            let bytes_read = file.read(&mut buffer).await?;
-            if bytes_read == 0 {
-                break;
-            }
-            hasher.update(&buffer[..bytes_read]);
-        }
-        Ok(format!("{:x}", hasher.finalize()))
+        checksum::ChecksumCache::global().checksum(path).await
+    }
+
+    /// SHA-256 of the file at `path` read afresh, for checks that must not trust
+    /// a cached checksum
+    pub async fn recompute_checksum(&self, path: &Path) -> Result<String> {
+        checksum::ChecksumCache::global().rehash(path).await
     }
 }
 
@@ -1087,6 +1321,40 @@ pub async fn record_model_usage(model_path: &Path) {
     }
 }
 
+/// Tenant whose namespace `path` lies in under `models_dir`, or `None` for a
+/// shared model
+fn namespace_of(models_dir: &Path, path: &Path) -> Option<String> {
+    let mut components = path.strip_prefix(models_dir).ok()?.components();
+    if components.next()?.as_os_str() != TENANTS_DIR {
+        return None;
+    }
+    Some(
+        components
+            .next()?
+            .as_os_str()
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+/// Check that a downloaded model file arrived whole before it is installed, so an
+/// interrupted download is never loaded
+///
+/// GGUF files are checked against their own tensor table; other formats carry
+/// nothing to check against.
+pub async fn check_download(path: &Path) -> Result<(), InfernoError> {
+    let is_gguf = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gguf"));
+    if !is_gguf {
+        return Ok(());
+    }
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || crate::optimization::gguf::check_integrity(&path))
+        .await
+        .map_err(|e| InfernoError::Model(format!("Download check failed: {}", e)))?
+}
+
 /// Walk up from `model_path` to find the directory containing `.inferno_registry.json`
 /// or an `.inferno_cache` subdirectory. Falls back to the immediate parent directory.
 fn infer_models_dir(model_path: &Path) -> PathBuf {
@@ -1145,6 +1413,232 @@ mod tests {
         assert_eq!(models[0].backend_type, "gguf");
     }
 
+    #[tokio::test]
+    async fn test_resolve_model_rejects_traversal() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let models_dir = temp_dir.path().join("models");
+        fs::create_dir_all(&models_dir).await.unwrap();
+        fs::write(temp_dir.path().join("secret.gguf"), b"GGUF\x03\x00\x00\x00")
+            .await
+            .unwrap();
+
+        let manager = ModelManager::new(&models_dir).with_external_paths(true);
+        let err = manager.resolve_model("../secret.gguf").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InfernoError>(),
+            Some(InfernoError::SecurityValidation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_model_in_models_dir() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let models_dir = temp_dir.path().join("models");
+        fs::create_dir_all(models_dir.join("llama")).await.unwrap();
+        fs::write(models_dir.join("llama/chat.gguf"), b"GGUF\x03\x00\x00\x00")
+            .await
+            .unwrap();
+
+        let manager = ModelManager::new(&models_dir);
+        let by_name = manager.resolve_model("chat").await.unwrap();
+        assert_eq!(by_name.name, "chat.gguf");
+        let by_path = manager.resolve_model("llama/chat.gguf").await.unwrap();
+        assert_eq!(by_path.name, "chat.gguf");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_model_external_path_requires_flag() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let models_dir = temp_dir.path().join("models");
+        fs::create_dir_all(&models_dir).await.unwrap();
+        let external = temp_dir.path().join("external.gguf");
+        fs::write(&external, b"GGUF\x03\x00\x00\x00").await.unwrap();
+        let external = external.to_string_lossy().to_string();
+
+        let err = ModelManager::new(&models_dir)
+            .resolve_model(&external)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InfernoError>(),
+            Some(InfernoError::SecurityValidation(_))
+        ));
+
+        let info = ModelManager::new(&models_dir)
+            .with_external_paths(true)
+            .resolve_model(&external)
+            .await
+            .unwrap();
+        assert_eq!(info.name, "external.gguf");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_model_relative_to_working_directory_first() {
+        // Relative to the working directory, which the tests run in
+        let temp_dir = tempfile::tempdir_in(".").expect("Failed to create temp dir");
+        let models_dir = temp_dir.path().join("models");
+        fs::create_dir_all(&models_dir).await.unwrap();
+        fs::write(models_dir.join("chat.gguf"), b"GGUF\x03\x00\x00\x00")
+            .await
+            .unwrap();
+        fs::write(temp_dir.path().join("other.gguf"), b"GGUF\x03\x00\x00\x00")
+            .await
+            .unwrap();
+        let manager = ModelManager::new(&models_dir);
+
+        let inside = models_dir.join("chat.gguf");
+        assert!(inside.is_relative());
+        let info = manager
+            .resolve_model(&inside.to_string_lossy())
+            .await
+            .unwrap();
+        assert_eq!(info.name, "chat.gguf");
+
+        // Outside the models directory it still needs external paths allowed
+        let outside = temp_dir
+            .path()
+            .join("other.gguf")
+            .to_string_lossy()
+            .to_string();
+        let err = manager.resolve_model(&outside).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InfernoError>(),
+            Some(InfernoError::SecurityValidation(_))
+        ));
+        let info = manager
+            .with_external_paths(true)
+            .resolve_model(&outside)
+            .await
+            .unwrap();
+        assert_eq!(info.name, "other.gguf");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_model_alias() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let models_dir = temp_dir.path().join("models");
+        fs::create_dir_all(models_dir.join("llama")).await.unwrap();
+        fs::write(models_dir.join("llama/chat.gguf"), b"GGUF\x03\x00\x00\x00")
+            .await
+            .unwrap();
+        fs::write(models_dir.join("minilm.onnx"), b"onnx")
+            .await
+            .unwrap();
+
+        let aliases: ModelAliases = toml::from_str(
+            r#"
+            default = "gpt-4"
+
+            [aliases]
+            gpt-4 = "llama/chat.gguf"
+            embed = { file = "minilm.onnx", backend = "gguf" }
+            "#,
+        )
+        .unwrap();
+        let manager = ModelManager::new(&models_dir).with_aliases(aliases);
+
+        let chat = manager.resolve_model("gpt-4").await.unwrap();
+        assert_eq!(chat.name, "chat.gguf");
+        assert!(chat.path.ends_with("llama/chat.gguf"));
+        let default = manager.resolve_model("").await.unwrap();
+        assert_eq!(default.path, chat.path);
+        let embed = manager.resolve_model("embed").await.unwrap();
+        assert_eq!(embed.name, "minilm.onnx");
+        assert_eq!(embed.backend_type, "gguf");
+        // Files can still be named directly
+        assert!(manager.resolve_model("chat").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_model_unknown_alias_lists_aliases() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let models_dir = temp_dir.path().join("models");
+        fs::create_dir_all(&models_dir).await.unwrap();
+
+        let mut aliases = ModelAliases::default();
+        for name in ["gpt-4", "embed"] {
+            aliases.aliases.insert(
+                name.to_string(),
+                AliasTarget::File(format!("{}.gguf", name)),
+            );
+        }
+        let err = ModelManager::new(&models_dir)
+            .with_aliases(aliases)
+            .resolve_model("gpt-5")
+            .await
+            .unwrap_err();
+        match err.downcast_ref::<InfernoError>() {
+            Some(InfernoError::ModelNotFound(message)) => {
+                assert!(message.contains("'gpt-5'"));
+                assert!(message.ends_with("available aliases: embed, gpt-4"));
+            }
+            other => panic!("expected ModelNotFound, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tenant_models_are_isolated() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let models_dir = temp_dir.path().join("models");
+        let manager = ModelManager::new(&models_dir);
+        fs::create_dir_all(manager.tenant_dir("a")).await.unwrap();
+        fs::write(
+            manager.tenant_dir("a").join("private.gguf"),
+            b"GGUF\x03\x00\x00\x00",
+        )
+        .await
+        .unwrap();
+        fs::write(models_dir.join("shared.gguf"), b"GGUF\x03\x00\x00\x00")
+            .await
+            .unwrap();
+
+        let (a, b) = (manager.for_tenant("a"), manager.for_tenant("b"));
+        let names = |models: Vec<ModelInfo>| {
+            let mut names: Vec<String> = models.into_iter().map(|model| model.name).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            names(a.list_models().await.unwrap()),
+            ["private.gguf", "shared.gguf"]
+        );
+        assert_eq!(names(b.list_models().await.unwrap()), ["shared.gguf"]);
+
+        assert!(a.resolve_model("private").await.is_ok());
+        assert!(a.resolve_model("shared").await.is_ok());
+        assert!(b.resolve_model("shared").await.is_ok());
+        assert!(matches!(
+            b.resolve_model("private")
+                .await
+                .unwrap_err()
+                .downcast_ref::<InfernoError>(),
+            Some(InfernoError::ModelNotFound(_))
+        ));
+        let absolute = manager.tenant_dir("a").join("private.gguf");
+        for reference in [
+            "tenants/a/private.gguf".to_string(),
+            absolute.display().to_string(),
+        ] {
+            let err = b.resolve_model(&reference).await.unwrap_err();
+            assert!(
+                matches!(
+                    err.downcast_ref::<InfernoError>(),
+                    Some(InfernoError::SecurityValidation(_))
+                ),
+                "{}: {}",
+                reference,
+                err
+            );
+        }
+        // Without a tenant every model is reachable, as before
+        assert!(
+            manager
+                .resolve_model("tenants/a/private.gguf")
+                .await
+                .is_ok()
+        );
+    }
+
     #[tokio::test]
     async fn test_recursive_discovery() {
         let temp_dir = tempdir().expect("Failed to create temp dir");
@@ -1164,6 +1658,41 @@ mod tests {
         assert_eq!(models.len(), 2);
     }
 
+    #[tokio::test]
+    async fn test_list_models_modified_since() {
+        let temp_dir = tempdir().expect("Failed to create temp dir");
+        let models_dir = temp_dir.path().join("models");
+        fs::create_dir_all(&models_dir).await.unwrap();
+
+        let manager = ModelManager::new(&models_dir);
+        let old_path = models_dir.join("old.gguf");
+        fs::write(&old_path, b"GGUF\x03\x00\x00\x00data")
+            .await
+            .unwrap();
+        fs::write(models_dir.join("new.gguf"), b"GGUF\x03\x00\x00\x00data")
+            .await
+            .unwrap();
+        let week_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(7 * 86_400);
+        std::fs::File::options()
+            .write(true)
+            .open(&old_path)
+            .unwrap()
+            .set_modified(week_ago)
+            .unwrap();
+
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(1);
+        let models = manager.list_models_modified_since(cutoff).await.unwrap();
+        let names: Vec<&str> = models.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["new.gguf"]);
+
+        let everything = cutoff - chrono::Duration::days(30);
+        let models = manager
+            .list_models_modified_since(everything)
+            .await
+            .unwrap();
+        assert_eq!(models.len(), 2);
+    }
+
     #[tokio::test]
     async fn test_model_validation() {
         let temp_dir = tempdir().expect("Failed to create temp dir");
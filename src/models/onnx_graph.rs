@@ -0,0 +1,479 @@
+//! Inputs and outputs of an ONNX model
+//!
+//! An ONNX file is a protobuf `ModelProto` whose `graph` lists the tensors the
+//! model takes and returns, each with a name, an element type and a shape whose
+//! dimensions are fixed sizes or named symbols such as `batch`. The graph's
+//! weights are stored in it before the inputs and outputs, so the file is read
+//! field by field, seeking past the weights rather than loading them.
+//!
+//! Weights that older exporters also list as graph inputs are left out: they
+//! are not something a caller provides.
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Longest name or string field read from the file
+const MAX_STRING_LEN: u64 = 64 * 1024;
+
+/// One dimension of a tensor's shape
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TensorDim {
+    Fixed(i64),
+    /// A size named in the model, such as `batch` or `sequence`
+    Symbolic(String),
+    Unknown,
+}
+
+impl fmt::Display for TensorDim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(size) => write!(f, "{}", size),
+            Self::Symbolic(name) => write!(f, "{}", name),
+            Self::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+/// Name, element type and shape of a graph input or output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TensorSpec {
+    pub name: String,
+    pub dtype: String,
+    /// `None` when the model leaves the shape, even its rank, open
+    pub shape: Option<Vec<TensorDim>>,
+}
+
+impl fmt::Display for TensorSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.dtype)?;
+        match &self.shape {
+            Some(shape) => {
+                let dims: Vec<String> = shape.iter().map(ToString::to_string).collect();
+                write!(f, " [{}]", dims.join(", "))
+            }
+            None => write!(f, " [unknown shape]"),
+        }
+    }
+}
+
+/// What an ONNX file says about its model and graph
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnnxGraphInfo {
+    pub ir_version: u64,
+    pub producer_name: String,
+    pub producer_version: String,
+    pub inputs: Vec<TensorSpec>,
+    pub outputs: Vec<TensorSpec>,
+}
+
+/// Read the graph inputs and outputs of the ONNX model at `path`
+pub fn read_graph_info(path: &Path) -> Result<OnnxGraphInfo> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    parse(BufReader::new(file), len)
+}
+
+/// Parse a `ModelProto` of `len` bytes from `reader`
+pub fn parse<R: Read + Seek>(reader: R, len: u64) -> Result<OnnxGraphInfo> {
+    let mut proto = Proto {
+        reader,
+        pos: 0,
+        len,
+    };
+    let mut info = OnnxGraphInfo::default();
+    let mut has_graph = false;
+    while let Some((field, wire)) = proto.key(len)? {
+        match (field, wire) {
+            (1, WIRE_VARINT) => info.ir_version = proto.varint()?,
+            (2, WIRE_LEN) => info.producer_name = proto.string()?,
+            (3, WIRE_LEN) => info.producer_version = proto.string()?,
+            (7, WIRE_LEN) => {
+                let end = proto.message_end()?;
+                graph(&mut proto, end, &mut info)?;
+                has_graph = true;
+            }
+            _ => proto.skip(wire)?,
+        }
+    }
+    if !has_graph {
+        bail!("No graph found in ONNX model");
+    }
+    Ok(info)
+}
+
+fn graph<R: Read + Seek>(proto: &mut Proto<R>, end: u64, info: &mut OnnxGraphInfo) -> Result<()> {
+    let mut weights = HashSet::new();
+    while let Some((field, wire)) = proto.key(end)? {
+        match (field, wire) {
+            (5, WIRE_LEN) => {
+                let end = proto.message_end()?;
+                if let Some(name) = tensor_name(proto, end)? {
+                    weights.insert(name);
+                }
+            }
+            (11, WIRE_LEN) => {
+                let end = proto.message_end()?;
+                info.inputs.push(value_info(proto, end)?);
+            }
+            (12, WIRE_LEN) => {
+                let end = proto.message_end()?;
+                info.outputs.push(value_info(proto, end)?);
+            }
+            _ => proto.skip(wire)?,
+        }
+    }
+    info.inputs.retain(|input| !weights.contains(&input.name));
+    Ok(())
+}
+
+/// Name of a `TensorProto`, skipping its data
+fn tensor_name<R: Read + Seek>(proto: &mut Proto<R>, end: u64) -> Result<Option<String>> {
+    let mut name = None;
+    while let Some((field, wire)) = proto.key(end)? {
+        match (field, wire) {
+            (8, WIRE_LEN) => name = Some(proto.string()?),
+            _ => proto.skip(wire)?,
+        }
+    }
+    Ok(name)
+}
+
+/// A `ValueInfoProto`: a name and a type
+fn value_info<R: Read + Seek>(proto: &mut Proto<R>, end: u64) -> Result<TensorSpec> {
+    let mut spec = TensorSpec {
+        name: String::new(),
+        dtype: "unknown".to_string(),
+        shape: None,
+    };
+    while let Some((field, wire)) = proto.key(end)? {
+        match (field, wire) {
+            (1, WIRE_LEN) => spec.name = proto.string()?,
+            (2, WIRE_LEN) => {
+                let end = proto.message_end()?;
+                // Only tensor types are described; sequences and maps stay unknown
+                while let Some((field, wire)) = proto.key(end)? {
+                    match (field, wire) {
+                        (1, WIRE_LEN) => {
+                            let end = proto.message_end()?;
+                            tensor_type(proto, end, &mut spec)?;
+                        }
+                        _ => proto.skip(wire)?,
+                    }
+                }
+            }
+            _ => proto.skip(wire)?,
+        }
+    }
+    Ok(spec)
+}
+
+fn tensor_type<R: Read + Seek>(
+    proto: &mut Proto<R>,
+    end: u64,
+    spec: &mut TensorSpec,
+) -> Result<()> {
+    while let Some((field, wire)) = proto.key(end)? {
+        match (field, wire) {
+            (1, WIRE_VARINT) => spec.dtype = dtype_name(proto.varint()?),
+            (2, WIRE_LEN) => {
+                let end = proto.message_end()?;
+                let mut shape = Vec::new();
+                while let Some((field, wire)) = proto.key(end)? {
+                    match (field, wire) {
+                        (1, WIRE_LEN) => {
+                            let end = proto.message_end()?;
+                            shape.push(dimension(proto, end)?);
+                        }
+                        _ => proto.skip(wire)?,
+                    }
+                }
+                spec.shape = Some(shape);
+            }
+            _ => proto.skip(wire)?,
+        }
+    }
+    Ok(())
+}
+
+fn dimension<R: Read + Seek>(proto: &mut Proto<R>, end: u64) -> Result<TensorDim> {
+    let mut dim = TensorDim::Unknown;
+    while let Some((field, wire)) = proto.key(end)? {
+        match (field, wire) {
+            (1, WIRE_VARINT) => dim = TensorDim::Fixed(proto.varint()? as i64),
+            (2, WIRE_LEN) => dim = TensorDim::Symbolic(proto.string()?),
+            _ => proto.skip(wire)?,
+        }
+    }
+    Ok(dim)
+}
+
+/// Name of an ONNX `TensorProto.DataType`
+fn dtype_name(elem_type: u64) -> String {
+    let name = match elem_type {
+        1 => "float32",
+        2 => "uint8",
+        3 => "int8",
+        4 => "uint16",
+        5 => "int16",
+        6 => "int32",
+        7 => "int64",
+        8 => "string",
+        9 => "bool",
+        10 => "float16",
+        11 => "float64",
+        12 => "uint32",
+        13 => "uint64",
+        14 => "complex64",
+        15 => "complex128",
+        16 => "bfloat16",
+        17 => "float8e4m3fn",
+        18 => "float8e4m3fnuz",
+        19 => "float8e5m2",
+        20 => "float8e5m2fnuz",
+        21 => "uint4",
+        22 => "int4",
+        other => return format!("type {}", other),
+    };
+    name.to_string()
+}
+
+const WIRE_VARINT: u8 = 0;
+const WIRE_FIXED64: u8 = 1;
+const WIRE_LEN: u8 = 2;
+const WIRE_FIXED32: u8 = 5;
+
+/// Protobuf wire format read from a seekable source
+struct Proto<R> {
+    reader: R,
+    pos: u64,
+    len: u64,
+}
+
+impl<R: Read + Seek> Proto<R> {
+    /// Next field number and wire type, or `None` at `end`
+    fn key(&mut self, end: u64) -> Result<Option<(u64, u8)>> {
+        if self.pos >= end {
+            if self.pos > end {
+                bail!("Malformed ONNX protobuf: field overruns its message");
+            }
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        Ok(Some((key >> 3, (key & 0x07) as u8)))
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let mut byte = [0u8];
+            self.reader.read_exact(&mut byte)?;
+            self.pos += 1;
+            value |= u64::from(byte[0] & 0x7f) << shift;
+            if byte[0] & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(anyhow!("Malformed ONNX protobuf: varint too long"))
+    }
+
+    /// End offset of the length-delimited message that starts here
+    fn message_end(&mut self) -> Result<u64> {
+        let len = self.varint()?;
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.len)
+            .ok_or_else(|| anyhow!("ONNX file is truncated"))?;
+        Ok(end)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.varint()?;
+        if len > MAX_STRING_LEN {
+            bail!("ONNX string of {} bytes exceeds sanity limit", len);
+        }
+        let mut bytes = vec![0u8; len as usize];
+        self.reader.read_exact(&mut bytes)?;
+        self.pos += len;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn skip(&mut self, wire: u8) -> Result<()> {
+        let len = match wire {
+            WIRE_VARINT => {
+                self.varint()?;
+                return Ok(());
+            }
+            WIRE_FIXED64 => 8,
+            WIRE_LEN => self.varint()?,
+            WIRE_FIXED32 => 4,
+            other => bail!("Unsupported protobuf wire type {} in ONNX file", other),
+        };
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.len)
+            .ok_or_else(|| anyhow!("ONNX file is truncated"))?;
+        self.reader.seek(SeekFrom::Current(len as i64))?;
+        self.pos = end;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn varint(out: &mut Vec<u8>, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(byte);
+                return;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    fn field_varint(out: &mut Vec<u8>, field: u64, value: u64) {
+        varint(out, field << 3);
+        varint(out, value);
+    }
+
+    fn field_bytes(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+        varint(out, (field << 3) | 2);
+        varint(out, bytes.len() as u64);
+        out.extend_from_slice(bytes);
+    }
+
+    enum Dim {
+        Fixed(u64),
+        Named(&'static str),
+    }
+
+    fn value_info(name: &str, elem_type: u64, dims: &[Dim]) -> Vec<u8> {
+        let mut shape = Vec::new();
+        for dim in dims {
+            let mut dimension = Vec::new();
+            match dim {
+                Dim::Fixed(size) => field_varint(&mut dimension, 1, *size),
+                Dim::Named(name) => field_bytes(&mut dimension, 2, name.as_bytes()),
+            }
+            field_bytes(&mut shape, 1, &dimension);
+        }
+        let mut tensor = Vec::new();
+        field_varint(&mut tensor, 1, elem_type);
+        field_bytes(&mut tensor, 2, &shape);
+        let mut type_proto = Vec::new();
+        field_bytes(&mut type_proto, 1, &tensor);
+
+        let mut info = Vec::new();
+        field_bytes(&mut info, 1, name.as_bytes());
+        field_bytes(&mut info, 2, &type_proto);
+        info
+    }
+
+    /// A sentence encoder taking token ids and an attention mask and returning
+    /// one embedding per sequence, with its weight also listed as an input
+    fn encoder_model() -> Vec<u8> {
+        let mut weight = Vec::new();
+        field_varint(&mut weight, 1, 384);
+        field_varint(&mut weight, 2, 1);
+        field_bytes(&mut weight, 8, b"embeddings.weight");
+        field_bytes(&mut weight, 9, &vec![0u8; 4096]);
+
+        let mut node = Vec::new();
+        field_bytes(&mut node, 4, b"Gather");
+
+        let mut graph = Vec::new();
+        field_bytes(&mut graph, 1, &node);
+        field_bytes(&mut graph, 2, b"encoder");
+        field_bytes(&mut graph, 5, &weight);
+        let batch_seq = [Dim::Named("batch"), Dim::Named("sequence")];
+        field_bytes(&mut graph, 11, &value_info("input_ids", 7, &batch_seq));
+        field_bytes(&mut graph, 11, &value_info("attention_mask", 7, &batch_seq));
+        field_bytes(
+            &mut graph,
+            11,
+            &value_info("embeddings.weight", 1, &[Dim::Fixed(384)]),
+        );
+        field_bytes(
+            &mut graph,
+            12,
+            &value_info(
+                "sentence_embedding",
+                1,
+                &[Dim::Named("batch"), Dim::Fixed(384)],
+            ),
+        );
+
+        let mut model = Vec::new();
+        field_varint(&mut model, 1, 8);
+        field_bytes(&mut model, 2, b"pytorch");
+        field_bytes(&mut model, 3, b"2.1.0");
+        field_bytes(&mut model, 7, &graph);
+        model
+    }
+
+    #[test]
+    fn test_reads_input_and_output_tensor_specs() {
+        let model = encoder_model();
+        let info = parse(Cursor::new(&model), model.len() as u64).unwrap();
+
+        assert_eq!(info.ir_version, 8);
+        assert_eq!(info.producer_name, "pytorch");
+        assert_eq!(info.producer_version, "2.1.0");
+        let batch_seq = Some(vec![
+            TensorDim::Symbolic("batch".to_string()),
+            TensorDim::Symbolic("sequence".to_string()),
+        ]);
+        assert_eq!(
+            info.inputs,
+            [
+                TensorSpec {
+                    name: "input_ids".to_string(),
+                    dtype: "int64".to_string(),
+                    shape: batch_seq.clone(),
+                },
+                TensorSpec {
+                    name: "attention_mask".to_string(),
+                    dtype: "int64".to_string(),
+                    shape: batch_seq,
+                },
+            ]
+        );
+        assert_eq!(
+            info.outputs,
+            [TensorSpec {
+                name: "sentence_embedding".to_string(),
+                dtype: "float32".to_string(),
+                shape: Some(vec![
+                    TensorDim::Symbolic("batch".to_string()),
+                    TensorDim::Fixed(384),
+                ]),
+            }]
+        );
+        assert_eq!(
+            info.outputs[0].to_string(),
+            "sentence_embedding: float32 [batch, 384]"
+        );
+
+        // A file cut off inside the graph is reported rather than misread
+        let truncated = &model[..model.len() - 20];
+        assert!(parse(Cursor::new(truncated), truncated.len() as u64).is_err());
+
+        // As is an unknown field claiming more bytes than any file holds
+        let mut overlong = model.clone();
+        varint(&mut overlong, (99 << 3) | 2);
+        varint(&mut overlong, u64::MAX);
+        assert!(parse(Cursor::new(&overlong), overlong.len() as u64).is_err());
+    }
+}
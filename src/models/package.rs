@@ -0,0 +1,457 @@
+//! Upgrades for models installed from a remote repository.
+//!
+//! `inferno models install` records where a model came from (repo, file and the
+//! revision that was downloaded) in the registry. The [`PackageUpgrader`] compares
+//! that revision with the latest one published by the repository and, when it has
+//! moved, downloads the replacement into a staging area next to the models
+//! directory, validates it, and only then swaps it into place. The installed file
+//! is left untouched until the new one has verified.
+
+use super::ModelManager;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::fs as async_fs;
+use tracing::{info, warn};
+
+/// Default HuggingFace Hub endpoint.
+pub const HUGGINGFACE_BASE_URL: &str = "https://huggingface.co";
+
+/// Origin of an installed model, persisted in its registry entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelSource {
+    /// Repository identifier, e.g. `TheBloke/Llama-2-7B-GGUF`
+    pub repo_id: String,
+    /// File within the repository that was installed
+    pub filename: String,
+    /// Repository revision (commit SHA) the installed file was downloaded from
+    pub revision: String,
+}
+
+/// A remote repository that publishes model files.
+#[async_trait::async_trait]
+pub trait ModelRepository: Send + Sync {
+    /// Return the latest published revision of `repo_id`.
+    async fn latest_revision(&self, repo_id: &str) -> Result<String>;
+
+    /// Download the file described by `source` to `dest`.
+    async fn download(&self, source: &ModelSource, dest: &Path) -> Result<()>;
+}
+
+/// [`ModelRepository`] backed by the HuggingFace Hub HTTP API.
+pub struct HuggingFaceRepository {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl Default for HuggingFaceRepository {
+    fn default() -> Self {
+        Self::new(HUGGINGFACE_BASE_URL)
+    }
+}
+
+impl HuggingFaceRepository {
+    pub fn new(base_url: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .user_agent("inferno/1.0")
+            .build()
+            .unwrap_or_default();
+        Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// URL that serves `filename` from `repo_id` at `revision`.
+    pub fn file_url(&self, repo_id: &str, revision: &str, filename: &str) -> String {
+        format!(
+            "{}/{}/resolve/{}/{}",
+            self.base_url, repo_id, revision, filename
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl ModelRepository for HuggingFaceRepository {
+    async fn latest_revision(&self, repo_id: &str) -> Result<String> {
+        let url = format!("{}/api/models/{}", self.base_url, repo_id);
+        let resp = self.client.get(&url).send().await?;
+        if !resp.status().is_success() {
+            anyhow::bail!("Cannot fetch repo '{}': HTTP {}", repo_id, resp.status());
+        }
+        let raw: serde_json::Value = resp.json().await?;
+        raw["sha"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| anyhow!("No revision ('sha') in response for repo '{}'", repo_id))
+    }
+
+    async fn download(&self, source: &ModelSource, dest: &Path) -> Result<()> {
+        use futures::StreamExt;
+        use tokio::io::AsyncWriteExt;
+
+        let url = self.file_url(&source.repo_id, &source.revision, &source.filename);
+        let resp = self.client.get(&url).send().await?;
+        if !resp.status().is_success() {
+            anyhow::bail!("Download failed: HTTP {}", resp.status());
+        }
+
+        let mut file = async_fs::File::create(dest).await?;
+        let mut stream = resp.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            file.write_all(&chunk?).await?;
+        }
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+/// An installed model whose repository has published a newer revision.
+#[derive(Debug, Clone)]
+pub struct AvailableUpgrade {
+    pub name: String,
+    pub path: PathBuf,
+    pub current: ModelSource,
+    pub latest_revision: String,
+}
+
+impl AvailableUpgrade {
+    /// Whether `name` is this model's file name, file stem or path
+    fn is_named(&self, name: &str) -> bool {
+        self.name == name
+            || self.path.file_stem().is_some_and(|stem| stem == name)
+            || self.path == Path::new(name)
+    }
+}
+
+/// What happened to a single model during an upgrade run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpgradeStatus {
+    /// Dry run: the upgrade would have been applied
+    Planned,
+    /// The new revision was downloaded, verified and swapped in
+    Upgraded,
+    /// The upgrade failed; the installed version was left in place
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct UpgradeResult {
+    pub upgrade: AvailableUpgrade,
+    pub status: UpgradeStatus,
+}
+
+/// Checks installed models for newer revisions and applies upgrades.
+pub struct PackageUpgrader<R: ModelRepository> {
+    manager: ModelManager,
+    repository: R,
+}
+
+impl<R: ModelRepository> PackageUpgrader<R> {
+    pub fn new(manager: ModelManager, repository: R) -> Self {
+        Self {
+            manager,
+            repository,
+        }
+    }
+
+    /// List installed models whose source repository has a newer revision.
+    ///
+    /// Models without a recorded source (local files, direct URL installs) are skipped.
+    /// A repository that cannot be reached is logged and skipped rather than failing
+    /// the whole check.
+    pub async fn check_upgrades(&self) -> Result<Vec<AvailableUpgrade>> {
+        let registry = self.manager.load_registry().await.unwrap_or_default();
+        let mut entries: Vec<_> = registry.entries.values().collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut upgrades = Vec::new();
+        for entry in entries {
+            let Some(source) = &entry.source else {
+                continue;
+            };
+            if !entry.path.exists() {
+                continue;
+            }
+            match self.repository.latest_revision(&source.repo_id).await {
+                Ok(latest) if latest != source.revision => upgrades.push(AvailableUpgrade {
+                    name: entry.name.clone(),
+                    path: entry.path.clone(),
+                    current: source.clone(),
+                    latest_revision: latest,
+                }),
+                Ok(_) => {}
+                Err(e) => warn!("Could not check '{}' for upgrades: {}", source.repo_id, e),
+            }
+        }
+        Ok(upgrades)
+    }
+
+    /// Upgrade every outdated model, or only the one named `model` when given.
+    ///
+    /// `model` is an alias or the model's exact file name, with or without its
+    /// extension, or path; a name that only prefixes a model's does not select it.
+    ///
+    /// With `dry_run` set, nothing is downloaded or modified; each pending upgrade is
+    /// reported as [`UpgradeStatus::Planned`].
+    pub async fn upgrade(&self, model: Option<&str>, dry_run: bool) -> Result<Vec<UpgradeResult>> {
+        let wanted = model.map(|model| {
+            self.manager
+                .aliases()
+                .get(model)
+                .map_or(model, |target| target.file())
+        });
+        let mut results = Vec::new();
+        for upgrade in self.check_upgrades().await? {
+            if let Some(wanted) = wanted
+                && !upgrade.is_named(wanted)
+            {
+                continue;
+            }
+
+            let status = if dry_run {
+                UpgradeStatus::Planned
+            } else {
+                match self.apply(&upgrade).await {
+                    Ok(()) => UpgradeStatus::Upgraded,
+                    Err(e) => {
+                        warn!("Upgrade of '{}' failed: {}", upgrade.name, e);
+                        UpgradeStatus::Failed(e.to_string())
+                    }
+                }
+            };
+            results.push(UpgradeResult { upgrade, status });
+        }
+        Ok(results)
+    }
+
+    /// Download, verify and swap in a single upgrade.
+    pub async fn apply(&self, upgrade: &AvailableUpgrade) -> Result<()> {
+        let staging_dir = self.manager.models_dir().join(".inferno_staging");
+        async_fs::create_dir_all(&staging_dir).await?;
+
+        let file_name = upgrade
+            .path
+            .file_name()
+            .ok_or_else(|| anyhow!("Invalid model path: {}", upgrade.path.display()))?;
+        let staged = staging_dir.join(file_name);
+        let backup = staging_dir.join(format!("{}.bak", file_name.to_string_lossy()));
+
+        let target = ModelSource {
+            revision: upgrade.latest_revision.clone(),
+            ..upgrade.current.clone()
+        };
+
+        info!(
+            "Downloading '{}' at revision {}",
+            upgrade.name, target.revision
+        );
+        if let Err(e) = self.repository.download(&target, &staged).await {
+            let _ = async_fs::remove_file(&staged).await;
+            return Err(e);
+        }
+
+        if let Err(e) = crate::models::check_download(&staged).await {
+            let _ = async_fs::remove_file(&staged).await;
+            anyhow::bail!(
+                "Downloaded revision {} of '{}' is incomplete; keeping installed version: {}",
+                target.revision,
+                upgrade.name,
+                e
+            );
+        }
+        if !self.manager.validate_model(&staged).await? {
+            let _ = async_fs::remove_file(&staged).await;
+            anyhow::bail!(
+                "Downloaded revision {} of '{}' failed validation; keeping installed version",
+                target.revision,
+                upgrade.name
+            );
+        }
+
+        // Both files live under models_dir, so these renames stay on one filesystem.
+        async_fs::rename(&upgrade.path, &backup).await?;
+        if let Err(e) = async_fs::rename(&staged, &upgrade.path).await {
+            async_fs::rename(&backup, &upgrade.path).await?;
+            let _ = async_fs::remove_file(&staged).await;
+            return Err(e.into());
+        }
+        if let Err(e) = async_fs::remove_file(&backup).await {
+            warn!("Could not remove backup {}: {}", backup.display(), e);
+        }
+
+        self.manager.set_model_source(&upgrade.path, target).await?;
+        info!("Upgraded '{}'", upgrade.name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+    use tokio::fs;
+
+    /// A complete GGUF file with no tensors or metadata, and some trailing bytes
+    const NEW_MODEL: &[u8] = b"GGUF\x03\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x00\x00\x00\x00\x00\x00\x00new";
+
+    /// In-memory repository serving fixed revisions and file contents.
+    struct MockRepository {
+        revisions: HashMap<String, String>,
+        content: Vec<u8>,
+    }
+
+    #[async_trait::async_trait]
+    impl ModelRepository for MockRepository {
+        async fn latest_revision(&self, repo_id: &str) -> Result<String> {
+            self.revisions
+                .get(repo_id)
+                .cloned()
+                .ok_or_else(|| anyhow!("unknown repo {}", repo_id))
+        }
+
+        async fn download(&self, _source: &ModelSource, dest: &Path) -> Result<()> {
+            fs::write(dest, &self.content).await?;
+            Ok(())
+        }
+    }
+
+    async fn install(manager: &ModelManager, name: &str, repo: &str, revision: &str) -> PathBuf {
+        let path = manager.models_dir().join(name);
+        fs::write(&path, b"GGUF\x03\x00\x00\x00old").await.unwrap();
+        manager.register_model(&path).await.unwrap();
+        manager
+            .set_model_source(
+                &path,
+                ModelSource {
+                    repo_id: repo.to_string(),
+                    filename: name.to_string(),
+                    revision: revision.to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        path
+    }
+
+    fn repository(revisions: &[(&str, &str)], content: &[u8]) -> MockRepository {
+        MockRepository {
+            revisions: revisions
+                .iter()
+                .map(|(r, s)| (r.to_string(), s.to_string()))
+                .collect(),
+            content: content.to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_detects_available_upgrade() {
+        let dir = tempdir().unwrap();
+        let manager = ModelManager::new(dir.path());
+        install(&manager, "a.gguf", "org/a", "rev1").await;
+        install(&manager, "b.gguf", "org/b", "rev1").await;
+
+        let repo = repository(
+            &[("org/a", "rev2"), ("org/b", "rev1")],
+            b"GGUF\x03\x00\x00\x00new",
+        );
+        let upgrader = PackageUpgrader::new(manager, repo);
+
+        let upgrades = upgrader.check_upgrades().await.unwrap();
+        assert_eq!(upgrades.len(), 1);
+        assert_eq!(upgrades[0].name, "a.gguf");
+        assert_eq!(upgrades[0].current.revision, "rev1");
+        assert_eq!(upgrades[0].latest_revision, "rev2");
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_matches_only_the_named_model() {
+        let dir = tempdir().unwrap();
+        let manager = ModelManager::new(dir.path());
+        install(&manager, "llama.gguf", "org/llama", "rev1").await;
+        install(&manager, "llama-2.gguf", "org/llama-2", "rev1").await;
+
+        let repo = repository(
+            &[("org/llama", "rev2"), ("org/llama-2", "rev2")],
+            b"GGUF\x03\x00\x00\x00new",
+        );
+        let upgrader = PackageUpgrader::new(manager, repo);
+
+        for name in ["llama", "llama.gguf"] {
+            let results = upgrader.upgrade(Some(name), true).await.unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].upgrade.name, "llama.gguf");
+        }
+        assert!(
+            upgrader
+                .upgrade(Some("lla"), true)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_leaves_model_untouched() {
+        let dir = tempdir().unwrap();
+        let manager = ModelManager::new(dir.path());
+        let path = install(&manager, "a.gguf", "org/a", "rev1").await;
+
+        let repo = repository(&[("org/a", "rev2")], b"GGUF\x03\x00\x00\x00new");
+        let upgrader = PackageUpgrader::new(manager.clone(), repo);
+
+        let results = upgrader.upgrade(None, true).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, UpgradeStatus::Planned);
+        assert_eq!(fs::read(&path).await.unwrap(), b"GGUF\x03\x00\x00\x00old");
+        assert_eq!(
+            manager.model_source(&path).await.unwrap().unwrap().revision,
+            "rev1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_swaps_in_verified_file() {
+        let dir = tempdir().unwrap();
+        let manager = ModelManager::new(dir.path());
+        let path = install(&manager, "a.gguf", "org/a", "rev1").await;
+
+        let repo = repository(&[("org/a", "rev2")], NEW_MODEL);
+        let upgrader = PackageUpgrader::new(manager.clone(), repo);
+
+        let results = upgrader.upgrade(None, false).await.unwrap();
+        assert_eq!(results[0].status, UpgradeStatus::Upgraded);
+        assert_eq!(fs::read(&path).await.unwrap(), NEW_MODEL);
+        assert_eq!(
+            manager.model_source(&path).await.unwrap().unwrap().revision,
+            "rev2"
+        );
+
+        let mut staging = fs::read_dir(dir.path().join(".inferno_staging"))
+            .await
+            .unwrap();
+        assert!(staging.next_entry().await.unwrap().is_none());
+        assert!(upgrader.check_upgrades().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_download_keeps_installed_version() {
+        let dir = tempdir().unwrap();
+        let manager = ModelManager::new(dir.path());
+        let path = install(&manager, "a.gguf", "org/a", "rev1").await;
+
+        let repo = repository(&[("org/a", "rev2")], b"not a model");
+        let upgrader = PackageUpgrader::new(manager.clone(), repo);
+
+        let results = upgrader.upgrade(None, false).await.unwrap();
+        assert!(matches!(results[0].status, UpgradeStatus::Failed(_)));
+        assert_eq!(fs::read(&path).await.unwrap(), b"GGUF\x03\x00\x00\x00old");
+        assert_eq!(
+            manager.model_source(&path).await.unwrap().unwrap().revision,
+            "rev1"
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Integrity checks across many models at once
+//!
+//! `inferno models verify` runs [`ModelManager::validate_model_comprehensive`]
+//! on every model concurrently and, given a checksum manifest, compares each
+//! model's SHA-256, read afresh rather than from the checksum cache, against the
+//! one recorded for it. The manifest uses the `sha256sum` layout, one
+//! `<hex digest>  <path>` line per model, with paths relative to the manifest's
+//! directory.
+
+use super::{ModelInfo, ModelManager, ValidationResult};
+use crate::config::Config;
+use anyhow::{Context, Result, bail};
+use futures::StreamExt;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Checksums recorded for model files
+#[derive(Debug, Clone, Default)]
+pub struct ChecksumManifest {
+    /// Paths as resolved against the manifest's directory, with their digests
+    entries: Vec<(PathBuf, String)>,
+}
+
+impl ChecksumManifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read checksum manifest {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&contents, dir)
+            .with_context(|| format!("Invalid checksum manifest {}", path.display()))
+    }
+
+    /// Parse `sha256sum` output, resolving paths against `dir`
+    pub fn parse(contents: &str, dir: &Path) -> Result<Self> {
+        let mut entries = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((digest, file)) = line.split_once(char::is_whitespace) else {
+                bail!("Line {} has no path after its checksum", number + 1);
+            };
+            if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+                bail!("Line {} does not start with a SHA-256 digest", number + 1);
+            }
+            // sha256sum marks files hashed in binary mode with '*'
+            let file = file.trim_start();
+            let file = file.strip_prefix('*').unwrap_or(file);
+            entries.push((dir.join(file), digest.to_ascii_lowercase()));
+        }
+        Ok(Self { entries })
+    }
+
+    /// Recorded digest of the model at `path`
+    pub fn expected(&self, path: &Path) -> Option<&str> {
+        let path = canonical(path);
+        self.entries
+            .iter()
+            .find(|(file, _)| canonical(file) == path)
+            .map(|(_, digest)| digest.as_str())
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.entries.iter().map(|(file, _)| file.as_path())
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Outcome of verifying one model
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelVerification {
+    pub name: String,
+    pub path: PathBuf,
+    pub passed: bool,
+    /// Checks the model failed: readable, size, security, format, checksum,
+    /// or missing for a manifest entry with no file
+    pub failed_checks: Vec<&'static str>,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ModelVerification {
+    fn from_validation(model: &ModelInfo, result: ValidationResult) -> Self {
+        // Validation stops at an unreadable or oversized file, so the checks
+        // after it never ran and are not reported as failed
+        let failed_checks = if !result.file_readable {
+            vec!["readable"]
+        } else if !result.size_valid {
+            vec!["size"]
+        } else {
+            [
+                ("security", result.security_valid),
+                ("format", result.format_valid && result.metadata_valid),
+                ("checksum", result.checksum_valid.unwrap_or(true)),
+            ]
+            .into_iter()
+            .filter(|(_, passed)| !passed)
+            .map(|(check, _)| check)
+            .collect()
+        };
+        Self {
+            name: model.name.clone(),
+            path: model.path.clone(),
+            passed: result.is_valid,
+            failed_checks,
+            errors: result.errors,
+            warnings: result.warnings,
+        }
+    }
+}
+
+/// Results of `models verify`, in the order the models were given
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VerifyReport {
+    pub models: Vec<ModelVerification>,
+}
+
+impl VerifyReport {
+    pub fn failed(&self) -> usize {
+        self.models.iter().filter(|model| !model.passed).count()
+    }
+
+    /// Process exit code: 1 when any model failed, so CI can gate on it
+    pub fn exit_code(&self) -> i32 {
+        i32::from(self.failed() > 0)
+    }
+
+    /// Fail every file `manifest` lists that no verified model is
+    pub fn record_missing(&mut self, manifest: &ChecksumManifest) {
+        let verified: Vec<PathBuf> = self.models.iter().map(|m| canonical(&m.path)).collect();
+        for file in manifest.files() {
+            if file.exists() && verified.contains(&canonical(file)) {
+                continue;
+            }
+            self.models.push(ModelVerification {
+                name: file
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                path: file.to_path_buf(),
+                passed: false,
+                failed_checks: vec!["missing"],
+                errors: vec!["Listed in the checksum manifest but not found".to_string()],
+                warnings: Vec::new(),
+            });
+        }
+    }
+}
+
+/// Validate `models`, up to `concurrency` at a time, checking the checksums
+/// `manifest` records
+pub async fn verify_models(
+    manager: &ModelManager,
+    models: &[ModelInfo],
+    manifest: Option<&ChecksumManifest>,
+    config: Option<&Config>,
+    concurrency: usize,
+) -> VerifyReport {
+    let models: Vec<_> = futures::stream::iter(models)
+        .map(|model| verify_model(manager, model, manifest, config))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+    VerifyReport { models }
+}
+
+async fn verify_model(
+    manager: &ModelManager,
+    model: &ModelInfo,
+    manifest: Option<&ChecksumManifest>,
+    config: Option<&Config>,
+) -> ModelVerification {
+    let mut result = match manager
+        .validate_model_comprehensive(&model.path, config)
+        .await
+    {
+        Ok(result) => result,
+        Err(e) => {
+            let mut result = ValidationResult::new();
+            result.add_error(format!("Validation failed: {:#}", e));
+            result.finalize();
+            result
+        }
+    };
+
+    if result.file_readable {
+        match manifest.map(|manifest| manifest.expected(&model.path)) {
+            Some(Some(expected)) => match manager.recompute_checksum(&model.path).await {
+                Ok(actual) if actual == expected => result.checksum_valid = Some(true),
+                Ok(actual) => {
+                    result.checksum_valid = Some(false);
+                    result.add_error(format!(
+                        "Checksum mismatch: expected {}, got {}",
+                        expected, actual
+                    ));
+                }
+                Err(e) => {
+                    result.checksum_valid = Some(false);
+                    result.add_error(format!("Cannot compute checksum: {:#}", e));
+                }
+            },
+            Some(None) => result.add_warning("No checksum recorded in the manifest".to_string()),
+            None => {}
+        }
+        result.finalize();
+    }
+    ModelVerification::from_validation(model, result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_verify_reports_each_model_and_fails_on_any_failure() {
+        let dir = tempdir().unwrap();
+        let models_dir = dir.path().join("models");
+        std::fs::create_dir_all(&models_dir).unwrap();
+        std::fs::write(models_dir.join("good.gguf"), b"GGUF\x03\x00\x00\x00good").unwrap();
+        std::fs::write(models_dir.join("corrupt.gguf"), b"INVALID_DATA").unwrap();
+        std::fs::write(
+            models_dir.join("tampered.gguf"),
+            b"GGUF\x03\x00\x00\x00evil",
+        )
+        .unwrap();
+
+        let manager = ModelManager::new(&models_dir);
+        let good_digest = manager
+            .compute_checksum(&models_dir.join("good.gguf"))
+            .await
+            .unwrap();
+        let manifest_path = models_dir.join("SHA256SUMS");
+        std::fs::write(
+            &manifest_path,
+            format!(
+                "{}  good.gguf\n{}  tampered.gguf\n{} *gone.gguf\n",
+                good_digest,
+                "0".repeat(64),
+                "1".repeat(64)
+            ),
+        )
+        .unwrap();
+        let manifest = ChecksumManifest::load(&manifest_path).unwrap();
+
+        let mut models = manager.list_models().await.unwrap();
+        models.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut report = verify_models(&manager, &models, Some(&manifest), None, 4).await;
+        report.record_missing(&manifest);
+
+        let results: Vec<_> = report
+            .models
+            .iter()
+            .map(|model| {
+                (
+                    model.name.as_str(),
+                    model.passed,
+                    model.failed_checks.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            results,
+            [
+                ("corrupt.gguf", false, vec!["format"]),
+                ("good.gguf", true, vec![]),
+                ("tampered.gguf", false, vec!["checksum"]),
+                ("gone.gguf", false, vec!["missing"]),
+            ]
+        );
+        assert!(report.models[2].errors[0].starts_with("Checksum mismatch"));
+        assert_eq!(report.failed(), 3);
+        assert_eq!(report.exit_code(), 1);
+
+        let good: Vec<_> = models
+            .into_iter()
+            .filter(|m| m.name == "good.gguf")
+            .collect();
+        let report = verify_models(&manager, &good, Some(&manifest), None, 4).await;
+        assert_eq!(report.exit_code(), 0);
+
+        assert!(ChecksumManifest::parse("nothex  model.gguf\n", dir.path()).is_err());
+    }
+}
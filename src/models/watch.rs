@@ -0,0 +1,271 @@
+//! Reloading of the served model when its file changes on disk.
+//!
+//! [`ModelWatcher`] watches the directory holding the model that `inferno serve
+//! --watch` loaded at startup. Once file events for that model have settled for the
+//! debounce period it loads the new file into a fresh backend, swaps it into the
+//! shared [`BackendHandle`] and unloads the old one. The swap waits for in-flight
+//! requests holding the backend, and the old copy is unloaded only once the
+//! streams still running on it end, so none are dropped. If the file is deleted
+//! the model is unloaded instead, after its streams end, and requests for it fail
+//! as not found until the file returns.
+
+use super::ModelManager;
+use crate::backends::{Backend, BackendHandle};
+use crate::metrics::MetricsCollector;
+use anyhow::{Result, anyhow};
+use notify::event::{AccessKind, AccessMode, EventKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Creates the backend a reloaded model is loaded into
+pub type BackendFactory = Arc<dyn Fn() -> Result<Backend> + Send + Sync>;
+
+/// Quiet period after the last file event before the model is reloaded
+pub const DEFAULT_WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+pub struct ModelWatcher {
+    model_path: PathBuf,
+    backend: BackendHandle,
+    factory: BackendFactory,
+    model_manager: ModelManager,
+    metrics: Option<MetricsCollector>,
+    debounce: Duration,
+}
+
+impl ModelWatcher {
+    /// Watch `model_path`, which is currently loaded into `backend`
+    pub fn new(
+        model_path: impl Into<PathBuf>,
+        backend: BackendHandle,
+        factory: BackendFactory,
+        model_manager: ModelManager,
+    ) -> Result<Self> {
+        // Events carry absolute paths, and the file may be gone by the time one
+        // arrives, so resolve the path once up front
+        let model_path = std::fs::canonicalize(model_path.into())?;
+        Ok(Self {
+            model_path,
+            backend,
+            factory,
+            model_manager,
+            metrics: None,
+            debounce: DEFAULT_WATCH_DEBOUNCE,
+        })
+    }
+
+    /// Record reloads and unloads as metrics events
+    pub fn with_metrics(mut self, metrics: MetricsCollector) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Start watching; the returned task runs until it is aborted
+    pub fn spawn(self) -> Result<JoinHandle<()>> {
+        let directory = self
+            .model_path
+            .parent()
+            .ok_or_else(|| anyhow!("Model path has no parent directory"))?
+            .to_path_buf();
+        let (sender, mut changes) = mpsc::unbounded_channel();
+        let model_path = self.model_path.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if is_change(&event.kind) && event.paths.contains(&model_path) => {
+                    let _ = sender.send(());
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Model file watch error: {}", e),
+            })?;
+        watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+        info!("Watching {} for changes", self.model_path.display());
+
+        Ok(tokio::spawn(async move {
+            // Dropping the watcher stops the events
+            let _watcher: RecommendedWatcher = watcher;
+            while changes.recv().await.is_some() {
+                // Editors and copies touch the file several times; act once they stop
+                loop {
+                    match tokio::time::timeout(self.debounce, changes.recv()).await {
+                        Ok(Some(())) => continue,
+                        Ok(None) => return,
+                        Err(_) => break,
+                    }
+                }
+                self.apply_change().await;
+            }
+        }))
+    }
+
+    async fn apply_change(&self) {
+        if !self.model_path.exists() {
+            info!(
+                "Model file {} was removed, unloading it",
+                self.model_path.display()
+            );
+            if let Err(e) = self.backend.unload_model().await {
+                warn!("Failed to unload removed model: {}", e);
+            }
+            self.increment_counter("model.unloads");
+            return;
+        }
+
+        match self.reload().await {
+            Ok(elapsed) => {
+                info!(
+                    "Reloaded model {} in {:.2}s",
+                    self.model_path.display(),
+                    elapsed.as_secs_f64()
+                );
+                self.increment_counter("model.reloads");
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to reload model {}, still serving the previous copy: {}",
+                    self.model_path.display(),
+                    e
+                );
+                self.increment_counter("model.reload_failures");
+            }
+        }
+    }
+
+    /// Load the new file, swap it in, then unload the old copy
+    async fn reload(&self) -> Result<Duration> {
+        let started = Instant::now();
+        let model_info = self
+            .model_manager
+            .resolve_model(&self.model_path.to_string_lossy())
+            .await?;
+        let mut backend = (self.factory)()?;
+        backend.load_model(&model_info).await?;
+        let backend_type = backend.get_backend_type();
+        let device = backend.device();
+
+        let mut previous = self.backend.replace(backend).await;
+        if let Err(e) = previous.unload_model().await {
+            warn!("Failed to unload the previous model copy: {}", e);
+        }
+
+        let elapsed = started.elapsed();
+        if let Some(metrics) = &self.metrics {
+            metrics.record_model_loaded(
+                model_info.name.clone(),
+                model_info.size_bytes,
+                elapsed,
+                backend_type.to_string(),
+            );
+            if let Some(device) = device {
+                metrics.record_model_device(&model_info.name, device);
+            }
+        }
+        Ok(elapsed)
+    }
+
+    fn increment_counter(&self, name: &str) {
+        if let Some(metrics) = &self.metrics {
+            metrics.increment_counter(name);
+        }
+    }
+}
+
+/// Whether an event may have changed the file's contents; reads, including the
+/// reload's own, are ignored
+fn is_change(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+        EventKind::Access(_) => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::InferenceParams;
+    use crate::backends::mock::MockBackend;
+    use tempfile::tempdir;
+
+    async fn served_text(backend: &BackendHandle) -> Option<String> {
+        backend.infer("", &InferenceParams::default()).await.ok()
+    }
+
+    async fn wait_for(backend: &BackendHandle, expected: Option<&str>) {
+        for _ in 0..100 {
+            if served_text(backend).await.as_deref() == expected {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!(
+            "backend serves {:?}, expected {:?}",
+            served_text(backend).await,
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_swapped_model_file_is_reloaded() {
+        let dir = tempdir().unwrap();
+        let model_path = dir.path().join("model.gguf");
+        std::fs::write(&model_path, "version 1").unwrap();
+
+        let manager = ModelManager::new(dir.path());
+        let model_info = manager
+            .resolve_model(&model_path.to_string_lossy())
+            .await
+            .unwrap();
+        let backend = MockBackend::model_file().handle();
+        backend.load_model(&model_info).await.unwrap();
+        let (metrics, processor) = MetricsCollector::new();
+        processor.start();
+
+        let factory: BackendFactory = Arc::new(|| Ok(MockBackend::model_file().backend()));
+        let watch = ModelWatcher::new(&model_path, backend.clone(), factory, manager)
+            .unwrap()
+            .with_metrics(metrics.clone())
+            .with_debounce(Duration::from_millis(100))
+            .spawn()
+            .unwrap();
+
+        // Replace the file the way a download or copy would: write, then rename over
+        let staged = dir.path().join("model.gguf.partial");
+        std::fs::write(&staged, "version 2").unwrap();
+        std::fs::rename(&staged, &model_path).unwrap();
+        wait_for(&backend, Some("version 2")).await;
+        assert_eq!(metrics.get_counters().get("model.reloads"), Some(&1));
+
+        // Deleting the file unloads the model
+        std::fs::remove_file(&model_path).unwrap();
+        wait_for(&backend, None).await;
+        assert!(!backend.is_loaded().await);
+        assert_eq!(metrics.get_counters().get("model.unloads"), Some(&1));
+
+        watch.abort();
+    }
+
+    #[test]
+    fn test_reads_are_not_changes() {
+        assert!(!is_change(&EventKind::Access(AccessKind::Open(
+            AccessMode::Any
+        ))));
+        assert!(!is_change(&EventKind::Access(AccessKind::Close(
+            AccessMode::Read
+        ))));
+        assert!(is_change(&EventKind::Access(AccessKind::Close(
+            AccessMode::Write
+        ))));
+        assert!(is_change(&EventKind::Remove(
+            notify::event::RemoveKind::File
+        )));
+    }
+}
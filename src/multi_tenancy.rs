@@ -0,0 +1,783 @@
+//! Per-tenant quotas for the HTTP API
+//!
+//! Each tenant owns one or more API keys, listed by their SHA-256 digest as in the
+//! API key file, and a [`TenantQuota`]. The [`enforce_tenant_quota`] middleware
+//! resolves the tenant from the request's bearer key and answers requests over
+//! the tenant's limits with `429 Too Many Requests` and a `Retry-After` header.
+//! Usage is tracked per tenant in sliding windows, so a tenant exhausting its
+//! quota never slows down another.
+//!
+//! Tenants also get their own model namespace: models under
+//! `models_dir/tenants/<id>/` are listed and served only for that tenant's keys,
+//! and naming another tenant's model is refused with `403 Forbidden`. Every other
+//! model in `models_dir` is shared by all tenants.
+//!
+//! Tenants are loaded from a TOML file that can be reloaded while the server runs:
+//!
+//! ```toml
+//! [[tenants]]
+//! id = "acme"
+//! # printf %s "$KEY" | sha256sum
+//! api_keys = ["819685611e044dc4918e558945f580790befd0786cc2fb36e3417477ed704a3d"]
+//!
+//! [tenants.quota]
+//! requests_per_minute = 600
+//! tokens_per_day = 5000000
+//! max_concurrent_models = 4
+//! ```
+
+use crate::api::{auth::ApiKeys, openai::default_max_tokens, openai_compliance::ErrorResponse};
+use anyhow::{Context, Result, bail};
+use axum::{
+    Json,
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+const MINUTE: Duration = Duration::from_secs(60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Largest request body the middleware buffers to find the model and token budget
+const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// Limits for one tenant; 0 disables a limit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TenantQuota {
+    pub requests_per_minute: u32,
+    pub tokens_per_day: u64,
+    /// Distinct models the tenant may have requests running against at once
+    pub max_concurrent_models: usize,
+}
+
+impl Default for TenantQuota {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: 60,
+            tokens_per_day: 1_000_000,
+            max_concurrent_models: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TenantConfig {
+    pub id: String,
+    /// SHA-256 digests of the tenant's API keys, in hex
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    #[serde(default)]
+    pub quota: TenantQuota,
+}
+
+/// Layout of the tenant quota file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TenantsFile {
+    #[serde(default)]
+    pub tenants: Vec<TenantConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaKind {
+    RequestsPerMinute,
+    TokensPerDay,
+    ConcurrentModels,
+}
+
+impl fmt::Display for QuotaKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaKind::RequestsPerMinute => write!(f, "requests per minute"),
+            QuotaKind::TokensPerDay => write!(f, "tokens per day"),
+            QuotaKind::ConcurrentModels => write!(f, "concurrent models"),
+        }
+    }
+}
+
+/// A request refused because its tenant is over quota
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    pub tenant: String,
+    pub kind: QuotaKind,
+    pub limit: u64,
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Tenant '{}' reached its limit of {} {}; retry in {}s",
+            self.tenant,
+            self.limit,
+            self.kind,
+            retry_after_secs(self.retry_after)
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantUsageSnapshot {
+    pub requests_last_minute: usize,
+    pub tokens_last_day: u64,
+    pub active_models: usize,
+}
+
+#[derive(Debug, Default)]
+struct TenantUsage {
+    requests: VecDeque<Instant>,
+    tokens: VecDeque<(Instant, u64)>,
+    tokens_in_window: u64,
+    active_models: HashMap<String, usize>,
+}
+
+impl TenantUsage {
+    fn prune(&mut self, now: Instant) {
+        while self
+            .requests
+            .front()
+            .is_some_and(|&at| now.duration_since(at) >= MINUTE)
+        {
+            self.requests.pop_front();
+        }
+        while let Some(&(at, tokens)) = self.tokens.front()
+            && now.duration_since(at) >= DAY
+        {
+            self.tokens_in_window -= tokens;
+            self.tokens.pop_front();
+        }
+    }
+}
+
+/// Tenants by API key digest, swapped out whole on reload
+#[derive(Debug, Default)]
+struct TenantTable {
+    by_key: HashMap<String, Arc<TenantConfig>>,
+    tenants: usize,
+}
+
+impl TenantTable {
+    fn build(tenants: Vec<TenantConfig>) -> Result<Self> {
+        let mut by_key = HashMap::new();
+        let count = tenants.len();
+        for mut tenant in tenants {
+            for digest in &mut tenant.api_keys {
+                *digest = digest.trim().to_ascii_lowercase();
+                if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    bail!(
+                        "Tenant '{}' lists an API key that is not a SHA-256 digest; api_keys must hold the 64 hex digit SHA-256 digest of each key",
+                        tenant.id
+                    );
+                }
+            }
+            let tenant = Arc::new(tenant);
+            for key in &tenant.api_keys {
+                if let Some(other) = by_key.insert(key.clone(), Arc::clone(&tenant)) {
+                    bail!(
+                        "API key is assigned to both tenant '{}' and tenant '{}'",
+                        other.id,
+                        tenant.id
+                    );
+                }
+            }
+        }
+        Ok(Self {
+            by_key,
+            tenants: count,
+        })
+    }
+}
+
+/// Resolves tenants from API keys and tracks their usage against quota
+#[derive(Debug, Default)]
+pub struct TenantQuotaManager {
+    table: RwLock<Arc<TenantTable>>,
+    usage: Mutex<HashMap<String, TenantUsage>>,
+}
+
+impl TenantQuotaManager {
+    pub fn new(tenants: Vec<TenantConfig>) -> Result<Self> {
+        Ok(Self {
+            table: RwLock::new(Arc::new(TenantTable::build(tenants)?)),
+            usage: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub async fn load(path: &Path) -> Result<Self> {
+        Self::new(read_tenants(path).await?)
+    }
+
+    /// Swap in a new tenant list; usage carries over for tenants that keep their id
+    pub fn replace(&self, tenants: Vec<TenantConfig>) -> Result<()> {
+        let table = TenantTable::build(tenants)?;
+        *self.table.write().unwrap() = Arc::new(table);
+        Ok(())
+    }
+
+    pub async fn reload(&self, path: &Path) -> Result<()> {
+        self.replace(read_tenants(path).await?)?;
+        info!("Reloaded tenant quotas from {}", path.display());
+        Ok(())
+    }
+
+    /// Reload `path` whenever its modification time changes
+    ///
+    /// A file that fails to parse is logged and the previous quotas stay in force.
+    pub fn spawn_hot_reload(self: Arc<Self>, path: PathBuf, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut last_modified = modified_at(&path).await;
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let modified = modified_at(&path).await;
+                if modified.is_none() || modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                if let Err(e) = self.reload(&path).await {
+                    warn!(
+                        "Keeping previous tenant quotas, {} is invalid: {:#}",
+                        path.display(),
+                        e
+                    );
+                }
+            }
+        })
+    }
+
+    /// Whether any tenants are configured; without them requests are not checked
+    pub fn is_enabled(&self) -> bool {
+        self.table.read().unwrap().tenants > 0
+    }
+
+    /// Tenant owning `api_key`, matched by its digest
+    pub fn resolve(&self, api_key: &str) -> Option<Arc<TenantConfig>> {
+        let digest = ApiKeys::digest(api_key);
+        self.table.read().unwrap().by_key.get(&digest).cloned()
+    }
+
+    /// Count a request against the tenant's quota, or refuse it if that would exceed
+    /// any limit
+    ///
+    /// The returned lease marks `model` as in use by the tenant until it is dropped.
+    pub fn admit(
+        self: &Arc<Self>,
+        tenant: &TenantConfig,
+        model: Option<&str>,
+    ) -> Result<TenantLease, QuotaExceeded> {
+        self.admit_at(tenant, model, Instant::now())
+    }
+
+    fn admit_at(
+        self: &Arc<Self>,
+        tenant: &TenantConfig,
+        model: Option<&str>,
+        now: Instant,
+    ) -> Result<TenantLease, QuotaExceeded> {
+        let quota = &tenant.quota;
+        let exceeded = |kind, limit: u64, retry_after| QuotaExceeded {
+            tenant: tenant.id.clone(),
+            kind,
+            limit,
+            retry_after,
+        };
+
+        let mut usage = self.usage.lock().unwrap();
+        let usage = usage.entry(tenant.id.clone()).or_default();
+        usage.prune(now);
+
+        if quota.requests_per_minute > 0
+            && usage.requests.len() >= quota.requests_per_minute as usize
+        {
+            let oldest = usage.requests[usage.requests.len() - quota.requests_per_minute as usize];
+            return Err(exceeded(
+                QuotaKind::RequestsPerMinute,
+                quota.requests_per_minute as u64,
+                MINUTE.saturating_sub(now.duration_since(oldest)),
+            ));
+        }
+
+        if quota.tokens_per_day > 0 && usage.tokens_in_window >= quota.tokens_per_day {
+            // Wait until enough of the oldest usage leaves the window
+            let mut remaining = usage.tokens_in_window;
+            let mut retry_after = DAY;
+            for &(at, tokens) in &usage.tokens {
+                remaining -= tokens;
+                if remaining < quota.tokens_per_day {
+                    retry_after = DAY.saturating_sub(now.duration_since(at));
+                    break;
+                }
+            }
+            return Err(exceeded(
+                QuotaKind::TokensPerDay,
+                quota.tokens_per_day,
+                retry_after,
+            ));
+        }
+
+        if let Some(model) = model
+            && quota.max_concurrent_models > 0
+            && !usage.active_models.contains_key(model)
+            && usage.active_models.len() >= quota.max_concurrent_models
+        {
+            return Err(exceeded(
+                QuotaKind::ConcurrentModels,
+                quota.max_concurrent_models as u64,
+                Duration::from_secs(1),
+            ));
+        }
+
+        usage.requests.push_back(now);
+        if let Some(model) = model {
+            *usage.active_models.entry(model.to_string()).or_default() += 1;
+        }
+
+        Ok(TenantLease {
+            manager: Arc::clone(self),
+            tenant: tenant.id.clone(),
+            model: model.map(str::to_string),
+        })
+    }
+
+    /// Charge tokens generated for the tenant against its daily quota
+    pub fn record_tokens(&self, tenant: &str, tokens: u64) {
+        if tokens == 0 {
+            return;
+        }
+        let mut usage = self.usage.lock().unwrap();
+        let usage = usage.entry(tenant.to_string()).or_default();
+        usage.tokens.push_back((Instant::now(), tokens));
+        usage.tokens_in_window += tokens;
+    }
+
+    pub fn usage(&self, tenant: &str) -> TenantUsageSnapshot {
+        let mut usage = self.usage.lock().unwrap();
+        let Some(usage) = usage.get_mut(tenant) else {
+            return TenantUsageSnapshot::default();
+        };
+        usage.prune(Instant::now());
+        TenantUsageSnapshot {
+            requests_last_minute: usage.requests.len(),
+            tokens_last_day: usage.tokens_in_window,
+            active_models: usage.active_models.len(),
+        }
+    }
+}
+
+/// Keeps a model counted as in use by a tenant while a request runs
+#[derive(Debug)]
+pub struct TenantLease {
+    manager: Arc<TenantQuotaManager>,
+    tenant: String,
+    model: Option<String>,
+}
+
+impl TenantLease {
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+}
+
+impl Drop for TenantLease {
+    fn drop(&mut self) {
+        let Some(model) = &self.model else {
+            return;
+        };
+        let mut usage = self.manager.usage.lock().unwrap();
+        if let Some(usage) = usage.get_mut(&self.tenant)
+            && let Some(count) = usage.active_models.get_mut(model)
+        {
+            *count -= 1;
+            if *count == 0 {
+                usage.active_models.remove(model);
+            }
+        }
+    }
+}
+
+/// Middleware enforcing tenant quotas on the inference endpoints
+///
+/// Tokens are charged from the `usage` of JSON responses. Streamed responses are
+/// charged up front the most they may generate, their `max_tokens` or without it
+/// the default cap, since their usage is not known until they end.
+pub async fn enforce_tenant_quota(
+    State(quotas): State<Arc<TenantQuotaManager>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !quotas.is_enabled() {
+        return next.run(request).await;
+    }
+
+    let Some(tenant) = request_tenant(&quotas, &request) else {
+        return unknown_api_key();
+    };
+
+    let (mut parts, body) = request.into_parts();
+    parts.extensions.insert(Arc::clone(&tenant));
+    let bytes = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(ErrorResponse::for_status(413, e.to_string(), None)),
+            )
+                .into_response();
+        }
+    };
+    let body: Option<Value> = serde_json::from_slice(&bytes).ok();
+    let model = body
+        .as_ref()
+        .and_then(|body| body.get("model"))
+        .and_then(Value::as_str);
+    let max_tokens = body
+        .as_ref()
+        .and_then(|body| {
+            body.get("max_tokens")
+                .or_else(|| body.get("max_completion_tokens"))
+        })
+        .and_then(Value::as_u64)
+        .unwrap_or(u64::from(default_max_tokens()));
+
+    let lease = match quotas.admit(&tenant, model) {
+        Ok(lease) => lease,
+        Err(exceeded) => return too_many_requests(&exceeded),
+    };
+
+    let response = next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let (parts, body) = response.into_parts();
+
+    if is_json {
+        let bytes = match to_bytes(body, usize::MAX).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("Failed to read response for tenant '{}': {}", tenant.id, e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+        let tokens = serde_json::from_slice::<Value>(&bytes)
+            .ok()
+            .and_then(|body| body["usage"]["total_tokens"].as_u64())
+            .unwrap_or(0);
+        quotas.record_tokens(&tenant.id, tokens);
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+
+    quotas.record_tokens(&tenant.id, max_tokens);
+    // Keep the model counted as in use until the stream finishes
+    let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+        let _ = &lease;
+        chunk
+    }));
+    Response::from_parts(parts, body)
+}
+
+/// Middleware resolving the tenant of requests that use no quota, such as model
+/// listings, so handlers can limit them to the tenant's models
+///
+/// The tenant is added to the request's extensions as an `Arc<TenantConfig>`,
+/// as [`enforce_tenant_quota`] does.
+pub async fn identify_tenant(
+    State(quotas): State<Arc<TenantQuotaManager>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !quotas.is_enabled() {
+        return next.run(request).await;
+    }
+    let Some(tenant) = request_tenant(&quotas, &request) else {
+        return unknown_api_key();
+    };
+    request.extensions_mut().insert(tenant);
+    next.run(request).await
+}
+
+fn request_tenant(quotas: &TenantQuotaManager, request: &Request) -> Option<Arc<TenantConfig>> {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|key| quotas.resolve(key.trim()))
+}
+
+fn unknown_api_key() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(ErrorResponse::new(
+            "Missing or unknown API key",
+            "invalid_request_error",
+            None,
+            Some("invalid_api_key"),
+        )),
+    )
+        .into_response()
+}
+
+fn too_many_requests(exceeded: &QuotaExceeded) -> Response {
+    let error_type = match exceeded.kind {
+        QuotaKind::TokensPerDay => "tokens",
+        QuotaKind::RequestsPerMinute | QuotaKind::ConcurrentModels => "requests",
+    };
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(ErrorResponse::new(
+            exceeded.to_string(),
+            error_type,
+            None,
+            Some("rate_limit_exceeded"),
+        )),
+    )
+        .into_response();
+    response.headers_mut().insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(retry_after_secs(exceeded.retry_after)),
+    );
+    response
+}
+
+/// Whole seconds to wait, rounded up so clients never retry early
+fn retry_after_secs(retry_after: Duration) -> u64 {
+    retry_after.as_millis().div_ceil(1000).max(1) as u64
+}
+
+async fn read_tenants(path: &Path) -> Result<Vec<TenantConfig>> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to read tenant quotas {}", path.display()))?;
+    let file: TenantsFile = toml::from_str(&content)
+        .with_context(|| format!("Invalid tenant quotas {}", path.display()))?;
+    Ok(file.tenants)
+}
+
+async fn modified_at(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, middleware, routing::post};
+    use tower::ServiceExt;
+
+    fn tenant(id: &str, requests_per_minute: u32, tokens_per_day: u64) -> TenantConfig {
+        TenantConfig {
+            id: id.to_string(),
+            api_keys: vec![ApiKeys::digest(&format!("sk-{}", id))],
+            quota: TenantQuota {
+                requests_per_minute,
+                tokens_per_day,
+                max_concurrent_models: 1,
+            },
+        }
+    }
+
+    fn app(quotas: Arc<TenantQuotaManager>) -> Router {
+        Router::new()
+            .route(
+                "/v1/chat/completions",
+                post(|| async { Json(serde_json::json!({"usage": {"total_tokens": 10}})) }),
+            )
+            .route(
+                "/v1/completions",
+                post(|| async {
+                    (
+                        [(header::CONTENT_TYPE, "text/event-stream")],
+                        "data: [DONE]\n\n",
+                    )
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(quotas, enforce_tenant_quota))
+    }
+
+    async fn send(app: &Router, key: &str) -> Response {
+        send_to(
+            app,
+            key,
+            "/v1/chat/completions",
+            r#"{"model": "llama", "max_tokens": 10}"#,
+        )
+        .await
+    }
+
+    async fn send_to(app: &Router, key: &str, uri: &str, body: &'static str) -> Response {
+        let request = Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", key))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_requests_per_minute_is_per_tenant() {
+        let quotas =
+            Arc::new(TenantQuotaManager::new(vec![tenant("a", 3, 0), tenant("b", 3, 0)]).unwrap());
+        let app = app(Arc::clone(&quotas));
+
+        for _ in 0..3 {
+            assert_eq!(send(&app, "sk-a").await.status(), StatusCode::OK);
+        }
+        let limited = send(&app, "sk-a").await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = limited.headers()[header::RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=60).contains(&retry_after));
+
+        // The second tenant is unaffected
+        assert_eq!(send(&app, "sk-b").await.status(), StatusCode::OK);
+        assert_eq!(quotas.usage("a").requests_last_minute, 3);
+        assert_eq!(quotas.usage("b").requests_last_minute, 1);
+
+        assert_eq!(
+            send(&app, "sk-unknown").await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tokens_per_day_charged_from_usage() {
+        let quotas = Arc::new(TenantQuotaManager::new(vec![tenant("a", 0, 15)]).unwrap());
+        let app = app(Arc::clone(&quotas));
+
+        assert_eq!(send(&app, "sk-a").await.status(), StatusCode::OK);
+        assert_eq!(send(&app, "sk-a").await.status(), StatusCode::OK);
+        assert_eq!(quotas.usage("a").tokens_last_day, 20);
+
+        let limited = send(&app, "sk-a").await;
+        assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body = to_bytes(limited.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["type"], "tokens");
+        assert_eq!(body["error"]["code"], "rate_limit_exceeded");
+    }
+
+    #[tokio::test]
+    async fn test_streams_are_charged_their_token_cap() {
+        let quotas = Arc::new(TenantQuotaManager::new(vec![tenant("a", 0, 0)]).unwrap());
+        let app = app(Arc::clone(&quotas));
+
+        let capped = r#"{"model": "llama", "stream": true, "max_completion_tokens": 20}"#;
+        let response = send_to(&app, "sk-a", "/v1/completions", capped).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(quotas.usage("a").tokens_last_day, 20);
+
+        // Without max_tokens the stream may run to the default cap
+        let uncapped = r#"{"model": "llama", "stream": true}"#;
+        send_to(&app, "sk-a", "/v1/completions", uncapped).await;
+        assert_eq!(
+            quotas.usage("a").tokens_last_day,
+            20 + u64::from(default_max_tokens())
+        );
+    }
+
+    #[test]
+    fn test_tenant_keys_must_be_digests() {
+        let mut plaintext = tenant("a", 0, 0);
+        plaintext.api_keys = vec!["sk-a".to_string()];
+        assert!(TenantQuotaManager::new(vec![plaintext]).is_err());
+
+        let quotas = TenantQuotaManager::new(vec![tenant("a", 0, 0)]).unwrap();
+        assert_eq!(quotas.resolve("sk-a").unwrap().id, "a");
+        // The digest itself is not a key
+        assert!(quotas.resolve(&ApiKeys::digest("sk-a")).is_none());
+    }
+
+    #[test]
+    fn test_sliding_window_and_concurrent_models() {
+        let quotas = Arc::new(TenantQuotaManager::default());
+        let tenant = tenant("a", 2, 0);
+        let start = Instant::now();
+
+        let first = quotas.admit_at(&tenant, Some("llama"), start).unwrap();
+        let other_model = quotas
+            .admit_at(&tenant, Some("mistral"), start + Duration::from_secs(1))
+            .unwrap_err();
+        assert_eq!(other_model.kind, QuotaKind::ConcurrentModels);
+
+        let _second = quotas
+            .admit_at(&tenant, Some("llama"), start + Duration::from_secs(20))
+            .unwrap();
+        let limited = quotas
+            .admit_at(&tenant, None, start + Duration::from_secs(30))
+            .unwrap_err();
+        assert_eq!(limited.kind, QuotaKind::RequestsPerMinute);
+        assert_eq!(limited.retry_after, Duration::from_secs(30));
+
+        // The first request leaves the window after a minute
+        drop(first);
+        assert!(
+            quotas
+                .admit_at(&tenant, None, start + Duration::from_secs(61))
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reload_replaces_quotas() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tenants.toml");
+        let tenants = |key: &str, requests_per_minute: u32| {
+            format!(
+                "[[tenants]]\nid = \"a\"\napi_keys = [\"{}\"]\n[tenants.quota]\nrequests_per_minute = {}\n",
+                ApiKeys::digest(key),
+                requests_per_minute
+            )
+        };
+        tokio::fs::write(&path, tenants("sk-a", 1)).await.unwrap();
+
+        let quotas = Arc::new(TenantQuotaManager::load(&path).await.unwrap());
+        let tenant = quotas.resolve("sk-a").unwrap();
+        assert_eq!(tenant.quota.requests_per_minute, 1);
+        assert_eq!(tenant.quota.tokens_per_day, 1_000_000);
+        let _lease = quotas.admit(&tenant, None).unwrap();
+        assert!(quotas.admit(&tenant, None).is_err());
+
+        tokio::fs::write(&path, tenants("sk-a2", 5)).await.unwrap();
+        quotas.reload(&path).await.unwrap();
+
+        assert!(quotas.resolve("sk-a").is_none());
+        let tenant = quotas.resolve("sk-a2").unwrap();
+        // Usage carries over: one of the five requests is already spent
+        for _ in 0..4 {
+            quotas.admit(&tenant, None).unwrap();
+        }
+        assert!(quotas.admit(&tenant, None).is_err());
+
+        tokio::fs::write(&path, "tenants = 5").await.unwrap();
+        assert!(quotas.reload(&path).await.is_err());
+        assert!(quotas.resolve("sk-a2").is_some());
+    }
+}
@@ -1,5 +1,6 @@
 #![allow(dead_code, unused_imports, unused_variables)]
 use crate::InfernoError;
+use crate::metrics::statsd::StatsdConfig;
 use anyhow::Result;
 use axum::{Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
 use chrono::{DateTime, Utc};
@@ -44,6 +45,10 @@ pub struct ObservabilityConfig {
     pub histogram_enabled: bool,
     /// Histogram bucket configuration
     pub histogram_buckets: Vec<f64>,
+
+    /// StatsD/DogStatsD push export
+    #[serde(default)]
+    pub statsd: StatsdConfig,
 }
 
 impl Default for ObservabilityConfig {
@@ -68,6 +73,8 @@ impl Default for ObservabilityConfig {
             histogram_buckets: vec![
                 0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
             ],
+
+            statsd: StatsdConfig::default(),
         }
     }
 }
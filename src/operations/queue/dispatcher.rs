@@ -0,0 +1,503 @@
+//! Request Dispatcher
+//!
+//! Admits inference requests through the [`FairScheduler`] so that, once every worker
+//! slot is busy, waiting requests are started in priority order instead of all
+//! hitting the backend at once. Aging in the scheduler keeps low-priority requests
+//! moving under sustained high-priority load.
+
+use crate::operations::queue::fair_scheduler::{FairScheduler, FairnessStats};
+use crate::operations::queue::metrics::{QueueMetricsCollector, QueueMetricsSnapshot};
+use crate::operations::queue::priority_queue::RequestMetadata;
+use crate::operations::queue::worker_pool::{WorkerPool, WorkerPoolConfig, WorkerPoolStats};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// Dispatcher configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DispatcherConfig {
+    pub workers: usize,
+    /// Requests a single worker runs at once
+    pub max_active_per_worker: u32,
+    pub starvation_threshold_ms: u64,
+}
+
+impl Default for DispatcherConfig {
+    fn default() -> Self {
+        Self {
+            workers: 1,
+            max_active_per_worker: 10,
+            starvation_threshold_ms: 30_000,
+        }
+    }
+}
+
+/// Where a request stands in the queue
+///
+/// The wait is estimated from recent throughput once enough requests have been
+/// dispatched, and from the average service time before that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuePlacement {
+    /// 1-based position among waiting requests; 0 when it started immediately
+    pub position: usize,
+    pub estimated_wait_ms: u64,
+}
+
+#[derive(Debug)]
+struct DispatchState {
+    scheduler: FairScheduler,
+    pool: WorkerPool,
+    max_active_per_worker: u32,
+    /// Submission order and dispatch channel of each waiting request
+    waiters: HashMap<String, (u64, oneshot::Sender<u32>)>,
+    submitted: u64,
+    /// Moving average of how long a dispatched request holds its worker
+    avg_service_ms: f64,
+    metrics: QueueMetricsCollector,
+}
+
+impl DispatchState {
+    /// Hand free worker slots to the highest priority waiting requests
+    fn pump(&mut self) {
+        while let Some(worker_id) = self.pool.get_available_worker(self.max_active_per_worker) {
+            let Some(request) = self.scheduler.dequeue() else {
+                break;
+            };
+            self.pool.assign_request(worker_id);
+            self.metrics
+                .record_processed(request.priority as u8, request.age_ms());
+            let delivered = self
+                .waiters
+                .remove(&request.request_id)
+                .is_some_and(|(_, waiter)| waiter.send(worker_id).is_ok());
+            if !delivered {
+                self.pool.complete_request(worker_id, false);
+            }
+        }
+    }
+
+    fn slots(&self) -> usize {
+        (self.pool.len() * self.max_active_per_worker as usize).max(1)
+    }
+
+    /// Current place of a waiting request; `None` once it has been dispatched
+    ///
+    /// Requests of higher effective priority, and those of equal priority
+    /// submitted earlier, go first.
+    fn placement(&self, request_id: &str) -> Option<QueuePlacement> {
+        let (submitted, _) = self.waiters.get(request_id)?;
+        let request = self.scheduler.iter().find(|r| r.request_id == request_id)?;
+        let priority = request.effective_priority();
+        let ahead = self
+            .scheduler
+            .iter()
+            .filter(|r| {
+                let other = r.effective_priority();
+                other > priority
+                    || (other == priority
+                        && self
+                            .waiters
+                            .get(&r.request_id)
+                            .is_some_and(|(order, _)| order < submitted))
+            })
+            .count();
+
+        let position = ahead + 1;
+        let estimated_wait_ms = match self.metrics.recent_throughput_per_sec() {
+            Some(per_sec) => position as f64 / per_sec * 1000.0,
+            None => position.div_ceil(self.slots()) as f64 * self.avg_service_ms,
+        };
+        Some(QueuePlacement {
+            position,
+            estimated_wait_ms: estimated_wait_ms as u64,
+        })
+    }
+}
+
+/// Priority-aware admission in front of the inference backends
+#[derive(Debug, Clone)]
+pub struct RequestDispatcher {
+    state: Arc<Mutex<DispatchState>>,
+}
+
+impl RequestDispatcher {
+    pub fn new(config: DispatcherConfig) -> Self {
+        let pool = WorkerPool::new(
+            WorkerPoolConfig::new("default".to_string())
+                .with_min_workers(config.workers.max(1))
+                .with_max_workers(config.workers.max(1)),
+        );
+
+        Self {
+            state: Arc::new(Mutex::new(DispatchState {
+                scheduler: FairScheduler::new()
+                    .with_starvation_threshold(config.starvation_threshold_ms),
+                pool,
+                max_active_per_worker: config.max_active_per_worker.max(1),
+                waiters: HashMap::new(),
+                submitted: 0,
+                avg_service_ms: 1_000.0,
+                metrics: QueueMetricsCollector::new(),
+            })),
+        }
+    }
+
+    /// Queue a request; await the returned ticket for a worker slot
+    ///
+    /// Waiting requests have unique ids: a request submitted under the id of
+    /// one still waiting is queued under a fresh id, see
+    /// [`QueueTicket::request_id`].
+    pub fn submit(&self, mut metadata: RequestMetadata) -> QueueTicket {
+        let (sender, receiver) = oneshot::channel();
+        let priority_level = metadata.priority as u8;
+
+        let mut state = self.state.lock().unwrap();
+        state.submitted += 1;
+        let submitted = state.submitted;
+        let mut request_id = metadata.request_id.clone();
+        loop {
+            match state.waiters.entry(request_id) {
+                Entry::Vacant(slot) => {
+                    request_id = slot.key().clone();
+                    slot.insert((submitted, sender));
+                    break;
+                }
+                Entry::Occupied(_) => request_id = Uuid::new_v4().to_string(),
+            }
+        }
+        metadata.request_id = request_id.clone();
+        state.metrics.record_queued(priority_level);
+        state.scheduler.enqueue(metadata);
+        let depth = state.scheduler.len();
+        state.metrics.record_queue_depth(depth);
+        state.pump();
+
+        let placement = state.placement(&request_id).unwrap_or(QueuePlacement {
+            position: 0,
+            estimated_wait_ms: 0,
+        });
+
+        QueueTicket {
+            request_id,
+            placement,
+            receiver,
+            dispatched: false,
+            state: Arc::clone(&self.state),
+        }
+    }
+
+    /// Current place of a waiting request; `None` when no request with this id
+    /// is waiting
+    pub fn placement(&self, request_id: &str) -> Option<QueuePlacement> {
+        self.state.lock().unwrap().placement(request_id)
+    }
+
+    /// Queue depth, wait times and throughput since the dispatcher started
+    pub fn metrics(&self) -> QueueMetricsSnapshot {
+        let state = self.state.lock().unwrap();
+        state.metrics.snapshot(state.scheduler.len())
+    }
+
+    /// Number of requests waiting for a worker
+    pub fn queued(&self) -> usize {
+        self.state.lock().unwrap().scheduler.len()
+    }
+
+    pub fn fairness_stats(&self) -> FairnessStats {
+        self.state.lock().unwrap().scheduler.fairness_stats()
+    }
+
+    pub fn worker_stats(&self) -> WorkerPoolStats {
+        self.state.lock().unwrap().pool.stats()
+    }
+}
+
+/// A submitted request waiting for a worker slot
+///
+/// Dropping the ticket before it is dispatched withdraws the request.
+#[derive(Debug)]
+pub struct QueueTicket {
+    request_id: String,
+    placement: QueuePlacement,
+    receiver: oneshot::Receiver<u32>,
+    dispatched: bool,
+    state: Arc<Mutex<DispatchState>>,
+}
+
+impl QueueTicket {
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// Where the request was placed when it was submitted
+    pub fn placement(&self) -> QueuePlacement {
+        self.placement
+    }
+
+    /// Where the request stands now; position 0 once it has been dispatched
+    pub fn current_placement(&self) -> QueuePlacement {
+        self.state
+            .lock()
+            .unwrap()
+            .placement(&self.request_id)
+            .unwrap_or(QueuePlacement {
+                position: 0,
+                estimated_wait_ms: 0,
+            })
+    }
+
+    /// Wait until the scheduler dispatches this request to a worker
+    pub async fn dispatched(mut self) -> Result<DispatchPermit> {
+        let worker_id = (&mut self.receiver)
+            .await
+            .map_err(|_| anyhow!("Request {} was dropped from the queue", self.request_id))?;
+        self.dispatched = true;
+
+        Ok(DispatchPermit {
+            worker_id,
+            started: Instant::now(),
+            failed: false,
+            state: Arc::clone(&self.state),
+        })
+    }
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        if self.dispatched {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        // Dispatches happen under the lock, so a slot is either still pending in the
+        // channel or the request is still queued
+        match self.receiver.try_recv() {
+            Ok(worker_id) => {
+                state.pool.complete_request(worker_id, false);
+                state.pump();
+            }
+            Err(_) => {
+                state.scheduler.cancel_request(&self.request_id);
+                state.waiters.remove(&self.request_id);
+            }
+        }
+    }
+}
+
+/// A worker slot held by a dispatched request, released on drop
+#[derive(Debug)]
+pub struct DispatchPermit {
+    worker_id: u32,
+    started: Instant,
+    failed: bool,
+    state: Arc<Mutex<DispatchState>>,
+}
+
+impl DispatchPermit {
+    pub fn worker_id(&self) -> u32 {
+        self.worker_id
+    }
+
+    /// Record the request as failed when the slot is released
+    pub fn mark_failed(&mut self) {
+        self.failed = true;
+    }
+}
+
+impl Drop for DispatchPermit {
+    fn drop(&mut self) {
+        let elapsed_ms = self.started.elapsed().as_secs_f64() * 1000.0;
+        let mut state = self.state.lock().unwrap();
+        state.avg_service_ms = state.avg_service_ms * 0.9 + elapsed_ms * 0.1;
+        state.pool.complete_request(self.worker_id, !self.failed);
+        state.pump();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operations::queue::priority_queue::Priority;
+
+    fn request(id: &str, priority: Priority) -> RequestMetadata {
+        RequestMetadata::new(
+            id.to_string(),
+            "user".to_string(),
+            priority,
+            "model".to_string(),
+        )
+    }
+
+    fn single_slot() -> RequestDispatcher {
+        RequestDispatcher::new(DispatcherConfig {
+            workers: 1,
+            max_active_per_worker: 1,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_dispatched_first() {
+        let dispatcher = single_slot();
+
+        let blocker = dispatcher.submit(request("blocker", Priority::Normal));
+        assert_eq!(blocker.placement().position, 0);
+        let blocker = blocker.dispatched().await.unwrap();
+
+        // A flood of low-priority traffic arrives before the high-priority requests
+        let mut tickets = Vec::new();
+        for i in 0..5 {
+            tickets.push(dispatcher.submit(request(&format!("low-{}", i), Priority::Low)));
+        }
+        for i in 0..3 {
+            tickets.push(dispatcher.submit(request(&format!("high-{}", i), Priority::High)));
+        }
+        assert_eq!(dispatcher.queued(), 8);
+        assert_eq!(tickets[4].placement().position, 5);
+        // High-priority requests queue ahead of every low-priority one
+        assert_eq!(tickets[7].placement().position, 3);
+
+        let (order_tx, mut order_rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut handles = Vec::new();
+        for ticket in tickets {
+            let order_tx = order_tx.clone();
+            handles.push(tokio::spawn(async move {
+                let id = ticket.request_id().to_string();
+                let permit = ticket.dispatched().await.unwrap();
+                order_tx.send(id).unwrap();
+                tokio::task::yield_now().await;
+                drop(permit);
+            }));
+        }
+        drop(order_tx);
+
+        drop(blocker);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let mut order = Vec::new();
+        while let Some(id) = order_rx.recv().await {
+            order.push(id);
+        }
+        assert_eq!(
+            order,
+            vec![
+                "high-0", "high-1", "high-2", "low-0", "low-1", "low-2", "low-3", "low-4"
+            ]
+        );
+        assert_eq!(dispatcher.queued(), 0);
+        assert_eq!(dispatcher.worker_stats().total_processed, 9);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_ticket_leaves_queue() {
+        let dispatcher = single_slot();
+        let running = dispatcher
+            .submit(request("running", Priority::Normal))
+            .dispatched()
+            .await
+            .unwrap();
+
+        let abandoned = dispatcher.submit(request("abandoned", Priority::VIP));
+        let waiting = dispatcher.submit(request("waiting", Priority::Low));
+        assert_eq!(waiting.placement().position, 2);
+        drop(abandoned);
+        assert_eq!(dispatcher.queued(), 1);
+
+        drop(running);
+        let permit = waiting.dispatched().await.unwrap();
+        assert_eq!(permit.worker_id(), 0);
+        assert_eq!(dispatcher.queued(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_waiting_id_is_queued_under_a_fresh_id() {
+        let dispatcher = single_slot();
+        let running = dispatcher
+            .submit(request("running", Priority::Normal))
+            .dispatched()
+            .await
+            .unwrap();
+
+        let first = dispatcher.submit(request("dup", Priority::Normal));
+        let second = dispatcher.submit(request("dup", Priority::Normal));
+        assert_eq!(first.request_id(), "dup");
+        assert_ne!(second.request_id(), "dup");
+        assert_eq!(dispatcher.queued(), 2);
+        assert_eq!(
+            dispatcher.placement(second.request_id()),
+            Some(second.current_placement())
+        );
+
+        // Withdrawing the duplicate leaves the original waiting under its id
+        drop(second);
+        assert_eq!(dispatcher.queued(), 1);
+        assert_eq!(dispatcher.placement("dup").unwrap().position, 1);
+
+        drop(running);
+        let _first = first.dispatched().await.unwrap();
+        assert_eq!(dispatcher.queued(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_position_decreases_as_earlier_requests_complete() {
+        let dispatcher = single_slot();
+        let running = dispatcher
+            .submit(request("running", Priority::Normal))
+            .dispatched()
+            .await
+            .unwrap();
+
+        let first = dispatcher.submit(request("first", Priority::Normal));
+        let second = dispatcher.submit(request("second", Priority::Normal));
+        let last = dispatcher.submit(request("last", Priority::Normal));
+        assert_eq!(last.placement().position, 3);
+        assert_eq!(dispatcher.placement("last"), Some(last.current_placement()));
+
+        drop(running);
+        let first = first.dispatched().await.unwrap();
+        let now = last.current_placement();
+        assert_eq!(now.position, 2);
+        assert!(now.estimated_wait_ms < last.placement().estimated_wait_ms);
+
+        drop(first);
+        let second = second.dispatched().await.unwrap();
+        assert_eq!(last.current_placement().position, 1);
+
+        drop(second);
+        let _last = last.dispatched().await.unwrap();
+        assert_eq!(dispatcher.placement("last"), None);
+        assert_eq!(dispatcher.metrics().total_processed, 4);
+    }
+
+    #[test]
+    fn test_estimated_wait_scales_with_position() {
+        let dispatcher = RequestDispatcher::new(DispatcherConfig {
+            workers: 2,
+            max_active_per_worker: 1,
+            ..Default::default()
+        });
+
+        // Tickets stay alive so their requests keep their place
+        let tickets: Vec<_> = ["r0", "r1", "q0", "q1", "q2"]
+            .into_iter()
+            .map(|id| dispatcher.submit(request(id, Priority::Normal)))
+            .collect();
+        let placements: Vec<_> = tickets.iter().map(|t| t.placement()).collect();
+
+        assert_eq!(placements[1].position, 0);
+        assert_eq!(placements[2].position, 1);
+        assert_eq!(placements[4].position, 3);
+        assert_eq!(
+            placements[2].estimated_wait_ms,
+            placements[3].estimated_wait_ms
+        );
+        assert!(placements[4].estimated_wait_ms > placements[3].estimated_wait_ms);
+    }
+}
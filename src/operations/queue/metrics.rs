@@ -3,7 +3,12 @@
 //! This module provides comprehensive metrics and monitoring for the queue system
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+
+/// Recent requests the throughput estimate is taken over
+const RECENT_PROCESSED: usize = 64;
+/// Shortest span of recent requests a throughput estimate is trusted from
+const MIN_THROUGHPUT_SPAN_MS: u64 = 1_000;
 
 /// Per-request metrics
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +55,8 @@ pub struct QueueMetricsCollector {
     max_queue_depth: usize,
     per_priority_metrics: HashMap<u8, Vec<u64>>, // wait times per priority
     start_time_ms: u64,
+    /// When the most recent requests were processed
+    recent_processed_ms: VecDeque<u64>,
 }
 
 impl QueueMetricsCollector {
@@ -62,6 +69,7 @@ impl QueueMetricsCollector {
             max_queue_depth: 0,
             per_priority_metrics: HashMap::new(),
             start_time_ms: Self::current_timestamp(),
+            recent_processed_ms: VecDeque::new(),
         }
     }
 
@@ -74,10 +82,30 @@ impl QueueMetricsCollector {
     /// Record a request being processed
     pub fn record_processed(&mut self, priority: u8, wait_time_ms: u64) {
         self.total_processed += 1;
-        self.per_priority_metrics
-            .entry(priority)
-            .or_default()
-            .push(wait_time_ms);
+        let wait_times = self.per_priority_metrics.entry(priority).or_default();
+        wait_times.push(wait_time_ms);
+        // Keep only last 10000 readings to avoid memory bloat
+        if wait_times.len() > 10000 {
+            wait_times.remove(0);
+        }
+
+        self.recent_processed_ms
+            .push_back(Self::current_timestamp());
+        if self.recent_processed_ms.len() > RECENT_PROCESSED {
+            self.recent_processed_ms.pop_front();
+        }
+    }
+
+    /// Requests processed per second over the most recent ones, up to now
+    ///
+    /// `None` until they span long enough to give a meaningful rate.
+    pub fn recent_throughput_per_sec(&self) -> Option<f64> {
+        let oldest = *self.recent_processed_ms.front()?;
+        let span_ms = Self::current_timestamp().saturating_sub(oldest);
+        if self.recent_processed_ms.len() < 2 || span_ms < MIN_THROUGHPUT_SPAN_MS {
+            return None;
+        }
+        Some(self.recent_processed_ms.len() as f64 * 1000.0 / span_ms as f64)
     }
 
     /// Record current queue depth
@@ -174,6 +202,7 @@ impl QueueMetricsCollector {
         self.max_queue_depth = 0;
         self.per_priority_metrics.clear();
         self.start_time_ms = Self::current_timestamp();
+        self.recent_processed_ms.clear();
     }
 }
 
@@ -7,6 +7,7 @@
 //! - Intelligent load balancing
 
 pub mod assignment;
+pub mod dispatcher;
 pub mod fair_scheduler;
 pub mod metrics;
 pub mod persistence;
@@ -16,6 +17,9 @@ pub mod worker_pool;
 pub use assignment::{
     AssignmentResult, AssignmentStrategy, BackpressureStatus, LoadBalancer, LoadStats, RequestGroup,
 };
+pub use dispatcher::{
+    DispatchPermit, DispatcherConfig, QueuePlacement, QueueTicket, RequestDispatcher,
+};
 pub use fair_scheduler::{FairScheduler, FairnessMetrics, FairnessStats};
 pub use metrics::{PriorityMetrics, QueueMetricsCollector, QueueMetricsSnapshot, RequestMetrics};
 pub use persistence::{
@@ -107,7 +107,7 @@ impl QueuePersistence {
         let compressed_size = compressed.len();
 
         // Write to disk
-        std::fs::write(&self.config.checkpoint_path, compressed)?;
+        crate::io::atomic::write_file(&self.config.checkpoint_path, compressed)?;
 
         self.last_checkpoint_secs = Self::current_timestamp_secs();
 
@@ -46,6 +46,26 @@ impl Priority {
     }
 }
 
+impl std::str::FromStr for Priority {
+    type Err = String;
+
+    /// Parse a level name (`vip`, `high`, `normal`, `low`) or its number (4-1)
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        match value.to_ascii_lowercase().as_str() {
+            "vip" => Ok(Priority::VIP),
+            "high" => Ok(Priority::High),
+            "normal" => Ok(Priority::Normal),
+            "low" => Ok(Priority::Low),
+            _ => value
+                .parse::<u8>()
+                .ok()
+                .and_then(Priority::from_u8)
+                .ok_or_else(|| format!("Unknown priority '{}'", value)),
+        }
+    }
+}
+
 /// Metadata for a queued inference request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RequestMetadata {
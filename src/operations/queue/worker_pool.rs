@@ -171,6 +171,15 @@ impl WorkerPool {
         best_worker_id
     }
 
+    /// Get the least loaded worker with fewer than `max_active` requests in flight
+    pub fn get_available_worker(&self, max_active: u32) -> Option<u32> {
+        self.worker_metrics
+            .values()
+            .filter(|m| m.state != WorkerState::Failed && m.active_requests < max_active)
+            .min_by_key(|m| (m.active_requests, m.worker_id))
+            .map(|m| m.worker_id)
+    }
+
     /// Assign request to a worker
     pub fn assign_request(&mut self, worker_id: u32) -> bool {
         if let Some(metrics) = self.worker_metrics.get_mut(&worker_id) {
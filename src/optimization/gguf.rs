@@ -0,0 +1,1734 @@
+// GGUF requantization for the model quantizer
+// Rewrites the tensors of a GGUF model to a llama.cpp quantization type, copying
+// metadata through unchanged apart from `general.file_type`. F32 and F16 weight
+// matrices are quantized; 1-D tensors (norms, biases) and tensors that are already
+// quantized are copied as they are.
+//
+// The same layout reader checks that a GGUF file is whole before it is installed
+// or loaded, so an interrupted download is reported as such instead of failing
+// somewhere inside llama.cpp, and lists a model's tensors and metadata for
+// `models inspect`.
+//
+// Models too large for one file are split into shards the way llama.cpp's
+// gguf-split does: `model-00001-of-00003.gguf` and so on, each a GGUF file of its
+// own holding some of the tensors. The first keeps the model's metadata, and
+// every shard carries `split.no`, `split.count` and `split.tensors.count`, the
+// index llama.cpp and `convert merge-shards` use to put the model back together.
+
+use crate::InfernoError;
+use anyhow::{Context, Result, anyhow, bail};
+use half::f16;
+use serde::Serialize;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+const DEFAULT_ALIGNMENT: u64 = 32;
+
+const QK: usize = 32; // Block size of the legacy quantization types
+const QK_K: usize = 256; // Super-block size of the k-quants
+
+// ggml tensor type ids
+const GGML_F32: u32 = 0;
+const GGML_F16: u32 = 1;
+const GGML_Q4_0: u32 = 2;
+const GGML_Q5_0: u32 = 6;
+const GGML_Q8_0: u32 = 8;
+const GGML_Q4_K: u32 = 12;
+const GGML_Q5_K: u32 = 13;
+const GGML_Q6_K: u32 = 14;
+
+// GGUF metadata value type ids
+const GGUF_TYPE_UINT16: u32 = 2;
+const GGUF_TYPE_UINT32: u32 = 4;
+const GGUF_TYPE_INT32: u32 = 5;
+const GGUF_TYPE_STRING: u32 = 8;
+const GGUF_TYPE_ARRAY: u32 = 9;
+
+/// Target type for GGUF requantization, named as llama.cpp's `quantize` tool names them
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GgufQuantType {
+    F32,
+    F16,
+    Q4_0,
+    Q5_0,
+    Q8_0,
+    Q4_K_S,
+    Q4_K_M,
+    Q5_K_S,
+    Q5_K_M,
+    Q6_K,
+}
+
+impl GgufQuantType {
+    pub const ALL: [GgufQuantType; 10] = [
+        GgufQuantType::F32,
+        GgufQuantType::F16,
+        GgufQuantType::Q4_0,
+        GgufQuantType::Q5_0,
+        GgufQuantType::Q8_0,
+        GgufQuantType::Q4_K_S,
+        GgufQuantType::Q4_K_M,
+        GgufQuantType::Q5_K_S,
+        GgufQuantType::Q5_K_M,
+        GgufQuantType::Q6_K,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GgufQuantType::F32 => "f32",
+            GgufQuantType::F16 => "f16",
+            GgufQuantType::Q4_0 => "q4_0",
+            GgufQuantType::Q5_0 => "q5_0",
+            GgufQuantType::Q8_0 => "q8_0",
+            GgufQuantType::Q4_K_S => "q4_k_s",
+            GgufQuantType::Q4_K_M => "q4_k_m",
+            GgufQuantType::Q5_K_S => "q5_k_s",
+            GgufQuantType::Q5_K_M => "q5_k_m",
+            GgufQuantType::Q6_K => "q6_k",
+        }
+    }
+
+    /// Value llama.cpp records in `general.file_type` for a model of this type
+    fn file_type(self) -> u32 {
+        match self {
+            GgufQuantType::F32 => 0,
+            GgufQuantType::F16 => 1,
+            GgufQuantType::Q4_0 => 2,
+            GgufQuantType::Q8_0 => 7,
+            GgufQuantType::Q5_0 => 8,
+            GgufQuantType::Q4_K_S => 14,
+            GgufQuantType::Q4_K_M => 15,
+            GgufQuantType::Q5_K_S => 16,
+            GgufQuantType::Q5_K_M => 17,
+            GgufQuantType::Q6_K => 18,
+        }
+    }
+
+    /// ggml type for the weight matrix `name`. As in llama.cpp, k-quant models keep
+    /// `output.weight` at Q6_K, and the `_M` mixes also keep the attention value and
+    /// feed-forward down projections at Q6_K.
+    fn tensor_type(self, name: &str) -> u32 {
+        let base = match self {
+            GgufQuantType::F32 => GGML_F32,
+            GgufQuantType::F16 => GGML_F16,
+            GgufQuantType::Q4_0 => GGML_Q4_0,
+            GgufQuantType::Q5_0 => GGML_Q5_0,
+            GgufQuantType::Q8_0 => GGML_Q8_0,
+            GgufQuantType::Q4_K_S | GgufQuantType::Q4_K_M => GGML_Q4_K,
+            GgufQuantType::Q5_K_S | GgufQuantType::Q5_K_M => GGML_Q5_K,
+            GgufQuantType::Q6_K => GGML_Q6_K,
+        };
+        let sensitive = name == "output.weight"
+            || (matches!(self, GgufQuantType::Q4_K_M | GgufQuantType::Q5_K_M)
+                && (name.contains("attn_v.") || name.contains("ffn_down.")));
+        if matches!(base, GGML_Q4_K | GGML_Q5_K) && sensitive {
+            GGML_Q6_K
+        } else {
+            base
+        }
+    }
+}
+
+impl fmt::Display for GgufQuantType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for GgufQuantType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        GgufQuantType::ALL
+            .into_iter()
+            .find(|t| t.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let supported: Vec<_> = GgufQuantType::ALL.iter().map(|t| t.name()).collect();
+                anyhow!(
+                    "Unsupported GGUF quantization type '{}'; supported types: {}",
+                    s,
+                    supported.join(", ")
+                )
+            })
+    }
+}
+
+/// Outcome of a GGUF requantization
+#[derive(Debug, Clone, Default)]
+pub struct RequantizeReport {
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub tensors_quantized: usize,
+    pub tensors_copied: usize,
+    /// RMS quantization error relative to the RMS of the original weights, across
+    /// every quantized tensor
+    pub relative_rms_error: f64,
+}
+
+#[derive(Clone)]
+struct MetadataEntry {
+    key: String,
+    value_type: u32,
+    /// Value bytes exactly as framed on disk
+    raw: Vec<u8>,
+}
+
+#[derive(Clone)]
+struct TensorInfo {
+    name: String,
+    dims: Vec<u64>,
+    ggml_type: u32,
+    offset: u64,
+}
+
+struct GgufLayout {
+    version: u32,
+    metadata: Vec<MetadataEntry>,
+    tensors: Vec<TensorInfo>,
+    alignment: u64,
+    data_start: u64,
+}
+
+/// Requantize the GGUF model at `input` to `target`, writing a new model to `output`
+pub fn requantize(input: &Path, output: &Path, target: GgufQuantType) -> Result<RequantizeReport> {
+    let mut reader = BufReader::new(File::open(input)?);
+    let input_bytes = reader.get_ref().metadata()?.len();
+    let layout = read_layout(&mut reader)?;
+
+    // Tensor infos, which carry the data offsets, precede the data, so every
+    // output type and size is settled before anything is written
+    let mut planned = Vec::with_capacity(layout.tensors.len());
+    let mut next_offset = 0u64;
+    for tensor in &layout.tensors {
+        let out_type = output_type(tensor, target)?;
+        let out_bytes = tensor_bytes(out_type, &tensor.dims)?;
+        planned.push((out_type, next_offset, out_bytes));
+        next_offset = align(next_offset + out_bytes, layout.alignment);
+    }
+
+    let mut writer = BufWriter::new(File::create(output)?);
+    write_preamble(&mut writer, &layout, &planned, target)?;
+    let position = writer.stream_position()?;
+    pad(&mut writer, align(position, layout.alignment) - position)?;
+
+    let mut report = RequantizeReport {
+        input_bytes,
+        ..Default::default()
+    };
+    let mut error = ErrorStats::default();
+    for (tensor, &(out_type, out_offset, out_bytes)) in layout.tensors.iter().zip(&planned) {
+        let in_bytes = tensor_bytes(tensor.ggml_type, &tensor.dims)?;
+        let start = layout.data_start.saturating_add(tensor.offset);
+        if start.saturating_add(in_bytes) > input_bytes {
+            bail!("Tensor '{}' extends past the end of the file", tensor.name);
+        }
+        reader.seek(SeekFrom::Start(start))?;
+        let mut data = vec![0u8; in_bytes as usize];
+        reader.read_exact(&mut data)?;
+
+        if out_type == tensor.ggml_type {
+            writer.write_all(&data)?;
+            report.tensors_copied += 1;
+        } else {
+            tracing::debug!(
+                "Quantizing tensor '{}' {:?} from type {} to {}",
+                tensor.name,
+                tensor.dims,
+                tensor.ggml_type,
+                out_type
+            );
+            let values = dequantize(&data, tensor.ggml_type)?;
+            let encoded = quantize(&values, out_type, &mut error);
+            debug_assert_eq!(encoded.len() as u64, out_bytes);
+            writer.write_all(&encoded)?;
+            report.tensors_quantized += 1;
+        }
+        let end = out_offset + out_bytes;
+        pad(&mut writer, align(end, layout.alignment) - end)?;
+    }
+    writer.flush()?;
+    drop(writer);
+
+    report.output_bytes = std::fs::metadata(output)?.len();
+    report.relative_rms_error = error.relative_rms();
+    Ok(report)
+}
+
+/// Check that the GGUF file at `path` is complete: its header, metadata and tensor
+/// table parse, and the data of every tensor lies within the file
+///
+/// Failures are [`InfernoError::Model`] errors naming the byte or tensor where the
+/// file stops making sense.
+pub fn check_integrity(path: &Path) -> Result<(), InfernoError> {
+    let corrupt = |detail: String| {
+        InfernoError::Model(format!(
+            "GGUF file {} is truncated or corrupt: {}",
+            path.display(),
+            detail
+        ))
+    };
+    let file = File::open(path).map_err(|e| {
+        InfernoError::Model(format!("Cannot open model file {}: {}", path.display(), e))
+    })?;
+    let file_bytes = file
+        .metadata()
+        .map_err(|e| corrupt(format!("cannot read its size: {}", e)))?
+        .len();
+
+    let mut reader = BufReader::new(file);
+    let layout = match read_layout(&mut reader) {
+        Ok(layout) => layout,
+        Err(e) => {
+            let position = reader.stream_position().unwrap_or(0);
+            let truncated = position >= file_bytes
+                || e.chain().any(|cause| {
+                    cause
+                        .downcast_ref::<std::io::Error>()
+                        .is_some_and(|io| io.kind() == std::io::ErrorKind::UnexpectedEof)
+                });
+            return Err(corrupt(if truncated {
+                format!(
+                    "the file ends at byte {}, before its header and tensor table do",
+                    file_bytes
+                )
+            } else {
+                format!("{:#} at byte {}", e, position)
+            }));
+        }
+    };
+    for tensor in &layout.tensors {
+        if !tensor.offset.is_multiple_of(layout.alignment) {
+            return Err(corrupt(format!(
+                "tensor '{}' has offset {}, which is not aligned to {} bytes",
+                tensor.name, tensor.offset, layout.alignment
+            )));
+        }
+        let start = layout.data_start.saturating_add(tensor.offset);
+        // Types newer than this reader still have their start checked
+        let bytes = tensor_bytes(tensor.ggml_type, &tensor.dims).unwrap_or(0);
+        let end = start.saturating_add(bytes);
+        if start > file_bytes || end > file_bytes {
+            return Err(corrupt(format!(
+                "tensor '{}' occupies bytes {}..{} but the file ends at byte {}; \
+                 it may be an interrupted download",
+                tensor.name, start, end, file_bytes
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Header, metadata and tensor table of a GGUF model
+#[derive(Debug, Clone, Serialize)]
+pub struct GgufInspection {
+    pub version: u32,
+    pub alignment: u64,
+    pub metadata: Vec<GgufMetadataValue>,
+    pub tensors: Vec<GgufTensor>,
+}
+
+/// One metadata key with its decoded value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GgufMetadataValue {
+    pub key: String,
+    /// GGUF value type, such as `u32`, `string` or `array[string]`
+    #[serde(rename = "type")]
+    pub value_type: String,
+    pub value: serde_json::Value,
+}
+
+/// One entry of the tensor table
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GgufTensor {
+    pub name: String,
+    /// Dimensions as GGUF stores them, innermost first
+    pub shape: Vec<u64>,
+    /// ggml type name, such as `f16` or `q4_k`
+    pub dtype: String,
+    /// Bytes of tensor data, unless the type is newer than this reader
+    pub size_bytes: Option<u64>,
+    /// Where the data starts, from the beginning of the file
+    pub offset: u64,
+}
+
+/// Read the metadata and tensor table of the GGUF file at `path`
+pub fn inspect(path: &Path) -> Result<GgufInspection> {
+    let file =
+        File::open(path).with_context(|| format!("Cannot open model file {}", path.display()))?;
+    let layout = read_layout(&mut BufReader::new(file))
+        .with_context(|| format!("Cannot parse GGUF file {}", path.display()))?;
+
+    let metadata = layout
+        .metadata
+        .iter()
+        .map(|entry| {
+            let value = decode_value(&mut entry.raw.as_slice(), entry.value_type)
+                .with_context(|| format!("Invalid value for metadata key '{}'", entry.key))?;
+            let value_type = if entry.value_type == GGUF_TYPE_ARRAY {
+                let element_type = u32::from_le_bytes(entry.raw[..4].try_into()?);
+                format!("array[{}]", value_type_name(element_type))
+            } else {
+                value_type_name(entry.value_type).to_string()
+            };
+            Ok(GgufMetadataValue {
+                key: entry.key.clone(),
+                value_type,
+                value,
+            })
+        })
+        .collect::<Result<_>>()?;
+    let tensors = layout
+        .tensors
+        .iter()
+        .map(|tensor| GgufTensor {
+            name: tensor.name.clone(),
+            shape: tensor.dims.clone(),
+            dtype: ggml_type_name(tensor.ggml_type)
+                .map_or_else(|| format!("type {}", tensor.ggml_type), str::to_string),
+            size_bytes: tensor_bytes(tensor.ggml_type, &tensor.dims).ok(),
+            offset: layout.data_start.saturating_add(tensor.offset),
+        })
+        .collect();
+
+    Ok(GgufInspection {
+        version: layout.version,
+        alignment: layout.alignment,
+        metadata,
+        tensors,
+    })
+}
+
+const SPLIT_NO: &str = "split.no";
+const SPLIT_COUNT: &str = "split.count";
+const SPLIT_TENSORS_COUNT: &str = "split.tensors.count";
+
+/// Shards written by [`split_shards`] or read by [`merge_shards`]
+#[derive(Debug, Clone, Default)]
+pub struct ShardReport {
+    pub shards: Vec<PathBuf>,
+    pub tensors: usize,
+    /// Bytes written, across every output file
+    pub bytes_written: u64,
+}
+
+/// Path of shard `index`, counting from 0, of a model split `count` ways whose
+/// single file would be `base`
+pub fn shard_path(base: &Path, index: usize, count: usize) -> PathBuf {
+    let stem = base
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("model");
+    base.with_file_name(format!("{}-{:05}-of-{:05}.gguf", stem, index + 1, count))
+}
+
+/// Paths of every shard of the model `shard` is part of, or `None` when its
+/// name is not a shard name
+fn sibling_shards(shard: &Path) -> Option<Vec<PathBuf>> {
+    let name = shard.file_name()?.to_str()?.strip_suffix(".gguf")?;
+    let (name, count) = name.rsplit_once("-of-")?;
+    let (stem, number) = name.rsplit_once('-')?;
+    let is_number = |s: &str| s.len() == 5 && s.bytes().all(|b| b.is_ascii_digit());
+    if !is_number(number) || !is_number(count) {
+        return None;
+    }
+    let count: usize = count.parse().ok().filter(|&count| count > 0)?;
+    let base = shard.with_file_name(format!("{}.gguf", stem));
+    Some((0..count).map(|i| shard_path(&base, i, count)).collect())
+}
+
+/// Split the GGUF model at `input` into shards of at most `max_shard_bytes`
+/// each, named after `output` as in `model-00001-of-00003.gguf`
+///
+/// A tensor larger than the limit gets a shard to itself. Merging the shards
+/// gives back `input` byte for byte.
+pub fn split_shards(input: &Path, output: &Path, max_shard_bytes: u64) -> Result<ShardReport> {
+    let mut reader = BufReader::new(File::open(input)?);
+    let input_bytes = reader.get_ref().metadata()?.len();
+    let layout = read_layout(&mut reader)?;
+    let extents = tensor_extents(&layout, input_bytes)?;
+
+    // Every split key has a fixed size, so shard sizes are known before the
+    // number of shards is
+    let split_keys = |no: usize, count: usize, tensors: usize| {
+        vec![
+            MetadataEntry::u16(SPLIT_NO, no as u16),
+            MetadataEntry::u16(SPLIT_COUNT, count as u16),
+            MetadataEntry::i32(SPLIT_TENSORS_COUNT, tensors as i32),
+        ]
+    };
+    // Shards after the first need the alignment to find their data
+    let alignment_key = layout
+        .metadata
+        .iter()
+        .find(|entry| entry.key == "general.alignment")
+        .cloned();
+    let shard_metadata = |no: usize, count: usize| {
+        let mut metadata = if no == 0 {
+            layout.metadata.clone()
+        } else {
+            alignment_key.iter().cloned().collect()
+        };
+        metadata.extend(split_keys(no, count, layout.tensors.len()));
+        metadata
+    };
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let (mut header, mut data) = (0, 0);
+    for (i, tensor) in layout.tensors.iter().enumerate() {
+        let info_bytes = tensor_info_bytes(tensor);
+        let data_bytes = align(extents[i], layout.alignment);
+        match groups.last_mut() {
+            Some(group)
+                if align(header + info_bytes, layout.alignment) + data + data_bytes
+                    <= max_shard_bytes =>
+            {
+                group.push(i);
+                header += info_bytes;
+                data += data_bytes;
+            }
+            _ => {
+                header = header_bytes(&shard_metadata(groups.len(), 0)) + info_bytes;
+                data = data_bytes;
+                groups.push(vec![i]);
+            }
+        }
+    }
+    if groups.is_empty() {
+        bail!("{} has no tensors to split", input.display());
+    }
+    if groups.len() > u16::MAX as usize {
+        bail!(
+            "Splitting into {} shards exceeds the {} a GGUF index can hold; raise the shard size",
+            groups.len(),
+            u16::MAX
+        );
+    }
+
+    let mut report = ShardReport {
+        tensors: layout.tensors.len(),
+        ..Default::default()
+    };
+    for (no, group) in groups.iter().enumerate() {
+        let path = shard_path(output, no, groups.len());
+        let tensors: Vec<_> = group
+            .iter()
+            .map(|&i| (&layout.tensors[i], extents[i]))
+            .collect();
+        report.bytes_written += write_gguf(
+            &path,
+            layout.version,
+            &shard_metadata(no, groups.len()),
+            layout.alignment,
+            &tensors,
+            |i, writer| {
+                let tensor = &layout.tensors[group[i]];
+                reader.seek(SeekFrom::Start(layout.data_start + tensor.offset))?;
+                Ok(std::io::copy(
+                    &mut (&mut reader).take(extents[group[i]]),
+                    writer,
+                )?)
+            },
+        )?;
+        report.shards.push(path);
+    }
+    Ok(report)
+}
+
+/// Merge the shards of the model `shard` belongs to, any one of them, into the
+/// single GGUF file `output`, which is checked to be whole once written
+///
+/// Every shard must be present; an error lists those that are not.
+pub fn merge_shards(shard: &Path, output: &Path) -> Result<ShardReport> {
+    let shards = sibling_shards(shard).ok_or_else(|| {
+        anyhow!(
+            "{} is not named like a shard, such as model-00001-of-00003.gguf",
+            shard.display()
+        )
+    })?;
+    let missing: Vec<String> = shards
+        .iter()
+        .filter(|path| !path.is_file())
+        .map(|path| path.display().to_string())
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "Missing {} of {} shards: {}",
+            missing.len(),
+            shards.len(),
+            missing.join(", ")
+        );
+    }
+
+    let mut layouts = Vec::with_capacity(shards.len());
+    for (no, path) in shards.iter().enumerate() {
+        let mut reader = BufReader::new(File::open(path)?);
+        let file_bytes = reader.get_ref().metadata()?.len();
+        let layout = read_layout(&mut reader)
+            .with_context(|| format!("Cannot parse shard {}", path.display()))?;
+        let index = (
+            metadata_u16(&layout, SPLIT_NO),
+            metadata_u16(&layout, SPLIT_COUNT),
+        );
+        if index != (Some(no as u16), Some(shards.len() as u16)) {
+            bail!(
+                "{} is not shard {} of {}: its split.no and split.count are {:?} and {:?}",
+                path.display(),
+                no + 1,
+                shards.len(),
+                index.0,
+                index.1
+            );
+        }
+        let extents = tensor_extents(&layout, file_bytes)?;
+        layouts.push((reader, layout, extents));
+    }
+
+    let first = &layouts[0].1;
+    let tensor_count: usize = layouts
+        .iter()
+        .map(|(_, layout, _)| layout.tensors.len())
+        .sum();
+    let expected = first
+        .metadata
+        .iter()
+        .find(|entry| entry.key == SPLIT_TENSORS_COUNT && entry.value_type == GGUF_TYPE_INT32)
+        .and_then(|entry| Some(i32::from_le_bytes(entry.raw.get(..4)?.try_into().ok()?)));
+    if expected != Some(tensor_count as i32) {
+        bail!(
+            "The shards hold {} tensors but their index lists {:?}",
+            tensor_count,
+            expected
+        );
+    }
+    let metadata: Vec<_> = first
+        .metadata
+        .iter()
+        .filter(|entry| !entry.key.starts_with("split."))
+        .cloned()
+        .collect();
+    let (version, alignment) = (first.version, first.alignment);
+
+    let tensors: Vec<(TensorInfo, u64, usize)> = layouts
+        .iter()
+        .enumerate()
+        .flat_map(|(shard, (_, layout, extents))| {
+            layout
+                .tensors
+                .iter()
+                .zip(extents)
+                .map(move |(tensor, &extent)| (tensor.clone(), extent, shard))
+        })
+        .collect();
+    let planned: Vec<_> = tensors
+        .iter()
+        .map(|(tensor, extent, _)| (tensor, *extent))
+        .collect();
+    let bytes_written = write_gguf(
+        output,
+        version,
+        &metadata,
+        alignment,
+        &planned,
+        |i, writer| {
+            let (tensor, extent, shard) = &tensors[i];
+            let (reader, layout, _) = &mut layouts[*shard];
+            reader.seek(SeekFrom::Start(layout.data_start + tensor.offset))?;
+            Ok(std::io::copy(&mut reader.take(*extent), writer)?)
+        },
+    )?;
+    check_integrity(output)?;
+
+    Ok(ShardReport {
+        shards,
+        tensors: tensor_count,
+        bytes_written,
+    })
+}
+
+impl MetadataEntry {
+    fn u16(key: &str, value: u16) -> Self {
+        Self {
+            key: key.to_string(),
+            value_type: GGUF_TYPE_UINT16,
+            raw: value.to_le_bytes().to_vec(),
+        }
+    }
+
+    fn i32(key: &str, value: i32) -> Self {
+        Self {
+            key: key.to_string(),
+            value_type: GGUF_TYPE_INT32,
+            raw: value.to_le_bytes().to_vec(),
+        }
+    }
+}
+
+fn metadata_u16(layout: &GgufLayout, key: &str) -> Option<u16> {
+    let entry = layout
+        .metadata
+        .iter()
+        .find(|entry| entry.key == key && entry.value_type == GGUF_TYPE_UINT16)?;
+    Some(u16::from_le_bytes(entry.raw.get(..2)?.try_into().ok()?))
+}
+
+/// Bytes from each tensor's data to the next tensor's, or to the end of the
+/// file for the last, so the padding between tensors travels with them
+fn tensor_extents(layout: &GgufLayout, file_bytes: u64) -> Result<Vec<u64>> {
+    let data_bytes = file_bytes.saturating_sub(layout.data_start);
+    let mut order: Vec<usize> = (0..layout.tensors.len()).collect();
+    order.sort_by_key(|&i| layout.tensors[i].offset);
+    let mut extents = vec![0; layout.tensors.len()];
+    for (k, &i) in order.iter().enumerate() {
+        let tensor = &layout.tensors[i];
+        let end = order
+            .get(k + 1)
+            .map_or(data_bytes, |&next| layout.tensors[next].offset);
+        extents[i] = end
+            .checked_sub(tensor.offset)
+            .ok_or_else(|| anyhow!("Tensor '{}' starts past the end of the file", tensor.name))?;
+    }
+    Ok(extents)
+}
+
+/// Bytes of one entry of the tensor table
+fn tensor_info_bytes(tensor: &TensorInfo) -> u64 {
+    8 + tensor.name.len() as u64 + 4 + 8 * tensor.dims.len() as u64 + 4 + 8
+}
+
+/// Bytes of the header up to the tensor table
+fn header_bytes(metadata: &[MetadataEntry]) -> u64 {
+    let metadata_bytes: u64 = metadata
+        .iter()
+        .map(|entry| 8 + entry.key.len() as u64 + 4 + entry.raw.len() as u64)
+        .sum();
+    24 + metadata_bytes
+}
+
+/// Write a GGUF file holding `tensors`, each with the bytes of data that
+/// follow it, which `copy` writes given the tensor's index and returns the
+/// count of; returns the file size
+fn write_gguf(
+    path: &Path,
+    version: u32,
+    metadata: &[MetadataEntry],
+    alignment: u64,
+    tensors: &[(&TensorInfo, u64)],
+    mut copy: impl FnMut(usize, &mut dyn Write) -> Result<u64>,
+) -> Result<u64> {
+    let mut writer = BufWriter::new(
+        File::create(path).with_context(|| format!("Cannot create {}", path.display()))?,
+    );
+    writer.write_all(GGUF_MAGIC)?;
+    writer.write_all(&version.to_le_bytes())?;
+    writer.write_all(&(tensors.len() as u64).to_le_bytes())?;
+    writer.write_all(&(metadata.len() as u64).to_le_bytes())?;
+    for entry in metadata {
+        write_string(&mut writer, &entry.key)?;
+        writer.write_all(&entry.value_type.to_le_bytes())?;
+        writer.write_all(&entry.raw)?;
+    }
+
+    // Data keeps the alignment of every tensor, whatever order it came in
+    let mut offsets = Vec::with_capacity(tensors.len());
+    let mut next_offset = 0;
+    for (tensor, extent) in tensors {
+        offsets.push(next_offset);
+        next_offset = align(next_offset + extent, alignment);
+        write_string(&mut writer, &tensor.name)?;
+        writer.write_all(&(tensor.dims.len() as u32).to_le_bytes())?;
+        for dim in &tensor.dims {
+            writer.write_all(&dim.to_le_bytes())?;
+        }
+        writer.write_all(&tensor.ggml_type.to_le_bytes())?;
+        writer.write_all(&offsets.last().copied().unwrap_or_default().to_le_bytes())?;
+    }
+    let position = writer.stream_position()?;
+    pad(&mut writer, align(position, alignment) - position)?;
+
+    for (i, &(tensor, extent)) in tensors.iter().enumerate() {
+        let copied = copy(i, &mut writer)?;
+        if copied != extent {
+            bail!(
+                "Tensor '{}' has {} bytes of data where {} were expected",
+                tensor.name,
+                copied,
+                extent
+            );
+        }
+        // The last tensor's data runs to the end of the file as it was
+        if i + 1 < tensors.len() {
+            let end = offsets[i] + extent;
+            pad(&mut writer, align(end, alignment) - end)?;
+        }
+    }
+    writer.flush()?;
+    Ok(writer.stream_position()?)
+}
+
+fn ggml_type_name(ggml_type: u32) -> Option<&'static str> {
+    Some(match ggml_type {
+        0 => "f32",
+        1 => "f16",
+        2 => "q4_0",
+        3 => "q4_1",
+        6 => "q5_0",
+        7 => "q5_1",
+        8 => "q8_0",
+        9 => "q8_1",
+        10 => "q2_k",
+        11 => "q3_k",
+        12 => "q4_k",
+        13 => "q5_k",
+        14 => "q6_k",
+        15 => "q8_k",
+        16 => "iq2_xxs",
+        17 => "iq2_xs",
+        18 => "iq3_xxs",
+        19 => "iq1_s",
+        20 => "iq4_nl",
+        21 => "iq3_s",
+        22 => "iq2_s",
+        23 => "iq4_xs",
+        24 => "i8",
+        25 => "i16",
+        26 => "i32",
+        27 => "i64",
+        28 => "f64",
+        29 => "iq1_m",
+        30 => "bf16",
+        _ => return None,
+    })
+}
+
+fn value_type_name(value_type: u32) -> &'static str {
+    match value_type {
+        0 => "u8",
+        1 => "i8",
+        2 => "u16",
+        3 => "i16",
+        4 => "u32",
+        5 => "i32",
+        6 => "f32",
+        7 => "bool",
+        GGUF_TYPE_STRING => "string",
+        GGUF_TYPE_ARRAY => "array",
+        10 => "u64",
+        11 => "i64",
+        12 => "f64",
+        _ => "unknown",
+    }
+}
+
+/// Decode one metadata value from its on-disk bytes, advancing `raw` past it
+fn decode_value(raw: &mut &[u8], value_type: u32) -> Result<serde_json::Value> {
+    fn take<const N: usize>(raw: &mut &[u8]) -> Result<[u8; N]> {
+        let (head, rest) = raw
+            .split_first_chunk::<N>()
+            .ok_or_else(|| anyhow!("Metadata value ends early"))?;
+        *raw = rest;
+        Ok(*head)
+    }
+    fn float(value: f64) -> serde_json::Value {
+        serde_json::Number::from_f64(value).map_or(serde_json::Value::Null, Into::into)
+    }
+
+    Ok(match value_type {
+        0 => u8::from_le_bytes(take(raw)?).into(),
+        1 => i8::from_le_bytes(take(raw)?).into(),
+        2 => u16::from_le_bytes(take(raw)?).into(),
+        3 => i16::from_le_bytes(take(raw)?).into(),
+        4 => u32::from_le_bytes(take(raw)?).into(),
+        5 => i32::from_le_bytes(take(raw)?).into(),
+        6 => float(f32::from_le_bytes(take(raw)?).into()),
+        7 => (take::<1>(raw)?[0] != 0).into(),
+        GGUF_TYPE_STRING => {
+            let len = u64::from_le_bytes(take(raw)?);
+            let len = usize::try_from(len).ok().filter(|&len| len <= raw.len());
+            let len = len.ok_or_else(|| anyhow!("Metadata value ends early"))?;
+            let (text, rest) = raw.split_at(len);
+            *raw = rest;
+            String::from_utf8_lossy(text).into_owned().into()
+        }
+        GGUF_TYPE_ARRAY => {
+            let element_type = u32::from_le_bytes(take(raw)?);
+            let len = u64::from_le_bytes(take(raw)?);
+            (0..len)
+                .map(|_| decode_value(raw, element_type))
+                .collect::<Result<Vec<_>>>()?
+                .into()
+        }
+        10 => u64::from_le_bytes(take(raw)?).into(),
+        11 => i64::from_le_bytes(take(raw)?).into(),
+        12 => float(f64::from_le_bytes(take(raw)?)),
+        other => bail!("Unknown GGUF metadata value type {}", other),
+    })
+}
+
+fn output_type(tensor: &TensorInfo, target: GgufQuantType) -> Result<u32> {
+    if tensor.dims.len() < 2 || !matches!(tensor.ggml_type, GGML_F32 | GGML_F16) {
+        return Ok(tensor.ggml_type);
+    }
+    let wanted = target.tensor_type(&tensor.name);
+    let row = tensor.dims[0];
+    let (block, _) = block_layout(wanted)?;
+    // Rows must hold whole blocks; fall back the way llama.cpp does for odd shapes
+    Ok(if row.is_multiple_of(block as u64) {
+        wanted
+    } else if row.is_multiple_of(QK as u64) {
+        GGML_Q8_0
+    } else {
+        tensor.ggml_type
+    })
+}
+
+/// Elements per block and bytes per block of a ggml tensor type
+fn block_layout(ggml_type: u32) -> Result<(usize, usize)> {
+    Ok(match ggml_type {
+        0 => (1, 4),
+        1 => (1, 2),
+        2 => (32, 18),
+        3 => (32, 20),
+        6 => (32, 22),
+        7 => (32, 24),
+        8 => (32, 34),
+        9 => (32, 36),
+        10 => (256, 84),
+        11 => (256, 110),
+        12 => (256, 144),
+        13 => (256, 176),
+        14 => (256, 210),
+        15 => (256, 292),
+        16 => (256, 66),
+        17 => (256, 74),
+        18 => (256, 98),
+        19 => (256, 50),
+        20 => (32, 18),
+        21 => (256, 110),
+        22 => (256, 82),
+        23 => (256, 136),
+        24 => (1, 1),
+        25 => (1, 2),
+        26 => (1, 4),
+        27 => (1, 8),
+        28 => (1, 8),
+        29 => (256, 56),
+        30 => (1, 2),
+        other => bail!("Unknown ggml tensor type {}", other),
+    })
+}
+
+fn tensor_bytes(ggml_type: u32, dims: &[u64]) -> Result<u64> {
+    let (block, block_bytes) = block_layout(ggml_type)?;
+    let elements = dims
+        .iter()
+        .try_fold(1u64, |n, &d| n.checked_mul(d))
+        .ok_or_else(|| anyhow!("Tensor dimensions {:?} overflow", dims))?;
+    if !elements.is_multiple_of(block as u64) {
+        bail!(
+            "Tensor of {} elements does not divide into blocks of {}",
+            elements,
+            block
+        );
+    }
+    Ok(elements / block as u64 * block_bytes as u64)
+}
+
+fn align(offset: u64, alignment: u64) -> u64 {
+    offset.div_ceil(alignment) * alignment
+}
+
+fn pad<W: Write>(writer: &mut W, len: u64) -> Result<()> {
+    writer.write_all(&vec![0u8; len as usize])?;
+    Ok(())
+}
+
+fn read_layout<R: Read + Seek>(reader: &mut R) -> Result<GgufLayout> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != GGUF_MAGIC {
+        bail!("Invalid GGUF file format");
+    }
+    let version = read_u32(reader)?;
+    if !(2..=3).contains(&version) {
+        bail!("Unsupported GGUF version {}", version);
+    }
+    let tensor_count = read_u64(reader)?;
+    let metadata_count = read_u64(reader)?;
+
+    let mut metadata = Vec::new();
+    let mut alignment = DEFAULT_ALIGNMENT;
+    for _ in 0..metadata_count {
+        let key = read_string(reader)?;
+        let value_type = read_u32(reader)?;
+        let mut raw = Vec::new();
+        read_value(reader, value_type, &mut raw)
+            .with_context(|| format!("Invalid value for metadata key '{}'", key))?;
+        if key == "general.alignment" && value_type == GGUF_TYPE_UINT32 {
+            alignment = u32::from_le_bytes(raw[..4].try_into()?) as u64;
+            if alignment == 0 {
+                bail!("Invalid general.alignment of 0");
+            }
+        }
+        metadata.push(MetadataEntry {
+            key,
+            value_type,
+            raw,
+        });
+    }
+
+    let mut tensors = Vec::new();
+    for _ in 0..tensor_count {
+        let name = read_string(reader)?;
+        let n_dims = read_u32(reader)?;
+        let dims = (0..n_dims)
+            .map(|_| read_u64(reader))
+            .collect::<Result<Vec<_>>>()?;
+        let ggml_type = read_u32(reader)?;
+        let offset = read_u64(reader)?;
+        tensors.push(TensorInfo {
+            name,
+            dims,
+            ggml_type,
+            offset,
+        });
+    }
+
+    let data_start = align(reader.stream_position()?, alignment);
+    Ok(GgufLayout {
+        version,
+        metadata,
+        tensors,
+        alignment,
+        data_start,
+    })
+}
+
+fn write_preamble<W: Write>(
+    writer: &mut W,
+    layout: &GgufLayout,
+    planned: &[(u32, u64, u64)],
+    target: GgufQuantType,
+) -> Result<()> {
+    let file_type = target.file_type().to_le_bytes();
+    let has_file_type = layout
+        .metadata
+        .iter()
+        .any(|entry| entry.key == "general.file_type");
+
+    writer.write_all(GGUF_MAGIC)?;
+    writer.write_all(&layout.version.to_le_bytes())?;
+    writer.write_all(&(layout.tensors.len() as u64).to_le_bytes())?;
+    let metadata_count = layout.metadata.len() + usize::from(!has_file_type);
+    writer.write_all(&(metadata_count as u64).to_le_bytes())?;
+
+    for entry in &layout.metadata {
+        write_string(writer, &entry.key)?;
+        if entry.key == "general.file_type" {
+            writer.write_all(&GGUF_TYPE_UINT32.to_le_bytes())?;
+            writer.write_all(&file_type)?;
+        } else {
+            writer.write_all(&entry.value_type.to_le_bytes())?;
+            writer.write_all(&entry.raw)?;
+        }
+    }
+    if !has_file_type {
+        write_string(writer, "general.file_type")?;
+        writer.write_all(&GGUF_TYPE_UINT32.to_le_bytes())?;
+        writer.write_all(&file_type)?;
+    }
+
+    for (tensor, &(out_type, out_offset, _)) in layout.tensors.iter().zip(planned) {
+        write_string(writer, &tensor.name)?;
+        writer.write_all(&(tensor.dims.len() as u32).to_le_bytes())?;
+        for dim in &tensor.dims {
+            writer.write_all(&dim.to_le_bytes())?;
+        }
+        writer.write_all(&out_type.to_le_bytes())?;
+        writer.write_all(&out_offset.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_string<R: Read>(reader: &mut R) -> Result<String> {
+    let len = read_u64(reader)?;
+    let mut raw = Vec::new();
+    read_exact_into(reader, len, &mut raw)?;
+    Ok(String::from_utf8(raw)?)
+}
+
+fn write_string<W: Write>(writer: &mut W, s: &str) -> Result<()> {
+    writer.write_all(&(s.len() as u64).to_le_bytes())?;
+    writer.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+/// Append `len` bytes to `out` without trusting `len` for the allocation size
+fn read_exact_into<R: Read>(reader: &mut R, len: u64, out: &mut Vec<u8>) -> Result<()> {
+    let read = reader.take(len).read_to_end(out)?;
+    if read as u64 != len {
+        bail!("Unexpected end of GGUF file");
+    }
+    Ok(())
+}
+
+/// Append the on-disk bytes of one metadata value to `out`
+fn read_value<R: Read>(reader: &mut R, value_type: u32, out: &mut Vec<u8>) -> Result<()> {
+    let width = match value_type {
+        0 | 1 | 7 => 1,
+        2 | 3 => 2,
+        4..=6 => 4,
+        10..=12 => 8,
+        GGUF_TYPE_STRING => {
+            let len = read_u64(reader)?;
+            out.extend_from_slice(&len.to_le_bytes());
+            return read_exact_into(reader, len, out);
+        }
+        GGUF_TYPE_ARRAY => {
+            let element_type = read_u32(reader)?;
+            let len = read_u64(reader)?;
+            out.extend_from_slice(&element_type.to_le_bytes());
+            out.extend_from_slice(&len.to_le_bytes());
+            for _ in 0..len {
+                read_value(reader, element_type, out)?;
+            }
+            return Ok(());
+        }
+        other => bail!("Unknown GGUF metadata value type {}", other),
+    };
+    read_exact_into(reader, width, out)
+}
+
+fn dequantize(data: &[u8], ggml_type: u32) -> Result<Vec<f32>> {
+    Ok(match ggml_type {
+        GGML_F32 => data
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        GGML_F16 => data
+            .chunks_exact(2)
+            .map(|b| f16::from_le_bytes([b[0], b[1]]).to_f32())
+            .collect(),
+        other => bail!("Cannot requantize tensors of ggml type {}", other),
+    })
+}
+
+/// Squared error and signal accumulated over every quantized weight
+#[derive(Default)]
+struct ErrorStats {
+    squared_error: f64,
+    squared_signal: f64,
+}
+
+impl ErrorStats {
+    fn record(&mut self, original: f32, reconstructed: f32) {
+        let diff = (original - reconstructed) as f64;
+        self.squared_error += diff * diff;
+        self.squared_signal += (original as f64) * (original as f64);
+    }
+
+    fn relative_rms(&self) -> f64 {
+        if self.squared_signal > 0.0 {
+            (self.squared_error / self.squared_signal).sqrt()
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Encode `values` as `ggml_type`; the caller has checked the type's block size divides them
+fn quantize(values: &[f32], ggml_type: u32, error: &mut ErrorStats) -> Vec<u8> {
+    let mut out = Vec::new();
+    match ggml_type {
+        GGML_F32 => {
+            for &x in values {
+                out.extend_from_slice(&x.to_le_bytes());
+                error.record(x, x);
+            }
+        }
+        GGML_F16 => {
+            for &x in values {
+                let h = f16::from_f32(x);
+                out.extend_from_slice(&h.to_le_bytes());
+                error.record(x, h.to_f32());
+            }
+        }
+        GGML_Q4_0 => values
+            .chunks_exact(QK)
+            .for_each(|block| quantize_q4_0(block, &mut out, error)),
+        GGML_Q5_0 => values
+            .chunks_exact(QK)
+            .for_each(|block| quantize_q5_0(block, &mut out, error)),
+        GGML_Q8_0 => values
+            .chunks_exact(QK)
+            .for_each(|block| quantize_q8_0(block, &mut out, error)),
+        GGML_Q4_K => values
+            .chunks_exact(QK_K)
+            .for_each(|block| quantize_q4_k(block, &mut out, error)),
+        GGML_Q5_K => values
+            .chunks_exact(QK_K)
+            .for_each(|block| quantize_q5_k(block, &mut out, error)),
+        GGML_Q6_K => values
+            .chunks_exact(QK_K)
+            .for_each(|block| quantize_q6_k(block, &mut out, error)),
+        other => unreachable!("no encoder for ggml type {}", other),
+    }
+    out
+}
+
+/// The value with the largest magnitude, keeping its sign
+fn signed_abs_max(values: &[f32]) -> f32 {
+    values
+        .iter()
+        .fold(0f32, |m, &x| if x.abs() > m.abs() { x } else { m })
+}
+
+fn quantize_q8_0(block: &[f32], out: &mut Vec<u8>, error: &mut ErrorStats) {
+    let amax = block.iter().fold(0f32, |m, &x| m.max(x.abs()));
+    let d = f16::from_f32(amax / 127.0);
+    let id = if amax > 0.0 { 127.0 / amax } else { 0.0 };
+    out.extend_from_slice(&d.to_le_bytes());
+    for &x in block {
+        let q = (x * id).round() as i8;
+        out.push(q as u8);
+        error.record(x, q as f32 * d.to_f32());
+    }
+}
+
+fn quantize_q4_0(block: &[f32], out: &mut Vec<u8>, error: &mut ErrorStats) {
+    let max = signed_abs_max(block);
+    let d = f16::from_f32(max / -8.0);
+    let id = if max != 0.0 { -8.0 / max } else { 0.0 };
+    out.extend_from_slice(&d.to_le_bytes());
+    let (low, high) = block.split_at(QK / 2);
+    for (&x0, &x1) in low.iter().zip(high) {
+        let q0 = ((x0 * id + 8.5) as u8).min(15);
+        let q1 = ((x1 * id + 8.5) as u8).min(15);
+        out.push(q0 | (q1 << 4));
+        error.record(x0, (q0 as f32 - 8.0) * d.to_f32());
+        error.record(x1, (q1 as f32 - 8.0) * d.to_f32());
+    }
+}
+
+fn quantize_q5_0(block: &[f32], out: &mut Vec<u8>, error: &mut ErrorStats) {
+    let max = signed_abs_max(block);
+    let d = f16::from_f32(max / -16.0);
+    let id = if max != 0.0 { -16.0 / max } else { 0.0 };
+    let mut qh = 0u32;
+    let mut qs = [0u8; QK / 2];
+    let (low, high) = block.split_at(QK / 2);
+    for (j, (&x0, &x1)) in low.iter().zip(high).enumerate() {
+        let q0 = ((x0 * id + 16.5) as u8).min(31);
+        let q1 = ((x1 * id + 16.5) as u8).min(31);
+        qs[j] = (q0 & 0x0F) | ((q1 & 0x0F) << 4);
+        qh |= ((q0 >> 4) as u32) << j;
+        qh |= ((q1 >> 4) as u32) << (j + QK / 2);
+        error.record(x0, (q0 as f32 - 16.0) * d.to_f32());
+        error.record(x1, (q1 as f32 - 16.0) * d.to_f32());
+    }
+    out.extend_from_slice(&d.to_le_bytes());
+    out.extend_from_slice(&qh.to_le_bytes());
+    out.extend_from_slice(&qs);
+}
+
+/// Super-block header and levels `0..=nmax` shared by Q4_K and Q5_K: eight
+/// sub-blocks of 32, each with a 6-bit scale and minimum relative to `d` and `dmin`
+struct AsymmetricSuperBlock {
+    d: f16,
+    dmin: f16,
+    scales: [u8; 12],
+    levels: [u8; QK_K],
+}
+
+fn quantize_k_asymmetric(block: &[f32], nmax: u8, error: &mut ErrorStats) -> AsymmetricSuperBlock {
+    let mut sub_scales = [0f32; 8];
+    let mut sub_mins = [0f32; 8];
+    for (j, sub) in block.chunks_exact(32).enumerate() {
+        let min = sub.iter().fold(0f32, |m, &x| m.min(x));
+        let max = sub.iter().fold(min, |m, &x| m.max(x));
+        sub_scales[j] = (max - min) / nmax as f32;
+        sub_mins[j] = -min;
+    }
+    let max_scale = sub_scales.iter().fold(0f32, |m, &s| m.max(s));
+    let max_min = sub_mins.iter().fold(0f32, |m, &s| m.max(s));
+    let inv_scale = if max_scale > 0.0 {
+        63.0 / max_scale
+    } else {
+        0.0
+    };
+    let inv_min = if max_min > 0.0 { 63.0 / max_min } else { 0.0 };
+
+    let mut ls = [0u8; 8];
+    let mut lm = [0u8; 8];
+    for (l, &s) in ls.iter_mut().zip(&sub_scales) {
+        *l = ((inv_scale * s).round() as u8).min(63);
+    }
+    for (l, &m) in lm.iter_mut().zip(&sub_mins) {
+        *l = ((inv_min * m).round() as u8).min(63);
+    }
+    let d = f16::from_f32(max_scale / 63.0);
+    let dmin = f16::from_f32(max_min / 63.0);
+
+    let mut levels = [0u8; QK_K];
+    for (j, sub) in block.chunks_exact(32).enumerate() {
+        let scale = d.to_f32() * ls[j] as f32;
+        let min = dmin.to_f32() * lm[j] as f32;
+        for (i, &x) in sub.iter().enumerate() {
+            let level = if scale > 0.0 {
+                (((x + min) / scale).round() as u8).min(nmax)
+            } else {
+                0
+            };
+            levels[32 * j + i] = level;
+            error.record(x, scale * level as f32 - min);
+        }
+    }
+
+    AsymmetricSuperBlock {
+        d,
+        dmin,
+        scales: pack_scales_mins(&ls, &lm),
+        levels,
+    }
+}
+
+/// Pack eight 6-bit scales and minimums the way ggml's k-quants store them
+fn pack_scales_mins(ls: &[u8; 8], lm: &[u8; 8]) -> [u8; 12] {
+    let mut packed = [0u8; 12];
+    for (j, (&scale, &min)) in ls.iter().zip(lm).enumerate() {
+        if j < 4 {
+            packed[j] = scale;
+            packed[j + 4] = min;
+        } else {
+            packed[j + 4] = (scale & 0x0F) | ((min & 0x0F) << 4);
+            packed[j - 4] |= (scale >> 4) << 6;
+            packed[j] |= (min >> 4) << 6;
+        }
+    }
+    packed
+}
+
+fn quantize_q4_k(block: &[f32], out: &mut Vec<u8>, error: &mut ErrorStats) {
+    let sb = quantize_k_asymmetric(block, 15, error);
+    out.extend_from_slice(&sb.d.to_le_bytes());
+    out.extend_from_slice(&sb.dmin.to_le_bytes());
+    out.extend_from_slice(&sb.scales);
+    for n in (0..QK_K).step_by(64) {
+        for l in 0..32 {
+            out.push(sb.levels[n + l] | (sb.levels[n + l + 32] << 4));
+        }
+    }
+}
+
+fn quantize_q5_k(block: &[f32], out: &mut Vec<u8>, error: &mut ErrorStats) {
+    let sb = quantize_k_asymmetric(block, 31, error);
+    let mut qh = [0u8; 32];
+    let mut qs = [0u8; QK_K / 2];
+    for (pair, n) in (0..QK_K).step_by(64).enumerate() {
+        let (m1, m2) = (1u8 << (2 * pair), 2u8 << (2 * pair));
+        for (j, high_bits) in qh.iter_mut().enumerate() {
+            let mut l1 = sb.levels[n + j];
+            let mut l2 = sb.levels[n + j + 32];
+            if l1 > 15 {
+                l1 -= 16;
+                *high_bits |= m1;
+            }
+            if l2 > 15 {
+                l2 -= 16;
+                *high_bits |= m2;
+            }
+            qs[n / 2 + j] = l1 | (l2 << 4);
+        }
+    }
+    out.extend_from_slice(&sb.d.to_le_bytes());
+    out.extend_from_slice(&sb.dmin.to_le_bytes());
+    out.extend_from_slice(&sb.scales);
+    out.extend_from_slice(&qh);
+    out.extend_from_slice(&qs);
+}
+
+fn quantize_q6_k(block: &[f32], out: &mut Vec<u8>, error: &mut ErrorStats) {
+    let mut sub_scales = [0f32; 16];
+    for (j, sub) in block.chunks_exact(16).enumerate() {
+        sub_scales[j] = signed_abs_max(sub) / -32.0;
+    }
+    let max_scale = signed_abs_max(&sub_scales);
+    let iscale = if max_scale != 0.0 {
+        -128.0 / max_scale
+    } else {
+        0.0
+    };
+    let d = f16::from_f32(if iscale != 0.0 { 1.0 / iscale } else { 0.0 });
+    let mut scales = [0i8; 16];
+    for (scale, &s) in scales.iter_mut().zip(&sub_scales) {
+        *scale = ((iscale * s).round() as i32).min(127) as i8;
+    }
+
+    let mut levels = [32u8; QK_K];
+    for (j, sub) in block.chunks_exact(16).enumerate() {
+        let scale = d.to_f32() * scales[j] as f32;
+        for (i, &x) in sub.iter().enumerate() {
+            let q = if scale != 0.0 {
+                ((x / scale).round() as i32).clamp(-32, 31)
+            } else {
+                0
+            };
+            levels[16 * j + i] = (q + 32) as u8;
+            error.record(x, scale * q as f32);
+        }
+    }
+
+    let mut ql = [0u8; QK_K / 2];
+    let mut qh = [0u8; QK_K / 4];
+    for (half, n) in (0..QK_K).step_by(128).enumerate() {
+        let (ql, qh) = (&mut ql[64 * half..], &mut qh[32 * half..32 * half + 32]);
+        for (l, high_bits) in qh.iter_mut().enumerate() {
+            let q = [
+                levels[n + l],
+                levels[n + l + 32],
+                levels[n + l + 64],
+                levels[n + l + 96],
+            ];
+            ql[l] = (q[0] & 0x0F) | ((q[2] & 0x0F) << 4);
+            ql[l + 32] = (q[1] & 0x0F) | ((q[3] & 0x0F) << 4);
+            *high_bits = (q[0] >> 4) | ((q[1] >> 4) << 2) | ((q[2] >> 4) << 4) | ((q[3] >> 4) << 6);
+        }
+    }
+    out.extend_from_slice(&ql);
+    out.extend_from_slice(&qh);
+    out.extend(scales.iter().map(|&s| s as u8));
+    out.extend_from_slice(&d.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimization::quantization::{ModelQuantizer, QuantizationConfig};
+    use tempfile::tempdir;
+
+    /// A two-tensor F32 model: one 64x8 weight matrix and a 1-D norm
+    fn write_tiny_model(path: &Path) -> Vec<f32> {
+        let weights: Vec<f32> = (0..512).map(|i| (i as f32 * 0.37).sin()).collect();
+        let mut file = Vec::new();
+        file.extend_from_slice(GGUF_MAGIC);
+        file.extend_from_slice(&3u32.to_le_bytes());
+        file.extend_from_slice(&2u64.to_le_bytes());
+        file.extend_from_slice(&1u64.to_le_bytes());
+        write_string(&mut file, "general.architecture").unwrap();
+        file.extend_from_slice(&GGUF_TYPE_STRING.to_le_bytes());
+        write_string(&mut file, "llama").unwrap();
+
+        for (name, dims, offset) in [
+            ("blk.0.attn_q.weight", vec![64u64, 8], 0u64),
+            ("blk.0.attn_norm.weight", vec![64], 2048),
+        ] {
+            write_string(&mut file, name).unwrap();
+            file.extend_from_slice(&(dims.len() as u32).to_le_bytes());
+            for dim in dims {
+                file.extend_from_slice(&dim.to_le_bytes());
+            }
+            file.extend_from_slice(&GGML_F32.to_le_bytes());
+            file.extend_from_slice(&offset.to_le_bytes());
+        }
+        file.resize(align(file.len() as u64, DEFAULT_ALIGNMENT) as usize, 0);
+        for x in weights.iter().chain(&[1.0f32; 64]) {
+            file.extend_from_slice(&x.to_le_bytes());
+        }
+        std::fs::write(path, file).unwrap();
+        weights
+    }
+
+    #[tokio::test]
+    async fn test_quantize_gguf_to_q8_0() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("tiny.gguf");
+        let weights = write_tiny_model(&input);
+
+        let mut quantizer = ModelQuantizer::new(QuantizationConfig::default())
+            .await
+            .unwrap();
+        let output = quantizer
+            .quantize_model(input.to_str().unwrap(), "Q8_0")
+            .await
+            .unwrap();
+        assert!(output.ends_with("tiny_q8_0.gguf"));
+
+        let input_size = std::fs::metadata(&input).unwrap().len();
+        let output_size = std::fs::metadata(&output).unwrap().len();
+        assert!(output_size < input_size);
+
+        // The output is a well-formed GGUF whose weight matrix is now Q8_0
+        let mut reader = BufReader::new(File::open(&output).unwrap());
+        let layout = read_layout(&mut reader).unwrap();
+        let file_type = layout
+            .metadata
+            .iter()
+            .find(|entry| entry.key == "general.file_type")
+            .unwrap();
+        assert_eq!(file_type.raw, 7u32.to_le_bytes());
+        assert_eq!(layout.tensors[0].ggml_type, GGML_Q8_0);
+        assert_eq!(layout.tensors[1].ggml_type, GGML_F32);
+        assert_eq!(layout.tensors[1].offset % layout.alignment, 0);
+        let data_end = layout.data_start
+            + layout.tensors[1].offset
+            + tensor_bytes(GGML_F32, &layout.tensors[1].dims).unwrap();
+        assert!(data_end <= output_size);
+
+        // Decoding the Q8_0 blocks gives back the original weights closely
+        reader
+            .seek(SeekFrom::Start(
+                layout.data_start + layout.tensors[0].offset,
+            ))
+            .unwrap();
+        let mut data =
+            vec![0u8; tensor_bytes(GGML_Q8_0, &layout.tensors[0].dims).unwrap() as usize];
+        reader.read_exact(&mut data).unwrap();
+        for (block, originals) in data.chunks_exact(34).zip(weights.chunks_exact(QK)) {
+            let d = f16::from_le_bytes([block[0], block[1]]).to_f32();
+            for (&q, &x) in block[2..].iter().zip(originals) {
+                assert!((q as i8 as f32 * d - x).abs() < 0.01);
+            }
+        }
+
+        let metrics = quantizer.get_metrics().await;
+        assert!(metrics.memory_reduction > 0.0);
+        assert_eq!(metrics.output_size_bytes, output_size);
+        assert!(metrics.accuracy_loss > 0.0 && metrics.accuracy_loss < 0.01);
+    }
+
+    #[test]
+    fn test_truncated_gguf_reports_where_it_ends() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("tiny.gguf");
+        write_tiny_model(&path);
+        check_integrity(&path).unwrap();
+        let whole = std::fs::read(&path).unwrap();
+
+        // Cut inside the tensor data, as an interrupted download leaves it
+        std::fs::write(&path, &whole[..whole.len() - 100]).unwrap();
+        let err = check_integrity(&path).unwrap_err();
+        let InfernoError::Model(message) = &err else {
+            panic!("expected a model error, got {:?}", err);
+        };
+        assert!(
+            message.contains("tensor 'blk.0.attn_norm.weight'")
+                && message.contains("interrupted download"),
+            "{}",
+            message
+        );
+
+        // Cut inside the tensor table
+        std::fs::write(&path, &whole[..80]).unwrap();
+        let message = check_integrity(&path).unwrap_err().to_string();
+        assert!(
+            message.contains("the file ends at byte 80, before its header"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_k_quant_mixes() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("tiny.gguf");
+        write_tiny_model(&input);
+
+        // 64-wide rows cannot hold 256-element k-quant super-blocks, so the
+        // matrix falls back to Q8_0
+        let output = dir.path().join("q4_k_m.gguf");
+        let report = requantize(&input, &output, GgufQuantType::Q4_K_M).unwrap();
+        assert_eq!((report.tensors_quantized, report.tensors_copied), (1, 1));
+        let layout = read_layout(&mut BufReader::new(File::open(&output).unwrap())).unwrap();
+        assert_eq!(layout.tensors[0].ggml_type, GGML_Q8_0);
+
+        let weights: Vec<f32> = (0..QK_K).map(|i| (i as f32 * 0.11).cos()).collect();
+        for (ggml_type, max_error) in [
+            (GGML_Q4_K, 0.1),
+            (GGML_Q5_K, 0.05),
+            (GGML_Q6_K, 0.03),
+            (GGML_Q4_0, 0.1),
+            (GGML_Q5_0, 0.05),
+        ] {
+            let mut error = ErrorStats::default();
+            let encoded = quantize(&weights, ggml_type, &mut error);
+            let (block, block_bytes) = block_layout(ggml_type).unwrap();
+            assert_eq!(encoded.len(), QK_K / block * block_bytes);
+            assert!(
+                error.relative_rms() < max_error,
+                "type {} error {}",
+                ggml_type,
+                error.relative_rms()
+            );
+        }
+    }
+
+    #[test]
+    fn test_unsupported_target_and_input() {
+        let err = "q3_k_m".parse::<GgufQuantType>().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Unsupported GGUF quantization type 'q3_k_m'")
+        );
+        assert_eq!(
+            "Q5_K_M".parse::<GgufQuantType>().unwrap(),
+            GgufQuantType::Q5_K_M
+        );
+
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("bad.gguf");
+        std::fs::write(&input, b"not a model").unwrap();
+        let err =
+            requantize(&input, &dir.path().join("out.gguf"), GgufQuantType::Q8_0).unwrap_err();
+        assert!(err.to_string().contains("Invalid GGUF file format"));
+    }
+
+    #[test]
+    fn test_inspect_lists_tensors_and_metadata() {
+        let mut file = Vec::new();
+        file.extend_from_slice(GGUF_MAGIC);
+        file.extend_from_slice(&3u32.to_le_bytes());
+        file.extend_from_slice(&2u64.to_le_bytes());
+        file.extend_from_slice(&4u64.to_le_bytes());
+        write_string(&mut file, "general.architecture").unwrap();
+        file.extend_from_slice(&GGUF_TYPE_STRING.to_le_bytes());
+        write_string(&mut file, "llama").unwrap();
+        write_string(&mut file, "llama.context_length").unwrap();
+        file.extend_from_slice(&GGUF_TYPE_UINT32.to_le_bytes());
+        file.extend_from_slice(&4096u32.to_le_bytes());
+        write_string(&mut file, "llama.rope.freq_base").unwrap();
+        file.extend_from_slice(&6u32.to_le_bytes());
+        file.extend_from_slice(&10000.0f32.to_le_bytes());
+        write_string(&mut file, "tokenizer.ggml.tokens").unwrap();
+        file.extend_from_slice(&GGUF_TYPE_ARRAY.to_le_bytes());
+        file.extend_from_slice(&GGUF_TYPE_STRING.to_le_bytes());
+        file.extend_from_slice(&3u64.to_le_bytes());
+        for token in ["<s>", "</s>", "hi"] {
+            write_string(&mut file, token).unwrap();
+        }
+        for (name, dims, ggml_type, offset) in [
+            ("token_embd.weight", vec![64u64, 3], GGML_F16, 0u64),
+            ("output_norm.weight", vec![64], GGML_F32, 384),
+        ] {
+            write_string(&mut file, name).unwrap();
+            file.extend_from_slice(&(dims.len() as u32).to_le_bytes());
+            for dim in dims {
+                file.extend_from_slice(&dim.to_le_bytes());
+            }
+            file.extend_from_slice(&ggml_type.to_le_bytes());
+            file.extend_from_slice(&offset.to_le_bytes());
+        }
+        let data_start = align(file.len() as u64, DEFAULT_ALIGNMENT);
+        file.resize(data_start as usize + 384 + 256, 0);
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("tiny.gguf");
+        std::fs::write(&path, file).unwrap();
+
+        let inspection = inspect(&path).unwrap();
+        assert_eq!((inspection.version, inspection.alignment), (3, 32));
+        let metadata: Vec<_> = inspection
+            .metadata
+            .iter()
+            .map(|entry| (entry.key.as_str(), entry.value_type.as_str(), &entry.value))
+            .collect();
+        assert_eq!(
+            metadata,
+            [
+                (
+                    "general.architecture",
+                    "string",
+                    &serde_json::json!("llama")
+                ),
+                ("llama.context_length", "u32", &serde_json::json!(4096)),
+                ("llama.rope.freq_base", "f32", &serde_json::json!(10000.0)),
+                (
+                    "tokenizer.ggml.tokens",
+                    "array[string]",
+                    &serde_json::json!(["<s>", "</s>", "hi"])
+                ),
+            ]
+        );
+        assert_eq!(
+            inspection.tensors,
+            [
+                GgufTensor {
+                    name: "token_embd.weight".to_string(),
+                    shape: vec![64, 3],
+                    dtype: "f16".to_string(),
+                    size_bytes: Some(384),
+                    offset: data_start,
+                },
+                GgufTensor {
+                    name: "output_norm.weight".to_string(),
+                    shape: vec![64],
+                    dtype: "f32".to_string(),
+                    size_bytes: Some(256),
+                    offset: data_start + 384,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_and_merge_shards() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("tiny.gguf");
+        write_tiny_model(&input);
+        let original = std::fs::read(&input).unwrap();
+
+        // The weight matrix fills a 2400 byte shard, so the norm gets its own
+        let split = split_shards(&input, &dir.path().join("tiny.gguf"), 2400).unwrap();
+        assert_eq!(split.tensors, 2);
+        assert_eq!(
+            split.shards,
+            [
+                dir.path().join("tiny-00001-of-00002.gguf"),
+                dir.path().join("tiny-00002-of-00002.gguf"),
+            ]
+        );
+        for shard in &split.shards {
+            assert!(std::fs::metadata(shard).unwrap().len() <= 2400);
+            check_integrity(shard).unwrap();
+            let mut reader = BufReader::new(File::open(shard).unwrap());
+            let layout = read_layout(&mut reader).unwrap();
+            assert_eq!(layout.tensors.len(), 1);
+            assert_eq!(metadata_u16(&layout, SPLIT_COUNT), Some(2));
+        }
+
+        // Any shard names the set, and merging gives back the original exactly
+        let merged = dir.path().join("merged.gguf");
+        let report = merge_shards(&split.shards[1], &merged).unwrap();
+        assert_eq!(report.tensors, 2);
+        assert_eq!(report.bytes_written, original.len() as u64);
+        assert_eq!(std::fs::read(&merged).unwrap(), original);
+
+        std::fs::remove_file(&split.shards[1]).unwrap();
+        let err = merge_shards(&split.shards[0], &merged).unwrap_err();
+        assert!(
+            err.to_string().contains("Missing 1 of 2 shards")
+                && err.to_string().contains("tiny-00002-of-00002.gguf"),
+            "{}",
+            err
+        );
+        assert!(merge_shards(&input, &merged).is_err());
+
+        // A limit above the model's size leaves it in one shard
+        let whole = split_shards(&input, &input, 1 << 30).unwrap();
+        assert_eq!(whole.shards, [dir.path().join("tiny-00001-of-00001.gguf")]);
+    }
+}
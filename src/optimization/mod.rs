@@ -2,6 +2,7 @@
 // Provides comprehensive ML optimization techniques for 10x performance improvement
 
 pub mod batching;
+pub mod gguf;
 pub mod hardware;
 pub mod inference;
 pub mod memory;
@@ -33,6 +34,8 @@ pub struct OptimizationMetrics {
     pub cache_hit_ratio: f64,
     pub batch_efficiency: f64,
     pub quantization_accuracy_loss: f64,
+    #[serde(default)]
+    pub quantization_size_reduction: f64,
 }
 
 /// Central optimization manager
@@ -135,6 +138,7 @@ impl OptimizationManager {
         metrics.cache_hit_ratio = inference_metrics.cache_hit_ratio;
         metrics.batch_efficiency = batch_metrics.efficiency_ratio;
         metrics.quantization_accuracy_loss = quant_metrics.accuracy_loss;
+        metrics.quantization_size_reduction = quant_metrics.memory_reduction;
 
         Ok(())
     }
@@ -2,6 +2,7 @@
 // Model quantization module for Inferno AI/ML platform
 // Supports INT8, INT4, FP16 quantization for GGUF and ONNX models
 
+use super::gguf::{self, GgufQuantType, RequantizeReport};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -65,6 +66,10 @@ pub struct QuantizationMetrics {
     pub inference_speedup: f64,
     pub memory_reduction: f64,
     pub quantization_time: f64,
+    #[serde(default)]
+    pub input_size_bytes: u64,
+    #[serde(default)]
+    pub output_size_bytes: u64,
 }
 
 /// Model quantizer implementation
@@ -84,7 +89,9 @@ impl ModelQuantizer {
         })
     }
 
-    /// Quantize a model to specified precision
+    /// Quantize a model to specified precision. For GGUF models `target_format` names
+    /// the llama.cpp quantization type to write (e.g. `q8_0`, `q4_k_m`); when it is
+    /// empty the type follows `default_precision`.
     pub async fn quantize_model(
         &mut self,
         model_path: &str,
@@ -99,27 +106,44 @@ impl ModelQuantizer {
         );
 
         let model_path = Path::new(model_path);
-        let output_path = self.generate_output_path(model_path, target_format)?;
+        let mut measured_accuracy_loss = None;
 
         // Determine model format and apply appropriate quantization
-        match model_path.extension().and_then(|s| s.to_str()) {
-            Some("gguf") => self.quantize_gguf_model(model_path, &output_path).await?,
-            Some("onnx") => self.quantize_onnx_model(model_path, &output_path).await?,
-            Some("pt") | Some("pth") => {
-                self.quantize_pytorch_model(model_path, &output_path)
-                    .await?
+        let output_path = match model_path.extension().and_then(|s| s.to_str()) {
+            Some("gguf") => {
+                let target = self.gguf_target(target_format)?;
+                let output_path = self.generate_gguf_output_path(model_path, target)?;
+                let report = self
+                    .quantize_gguf_model(model_path, &output_path, target)
+                    .await?;
+                measured_accuracy_loss = Some(report.relative_rms_error);
+                output_path
             }
-            Some("safetensors") => {
-                self.quantize_safetensors_model(model_path, &output_path)
-                    .await?
+            extension => {
+                let output_path = self.generate_output_path(model_path, target_format)?;
+                match extension {
+                    Some("onnx") => self.quantize_onnx_model(model_path, &output_path).await?,
+                    Some("pt") | Some("pth") => {
+                        self.quantize_pytorch_model(model_path, &output_path)
+                            .await?
+                    }
+                    Some("safetensors") => {
+                        self.quantize_safetensors_model(model_path, &output_path)
+                            .await?
+                    }
+                    _ => return Err(anyhow::anyhow!("Unsupported model format for quantization")),
+                }
+                output_path
             }
-            _ => return Err(anyhow::anyhow!("Unsupported model format for quantization")),
-        }
+        };
 
         // Update metrics
         self.metrics.quantization_time = start_time.elapsed().as_secs_f64();
         self.calculate_compression_metrics(model_path, &output_path)
             .await?;
+        if let Some(accuracy_loss) = measured_accuracy_loss {
+            self.metrics.accuracy_loss = accuracy_loss;
+        }
 
         tracing::info!(
             "Quantization completed in {:.2}s, compression ratio: {:.2}x",
@@ -130,118 +154,43 @@ impl ModelQuantizer {
         Ok(output_path.to_string_lossy().to_string())
     }
 
-    /// Quantize GGUF model
-    async fn quantize_gguf_model(&mut self, input_path: &Path, output_path: &Path) -> Result<()> {
-        tracing::debug!("Quantizing GGUF model: {:?}", input_path);
-
-        let mut input_file = fs::File::open(input_path).await?;
-        let mut output_file = fs::File::create(output_path).await?;
-
-        // Read GGUF header
-        let mut header_buffer = vec![0u8; 12]; // GGUF magic + version + tensor count
-        input_file.read_exact(&mut header_buffer).await?;
-
-        // Verify GGUF magic
-        if &header_buffer[0..4] != b"GGUF" {
-            return Err(anyhow::anyhow!("Invalid GGUF file format"));
-        }
-
-        // Write header to output
-        output_file.write_all(&header_buffer).await?;
-
-        // Read and process tensors
-        let tensor_count = u64::from_le_bytes([
-            header_buffer[8],
-            header_buffer[9],
-            header_buffer[10],
-            header_buffer[11],
-            0,
-            0,
-            0,
-            0,
-        ]);
-
-        tracing::debug!("Processing {} tensors for quantization", tensor_count);
-
-        for i in 0..tensor_count {
-            self.quantize_gguf_tensor(&mut input_file, &mut output_file, i)
-                .await?;
+    /// Resolve the GGUF quantization type for `target_format`
+    fn gguf_target(&self, target_format: &str) -> Result<GgufQuantType> {
+        if !target_format.is_empty() && !target_format.eq_ignore_ascii_case("gguf") {
+            return target_format.parse();
         }
-
-        Ok(())
+        Ok(match self.config.default_precision {
+            QuantizationType::FP32 => GgufQuantType::F32,
+            QuantizationType::FP16 => GgufQuantType::F16,
+            QuantizationType::INT8 => GgufQuantType::Q8_0,
+            QuantizationType::INT4 => GgufQuantType::Q4_K_M,
+        })
     }
 
-    /// Quantize individual GGUF tensor
-    async fn quantize_gguf_tensor(
-        &self,
-        input: &mut fs::File,
-        output: &mut fs::File,
-        tensor_idx: u64,
-    ) -> Result<()> {
-        // Read tensor metadata
-        let mut name_len_bytes = [0u8; 8];
-        input.read_exact(&mut name_len_bytes).await?;
-        let name_len = u64::from_le_bytes(name_len_bytes);
-
-        let mut name_bytes = vec![0u8; name_len as usize];
-        input.read_exact(&mut name_bytes).await?;
-        let tensor_name = String::from_utf8(name_bytes)?;
-
-        // Read tensor dimensions and type
-        let mut dims_count_bytes = [0u8; 4];
-        input.read_exact(&mut dims_count_bytes).await?;
-        let dims_count = u32::from_le_bytes(dims_count_bytes);
-
-        let mut dims = vec![0u64; dims_count as usize];
-        for dim in dims.iter_mut() {
-            let mut dim_bytes = [0u8; 8];
-            input.read_exact(&mut dim_bytes).await?;
-            *dim = u64::from_le_bytes(dim_bytes);
-        }
-
-        let mut type_bytes = [0u8; 4];
-        input.read_exact(&mut type_bytes).await?;
-        let tensor_type = u32::from_le_bytes(type_bytes);
-
-        // Calculate tensor size
-        let element_count: u64 = dims.iter().product();
-        let element_size = self.get_element_size_from_type(tensor_type);
-        let tensor_size = element_count * element_size as u64;
+    /// Quantize GGUF model, rewriting its tensors to `target`
+    async fn quantize_gguf_model(
+        &mut self,
+        input_path: &Path,
+        output_path: &Path,
+        target: GgufQuantType,
+    ) -> Result<RequantizeReport> {
+        tracing::debug!("Quantizing GGUF model: {:?} to {}", input_path, target);
+
+        let input_path = input_path.to_path_buf();
+        let output_path = output_path.to_path_buf();
+        let report = tokio::task::spawn_blocking(move || {
+            gguf::requantize(&input_path, &output_path, target)
+        })
+        .await??;
 
         tracing::debug!(
-            "Quantizing tensor '{}' ({}x{} elements, type: {})",
-            tensor_name,
-            element_count,
-            element_size,
-            tensor_type
+            "Quantized {} tensors, copied {}, relative RMS error {:.5}",
+            report.tensors_quantized,
+            report.tensors_copied,
+            report.relative_rms_error
         );
 
-        // Read tensor data
-        let mut tensor_data = vec![0u8; tensor_size as usize];
-        input.read_exact(&mut tensor_data).await?;
-
-        // Apply quantization based on layer type and config
-        let quantized_data = self
-            .apply_quantization(&tensor_data, &tensor_name, tensor_type)
-            .await?;
-
-        // Write quantized tensor to output
-        output
-            .write_all(&u64::to_le_bytes(tensor_name.len() as u64))
-            .await?;
-        output.write_all(tensor_name.as_bytes()).await?;
-        output.write_all(&u32::to_le_bytes(dims_count)).await?;
-
-        for &dim in &dims {
-            output.write_all(&u64::to_le_bytes(dim)).await?;
-        }
-
-        // Update tensor type if quantized
-        let output_type = self.get_quantized_tensor_type(tensor_type);
-        output.write_all(&u32::to_le_bytes(output_type)).await?;
-        output.write_all(&quantized_data).await?;
-
-        Ok(())
+        Ok(report)
     }
 
     /// Quantize ONNX model
@@ -489,25 +438,17 @@ impl ModelQuantizer {
         Ok(output_path)
     }
 
-    /// Get element size from GGUF tensor type
-    fn get_element_size_from_type(&self, tensor_type: u32) -> usize {
-        match tensor_type {
-            0 => 4, // FP32
-            1 => 2, // FP16
-            2 => 1, // INT8
-            3 => 1, // INT4 (packed)
-            _ => 4, // Default to FP32
-        }
-    }
+    /// Generate output path for a requantized GGUF model
+    fn generate_gguf_output_path(
+        &self,
+        input_path: &Path,
+        target: GgufQuantType,
+    ) -> Result<PathBuf> {
+        let stem = input_path
+            .file_stem()
+            .ok_or_else(|| anyhow::anyhow!("Invalid input path"))?;
 
-    /// Get quantized tensor type
-    fn get_quantized_tensor_type(&self, original_type: u32) -> u32 {
-        match self.config.default_precision {
-            QuantizationType::FP32 => 0,
-            QuantizationType::FP16 => 1,
-            QuantizationType::INT8 => 2,
-            QuantizationType::INT4 => 3,
-        }
+        Ok(input_path.with_file_name(format!("{}_{}.gguf", stem.to_string_lossy(), target)))
     }
 
     /// Quantize ONNX data (simplified)
@@ -540,6 +481,8 @@ impl ModelQuantizer {
         let input_size = fs::metadata(input_path).await?.len();
         let output_size = fs::metadata(output_path).await?.len();
 
+        self.metrics.input_size_bytes = input_size;
+        self.metrics.output_size_bytes = output_size;
         self.metrics.compression_ratio = input_size as f64 / output_size as f64;
         self.metrics.memory_reduction = 1.0 - (output_size as f64 / input_size as f64);
 
@@ -244,10 +244,17 @@ impl PerformanceBaseline {
             max_tokens: 50,
             temperature: 0.7,
             top_k: 40,
+            repeat_penalty: None,
+            repeat_last_n: None,
+            min_p: None,
             top_p: 0.9,
             stream: false,
             stop_sequences: vec![],
+            stop_regex: None,
+            max_generation_ms: None,
             seed: None,
+            response_format: None,
+            logprobs: None,
         };
 
         let test_prompts = vec![
@@ -1,11 +1,17 @@
 /// Production-ready error recovery and resilience patterns for Inferno
+use crate::{
+    InfernoError,
+    backends::{BackendHandle, CancellationToken, Generation, InferenceParams, TokenStream},
+    metrics::MetricsCollector,
+};
 use anyhow::{Result, anyhow};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     sync::{
-        Arc, RwLock,
-        atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
     },
     time::{Duration, Instant, SystemTime},
 };
@@ -31,6 +37,16 @@ pub struct CircuitBreakerConfig {
     pub success_threshold: u32,   // Successes needed in half-open to close
     pub timeout_ms: u64,          // Request timeout
     pub max_concurrent_requests: usize, // Max concurrent requests
+    /// Failure rate (0.0-1.0) over the sliding window that also trips the breaker
+    #[serde(default)]
+    pub failure_rate_threshold: Option<f64>,
+    /// Number of most recent calls the failure rate is measured over
+    #[serde(default = "default_sliding_window_size")]
+    pub sliding_window_size: usize,
+}
+
+fn default_sliding_window_size() -> usize {
+    20
 }
 
 impl Default for CircuitBreakerConfig {
@@ -41,10 +57,31 @@ impl Default for CircuitBreakerConfig {
             success_threshold: 3,
             timeout_ms: 30000, // 30 seconds
             max_concurrent_requests: 100,
+            failure_rate_threshold: None,
+            sliding_window_size: default_sliding_window_size(),
+        }
+    }
+}
+
+impl CircuitState {
+    /// Numeric encoding used for the state gauge
+    fn gauge_value(&self) -> f64 {
+        match self {
+            CircuitState::Closed => 0.0,
+            CircuitState::HalfOpen => 1.0,
+            CircuitState::Open => 2.0,
         }
     }
 }
 
+/// Whether a call may proceed
+enum Admission {
+    Allow,
+    /// The single trial call allowed through while half-open
+    Probe,
+    Reject,
+}
+
 /// Circuit breaker for service resilience
 #[derive(Debug)]
 pub struct CircuitBreaker {
@@ -56,6 +93,10 @@ pub struct CircuitBreaker {
     last_failure_time: Arc<RwLock<Option<Instant>>>,
     semaphore: Arc<Semaphore>,
     metrics: CircuitBreakerMetrics,
+    /// Outcomes of the most recent calls, `true` for a failure
+    recent_outcomes: Arc<Mutex<VecDeque<bool>>>,
+    probe_in_flight: Arc<AtomicBool>,
+    collector: Option<Arc<MetricsCollector>>,
 }
 
 #[derive(Debug, Clone)]
@@ -86,6 +127,29 @@ impl CircuitBreaker {
                 rejected_requests: Arc::new(AtomicU64::new(0)),
                 state_changes: Arc::new(AtomicU64::new(0)),
             },
+            recent_outcomes: Arc::new(Mutex::new(VecDeque::new())),
+            probe_in_flight: Arc::new(AtomicBool::new(false)),
+            collector: None,
+        }
+    }
+
+    /// Publish state changes and rejections to `collector`
+    ///
+    /// The state gauge `circuit_breaker.<name>.state` reads 0 when closed, 1 when
+    /// half-open and 2 when open; `circuit_breaker.<name>.rejected` counts fast failures.
+    pub fn with_metrics(mut self, collector: Arc<MetricsCollector>) -> Self {
+        collector.record_gauge(&self.state_gauge_name(), self.get_state().gauge_value());
+        self.collector = Some(collector);
+        self
+    }
+
+    fn state_gauge_name(&self) -> String {
+        format!("circuit_breaker.{}.state", self.name)
+    }
+
+    fn publish_state(&self, state: &CircuitState) {
+        if let Some(collector) = &self.collector {
+            collector.record_gauge(&self.state_gauge_name(), state.gauge_value());
         }
     }
 
@@ -98,13 +162,23 @@ impl CircuitBreaker {
         self.metrics.total_requests.fetch_add(1, Ordering::Relaxed);
 
         // Check if circuit is open
-        if self.should_reject_request().await? {
+        let admission = self.admit().await?;
+        if matches!(admission, Admission::Reject) {
             self.metrics
                 .rejected_requests
                 .fetch_add(1, Ordering::Relaxed);
-            return Err(anyhow!("Circuit breaker {} is OPEN", self.name));
+            if let Some(collector) = &self.collector {
+                collector.increment_counter(&format!("circuit_breaker.{}.rejected", self.name));
+            }
+            return Err(
+                InfernoError::Resource(format!("Circuit breaker {} is OPEN", self.name)).into(),
+            );
         }
 
+        // Let the next probe through once this one has settled
+        let _probe =
+            matches!(admission, Admission::Probe).then(|| ProbeGuard(&self.probe_in_flight));
+
         // Acquire semaphore permit
         let _permit = self.semaphore.acquire().await.map_err(|_| {
             anyhow!(
@@ -140,39 +214,68 @@ impl CircuitBreaker {
         }
     }
 
-    async fn should_reject_request(&self) -> Result<bool> {
+    async fn admit(&self) -> Result<Admission> {
         let state = self
             .state
             .read()
-            .map_err(|_| anyhow!("Failed to read circuit state"))?;
+            .map_err(|_| anyhow!("Failed to read circuit state"))?
+            .clone();
 
-        match *state {
+        match state {
             CircuitState::Open => {
                 // Check if we should transition to half-open
-                if let Some(last_failure) = *self
-                    .last_failure_time
-                    .read()
-                    .map_err(|_| anyhow!("Failed to read last failure time"))?
-                {
-                    if last_failure.elapsed()
-                        > Duration::from_millis(self.config.recovery_timeout_ms)
-                    {
-                        drop(state);
-                        self.transition_to_half_open().await?;
-                        return Ok(false);
-                    }
+                if !self.cooled_down()? {
+                    return Ok(Admission::Reject);
                 }
-                Ok(true)
-            }
-            CircuitState::HalfOpen => {
-                // Allow limited requests in half-open state
-                Ok(false)
+                self.transition_to_half_open().await?;
+                Ok(self.try_probe())
             }
-            CircuitState::Closed => Ok(false),
+            // Only one trial call at a time while testing recovery
+            CircuitState::HalfOpen => Ok(self.try_probe()),
+            CircuitState::Closed => Ok(Admission::Allow),
+        }
+    }
+
+    /// Whether the recovery timeout has passed since the last failure
+    fn cooled_down(&self) -> Result<bool> {
+        Ok(self
+            .last_failure_time
+            .read()
+            .map_err(|_| anyhow!("Failed to read last failure time"))?
+            .is_some_and(|last_failure| {
+                last_failure.elapsed() > Duration::from_millis(self.config.recovery_timeout_ms)
+            }))
+    }
+
+    fn try_probe(&self) -> Admission {
+        if self
+            .probe_in_flight
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            Admission::Probe
+        } else {
+            Admission::Reject
         }
     }
 
+    /// Record a call outcome and return the failure rate once the window is full
+    fn record_outcome(&self, failed: bool) -> Option<f64> {
+        let window_size = self.config.sliding_window_size.max(1);
+        let mut outcomes = self
+            .recent_outcomes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        outcomes.push_back(failed);
+        while outcomes.len() > window_size {
+            outcomes.pop_front();
+        }
+        (outcomes.len() == window_size)
+            .then(|| outcomes.iter().filter(|&&failed| failed).count() as f64 / window_size as f64)
+    }
+
     async fn on_success(&self) {
+        self.record_outcome(false);
         let state = {
             let state_guard = self.state.read().unwrap();
             state_guard.clone()
@@ -196,6 +299,7 @@ impl CircuitBreaker {
 
     async fn on_failure(&self) {
         let failure_count = self.failure_count.fetch_add(1, Ordering::Relaxed) + 1;
+        let failure_rate = self.record_outcome(true);
 
         *self.last_failure_time.write().unwrap() = Some(Instant::now());
 
@@ -206,7 +310,12 @@ impl CircuitBreaker {
 
         match state {
             CircuitState::Closed => {
-                if failure_count >= self.config.failure_threshold as u64 {
+                let rate_exceeded = self
+                    .config
+                    .failure_rate_threshold
+                    .zip(failure_rate)
+                    .is_some_and(|(threshold, rate)| rate >= threshold);
+                if failure_count >= self.config.failure_threshold as u64 || rate_exceeded {
                     self.transition_to_open().await.unwrap_or_else(|e| {
                         error!("Failed to transition circuit breaker to open: {}", e);
                     });
@@ -229,6 +338,7 @@ impl CircuitBreaker {
         if *state != CircuitState::Open {
             *state = CircuitState::Open;
             self.metrics.state_changes.fetch_add(1, Ordering::Relaxed);
+            self.publish_state(&state);
             warn!("Circuit breaker {} transitioned to OPEN", self.name);
         }
         Ok(())
@@ -243,6 +353,7 @@ impl CircuitBreaker {
             *state = CircuitState::HalfOpen;
             self.success_count.store(0, Ordering::Relaxed);
             self.metrics.state_changes.fetch_add(1, Ordering::Relaxed);
+            self.publish_state(&state);
             info!("Circuit breaker {} transitioned to HALF-OPEN", self.name);
         }
         Ok(())
@@ -257,7 +368,12 @@ impl CircuitBreaker {
             *state = CircuitState::Closed;
             self.failure_count.store(0, Ordering::Relaxed);
             self.success_count.store(0, Ordering::Relaxed);
+            self.recent_outcomes
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clear();
             self.metrics.state_changes.fetch_add(1, Ordering::Relaxed);
+            self.publish_state(&state);
             info!("Circuit breaker {} transitioned to CLOSED", self.name);
         }
         Ok(())
@@ -270,6 +386,205 @@ impl CircuitBreaker {
     pub fn get_metrics(&self) -> CircuitBreakerMetrics {
         self.metrics.clone()
     }
+
+    /// Whether a call made now would be admitted without waiting for a slot
+    pub fn is_available(&self) -> bool {
+        let probe_free = !self.probe_in_flight.load(Ordering::Acquire);
+        match self.get_state() {
+            CircuitState::Closed => self.semaphore.available_permits() > 0,
+            CircuitState::HalfOpen => probe_free,
+            CircuitState::Open => probe_free && self.cooled_down().unwrap_or(false),
+        }
+    }
+}
+
+/// Clears the half-open probe slot when the probe call finishes
+struct ProbeGuard<'a>(&'a AtomicBool);
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// Backend handle whose inference calls go through a circuit breaker
+///
+/// While the breaker is open, calls fail immediately with [`InfernoError::Resource`]
+/// instead of queueing on a backend that keeps failing. The breaker's `timeout_ms`
+/// bounds each `infer` call, so size it for the longest expected generation.
+#[derive(Clone)]
+pub struct ProtectedBackend {
+    backend: BackendHandle,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl ProtectedBackend {
+    pub fn new(backend: BackendHandle, breaker: Arc<CircuitBreaker>) -> Self {
+        Self { backend, breaker }
+    }
+
+    pub fn backend(&self) -> &BackendHandle {
+        &self.backend
+    }
+
+    pub fn breaker(&self) -> &Arc<CircuitBreaker> {
+        &self.breaker
+    }
+
+    pub async fn infer(&self, input: &str, params: &InferenceParams) -> Result<String> {
+        self.infer_cancellable(input, params, &CancellationToken::new())
+            .await
+    }
+
+    /// Infer through the breaker, stopping early once `cancel` fires
+    pub async fn infer_cancellable(
+        &self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<String> {
+        self.breaker
+            .call(|| self.backend.infer_cancellable(input, params, cancel))
+            .await
+    }
+
+    /// Generate through the breaker, reporting why generation ended and its seed
+    pub async fn generate(
+        &self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<Generation> {
+        self.breaker
+            .call(|| self.backend.generate(input, params, cancel))
+            .await
+    }
+
+    /// Start a stream through the breaker; an error partway through the stream also
+    /// counts as a failure
+    pub async fn infer_stream(&self, input: &str, params: &InferenceParams) -> Result<TokenStream> {
+        self.infer_stream_cancellable(input, params, &CancellationToken::new())
+            .await
+    }
+
+    /// Start a stream through the breaker that ends once `cancel` fires
+    pub async fn infer_stream_cancellable(
+        &self,
+        input: &str,
+        params: &InferenceParams,
+        cancel: &CancellationToken,
+    ) -> Result<TokenStream> {
+        let stream = self
+            .breaker
+            .call(|| self.backend.infer_stream_cancellable(input, params, cancel))
+            .await?;
+
+        let breaker = self.breaker.clone();
+        let mut failed = false;
+        Ok(Box::pin(stream.then(move |item| {
+            let breaker = breaker.clone();
+            let first_failure = item.is_err() && !std::mem::replace(&mut failed, true);
+            async move {
+                if first_failure {
+                    breaker.on_failure().await;
+                    breaker
+                        .metrics
+                        .failed_requests
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+                item
+            }
+        })))
+    }
+}
+
+/// Fallback chains of models served by the API
+///
+/// A request naming a chain is served by the first model in it whose circuit
+/// breaker is closed and below its concurrency limit, so a large model can hand
+/// over to a smaller one while it is failing or overloaded. Every model listed in
+/// a chain gets one breaker, shared by all chains it appears in and by requests
+/// that name the model directly.
+pub struct ModelFallbacks {
+    chains: HashMap<String, Vec<String>>,
+    breakers: HashMap<String, Arc<CircuitBreaker>>,
+    collector: Option<Arc<MetricsCollector>>,
+}
+
+/// The model picked to serve a request
+#[derive(Clone)]
+pub struct ModelSelection {
+    pub model: String,
+    /// Breaker that records the outcome, for models in a fallback chain
+    pub breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl ModelFallbacks {
+    pub fn new(
+        chains: HashMap<String, Vec<String>>,
+        config: CircuitBreakerConfig,
+        collector: Option<Arc<MetricsCollector>>,
+    ) -> Self {
+        let mut breakers = HashMap::new();
+        for model in chains.values().flatten() {
+            breakers.entry(model.clone()).or_insert_with(|| {
+                let breaker = CircuitBreaker::new(format!("model.{}", model), config.clone());
+                Arc::new(match &collector {
+                    Some(collector) => breaker.with_metrics(collector.clone()),
+                    None => breaker,
+                })
+            });
+        }
+
+        Self {
+            chains,
+            breakers,
+            collector,
+        }
+    }
+
+    pub fn breaker(&self, model: &str) -> Option<&Arc<CircuitBreaker>> {
+        self.breakers.get(model)
+    }
+
+    /// Pick the model to serve a request for `requested`
+    ///
+    /// Names that are not chains resolve to themselves. For a chain, the model picked
+    /// is counted under `model_fallback.<chain>.<model>`, and an error is returned when
+    /// no model in it is available.
+    pub fn resolve(&self, requested: &str) -> Result<ModelSelection> {
+        let Some(chain) = self.chains.get(requested) else {
+            return Ok(ModelSelection {
+                model: requested.to_string(),
+                breaker: self.breakers.get(requested).cloned(),
+            });
+        };
+
+        let (position, model) = chain
+            .iter()
+            .enumerate()
+            .find(|(_, model)| self.breakers[*model].is_available())
+            .ok_or_else(|| {
+                InfernoError::Resource(format!(
+                    "No model in fallback chain '{}' is available",
+                    requested
+                ))
+            })?;
+        if position > 0 {
+            info!(
+                "Fallback chain {} is serving {} in place of {}",
+                requested, model, chain[0]
+            );
+        }
+        if let Some(collector) = &self.collector {
+            collector.increment_counter(&format!("model_fallback.{}.{}", requested, model));
+        }
+
+        Ok(ModelSelection {
+            model: model.clone(),
+            breaker: Some(self.breakers[model].clone()),
+        })
+    }
 }
 
 /// Retry policy configuration
@@ -868,6 +1183,189 @@ mod tests {
         }
     }
 
+    fn fast_breaker_config() -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            failure_threshold: 3,
+            recovery_timeout_ms: 50,
+            success_threshold: 1,
+            timeout_ms: 1000,
+            max_concurrent_requests: 10,
+            ..Default::default()
+        }
+    }
+
+    async fn fail(breaker: &CircuitBreaker) -> Result<()> {
+        breaker
+            .call(|| async { Err(anyhow!("backend down")) })
+            .await
+    }
+
+    async fn succeed(breaker: &CircuitBreaker) -> Result<()> {
+        breaker.call(|| async { Ok(()) }).await
+    }
+
+    fn is_resource_error(error: &anyhow::Error) -> bool {
+        matches!(
+            error.downcast_ref::<InfernoError>(),
+            Some(InfernoError::Resource(_))
+        )
+    }
+
+    #[tokio::test]
+    async fn breaker_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new("inference".to_string(), fast_breaker_config());
+
+        for _ in 0..2 {
+            assert!(fail(&breaker).await.is_err());
+        }
+        assert_eq!(breaker.get_state(), CircuitState::Closed);
+
+        // A success resets the consecutive count
+        succeed(&breaker).await.unwrap();
+        for _ in 0..2 {
+            assert!(fail(&breaker).await.is_err());
+        }
+        assert_eq!(breaker.get_state(), CircuitState::Closed);
+
+        assert!(fail(&breaker).await.is_err());
+        assert_eq!(breaker.get_state(), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn breaker_opens_on_failure_rate() {
+        let config = CircuitBreakerConfig {
+            failure_threshold: 100,
+            failure_rate_threshold: Some(0.5),
+            sliding_window_size: 4,
+            ..fast_breaker_config()
+        };
+        let breaker = CircuitBreaker::new("inference".to_string(), config);
+
+        // Alternating outcomes never reach the consecutive threshold
+        succeed(&breaker).await.unwrap();
+        assert!(fail(&breaker).await.is_err());
+        succeed(&breaker).await.unwrap();
+        assert_eq!(breaker.get_state(), CircuitState::Closed);
+        assert!(fail(&breaker).await.is_err());
+        assert_eq!(breaker.get_state(), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn open_breaker_fails_fast_with_resource_error() {
+        let breaker = CircuitBreaker::new("inference".to_string(), fast_breaker_config());
+        for _ in 0..3 {
+            let _ = fail(&breaker).await;
+        }
+
+        let calls = AtomicUsize::new(0);
+        let result: Result<()> = breaker
+            .call(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+            .await;
+
+        assert!(is_resource_error(&result.unwrap_err()));
+        assert_eq!(
+            calls.load(Ordering::SeqCst),
+            0,
+            "open breaker must not call through"
+        );
+        assert_eq!(
+            breaker
+                .get_metrics()
+                .rejected_requests
+                .load(Ordering::Relaxed),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn half_open_probe_success_closes_breaker() {
+        let (collector, _processor) = MetricsCollector::new();
+        let collector = Arc::new(collector);
+        let breaker = CircuitBreaker::new("inference".to_string(), fast_breaker_config())
+            .with_metrics(collector.clone());
+        let state_gauge = || collector.get_gauges()["circuit_breaker.inference.state"];
+
+        for _ in 0..3 {
+            let _ = fail(&breaker).await;
+        }
+        assert_eq!(state_gauge(), 2.0);
+
+        // A failed probe after the cooldown reopens the breaker
+        sleep(Duration::from_millis(60)).await;
+        assert!(!is_resource_error(&fail(&breaker).await.unwrap_err()));
+        assert_eq!(breaker.get_state(), CircuitState::Open);
+
+        // A successful probe closes it again
+        sleep(Duration::from_millis(60)).await;
+        succeed(&breaker).await.unwrap();
+        assert_eq!(breaker.get_state(), CircuitState::Closed);
+        assert_eq!(state_gauge(), 0.0);
+        assert_eq!(
+            collector
+                .get_counters()
+                .get("circuit_breaker.inference.rejected"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn half_open_admits_one_probe_at_a_time() {
+        let breaker = CircuitBreaker::new("inference".to_string(), fast_breaker_config());
+        for _ in 0..3 {
+            let _ = fail(&breaker).await;
+        }
+        sleep(Duration::from_millis(60)).await;
+
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        let probe = breaker.call(|| async {
+            let _ = release_rx.await;
+            Ok(())
+        });
+        let concurrent = async {
+            tokio::task::yield_now().await;
+            let result = succeed(&breaker).await;
+            let _ = release_tx.send(());
+            result
+        };
+
+        let (probe, concurrent) = tokio::join!(probe, concurrent);
+        probe.unwrap();
+        assert!(is_resource_error(&concurrent.unwrap_err()));
+        assert_eq!(breaker.get_state(), CircuitState::Closed);
+    }
+
+    #[cfg(feature = "gguf")]
+    #[tokio::test]
+    async fn protected_backend_trips_on_failing_backend() {
+        use crate::backends::{BackendConfig, BackendType};
+
+        // No model is loaded, so every call fails
+        let backend =
+            BackendHandle::new_shared(BackendType::Gguf, &BackendConfig::default()).unwrap();
+        let breaker = Arc::new(CircuitBreaker::new(
+            "inference".to_string(),
+            fast_breaker_config(),
+        ));
+        let protected = ProtectedBackend::new(backend, breaker.clone());
+        let params = InferenceParams::default();
+
+        for _ in 0..3 {
+            let error = protected.infer("hello", &params).await.unwrap_err();
+            assert!(!is_resource_error(&error));
+        }
+        assert_eq!(breaker.get_state(), CircuitState::Open);
+
+        let error = protected
+            .infer_stream("hello", &params)
+            .await
+            .err()
+            .unwrap();
+        assert!(is_resource_error(&error));
+    }
+
     #[tokio::test]
     async fn retry_succeeds_after_transient_failures() {
         let attempts = AtomicUsize::new(0);
@@ -905,4 +1403,33 @@ mod tests {
         // Exactly max_attempts calls, no more.
         assert_eq!(attempts.load(Ordering::SeqCst), 3);
     }
+
+    #[tokio::test]
+    async fn fallback_chain_skips_models_with_open_breakers() {
+        let chains = HashMap::from([(
+            "chat".to_string(),
+            vec!["big".to_string(), "small".to_string()],
+        )]);
+        let fallbacks = ModelFallbacks::new(chains, fast_breaker_config(), None);
+        assert_eq!(fallbacks.resolve("chat").unwrap().model, "big");
+        // Names that are not chains pass through unguarded
+        let direct = fallbacks.resolve("other").unwrap();
+        assert_eq!(direct.model, "other");
+        assert!(direct.breaker.is_none());
+
+        for _ in 0..3 {
+            let _ = fail(fallbacks.breaker("big").unwrap()).await;
+        }
+        assert_eq!(fallbacks.resolve("chat").unwrap().model, "small");
+
+        for _ in 0..3 {
+            let _ = fail(fallbacks.breaker("small").unwrap()).await;
+        }
+        let error = fallbacks.resolve("chat").unwrap_err();
+        assert!(is_resource_error(&error));
+
+        // The primary is tried again once its breaker lets a probe through
+        sleep(Duration::from_millis(60)).await;
+        assert_eq!(fallbacks.resolve("chat").unwrap().model, "big");
+    }
 }
@@ -88,7 +88,7 @@ pub struct InferenceStats {
 
 impl App {
     pub async fn new(config: Config) -> Result<Self> {
-        let model_manager = ModelManager::new(&config.models_dir);
+        let model_manager = ModelManager::from_config(&config);
         let models = model_manager.list_models().await?;
 
         let mut app = Self {
@@ -797,7 +797,7 @@ impl App {
             self.loading_progress = 0.0;
 
             // First, validate the model comprehensively
-            let model_manager = crate::models::ModelManager::new(&self.config.models_dir);
+            let model_manager = crate::models::ModelManager::from_config(&self.config);
             match model_manager
                 .validate_model_comprehensive(&model.path, Some(&self.config))
                 .await
@@ -889,10 +889,17 @@ impl App {
             max_tokens: 512,
             temperature: 0.7,
             top_k: 40,
+            repeat_penalty: None,
+            repeat_last_n: None,
+            min_p: None,
             top_p: 0.9,
             stream: true,
             seed: None,
+            response_format: None,
+            logprobs: None,
             stop_sequences: vec![],
+            stop_regex: None,
+            max_generation_ms: None,
         };
 
         // Create channel for streaming
@@ -3,15 +3,32 @@
 //!
 //! Comprehensive backup and restore system for safe application upgrades
 //! with versioned backups, compression, and integrity verification.
+//!
+//! With `backup_encryption` enabled, each archive is encrypted as it is written,
+//! so no plaintext copy ever reaches the disk. The archive is sealed in 64 KiB
+//! segments with AES-256-GCM in the STREAM construction, under a key derived with
+//! Argon2 from the configured passphrase; a reordered, altered or truncated
+//! archive fails to decrypt. The salt and nonce prefix are kept in the backup's
+//! metadata, and restoring decrypts the archive segment by segment as it is
+//! unpacked.
 
 use super::{ApplicationVersion, UpgradeConfig};
+use aes_gcm::{
+    Aes256Gcm, Key,
+    aead::{
+        KeyInit,
+        generic_array::GenericArray,
+        stream::{DecryptorBE32, EncryptorBE32},
+    },
+};
 use anyhow::Result;
+use argon2::Argon2;
 use chrono::{DateTime, Utc};
 use flate2::{Compression, read::GzDecoder, write::GzEncoder};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::fs::{self, File};
-use std::io::Read;
+use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use tar::{Archive, Builder};
 use tracing::{debug, error, info, warn};
@@ -31,6 +48,18 @@ pub struct BackupMetadata {
     pub includes_config: bool,
     pub includes_data: bool,
     pub description: String,
+    /// Set when the archive is encrypted
+    #[serde(default)]
+    pub encryption: Option<BackupEncryption>,
+}
+
+/// Salt and nonce prefix an encrypted backup was sealed with, hex-encoded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupEncryption {
+    /// Argon2 salt the key was derived with
+    pub salt: String,
+    /// 7-byte STREAM nonce prefix; each segment's counter completes the nonce
+    pub nonce: String,
 }
 
 /// Type of backup
@@ -54,6 +83,15 @@ pub enum CompressionMethod {
     Zstd,
 }
 
+/// Backup that would be created before an upgrade, computed without writing anything
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupPlan {
+    pub backup_path: PathBuf,
+    pub source_paths: Vec<PathBuf>,
+    /// Total size of the source files; the compressed archive will be smaller
+    pub estimated_size_bytes: u64,
+}
+
 /// Backup and restore manager
 pub struct BackupManager {
     config: UpgradeConfig,
@@ -95,8 +133,8 @@ impl BackupManager {
         let backup_path = self.backup_dir.join(&backup_filename);
 
         // Create compressed tar archive
-        let (compressed_size, uncompressed_size, checksum) = self
-            .create_compressed_archive(&paths_to_backup, &backup_path)
+        let (compressed_size, uncompressed_size, checksum, encryption) = self
+            .create_backup_archive(&paths_to_backup, &backup_path)
             .await?;
 
         // Create metadata
@@ -113,6 +151,7 @@ impl BackupManager {
             includes_config: true,
             includes_data: true,
             description: "Pre-upgrade full backup".to_string(),
+            encryption,
         };
 
         // Save metadata
@@ -125,6 +164,25 @@ impl BackupManager {
         Ok(backup_path)
     }
 
+    /// Describe the full backup `create_backup` would produce, without creating it
+    pub fn plan_backup(&self) -> Result<BackupPlan> {
+        let current_version = ApplicationVersion::current();
+        let source_paths = self.get_backup_paths(BackupType::Full)?;
+        let estimated_size_bytes = source_paths.iter().map(|p| path_size(p)).sum();
+
+        let backup_filename = format!(
+            "inferno_backup_{}_{}.tar.gz",
+            current_version.to_string().replace('.', "_"),
+            self.generate_backup_id()
+        );
+
+        Ok(BackupPlan {
+            backup_path: self.backup_dir.join(backup_filename),
+            source_paths,
+            estimated_size_bytes,
+        })
+    }
+
     /// Create a selective backup
     pub async fn create_selective_backup(
         &self,
@@ -145,8 +203,8 @@ impl BackupManager {
         );
         let backup_path = self.backup_dir.join(&backup_filename);
 
-        let (compressed_size, uncompressed_size, checksum) = self
-            .create_compressed_archive(&paths_to_backup, &backup_path)
+        let (compressed_size, uncompressed_size, checksum, encryption) = self
+            .create_backup_archive(&paths_to_backup, &backup_path)
             .await?;
 
         let (includes_config, includes_data) = match &backup_type {
@@ -169,6 +227,7 @@ impl BackupManager {
             includes_config,
             includes_data,
             description,
+            encryption,
         };
 
         self.save_backup_metadata(&metadata).await?;
@@ -287,8 +346,7 @@ impl BackupManager {
         // Verify archive can be opened
         match metadata.compression_method {
             CompressionMethod::Gzip => {
-                let file = File::open(&metadata.file_path)?;
-                let decoder = GzDecoder::new(file);
+                let decoder = GzDecoder::new(self.open_archive(metadata)?);
                 let mut archive = Archive::new(decoder);
 
                 // Try to list entries without extracting
@@ -298,8 +356,7 @@ impl BackupManager {
                 }
             }
             CompressionMethod::None => {
-                let file = File::open(&metadata.file_path)?;
-                let mut archive = Archive::new(file);
+                let mut archive = Archive::new(self.open_archive(metadata)?);
 
                 for entry in archive.entries()? {
                     let entry = entry?;
@@ -447,9 +504,20 @@ impl BackupManager {
         paths: &[PathBuf],
         output_path: &PathBuf,
     ) -> Result<(u64, u64, String)> {
-        let file = File::create(output_path)?;
-        let encoder = GzEncoder::new(file, Compression::default());
-        let mut archive = Builder::new(encoder);
+        let file = BufWriter::new(File::create(output_path)?);
+        let (file, uncompressed_size) = self.write_archive(paths, file)?;
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+        let compressed_size = fs::metadata(output_path)?.len();
+        let checksum = self.calculate_file_checksum(output_path).await?;
+
+        Ok((compressed_size, uncompressed_size, checksum))
+    }
+
+    /// Write a gzipped tar archive of `paths` to `writer`, returning the writer and
+    /// the total size of the archived files
+    fn write_archive<W: Write>(&self, paths: &[PathBuf], writer: W) -> Result<(W, u64)> {
+        let mut archive = Builder::new(GzEncoder::new(writer, Compression::default()));
 
         let mut uncompressed_size = 0u64;
 
@@ -471,18 +539,14 @@ impl BackupManager {
             }
         }
 
-        archive.finish()?;
-
-        let compressed_size = fs::metadata(output_path)?.len();
-        let checksum = self.calculate_file_checksum(output_path).await?;
-
-        Ok((compressed_size, uncompressed_size, checksum))
+        let writer = archive.into_inner()?.finish()?;
+        Ok((writer, uncompressed_size))
     }
 
     /// Add directory recursively to archive
-    fn add_directory_to_archive(
+    fn add_directory_to_archive<W: Write>(
         &self,
-        archive: &mut Builder<GzEncoder<File>>,
+        archive: &mut Builder<W>,
         dir_path: &Path,
         uncompressed_size: &mut u64,
     ) -> Result<()> {
@@ -508,34 +572,82 @@ impl BackupManager {
     async fn perform_restore(&self, metadata: &BackupMetadata) -> Result<()> {
         info!("Performing restore from backup: {}", metadata.id);
 
-        let file = File::open(&metadata.file_path)?;
+        // Extract to a temporary directory first
+        let temp_dir = tempfile::TempDir::new()?;
+        self.unpack_backup(metadata, temp_dir.path())?;
 
-        match metadata.compression_method {
-            CompressionMethod::Gzip => {
-                let decoder = GzDecoder::new(file);
-                let mut archive = Archive::new(decoder);
+        // Move files to their final locations
+        self.move_restored_files(temp_dir.path()).await?;
 
-                // Extract to a temporary directory first
-                let temp_dir = tempfile::TempDir::new()?;
-                archive.unpack(temp_dir.path())?;
+        Ok(())
+    }
 
-                // Move files to their final locations
-                self.move_restored_files(temp_dir.path()).await?;
-            }
-            CompressionMethod::None => {
-                let mut archive = Archive::new(file);
-                let temp_dir = tempfile::TempDir::new()?;
-                archive.unpack(temp_dir.path())?;
-                self.move_restored_files(temp_dir.path()).await?;
-            }
+    /// Unpack a backup's archive into `dest`, decrypting it first if it is encrypted
+    fn unpack_backup(&self, metadata: &BackupMetadata, dest: &Path) -> Result<()> {
+        let reader = self.open_archive(metadata)?;
+        match metadata.compression_method {
+            CompressionMethod::Gzip => Archive::new(GzDecoder::new(reader)).unpack(dest)?,
+            CompressionMethod::None => Archive::new(reader).unpack(dest)?,
             CompressionMethod::Zstd => {
                 return Err(anyhow::anyhow!("Zstd decompression not yet implemented"));
             }
         }
-
         Ok(())
     }
 
+    /// Reader over a backup's archive, decrypted as it is read if it is encrypted
+    fn open_archive(&self, metadata: &BackupMetadata) -> Result<Box<dyn Read>> {
+        let file = BufReader::new(File::open(&metadata.file_path)?);
+        let Some(encryption) = &metadata.encryption else {
+            return Ok(Box::new(file));
+        };
+        let passphrase = self.config.backup_encryption.passphrase()?;
+        // Every segment fails under the wrong key, so opening the first one tells a
+        // wrong passphrase apart before anything is unpacked
+        let reader = DecryptingReader::new(file, &passphrase, encryption).map_err(|_| {
+            anyhow::anyhow!(
+                "Cannot decrypt backup {}: wrong passphrase or corrupted archive",
+                metadata.id
+            )
+        })?;
+        Ok(Box::new(reader))
+    }
+
+    /// Create the archive of a backup, encrypted as it is written when backup
+    /// encryption is on
+    async fn create_backup_archive(
+        &self,
+        paths: &[PathBuf],
+        output_path: &PathBuf,
+    ) -> Result<(u64, u64, String, Option<BackupEncryption>)> {
+        if !self.config.backup_encryption.enabled {
+            let (compressed_size, uncompressed_size, checksum) =
+                self.create_compressed_archive(paths, output_path).await?;
+            return Ok((compressed_size, uncompressed_size, checksum, None));
+        }
+
+        let passphrase = self.config.backup_encryption.passphrase()?;
+        let file = BufWriter::new(File::create(output_path)?);
+        let (writer, encryption) = EncryptingWriter::new(file, &passphrase)?;
+        let (writer, uncompressed_size) = self.write_archive(paths, writer)?;
+        writer
+            .finish()?
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+
+        // Size and checksum cover the file as stored, so integrity can be checked
+        // before decrypting
+        let compressed_size = fs::metadata(output_path)?.len();
+        let checksum = self.calculate_file_checksum(output_path).await?;
+        Ok((
+            compressed_size,
+            uncompressed_size,
+            checksum,
+            Some(encryption),
+        ))
+    }
+
     /// Move restored files to their final locations
     async fn move_restored_files(&self, temp_dir: &Path) -> Result<()> {
         // This is a simplified implementation
@@ -664,6 +776,196 @@ impl BackupManager {
     }
 }
 
+/// Derive the 256-bit archive key from `passphrase` and `salt` with Argon2
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+/// Plaintext bytes sealed into each segment of an encrypted archive
+const SEGMENT_SIZE: usize = 64 * 1024;
+
+/// Bytes of a full segment as stored, including its authentication tag
+const SEALED_SEGMENT_SIZE: usize = SEGMENT_SIZE + 16;
+
+/// Bytes of the nonce chosen per archive; the STREAM counter fills the rest
+const NONCE_PREFIX_SIZE: usize = 7;
+
+fn stream_error(e: aes_gcm::Error) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Backup encryption failed: {}", e),
+    )
+}
+
+/// Encrypts everything written to it into `inner`, one segment at a time
+///
+/// Only the last segment is shorter than [`SEGMENT_SIZE`], so a reader can tell
+/// where the archive ends; [`finish`](Self::finish) must be called to seal it.
+struct EncryptingWriter<W: Write> {
+    inner: W,
+    encryptor: Option<EncryptorBE32<Aes256Gcm>>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    /// Encrypt under a key derived from `passphrase` with a fresh salt and nonce
+    fn new(inner: W, passphrase: &str) -> Result<(Self, BackupEncryption)> {
+        let salt: [u8; 16] = rand::random();
+        let nonce: [u8; NONCE_PREFIX_SIZE] = rand::random();
+        let key = derive_key(passphrase, &salt)?;
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+        let writer = Self {
+            inner,
+            encryptor: Some(EncryptorBE32::from_aead(
+                cipher,
+                GenericArray::from_slice(&nonce),
+            )),
+            buffer: Vec::with_capacity(SEGMENT_SIZE),
+        };
+        let encryption = BackupEncryption {
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+        };
+        Ok((writer, encryption))
+    }
+
+    /// Seal the remaining bytes as the last segment and return the inner writer
+    fn finish(mut self) -> io::Result<W> {
+        let encryptor = self
+            .encryptor
+            .take()
+            .expect("encrypting writer finished twice");
+        let sealed = encryptor
+            .encrypt_last(self.buffer.as_slice())
+            .map_err(stream_error)?;
+        self.inner.write_all(&sealed)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let taken = data.len().min(SEGMENT_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&data[..taken]);
+        if self.buffer.len() == SEGMENT_SIZE {
+            let encryptor = self
+                .encryptor
+                .as_mut()
+                .expect("encrypting writer used after finish");
+            let sealed = encryptor
+                .encrypt_next(self.buffer.as_slice())
+                .map_err(stream_error)?;
+            self.inner.write_all(&sealed)?;
+            self.buffer.clear();
+        }
+        Ok(taken)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // A partial segment can't be sealed until it is full or the last one
+        self.inner.flush()
+    }
+}
+
+/// Decrypts an archive written by [`EncryptingWriter`] as it is read
+///
+/// Every segment is authenticated before its bytes are returned, and an archive
+/// cut short at a segment boundary fails when its missing last segment is read.
+struct DecryptingReader<R: Read> {
+    inner: R,
+    decryptor: Option<DecryptorBE32<Aes256Gcm>>,
+    segment: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> DecryptingReader<R> {
+    /// Decrypt with the key derived from `passphrase`, failing if the first
+    /// segment does not decrypt under it
+    fn new(inner: R, passphrase: &str, encryption: &BackupEncryption) -> Result<Self> {
+        let salt = hex::decode(&encryption.salt)?;
+        let nonce = hex::decode(&encryption.nonce)?;
+        if nonce.len() != NONCE_PREFIX_SIZE {
+            return Err(anyhow::anyhow!("Invalid backup nonce"));
+        }
+        let key = derive_key(passphrase, &salt)?;
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+        let mut reader = Self {
+            inner,
+            decryptor: Some(DecryptorBE32::from_aead(
+                cipher,
+                GenericArray::from_slice(&nonce),
+            )),
+            segment: Vec::new(),
+            position: 0,
+        };
+        reader.next_segment()?;
+        Ok(reader)
+    }
+
+    /// Read and decrypt the next segment, if the last one has not been read yet
+    fn next_segment(&mut self) -> io::Result<()> {
+        let Some(mut decryptor) = self.decryptor.take() else {
+            return Ok(());
+        };
+        let mut sealed = Vec::with_capacity(SEALED_SEGMENT_SIZE);
+        (&mut self.inner)
+            .take(SEALED_SEGMENT_SIZE as u64)
+            .read_to_end(&mut sealed)?;
+        // Only the last segment is short
+        self.segment = if sealed.len() < SEALED_SEGMENT_SIZE {
+            decryptor.decrypt_last(sealed.as_slice())
+        } else {
+            let segment = decryptor.decrypt_next(sealed.as_slice());
+            self.decryptor = Some(decryptor);
+            segment
+        }
+        .map_err(stream_error)?;
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.segment.len() {
+            if self.decryptor.is_none() {
+                return Ok(0);
+            }
+            self.next_segment()?;
+        }
+        let read = buf.len().min(self.segment.len() - self.position);
+        buf[..read].copy_from_slice(&self.segment[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+/// Total size of a file, or of all files under a directory (symlinks are not followed)
+fn path_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if metadata.is_file() {
+        return metadata.len();
+    }
+    if !metadata.is_dir() {
+        return 0;
+    }
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| path_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
 /// Backup storage statistics
 #[derive(Debug, Clone)]
 pub struct BackupStorageStats {
@@ -729,4 +1031,113 @@ mod tests {
         assert_eq!(stats.total_backups, 0);
         assert_eq!(stats.total_size_bytes, 0);
     }
+
+    fn encrypted_config(backup_dir: &Path, passphrase: &str) -> UpgradeConfig {
+        let mut config = UpgradeConfig {
+            backup_dir: backup_dir.to_path_buf(),
+            max_backups: 3,
+            ..Default::default()
+        };
+        config.backup_encryption.enabled = true;
+        config.backup_encryption.passphrase = Some(passphrase.to_string());
+        config
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_backup_round_trips() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("notes.txt");
+        fs::write(&source, "model settings worth keeping").unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+
+        let manager = BackupManager::new(&encrypted_config(&backup_dir, "correct horse")).unwrap();
+        let backup_path = manager
+            .create_selective_backup(
+                BackupType::Custom {
+                    paths: vec![source.clone()],
+                },
+                "encrypted".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let metadata = manager
+            .get_backup_metadata_by_path(&backup_path)
+            .await
+            .unwrap();
+        assert!(metadata.encryption.is_some());
+        // The file on disk is not a readable gzip archive
+        let stored = fs::read(&backup_path).unwrap();
+        assert_ne!(&stored[..2], &[0x1f, 0x8b]);
+        manager.verify_backup_integrity(&metadata).await.unwrap();
+
+        let restored = temp_dir.path().join("restored");
+        manager.unpack_backup(&metadata, &restored).unwrap();
+        assert_eq!(
+            fs::read_to_string(restored.join("notes.txt")).unwrap(),
+            "model settings worth keeping"
+        );
+    }
+
+    fn encrypt(data: &[u8]) -> (Vec<u8>, BackupEncryption) {
+        let (mut writer, encryption) = EncryptingWriter::new(Vec::new(), "correct horse").unwrap();
+        writer.write_all(data).unwrap();
+        (writer.finish().unwrap(), encryption)
+    }
+
+    fn decrypt(sealed: &[u8], encryption: &BackupEncryption) -> io::Result<Vec<u8>> {
+        let mut reader = DecryptingReader::new(sealed, "correct horse", encryption)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn test_segmented_encryption_round_trips_and_detects_truncation() {
+        for len in [0, 10, SEGMENT_SIZE, 2 * SEGMENT_SIZE + 5] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let (sealed, encryption) = encrypt(&data);
+            assert_eq!(
+                decrypt(&sealed, &encryption).unwrap(),
+                data,
+                "{} bytes",
+                len
+            );
+        }
+
+        // Dropping the last segment, or a byte of it, is caught
+        let data = vec![7u8; 2 * SEGMENT_SIZE + 5];
+        let (sealed, encryption) = encrypt(&data);
+        for cut in [2 * SEALED_SEGMENT_SIZE, sealed.len() - 1] {
+            assert!(
+                decrypt(&sealed[..cut], &encryption).is_err(),
+                "cut at {}",
+                cut
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restore_with_wrong_passphrase_fails() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("notes.txt");
+        fs::write(&source, "secret").unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+
+        let manager = BackupManager::new(&encrypted_config(&backup_dir, "correct horse")).unwrap();
+        let backup_path = manager
+            .create_selective_backup(
+                BackupType::Custom {
+                    paths: vec![source],
+                },
+                "encrypted".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let wrong = BackupManager::new(&encrypted_config(&backup_dir, "battery staple")).unwrap();
+        let err = wrong.restore_backup(&backup_path).await.unwrap_err();
+        assert!(err.to_string().contains("wrong passphrase"), "{}", err);
+    }
 }
@@ -63,6 +63,10 @@ pub struct UpgradeConfig {
     /// Directory for storing backups
     pub backup_dir: PathBuf,
 
+    /// Encryption of backup archives at rest
+    #[serde(default)]
+    pub backup_encryption: BackupEncryptionConfig,
+
     /// Require cryptographic signature verification
     pub require_signatures: bool,
 
@@ -75,9 +79,18 @@ pub struct UpgradeConfig {
     /// Download timeout (in seconds)
     pub download_timeout: u64,
 
-    /// Retry attempts for failed downloads
+    /// Retries after a failed download or checksum fetch, on top of the first try;
+    /// 0 disables retrying. Set with `INFERNO_DOWNLOAD_RETRIES`.
     pub download_retries: u32,
 
+    /// Base delay for exponential backoff between download attempts
+    #[serde(default = "default_download_retry_base_delay")]
+    pub download_retry_base_delay: Duration,
+
+    /// Upper bound on the backoff delay between download attempts
+    #[serde(default = "default_download_retry_max_delay")]
+    pub download_retry_max_delay: Duration,
+
     /// Enable parallel chunk downloading
     pub parallel_download: bool,
 
@@ -94,6 +107,14 @@ pub struct UpgradeConfig {
     pub enterprise: EnterpriseConfig,
 }
 
+fn default_download_retry_base_delay() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_download_retry_max_delay() -> Duration {
+    Duration::from_secs(30)
+}
+
 impl Default for UpgradeConfig {
     fn default() -> Self {
         let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
@@ -109,6 +130,7 @@ impl Default for UpgradeConfig {
             max_backups: 5,
             download_dir: home_dir.join(".inferno").join("downloads"),
             backup_dir: home_dir.join(".inferno").join("backups"),
+            backup_encryption: BackupEncryptionConfig::default(),
             require_signatures: true,
             trusted_keys: vec![
                 // Default Inferno public key (placeholder)
@@ -117,6 +139,8 @@ impl Default for UpgradeConfig {
             max_download_size: 1024 * 1024 * 1024, // 1GB
             download_timeout: 300, // 5 minutes
             download_retries: 3,
+            download_retry_base_delay: default_download_retry_base_delay(),
+            download_retry_max_delay: default_download_retry_max_delay(),
             parallel_download: true,
             download_chunks: 4,
             safety_checks: SafetyChecksConfig::default(),
@@ -126,6 +150,52 @@ impl Default for UpgradeConfig {
     }
 }
 
+/// Backup encryption configuration
+///
+/// Archives are sealed with AES-256-GCM under a key derived with Argon2 from a
+/// passphrase. Restoring an encrypted backup needs the same passphrase, even
+/// after `enabled` is turned off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupEncryptionConfig {
+    /// Encrypt new backups
+    pub enabled: bool,
+
+    /// Passphrase to derive the key from; read from `passphrase_env` when unset
+    pub passphrase: Option<String>,
+
+    /// Environment variable holding the passphrase
+    pub passphrase_env: String,
+}
+
+impl Default for BackupEncryptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            passphrase: None,
+            passphrase_env: "INFERNO_BACKUP_PASSPHRASE".to_string(),
+        }
+    }
+}
+
+impl BackupEncryptionConfig {
+    /// The configured passphrase, or the one in `passphrase_env`
+    pub fn passphrase(&self) -> Result<String> {
+        if let Some(passphrase) = &self.passphrase {
+            return Ok(passphrase.clone());
+        }
+        std::env::var(&self.passphrase_env)
+            .ok()
+            .filter(|passphrase| !passphrase.is_empty())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Backup encryption needs a passphrase: set backup_encryption.passphrase or {}",
+                    self.passphrase_env
+                )
+            })
+    }
+}
+
 /// Safety checks configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SafetyChecksConfig {
@@ -382,6 +452,12 @@ impl UpgradeConfig {
             }
         }
 
+        if let Ok(retries) = env::var("INFERNO_DOWNLOAD_RETRIES") {
+            if let Ok(retries) = retries.parse::<u32>() {
+                self.download_retries = retries;
+            }
+        }
+
         Ok(())
     }
 
@@ -423,6 +499,12 @@ impl UpgradeConfig {
             }
         }
 
+        if self.download_retry_base_delay > self.download_retry_max_delay {
+            return Err(UpgradeError::ConfigurationError(
+                "Download retry base delay cannot exceed the maximum delay".to_string(),
+            ));
+        }
+
         // Validate safety check configuration
         if self.safety_checks.min_free_space_mb == 0 {
             return Err(UpgradeError::ConfigurationError(
@@ -496,6 +578,19 @@ mod tests {
         assert!(config.create_backups);
     }
 
+    #[test]
+    fn test_config_without_retry_delays_loads_defaults() {
+        // Written before the backoff delays existed
+        let mut saved = serde_json::to_value(UpgradeConfig::default()).unwrap();
+        let fields = saved.as_object_mut().unwrap();
+        fields.remove("download_retry_base_delay");
+        fields.remove("download_retry_max_delay");
+
+        let config: UpgradeConfig = serde_json::from_value(saved).unwrap();
+        assert_eq!(config.download_retry_base_delay, Duration::from_millis(500));
+        assert_eq!(config.download_retry_max_delay, Duration::from_secs(30));
+    }
+
     #[test]
     fn test_config_validation() {
         let config = UpgradeConfig::default();
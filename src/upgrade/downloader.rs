@@ -4,9 +4,11 @@
 //! Secure download system for application updates with cryptographic verification,
 //! progress tracking, and resume capabilities.
 
-use super::{UpgradeConfig, UpgradeError, UpgradeResult};
+use super::{UpgradeConfig, UpgradeError, UpgradeEvent, UpgradeEventType, UpgradeResult};
 use anyhow::Result;
-use reqwest::Client;
+use chrono::Utc;
+use rand::Rng;
+use reqwest::{Client, StatusCode};
 use sha2::{Digest, Sha256};
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
@@ -14,8 +16,10 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 use tokio::time::sleep;
 use tracing::{debug, info, warn};
+use uuid::Uuid;
 
 /// Progress callback function type
 pub type ProgressCallback = dyn Fn(u64, u64, u64) + Send + Sync;
@@ -26,6 +30,54 @@ pub struct UpdateDownloader {
     http_client: Client,
     download_dir: PathBuf,
     resume_enabled: bool,
+    event_sender: Option<broadcast::Sender<UpgradeEvent>>,
+}
+
+/// A failed download attempt, tagged with whether trying again could help
+#[derive(Debug)]
+struct AttemptError {
+    error: UpgradeError,
+    retryable: bool,
+}
+
+impl AttemptError {
+    fn fatal(error: UpgradeError) -> Self {
+        Self {
+            error,
+            retryable: false,
+        }
+    }
+
+    /// Timeouts, connection failures and interrupted bodies are transient
+    fn from_reqwest(error: reqwest::Error) -> Self {
+        let retryable = error.is_timeout()
+            || error.is_connect()
+            || error.is_request()
+            || error.is_body()
+            || error.status().is_some_and(is_retryable_status);
+        Self {
+            error: UpgradeError::NetworkError(error.to_string()),
+            retryable,
+        }
+    }
+
+    fn from_status(status: StatusCode) -> Self {
+        Self {
+            error: UpgradeError::NetworkError(format!(
+                "HTTP {}: {}",
+                status,
+                status.canonical_reason().unwrap_or("Unknown")
+            )),
+            retryable: is_retryable_status(status),
+        }
+    }
+}
+
+/// 5xx, request timeout and rate limiting are worth retrying; other 4xx are not
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
 }
 
 /// Download session state for resume capability
@@ -60,9 +112,16 @@ impl UpdateDownloader {
             http_client,
             download_dir,
             resume_enabled: true,
+            event_sender: None,
         })
     }
 
+    /// Publish retry notifications on an upgrade event channel
+    pub fn with_event_sender(mut self, sender: broadcast::Sender<UpgradeEvent>) -> Self {
+        self.event_sender = Some(sender);
+        self
+    }
+
     /// Download an update package with verification
     pub async fn download_update<F>(
         &self,
@@ -101,14 +160,24 @@ impl UpdateDownloader {
             .download_with_retry(&mut session, progress_callback)
             .await?;
 
+        // Checksums may be published as a separate file next to the package
+        let expected_checksum = if expected_checksum.starts_with("http://")
+            || expected_checksum.starts_with("https://")
+        {
+            self.fetch_checksum(expected_checksum).await?
+        } else {
+            expected_checksum.to_string()
+        };
+
         // Verify the downloaded file
-        self.verify_download(&final_path, expected_checksum).await?;
+        self.verify_download(&final_path, &expected_checksum)
+            .await?;
 
         info!("Download completed and verified: {:?}", final_path);
         Ok(final_path)
     }
 
-    /// Download with automatic retry on failure
+    /// Download with automatic retry on transient failures
     async fn download_with_retry<F>(
         &self,
         session: &mut DownloadSession,
@@ -117,28 +186,24 @@ impl UpdateDownloader {
     where
         F: Fn(u64, u64, u64) + Send + Sync,
     {
-        let max_retries = 3;
-        let mut retry_count = 0;
+        let max_attempts = self.config.download_retries.saturating_add(1);
+        let mut attempt = 1;
 
         loop {
             match self.perform_download(session, &progress_callback).await {
                 Ok(path) => return Ok(path),
+                Err(e) if !e.retryable || attempt >= max_attempts => return Err(e.error),
                 Err(e) => {
-                    retry_count += 1;
-                    if retry_count >= max_retries {
-                        return Err(e);
-                    }
-
+                    let delay = self.backoff_delay(attempt);
                     warn!(
-                        "Download failed (attempt {}/{}): {}",
-                        retry_count, max_retries, e
+                        "Download failed (attempt {}/{}): {}; retrying in {:?}",
+                        attempt, max_attempts, e.error, delay
                     );
-
-                    // Exponential backoff
-                    let delay = Duration::from_secs(2_u64.pow(retry_count as u32));
+                    attempt += 1;
+                    self.emit_retry_event(&session.url, attempt, max_attempts, delay, &e.error);
                     sleep(delay).await;
 
-                    // Reset session for retry
+                    // Resume from whatever made it to disk
                     session.downloaded_size = if session.file_path.exists() {
                         self.get_file_size(&session.file_path)?
                     } else {
@@ -149,12 +214,103 @@ impl UpdateDownloader {
         }
     }
 
+    /// Fetch a published checksum file, retrying transient failures
+    async fn fetch_checksum(&self, url: &str) -> UpgradeResult<String> {
+        let max_attempts = self.config.download_retries.saturating_add(1);
+        let mut attempt = 1;
+
+        loop {
+            match self.perform_checksum_fetch(url).await {
+                Ok(checksum) => return Ok(checksum),
+                Err(e) if !e.retryable || attempt >= max_attempts => return Err(e.error),
+                Err(e) => {
+                    let delay = self.backoff_delay(attempt);
+                    warn!(
+                        "Checksum fetch failed (attempt {}/{}): {}; retrying in {:?}",
+                        attempt, max_attempts, e.error, delay
+                    );
+                    attempt += 1;
+                    self.emit_retry_event(url, attempt, max_attempts, delay, &e.error);
+                    sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    /// Single checksum fetch; accepts `sha256sum`-style "<hash>  <file>" content
+    async fn perform_checksum_fetch(&self, url: &str) -> Result<String, AttemptError> {
+        let response = self
+            .http_client
+            .get(url)
+            .send()
+            .await
+            .map_err(AttemptError::from_reqwest)?;
+
+        if !response.status().is_success() {
+            return Err(AttemptError::from_status(response.status()));
+        }
+
+        let body = response.text().await.map_err(AttemptError::from_reqwest)?;
+        body.split_whitespace()
+            .next()
+            .map(|s| s.to_string())
+            .ok_or_else(|| {
+                AttemptError::fatal(UpgradeError::VerificationFailed(format!(
+                    "Empty checksum file at {}",
+                    url
+                )))
+            })
+    }
+
+    /// Exponential backoff with full jitter: a random delay in
+    /// `[0, min(max_delay, base_delay * 2^(attempt - 1))]`
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let base = self.config.download_retry_base_delay.as_millis() as u64;
+        let max = self.config.download_retry_max_delay.as_millis() as u64;
+        let exp = base.saturating_mul(1u64 << attempt.saturating_sub(1).min(32));
+        let cap = exp.min(max);
+        Duration::from_millis(rand::rng().random_range(0..=cap))
+    }
+
+    /// Report an upcoming retry as a `DownloadProgress` event
+    fn emit_retry_event(
+        &self,
+        url: &str,
+        attempt: u32,
+        max_attempts: u32,
+        delay: Duration,
+        error: &UpgradeError,
+    ) {
+        let Some(sender) = &self.event_sender else {
+            return;
+        };
+
+        let event = UpgradeEvent {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            event_type: UpgradeEventType::DownloadProgress,
+            version: None,
+            message: format!(
+                "Retrying download (attempt {}/{}) after error: {}",
+                attempt, max_attempts, error
+            ),
+            data: Some(serde_json::json!({
+                "url": url,
+                "retry_attempt": attempt,
+                "max_attempts": max_attempts,
+                "delay_ms": delay.as_millis() as u64,
+                "error": error.to_string(),
+            })),
+        };
+        let _ = sender.send(event);
+    }
+
     /// Perform the actual download
     async fn perform_download<F>(
         &self,
         session: &mut DownloadSession,
         progress_callback: &F,
-    ) -> UpgradeResult<PathBuf>
+    ) -> Result<PathBuf, AttemptError>
     where
         F: Fn(u64, u64, u64) + Send + Sync,
     {
@@ -164,18 +320,18 @@ impl UpdateDownloader {
             request = request.header("Range", format!("bytes={}-", session.downloaded_size));
         }
 
-        let response = request
-            .send()
-            .await
-            .map_err(|e| UpgradeError::NetworkError(e.to_string()))?;
+        let response = request.send().await.map_err(AttemptError::from_reqwest)?;
 
         // Handle response status
-        if !response.status().is_success() && response.status().as_u16() != 206 {
-            return Err(UpgradeError::NetworkError(format!(
-                "HTTP {}: {}",
-                response.status(),
-                response.status().canonical_reason().unwrap_or("Unknown")
-            )));
+        if !response.status().is_success() {
+            return Err(AttemptError::from_status(response.status()));
+        }
+
+        // A server that ignores the Range header sends the whole file again
+        if session.downloaded_size > 0 && response.status() != StatusCode::PARTIAL_CONTENT {
+            debug!("Server does not support resume; restarting download");
+            session.downloaded_size = 0;
+            session.total_size = None;
         }
 
         // Get content length
@@ -192,10 +348,10 @@ impl UpdateDownloader {
                 .create(true)
                 .append(true)
                 .open(&session.file_path)
-                .map_err(|e| UpgradeError::InvalidPackage(e.to_string()))?
+                .map_err(|e| AttemptError::fatal(UpgradeError::InvalidPackage(e.to_string())))?
         } else {
             File::create(&session.file_path)
-                .map_err(|e| UpgradeError::InvalidPackage(e.to_string()))?
+                .map_err(|e| AttemptError::fatal(UpgradeError::InvalidPackage(e.to_string())))?
         };
 
         // Download with progress tracking
@@ -207,14 +363,14 @@ impl UpdateDownloader {
         while let Some(chunk) = bytes_stream.next().await {
             // Check for cancellation
             if session.should_cancel.load(Ordering::Relaxed) {
-                return Err(UpgradeError::Cancelled);
+                return Err(AttemptError::fatal(UpgradeError::Cancelled));
             }
 
-            let chunk = chunk.map_err(|e| UpgradeError::NetworkError(e.to_string()))?;
+            let chunk = chunk.map_err(AttemptError::from_reqwest)?;
 
             // Write chunk to file
             file.write_all(&chunk)
-                .map_err(|e| UpgradeError::InvalidPackage(e.to_string()))?;
+                .map_err(|e| AttemptError::fatal(UpgradeError::InvalidPackage(e.to_string())))?;
 
             session.downloaded_size += chunk.len() as u64;
 
@@ -232,7 +388,7 @@ impl UpdateDownloader {
 
         // Ensure file is flushed
         file.sync_all()
-            .map_err(|e| UpgradeError::InvalidPackage(e.to_string()))?;
+            .map_err(|e| AttemptError::fatal(UpgradeError::InvalidPackage(e.to_string())))?;
 
         Ok(session.file_path.clone())
     }
@@ -471,6 +627,151 @@ mod tests {
         assert_eq!(filename, "app-v1.2.3.tar.gz");
     }
 
+    /// Serve `body` at `/pkg.bin`, answering 503 for the first `failures` requests
+    async fn spawn_flaky_server(failures: usize, body: &'static [u8]) -> String {
+        use axum::{Router, extract::State, http::StatusCode, routing::get};
+        use std::sync::atomic::AtomicUsize;
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let app = Router::new()
+            .route(
+                "/pkg.bin",
+                get(move |State(hits): State<Arc<AtomicUsize>>| async move {
+                    if hits.fetch_add(1, Ordering::SeqCst) < failures {
+                        Err(StatusCode::SERVICE_UNAVAILABLE)
+                    } else {
+                        Ok(body)
+                    }
+                }),
+            )
+            .route("/missing.bin", get(|| async { StatusCode::NOT_FOUND }))
+            .with_state(hits);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    fn fast_retry_config(temp_dir: &TempDir) -> UpgradeConfig {
+        UpgradeConfig {
+            download_dir: temp_dir.path().to_path_buf(),
+            download_retries: 3,
+            download_retry_base_delay: Duration::from_millis(1),
+            download_retry_max_delay: Duration::from_millis(5),
+            ..UpgradeConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_retries_transient_failures() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = fast_retry_config(&temp_dir);
+        let (sender, mut events) = broadcast::channel(16);
+        let downloader = UpdateDownloader::new(&config)
+            .unwrap()
+            .with_event_sender(sender);
+
+        let base = spawn_flaky_server(2, b"Hello, world!").await;
+        let path = downloader
+            .download_update(
+                &format!("{}/pkg.bin", base),
+                "315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3",
+                |_, _, _| {},
+            )
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), b"Hello, world!");
+
+        let mut attempts = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            assert!(matches!(
+                event.event_type,
+                UpgradeEventType::DownloadProgress
+            ));
+            attempts.push(event.data.unwrap()["retry_attempt"].as_u64().unwrap());
+        }
+        assert_eq!(attempts, vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_download_gives_up_after_max_attempts() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = fast_retry_config(&temp_dir);
+        let downloader = UpdateDownloader::new(&config).unwrap();
+
+        let base = spawn_flaky_server(5, b"Hello, world!").await;
+        let result = downloader
+            .download_update(&format!("{}/pkg.bin", base), "", |_, _, _| {})
+            .await;
+        assert!(matches!(result, Err(UpgradeError::NetworkError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_zero_retries_tries_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = UpgradeConfig {
+            download_retries: 0,
+            ..fast_retry_config(&temp_dir)
+        };
+        let (sender, mut events) = broadcast::channel(16);
+        let downloader = UpdateDownloader::new(&config)
+            .unwrap()
+            .with_event_sender(sender);
+
+        let base = spawn_flaky_server(1, b"Hello, world!").await;
+        let result = downloader
+            .download_update(&format!("{}/pkg.bin", base), "", |_, _, _| {})
+            .await;
+        assert!(matches!(result, Err(UpgradeError::NetworkError(_))));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_download_does_not_retry_not_found() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = fast_retry_config(&temp_dir);
+        let (sender, mut events) = broadcast::channel(16);
+        let downloader = UpdateDownloader::new(&config)
+            .unwrap()
+            .with_event_sender(sender);
+
+        let base = spawn_flaky_server(0, b"").await;
+        let result = downloader
+            .download_update(&format!("{}/missing.bin", base), "", |_, _, _| {})
+            .await;
+        assert!(result.is_err());
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_backoff_delay_is_capped() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = UpgradeConfig {
+            download_dir: temp_dir.path().to_path_buf(),
+            download_retry_base_delay: Duration::from_millis(100),
+            download_retry_max_delay: Duration::from_millis(250),
+            ..UpgradeConfig::default()
+        };
+        let downloader = UpdateDownloader::new(&config).unwrap();
+
+        for attempt in 1..10 {
+            assert!(downloader.backoff_delay(attempt) <= Duration::from_millis(250));
+        }
+        assert!(downloader.backoff_delay(1) <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_retryable_status_classification() {
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(StatusCode::FORBIDDEN));
+    }
+
     #[test]
     fn test_disk_space_check() {
         // Keep temp_dir alive for the duration of the test
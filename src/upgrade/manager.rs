@@ -8,7 +8,7 @@ use super::{
     ApplicationVersion, InstallationStage, PlatformUpgradeHandler, UpdateChannel, UpdateInfo,
     UpgradeConfig, UpgradeError, UpgradeEvent, UpgradeEventType, UpgradeResult, UpgradeStatus,
 };
-use crate::upgrade::{BackupManager, SafetyChecker, UpdateChecker, UpdateDownloader};
+use crate::upgrade::{BackupManager, BackupPlan, SafetyChecker, UpdateChecker, UpdateDownloader};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -30,14 +30,30 @@ pub struct UpgradeManager {
     status: Arc<RwLock<UpgradeStatus>>,
     event_sender: broadcast::Sender<UpgradeEvent>,
     _event_receiver: broadcast::Receiver<UpgradeEvent>,
+    dry_run: bool,
+}
+
+/// Everything an install would do, computed without changing any files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DryRunReport {
+    pub current_version: ApplicationVersion,
+    pub target_version: ApplicationVersion,
+    /// Backup that would be created first, if backups are enabled
+    pub planned_backup: Option<BackupPlan>,
+    /// Download, unpack and backup space the install would consume
+    pub estimated_disk_usage_bytes: u64,
+    pub requires_elevated_privileges: bool,
+    /// Installation stages that would run, in order
+    pub stages: Vec<InstallationStage>,
 }
 
 impl UpgradeManager {
     /// Create a new upgrade manager
     pub async fn new(config: UpgradeConfig) -> Result<Self> {
         let current_version = ApplicationVersion::current();
+        let (event_sender, event_receiver) = broadcast::channel(1000);
         let update_checker = UpdateChecker::new(&config).await?;
-        let downloader = UpdateDownloader::new(&config)?;
+        let downloader = UpdateDownloader::new(&config)?.with_event_sender(event_sender.clone());
         let backup_manager = BackupManager::new(&config)?;
         let safety_checker = SafetyChecker::new(&config);
 
@@ -45,7 +61,7 @@ impl UpgradeManager {
         let platform_handler = Self::create_platform_handler(&config)?;
 
         let status = Arc::new(RwLock::new(UpgradeStatus::UpToDate));
-        let (event_sender, event_receiver) = broadcast::channel(1000);
+        let dry_run = config.safety_checks.dry_run_install;
 
         Ok(Self {
             config,
@@ -58,9 +74,20 @@ impl UpgradeManager {
             status,
             event_sender,
             _event_receiver: event_receiver,
+            dry_run,
         })
     }
 
+    /// Simulate installs instead of performing them
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Whether installs are simulated
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
     /// Get current upgrade status
     pub async fn get_status(&self) -> UpgradeStatus {
         self.status.read().await.clone()
@@ -147,7 +174,20 @@ impl UpgradeManager {
     }
 
     /// Download and install an available update
+    ///
+    /// In dry-run mode this only plans the install; see [`Self::dry_run_install`].
     pub async fn install_update(&self, update_info: &UpdateInfo) -> UpgradeResult<()> {
+        if self.dry_run {
+            let report = self.dry_run_install(update_info).await?;
+            info!(
+                "Dry run: installing {} would run {} stages and use ~{} MB",
+                report.target_version.to_string(),
+                report.stages.len(),
+                report.estimated_disk_usage_bytes / 1024 / 1024
+            );
+            return Ok(());
+        }
+
         info!(
             "Starting installation of version {}",
             update_info.version.to_string()
@@ -234,6 +274,82 @@ impl UpgradeManager {
         }
     }
 
+    /// Plan an install without downloading, backing up or replacing anything
+    ///
+    /// Runs the compatibility and disk space checks, computes the backup that would be
+    /// created, and emits an `InstallationProgress` event for each stage the real
+    /// install would go through.
+    pub async fn dry_run_install(&self, update_info: &UpdateInfo) -> UpgradeResult<DryRunReport> {
+        info!(
+            "Dry run: planning installation of version {}",
+            update_info.version.to_string()
+        );
+
+        self.emit_event(
+            UpgradeEventType::InstallationStarted,
+            "Dry run: simulating installation, no changes will be made",
+        )
+        .await;
+
+        {
+            let mut checker = self.safety_checker.write().await;
+            checker.check_dry_run(update_info).await?;
+        }
+
+        let planned_backup = if self.config.create_backups {
+            Some(
+                self.backup_manager
+                    .plan_backup()
+                    .map_err(|e| UpgradeError::BackupFailed(e.to_string()))?,
+            )
+        } else {
+            None
+        };
+
+        let estimated_disk_usage_bytes = self
+            .safety_checker
+            .read()
+            .await
+            .estimate_installation_space(update_info)
+            + planned_backup
+                .as_ref()
+                .map_or(0, |plan| plan.estimated_size_bytes);
+
+        let mut plan = Vec::new();
+        if planned_backup.is_some() {
+            plan.push((InstallationStage::PreparingBackup, 0.0));
+            plan.push((InstallationStage::CreatingBackup, 5.0));
+        }
+        plan.extend([
+            (InstallationStage::VerifyingUpdate, 10.0),
+            (InstallationStage::StoppingServices, 20.0),
+            (InstallationStage::InstallingFiles, 40.0),
+            (InstallationStage::UpdatingConfiguration, 70.0),
+            (InstallationStage::StartingServices, 80.0),
+            (InstallationStage::VerifyingInstallation, 90.0),
+            (InstallationStage::CleaningUp, 100.0),
+        ]);
+
+        let mut stages = Vec::with_capacity(plan.len());
+        for (stage, progress) in plan {
+            self.emit_event(
+                UpgradeEventType::InstallationProgress,
+                &format!("Dry run: {} ({}%)", stage.description(), progress),
+            )
+            .await;
+            stages.push(stage);
+        }
+
+        Ok(DryRunReport {
+            current_version: self.current_version.clone(),
+            target_version: update_info.version.clone(),
+            planned_backup,
+            estimated_disk_usage_bytes,
+            requires_elevated_privileges: self.platform_handler.requires_elevated_privileges(),
+            stages,
+        })
+    }
+
     /// Download an update package
     async fn download_update(&self, update_info: &UpdateInfo) -> UpgradeResult<PathBuf> {
         info!("Downloading update package");
@@ -1120,6 +1236,97 @@ mod tests {
         assert!(matches!(initial_status, UpgradeStatus::UpToDate));
     }
 
+    fn test_update_info() -> UpdateInfo {
+        let platform = std::env::consts::OS.to_string();
+        UpdateInfo {
+            version: ApplicationVersion::new(99, 0, 0),
+            release_date: Utc::now(),
+            changelog: String::new(),
+            download_urls: [(
+                platform.clone(),
+                "https://example.com/inferno.tar.gz".to_string(),
+            )]
+            .into(),
+            checksums: [(platform.clone(), String::new())].into(),
+            signatures: Default::default(),
+            size_bytes: [(platform, 1024)].into(),
+            is_critical: false,
+            is_security_update: false,
+            minimum_version: None,
+            deprecation_warnings: vec![],
+        }
+    }
+
+    /// Every file under `dir` with its size
+    fn snapshot(dir: &std::path::Path) -> Vec<(PathBuf, u64)> {
+        let mut files = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else {
+                    let len = std::fs::metadata(&path).unwrap().len();
+                    files.push((path, len));
+                }
+            }
+        }
+        files.sort();
+        files
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_install_modifies_nothing() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = UpgradeConfig {
+            download_dir: temp_dir.path().join("downloads"),
+            backup_dir: temp_dir.path().join("backups"),
+            ..Default::default()
+        };
+        config.safety_checks.min_free_space_mb = 1;
+        let mut manager = UpgradeManager::new(config).await.unwrap();
+        manager.set_dry_run(true);
+        let mut events = manager.subscribe_to_events();
+        let update_info = test_update_info();
+
+        let before = snapshot(temp_dir.path());
+        let report = manager.dry_run_install(&update_info).await.unwrap();
+        manager.install_update(&update_info).await.unwrap();
+        assert_eq!(snapshot(temp_dir.path()), before);
+
+        assert_eq!(report.target_version, ApplicationVersion::new(99, 0, 0));
+        assert_eq!(
+            report.stages,
+            vec![
+                InstallationStage::PreparingBackup,
+                InstallationStage::CreatingBackup,
+                InstallationStage::VerifyingUpdate,
+                InstallationStage::StoppingServices,
+                InstallationStage::InstallingFiles,
+                InstallationStage::UpdatingConfiguration,
+                InstallationStage::StartingServices,
+                InstallationStage::VerifyingInstallation,
+                InstallationStage::CleaningUp,
+            ]
+        );
+        let backup = report.planned_backup.unwrap();
+        assert!(!backup.backup_path.exists());
+        assert!(report.estimated_disk_usage_bytes >= 3 * 1024);
+
+        let mut stage_events = 0;
+        while let Ok(event) = events.try_recv() {
+            if matches!(event.event_type, UpgradeEventType::InstallationProgress) {
+                stage_events += 1;
+            }
+        }
+        assert_eq!(stage_events, 2 * report.stages.len());
+        assert!(matches!(
+            manager.get_status().await,
+            UpgradeStatus::UpToDate
+        ));
+    }
+
     #[tokio::test]
     async fn test_event_subscription() {
         let config = UpgradeConfig::default();
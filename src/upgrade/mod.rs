@@ -40,12 +40,12 @@ use tracing::info;
 use uuid::Uuid;
 
 pub use background_service::{BackgroundUpdateService, ServiceStatistics, ServiceStatus};
-pub use backup::{BackupManager, BackupMetadata, BackupStorageStats, BackupType};
+pub use backup::{BackupManager, BackupMetadata, BackupPlan, BackupStorageStats, BackupType};
 pub use checker::UpdateChecker;
 pub use config::UpdateSource;
 pub use config::UpgradeConfig;
 pub use downloader::{ProgressCallback, UpdateDownloader};
-pub use manager::UpgradeManager;
+pub use manager::{DryRunReport, UpgradeManager};
 pub use safety::{CompatibilityReport, ResourceReport, SafetyChecker};
 
 /// Current application version information
@@ -166,7 +166,7 @@ pub enum UpgradeStatus {
 }
 
 /// Installation stages for progress tracking
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum InstallationStage {
     PreparingBackup,
     CreatingBackup,
@@ -144,6 +144,33 @@ impl SafetyChecker {
         Ok(())
     }
 
+    /// Run the compatibility and disk space checks used by dry-run installs
+    ///
+    /// Network, process and dependency checks are skipped: a dry run never
+    /// downloads or replaces anything they could interfere with.
+    pub async fn check_dry_run(&mut self, update_info: &UpdateInfo) -> UpgradeResult<()> {
+        self.system.refresh_disks();
+
+        if self.config.safety_checks.check_compatibility {
+            self.check_system_compatibility(update_info).await?;
+        }
+
+        if self.config.safety_checks.check_disk_space {
+            self.check_disk_space(update_info).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Bytes needed to download and unpack the update for this platform
+    pub fn estimate_installation_space(&self, update_info: &UpdateInfo) -> u64 {
+        let platform = std::env::consts::OS;
+        let package_size = update_info.size_bytes.get(platform).copied().unwrap_or(0);
+
+        // Account for decompression (estimate 3x package size)
+        package_size * 3
+    }
+
     /// Check system compatibility
     async fn check_system_compatibility(&self, update_info: &UpdateInfo) -> UpgradeResult<()> {
         debug!("Checking system compatibility");
@@ -180,12 +207,8 @@ impl SafetyChecker {
     async fn check_disk_space(&self, update_info: &UpdateInfo) -> UpgradeResult<()> {
         debug!("Checking disk space");
 
-        let platform = std::env::consts::OS;
-        let package_size = update_info.size_bytes.get(platform).copied().unwrap_or(0);
-
-        // Account for decompression (estimate 3x package size)
-        let required_space =
-            package_size * 3 + (self.config.safety_checks.min_free_space_mb * 1024 * 1024);
+        let required_space = self.estimate_installation_space(update_info)
+            + (self.config.safety_checks.min_free_space_mb * 1024 * 1024);
 
         // Get available disk space
         let available_space = self.get_available_disk_space(&self.config.download_dir)?;
@@ -20,6 +20,159 @@ use inferno::api::{
 };
 use std::time::Duration;
 
+#[path = "common/mocks.rs"]
+#[allow(clippy::new_without_default)]
+mod mocks;
+
+// ============================================================================
+// MODELS ENDPOINT TESTS
+// ============================================================================
+
+mod models_endpoint {
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        http::{Request, StatusCode},
+        routing::get,
+    };
+    use inferno::{
+        api::openai, cli::serve::ServerState, config::Config, metrics::MetricsCollector,
+    };
+    use std::{path::Path, sync::Arc};
+    use tower::ServiceExt;
+
+    fn models_router(models_dir: &Path) -> Router {
+        let config = Config {
+            models_dir: models_dir.to_path_buf(),
+            ..Config::default()
+        };
+        let state = Arc::new(ServerState::new(config, MetricsCollector::new().0));
+        Router::new()
+            .route("/v1/models", get(openai::list_models))
+            .route("/v1/models/:id", get(openai::retrieve_model))
+            .with_state(state)
+    }
+
+    async fn get_json(app: &Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_models_endpoint_lists_model_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("llama-7b.gguf"), b"GGUF\x03\x00\x00\x00").unwrap();
+        std::fs::write(dir.path().join("encoder.onnx"), b"\x08\x07\x12\x04test").unwrap();
+        let app = models_router(dir.path());
+
+        let (status, body) = get_json(&app, "/v1/models").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["object"], "list");
+        let mut ids: Vec<&str> = body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|model| model["id"].as_str().unwrap())
+            .collect();
+        ids.sort_unstable();
+        assert_eq!(ids, ["encoder.onnx", "llama-7b.gguf"]);
+
+        let (status, model) = get_json(&app, "/v1/models/llama-7b.gguf").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(model["id"], "llama-7b.gguf");
+        assert_eq!(model["object"], "model");
+        assert_eq!(model["owned_by"], "inferno");
+        assert_eq!(model["backend_type"], "gguf");
+        assert!(model["created"].as_i64().unwrap() > 0);
+
+        let (status, error) = get_json(&app, "/v1/models/missing.gguf").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error["error"]["code"], "model_not_found");
+    }
+}
+
+// ============================================================================
+// METRICS ENDPOINT TESTS
+// ============================================================================
+
+mod metrics_endpoint {
+    use crate::mocks::MockBackend;
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        http::{Request, StatusCode, header},
+    };
+    use clap::ValueEnum;
+    use inferno::{
+        backends::{Backend, BackendConfig, BackendHandle, BackendType},
+        cli::serve::{self, ServerState},
+        config::Config,
+        metrics::MetricsCollector,
+    };
+    use std::{sync::Arc, time::Duration};
+    use tower::ServiceExt;
+
+    fn serve_router(config: Config) -> Router {
+        let (metrics, processor) = MetricsCollector::new();
+        processor.start();
+        let mut backend =
+            MockBackend::new(BackendType::value_variants()[0], BackendConfig::default())
+                .with_delay(0);
+        backend.is_loaded = true;
+        let backend = BackendHandle::new(Backend::from_impl(Box::new(backend)));
+        let state = Arc::new(ServerState::new(config, metrics).with_backend("echo", backend));
+        serve::router(state, None, None)
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint_counts_served_requests() {
+        let app = serve_router(Config::default());
+
+        let request = Request::post("/v1/completions")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::json!({"model": "echo", "prompt": "hello"}).to_string(),
+            ))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // Events are applied by the background processor
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let request = Request::get("/metrics").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/plain; version=0.0.4; charset=utf-8"
+        );
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+        for line in [
+            "inferno_inference_requests_total 1",
+            "inferno_inference_requests_successful_total 1",
+            "inferno_prompt_tokens_count 1",
+        ] {
+            assert!(
+                body.lines().any(|l| l == line),
+                "missing `{}` in:\n{}",
+                line,
+                body
+            );
+        }
+
+        // Disabled metrics are not served
+        let mut config = Config::default();
+        config.metrics.enabled = false;
+        let request = Request::get("/metrics").body(Body::empty()).unwrap();
+        let response = serve_router(config).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
+
 // ============================================================================
 // CHAT COMPLETIONS ENDPOINT TESTS
 // ============================================================================
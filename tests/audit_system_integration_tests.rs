@@ -105,6 +105,7 @@ fn make_event(
             records_affected: Some(1),
         },
         metadata: HashMap::new(),
+        chain_hash: None,
     }
 }
 
@@ -1,7 +1,10 @@
 use anyhow::Result;
 use futures::StreamExt;
 use inferno::{
-    backends::{Backend, BackendConfig, BackendHandle, BackendType, InferenceParams},
+    InfernoError,
+    backends::{
+        Backend, BackendConfig, BackendHandle, BackendType, InferenceParams, ResponseFormat,
+    },
     cache::{CacheConfig, ModelCache},
     models::{ModelInfo, ModelManager},
 };
@@ -147,6 +150,14 @@ mod test_utils {
             context_size: 512,
             batch_size: 8,
             memory_map: true,
+            prefetch_model: false,
+            lock_memory: false,
+            blocking_threads: None,
+            memory_limit_mb: None,
+            rope: Default::default(),
+            context_policy: Default::default(),
+            preflight: true,
+            require_gpu: false,
         }
     }
 }
@@ -366,6 +377,110 @@ async fn test_streaming_rejects_oversized_prompt() -> Result<()> {
     Ok(())
 }
 
+/// With top_k=1 only the most likely token survives, so a seeded run at any
+/// temperature reproduces itself and matches plain greedy decoding.
+#[tokio::test]
+async fn test_gguf_seeded_top_k_one_is_greedy() -> Result<()> {
+    let Some(model_path) = test_utils::require_gguf_model() else {
+        return Ok(());
+    };
+    let config = test_utils::create_test_config();
+
+    let mut backend = Backend::new(BackendType::Gguf, &config)?;
+    let model_info = test_utils::model_info_for(&model_path).await?;
+    backend.load_model(&model_info).await?;
+
+    let prompt = "The capital of France is";
+    let top_k_one = InferenceParams {
+        max_tokens: 24,
+        temperature: 0.9,
+        top_k: 1,
+        seed: Some(1234),
+        ..Default::default()
+    };
+    let greedy = InferenceParams {
+        max_tokens: 24,
+        temperature: 0.0,
+        ..Default::default()
+    };
+
+    let first = backend.infer(prompt, &top_k_one).await?;
+    let second = backend.infer(prompt, &top_k_one).await?;
+    assert_eq!(
+        first, second,
+        "seeded top_k=1 output should be reproducible"
+    );
+    assert_eq!(first, backend.infer(prompt, &greedy).await?);
+
+    backend.unload_model().await?;
+    Ok(())
+}
+
+/// A JSON schema response format must yield parseable JSON with every
+/// required key, whatever the model would have said unconstrained.
+#[tokio::test]
+async fn test_gguf_json_schema_response_format() -> Result<()> {
+    let Some(model_path) = test_utils::require_gguf_model() else {
+        return Ok(());
+    };
+    let config = test_utils::create_test_config();
+
+    let mut backend = Backend::new(BackendType::Gguf, &config)?;
+    let model_info = test_utils::model_info_for(&model_path).await?;
+    backend.load_model(&model_info).await?;
+
+    let schema = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "age": {"type": "integer"},
+            "name": {"type": "string"}
+        },
+        "required": ["age", "name"]
+    });
+    let params = InferenceParams {
+        max_tokens: 128,
+        temperature: 0.0,
+        response_format: Some(ResponseFormat::JsonSchema(schema)),
+        ..Default::default()
+    };
+
+    let output = backend
+        .infer("Describe a person named Ada who is 36 years old.", &params)
+        .await?;
+    let value: serde_json::Value = serde_json::from_str(output.trim())
+        .unwrap_or_else(|e| panic!("output should be JSON ({}): {}", e, output));
+    assert!(
+        value["age"].is_i64(),
+        "age should be an integer: {}",
+        output
+    );
+    assert!(
+        value["name"].is_string(),
+        "name should be a string: {}",
+        output
+    );
+
+    // Schemas the grammar converter cannot honour are rejected up front
+    let invalid = InferenceParams {
+        response_format: Some(ResponseFormat::JsonSchema(
+            serde_json::json!({"type": "tuple"}),
+        )),
+        ..Default::default()
+    };
+    let error = backend.infer("ignored", &invalid).await.unwrap_err();
+    assert!(
+        matches!(
+            error.downcast_ref::<InfernoError>(),
+            Some(InfernoError::Validation(_))
+        ),
+        "invalid schema should be a validation error, got: {}",
+        error
+    );
+
+    backend.unload_model().await?;
+    Ok(())
+}
+
 /// Test BackendHandle thread safety
 #[tokio::test]
 async fn test_backend_handle_thread_safety() -> Result<()> {
@@ -385,6 +385,14 @@ async fn test_batch_processor_real_inference() {
         context_size: 512,
         batch_size: 8,
         memory_map: true,
+        prefetch_model: false,
+        lock_memory: false,
+        blocking_threads: None,
+        memory_limit_mb: None,
+        rope: Default::default(),
+        context_policy: Default::default(),
+        preflight: true,
+        require_gpu: false,
     };
     let mut backend =
         Backend::new(BackendType::Gguf, &backend_config).expect("create gguf backend");
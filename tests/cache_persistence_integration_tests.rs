@@ -95,6 +95,14 @@ mod cache_test_utils {
             context_size: 512,
             batch_size: 8,
             memory_map: true,
+            prefetch_model: false,
+            lock_memory: false,
+            blocking_threads: None,
+            memory_limit_mb: None,
+            rope: Default::default(),
+            context_policy: Default::default(),
+            preflight: true,
+            require_gpu: false,
         }
     }
 
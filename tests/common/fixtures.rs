@@ -173,6 +173,14 @@ impl ConfigFixtures {
             context_size: 512,
             batch_size: 8,
             memory_map: true,
+            prefetch_model: false,
+            lock_memory: false,
+            blocking_threads: None,
+            memory_limit_mb: None,
+            rope: Default::default(),
+            context_policy: Default::default(),
+            preflight: true,
+            require_gpu: false,
         }
     }
 
@@ -333,9 +341,16 @@ impl DataFixtures {
             temperature: 0.7,
             top_p: 0.9,
             top_k: 40,
+            repeat_penalty: None,
+            repeat_last_n: None,
+            min_p: None,
             stream: false,
             stop_sequences: vec![],
+            stop_regex: None,
+            max_generation_ms: None,
             seed: None,
+            response_format: None,
+            logprobs: None,
         }
     }
 
@@ -451,6 +466,7 @@ impl DataFixtures {
             metadata: HashMap::from([
                 ("test_event".to_string(), serde_json::Value::Bool(true)),
             ]),
+            chain_hash: None,
         }
     }
 }
@@ -1,4 +1,4 @@
-/// Mock implementations for testing
+//! Mock implementations for testing
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -187,6 +187,9 @@ async fn test_audit_logger() {
         alert_on_critical: true,
         alerting: AlertConfiguration::default(),
         export_format: ExportFormat::Json,
+        hash_chain: false,
+        rotation_interval_secs: 24 * 3600,
+        compress_rotated: true,
     };
 
     let logger = AuditLogger::new(config).await.unwrap();
@@ -243,6 +246,7 @@ async fn test_audit_logger() {
             records_affected: None,
         },
         metadata: HashMap::new(),
+        chain_hash: None,
     };
 
     logger.log_event(event.clone()).await.unwrap();
@@ -354,6 +358,7 @@ async fn test_distributed_inference() {
         pool_strategy: PoolStrategy::RoundRobin,
         preload_models: false,
         max_models_per_worker: 2,
+        ..Default::default()
     };
 
     let backend_config = BackendConfig::default();
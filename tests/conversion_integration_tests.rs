@@ -4,7 +4,8 @@
 //! deliberately honest about what the module actually does today:
 //!
 //!   * Real end-to-end: GGUF read/analyze, SafeTensors -> GGUF, GGUF quantization,
-//!     GGUF passthrough, progress tracking.
+//!     GGUF -> GGUF requantization, GGUF passthrough, progress tracking.
+//!   * Validation: ONNX -> GGUF rejects unsupported operator sets before converting.
 //!   * Stub-but-runs: GGUF -> ONNX writes a placeholder ONNX header (the graph
 //!     builder is not a full implementation); the test asserts only that the
 //!     pipeline runs and produces a file, not that the ONNX is a real model.
@@ -50,7 +51,7 @@ fn build_synthetic_gguf(path: &Path) -> Result<()> {
     const GGUF_TYPE_UINT32: u32 = 4;
     const GGML_F32: u32 = 0;
 
-    let dims: [u64; 2] = [8, 8]; // 64 F32 elements = 256 bytes per tensor (2 Q4_0 blocks)
+    let dims: [u64; 2] = [32, 2]; // 64 F32 elements = 256 bytes per tensor (2 Q4_0 blocks)
     let elems: usize = 64;
     let tensor_names = ["token_embd.weight", "output.weight"];
     let tensor_bytes = elems * 4;
@@ -140,6 +141,38 @@ fn write_onnxish_file(path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Write a minimal ONNX `ModelProto` that imports a single operator set
+fn write_onnx_with_opset(path: &Path, domain: &str, version: u64) -> Result<()> {
+    fn varint(buf: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        buf.push(value as u8);
+    }
+
+    let mut opset = Vec::new();
+    if !domain.is_empty() {
+        opset.push(0x0a); // domain: field 1, length-delimited
+        varint(&mut opset, domain.len() as u64);
+        opset.extend_from_slice(domain.as_bytes());
+    }
+    opset.push(0x10); // version: field 2, varint
+    varint(&mut opset, version);
+
+    let mut model = vec![0x08, 0x08]; // ir_version 8
+    let producer = b"onnx-test-exporter";
+    model.push(0x12); // producer_name: field 2
+    varint(&mut model, producer.len() as u64);
+    model.extend_from_slice(producer);
+    model.push(0x42); // opset_import: field 8
+    varint(&mut model, opset.len() as u64);
+    model.extend_from_slice(&opset);
+
+    fs::write(path, model)?;
+    Ok(())
+}
+
 // ── Test harness helpers ─────────────────────────────────────────────────────
 
 fn make_converter(models_dir: &Path) -> ModelConverter {
@@ -323,6 +356,60 @@ async fn test_gguf_quantization() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_gguf_to_gguf_requantize() -> Result<()> {
+    let dir = TempDir::new()?;
+    let converter = make_converter(dir.path());
+
+    let input = dir.path().join("input.gguf");
+    let output = dir.path().join("requantized.gguf");
+    build_synthetic_gguf(&input)?;
+
+    let mut config = base_config(ModelFormat::Gguf);
+    config.quantization = Some(QuantizationType::Q4_0);
+
+    let result = converter.convert_model(&input, &output, &config).await?;
+    assert!(
+        result.success,
+        "requantize should succeed: {:?}",
+        result.errors
+    );
+    assert!(
+        result.output_size < result.input_size,
+        "Q4_0 output should be smaller than the F32 input ({} >= {})",
+        result.output_size,
+        result.input_size
+    );
+
+    let analysis = converter.analyze_model(&output).await?;
+    assert_eq!(analysis.tensor_count, 2);
+    assert_eq!(
+        analysis.metadata.get("general.name").map(String::as_str),
+        Some("synthetic_test_model")
+    );
+
+    config.quantization = Some(QuantizationType::Q8_0);
+    let result = converter
+        .convert_model(&input, &dir.path().join("q8.gguf"), &config)
+        .await?;
+    assert!(result.success, "{:?}", result.errors);
+    assert!(result.output_size < result.input_size);
+
+    // A type the requantizer lacks fails instead of relabelling the F32 data
+    config.quantization = Some(QuantizationType::Q4_1);
+    let result = converter
+        .convert_model(&input, &dir.path().join("q4_1.gguf"), &config)
+        .await?;
+    assert!(!result.success);
+    assert!(
+        result.errors.iter().any(|e| e.contains("not supported")),
+        "{:?}",
+        result.errors
+    );
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_gguf_passthrough_no_conversion() -> Result<()> {
     let dir = TempDir::new()?;
@@ -509,6 +596,36 @@ async fn test_onnx_input_unsupported() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_onnx_unsupported_opset_rejected() -> Result<()> {
+    let dir = TempDir::new()?;
+    let converter = make_converter(dir.path());
+
+    let cases = [
+        ("old_opset.onnx", "", 3, "opset 3"),
+        ("custom_domain.onnx", "com.microsoft", 1, "com.microsoft"),
+    ];
+    for (name, domain, version, expected) in cases {
+        let input = dir.path().join(name);
+        let output = dir.path().join(name).with_extension("gguf");
+        write_onnx_with_opset(&input, domain, version)?;
+
+        let result = converter
+            .convert_model(&input, &output, &base_config(ModelFormat::Gguf))
+            .await?;
+
+        assert!(!result.success, "{name} must not convert");
+        assert!(!output.exists(), "no output file for {name}");
+        assert!(
+            result.errors.iter().any(|e| e.contains(expected)),
+            "error should name the rejected operator set: {:?}",
+            result.errors
+        );
+    }
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_pytorch_input_unsupported() -> Result<()> {
     // PyTorch support was removed at the dependency level (tch), so .pt input
@@ -52,6 +52,14 @@ fn cpu_backend_config() -> BackendConfig {
         context_size: 512,
         batch_size: 8,
         memory_map: true,
+        prefetch_model: false,
+        lock_memory: false,
+        blocking_threads: None,
+        memory_limit_mb: None,
+        rope: Default::default(),
+        context_policy: Default::default(),
+        preflight: true,
+        require_gpu: false,
     }
 }
 
@@ -235,6 +243,7 @@ fn make_event(actor_id: &str, actor_name: &str, action: &str) -> AuditEvent {
             records_affected: Some(1),
         },
         metadata: HashMap::new(),
+        chain_hash: None,
     }
 }
 
@@ -24,6 +24,14 @@ mod metal_gpu_tests {
             context_size: 512, // Small context for fast test
             batch_size: 128,
             memory_map: true,
+            prefetch_model: false,
+            lock_memory: false,
+            blocking_threads: None,
+            memory_limit_mb: None,
+            rope: Default::default(),
+            context_policy: Default::default(),
+            preflight: true,
+            require_gpu: false,
         };
 
         // Create GGUF backend with Metal
@@ -45,9 +53,16 @@ mod metal_gpu_tests {
             temperature: 0.7,
             top_p: 0.9,
             top_k: 40,
+            repeat_penalty: None,
+            repeat_last_n: None,
+            min_p: None,
             stream: false,
             stop_sequences: vec![],
+            stop_regex: None,
+            max_generation_ms: None,
             seed: Some(42), // Deterministic output
+            response_format: None,
+            logprobs: None,
         };
 
         let result = backend_handle
@@ -172,6 +172,7 @@ fn make_event(logger_id: usize, event_id: usize) -> AuditEvent {
             records_affected: None,
         },
         metadata: HashMap::new(),
+        chain_hash: None,
     }
 }
 
@@ -183,6 +184,14 @@ fn cpu_backend_config() -> BackendConfig {
         context_size: 512,
         batch_size: 8,
         memory_map: true,
+        prefetch_model: false,
+        lock_memory: false,
+        blocking_threads: None,
+        memory_limit_mb: None,
+        rope: Default::default(),
+        context_policy: Default::default(),
+        preflight: true,
+        require_gpu: false,
     }
 }
 
@@ -20,6 +20,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         context_size: 2048,
         batch_size: 32,
         memory_map: true,
+        prefetch_model: false,
+        lock_memory: false,
+        blocking_threads: None,
+        memory_limit_mb: None,
+        rope: Default::default(),
+        context_policy: Default::default(),
+        preflight: true,
+        require_gpu: false,
     };
 
     // Create GGUF backend
@@ -56,9 +64,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         temperature: 0.7,
         top_p: 0.9,
         top_k: 40,
+        repeat_penalty: None,
+        repeat_last_n: None,
+        min_p: None,
         stream: false,
         stop_sequences: vec![],
+        stop_regex: None,
+        max_generation_ms: None,
         seed: None,
+        response_format: None,
+        logprobs: None,
     };
 
     println!("Running inference...");